@@ -0,0 +1,107 @@
+use phase_1_connect::{parse_block_with_options, parse_transaction, DecoderRegistry, ParseOptions, ParsedBlock, Pubkey, RpcBlockResponse};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// ==========================================
+// PARSER BENCHMARKS
+// ==========================================
+// `cargo bench` times each stage of turning a saved getBlock response into
+// a ParsedBlock: JSON deserialization, then parsing with the default
+// options, without decoders, single-threaded and one transaction at a
+// time. Each case reports the fastest and median of its runs. Pass a path
+// to benchmark another block (large mainnet blocks are the interesting
+// case) and BENCH_RUNS to change the run count. It's a plain timing loop
+// rather than criterion, which keeps the crate's dev-dependencies as they
+// are; the min and median are enough to compare two builds.
+//
+//     cargo bench --bench parse -- blocks/250000000.json
+
+const DEFAULT_BLOCK: &str = "src/json/block.json";
+const DEFAULT_RUNS: usize = 20;
+
+fn main() {
+    // cargo bench passes --bench; anything else is the block to load.
+    let path = env::args().skip(1).find(|arg| !arg.starts_with("--")).unwrap_or_else(|| DEFAULT_BLOCK.to_string());
+    let runs = env::var("BENCH_RUNS").ok().and_then(|runs| runs.parse().ok()).unwrap_or(DEFAULT_RUNS);
+    let json = fs::read_to_string(&path).unwrap_or_else(|e| panic!("failed to read {}: {}", path, e));
+    let load = || serde_json::from_str::<RpcBlockResponse>(&json).unwrap().result;
+    let transactions = load().transactions.len();
+    println!("{}: {} bytes, {} transactions, {} runs", path, json.len(), transactions, runs);
+
+    bench("deserialize", runs, || (), |()| load());
+
+    let cases = [
+        ("parse_block", ParseOptions::default()),
+        ("parse_block (1 thread)", ParseOptions { threads: 1, ..ParseOptions::default() }),
+        ("parse_block (no decoders)", ParseOptions { threads: 1, decoders: Arc::new(DecoderRegistry::new()), ..ParseOptions::default() }),
+        ("parse_block (unresolved accounts)", ParseOptions { threads: 1, resolve_accounts: false, ..ParseOptions::default() }),
+    ];
+    for (name, options) in &cases {
+        bench(name, runs, load, |block| parse_block_with_options(block, options).unwrap());
+    }
+
+    let block = load();
+    bench("parse_transaction (each)", runs, || (), |()| {
+        for tx in &block.transactions {
+            black_box(parse_transaction(&tx.transaction, &tx.meta).ok());
+        }
+    });
+
+    report_pubkey_memory(&parse_block_with_options(load(), &ParseOptions::default()).unwrap().0);
+}
+
+// Addresses are the bulk of a parsed block, and the same few programs and
+// accounts repeat throughout it. Each one is an inline 32-byte Pubkey,
+// with no allocation of its own; for comparison, the same block with base58
+// Strings (the representation before Pubkey), or with Strings interned into
+// one shared Arc<str> per unique key.
+fn report_pubkey_memory(block: &ParsedBlock) {
+    let mut keys: Vec<Pubkey> = Vec::new();
+    for tx in &block.transactions {
+        keys.push(tx.fee_payer);
+        keys.extend(&tx.account_keys);
+        keys.extend(tx.accounts.iter().map(|account| account.pubkey));
+        for ix in tx.instructions.iter().chain(tx.inner_instructions.iter().flat_map(|inner| &inner.instructions)) {
+            keys.push(ix.program_id);
+            keys.extend(ix.account_keys());
+        }
+        for balance in tx.pre_token_balances.iter().chain(&tx.post_token_balances) {
+            keys.extend([balance.account, balance.mint]);
+            keys.extend(balance.owner.iter().chain(&balance.program_id));
+        }
+        keys.extend(tx.token_balance_changes.iter().flat_map(|change| [change.owner, change.mint]));
+    }
+    let unique: HashSet<Pubkey> = keys.iter().copied().collect();
+    let base58_len = |key: &Pubkey| key.to_string().len();
+
+    let inline = keys.len() * size_of::<Pubkey>();
+    let strings: usize = keys.iter().map(|key| size_of::<String>() + base58_len(key)).sum();
+    // A fat pointer per use; one allocation (two counts and the text) per key.
+    let interned = keys.len() * size_of::<Arc<str>>() + unique.iter().map(|key| 2 * size_of::<usize>() + base58_len(key)).sum::<usize>();
+    println!("  pubkeys: {} in the parsed block, {} unique", keys.len(), unique.len());
+    for (name, bytes) in [("Pubkey (inline)", inline), ("String", strings), ("interned Arc<str>", interned)] {
+        println!("  {:<36} {:>9.1} KiB", name, bytes as f64 / 1024.0);
+    }
+}
+
+// Times only `run`; `setup` builds its input outside the measurement.
+fn bench<I, O>(name: &str, runs: usize, mut setup: impl FnMut() -> I, mut run: impl FnMut(I) -> O) {
+    let mut times: Vec<Duration> = (0..runs.max(1))
+        .map(|_| {
+            let input = setup();
+            let start = Instant::now();
+            black_box(run(black_box(input)));
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    println!("  {:<36} min {:>9.3} ms   median {:>9.3} ms", name, ms(times[0]), ms(times[times.len() / 2]));
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
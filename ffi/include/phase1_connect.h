@@ -0,0 +1,54 @@
+#ifndef PHASE1_CONNECT_H
+#define PHASE1_CONNECT_H
+
+/* Generated by cbindgen from src/lib.rs; regenerate it rather than editing by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Parses a getBlock response into --output json text. Returns NULL on
+ * failure; see phase1_last_error.
+ *
+ * # Safety
+ *
+ * `json` must be NULL or point to a NUL-terminated string.
+ */
+char *phase1_parse_block(const char *json);
+
+/**
+ * Parses a getTransaction response into --output json text. Returns NULL
+ * on failure; see phase1_last_error.
+ *
+ * # Safety
+ *
+ * `json` must be NULL or point to a NUL-terminated string.
+ */
+char *phase1_parse_transaction(const char *json);
+
+/**
+ * Why the calling thread's last parse failed, or NULL if it didn't. The
+ * caller owns the result and frees it with phase1_string_free.
+ */
+char *phase1_last_error(void);
+
+/**
+ * Frees a string returned by this library. NULL is ignored.
+ *
+ * # Safety
+ *
+ * `s` must be NULL or a string from this library not already freed.
+ */
+void phase1_string_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PHASE1_CONNECT_H */
@@ -0,0 +1,157 @@
+use phase_1_connect::error::ParseError;
+use phase_1_connect::parser::{parse_block_with_options, parse_transaction_result, ParseOptions};
+use phase_1_connect::types::{RpcBlockResult, RpcResult};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic;
+use std::ptr;
+
+// ==========================================
+// C FFI
+// ==========================================
+// The parser behind a C ABI, for services in other languages that would
+// otherwise run the CLI as a subprocess. Strings cross as NUL-terminated
+// UTF-8. The parse functions take the JSON text of a getBlock or
+// getTransaction response, with or without the JSON-RPC envelope, and
+// return the --output json text; the caller owns it and hands it back to
+// phase1_string_free. On failure they return NULL and phase1_last_error says
+// why. include/phase1_connect.h declares all of it.
+
+thread_local! {
+    // The calling thread's last failure.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Parses a getBlock response into --output json text. Returns NULL on
+/// failure; see phase1_last_error.
+///
+/// # Safety
+///
+/// `json` must be NULL or point to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn phase1_parse_block(json: *const c_char) -> *mut c_char {
+    unsafe { call(json, parse_block) }
+}
+
+/// Parses a getTransaction response into --output json text. Returns NULL
+/// on failure; see phase1_last_error.
+///
+/// # Safety
+///
+/// `json` must be NULL or point to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn phase1_parse_transaction(json: *const c_char) -> *mut c_char {
+    unsafe { call(json, parse_single_transaction) }
+}
+
+/// Why the calling thread's last parse failed, or NULL if it didn't. The
+/// caller owns the result and frees it with phase1_string_free.
+#[unsafe(no_mangle)]
+pub extern "C" fn phase1_last_error() -> *mut c_char {
+    LAST_ERROR.with(|last| last.borrow().clone().map_or(ptr::null_mut(), CString::into_raw))
+}
+
+/// Frees a string returned by this library. NULL is ignored.
+///
+/// # Safety
+///
+/// `s` must be NULL or a string from this library not already freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn phase1_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+unsafe fn call(json: *const c_char, parse: fn(&str) -> Result<String, ParseError>) -> *mut c_char {
+    let output = if json.is_null() {
+        Err("json is NULL".to_string())
+    } else {
+        match unsafe { CStr::from_ptr(json) }.to_str() {
+            // Nothing may unwind into the caller's frames.
+            Ok(json) => match panic::catch_unwind(|| parse(json)) {
+                Ok(parsed) => parsed.map_err(|e| e.to_string()),
+                Err(_) => Err("the parser panicked".to_string()),
+            },
+            Err(e) => Err(format!("json isn't UTF-8: {}", e)),
+        }
+    };
+    // serde_json escapes NULs, so the output never has one.
+    let result = output.and_then(|output| CString::new(output).map_err(|e| e.to_string()));
+    LAST_ERROR.with(|last| *last.borrow_mut() = result.as_ref().err().map(|e| CString::new(e.replace('\0', "")).unwrap()));
+    result.map_or(ptr::null_mut(), CString::into_raw)
+}
+
+fn parse_block(json: &str) -> Result<String, ParseError> {
+    let raw: RpcBlockResult = serde_json::from_value(result(json)?)?;
+    let (block, _) = parse_block_with_options(raw, &ParseOptions::default())?;
+    Ok(serde_json::to_string(&block)?)
+}
+
+fn parse_single_transaction(json: &str) -> Result<String, ParseError> {
+    let raw: RpcResult = serde_json::from_value(result(json)?)?;
+    let (tx, _) = parse_transaction_result(&raw, &ParseOptions::default())?;
+    Ok(serde_json::to_string(&tx)?)
+}
+
+// The response's "result", or the whole document when it has no envelope.
+fn result(json: &str) -> Result<Value, ParseError> {
+    let mut value: Value = serde_json::from_str(json)?;
+    Ok(match value.get_mut("result") {
+        Some(result) => result.take(),
+        None => value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_file(parse: unsafe extern "C" fn(*const c_char) -> *mut c_char, path: &str) -> Result<Value, String> {
+        let json = CString::new(std::fs::read_to_string(path).unwrap()).unwrap();
+        unsafe {
+            let output = parse(json.as_ptr());
+            if output.is_null() {
+                let error = phase1_last_error();
+                let message = CStr::from_ptr(error).to_str().unwrap().to_string();
+                phase1_string_free(error);
+                return Err(message);
+            }
+            let parsed = serde_json::from_str(CStr::from_ptr(output).to_str().unwrap()).unwrap();
+            phase1_string_free(output);
+            Ok(parsed)
+        }
+    }
+
+    #[test]
+    fn parses_through_the_c_abi_and_reports_failures() {
+        let tx = parse_file(phase1_parse_transaction, "../src/json/swap.json").unwrap();
+        assert!(tx["signature"].as_str().unwrap().starts_with("3FfuLctD"));
+        assert!(phase1_last_error().is_null());
+        let block = parse_file(phase1_parse_block, "../src/json/vote_block.json").unwrap();
+        assert_eq!(block["transactions"].as_array().unwrap().len(), 5);
+
+        let error = parse_file(phase1_parse_transaction, "../src/json/vote_block.json").unwrap_err();
+        assert!(error.starts_with("invalid JSON: missing field"), "{}", error);
+        unsafe {
+            assert!(phase1_parse_block(ptr::null()).is_null());
+            phase1_string_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn header_declares_every_export() {
+        let header = std::fs::read_to_string("include/phase1_connect.h").unwrap();
+        let source = std::fs::read_to_string("src/lib.rs").unwrap();
+        let exports: Vec<&str> = source
+            .lines()
+            .filter_map(|line| line.split(" extern \"C\" fn ").nth(1))
+            .map(|rest| &rest[..rest.find('(').unwrap()])
+            .collect();
+        assert_eq!(exports.len(), 4);
+        for name in exports {
+            assert!(header.contains(&format!("{}(", name)), "{} isn't in the header; regenerate it with cbindgen", name);
+        }
+    }
+}
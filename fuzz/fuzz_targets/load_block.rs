@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use phase_1_connect::incremental::parse_block_reader;
+use phase_1_connect::io::load_from_reader;
+use phase_1_connect::parser::{parse_block_with_options, ParseOptions};
+use phase_1_connect::types::RpcBlockResponse;
+
+// Arbitrary bytes as a getBlock response, read whole and incrementally.
+// Anything may fail to parse; nothing may panic.
+fuzz_target!(|data: &[u8]| {
+    let options = ParseOptions { threads: 1, ..ParseOptions::default() };
+    if let Ok(raw) = load_from_reader::<RpcBlockResponse, _>(data) {
+        let _ = parse_block_with_options(raw.result, &options);
+    }
+    let _ = parse_block_reader(data, &options);
+});
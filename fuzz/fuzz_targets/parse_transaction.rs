@@ -0,0 +1,30 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use phase_1_connect::encoding::DataEncoding;
+use phase_1_connect::io::load_from_reader;
+use phase_1_connect::parser::{parse_transaction_with_options, ParseOptions};
+use phase_1_connect::types::RpcResponse;
+
+// getTransaction responses, best seeded with the fixtures in src/json so
+// the mutations stay close to valid transactions: out-of-range indices,
+// missing signatures and keys, truncated instruction data for the decoders.
+// Every option that changes which code runs gets a turn.
+fuzz_target!(|data: &[u8]| {
+    let Ok(raw) = load_from_reader::<RpcResponse, _>(data) else {
+        return;
+    };
+    let option_sets = [
+        ParseOptions::default(),
+        ParseOptions { strict: true, ..ParseOptions::default() },
+        ParseOptions {
+            resolve_accounts: false,
+            data_encoding: DataEncoding::Base64,
+            verify_signatures: true,
+            ..ParseOptions::default()
+        },
+    ];
+    for options in &option_sets {
+        let _ = parse_transaction_with_options(&raw.result.transaction, &raw.result.meta, options);
+    }
+});
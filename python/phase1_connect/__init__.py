@@ -0,0 +1,50 @@
+"""Solana block and transaction parsing, backed by the phase-1 Rust parser.
+
+``parse_block_json`` and ``parse_transaction_json`` take the JSON text of a
+getBlock or getTransaction response (with or without the JSON-RPC envelope)
+and return the parsed output as plain dicts; ``parse_block`` and
+``parse_transaction`` return it as the dataclasses in ``phase1_connect.types``.
+Invalid input raises ValueError.
+"""
+
+from ._native import parse_block_json, parse_transaction_json
+from .types import (
+    AccountSnapshot,
+    BlockReward,
+    DurableNonce,
+    FeeBreakdown,
+    InnerInstructions,
+    ParsedBlock,
+    ParsedInstruction,
+    ParsedTransaction,
+    TokenBalance,
+    TokenBalanceChange,
+    TransactionAccount,
+)
+
+
+def parse_block(json: str) -> ParsedBlock:
+    return ParsedBlock.from_dict(parse_block_json(json))
+
+
+def parse_transaction(json: str) -> ParsedTransaction:
+    return ParsedTransaction.from_dict(parse_transaction_json(json))
+
+
+__all__ = [
+    "AccountSnapshot",
+    "BlockReward",
+    "DurableNonce",
+    "FeeBreakdown",
+    "InnerInstructions",
+    "ParsedBlock",
+    "ParsedInstruction",
+    "ParsedTransaction",
+    "TokenBalance",
+    "TokenBalanceChange",
+    "TransactionAccount",
+    "parse_block",
+    "parse_block_json",
+    "parse_transaction",
+    "parse_transaction_json",
+]
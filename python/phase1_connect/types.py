@@ -0,0 +1,236 @@
+"""Dataclasses for the parser's output.
+
+Field names and values are those of the JSON output (``--output json``):
+addresses and signatures are base58 strings, amounts integers in base
+units. Structures without a class of their own here (decoded instructions,
+events, errors, failure causes, per-program compute) stay dicts. Keys the
+classes don't know, from a newer parser, are ignored.
+"""
+
+from __future__ import annotations
+
+from dataclasses import dataclass, field, fields
+from typing import Any, Optional
+
+
+def _known(cls, data: dict[str, Any]) -> dict[str, Any]:
+    names = {f.name for f in fields(cls)}
+    return {key: value for key, value in data.items() if key in names}
+
+
+@dataclass(frozen=True, kw_only=True)
+class BlockReward:
+    pubkey: str
+    lamports: int
+    post_balance: int
+    reward_type: Optional[str] = None
+    commission: Optional[int] = None
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> BlockReward:
+        return cls(**_known(cls, data))
+
+
+@dataclass(frozen=True, kw_only=True)
+class AccountSnapshot:
+    pubkey: str
+    context_slot: int
+    lamports: int
+    data: str
+    # None once the account no longer exists.
+    owner: Optional[str] = None
+    data_encoding: str = "base64"
+    changed: Optional[bool] = None
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> AccountSnapshot:
+        return cls(**_known(cls, data))
+
+
+@dataclass(frozen=True, kw_only=True)
+class TransactionAccount:
+    pubkey: str
+    is_signer: bool
+    is_writable: bool
+    # "static" or "lookup".
+    source: str
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> TransactionAccount:
+        return cls(**_known(cls, data))
+
+
+@dataclass(frozen=True, kw_only=True)
+class ParsedInstruction:
+    program_id: str
+    # Addresses, or {"index": n} for an index past the account keys.
+    accounts: list[str | dict[str, int]]
+    account_indices: list[int]
+    data: str
+    data_encoding: str = "base58"
+    decoded: Optional[dict[str, Any]] = None
+    # The node's own parse, for jsonParsed input.
+    parsed: Optional[dict[str, Any]] = None
+    stack_height: Optional[int] = None
+    # Position of the calling instruction in the same inner list.
+    invoked_by: Optional[int] = None
+    # In failed transactions: {"status": "executed" | "failed" | "not_executed"},
+    # with the instruction error for the failed one.
+    execution: Optional[dict[str, Any]] = None
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> ParsedInstruction:
+        return cls(**_known(cls, data))
+
+
+@dataclass(frozen=True, kw_only=True)
+class InnerInstructions:
+    # The top-level instruction these were invoked from.
+    index: int
+    instructions: list[ParsedInstruction]
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> InnerInstructions:
+        return cls(index=data["index"], instructions=[ParsedInstruction.from_dict(ix) for ix in data["instructions"]])
+
+
+@dataclass(frozen=True, kw_only=True)
+class TokenBalance:
+    account_index: int
+    account: str
+    mint: str
+    amount: int
+    decimals: int
+    owner: Optional[str] = None
+    program_id: Optional[str] = None
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> TokenBalance:
+        return cls(**_known(cls, data))
+
+
+@dataclass(frozen=True, kw_only=True)
+class TokenBalanceChange:
+    owner: str
+    mint: str
+    decimals: int
+    pre_amount: int
+    post_amount: int
+    delta: int
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> TokenBalanceChange:
+        return cls(**_known(cls, data))
+
+
+@dataclass(frozen=True, kw_only=True)
+class FeeBreakdown:
+    signatures: int = 0
+    base_fee: int = 0
+    priority_fee: int = 0
+    compute_unit_limit: int = 0
+    compute_unit_price_micro_lamports: int = 0
+    consumed_priority_fee: Optional[int] = None
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> FeeBreakdown:
+        return cls(**_known(cls, data))
+
+
+@dataclass(frozen=True, kw_only=True)
+class DurableNonce:
+    nonce_account: str
+    nonce_authority: str
+    # The message's recent blockhash, which holds the nonce value.
+    nonce: Optional[str] = None
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> DurableNonce:
+        return cls(**_known(cls, data))
+
+
+@dataclass(frozen=True, kw_only=True)
+class ParsedTransaction:
+    signature: str
+    fee_payer: str
+    is_success: bool
+    account_keys: list[str]
+    instructions: list[ParsedInstruction]
+    inner_instructions: list[InnerInstructions]
+    log_messages: list[str]
+    pre_balances: list[int]
+    post_balances: list[int]
+    pre_token_balances: list[TokenBalance]
+    post_token_balances: list[TokenBalance]
+    token_balance_changes: list[TokenBalanceChange]
+    fee: int
+    base_fee: int
+    priority_fee: int
+    schema_version: int = 0
+    slot: Optional[int] = None
+    index_in_block: Optional[int] = None
+    timestamp_ms: Optional[int] = None
+    error: Optional[Any] = None
+    failure_reason: Optional[str] = None
+    failure_cause: Optional[dict[str, Any]] = None
+    accounts: list[TransactionAccount] = field(default_factory=list)
+    events: list[dict[str, Any]] = field(default_factory=list)
+    memos: list[str] = field(default_factory=list)
+    compute_units_consumed: Optional[int] = None
+    compute_unit_limit: Optional[int] = None
+    unit_price_micro_lamports: Optional[int] = None
+    compute_by_program: list[dict[str, Any]] = field(default_factory=list)
+    fee_breakdown: FeeBreakdown = field(default_factory=FeeBreakdown)
+    signatures_verified: Optional[bool] = None
+    labels: dict[str, str] = field(default_factory=dict)
+    durable_nonce: Optional[DurableNonce] = None
+    # Token account -> owner, for accounts the token balances don't name.
+    token_owners: dict[str, str] = field(default_factory=dict)
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> ParsedTransaction:
+        values = _known(cls, data)
+        values["accounts"] = [TransactionAccount.from_dict(a) for a in data.get("accounts", [])]
+        values["instructions"] = [ParsedInstruction.from_dict(ix) for ix in data["instructions"]]
+        values["inner_instructions"] = [InnerInstructions.from_dict(inner) for inner in data["inner_instructions"]]
+        values["pre_token_balances"] = [TokenBalance.from_dict(b) for b in data["pre_token_balances"]]
+        values["post_token_balances"] = [TokenBalance.from_dict(b) for b in data["post_token_balances"]]
+        values["token_balance_changes"] = [TokenBalanceChange.from_dict(c) for c in data["token_balance_changes"]]
+        if "fee_breakdown" in data:
+            values["fee_breakdown"] = FeeBreakdown.from_dict(data["fee_breakdown"])
+        if data.get("durable_nonce"):
+            values["durable_nonce"] = DurableNonce.from_dict(data["durable_nonce"])
+        return cls(**values)
+
+
+@dataclass(frozen=True, kw_only=True)
+class ParsedBlock:
+    blockhash: str
+    parent_slot: int
+    previous_blockhash: str
+    rewards: list[BlockReward]
+    transactions: list[ParsedTransaction]
+    schema_version: int = 0
+    # parent_slot + 1 unless the caller knew better.
+    slot: int = 0
+    epoch: int = 0
+    slot_index: int = 0
+    block_height: Optional[int] = None
+    block_time: Optional[int] = None
+    block_time_rfc3339: Optional[str] = None
+    leader: Optional[str] = None
+    # Only set for blocks fetched with transactionDetails: signatures.
+    signatures: list[str] = field(default_factory=list)
+    excluded_votes: Optional[int] = None
+    # Only set with --snapshot-account.
+    account_snapshots: list[AccountSnapshot] = field(default_factory=list)
+    # The RPC endpoint that served the block, with --rpc-endpoint.
+    endpoint: Optional[str] = None
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> ParsedBlock:
+        values = _known(cls, data)
+        values["rewards"] = [BlockReward.from_dict(r) for r in data["rewards"]]
+        values["transactions"] = [ParsedTransaction.from_dict(tx) for tx in data["transactions"]]
+        values["account_snapshots"] = [AccountSnapshot.from_dict(s) for s in data.get("account_snapshots", [])]
+        return cls(**values)
@@ -0,0 +1,30 @@
+import json
+import pathlib
+import unittest
+
+import phase1_connect
+
+SAMPLES = pathlib.Path(__file__).resolve().parents[2] / "src" / "json"
+
+
+class ParseTest(unittest.TestCase):
+    def test_block_with_or_without_the_envelope(self):
+        text = (SAMPLES / "block.json").read_text()
+        wrapped = phase1_connect.parse_block_json(text)
+        bare = phase1_connect.parse_block_json(json.dumps(json.loads(text)["result"]))
+        self.assertEqual(wrapped, bare)
+        self.assertTrue(wrapped["transactions"])
+        self.assertEqual(phase1_connect.parse_block(text).blockhash, wrapped["blockhash"])
+
+    def test_transaction(self):
+        text = (SAMPLES / "transaction.json").read_text()
+        tx = phase1_connect.parse_transaction(text)
+        self.assertEqual(tx.signature, phase1_connect.parse_transaction_json(text)["signature"])
+
+    def test_invalid_input_raises_value_error(self):
+        with self.assertRaises(ValueError):
+            phase1_connect.parse_block_json("{}")
+
+
+if __name__ == "__main__":
+    unittest.main()
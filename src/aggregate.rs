@@ -0,0 +1,376 @@
+use crate::epoch::format_rfc3339;
+use crate::pubkey::Pubkey;
+use crate::types::ParsedBlock;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Write};
+
+// ==========================================
+// PERIODIC ROLLUPS
+// ==========================================
+// Hourly or daily totals over a run, for dashboards that don't need every
+// transaction: blocks, transactions, fees, unique fee payers and activity
+// per program, keyed by the start of the period containing the block time.
+// Blocks without a block time aren't counted.
+//
+// A period stays open, with its set of fee payers, until a block `grace`
+// seconds past its end arrives; it's then closed down to its totals, so a
+// long run holds at most a couple of fee payer sets. A block landing in a
+// period already closed is only counted in `late_blocks`. Without a grace
+// (out of order input such as backfill) every period stays open.
+//
+// Over a long backfill the open fee payer sets are most of the memory a
+// run holds. With a FeePayerSpill and a memory budget, the sets are moved
+// out to sorted runs whenever they outgrow the budget, and a period's
+// unique fee payers are counted by merging its runs.
+
+// Rough heap size of one entry in a fee payer set.
+const FEE_PAYER_BYTES: usize = 40;
+
+// Where fee payer sets go when they outgrow the memory budget (see
+// spill::SpillDir).
+pub trait FeePayerSpill: Send + fmt::Debug {
+    // Stores `fee_payers`, sorted, as another run for the period.
+    fn spill(&mut self, period_start: i64, fee_payers: &[Pubkey]) -> io::Result<()>;
+    // Distinct fee payers across the period's runs and `fee_payers`, sorted.
+    fn count_unique(&self, period_start: i64, fee_payers: &[Pubkey]) -> io::Result<usize>;
+    // Drops the period's runs once it's closed.
+    fn discard(&mut self, period_start: i64) -> io::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RollupPeriod {
+    Hour,
+    Day,
+}
+
+impl RollupPeriod {
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "hour" => Ok(RollupPeriod::Hour),
+            "day" => Ok(RollupPeriod::Day),
+            _ => Err(format!("invalid rollup period '{}' (expected hour or day)", value)),
+        }
+    }
+
+    pub fn seconds(self) -> i64 {
+        match self {
+            RollupPeriod::Hour => 3_600,
+            RollupPeriod::Day => 86_400,
+        }
+    }
+
+    // Start of the period containing `unix_seconds`.
+    pub fn start_of(self, unix_seconds: i64) -> i64 {
+        unix_seconds - unix_seconds.rem_euclid(self.seconds())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgramActivity {
+    pub program_id: Pubkey,
+    // Transactions calling the program, directly or via CPI.
+    pub transactions: usize,
+    pub failed_transactions: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rollup {
+    // Unix seconds, and the same in RFC 3339.
+    pub period_start: i64,
+    pub period_start_time: String,
+    pub blocks: usize,
+    pub first_slot: u64,
+    pub last_slot: u64,
+    pub transactions: usize,
+    pub vote_transactions: usize,
+    pub failed_transactions: usize,
+    pub total_fees: u64,
+    pub priority_fees: u64,
+    pub compute_units: u64,
+    pub unique_fee_payers: usize,
+    // Most transactions first; ties by program ID.
+    pub programs: Vec<ProgramActivity>,
+}
+
+// A period still taking blocks.
+#[derive(Debug)]
+struct OpenRollup {
+    rollup: Rollup,
+    fee_payers: HashSet<Pubkey>,
+    programs: HashMap<Pubkey, ProgramActivity>,
+}
+
+impl OpenRollup {
+    fn new(period_start: i64, slot: u64) -> Self {
+        OpenRollup {
+            rollup: Rollup {
+                period_start,
+                period_start_time: format_rfc3339(period_start),
+                blocks: 0,
+                first_slot: slot,
+                last_slot: slot,
+                transactions: 0,
+                vote_transactions: 0,
+                failed_transactions: 0,
+                total_fees: 0,
+                priority_fees: 0,
+                compute_units: 0,
+                unique_fee_payers: 0,
+                programs: Vec::new(),
+            },
+            fee_payers: HashSet::new(),
+            programs: HashMap::new(),
+        }
+    }
+
+    fn record(&mut self, block: &ParsedBlock) {
+        let rollup = &mut self.rollup;
+        rollup.blocks += 1;
+        rollup.first_slot = rollup.first_slot.min(block.slot);
+        rollup.last_slot = rollup.last_slot.max(block.slot);
+        for tx in &block.transactions {
+            rollup.transactions += 1;
+            rollup.vote_transactions += usize::from(tx.is_vote());
+            rollup.failed_transactions += usize::from(!tx.is_success);
+            rollup.total_fees += tx.fee;
+            rollup.priority_fees += tx.priority_fee;
+            rollup.compute_units += tx.compute_units_consumed.unwrap_or(0);
+            self.fee_payers.insert(tx.fee_payer);
+
+            let programs: BTreeSet<Pubkey> = tx
+                .instructions
+                .iter()
+                .chain(tx.inner_instructions.iter().flat_map(|inner| &inner.instructions))
+                .map(|ix| ix.program_id)
+                .collect();
+            for program_id in programs {
+                let activity = self
+                    .programs
+                    .entry(program_id)
+                    .or_insert(ProgramActivity { program_id, transactions: 0, failed_transactions: 0 });
+                activity.transactions += 1;
+                activity.failed_transactions += usize::from(!tx.is_success);
+            }
+        }
+    }
+
+    fn snapshot(&self, spill: Option<&dyn FeePayerSpill>) -> io::Result<Rollup> {
+        let mut rollup = self.rollup.clone();
+        rollup.unique_fee_payers = match spill {
+            Some(spill) => spill.count_unique(rollup.period_start, &sorted(&self.fee_payers))?,
+            None => self.fee_payers.len(),
+        };
+        rollup.programs = self.programs.values().cloned().collect();
+        rollup.programs.sort_by(|a, b| b.transactions.cmp(&a.transactions).then(a.program_id.cmp(&b.program_id)));
+        Ok(rollup)
+    }
+}
+
+fn sorted(fee_payers: &HashSet<Pubkey>) -> Vec<Pubkey> {
+    let mut sorted: Vec<Pubkey> = fee_payers.iter().copied().collect();
+    sorted.sort_unstable();
+    sorted
+}
+
+#[derive(Debug)]
+pub struct Aggregator {
+    period: RollupPeriod,
+    grace: Option<i64>,
+    open: BTreeMap<i64, OpenRollup>,
+    closed: Vec<Rollup>,
+    // Periods before this one are closed.
+    closed_before: i64,
+    newest_time: i64,
+    untimed_blocks: usize,
+    late_blocks: usize,
+    // Fee payers held in the open periods' sets.
+    fee_payers_held: usize,
+    memory_budget: usize,
+    spill: Option<Box<dyn FeePayerSpill>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollupReport {
+    pub period: RollupPeriod,
+    // Oldest first.
+    pub rollups: Vec<Rollup>,
+    pub untimed_blocks: usize,
+    pub late_blocks: usize,
+}
+
+impl Aggregator {
+    // Every period stays open until `with_grace` says otherwise.
+    pub fn new(period: RollupPeriod) -> Self {
+        Aggregator {
+            period,
+            grace: None,
+            open: BTreeMap::new(),
+            closed: Vec::new(),
+            closed_before: i64::MIN,
+            newest_time: i64::MIN,
+            untimed_blocks: 0,
+            late_blocks: 0,
+            fee_payers_held: 0,
+            memory_budget: usize::MAX,
+            spill: None,
+        }
+    }
+
+    // Keeps the fee payer sets within about `bytes`, moving them to `spill`
+    // when they grow past it.
+    pub fn with_memory_budget(mut self, bytes: usize, spill: Box<dyn FeePayerSpill>) -> Self {
+        self.memory_budget = bytes;
+        self.spill = Some(spill);
+        self
+    }
+
+    // Seconds past a period's end before it's closed; for input arriving
+    // roughly in block time order.
+    pub fn with_grace(mut self, seconds: i64) -> Self {
+        self.grace = Some(seconds.max(0));
+        self
+    }
+
+    // Returns true when the block closed a period, i.e. the rollups are
+    // worth writing out. Errors come from the spill only.
+    pub fn record_block(&mut self, block: &ParsedBlock) -> io::Result<bool> {
+        let Some(block_time) = block.block_time else {
+            self.untimed_blocks += 1;
+            return Ok(false);
+        };
+        let period_start = self.period.start_of(block_time);
+        if period_start < self.closed_before {
+            self.late_blocks += 1;
+            return Ok(false);
+        }
+        let open = self.open.entry(period_start).or_insert_with(|| OpenRollup::new(period_start, block.slot));
+        let held = open.fee_payers.len();
+        open.record(block);
+        self.fee_payers_held += open.fee_payers.len() - held;
+        self.newest_time = self.newest_time.max(block_time);
+        if self.fee_payers_held.saturating_mul(FEE_PAYER_BYTES) > self.memory_budget
+            && let Some(spill) = &mut self.spill
+        {
+            for (period_start, open) in &mut self.open {
+                spill.spill(*period_start, &sorted(&open.fee_payers))?;
+                open.fee_payers = HashSet::new();
+            }
+            self.fee_payers_held = 0;
+        }
+
+        let Some(grace) = self.grace else {
+            return Ok(false);
+        };
+        let mut closed_any = false;
+        while let Some(entry) = self.open.first_entry().filter(|entry| entry.key() + self.period.seconds() + grace <= self.newest_time) {
+            let period_start = *entry.key();
+            self.closed_before = period_start + self.period.seconds();
+            let open = entry.remove();
+            self.closed.push(open.snapshot(self.spill.as_deref())?);
+            self.fee_payers_held -= open.fee_payers.len();
+            if let Some(spill) = &mut self.spill {
+                spill.discard(period_start)?;
+            }
+            closed_any = true;
+        }
+        Ok(closed_any)
+    }
+
+    pub fn report(&self) -> io::Result<RollupReport> {
+        let open = self.open.values().map(|open| open.snapshot(self.spill.as_deref())).collect::<io::Result<Vec<_>>>()?;
+        Ok(RollupReport {
+            period: self.period,
+            rollups: self.closed.iter().cloned().chain(open).collect(),
+            untimed_blocks: self.untimed_blocks,
+            late_blocks: self.late_blocks,
+        })
+    }
+}
+
+impl RollupReport {
+    // One row per period, and one per period and program.
+    pub fn write_csv(&self, periods: &mut impl Write, programs: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            periods,
+            "period_start,period_start_time,blocks,first_slot,last_slot,transactions,vote_transactions,failed_transactions,total_fees,priority_fees,compute_units,unique_fee_payers"
+        )?;
+        writeln!(programs, "period_start,period_start_time,program_id,transactions,failed_transactions")?;
+        for rollup in &self.rollups {
+            writeln!(
+                periods,
+                "{},{},{},{},{},{},{},{},{},{},{},{}",
+                rollup.period_start,
+                rollup.period_start_time,
+                rollup.blocks,
+                rollup.first_slot,
+                rollup.last_slot,
+                rollup.transactions,
+                rollup.vote_transactions,
+                rollup.failed_transactions,
+                rollup.total_fees,
+                rollup.priority_fees,
+                rollup.compute_units,
+                rollup.unique_fee_payers,
+            )?;
+            for program in &rollup.programs {
+                writeln!(
+                    programs,
+                    "{},{},{},{},{}",
+                    rollup.period_start, rollup.period_start_time, program.program_id, program.transactions, program.failed_transactions,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn rolls_blocks_up_by_hour_and_closes_old_periods() {
+        let block = fixtures::block();
+        let hour = RollupPeriod::Hour.start_of(block.block_time.unwrap());
+        let at = |slot: u64, time: i64| {
+            let mut block = block.clone();
+            block.slot = slot;
+            block.block_time = Some(time);
+            block
+        };
+
+        let mut aggregator = Aggregator::new(RollupPeriod::Hour).with_grace(60);
+        assert!(!aggregator.record_block(&at(100, hour + 10)).unwrap());
+        assert!(!aggregator.record_block(&at(101, hour + 3_599)).unwrap());
+        // Within the grace period, so the first hour stays open...
+        assert!(!aggregator.record_block(&at(102, hour + 3_610)).unwrap());
+        // ...until a block past it arrives.
+        assert!(aggregator.record_block(&at(103, hour + 3_660)).unwrap());
+        assert!(!aggregator.record_block(&at(99, hour + 5)).unwrap());
+        let mut untimed = block.clone();
+        untimed.block_time = None;
+        assert!(!aggregator.record_block(&untimed).unwrap());
+
+        let report = aggregator.report().unwrap();
+        assert_eq!((report.untimed_blocks, report.late_blocks), (1, 1));
+        assert_eq!(report.rollups.len(), 2);
+        let first = &report.rollups[0];
+        assert_eq!((first.period_start, first.blocks, first.first_slot, first.last_slot), (hour, 2, 100, 101));
+        assert_eq!(first.transactions, block.transactions.len() * 2);
+        assert_eq!(first.total_fees, block.transactions.iter().map(|tx| tx.fee).sum::<u64>() * 2);
+        let fee_payers: HashSet<Pubkey> = block.transactions.iter().map(|tx| tx.fee_payer).collect();
+        assert_eq!(first.unique_fee_payers, fee_payers.len());
+        assert!(first.programs.windows(2).all(|pair| pair[0].transactions >= pair[1].transactions));
+        assert_eq!(report.rollups[1].blocks, 2);
+
+        let (mut periods, mut programs) = (Vec::new(), Vec::new());
+        report.write_csv(&mut periods, &mut programs).unwrap();
+        assert_eq!(String::from_utf8(periods).unwrap().lines().count(), 3);
+        let programs = String::from_utf8(programs).unwrap();
+        assert_eq!(programs.lines().count(), 1 + first.programs.len() + report.rollups[1].programs.len());
+    }
+}
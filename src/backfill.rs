@@ -0,0 +1,211 @@
+use crate::parser::ParseOptions;
+use crate::rpc::{BlockFetch, RpcClient};
+use crate::stream::{SlotFetcher, StreamedBlock};
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread;
+
+// ==========================================
+// HISTORICAL BACKFILL
+// ==========================================
+// Reindexes a fixed slot range with several workers at once. The range is
+// split into contiguous chunks, one per worker thread; each worker fetches
+// and parses its chunk in slot order and hands the results to a single
+// consumer, which does the writing. Blocks are in order within a chunk but
+// interleaved across chunks. Every slot comes back exactly once, as a block,
+// a skip or a failure; `GapReport` keeps the skips and failures so a rerun
+// can target them.
+
+pub enum BackfillItem {
+    Block(Box<StreamedBlock>),
+    Skipped(u64),
+    Failed(u64, String),
+}
+
+impl BackfillItem {
+    pub fn slot(&self) -> u64 {
+        match self {
+            BackfillItem::Block(streamed) => streamed.slot,
+            BackfillItem::Skipped(slot) | BackfillItem::Failed(slot, _) => *slot,
+        }
+    }
+}
+
+pub struct Backfill {
+    client: RpcClient,
+    options: ParseOptions,
+    start_slot: u64,
+    end_slot: u64,
+    workers: usize,
+}
+
+impl Backfill {
+    // Inclusive range.
+    pub fn new(client: RpcClient, start_slot: u64, end_slot: u64) -> Self {
+        Backfill { client, options: ParseOptions::default(), start_slot, end_slot, workers: 1 }
+    }
+
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    pub fn slots(&self) -> u64 {
+        (self.end_slot + 1).saturating_sub(self.start_slot)
+    }
+
+    // Starts the workers. The receiver yields one item per slot and closes
+    // once every chunk is done; dropping it stops the workers.
+    pub fn start(self) -> Receiver<BackfillItem> {
+        run(Arc::new(self.client), Arc::new(self.options), partition(self.start_slot, self.end_slot, self.workers))
+    }
+}
+
+// Contiguous chunks as even as possible, the earlier ones taking the
+// remainder. Never more chunks than slots; none for an empty range.
+pub fn partition(start_slot: u64, end_slot: u64, parts: usize) -> Vec<RangeInclusive<u64>> {
+    let slots = (end_slot + 1).saturating_sub(start_slot);
+    let parts = (parts.max(1) as u64).min(slots);
+    let mut chunks = Vec::new();
+    let mut next = start_slot;
+    for part in 0..parts {
+        let len = slots / parts + u64::from(part < slots % parts);
+        chunks.push(next..=next + len - 1);
+        next += len;
+    }
+    chunks
+}
+
+fn run<S: SlotFetcher>(source: Arc<S>, options: Arc<ParseOptions>, chunks: Vec<RangeInclusive<u64>>) -> Receiver<BackfillItem> {
+    let (items, received) = mpsc::sync_channel(chunks.len() * 2);
+    for chunk in chunks {
+        let (source, options, items) = (source.clone(), options.clone(), items.clone());
+        thread::spawn(move || backfill_worker(&*source, &options, chunk, &items));
+    }
+    received
+}
+
+fn backfill_worker<S: SlotFetcher>(source: &S, options: &ParseOptions, chunk: RangeInclusive<u64>, items: &SyncSender<BackfillItem>) {
+    for slot in chunk {
+        let item = match source.fetch_block(slot) {
+            Ok(BlockFetch::Block(raw_block)) => match StreamedBlock::parse(slot, raw_block, options) {
+                Ok(streamed) => BackfillItem::Block(Box::new(streamed)),
+                Err(e) => BackfillItem::Failed(slot, e.to_string()),
+            },
+            Ok(BlockFetch::SlotSkipped) => BackfillItem::Skipped(slot),
+            // Past slots that aren't available have usually been pruned from
+            // the node's ledger.
+            Ok(BlockFetch::BlockUnavailable) => BackfillItem::Failed(slot, format!("block for slot {} is not available", slot)),
+            Err(e) => BackfillItem::Failed(slot, e),
+        };
+        if items.send(item).is_err() {
+            return;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlotFailure {
+    pub slot: u64,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GapReport {
+    pub start_slot: u64,
+    pub end_slot: u64,
+    pub blocks: u64,
+    // Slots without a block: not an error, nothing to retry.
+    pub skipped: Vec<u64>,
+    // Slots to retry, with the error each one last hit.
+    pub failed: Vec<SlotFailure>,
+}
+
+impl GapReport {
+    pub fn new(start_slot: u64, end_slot: u64) -> Self {
+        GapReport { start_slot, end_slot, blocks: 0, skipped: Vec::new(), failed: Vec::new() }
+    }
+
+    pub fn record(&mut self, item: &BackfillItem) {
+        match item {
+            BackfillItem::Block(_) => self.blocks += 1,
+            BackfillItem::Skipped(slot) => self.skipped.push(*slot),
+            BackfillItem::Failed(slot, error) => self.failed.push(SlotFailure { slot: *slot, error: error.clone() }),
+        }
+    }
+
+    pub fn processed(&self) -> u64 {
+        self.blocks + self.skipped.len() as u64 + self.failed.len() as u64
+    }
+
+    // Puts the skipped and failed slots in slot order; they're recorded in
+    // the order the workers finished them.
+    pub fn sort(&mut self) {
+        self.skipped.sort_unstable();
+        self.failed.sort_by_key(|failure| failure.slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::RpcBlockResponse;
+
+    struct FakeSource {
+        block: String,
+    }
+
+    impl SlotFetcher for FakeSource {
+        fn fetch_block(&self, slot: u64) -> Result<BlockFetch, String> {
+            match slot {
+                13 | 22 => Ok(BlockFetch::SlotSkipped),
+                17 => Err("connection reset".to_string()),
+                25 => Ok(BlockFetch::BlockUnavailable),
+                _ => Ok(BlockFetch::Block(serde_json::from_str::<RpcBlockResponse>(&self.block).unwrap().result)),
+            }
+        }
+
+        fn get_slot(&self) -> Result<u64, String> {
+            Ok(u64::MAX)
+        }
+    }
+
+    #[test]
+    fn partitions_evenly() {
+        assert_eq!(partition(10, 29, 3), [10..=16, 17..=23, 24..=29]);
+        assert_eq!(partition(10, 11, 4), [10..=10, 11..=11]);
+        assert_eq!(partition(10, 10, 0), [10..=10]);
+        assert!(partition(10, 9, 2).is_empty());
+    }
+
+    #[test]
+    fn every_slot_comes_back_once_and_gaps_are_reported() {
+        let source = Arc::new(FakeSource { block: std::fs::read_to_string("src/json/empty_block.json").unwrap() });
+        let items = run(source, Arc::new(ParseOptions::default()), partition(10, 29, 3));
+        let mut report = GapReport::new(10, 29);
+        let mut slots = Vec::new();
+        for item in items {
+            if let BackfillItem::Block(streamed) = &item {
+                assert_eq!(streamed.block.slot, streamed.slot);
+            }
+            report.record(&item);
+            slots.push(item.slot());
+        }
+        slots.sort_unstable();
+        assert_eq!(slots, (10..=29).collect::<Vec<_>>());
+
+        report.sort();
+        assert_eq!(report.processed(), 20);
+        assert_eq!(report.blocks, 16);
+        assert_eq!(report.skipped, [13, 22]);
+        assert_eq!(report.failed.iter().map(|failure| failure.slot).collect::<Vec<_>>(), [17, 25]);
+        assert_eq!(report.failed[0].error, "connection reset");
+    }
+}
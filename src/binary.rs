@@ -0,0 +1,415 @@
+use crate::error::ParseError;
+use crate::reorg::ChainEvent;
+use crate::types::{ParsedBlock, ParsedTransaction};
+use bincode::Options;
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::de::value::{Error as DeError, MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{DeserializeOwned, Error as _, IntoDeserializer, Visitor};
+use serde::{forward_to_deserialize_any, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+
+// ==========================================
+// BINARY OUTPUT
+// ==========================================
+// Parsed blocks and transactions in bincode or borsh, for handing output
+// from one pipeline stage to the next without writing and re-reading JSON
+// text. Neither format is self-describing, and the parsed types' serde
+// shape is: empty optional fields are left out, and decoded instruction
+// arguments and transaction errors are free-form JSON. So a value goes
+// through `Packed`, the same tree as its JSON with numbers kept binary and
+// every distinct string (field names, addresses) stored once per record,
+// and reading back runs the usual schema upgrade. Bincode uses variable
+// length integers.
+//
+// A file starts with MAGIC and a format byte, followed by records of a
+// kind byte, a little-endian u32 length and the encoded value.
+
+pub const MAGIC: &[u8; 4] = b"P1PO";
+
+// Record kinds.
+const BLOCK: u8 = 0;
+const TRANSACTION: u8 = 1;
+const CHAIN_EVENT: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryFormat {
+    Bincode,
+    Borsh,
+}
+
+impl BinaryFormat {
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "bincode" => Ok(BinaryFormat::Bincode),
+            "borsh" => Ok(BinaryFormat::Borsh),
+            _ => Err(format!("unknown binary format '{}' (expected bincode or borsh)", value)),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinaryFormat::Bincode => "bincode",
+            BinaryFormat::Borsh => "borsh",
+        }
+    }
+
+    fn tag(self) -> u8 {
+        match self {
+            BinaryFormat::Bincode => 1,
+            BinaryFormat::Borsh => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            1 => Some(BinaryFormat::Bincode),
+            2 => Some(BinaryFormat::Borsh),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParsedRecord {
+    Block(Box<ParsedBlock>),
+    // Parsed outside a block (parse-tx, log subscriptions).
+    Transaction(Box<ParsedTransaction>),
+    ChainEvent(ChainEvent),
+}
+
+// serde_json::Value minus the need for a self-describing format. Strings
+// are indices into the record's string table.
+#[derive(Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+enum Packed {
+    Null,
+    Bool(bool),
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    String(u32),
+    Array(Vec<Packed>),
+    Object(Vec<(u32, Packed)>),
+}
+
+#[derive(Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+struct PackedRecord {
+    strings: Vec<String>,
+    root: Packed,
+}
+
+#[derive(Default)]
+struct Interner {
+    strings: Vec<String>,
+    indices: HashMap<String, u32>,
+}
+
+impl Interner {
+    fn intern(&mut self, value: String) -> u32 {
+        if let Some(index) = self.indices.get(&value) {
+            return *index;
+        }
+        let index = self.strings.len() as u32;
+        self.strings.push(value.clone());
+        self.indices.insert(value, index);
+        index
+    }
+
+    fn pack(&mut self, value: Value) -> Packed {
+        match value {
+            Value::Null => Packed::Null,
+            Value::Bool(value) => Packed::Bool(value),
+            Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+                (Some(value), _) => Packed::Unsigned(value),
+                (None, Some(value)) => Packed::Signed(value),
+                (None, None) => Packed::Float(number.as_f64().unwrap_or_default()),
+            },
+            Value::String(value) => Packed::String(self.intern(value)),
+            Value::Array(values) => Packed::Array(values.into_iter().map(|value| self.pack(value)).collect()),
+            Value::Object(fields) => Packed::Object(fields.into_iter().map(|(key, value)| (self.intern(key), self.pack(value))).collect()),
+        }
+    }
+}
+
+impl PackedRecord {
+    fn pack(value: Value) -> Self {
+        let mut interner = Interner::default();
+        let root = interner.pack(value);
+        PackedRecord { strings: interner.strings, root }
+    }
+
+    // Deserializes `T` straight out of the tree, without building a Value.
+    fn unpack<T: DeserializeOwned>(&self) -> Result<T, ParseError> {
+        self.check(&self.root)?;
+        T::deserialize(PackedDeserializer { packed: &self.root, strings: &self.strings })
+            .map_err(|e| ParseError::InvalidBinary(e.to_string()))
+    }
+
+    // Every string index is in range, so the deserializer can index freely.
+    fn check(&self, packed: &Packed) -> Result<(), ParseError> {
+        let check_index = |index: u32| match (index as usize) < self.strings.len() {
+            true => Ok(()),
+            false => Err(ParseError::InvalidBinary(format!("string {} out of range", index))),
+        };
+        match packed {
+            Packed::String(index) => check_index(*index),
+            Packed::Array(values) => values.iter().try_for_each(|value| self.check(value)),
+            Packed::Object(fields) => fields.iter().try_for_each(|(key, value)| {
+                check_index(*key)?;
+                self.check(value)
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PackedDeserializer<'a> {
+    packed: &'a Packed,
+    strings: &'a [String],
+}
+
+impl<'a> PackedDeserializer<'a> {
+    fn at(self, packed: &'a Packed) -> Self {
+        PackedDeserializer { packed, strings: self.strings }
+    }
+
+    fn fields(self, fields: &'a [(u32, Packed)]) -> MapDeserializer<'a, impl Iterator<Item = (&'a str, Self)>, DeError> {
+        MapDeserializer::new(fields.iter().map(move |(key, value)| (self.strings[*key as usize].as_str(), self.at(value))))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeError> for PackedDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for PackedDeserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.packed {
+            Packed::Null => visitor.visit_unit(),
+            Packed::Bool(value) => visitor.visit_bool(*value),
+            Packed::Unsigned(value) => visitor.visit_u64(*value),
+            Packed::Signed(value) => visitor.visit_i64(*value),
+            Packed::Float(value) => visitor.visit_f64(*value),
+            Packed::String(index) => visitor.visit_borrowed_str(&self.strings[*index as usize]),
+            Packed::Array(values) => visitor.visit_seq(SeqDeserializer::new(values.iter().map(|value| self.at(value)))),
+            Packed::Object(fields) => visitor.visit_map(self.fields(fields)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.packed {
+            Packed::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // Externally tagged, as in JSON: "Variant" or {"Variant": value}.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        match self.packed {
+            Packed::String(index) => visitor.visit_enum(self.strings[*index as usize].as_str().into_deserializer()),
+            Packed::Object(fields) if fields.len() == 1 => visitor.visit_enum(MapAccessDeserializer::new(self.fields(fields))),
+            _ => Err(DeError::custom("expected a string or single-key object for an enum")),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl ParsedRecord {
+    // Blocks and transactions come back upgraded to the current schema.
+    pub fn decode(kind: u8, bytes: &[u8], format: BinaryFormat) -> Result<Self, ParseError> {
+        let packed: PackedRecord = match format {
+            BinaryFormat::Bincode => bincode::DefaultOptions::new().deserialize(bytes).map_err(|e| ParseError::InvalidBinary(e.to_string()))?,
+            BinaryFormat::Borsh => borsh::from_slice(bytes).map_err(|e| ParseError::InvalidBinary(e.to_string()))?,
+        };
+        match kind {
+            BLOCK => {
+                let mut block: ParsedBlock = packed.unpack()?;
+                block.upgrade()?;
+                Ok(ParsedRecord::Block(Box::new(block)))
+            }
+            TRANSACTION => {
+                let mut tx: ParsedTransaction = packed.unpack()?;
+                tx.upgrade()?;
+                Ok(ParsedRecord::Transaction(Box::new(tx)))
+            }
+            CHAIN_EVENT => Ok(ParsedRecord::ChainEvent(packed.unpack()?)),
+            _ => Err(ParseError::InvalidBinary(format!("unknown record kind {}", kind))),
+        }
+    }
+}
+
+pub struct BinaryWriter<W: Write> {
+    out: W,
+    format: BinaryFormat,
+}
+
+impl<W: Write> BinaryWriter<W> {
+    // Starts a new file with the header.
+    pub fn new(mut out: W, format: BinaryFormat) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[format.tag()])?;
+        Ok(BinaryWriter { out, format })
+    }
+
+    // Continues a file that already has its header.
+    pub fn append(out: W, format: BinaryFormat) -> Self {
+        BinaryWriter { out, format }
+    }
+
+    pub fn format(&self) -> BinaryFormat {
+        self.format
+    }
+
+    pub fn write(&mut self, record: &ParsedRecord) -> io::Result<()> {
+        match record {
+            ParsedRecord::Block(block) => self.write_block(block),
+            ParsedRecord::Transaction(tx) => self.write_transaction(tx),
+            ParsedRecord::ChainEvent(event) => self.write_chain_event(event),
+        }
+    }
+
+    pub fn write_block(&mut self, block: &ParsedBlock) -> io::Result<()> {
+        self.write_record(BLOCK, serde_json::to_value(block)?)
+    }
+
+    pub fn write_transaction(&mut self, tx: &ParsedTransaction) -> io::Result<()> {
+        self.write_record(TRANSACTION, serde_json::to_value(tx)?)
+    }
+
+    pub fn write_chain_event(&mut self, event: &ChainEvent) -> io::Result<()> {
+        self.write_record(CHAIN_EVENT, serde_json::to_value(event)?)
+    }
+
+    fn write_record(&mut self, kind: u8, value: Value) -> io::Result<()> {
+        let packed = PackedRecord::pack(value);
+        let bytes = match self.format {
+            BinaryFormat::Bincode => bincode::DefaultOptions::new().serialize(&packed).map_err(io::Error::other)?,
+            BinaryFormat::Borsh => borsh::to_vec(&packed)?,
+        };
+        let len = u32::try_from(bytes.len()).map_err(|_| io::Error::other(format!("record of {} bytes is too large", bytes.len())))?;
+        self.out.write_all(&[kind])?;
+        self.out.write_all(&len.to_le_bytes())?;
+        self.out.write_all(&bytes)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+// Reads records back in order; the format comes from the header.
+pub struct BinaryReader<R: Read> {
+    input: R,
+    format: BinaryFormat,
+}
+
+impl<R: Read> BinaryReader<R> {
+    pub fn new(mut input: R) -> Result<Self, ParseError> {
+        let mut header = [0u8; 5];
+        input.read_exact(&mut header).map_err(|e| ParseError::InvalidBinary(format!("missing header: {}", e)))?;
+        if &header[..4] != MAGIC {
+            return Err(ParseError::InvalidBinary("not a parsed output file".to_string()));
+        }
+        let format = BinaryFormat::from_tag(header[4])
+            .ok_or_else(|| ParseError::InvalidBinary(format!("unknown format byte {}", header[4])))?;
+        Ok(BinaryReader { input, format })
+    }
+
+    pub fn format(&self) -> BinaryFormat {
+        self.format
+    }
+
+    fn next_record(&mut self) -> Result<Option<ParsedRecord>, ParseError> {
+        let mut kind = [0u8; 1];
+        match self.input.read(&mut kind) {
+            Ok(0) => return Ok(None),
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::Interrupted => return self.next_record(),
+            Err(e) => return Err(ParseError::InvalidBinary(format!("read failed: {}", e))),
+        }
+        let mut len = [0u8; 4];
+        self.input.read_exact(&mut len).map_err(|e| ParseError::InvalidBinary(format!("truncated record: {}", e)))?;
+        let mut bytes = vec![0u8; u32::from_le_bytes(len) as usize];
+        self.input.read_exact(&mut bytes).map_err(|e| ParseError::InvalidBinary(format!("truncated record: {}", e)))?;
+        ParsedRecord::decode(kind[0], &bytes, self.format).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for BinaryReader<R> {
+    type Item = Result<ParsedRecord, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn as_json(record: &ParsedRecord) -> Value {
+        match record {
+            ParsedRecord::Block(block) => serde_json::to_value(block).unwrap(),
+            ParsedRecord::Transaction(tx) => serde_json::to_value(tx).unwrap(),
+            ParsedRecord::ChainEvent(event) => serde_json::to_value(event).unwrap(),
+        }
+    }
+
+    #[test]
+    fn records_round_trip_in_both_formats() {
+        let block = fixtures::block();
+        let records = [
+            ParsedRecord::Block(Box::new(block.clone())),
+            ParsedRecord::Transaction(Box::new(block.transactions[3].clone())),
+            ParsedRecord::ChainEvent(ChainEvent::Finalized { slot: block.slot, blockhash: block.blockhash.clone() }),
+        ];
+        let json = serde_json::to_vec(&block).unwrap();
+
+        for format in [BinaryFormat::Bincode, BinaryFormat::Borsh] {
+            let mut writer = BinaryWriter::new(Vec::new(), format).unwrap();
+            for record in &records {
+                writer.write(record).unwrap();
+            }
+            let bytes = writer.into_inner();
+            assert!(bytes.len() < json.len() / 2, "{}: {} bytes", format.as_str(), bytes.len());
+
+            let reader = BinaryReader::new(bytes.as_slice()).unwrap();
+            assert_eq!(reader.format(), format);
+            let read: Vec<ParsedRecord> = reader.collect::<Result<_, _>>().unwrap();
+            assert_eq!(read.len(), records.len());
+            for (read, record) in read.iter().zip(&records) {
+                assert_eq!(as_json(read), as_json(record));
+            }
+
+            let truncated = BinaryReader::new(&bytes[..bytes.len() - 1]).unwrap();
+            assert!(truncated.last().unwrap().is_err());
+        }
+        assert!(BinaryReader::new(json.as_slice()).is_err());
+    }
+}
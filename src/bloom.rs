@@ -0,0 +1,90 @@
+use crate::pubkey::Pubkey;
+
+// ==========================================
+// ADDRESS BLOOM FILTER
+// ==========================================
+// A set of addresses tested by their base58 text, so strings read straight
+// out of raw JSON can be checked without decoding or allocating. There are
+// no false negatives; about one string in FALSE_POSITIVE_RATE⁻¹ that isn't
+// in the set passes anyway, which only costs a transaction the parse it
+// would have had without the filter.
+
+const FALSE_POSITIVE_RATE: f64 = 1e-4;
+// Beyond this many hash functions a lookup costs more than it saves.
+const MAX_HASHES: f64 = 16.0;
+// Double hashing needs room to spread out: sized for a handful of
+// addresses, the table would only be a couple of words.
+const MIN_WORDS: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressBloom {
+    bits: Vec<u64>,
+    hashes: u64,
+}
+
+impl AddressBloom {
+    pub fn new<'a>(addresses: impl IntoIterator<Item = &'a Pubkey>) -> Self {
+        let encoded: Vec<String> = addresses.into_iter().map(Pubkey::to_string).collect();
+        let count = encoded.len().max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-count * FALSE_POSITIVE_RATE.ln() / (ln2 * ln2)).ceil() as usize;
+        let words = bits.div_ceil(64).max(MIN_WORDS);
+        let hashes = ((words * 64) as f64 / count * ln2).round().clamp(1.0, MAX_HASHES) as u64;
+        let mut bloom = AddressBloom { bits: vec![0; words], hashes };
+        for address in &encoded {
+            let positions: Vec<usize> = bloom.positions(address).collect();
+            for bit in positions {
+                bloom.bits[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+        bloom
+    }
+
+    pub fn contains(&self, address: &Pubkey) -> bool {
+        self.may_contain(&address.to_string())
+    }
+
+    // `base58` is an address as the RPC writes it.
+    pub fn may_contain(&self, base58: &str) -> bool {
+        self.positions(base58).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    // Double hashing: the i-th position is h1 + i * h2.
+    fn positions(&self, text: &str) -> impl Iterator<Item = usize> {
+        let h1 = mix(fnv1a(text.as_bytes()));
+        let h2 = mix(h1) | 1;
+        let len = (self.bits.len() * 64) as u64;
+        (0..self.hashes).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+// The splitmix64 finalizer: spreads FNV's weak low bits, and gives a second
+// hash independent of the first.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holds_every_address_and_few_others() {
+        let targets: Vec<Pubkey> = (1..=5).map(|byte| Pubkey::new([byte; 32])).collect();
+        let bloom = AddressBloom::new(&targets);
+        assert!(targets.iter().all(|address| bloom.contains(address)));
+        let others = (0..10_000u32).filter(|i| {
+            let mut bytes = [0xaa; 32];
+            bytes[..4].copy_from_slice(&i.to_le_bytes());
+            bloom.contains(&Pubkey::new(bytes))
+        });
+        assert!(others.count() <= 10);
+        assert!(!AddressBloom::new(&[]).may_contain("Vote111111111111111111111111111111111111111"));
+    }
+}
@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+// ==========================================
+// CHECKPOINTS
+// ==========================================
+// Records the last slot a long-running ingestion has fully handled, so a
+// restarted run can pick up right after it. "Handled" covers skipped slots
+// too: every slot at or below the checkpoint has either been written to the
+// outputs or does not exist. The file is replaced atomically (write to a
+// sibling temp file, then rename), so a crash mid-save leaves the previous
+// checkpoint intact.
+//
+// address-history walks signatures rather than slots, newest first; its
+// checkpoint also holds the last handled signature, the cursor to continue
+// from.
+
+#[derive(Serialize, Deserialize)]
+struct CheckpointFile {
+    last_slot: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    path: PathBuf,
+}
+
+impl Checkpoint {
+    pub fn new(path: &Path) -> Self {
+        Checkpoint { path: path.to_path_buf() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // None if nothing has been checkpointed yet.
+    pub fn load(&self) -> Result<Option<u64>, Box<dyn Error>> {
+        Ok(self.read()?.map(|file| file.last_slot))
+    }
+
+    // The signature saved with `save_signature`, None if there is none.
+    pub fn load_signature(&self) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(self.read()?.and_then(|file| file.signature))
+    }
+
+    pub fn save(&self, last_slot: u64) -> Result<(), Box<dyn Error>> {
+        self.write(&CheckpointFile { last_slot, signature: None })
+    }
+
+    pub fn save_signature(&self, slot: u64, signature: &str) -> Result<(), Box<dyn Error>> {
+        self.write(&CheckpointFile { last_slot: slot, signature: Some(signature.to_string()) })
+    }
+
+    fn read(&self) -> Result<Option<CheckpointFile>, Box<dyn Error>> {
+        match fs::read_to_string(&self.path) {
+            Ok(contents) => {
+                let file = serde_json::from_str(&contents)
+                    .map_err(|e| format!("invalid checkpoint {}: {}", self.path.display(), e))?;
+                Ok(Some(file))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("failed to read checkpoint {}: {}", self.path.display(), e).into()),
+        }
+    }
+
+    fn write(&self, file: &CheckpointFile) -> Result<(), Box<dyn Error>> {
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        fs::write(&temp, serde_json::to_string(file)? + "\n")?;
+        fs::rename(&temp, &self.path)
+            .map_err(|e| format!("failed to write checkpoint {}: {}", self.path.display(), e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_and_loads_last_slot() {
+        let path = std::env::temp_dir().join(format!("phase-1-checkpoint-{}.json", std::process::id()));
+        let checkpoint = Checkpoint::new(&path);
+        assert_eq!(checkpoint.load().unwrap(), None);
+
+        checkpoint.save(250_000_000).unwrap();
+        checkpoint.save(250_000_007).unwrap();
+        assert_eq!(checkpoint.load().unwrap(), Some(250_000_007));
+        assert_eq!(checkpoint.load_signature().unwrap(), None);
+        checkpoint.save_signature(250_000_009, "5a5bVs8x").unwrap();
+        assert_eq!(checkpoint.load_signature().unwrap().as_deref(), Some("5a5bVs8x"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,1452 @@
+// ==========================================
+// COMMAND LINE PARSING
+// ==========================================
+// Hand-rolled to keep the dependency tree to serde + solana-sdk.
+
+use phase_1_connect::aggregate::RollupPeriod;
+use phase_1_connect::binary::BinaryFormat;
+use phase_1_connect::compress::Compression;
+use phase_1_connect::config::Config;
+use phase_1_connect::dedup::{DuplicatePolicy, DEFAULT_DEDUP_WINDOW};
+use phase_1_connect::decoders::DecoderSpec;
+use phase_1_connect::encoding::DataEncoding;
+use phase_1_connect::export::Projection;
+use phase_1_connect::epoch::{EpochSchedule, DEFAULT_SLOTS_PER_EPOCH};
+use phase_1_connect::filter::{InstructionPattern, TxFilter, TxStatus};
+use phase_1_connect::logging::{level_from_arg, LogFormat};
+use phase_1_connect::logs::LogRetention;
+use phase_1_connect::mev::DEFAULT_MAX_SPAN;
+use phase_1_connect::pubkey::Pubkey;
+use phase_1_connect::rpc::{
+    Commitment, Endpoint, EndpointPool, RateLimiter, RetryPolicy, TransactionDetails, TransactionEncoding, DEFAULT_RPC_URL,
+};
+use phase_1_connect::sink::webhook::{SwapThreshold, WRAPPED_SOL_MINT};
+use phase_1_connect::stats::ProgramRank;
+use phase_1_connect::timestamps::TimestampMode;
+use log::LevelFilter;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage: phase-1-connect [--output json|ndjson|pretty|csv|parquet] <command> [args]
+
+Commands:
+  parse-tx <file>       Parse a saved getTransaction response
+  parse-block <file>    Parse a saved getBlock response. With '-', either
+                        command reads one response (or just its result)
+                        from stdin and parses it as a block or transaction,
+                        whichever it is
+  parse-dir <dir>       Parse every saved getBlock response (*.json) in a
+                        directory on --threads workers, written in file
+                        order; files named <slot>.json take their slot from
+                        the name. Logs progress; files that fail are
+                        reported at the end without stopping the run
+  parse <source>        Parse every block a source yields: '-' for getBlock
+                        responses piped on stdin, a file or directory of
+                        them, <start>..[end] for a slot range over RPC (no
+                        end follows the tip), ws for blockSubscribe or
+                        geyser for the --geyser-url block stream
+  validate <source>     Parse every block from a source as parse does, writing
+                        nothing, and report blocks that couldn't be read or
+                        parsed, parser warnings, programs without a decoder
+                        and anomalies (schema violations, blocks that don't
+                        chain onto the one before); exits non-zero on
+                        failures or anomalies
+  read-parsed <file>    Load blocks written with --output bincode or borsh
+                        ('-' reads stdin) and write them to the outputs
+  fetch-block <slot>    Fetch a block over JSON-RPC and parse it
+  parse-sig <signature> Fetch a transaction with getTransaction and parse it
+  address-history <pubkey>
+                        Fetch and parse every transaction touching an
+                        address, newest first, paging through
+                        getSignaturesForAddress (--before, --until, --limit)
+  stream <start> [end]  Fetch and parse every block in a slot range
+  backfill <start> <end>
+                        Fetch and parse a slot range with --concurrency
+                        workers, each taking a contiguous share; blocks are
+                        written as they finish, not in slot order. Logs
+                        progress and ends with a report of skipped and failed
+                        slots
+  subscribe blocks [account]
+                        Parse blocks as they are produced (blockSubscribe),
+                        optionally only those mentioning an account/program
+  subscribe logs [account]
+                        Fetch and parse each transaction reported by
+                        logsSubscribe, optionally filtered by account/program.
+                        With --geyser, full transactions come from the
+                        Geyser stream instead
+  diff <a> <b>          Compare two blocks (saved getBlock responses or
+                        slots fetched over RPC): transactions missing on
+                        either side or differing, ordering, and rewards
+  wallet-report <pubkey> <file|dir>...
+  wallet-report <pubkey> <start> <end>
+                        Everything the given blocks (saved getBlock
+                        responses, directories of them, or a slot range
+                        fetched over RPC) show about an address: SOL and
+                        token changes, token transfers, fees paid, programs
+                        used, and the first and last slot it was seen in
+  top-programs <file|dir>...
+  top-programs <start> <end>
+                        Rank the programs called in the given blocks by
+                        invocations (top-level and CPI), compute units or
+                        the fees of the transactions calling them, with
+                        labels (--rank-by, --top)
+  sandwiches <file|dir>...
+  sandwiches <start> <end>
+                        Flag candidate sandwiches in the given blocks: a
+                        trader's swap on a pool, other traders swapping the
+                        same way on it, then the first trader swapping back,
+                        within a few transactions (--max-span)
+  serve <addr>          Serve the parser over HTTP on --threads workers:
+                        POST /parse/transaction and /parse/block (saved
+                        getTransaction / getBlock responses, ?slot=<n>) and
+                        GET /block/<slot> (fetched over RPC), answering
+                        with the parsed JSON; with --sqlite or --postgres,
+                        also POST /graphql over the blocks stored there
+  schema [block|transaction|arrow]
+                        Print the JSON Schema of the parsed block (default)
+                        or transaction output, or with arrow the Arrow
+                        schemas of the parquet files, for generating
+                        consumer types
+  help                  Print this message
+
+Options:
+  --config <file>       Load settings from a TOML file; flags given on the
+                        command line take precedence (list flags such as
+                        --program replace the file's list, and switches the
+                        file turns on are turned off with --no-<switch>,
+                        e.g. --no-strict)
+  -o, --output <fmt>    Output format: pretty (default), json, ndjson, csv,
+                        parquet, or bincode or borsh records of whole blocks
+                        for read-parsed (to --out-file, or stdout), or
+                        account-events: ndjson with a line per account per
+                        transaction (role, lamport and token deltas)
+      --format <fmt>    Alias for --output
+  --out-dir <dir>       Directory for csv/parquet output (default: .)
+  --out-file <file>     File for ndjson, bincode or borsh output (default:
+                        stdout)
+  --compress <codec>    Compress file output with gzip or zstd (ndjson, bincode
+                        and borsh with --out-file, csv, and parse-dir
+                        --per-block); csv and
+                        --per-block files get a .gz/.zst suffix. Compressed
+                        input is always detected and read as is
+  --fields <list>       With json or ndjson: write only these transaction
+                        fields, comma-separated; dotted paths pick fields
+                        inside objects and arrays, e.g.
+                        signature,fee,instructions.program_id
+  --rpc-url <url>       JSON-RPC endpoint (default: mainnet-beta)
+  --ws-url <url>        Pubsub endpoint (default: derived from --rpc-url;
+                        needed for https endpoints, as wss:// isn't supported)
+  --geyser <url>        Subscribe through a Yellowstone gRPC endpoint instead
+                        of websocket pubsub
+  --x-token <token>     Auth token for --geyser
+  --commitment <level>  processed, confirmed or finalized (default);
+                        processed only for --geyser, as getBlock,
+                        getTransaction and blockSubscribe refuse it
+  --max-tx-version <v>  Newest transaction version to request: a number
+                        (default: 0) or legacy
+  --encoding <enc>      Transaction encoding to request: json (default),
+                        jsonParsed or base64 (the default with --verify)
+  --transaction-details <level>
+                        What getBlock returns per transaction: full
+                        (default), accounts (keys and balances, no
+                        instructions or logs), signatures (kept in the
+                        block's signatures) or none
+  --no-rewards          Request blocks without rewards
+  --max-retries <n>     Retries for failed, rate-limited (429) or not yet
+                        available RPC requests (default: 3)
+  --retry-backoff <s>   First retry delay, doubling up to 10s (default: 0.5)
+  --rate-limit <n>      Maximum RPC requests per second
+  --rpc-endpoint <url>[,weight=<n>][,rate-limit=<n>]
+                        Send RPC requests to a pool of endpoints instead of
+                        --rpc-url (repeatable): round-robin by weight, each
+                        within its own rate limit, failing over from an
+                        endpoint that errors until it passes a health check.
+                        Fetched blocks record the endpoint that served them
+  --diff-rpc-url <url>  With diff: endpoint for the second block, to compare
+                        two providers (default: --rpc-url)
+  --consensus-rpc-url <url>
+                        With fetch-block: also fetch the block from this
+                        endpoint (repeatable) and output the majority view,
+                        warning about endpoints with missing, extra or
+                        differing transactions
+  --follow              With stream: keep following the tip past <end>
+  --concurrency <n>     With stream: fetch up to n blocks at once ahead of the
+                        parser, still writing them in slot order. With
+                        backfill: the number of workers. With
+                        address-history: transactions fetched at once
+                        (default: 1)
+  --before <signature>  With address-history: start with the transaction
+                        before this one
+  --until <signature>   With address-history: stop at this transaction
+                        (e.g. the newest one a previous run wrote)
+  --limit <n>           With address-history: stop after n transactions
+  --reorder-window <n>  With subscribe blocks (no account filter): hold
+                        blocks arriving early until the slots before them
+                        are in, fetching a missed slot over RPC once a block
+                        n slots past it has arrived, so blocks are written
+                        in slot order without gaps (default: 4)
+  --confirmation-depth <n>
+                        With stream and subscribe blocks below finalized
+                        commitment: once a block is n slots deep, check it
+                        against the finalized chain and write a finalized
+                        event, or a retracted event if it was orphaned (a
+                        block skipped over by a later block's parent is
+                        retracted at once). Events go to json/ndjson
+                        output, the databases and the Kafka block topic
+  --gap-report <file>   With backfill: write the skipped and failed slots to
+                        this file as JSON; with parse-dir, the files that
+                        failed and why
+  --leader-stats <file> Write per-leader totals (blocks, transactions, votes,
+                        fees) for the run to this file as JSON; pretty
+                        output prints them after the last of several blocks
+  --rank-by <key>       With top-programs: invocations (default), compute or
+                        fees
+  --top <n>             With top-programs: list only the first n (default: 20)
+  --max-span <n>        With sandwiches: the most transactions from front-run
+                        to back-run (default: 8)
+  --rollup <file>       Write hourly (or --rollup-period day) totals for the
+                        run to this file: blocks, transactions, fees,
+                        unique fee payers and per-program activity. JSON,
+                        or CSV for a .csv file plus <name>.programs.csv for
+                        the programs. With stream and subscribe the file is
+                        rewritten as each period closes
+  --rollup-period <p>   hour (default) or day
+  --memory-budget <size>
+                        Keep --rollup's per-period fee payer sets, which
+                        grow without bound over a long backfill or
+                        parse-dir run, within about this much memory (e.g.
+                        512M, 2G): past it they are written out to sorted
+                        files and merged when the rollups are written. Only
+                        bounds those sets: the rollups' per-program totals,
+                        leader stats, --dedup's window and other reports
+                        still grow in memory, as does the process as a
+                        whole. Needs --rollup
+  --spill-dir <dir>     Where --memory-budget writes its files (default: the
+                        system temp directory); removed at the end of the run.
+                        Needs --rollup
+  --summary <file>      Write a JSON summary of the run to this file when it
+                        ends, including on failure or Ctrl-C: outcome,
+                        blocks and transactions, errors by category,
+                        throughput, per-sink write stats and the slots and
+                        block times covered
+  --checkpoint <file>   With stream: record the last handled slot after each
+                        block. A slot that still fails after retries stops
+                        the run instead of being skipped. With
+                        address-history: the last handled signature
+  --resume              With stream: start after the slot in --checkpoint
+                        (address-history: the signature),
+                        appending to existing ndjson/csv/binary output
+  --manifest <file>     List each block written to the --output format in
+                        this file and skip blocks already listed, appending
+                        to existing ndjson/csv/binary output, so a retried
+                        backfill writes every block once (the databases
+                        upsert)
+  --dedup <policy>      Watch for transactions already seen in this run
+                        (overlapping ranges, a switched RPC): skip leaves
+                        them, and blocks seen whole, out of the output; flag
+                        writes them with a warning. Counted at the end of
+                        the run and in the metrics
+  --dedup-window <n>    Signatures --dedup remembers (default: 1000000)
+  --metrics-addr <addr> With stream and subscribe: serve Prometheus metrics
+                        at http://<addr>/metrics (e.g. 0.0.0.0:9100)
+  --log-level <level>   Diagnostics on stderr: off, error, warn, info
+                        (default), debug (adds block parse and RPC timings)
+                        or trace (adds per-transaction timings)
+  --log-format <fmt>    text (default) or json, one object per line
+  --threads <n>         Parser worker threads (default: one per core)
+  --per-block           With parse-dir: write each block to its own output
+                        in --out-dir (<name>.json, <name>.ndjson, or a
+                        <name>/ directory for csv and parquet)
+  --logs <mode>         log_messages in the output, for every format and sink:
+                        keep (default), drop, compact (without the per-program
+                        compute unit lines) or a number of lines to keep
+  --data-encoding <enc> Instruction data output: base58 (default), base64, hex
+  --input-data-encoding <enc>
+                        Instruction data encoding in the input (default:
+                        base58, as returned for encoding: json)
+  --slots-per-epoch <n> Epoch length used for epoch/slot_index (default: 432000)
+  --epoch-warmup        The cluster uses epoch warmup (short early epochs)
+  --idl <file>          Decode an Anchor program's instructions and events
+                        with its IDL (repeatable)
+  --decoders <file>     Decode instructions with the declarative decoders in
+                        this file: [[decoders]] tables giving a program ID
+                        and, per instruction, a discriminator, account names
+                        and borsh field names and types, as in the config
+                        file (repeatable; JSON for a .json file)
+  --labels              Attach labels for well-known programs and accounts
+                        (exchanges, DEXes) to each parsed transaction
+  --labels-file <file>  Extra address labels, a JSON object or TOML table of
+                        address = label; implies --labels and is used by
+                        pretty summaries too
+  --token-list <file>   Extra token symbols and decimals, a token-list JSON
+                        file, used by wallet-report on top of the built-in
+                        list of major mints
+  --program <pubkey>    Only keep transactions invoking this program,
+                        directly or via CPI (repeatable)
+  --instruction <program>[:<prefix>]
+                        Only keep transactions with an instruction of this
+                        program (top-level or CPI) whose data starts with the
+                        prefix, 0x-prefixed hex or base58, e.g. an Anchor
+                        discriminator (repeatable)
+  --account <pubkey>    Only keep transactions touching this account
+                        (repeatable)
+  --prefilter           Check each block transaction's raw JSON for the
+                        --account addresses before parsing it; faster when
+                        they're in few of the transactions
+  --status <status>     Only keep success or failed transactions
+  --min-fee <lamports>  Only keep transactions paying at least this fee
+  --min-cu <units>      Only keep transactions consuming at least this many
+                        compute units
+  --exclude-votes       Drop Vote program transactions (counted in the
+                        block summary)
+  --strict              Fail on malformed transactions and out-of-range
+                        account indices instead of warning
+  --verify              Verify each transaction's signatures against its
+                        message and flag those that don't validate (blocks
+                        and transactions are fetched with encoding: base64)
+  --fetch-lookup-tables Resolve v0 lookup tables over RPC when the
+                        response has no loadedAddresses
+  --lookup-table-cache <file>
+                        Keep fetched lookup tables in this file for later
+                        runs (implies --fetch-lookup-tables)
+  --fetch-leaders       Look up the leader of fetched blocks without a fee
+                        reward with getSlotLeaders (recent epochs only)
+  --snapshot-account <pubkey>
+                        After each fetched or streamed block that writes
+                        this account, read its state back with
+                        getMultipleAccounts into account_snapshots
+                        (repeatable)
+  --fetch-token-decimals
+                        With wallet-report: read the decimals of mints the
+                        token lists don't cover from the mint account
+  --fetch-token-owners  Read the owner of token accounts a transfer uses that
+                        the transaction's token balances don't name (older
+                        blocks, accounts closed in the transaction) with
+                        getAccountInfo, into token_owners; the accounts are
+                        read as they are now
+  --postgres <url>      Also persist parsed blocks to Postgres through psql
+                        ('-' writes the SQL to stdout instead, which needs
+                        csv or parquet output, or --out-file)
+  --sqlite <path>       Also persist parsed blocks to a SQLite database file
+                        through sqlite3 ('-' writes the SQL to stdout, as
+                        for --postgres)
+  --clickhouse <url>    Also persist parsed blocks to ClickHouse over HTTP
+                        (e.g. http://localhost:8123)
+  --kafka <url>         Also publish parsed transactions through a Kafka REST
+                        Proxy (e.g. http://localhost:8082)
+  --kafka-topic <topic> Topic for transactions (default: solana.transactions)
+  --kafka-block-topic <topic>
+                        Also publish block headers to this topic
+  --webhook <url>       POST an alert for each matching transaction (Slack
+                        and Discord webhook URLs get a chat message); needs
+                        at least one of the three options below
+  --webhook-program <pubkey>
+                        Only alert on transactions invoking this program
+                        (repeatable)
+  --webhook-account <pubkey>
+                        Only alert on transactions touching this account
+                        (repeatable)
+  --webhook-min-swap <amount>
+                        Only alert on swaps moving at least this many whole
+                        tokens of the swap mint
+  --webhook-swap-mint <mint>
+                        Mint for --webhook-min-swap (default: SOL)
+  --batch-size <n>      Rows per sink insert batch
+  --flush-interval <s>  Seconds between ClickHouse (default: 5) and Kafka
+                        (default: 1) flushes
+  --timestamps [<sink>=]<mode>
+                        Give each transaction a timestamp_ms from its block's
+                        blockTime: none (default), block-time, or
+                        interpolated (spread across the 400ms slot by
+                        position in the block). With a sink (output,
+                        postgres, sqlite, clickhouse, kafka, webhook) only
+                        for that one, otherwise for all (repeatable, later
+                        ones win)";
+
+// Names --timestamps takes, "output" being the --output format.
+pub const TIMESTAMP_SINKS: &[&str] = &["output", "postgres", "sqlite", "clickhouse", "kafka", "webhook"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    Ndjson,
+    Pretty,
+    Csv,
+    Parquet,
+    Binary(BinaryFormat),
+    // NDJSON with a line per (account, transaction) rather than per transaction.
+    AccountEvents,
+}
+
+impl OutputFormat {
+    fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "pretty" => Ok(OutputFormat::Pretty),
+            "csv" => Ok(OutputFormat::Csv),
+            "parquet" => Ok(OutputFormat::Parquet),
+            "bincode" | "borsh" => Ok(OutputFormat::Binary(BinaryFormat::from_arg(value)?)),
+            "account-events" => Ok(OutputFormat::AccountEvents),
+            other => Err(format!("unknown output format '{}' (expected json, ndjson, pretty, csv, parquet, bincode, borsh or account-events)", other)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Command {
+    ParseTx { path: String },
+    ParseBlock { path: String },
+    ReadParsed { path: String },
+    ParseDir { dir: String },
+    Parse { input: InputSource },
+    Validate { input: InputSource },
+    FetchBlock { slot: u64 },
+    ParseSig { signature: String },
+    AddressHistory { address: Pubkey },
+    Stream { start: u64, end: Option<u64> },
+    Backfill { start: u64, end: u64 },
+    Subscribe { kind: SubscriptionKind, mentions: Option<String> },
+    Diff { left: BlockSource, right: BlockSource },
+    WalletReport { address: Pubkey, blocks: BlockInputs },
+    TopPrograms { blocks: BlockInputs },
+    Sandwiches { blocks: BlockInputs },
+    Serve { addr: String },
+    Schema { kind: SchemaKind },
+    Help,
+}
+
+impl Command {
+    // As typed on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::ParseTx { .. } => "parse-tx",
+            Command::ParseBlock { .. } => "parse-block",
+            Command::ReadParsed { .. } => "read-parsed",
+            Command::ParseDir { .. } => "parse-dir",
+            Command::Parse { .. } => "parse",
+            Command::Validate { .. } => "validate",
+            Command::FetchBlock { .. } => "fetch-block",
+            Command::ParseSig { .. } => "parse-sig",
+            Command::AddressHistory { .. } => "address-history",
+            Command::Stream { .. } => "stream",
+            Command::Backfill { .. } => "backfill",
+            Command::Subscribe { .. } => "subscribe",
+            Command::Diff { .. } => "diff",
+            Command::WalletReport { .. } => "wallet-report",
+            Command::TopPrograms { .. } => "top-programs",
+            Command::Sandwiches { .. } => "sandwiches",
+            Command::Serve { .. } => "serve",
+            Command::Schema { .. } => "schema",
+            Command::Help => "help",
+        }
+    }
+
+    // Whether the command reads blocks or transactions through getBlock,
+    // getTransaction or blockSubscribe, none of which serve processed
+    // commitment. Yellowstone does.
+    fn fetches_blocks(&self, geyser: bool) -> bool {
+        match self {
+            Command::FetchBlock { .. }
+            | Command::ParseSig { .. }
+            | Command::AddressHistory { .. }
+            | Command::Stream { .. }
+            | Command::Backfill { .. }
+            | Command::Serve { .. } => true,
+            Command::Subscribe { .. } => !geyser,
+            Command::Parse { input } | Command::Validate { input } => matches!(input, InputSource::Slots { .. } | InputSource::WebSocket),
+            Command::Diff { left, right } => [left, right].iter().any(|source| matches!(source, BlockSource::Slot(_))),
+            Command::WalletReport { blocks, .. } | Command::TopPrograms { blocks } | Command::Sandwiches { blocks } => {
+                matches!(blocks, BlockInputs::Slots { .. })
+            }
+            _ => false,
+        }
+    }
+}
+
+// What wallet-report, top-programs and sandwiches scan: block files and directories of
+// them, or a slot range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockInputs {
+    Files(Vec<String>),
+    Slots { start: u64, end: u64 },
+}
+
+// A diff argument: a saved getBlock response, or a slot to fetch when no
+// file by that name exists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockSource {
+    File(String),
+    Slot(u64),
+}
+
+impl BlockSource {
+    fn from_arg(value: String) -> Self {
+        match value.parse() {
+            Ok(slot) if !Path::new(&value).exists() => BlockSource::Slot(slot),
+            _ => BlockSource::File(value),
+        }
+    }
+}
+
+// Where `parse` reads blocks from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputSource {
+    Stdin,
+    // A file or a directory of files.
+    Path(String),
+    Slots { start: u64, end: Option<u64> },
+    WebSocket,
+    Geyser,
+}
+
+impl InputSource {
+    fn from_arg(value: String) -> Result<Self, String> {
+        if Path::new(&value).exists() {
+            return Ok(InputSource::Path(value));
+        }
+        match value.as_str() {
+            "-" => return Ok(InputSource::Stdin),
+            "ws" => return Ok(InputSource::WebSocket),
+            "geyser" => return Ok(InputSource::Geyser),
+            _ => {}
+        }
+        let Some((start, end)) = value.split_once("..") else {
+            return Err(format!("no such file or directory '{}'", value));
+        };
+        let start = parse_slot(start.to_string())?;
+        let end = match end {
+            "" => None,
+            end => Some(parse_slot(end.to_string())?),
+        };
+        if let Some(end) = end.filter(|&end| end < start) {
+            return Err(format!("parse end slot {} is before start slot {}", end, start));
+        }
+        Ok(InputSource::Slots { start, end })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionKind {
+    Blocks,
+    Logs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaKind {
+    Block,
+    Transaction,
+    Arrow,
+}
+
+#[derive(Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub output: OutputFormat,
+    pub rpc_url: String,
+    pub diff_rpc_url: Option<String>,
+    // Endpoints besides rpc_url for fetch-block to compare against.
+    pub consensus_rpc_urls: Vec<String>,
+    pub ws_url: Option<String>,
+    pub geyser_url: Option<String>,
+    pub x_token: Option<String>,
+    pub commitment: Commitment,
+    // None requests legacy transactions only.
+    pub max_transaction_version: Option<u8>,
+    // None for json, or base64 with --verify.
+    pub encoding: Option<TransactionEncoding>,
+    pub transaction_details: TransactionDetails,
+    pub rewards: bool,
+    pub retry: RetryPolicy,
+    // None when no --rate-limit was given.
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    // None when no --rpc-endpoint was given.
+    pub endpoint_pool: Option<Arc<EndpointPool>>,
+    pub fetch_lookup_tables: bool,
+    pub lookup_table_cache: Option<PathBuf>,
+    pub fetch_leaders: bool,
+    // Accounts read back with getMultipleAccounts after each block that
+    // writes them.
+    pub snapshot_accounts: Vec<Pubkey>,
+    pub threads: usize,
+    // parse-dir writes one output per input file instead of a combined one.
+    pub per_block: bool,
+    pub strict: bool,
+    pub verify_signatures: bool,
+    pub data_encoding: DataEncoding,
+    pub log_retention: LogRetention,
+    pub input_data_encoding: DataEncoding,
+    pub idl_paths: Vec<String>,
+    pub decoder_files: Vec<String>,
+    // Declarative decoders from the config file's [[decoders]].
+    pub decoders: Vec<DecoderSpec>,
+    pub labels: bool,
+    pub labels_file: Option<PathBuf>,
+    pub token_list: Option<PathBuf>,
+    pub fetch_token_decimals: bool,
+    pub fetch_token_owners: bool,
+    pub epoch_schedule: EpochSchedule,
+    // None when no filter flags were given.
+    pub filter: Option<TxFilter>,
+    pub out_dir: PathBuf,
+    pub out_file: Option<PathBuf>,
+    pub compression: Compression,
+    // Transaction fields kept in json/ndjson output, None for all.
+    pub fields: Option<Projection>,
+    pub manifest: Option<PathBuf>,
+    pub dedup: Option<DuplicatePolicy>,
+    pub dedup_window: usize,
+    // Per TIMESTAMP_SINKS name; missing ones get none.
+    pub timestamps: BTreeMap<&'static str, TimestampMode>,
+    pub checkpoint: Option<PathBuf>,
+    pub concurrency: usize,
+    pub reorder_window: u64,
+    pub confirmation_depth: Option<u64>,
+    pub gap_report: Option<PathBuf>,
+    pub leader_stats: Option<PathBuf>,
+    pub rollup: Option<PathBuf>,
+    pub rollup_period: RollupPeriod,
+    pub summary: Option<PathBuf>,
+    // --rollup fee payer sets beyond this many bytes go to spill_dir.
+    pub memory_budget: Option<usize>,
+    pub spill_dir: Option<PathBuf>,
+    pub rank_by: ProgramRank,
+    pub top: usize,
+    pub max_span: usize,
+    // address-history paging.
+    pub before: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<usize>,
+    pub metrics_addr: Option<String>,
+    pub log_level: LevelFilter,
+    pub log_format: LogFormat,
+    pub resume: bool,
+    pub postgres_url: Option<String>,
+    pub sqlite_path: Option<String>,
+    pub clickhouse_url: Option<String>,
+    pub kafka_url: Option<String>,
+    pub kafka_topic: Option<String>,
+    pub kafka_block_topic: Option<String>,
+    pub webhook_url: Option<String>,
+    // The webhook's own filter, independent of --program/--account.
+    pub webhook_filter: TxFilter,
+    pub webhook_min_swap: Option<SwapThreshold>,
+    pub batch_size: Option<usize>,
+    pub flush_interval: Option<Duration>,
+}
+
+impl Cli {
+    pub fn parse<I>(args: I) -> Result<Cli, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let args: Vec<String> = args.into_iter().collect();
+        // The config file supplies the defaults, so it's loaded before the
+        // remaining flags are applied.
+        let config = match args.iter().position(|arg| arg == "--config") {
+            Some(pos) => {
+                let path = args.get(pos + 1).ok_or("--config requires a value")?;
+                Config::load(Path::new(path)).map_err(|e| e.to_string())?
+            }
+            None => Config::default(),
+        };
+        let (rpc, geyser, out, filter, parser, stream, sinks, log) =
+            (config.rpc, config.geyser, config.output, config.filter, config.parser, config.stream, config.sinks, config.log);
+        let decoders = config.decoders;
+
+        let mut output = out.format.as_deref().map(OutputFormat::from_arg).transpose()?.unwrap_or(OutputFormat::Pretty);
+        let mut rpc_url = rpc.url.unwrap_or_else(|| DEFAULT_RPC_URL.to_string());
+        let mut diff_rpc_url = None;
+        let mut consensus_rpc_urls = Vec::new();
+        let mut ws_url = rpc.ws_url;
+        let mut geyser_url = geyser.url;
+        let mut x_token = geyser.x_token;
+        let mut commitment = rpc.commitment.as_deref().map(Commitment::from_arg).transpose()?.unwrap_or(Commitment::Finalized);
+        let mut max_transaction_version = rpc.max_transaction_version.as_deref().map(parse_max_version).transpose()?.unwrap_or(Some(0));
+        let mut encoding = rpc.encoding.as_deref().map(TransactionEncoding::from_arg).transpose()?;
+        let mut transaction_details =
+            rpc.transaction_details.as_deref().map(TransactionDetails::from_arg).transpose()?.unwrap_or(TransactionDetails::Full);
+        let mut rewards = rpc.rewards.unwrap_or(true);
+        let mut retry = RetryPolicy::default();
+        if let Some(max_retries) = rpc.max_retries {
+            retry.max_retries = max_retries;
+        }
+        if let Some(secs) = rpc.retry_backoff {
+            retry.initial_backoff = Duration::try_from_secs_f64(secs).map_err(|_| format!("invalid retry backoff '{}'", secs))?;
+        }
+        let mut rate_limiter = rpc.rate_limit.map(new_rate_limiter).transpose()?;
+        let mut endpoints = Vec::new();
+        let mut fetch_lookup_tables = rpc.fetch_lookup_tables;
+        let mut lookup_table_cache = rpc.lookup_table_cache;
+        let mut fetch_leaders = rpc.fetch_leaders;
+        let mut snapshot_accounts = Vec::new();
+        let mut follow = false;
+        let mut threads = parser.threads.unwrap_or(0);
+        let mut strict = parser.strict;
+        let mut verify_signatures = parser.verify_signatures;
+        let mut data_encoding = parser.data_encoding.as_deref().map(DataEncoding::from_arg).transpose()?.unwrap_or(DataEncoding::Base58);
+        let mut log_retention = parser.logs.as_deref().map(LogRetention::from_arg).transpose()?.unwrap_or_default();
+        let mut input_data_encoding =
+            parser.input_data_encoding.as_deref().map(DataEncoding::from_arg).transpose()?.unwrap_or(DataEncoding::Base58);
+        let mut idl_paths = Vec::new();
+        let mut decoder_files = Vec::new();
+        let mut labels = parser.labels;
+        let mut labels_file = parser.labels_file;
+        let mut token_list = parser.token_list;
+        let mut fetch_token_decimals = rpc.fetch_token_decimals;
+        let mut fetch_token_owners = rpc.fetch_token_owners;
+        let mut slots_per_epoch = parser.slots_per_epoch.unwrap_or(DEFAULT_SLOTS_PER_EPOCH);
+        let mut epoch_warmup = parser.epoch_warmup;
+        let mut programs = Vec::new();
+        let mut instructions = Vec::new();
+        let mut accounts = Vec::new();
+        let mut status = filter.status.as_deref().map(TxStatus::from_arg).transpose()?;
+        let mut min_fee = filter.min_fee;
+        let mut min_compute_units = filter.min_compute_units;
+        let mut exclude_votes = filter.exclude_votes;
+        let mut prefilter = filter.prefilter;
+        let mut out_dir = out.dir.unwrap_or_else(|| PathBuf::from("."));
+        let mut out_file = out.file;
+        let mut compression = out.compress.as_deref().map(Compression::from_arg).transpose()?.unwrap_or_default();
+        let mut manifest = out.manifest;
+        let mut dedup = out.dedup.as_deref().map(DuplicatePolicy::from_arg).transpose()?;
+        let mut dedup_window = out.dedup_window.unwrap_or(DEFAULT_DEDUP_WINDOW);
+        let mut fields = if out.fields.is_empty() { None } else { Some(Projection::parse(&out.fields.join(","))?) };
+        let mut checkpoint = stream.checkpoint;
+        let mut concurrency = stream.concurrency.unwrap_or(1);
+        let mut reorder_window = stream.reorder_window.unwrap_or(4);
+        let mut confirmation_depth = stream.confirmation_depth;
+        let mut gap_report = None;
+        let mut leader_stats = None;
+        let mut rollup = out.rollup;
+        let mut summary = out.summary;
+        let mut memory_budget = out.memory_budget.as_deref().map(parse_byte_size).transpose()?;
+        let mut spill_dir = out.spill_dir;
+        let mut rank_by = ProgramRank::Invocations;
+        let mut top = 20;
+        let mut max_span = DEFAULT_MAX_SPAN;
+        let mut before = None;
+        let mut until = None;
+        let mut limit = None;
+        let mut rollup_period = out.rollup_period.as_deref().map(RollupPeriod::from_arg).transpose()?.unwrap_or(RollupPeriod::Hour);
+        let mut metrics_addr = stream.metrics_addr;
+        let mut log_level = log.level.as_deref().map(level_from_arg).transpose()?.unwrap_or(LevelFilter::Info);
+        let mut log_format = log.format.as_deref().map(LogFormat::from_arg).transpose()?.unwrap_or(LogFormat::Text);
+        let mut resume = false;
+        let mut per_block = false;
+        // From the config first; --timestamps applies on top, in order.
+        let configured_timestamps = [
+            out.timestamps,
+            sinks.postgres.as_ref().and_then(|sink| sink.timestamps.clone()),
+            sinks.sqlite.as_ref().and_then(|sink| sink.timestamps.clone()),
+            sinks.clickhouse.as_ref().and_then(|sink| sink.timestamps.clone()),
+            sinks.kafka.as_ref().and_then(|sink| sink.timestamps.clone()),
+            sinks.webhook.as_ref().and_then(|sink| sink.timestamps.clone()),
+        ];
+        let mut timestamps = BTreeMap::new();
+        for (sink, mode) in TIMESTAMP_SINKS.iter().zip(configured_timestamps) {
+            if let Some(mode) = mode {
+                timestamps.insert(*sink, TimestampMode::from_arg(&mode)?);
+            }
+        }
+        let mut postgres_url = sinks.postgres.map(|sink| sink.url);
+        let mut sqlite_path = sinks.sqlite.map(|sink| sink.path);
+        let mut clickhouse_url = sinks.clickhouse.map(|sink| sink.url);
+        let (mut kafka_url, mut kafka_topic, mut kafka_block_topic) = match sinks.kafka {
+            Some(kafka) => (Some(kafka.url), kafka.topic, kafka.block_topic),
+            None => (None, None, None),
+        };
+        let (mut webhook_url, mut webhook_programs, mut webhook_accounts, mut webhook_min_swap, mut webhook_swap_mint) = match sinks.webhook {
+            Some(webhook) => (Some(webhook.url), webhook.programs, webhook.accounts, webhook.min_swap, webhook.swap_mint),
+            None => (None, Vec::new(), Vec::new(), None, None),
+        };
+        let (mut cli_webhook_programs, mut cli_webhook_accounts) = (Vec::new(), Vec::new());
+        let mut batch_size = sinks.batch_size;
+        let mut flush_interval = sinks
+            .flush_interval
+            .map(|secs| Duration::try_from_secs_f64(secs).map_err(|_| format!("invalid flush interval '{}'", secs)))
+            .transpose()?;
+        let mut positional: Vec<String> = Vec::new();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => {
+                    args.next();
+                }
+                "-o" | "--output" | "--format" => {
+                    let value = args.next().ok_or("--output requires a value")?;
+                    output = OutputFormat::from_arg(&value)?;
+                }
+                "--rpc-url" => {
+                    rpc_url = args.next().ok_or("--rpc-url requires a value")?;
+                }
+                "--diff-rpc-url" => {
+                    diff_rpc_url = Some(args.next().ok_or("--diff-rpc-url requires a value")?);
+                }
+                "--consensus-rpc-url" => {
+                    consensus_rpc_urls.push(args.next().ok_or("--consensus-rpc-url requires a value")?);
+                }
+                "--ws-url" => {
+                    ws_url = Some(args.next().ok_or("--ws-url requires a value")?);
+                }
+                "--geyser" => {
+                    geyser_url = Some(args.next().ok_or("--geyser requires a value")?);
+                }
+                "--x-token" => {
+                    x_token = Some(args.next().ok_or("--x-token requires a value")?);
+                }
+                "--commitment" => {
+                    let value = args.next().ok_or("--commitment requires a value")?;
+                    commitment = Commitment::from_arg(&value)?;
+                }
+                "--max-tx-version" => {
+                    let value = args.next().ok_or("--max-tx-version requires a value")?;
+                    max_transaction_version = parse_max_version(&value)?;
+                }
+                "--encoding" => encoding = Some(TransactionEncoding::from_arg(&args.next().ok_or("--encoding requires a value")?)?),
+                "--transaction-details" => {
+                    transaction_details = TransactionDetails::from_arg(&args.next().ok_or("--transaction-details requires a value")?)?;
+                }
+                "--no-rewards" => rewards = false,
+                "--max-retries" => {
+                    let value = args.next().ok_or("--max-retries requires a value")?;
+                    retry.max_retries = value.parse().map_err(|_| format!("invalid retry count '{}'", value))?;
+                }
+                "--retry-backoff" => {
+                    let value = args.next().ok_or("--retry-backoff requires a value")?;
+                    let secs: f64 = value.parse().map_err(|_| format!("invalid retry backoff '{}'", value))?;
+                    retry.initial_backoff = Duration::try_from_secs_f64(secs).map_err(|_| format!("invalid retry backoff '{}'", value))?;
+                }
+                "--rate-limit" => {
+                    let value = args.next().ok_or("--rate-limit requires a value")?;
+                    let rate: f64 = value.parse().map_err(|_| format!("invalid rate limit '{}'", value))?;
+                    rate_limiter = Some(new_rate_limiter(rate)?);
+                }
+                "--rpc-endpoint" => {
+                    endpoints.push(Endpoint::from_arg(&args.next().ok_or("--rpc-endpoint requires a value")?)?);
+                }
+                "--fetch-lookup-tables" => fetch_lookup_tables = true,
+                // The cache file would turn fetching back on.
+                "--no-fetch-lookup-tables" => {
+                    fetch_lookup_tables = false;
+                    lookup_table_cache = None;
+                }
+                "--lookup-table-cache" => {
+                    lookup_table_cache = Some(PathBuf::from(args.next().ok_or("--lookup-table-cache requires a value")?));
+                }
+                "--fetch-leaders" => fetch_leaders = true,
+                "--no-fetch-leaders" => fetch_leaders = false,
+                "--snapshot-account" => {
+                    let value = args.next().ok_or("--snapshot-account requires a value")?;
+                    snapshot_accounts.push(value.parse().map_err(|_| format!("invalid account '{}'", value))?);
+                }
+                "--follow" => follow = true,
+                "--strict" => strict = true,
+                "--no-strict" => strict = false,
+                "--verify" => verify_signatures = true,
+                "--no-verify" => verify_signatures = false,
+                "--data-encoding" => {
+                    let value = args.next().ok_or("--data-encoding requires a value")?;
+                    data_encoding = DataEncoding::from_arg(&value)?;
+                }
+                "--logs" => log_retention = LogRetention::from_arg(&args.next().ok_or("--logs requires a value")?)?,
+                "--slots-per-epoch" => {
+                    let value = args.next().ok_or("--slots-per-epoch requires a value")?;
+                    slots_per_epoch = value.parse().map_err(|_| format!("invalid slots per epoch '{}'", value))?;
+                }
+                "--epoch-warmup" => epoch_warmup = true,
+                "--no-epoch-warmup" => epoch_warmup = false,
+                "--idl" => {
+                    idl_paths.push(args.next().ok_or("--idl requires a value")?);
+                }
+                "--decoders" => {
+                    decoder_files.push(args.next().ok_or("--decoders requires a value")?);
+                }
+                "--labels" => labels = true,
+                "--no-labels" => {
+                    labels = false;
+                    labels_file = None;
+                }
+                "--token-list" => {
+                    token_list = Some(PathBuf::from(args.next().ok_or("--token-list requires a value")?));
+                }
+                "--fetch-token-decimals" => fetch_token_decimals = true,
+                "--no-fetch-token-decimals" => fetch_token_decimals = false,
+                "--fetch-token-owners" => fetch_token_owners = true,
+                "--no-fetch-token-owners" => fetch_token_owners = false,
+                "--labels-file" => {
+                    labels_file = Some(PathBuf::from(args.next().ok_or("--labels-file requires a value")?));
+                }
+                "--program" => {
+                    let value = args.next().ok_or("--program requires a value")?;
+                    programs.push(value.parse().map_err(|_| format!("invalid program id '{}'", value))?);
+                }
+                "--instruction" => {
+                    let value = args.next().ok_or("--instruction requires a value")?;
+                    instructions.push(InstructionPattern::from_arg(&value)?);
+                }
+                "--account" => {
+                    let value = args.next().ok_or("--account requires a value")?;
+                    accounts.push(value.parse().map_err(|_| format!("invalid account '{}'", value))?);
+                }
+                "--status" => {
+                    let value = args.next().ok_or("--status requires a value")?;
+                    status = Some(TxStatus::from_arg(&value)?);
+                }
+                "--min-fee" => {
+                    let value = args.next().ok_or("--min-fee requires a value")?;
+                    min_fee = Some(value.parse().map_err(|_| format!("invalid fee '{}'", value))?);
+                }
+                "--min-cu" => {
+                    let value = args.next().ok_or("--min-cu requires a value")?;
+                    min_compute_units = Some(value.parse().map_err(|_| format!("invalid compute units '{}'", value))?);
+                }
+                "--exclude-votes" => exclude_votes = true,
+                "--no-exclude-votes" => exclude_votes = false,
+                "--prefilter" => prefilter = true,
+                "--no-prefilter" => prefilter = false,
+                "--input-data-encoding" => {
+                    let value = args.next().ok_or("--input-data-encoding requires a value")?;
+                    input_data_encoding = DataEncoding::from_arg(&value)?;
+                }
+                "--threads" => {
+                    let value = args.next().ok_or("--threads requires a value")?;
+                    threads = value.parse().map_err(|_| format!("invalid thread count '{}'", value))?;
+                }
+                "-h" | "--help" => positional.insert(0, "help".to_string()),
+                "--out-dir" => {
+                    out_dir = PathBuf::from(args.next().ok_or("--out-dir requires a value")?);
+                }
+                "--out-file" => {
+                    out_file = Some(PathBuf::from(args.next().ok_or("--out-file requires a value")?));
+                }
+                "--checkpoint" => {
+                    checkpoint = Some(PathBuf::from(args.next().ok_or("--checkpoint requires a value")?));
+                }
+                "--gap-report" => {
+                    gap_report = Some(PathBuf::from(args.next().ok_or("--gap-report requires a value")?));
+                }
+                "--leader-stats" => {
+                    leader_stats = Some(PathBuf::from(args.next().ok_or("--leader-stats requires a value")?));
+                }
+                "--rank-by" => {
+                    rank_by = ProgramRank::from_arg(&args.next().ok_or("--rank-by requires a value")?)?;
+                }
+                "--top" => {
+                    let value = args.next().ok_or("--top requires a value")?;
+                    top = value.parse().map_err(|_| format!("invalid count '{}'", value))?;
+                }
+                "--max-span" => {
+                    let value = args.next().ok_or("--max-span requires a value")?;
+                    max_span = value.parse().map_err(|_| format!("invalid count '{}'", value))?;
+                }
+                "--before" => before = Some(parse_signature(args.next().ok_or("--before requires a value")?)?),
+                "--until" => until = Some(parse_signature(args.next().ok_or("--until requires a value")?)?),
+                "--limit" => {
+                    let value = args.next().ok_or("--limit requires a value")?;
+                    limit = Some(value.parse().map_err(|_| format!("invalid count '{}'", value))?);
+                }
+                "--memory-budget" => {
+                    memory_budget = Some(parse_byte_size(&args.next().ok_or("--memory-budget requires a value")?)?);
+                }
+                "--spill-dir" => {
+                    spill_dir = Some(PathBuf::from(args.next().ok_or("--spill-dir requires a value")?));
+                }
+                "--summary" => {
+                    summary = Some(PathBuf::from(args.next().ok_or("--summary requires a value")?));
+                }
+                "--rollup" => {
+                    rollup = Some(PathBuf::from(args.next().ok_or("--rollup requires a value")?));
+                }
+                "--rollup-period" => {
+                    let value = args.next().ok_or("--rollup-period requires a value")?;
+                    rollup_period = RollupPeriod::from_arg(&value)?;
+                }
+                "--concurrency" => {
+                    let value = args.next().ok_or("--concurrency requires a value")?;
+                    concurrency = value.parse().map_err(|_| format!("invalid concurrency '{}'", value))?;
+                }
+                "--reorder-window" => {
+                    let value = args.next().ok_or("--reorder-window requires a value")?;
+                    reorder_window = value.parse().map_err(|_| format!("invalid reorder window '{}'", value))?;
+                }
+                "--confirmation-depth" => {
+                    let value = args.next().ok_or("--confirmation-depth requires a value")?;
+                    confirmation_depth = Some(value.parse().map_err(|_| format!("invalid confirmation depth '{}'", value))?);
+                }
+                "--resume" => resume = true,
+                "--manifest" => {
+                    manifest = Some(PathBuf::from(args.next().ok_or("--manifest requires a value")?));
+                }
+                "--dedup" => dedup = Some(DuplicatePolicy::from_arg(&args.next().ok_or("--dedup requires a value")?)?),
+                "--dedup-window" => {
+                    let value = args.next().ok_or("--dedup-window requires a value")?;
+                    dedup_window = value.parse().map_err(|_| format!("invalid dedup window '{}'", value))?;
+                }
+                "--per-block" => per_block = true,
+                "--fields" => fields = Some(Projection::parse(&args.next().ok_or("--fields requires a value")?)?),
+                "--compress" => {
+                    let value = args.next().ok_or("--compress requires a value")?;
+                    compression = Compression::from_arg(&value)?;
+                }
+                "--log-level" => {
+                    let value = args.next().ok_or("--log-level requires a value")?;
+                    log_level = level_from_arg(&value)?;
+                }
+                "--log-format" => {
+                    let value = args.next().ok_or("--log-format requires a value")?;
+                    log_format = LogFormat::from_arg(&value)?;
+                }
+                "--metrics-addr" => {
+                    metrics_addr = Some(args.next().ok_or("--metrics-addr requires a value")?);
+                }
+                "--postgres" => {
+                    postgres_url = Some(args.next().ok_or("--postgres requires a value")?);
+                }
+                "--sqlite" => {
+                    sqlite_path = Some(args.next().ok_or("--sqlite requires a value")?);
+                }
+                "--clickhouse" => {
+                    clickhouse_url = Some(args.next().ok_or("--clickhouse requires a value")?);
+                }
+                "--kafka" => {
+                    kafka_url = Some(args.next().ok_or("--kafka requires a value")?);
+                }
+                "--kafka-topic" => {
+                    kafka_topic = Some(args.next().ok_or("--kafka-topic requires a value")?);
+                }
+                "--kafka-block-topic" => {
+                    kafka_block_topic = Some(args.next().ok_or("--kafka-block-topic requires a value")?);
+                }
+                "--timestamps" => {
+                    let value = args.next().ok_or("--timestamps requires a value")?;
+                    match value.split_once('=') {
+                        Some((sink, mode)) => {
+                            let sink = TIMESTAMP_SINKS
+                                .iter()
+                                .find(|name| **name == sink)
+                                .ok_or_else(|| format!("unknown sink '{}' (expected {})", sink, TIMESTAMP_SINKS.join(", ")))?;
+                            timestamps.insert(*sink, TimestampMode::from_arg(mode)?);
+                        }
+                        None => {
+                            let mode = TimestampMode::from_arg(&value)?;
+                            timestamps = TIMESTAMP_SINKS.iter().map(|sink| (*sink, mode)).collect();
+                        }
+                    }
+                }
+                "--webhook" => {
+                    webhook_url = Some(args.next().ok_or("--webhook requires a value")?);
+                }
+                "--webhook-program" => {
+                    let value = args.next().ok_or("--webhook-program requires a value")?;
+                    cli_webhook_programs.push(value.parse().map_err(|_| format!("invalid program id '{}'", value))?);
+                }
+                "--webhook-account" => {
+                    let value = args.next().ok_or("--webhook-account requires a value")?;
+                    cli_webhook_accounts.push(value.parse().map_err(|_| format!("invalid account '{}'", value))?);
+                }
+                "--webhook-min-swap" => {
+                    let value = args.next().ok_or("--webhook-min-swap requires a value")?;
+                    webhook_min_swap = Some(value.parse().map_err(|_| format!("invalid swap amount '{}'", value))?);
+                }
+                "--webhook-swap-mint" => {
+                    let value = args.next().ok_or("--webhook-swap-mint requires a value")?;
+                    webhook_swap_mint = Some(value.parse().map_err(|_| format!("invalid mint '{}'", value))?);
+                }
+                "--batch-size" => {
+                    let value = args.next().ok_or("--batch-size requires a value")?;
+                    batch_size = Some(value.parse().map_err(|_| format!("invalid batch size '{}'", value))?);
+                }
+                "--flush-interval" => {
+                    let value = args.next().ok_or("--flush-interval requires a value")?;
+                    let secs: f64 = value.parse().map_err(|_| format!("invalid flush interval '{}'", value))?;
+                    flush_interval = Some(Duration::try_from_secs_f64(secs).map_err(|_| format!("invalid flush interval '{}'", value))?);
+                }
+                _ => match arg.strip_prefix("--output=").or_else(|| arg.strip_prefix("--format=")) {
+                    Some(value) => output = OutputFormat::from_arg(value)?,
+                    None if arg.starts_with('-') && arg != "-" => {
+                        return Err(format!("unknown option '{}'", arg));
+                    }
+                    None => positional.push(arg),
+                },
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let command = match positional.next().as_deref() {
+            None | Some("help") => Command::Help,
+            Some("parse-tx") => Command::ParseTx { path: required(positional.next(), "parse-tx <file>")? },
+            Some("parse-block") => Command::ParseBlock { path: required(positional.next(), "parse-block <file>")? },
+            Some("read-parsed") => Command::ReadParsed { path: required(positional.next(), "read-parsed <file>")? },
+            Some("parse-dir") => Command::ParseDir { dir: required(positional.next(), "parse-dir <dir>")? },
+            Some("parse") => Command::Parse { input: InputSource::from_arg(required(positional.next(), "parse <source>")?)? },
+            Some("validate") => {
+                Command::Validate { input: InputSource::from_arg(required(positional.next(), "validate <source>")?)? }
+            }
+            Some("fetch-block") => {
+                let slot = parse_slot(required(positional.next(), "fetch-block <slot>")?)?;
+                Command::FetchBlock { slot }
+            }
+            Some("parse-sig") => {
+                let signature = parse_signature(required(positional.next(), "parse-sig <signature>")?)?;
+                Command::ParseSig { signature }
+            }
+            Some("address-history") => {
+                let value = required(positional.next(), "address-history <pubkey>")?;
+                let address = value.parse().map_err(|_| format!("invalid address '{}'", value))?;
+                Command::AddressHistory { address }
+            }
+            Some("stream") => {
+                let start = parse_slot(required(positional.next(), "stream <start> [end]")?)?;
+                let end = positional.next().map(parse_slot).transpose()?;
+                if end.is_none() && !follow {
+                    return Err("stream needs an end slot or --follow".to_string());
+                }
+                Command::Stream { start, end: if follow { None } else { end } }
+            }
+            Some("backfill") => {
+                let start = parse_slot(required(positional.next(), "backfill <start> <end>")?)?;
+                let end = parse_slot(required(positional.next(), "backfill <start> <end>")?)?;
+                if end < start {
+                    return Err(format!("backfill end slot {} is before start slot {}", end, start));
+                }
+                Command::Backfill { start, end }
+            }
+            Some("subscribe") => {
+                let kind = match required(positional.next(), "subscribe blocks|logs [account]")?.as_str() {
+                    "blocks" => SubscriptionKind::Blocks,
+                    "logs" => SubscriptionKind::Logs,
+                    other => return Err(format!("unknown subscription '{}' (expected blocks or logs)", other)),
+                };
+                Command::Subscribe { kind, mentions: positional.next() }
+            }
+            Some("diff") => {
+                let left = BlockSource::from_arg(required(positional.next(), "diff <a> <b>")?);
+                let right = BlockSource::from_arg(required(positional.next(), "diff <a> <b>")?);
+                Command::Diff { left, right }
+            }
+            Some("wallet-report") => {
+                let usage = "wallet-report <pubkey> <file|dir>... or <start> <end>";
+                let value = required(positional.next(), usage)?;
+                let address = value.parse().map_err(|_| format!("invalid address '{}'", value))?;
+                let blocks = block_inputs(positional.by_ref().collect(), "wallet-report", usage)?;
+                Command::WalletReport { address, blocks }
+            }
+            Some("top-programs") => {
+                let blocks = block_inputs(positional.by_ref().collect(), "top-programs", "top-programs <file|dir>... or <start> <end>")?;
+                Command::TopPrograms { blocks }
+            }
+            Some("sandwiches") => {
+                let blocks = block_inputs(positional.by_ref().collect(), "sandwiches", "sandwiches <file|dir>... or <start> <end>")?;
+                Command::Sandwiches { blocks }
+            }
+            Some("serve") => Command::Serve { addr: required(positional.next(), "serve <addr>")?.to_string() },
+            Some("schema") => {
+                let kind = match positional.next().as_deref() {
+                    None | Some("block") => SchemaKind::Block,
+                    Some("transaction") => SchemaKind::Transaction,
+                    Some("arrow") => SchemaKind::Arrow,
+                    Some(other) => return Err(format!("unknown schema '{}' (expected block, transaction or arrow)", other)),
+                };
+                Command::Schema { kind }
+            }
+            Some(other) => return Err(format!("unknown command '{}'", other)),
+        };
+
+        if let Some(extra) = positional.next() {
+            return Err(format!("unexpected argument '{}'", extra));
+        }
+
+        if resume && checkpoint.is_none() {
+            return Err("--resume requires --checkpoint".to_string());
+        }
+        // The Parquet footer is written once at the end, so an existing file
+        // can't be extended.
+        if resume && output == OutputFormat::Parquet {
+            return Err("--resume can't append to parquet output".to_string());
+        }
+        if manifest.is_some() && output == OutputFormat::Parquet {
+            return Err("--manifest can't append to parquet output".to_string());
+        }
+        if per_block && output == OutputFormat::Pretty {
+            return Err("--per-block needs --output json, ndjson, csv, parquet, bincode, borsh or account-events".to_string());
+        }
+        if commitment == Commitment::Processed && command.fetches_blocks(geyser_url.is_some()) {
+            return Err(format!("{} can't use processed commitment: blocks and transactions are only served confirmed or finalized", command.name()));
+        }
+        if fields.is_some() && !matches!(output, OutputFormat::Json | OutputFormat::Ndjson) {
+            return Err("--fields needs --output json or ndjson".to_string());
+        }
+        if rollup.is_none() {
+            if memory_budget.is_some() {
+                return Err("--memory-budget needs --rollup".to_string());
+            }
+            if spill_dir.is_some() {
+                return Err("--spill-dir needs --rollup".to_string());
+            }
+        }
+        let writes_files = match output {
+            OutputFormat::Ndjson | OutputFormat::Binary(_) | OutputFormat::AccountEvents => out_file.is_some() || per_block,
+            OutputFormat::Csv => true,
+            OutputFormat::Json => per_block,
+            OutputFormat::Pretty | OutputFormat::Parquet => false,
+        };
+        if compression != Compression::None && !writes_files {
+            return Err("--compress needs ndjson, bincode, borsh or account-events with --out-file, csv, or parse-dir --per-block".to_string());
+        }
+        // SQL written to stdout can't share it with the output format.
+        let stdout_output = match output {
+            OutputFormat::Ndjson | OutputFormat::Binary(_) | OutputFormat::AccountEvents => out_file.is_none(),
+            OutputFormat::Json | OutputFormat::Pretty => true,
+            OutputFormat::Csv | OutputFormat::Parquet => false,
+        };
+        let sql_to_stdout = [postgres_url.as_deref(), sqlite_path.as_deref()].iter().filter(|target| **target == Some("-")).count();
+        if sql_to_stdout > 1 {
+            return Err("--postgres - and --sqlite - can't both write to stdout".to_string());
+        }
+        if sql_to_stdout == 1 && stdout_output {
+            return Err("writing SQL to stdout ('-') needs csv or parquet output, or --out-file".to_string());
+        }
+        let sinks = [postgres_url.is_some(), sqlite_path.is_some(), clickhouse_url.is_some(), kafka_url.is_some(), webhook_url.is_some()];
+        if per_block && sinks.contains(&true) {
+            return Err("--per-block can't be combined with database, Kafka or webhook sinks".to_string());
+        }
+
+        // Lists given on the command line replace the config file's.
+        if endpoints.is_empty() {
+            endpoints = rpc.endpoints.iter().map(|value| Endpoint::from_arg(value)).collect::<Result<_, _>>()?;
+        }
+        let endpoint_pool = (!endpoints.is_empty()).then(|| Arc::new(EndpointPool::new(endpoints)));
+        if snapshot_accounts.is_empty() {
+            snapshot_accounts = rpc.snapshot_accounts;
+        }
+        if idl_paths.is_empty() {
+            idl_paths = parser.idl;
+        }
+        if decoder_files.is_empty() {
+            decoder_files = parser.decoder_files;
+        }
+        if consensus_rpc_urls.is_empty() {
+            consensus_rpc_urls = rpc.consensus_urls;
+        }
+        if !cli_webhook_programs.is_empty() {
+            webhook_programs = cli_webhook_programs;
+        }
+        if !cli_webhook_accounts.is_empty() {
+            webhook_accounts = cli_webhook_accounts;
+        }
+        let webhook_filter = webhook_programs.into_iter().fold(TxFilter::new(), TxFilter::program);
+        let webhook_filter = webhook_accounts.into_iter().fold(webhook_filter, TxFilter::account);
+        let webhook_min_swap = webhook_min_swap.map(|amount| SwapThreshold {
+            mint: webhook_swap_mint.unwrap_or(WRAPPED_SOL_MINT),
+            amount,
+        });
+        // An alert per transaction would flood the webhook.
+        if webhook_url.is_some() && webhook_filter.is_empty() && webhook_min_swap.is_none() {
+            return Err("--webhook needs --webhook-program, --webhook-account or --webhook-min-swap".to_string());
+        }
+        if programs.is_empty() {
+            programs = filter.programs;
+        }
+        if instructions.is_empty() {
+            instructions = filter.instructions.iter().map(|value| InstructionPattern::from_arg(value)).collect::<Result<_, _>>()?;
+        }
+        if accounts.is_empty() {
+            accounts = filter.accounts;
+        }
+        let mut filter = TxFilter::new();
+        for program in programs {
+            filter = filter.program(program);
+        }
+        for pattern in instructions {
+            filter = filter.instruction(pattern);
+        }
+        if prefilter && accounts.is_empty() {
+            return Err("--prefilter needs --account".to_string());
+        }
+        for account in accounts {
+            filter = filter.account(account);
+        }
+        if prefilter {
+            filter = filter.prefilter();
+        }
+        if let Some(status) = status {
+            filter = filter.status(status);
+        }
+        if let Some(min_fee) = min_fee {
+            filter = filter.min_fee(min_fee);
+        }
+        if let Some(min_compute_units) = min_compute_units {
+            filter = filter.min_compute_units(min_compute_units);
+        }
+        if exclude_votes {
+            filter = filter.exclude_votes();
+        }
+
+        // Transactions decoded from base64 carry their instruction data as
+        // base58, whatever the input encoding was.
+        if verify_signatures && input_data_encoding != DataEncoding::Base58 {
+            return Err("--verify can't be combined with --input-data-encoding".to_string());
+        }
+        // Signatures are checked against the wire bytes.
+        if verify_signatures && encoding.is_some_and(|encoding| encoding != TransactionEncoding::Base64) {
+            return Err("--verify needs --encoding base64".to_string());
+        }
+
+        let epoch_schedule = if epoch_warmup {
+            EpochSchedule::with_warmup(slots_per_epoch)
+        } else {
+            EpochSchedule::without_warmup(slots_per_epoch)
+        };
+
+        Ok(Cli {
+            command,
+            output,
+            rpc_url,
+            diff_rpc_url,
+            consensus_rpc_urls,
+            ws_url,
+            geyser_url,
+            x_token,
+            commitment,
+            max_transaction_version,
+            encoding,
+            transaction_details,
+            rewards,
+            retry,
+            rate_limiter,
+            endpoint_pool,
+            fetch_lookup_tables: fetch_lookup_tables || lookup_table_cache.is_some(),
+            lookup_table_cache,
+            fetch_leaders,
+            snapshot_accounts,
+            threads,
+            per_block,
+            strict,
+            verify_signatures,
+            data_encoding,
+            log_retention,
+            input_data_encoding,
+            out_dir,
+            out_file,
+            compression,
+            fields,
+            manifest,
+            dedup,
+            dedup_window,
+            timestamps,
+            checkpoint,
+            concurrency,
+            reorder_window,
+            confirmation_depth,
+            gap_report,
+            leader_stats,
+            rollup,
+            summary,
+            memory_budget,
+            spill_dir,
+            rollup_period,
+            rank_by,
+            top,
+            max_span,
+            before,
+            until,
+            limit,
+            metrics_addr,
+            log_level,
+            log_format,
+            resume,
+            postgres_url,
+            sqlite_path,
+            clickhouse_url,
+            kafka_url,
+            kafka_topic,
+            kafka_block_topic,
+            webhook_url,
+            webhook_filter,
+            webhook_min_swap,
+            batch_size,
+            flush_interval,
+            idl_paths,
+            decoder_files,
+            decoders,
+            labels: labels || labels_file.is_some(),
+            labels_file,
+            token_list,
+            fetch_token_decimals,
+            fetch_token_owners,
+            epoch_schedule,
+            filter: if filter.is_empty() { None } else { Some(filter) },
+        })
+    }
+}
+
+fn required(value: Option<String>, usage: &str) -> Result<String, String> {
+    value.ok_or_else(|| format!("missing argument: {}", usage))
+}
+
+fn new_rate_limiter(rate: f64) -> Result<Arc<RateLimiter>, String> {
+    if !(rate.is_finite() && rate > 0.0) {
+        return Err(format!("invalid rate limit '{}'", rate));
+    }
+    Ok(Arc::new(RateLimiter::new(rate)))
+}
+
+fn parse_max_version(value: &str) -> Result<Option<u8>, String> {
+    match value {
+        "legacy" => Ok(None),
+        _ => value.parse().map(Some).map_err(|_| format!("invalid transaction version '{}' (expected a number or legacy)", value)),
+    }
+}
+
+fn parse_signature(value: String) -> Result<String, String> {
+    match bs58::decode(&value).into_vec() {
+        Ok(bytes) if bytes.len() == 64 => Ok(value),
+        _ => Err(format!("invalid signature '{}'", value)),
+    }
+}
+
+fn parse_slot(value: String) -> Result<u64, String> {
+    value.parse().map_err(|_| format!("invalid slot '{}'", value))
+}
+
+// Bytes, or a number of K, M or G (powers of 1024), e.g. 512M.
+fn parse_byte_size(value: &str) -> Result<usize, String> {
+    let invalid = || format!("invalid size '{}' (expected e.g. 512M or 2G)", value);
+    let (digits, unit) = match value.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((at, _)) => value.split_at(at),
+        None => (value, ""),
+    };
+    let shift = match unit.to_ascii_uppercase().trim_end_matches('B') {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        _ => return Err(invalid()),
+    };
+    let count: usize = digits.parse().map_err(|_| invalid())?;
+    count.checked_mul(1 << shift).filter(|&bytes| bytes > 0).ok_or_else(invalid)
+}
+
+// Two slots make a range; anything else is a list of files and directories.
+fn block_inputs(args: Vec<String>, command: &str, usage: &str) -> Result<BlockInputs, String> {
+    let sources: Vec<BlockSource> = args.into_iter().map(BlockSource::from_arg).collect();
+    match sources.as_slice() {
+        [] => Err(format!("missing argument: {}", usage)),
+        [BlockSource::Slot(start), BlockSource::Slot(end)] if end < start => {
+            Err(format!("{} end slot {} is before start slot {}", command, end, start))
+        }
+        [BlockSource::Slot(start), BlockSource::Slot(end)] => Ok(BlockInputs::Slots { start: *start, end: *end }),
+        _ => {
+            let files = sources.into_iter().map(|source| match source {
+                BlockSource::File(path) => Ok(path),
+                BlockSource::Slot(slot) => Err(format!("no such file '{}'; a slot range takes <start> <end>", slot)),
+            });
+            Ok(BlockInputs::Files(files.collect::<Result<_, _>>()?))
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use crate::shutdown;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+// ==========================================
+// COMPRESSION
+// ==========================================
+// Block archives are usually gzip- or zstd-compressed. Both go through the
+// system `gzip` / `zstd` binaries, the way the Postgres sink goes through
+// psql: a reader is the stdout of a decompressing child and a writer the
+// stdin of a compressing one. Inputs are recognized by their magic bytes,
+// so the file name doesn't matter. Appending adds a new gzip member or zstd
+// frame, which both tools read back as one stream.
+//
+// A compressor only reports failure (disk full, killed) through its exit
+// status, so writers are finished explicitly: `Writer::finish` closes the
+// pipe, waits and fails on a bad exit. Dropping a writer still waits, but
+// can't report anything. A reader dropped before the end kills its
+// decompressor rather than leave it behind.
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "none" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            other => Err(format!("unknown compression '{}' (expected gzip, zstd or none)", other)),
+        }
+    }
+
+    pub fn detect(header: &[u8]) -> Self {
+        if header.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else if header.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    // Appended to output file names: "" / ".gz" / ".zst".
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Compression::None => "cat",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+}
+
+// Reads a file, decompressing it if it starts with a gzip or zstd header.
+pub fn open(path: &Path) -> io::Result<Box<dyn Read + Send>> {
+    let mut file = File::open(path)?;
+    let mut header = Vec::with_capacity(ZSTD_MAGIC.len());
+    (&mut file).take(ZSTD_MAGIC.len() as u64).read_to_end(&mut header)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let compression = Compression::detect(&header);
+    if compression == Compression::None {
+        return Ok(Box::new(file));
+    }
+    let mut child = shutdown::own_process_group(&mut Command::new(compression.program()))
+        .args(["-d", "-c", "-q"])
+        .stdin(file)
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::other(format!("failed to run {}: {}", compression.program(), e)))?;
+    let stdout = child.stdout.take().ok_or_else(|| io::Error::other("decompressor stdout unavailable"))?;
+    Ok(Box::new(ChildReader { compression, child, stdout }))
+}
+
+// Creates (or appends to) a file, compressing what's written to it.
+pub fn create(path: &Path, compression: Compression, append: bool) -> io::Result<Writer> {
+    let file = OpenOptions::new().create(true).write(true).append(append).truncate(!append).open(path)?;
+    if compression == Compression::None {
+        return Ok(Writer::from(Box::new(file) as Box<dyn Write + Send>));
+    }
+    let mut child = shutdown::own_process_group(&mut Command::new(compression.program()))
+        .args(["-c", "-q"])
+        .stdin(Stdio::piped())
+        .stdout(file)
+        .spawn()
+        .map_err(|e| io::Error::other(format!("failed to run {}: {}", compression.program(), e)))?;
+    let stdin = child.stdin.take().ok_or_else(|| io::Error::other("compressor stdin unavailable"))?;
+    Ok(Writer::Compressed(ChildWriter { compression, child, stdin: Some(stdin) }))
+}
+
+// What the file exporters write to: a file, stdout or any other writer, or
+// a compressor (see `create`).
+pub enum Writer {
+    Plain(Box<dyn Write + Send>),
+    Compressed(ChildWriter),
+}
+
+impl Writer {
+    // Flushes, and for a compressor waits for it to finish the file.
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Writer::Plain(mut out) => out.flush(),
+            Writer::Compressed(child) => child.finish(),
+        }
+    }
+}
+
+impl From<Box<dyn Write + Send>> for Writer {
+    fn from(out: Box<dyn Write + Send>) -> Self {
+        Writer::Plain(out)
+    }
+}
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Writer::Plain(out) => out.write(buf),
+            Writer::Compressed(child) => child.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Writer::Plain(out) => out.flush(),
+            Writer::Compressed(child) => child.flush(),
+        }
+    }
+}
+
+struct ChildReader {
+    compression: Compression,
+    child: Child,
+    stdout: ChildStdout,
+}
+
+impl Read for ChildReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stdout.read(buf)?;
+        // A truncated or corrupt archive shows up as a failed exit.
+        if n == 0 && !buf.is_empty() {
+            let status = self.child.wait()?;
+            if !status.success() {
+                return Err(io::Error::other(format!("{} failed to decompress ({})", self.compression.program(), status)));
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for ChildReader {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+        }
+        let _ = self.child.wait();
+    }
+}
+
+pub struct ChildWriter {
+    compression: Compression,
+    child: Child,
+    // Taken on drop to close the pipe so the compressor finishes the file.
+    stdin: Option<ChildStdin>,
+}
+
+impl Write for ChildWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdin.as_mut().map_or(Ok(0), |stdin| stdin.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdin.as_mut().map_or(Ok(()), |stdin| stdin.flush())
+    }
+}
+
+impl ChildWriter {
+    fn finish(mut self) -> io::Result<()> {
+        if let Some(mut stdin) = self.stdin.take() {
+            stdin.flush()?;
+        }
+        let status = self.child.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("{} failed to compress ({})", self.compression.program(), status)));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ChildWriter {
+    fn drop(&mut self) {
+        drop(self.stdin.take());
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::load_from_json;
+    use crate::types::RpcBlockResponse;
+
+    #[test]
+    fn round_trips_through_gzip_and_zstd() {
+        let json = std::fs::read("src/json/empty_block.json").unwrap();
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let path = std::env::temp_dir().join(format!("phase1-compress-{}{}", std::process::id(), compression.extension()));
+            let mut out = create(&path, compression, false).unwrap();
+            out.write_all(&json).unwrap();
+            out.finish().unwrap();
+
+            assert_eq!(Compression::detect(&std::fs::read(&path).unwrap()), compression);
+            let mut decompressed = Vec::new();
+            open(&path).unwrap().read_to_end(&mut decompressed).unwrap();
+            assert_eq!(decompressed, json);
+            let block: RpcBlockResponse = load_from_json(path.to_str().unwrap()).unwrap();
+            assert!(block.result.transactions.is_empty());
+
+            // A reader dropped early takes its decompressor down with it.
+            let mut reader = open(&path).unwrap();
+            reader.read_exact(&mut [0u8; 1]).unwrap();
+            drop(reader);
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        // A compressor that can't write its file fails finish.
+        let mut out = create(Path::new("/dev/full"), Compression::Gzip, true).unwrap();
+        out.write_all(&json).unwrap();
+        assert!(out.finish().unwrap_err().to_string().contains("gzip failed to compress"));
+    }
+}
@@ -0,0 +1,330 @@
+use crate::decoders::DecoderSpec;
+use crate::pubkey::Pubkey;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{Document, Item, Table};
+
+// ==========================================
+// CONFIG FILE
+// ==========================================
+// Deployment settings loaded from TOML, so endpoints, sinks and filters
+// don't have to be repeated on every command line. Every field is optional;
+// the CLI applies the file first and its own flags on top. Enum-like values
+// (commitment, format, status, encodings) stay strings here and are checked
+// where the matching flag is parsed, so both report the same errors.
+// Unknown keys are rejected to catch typos.
+//
+//     [rpc]
+//     url = "https://api.mainnet-beta.solana.com"
+//     rate_limit = 10
+//
+//     [filter]
+//     programs = ["675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8"]
+//
+//     [sinks.clickhouse]
+//     url = "http://localhost:8123"
+//
+// Declarative decoders go in [[decoders]] tables, here or in files of
+// their own (see decoders::declarative).
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub rpc: RpcConfig,
+    pub geyser: GeyserConfig,
+    pub output: OutputConfig,
+    pub filter: FilterConfig,
+    pub parser: ParserConfig,
+    pub stream: StreamConfig,
+    pub sinks: SinksConfig,
+    pub log: LogConfig,
+    pub decoders: Vec<DecoderSpec>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcConfig {
+    pub url: Option<String>,
+    pub ws_url: Option<String>,
+    // Further endpoints fetch-block compares `url` against.
+    pub consensus_urls: Vec<String>,
+    pub commitment: Option<String>,
+    // A number, or "legacy".
+    pub max_transaction_version: Option<String>,
+    // json, jsonParsed or base64.
+    pub encoding: Option<String>,
+    // full, accounts, signatures or none.
+    pub transaction_details: Option<String>,
+    // Request blocks with rewards (default: true).
+    pub rewards: Option<bool>,
+    pub max_retries: Option<u32>,
+    // Seconds.
+    pub retry_backoff: Option<f64>,
+    // Requests per second.
+    pub rate_limit: Option<f64>,
+    // <url>[,weight=<n>][,rate-limit=<n>], as with --rpc-endpoint; requests
+    // go to these instead of `url`.
+    pub endpoints: Vec<String>,
+    pub fetch_lookup_tables: bool,
+    pub lookup_table_cache: Option<PathBuf>,
+    pub fetch_leaders: bool,
+    pub fetch_token_decimals: bool,
+    pub fetch_token_owners: bool,
+    // Accounts to read back after each block that writes them.
+    pub snapshot_accounts: Vec<Pubkey>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeyserConfig {
+    pub url: Option<String>,
+    pub x_token: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub format: Option<String>,
+    pub file: Option<PathBuf>,
+    pub dir: Option<PathBuf>,
+    // gzip or zstd.
+    pub compress: Option<String>,
+    // Blocks already written to the file output, skipped on a rerun.
+    pub manifest: Option<PathBuf>,
+    // skip or flag transactions seen earlier in the run.
+    pub dedup: Option<String>,
+    // Signatures remembered for dedup.
+    pub dedup_window: Option<usize>,
+    // Transaction fields to keep in json/ndjson output, e.g. "instructions.program_id".
+    pub fields: Vec<String>,
+    // Periodic totals, JSON or (.csv) CSV.
+    pub rollup: Option<PathBuf>,
+    // hour or day.
+    pub rollup_period: Option<String>,
+    // none, block-time or interpolated, for the --output format; the sinks
+    // each have their own.
+    pub timestamps: Option<String>,
+    // JSON run summary, written when the run ends.
+    pub summary: Option<PathBuf>,
+    // e.g. "512M"; see --memory-budget.
+    pub memory_budget: Option<String>,
+    pub spill_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    pub programs: Vec<Pubkey>,
+    // <program>[:<data prefix>], as with --instruction.
+    pub instructions: Vec<String>,
+    pub accounts: Vec<Pubkey>,
+    pub status: Option<String>,
+    pub min_fee: Option<u64>,
+    pub min_compute_units: Option<u64>,
+    pub exclude_votes: bool,
+    pub prefilter: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParserConfig {
+    pub threads: Option<usize>,
+    pub strict: bool,
+    pub verify_signatures: bool,
+    pub data_encoding: Option<String>,
+    pub input_data_encoding: Option<String>,
+    // log_messages to keep: keep, drop, compact or a line count.
+    pub logs: Option<String>,
+    // Anchor IDL files.
+    pub idl: Vec<String>,
+    // Files of [[decoders]] tables, TOML or (.json) JSON.
+    pub decoder_files: Vec<String>,
+    pub slots_per_epoch: Option<u64>,
+    pub epoch_warmup: bool,
+    // Attach address labels to parsed transactions.
+    pub labels: bool,
+    // Extra labels, JSON or TOML; implies `labels`.
+    pub labels_file: Option<PathBuf>,
+    // Extra token symbols and decimals, a token-list JSON file.
+    pub token_list: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub level: Option<String>,
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StreamConfig {
+    pub checkpoint: Option<PathBuf>,
+    // host:port for the Prometheus endpoint.
+    pub metrics_addr: Option<String>,
+    // getBlock requests in flight ahead of the parser.
+    pub concurrency: Option<usize>,
+    // Slots a subscription may run past a missing one before it's fetched.
+    pub reorder_window: Option<u64>,
+    // Below finalized commitment, slots before a block is checked against
+    // the finalized chain.
+    pub confirmation_depth: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SinksConfig {
+    pub batch_size: Option<usize>,
+    // Seconds.
+    pub flush_interval: Option<f64>,
+    pub postgres: Option<UrlConfig>,
+    pub sqlite: Option<PathConfig>,
+    pub clickhouse: Option<UrlConfig>,
+    pub kafka: Option<KafkaConfig>,
+    pub webhook: Option<WebhookConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UrlConfig {
+    pub url: String,
+    pub timestamps: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PathConfig {
+    pub path: String,
+    pub timestamps: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KafkaConfig {
+    pub url: String,
+    pub topic: Option<String>,
+    pub block_topic: Option<String>,
+    pub timestamps: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default)]
+    pub programs: Vec<Pubkey>,
+    #[serde(default)]
+    pub accounts: Vec<Pubkey>,
+    // Whole tokens of `swap_mint` (default: SOL).
+    pub min_swap: Option<f64>,
+    pub swap_mint: Option<Pubkey>,
+    pub timestamps: Option<String>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("failed to read config {}: {}", path.display(), e))?;
+        Self::from_toml(&contents).map_err(|e| format!("invalid config {}: {}", path.display(), e).into())
+    }
+
+    // The TOML document is converted to JSON and deserialized from there,
+    // which keeps serde support out of the TOML dependency.
+    pub fn from_toml(contents: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_json::from_value(toml_to_json(contents)?)?)
+    }
+}
+
+// A --decoders file: [[decoders]] tables, or {"decoders": [...]} in a .json
+// file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DecoderFile {
+    decoders: Vec<DecoderSpec>,
+}
+
+pub fn load_decoders(path: &Path) -> Result<Vec<DecoderSpec>, Box<dyn Error>> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("failed to read decoders {}: {}", path.display(), e))?;
+    let value = match path.extension().is_some_and(|extension| extension == "json") {
+        true => serde_json::from_str(&contents)?,
+        false => toml_to_json(&contents).map_err(|e| format!("invalid decoders {}: {}", path.display(), e))?,
+    };
+    let file: DecoderFile = serde_json::from_value(value).map_err(|e| format!("invalid decoders {}: {}", path.display(), e))?;
+    Ok(file.decoders)
+}
+
+pub(crate) fn toml_to_json(contents: &str) -> Result<Value, Box<dyn Error>> {
+    table_to_json(Document::parse(contents)?.as_table())
+}
+
+fn table_to_json(table: &Table) -> Result<Value, Box<dyn Error>> {
+    let mut object = Map::new();
+    for (key, item) in table.iter() {
+        object.insert(key.to_string(), item_to_json(item)?);
+    }
+    Ok(Value::Object(object))
+}
+
+fn item_to_json(item: &Item) -> Result<Value, Box<dyn Error>> {
+    match item {
+        Item::None => Ok(Value::Null),
+        Item::Value(value) => value_to_json(value),
+        Item::Table(table) => table_to_json(table),
+        Item::ArrayOfTables(tables) => Ok(Value::Array(tables.iter().map(table_to_json).collect::<Result<_, _>>()?)),
+    }
+}
+
+fn value_to_json(value: &toml_edit::Value) -> Result<Value, Box<dyn Error>> {
+    use toml_edit::Value as Toml;
+    Ok(match value {
+        Toml::String(s) => Value::from(s.value().as_str()),
+        Toml::Integer(i) => Value::from(*i.value()),
+        Toml::Float(f) => serde_json::Number::from_f64(*f.value())
+            .map(Value::Number)
+            .ok_or_else(|| format!("unsupported float {}", f.value()))?,
+        Toml::Boolean(b) => Value::from(*b.value()),
+        Toml::Datetime(d) => Value::from(d.value().to_string()),
+        Toml::Array(array) => Value::Array(array.iter().map(value_to_json).collect::<Result<_, _>>()?),
+        Toml::InlineTable(table) => {
+            let mut object = Map::new();
+            for (key, value) in table.iter() {
+                object.insert(key.to_string(), value_to_json(value)?);
+            }
+            Value::Object(object)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sections_and_rejects_unknown_keys() {
+        let config = Config::from_toml(
+            r#"
+            [rpc]
+            url = "http://localhost:8899"
+            retry_backoff = 1
+
+            [filter]
+            programs = ["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"]
+            min_fee = 5000
+
+            [sinks]
+            kafka = { url = "http://localhost:8082", topic = "txs" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.rpc.url.as_deref(), Some("http://localhost:8899"));
+        assert_eq!(config.rpc.retry_backoff, Some(1.0));
+        assert_eq!(config.filter.programs.len(), 1);
+        assert_eq!(config.filter.min_fee, Some(5000));
+        assert_eq!(config.sinks.kafka.unwrap().topic.as_deref(), Some("txs"));
+        assert!(config.sinks.postgres.is_none());
+
+        assert!(Config::from_toml("[rpc]\nulr = \"x\"").is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::diff::{diff_blocks, BlockDiff};
+use crate::types::{ParsedBlock, ParsedTransaction};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+// ==========================================
+// MULTI-RPC CONSENSUS
+// ==========================================
+// The same slot as returned by several RPC endpoints, folded into one
+// majority view. Providers occasionally serve a truncated block or stale
+// meta; comparing them catches that instead of passing it downstream.
+//
+// The header (blockhash, parent, height, time) shared by the most endpoints
+// wins, and only endpoints on that header contribute transactions: a block
+// from another fork isn't merged in. A transaction is kept when at least
+// half of those endpoints returned it, so with two endpoints a transaction
+// missing from a truncated copy still comes through. Where copies of a
+// transaction differ, the most common one is kept. Rewards are merged the
+// same way, by (pubkey, reward type). Ties go to the endpoint listed first.
+// Duplicate signatures within one endpoint's block are dropped.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EndpointDiff {
+    pub endpoint: String,
+    // `left` is the majority view, `right` this endpoint's block: `only_left`
+    // are transactions it's missing, `only_right` ones nobody else has.
+    pub diff: BlockDiff,
+}
+
+#[derive(Debug)]
+pub struct Consensus {
+    pub block: ParsedBlock,
+    // Every endpoint, in the order given.
+    pub endpoints: Vec<EndpointDiff>,
+}
+
+impl Consensus {
+    pub fn is_unanimous(&self) -> bool {
+        self.endpoints.iter().all(|endpoint| endpoint.diff.is_empty())
+    }
+
+    pub fn disagreeing(&self) -> impl Iterator<Item = &EndpointDiff> {
+        self.endpoints.iter().filter(|endpoint| !endpoint.diff.is_empty())
+    }
+}
+
+// `views` pairs an endpoint name with the block it returned. Returns None
+// when there are no views.
+pub fn block_consensus(views: &[(String, ParsedBlock)]) -> Option<Consensus> {
+    let headers: Vec<Value> = views.iter().map(|(_, block)| header(block)).collect();
+    let majority_header = most_common(headers.iter())?;
+    let agreeing: Vec<&ParsedBlock> =
+        views.iter().zip(&headers).filter(|(_, header)| *header == majority_header).map(|((_, block), _)| block).collect();
+
+    let template = agreeing[0];
+    let transactions: Vec<&[ParsedTransaction]> = agreeing.iter().map(|block| block.transactions.as_slice()).collect();
+    let rewards: Vec<_> = agreeing.iter().map(|block| block.rewards.as_slice()).collect();
+    let block = ParsedBlock {
+        schema_version: template.schema_version,
+        slot: template.slot,
+        epoch: template.epoch,
+        slot_index: template.slot_index,
+        block_height: template.block_height,
+        block_time: template.block_time,
+        block_time_rfc3339: template.block_time_rfc3339.clone(),
+        blockhash: template.blockhash.clone(),
+        parent_slot: template.parent_slot,
+        previous_blockhash: template.previous_blockhash.clone(),
+        leader: template.leader,
+        rewards: merge(&rewards, |reward| (reward.pubkey, reward.reward_type.as_str())),
+        transactions: merge(&transactions, |tx| tx.signature.as_str()),
+        signatures: template.signatures.clone(),
+        excluded_votes: template.excluded_votes,
+        account_snapshots: template.account_snapshots.clone(),
+        endpoint: None,
+    };
+
+    let endpoints = views
+        .iter()
+        .map(|(endpoint, view)| EndpointDiff { endpoint: endpoint.clone(), diff: diff_blocks(&block, view) })
+        .collect();
+    Some(Consensus { block, endpoints })
+}
+
+fn header(block: &ParsedBlock) -> Value {
+    json!([block.slot, block.blockhash, block.previous_blockhash, block.parent_slot, block.block_height, block.block_time])
+}
+
+// The value seen most often, the earliest on a tie.
+fn most_common<'a, T: PartialEq + 'a>(values: impl Iterator<Item = &'a T>) -> Option<&'a T> {
+    let mut counts: Vec<(&T, usize)> = Vec::new();
+    for value in values {
+        match counts.iter_mut().find(|(seen, _)| *seen == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+    // max_by_key keeps the last of equal maxima, so walk the list backwards.
+    counts.into_iter().rev().max_by_key(|(_, count)| *count).map(|(value, _)| value)
+}
+
+// Items present in at least half of `lists`, each as its most common copy.
+// The order is that of the list holding the most kept items; items it lacks
+// go after their predecessor in the first list that has them.
+fn merge<'a, T, K>(lists: &[&'a [T]], key: impl Fn(&'a T) -> K) -> Vec<T>
+where
+    T: Clone + Serialize,
+    K: Eq + Hash + Copy,
+{
+    let mut copies: HashMap<K, Vec<&T>> = HashMap::new();
+    let deduped: Vec<Vec<&'a T>> = lists
+        .iter()
+        .map(|list| {
+            let mut seen = HashSet::new();
+            list.iter().filter(|item| seen.insert(key(item))).collect()
+        })
+        .collect();
+    for list in &deduped {
+        for &item in list {
+            copies.entry(key(item)).or_default().push(item);
+        }
+    }
+    let kept: HashMap<K, &T> = copies
+        .into_iter()
+        .filter(|(_, copies)| copies.len() * 2 >= lists.len())
+        .filter_map(|(k, copies)| {
+            let values: Vec<Value> = copies.iter().map(|item| serde_json::to_value(item).unwrap_or(Value::Null)).collect();
+            let chosen = most_common(values.iter())?;
+            let position = values.iter().position(|value| value == chosen)?;
+            Some((k, copies[position]))
+        })
+        .collect();
+
+    let Some(base) = deduped.iter().rev().max_by_key(|list| list.iter().filter(|&&item| kept.contains_key(&key(item))).count()) else {
+        return Vec::new();
+    };
+    let mut order: Vec<K> = base.iter().map(|&item| key(item)).filter(|k| kept.contains_key(k)).collect();
+    let mut placed: HashSet<K> = order.iter().copied().collect();
+    for list in &deduped {
+        let mut previous: Option<K> = None;
+        for &item in list {
+            let k = key(item);
+            if kept.contains_key(&k) && placed.insert(k) {
+                let at = previous.and_then(|p| order.iter().position(|o| *o == p)).map_or(0, |i| i + 1);
+                order.insert(at, k);
+            }
+            if placed.contains(&k) {
+                previous = Some(k);
+            }
+        }
+    }
+    order.into_iter().map(|k| kept[&k].clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn block() -> ParsedBlock {
+        fixtures::block().clone()
+    }
+
+    #[test]
+    fn majority_view_outvotes_a_truncated_and_a_stale_endpoint() {
+        let full = block();
+        let mut truncated = block();
+        truncated.transactions.truncate(100);
+        truncated.rewards.clear();
+        let mut stale = block();
+        stale.transactions[5].fee += 1;
+        let duplicate = stale.transactions[6].clone();
+        stale.transactions.push(duplicate);
+
+        let views = vec![("a".to_string(), truncated.clone()), ("b".to_string(), stale), ("c".to_string(), full.clone())];
+        let consensus = block_consensus(&views).unwrap();
+        assert!(diff_blocks(&full, &consensus.block).is_empty());
+        assert!(!consensus.is_unanimous());
+        let disagreeing: Vec<&str> = consensus.disagreeing().map(|endpoint| endpoint.endpoint.as_str()).collect();
+        assert_eq!(disagreeing, ["a", "b"]);
+        assert_eq!(consensus.endpoints[0].diff.only_left.len(), full.transactions.len() - 100);
+        assert_eq!(consensus.endpoints[1].diff.changed.len(), 1);
+        assert_eq!(consensus.endpoints[1].diff.changed[0].fields, ["fee"]);
+
+        // With two endpoints, the truncated copy doesn't drop anything.
+        let consensus = block_consensus(&[("a".to_string(), truncated), ("c".to_string(), full.clone())]).unwrap();
+        assert!(diff_blocks(&full, &consensus.block).is_empty());
+
+        // A block from another fork is reported but not merged in.
+        let mut fork = block();
+        fork.blockhash = "fork".to_string();
+        fork.transactions.truncate(1);
+        let consensus = block_consensus(&[("a".to_string(), full.clone()), ("b".to_string(), full.clone()), ("c".to_string(), fork)]).unwrap();
+        assert!(diff_blocks(&full, &consensus.block).is_empty());
+        assert_eq!(consensus.endpoints[2].diff.header[0].field, "blockhash");
+    }
+}
@@ -0,0 +1,201 @@
+use crate::decoders::{ComputeBudgetDecoder, ComputeBudgetInstruction, DecodedInstruction, ProgramDecoder};
+use crate::parser::{COMPUTE_BUDGET_PROGRAM_ID, ED25519_PROGRAM_ID, SECP256K1_PROGRAM_ID, SECP256R1_PROGRAM_ID};
+use crate::pubkey::Pubkey;
+use crate::types::{ParsedBlock, ParsedTransaction};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// ==========================================
+// BLOCK COST MODEL
+// ==========================================
+// What each transaction costs against the leader's block limits, following
+// the validator's cost model: signatures, write locks, instruction data,
+// executed compute and the loaded accounts data size allowance, or a flat
+// cost for simple votes. A writable account is charged the whole cost of
+// every transaction that writes it, which is what the per-account limit
+// caps.
+//
+// Execution cost is the compute actually consumed when the meta reports
+// it, as the cost tracker settles to after execution, otherwise the
+// requested limit. Write locks count the accounts the message marks
+// writable; the runtime's demotion of reserved accounts isn't modelled.
+
+const SIGNATURE_COST: u64 = 720;
+const ED25519_VERIFY_COST: u64 = 2_400;
+const SECP256K1_VERIFY_COST: u64 = 6_690;
+const SECP256R1_VERIFY_COST: u64 = 4_800;
+const WRITE_LOCK_UNITS: u64 = 300;
+const INSTRUCTION_DATA_BYTES_COST: u64 = 4;
+const SIMPLE_VOTE_USAGE_COST: u64 = 3_428;
+
+// Loaded account data is billed per 32KiB page, up to 64MiB unless
+// SetLoadedAccountsDataSizeLimit asks for less.
+const MAX_LOADED_ACCOUNTS_DATA_SIZE_BYTES: u64 = 64 * 1024 * 1024;
+const ACCOUNT_DATA_COST_PAGE_SIZE: u64 = 32 * 1024;
+const HEAP_COST: u64 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockLimits {
+    pub block_units: u64,
+    pub writable_account_units: u64,
+    pub vote_units: u64,
+}
+
+impl Default for BlockLimits {
+    // Mainnet limits since the 60M block limit (SIMD-0256).
+    fn default() -> Self {
+        BlockLimits { block_units: 60_000_000, writable_account_units: 12_000_000, vote_units: 36_000_000 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionCost {
+    pub is_simple_vote: bool,
+    // Zero for simple votes, which pay SIMPLE_VOTE_USAGE_COST instead.
+    pub signature_cost: u64,
+    pub write_lock_cost: u64,
+    pub data_bytes_cost: u64,
+    pub programs_execution_cost: u64,
+    pub loaded_accounts_data_size_cost: u64,
+}
+
+impl TransactionCost {
+    pub fn total(&self) -> u64 {
+        match self.is_simple_vote {
+            true => SIMPLE_VOTE_USAGE_COST,
+            false => {
+                self.signature_cost
+                    + self.write_lock_cost
+                    + self.data_bytes_cost
+                    + self.programs_execution_cost
+                    + self.loaded_accounts_data_size_cost
+            }
+        }
+    }
+}
+
+pub fn transaction_cost(tx: &ParsedTransaction) -> TransactionCost {
+    if tx.is_vote() && tx.instructions.len() == 1 && tx.accounts.iter().filter(|account| account.is_signer).count() <= 2 {
+        return TransactionCost { is_simple_vote: true, ..TransactionCost::default() };
+    }
+
+    let data: Vec<(Pubkey, Vec<u8>)> =
+        tx.instructions.iter().map(|ix| (ix.program_id, ix.data_bytes().unwrap_or_default())).collect();
+    let precompile_signatures = |program_id: Pubkey| -> u64 {
+        data.iter().filter(|(id, _)| *id == program_id).filter_map(|(_, data)| data.first()).map(|&n| n as u64).sum()
+    };
+    let signers = tx.accounts.iter().filter(|account| account.is_signer).count() as u64;
+    let signature_cost = signers * SIGNATURE_COST
+        + precompile_signatures(ED25519_PROGRAM_ID) * ED25519_VERIFY_COST
+        + precompile_signatures(SECP256K1_PROGRAM_ID) * SECP256K1_VERIFY_COST
+        + precompile_signatures(SECP256R1_PROGRAM_ID) * SECP256R1_VERIFY_COST;
+
+    let loaded_accounts_data_size = data
+        .iter()
+        .rev()
+        .filter(|(id, _)| *id == COMPUTE_BUDGET_PROGRAM_ID)
+        .filter_map(|(_, data)| match ComputeBudgetDecoder.decode(data, &[]) {
+            Some(DecodedInstruction::ComputeBudget(ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit { bytes })) => {
+                Some(bytes as u64)
+            }
+            _ => None,
+        })
+        .next()
+        .unwrap_or(MAX_LOADED_ACCOUNTS_DATA_SIZE_BYTES)
+        .min(MAX_LOADED_ACCOUNTS_DATA_SIZE_BYTES);
+
+    TransactionCost {
+        is_simple_vote: false,
+        signature_cost,
+        write_lock_cost: tx.accounts.iter().filter(|account| account.is_writable).count() as u64 * WRITE_LOCK_UNITS,
+        data_bytes_cost: data.iter().map(|(_, data)| data.len() as u64).sum::<u64>() / INSTRUCTION_DATA_BYTES_COST,
+        programs_execution_cost: tx.compute_units_consumed.unwrap_or(tx.fee_breakdown.compute_unit_limit as u64),
+        loaded_accounts_data_size_cost: loaded_accounts_data_size.div_ceil(ACCOUNT_DATA_COST_PAGE_SIZE) * HEAP_COST,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountCost {
+    pub pubkey: Pubkey,
+    pub units: u64,
+    pub transactions: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockCost {
+    pub limits: BlockLimits,
+    pub total_units: u64,
+    pub vote_units: u64,
+    // Percent of the matching limit.
+    pub block_utilization: f64,
+    pub vote_utilization: f64,
+    // Most expensive first, ties by pubkey; the first ten.
+    pub hottest_accounts: Vec<AccountCost>,
+    // The first of hottest_accounts against the per-account limit.
+    pub account_utilization: f64,
+}
+
+const HOTTEST_ACCOUNTS: usize = 10;
+
+pub fn block_cost(block: &ParsedBlock, limits: &BlockLimits) -> BlockCost {
+    let mut total_units = 0;
+    let mut vote_units = 0;
+    let mut accounts: HashMap<Pubkey, AccountCost> = HashMap::new();
+    for tx in &block.transactions {
+        let cost = transaction_cost(tx);
+        let units = cost.total();
+        total_units += units;
+        if cost.is_simple_vote {
+            vote_units += units;
+        }
+        for account in tx.accounts.iter().filter(|account| account.is_writable) {
+            let entry =
+                accounts.entry(account.pubkey).or_insert(AccountCost { pubkey: account.pubkey, units: 0, transactions: 0 });
+            entry.units += units;
+            entry.transactions += 1;
+        }
+    }
+    let mut hottest_accounts: Vec<AccountCost> = accounts.into_values().collect();
+    hottest_accounts.sort_by(|a, b| b.units.cmp(&a.units).then_with(|| a.pubkey.cmp(&b.pubkey)));
+    hottest_accounts.truncate(HOTTEST_ACCOUNTS);
+
+    let percent = |units: u64, limit: u64| units as f64 * 100.0 / limit.max(1) as f64;
+    BlockCost {
+        limits: *limits,
+        total_units,
+        vote_units,
+        block_utilization: percent(total_units, limits.block_units),
+        vote_utilization: percent(vote_units, limits.vote_units),
+        account_utilization: percent(hottest_accounts.first().map_or(0, |account| account.units), limits.writable_account_units),
+        hottest_accounts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn costs_add_up_to_block_utilization() {
+        let block = fixtures::block();
+        let costs: Vec<TransactionCost> = block.transactions.iter().map(transaction_cost).collect();
+
+        for (tx, cost) in block.transactions.iter().zip(&costs) {
+            if cost.is_simple_vote {
+                assert_eq!(cost.total(), SIMPLE_VOTE_USAGE_COST);
+                continue;
+            }
+            assert!(cost.signature_cost >= SIGNATURE_COST);
+            assert_eq!(cost.write_lock_cost % WRITE_LOCK_UNITS, 0);
+            assert_eq!(cost.programs_execution_cost, tx.compute_units_consumed.unwrap());
+        }
+
+        let report = block_cost(block, &BlockLimits::default());
+        assert_eq!(report.total_units, costs.iter().map(TransactionCost::total).sum::<u64>());
+        assert!(report.vote_units > 0 && report.vote_units < report.total_units);
+        assert!(report.block_utilization > 0.0 && report.block_utilization < 100.0);
+        assert!(report.hottest_accounts.windows(2).all(|pair| pair[0].units >= pair[1].units));
+        assert!(report.hottest_accounts[0].units <= report.total_units);
+    }
+}
@@ -0,0 +1,420 @@
+use super::{DataReader, DecodedEvent, DecodedInstruction, ProgramDecoder};
+use crate::error::ParseError;
+use crate::pubkey::Pubkey;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+#[cfg(feature = "native")]
+use std::fs;
+
+// ==========================================
+// ANCHOR IDL DECODER
+// ==========================================
+// Decodes any Anchor program from its IDL: the first 8 bytes of instruction
+// data (and of "Program data:" event payloads) select the instruction/event,
+// and the rest is borsh, decoded into JSON by walking the IDL type tree.
+// Handles both the 0.30+ IDL format (explicit discriminators, `address`) and
+// the legacy one (discriminators derived from sha256 of the name).
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnchorInstruction {
+    pub name: String,
+    pub accounts: Vec<AnchorAccount>,
+    pub args: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnchorAccount {
+    pub name: String,
+    pub pubkey: Pubkey,
+}
+
+struct IdlInstruction {
+    name: String,
+    accounts: Vec<String>,
+    args: Vec<(String, Value)>,
+}
+
+struct IdlEvent {
+    name: String,
+    fields: Vec<(String, Value)>,
+}
+
+pub struct AnchorDecoder {
+    program_id: Pubkey,
+    instructions: HashMap<[u8; 8], IdlInstruction>,
+    events: HashMap<[u8; 8], IdlEvent>,
+    types: HashMap<String, Value>,
+    // The program's own error codes (6000 and up) from the IDL.
+    errors: HashMap<u32, String>,
+}
+
+impl AnchorDecoder {
+    #[cfg(feature = "native")]
+    pub fn from_idl_file(path: &str) -> Result<Self, ParseError> {
+        let text = fs::read_to_string(path).map_err(|source| ParseError::Io { path: path.to_string(), source })?;
+        Self::from_idl_json(&text)
+    }
+
+    pub fn from_idl_json(text: &str) -> Result<Self, ParseError> {
+        let idl: Value = serde_json::from_str(text)?;
+        let program_id = idl["address"].as_str()
+            .or_else(|| idl["metadata"]["address"].as_str())
+            .ok_or_else(|| ParseError::InvalidIdl("IDL has no program address".to_string()))?
+            .parse()
+            .map_err(|e| ParseError::InvalidIdl(format!("program address: {}", e)))?;
+
+        let types: HashMap<String, Value> = idl["types"].as_array().into_iter().flatten()
+            .filter_map(|def| Some((def["name"].as_str()?.to_string(), def["type"].clone())))
+            .collect();
+
+        let mut instructions = HashMap::new();
+        for ix in idl["instructions"].as_array().into_iter().flatten() {
+            let name = idl_name(ix)?;
+            let discriminator = match discriminator(ix) {
+                Some(d) => d,
+                None => sighash("global", &to_snake_case(&name)),
+            };
+            let mut accounts = Vec::new();
+            flatten_accounts(&ix["accounts"], &mut accounts);
+            instructions.insert(discriminator, IdlInstruction { name, accounts, args: fields(&ix["args"])? });
+        }
+
+        // New-format events keep their fields in `types` under the same name.
+        let mut events = HashMap::new();
+        for event in idl["events"].as_array().into_iter().flatten() {
+            let name = idl_name(event)?;
+            let discriminator = match discriminator(event) {
+                Some(d) => d,
+                None => sighash("event", &name),
+            };
+            let fields = match event.get("fields") {
+                Some(list) => fields(list)?,
+                None => match types.get(&name) {
+                    Some(def) => fields(&def["fields"])?,
+                    None => Vec::new(),
+                },
+            };
+            events.insert(discriminator, IdlEvent { name, fields });
+        }
+
+        let errors = idl["errors"].as_array().into_iter().flatten()
+            .filter_map(|error| Some((u32::try_from(error["code"].as_u64()?).ok()?, error["name"].as_str()?.to_string())))
+            .collect();
+
+        Ok(AnchorDecoder { program_id, instructions, events, types, errors })
+    }
+
+    fn decode_fields(&self, reader: &mut DataReader, fields: &[(String, Value)]) -> Option<Value> {
+        decode_fields(reader, fields, &self.types)
+    }
+}
+
+impl ProgramDecoder for AnchorDecoder {
+    fn program_id(&self) -> Pubkey {
+        self.program_id
+    }
+
+    // Accounts past the IDL's list (remaining accounts) are named by position.
+    fn decode(&self, data: &[u8], accounts: &[Pubkey]) -> Option<DecodedInstruction> {
+        let discriminator: [u8; 8] = data.get(..8)?.try_into().ok()?;
+        let ix = self.instructions.get(&discriminator)?;
+        let mut reader = DataReader::new(&data[8..]);
+        let args = self.decode_fields(&mut reader, &ix.args)?;
+
+        let accounts = accounts.iter().enumerate()
+            .map(|(i, pubkey)| AnchorAccount {
+                name: ix.accounts.get(i).cloned().unwrap_or_else(|| format!("remaining_{}", i - ix.accounts.len())),
+                pubkey: *pubkey,
+            })
+            .collect();
+
+        Some(DecodedInstruction::Anchor(AnchorInstruction { name: ix.name.clone(), accounts, args }))
+    }
+
+    fn decodes_events(&self) -> bool {
+        !self.events.is_empty()
+    }
+
+    fn decode_event(&self, data: &[u8]) -> Option<DecodedEvent> {
+        let discriminator: [u8; 8] = data.get(..8)?.try_into().ok()?;
+        let event = self.events.get(&discriminator)?;
+        let mut reader = DataReader::new(&data[8..]);
+        Some(DecodedEvent {
+            program_id: self.program_id,
+            name: event.name.clone(),
+            data: self.decode_fields(&mut reader, &event.fields)?,
+        })
+    }
+
+    fn error_name(&self, code: u32) -> Option<String> {
+        if let Some(name) = self.errors.get(&code) {
+            return Some(name.clone());
+        }
+        ANCHOR_ERRORS.iter().find(|(c, _)| *c == code).map(|(_, name)| name.to_string())
+    }
+}
+
+// Errors raised by the Anchor framework itself, below the 6000 range
+// programs define their own in.
+const ANCHOR_ERRORS: &[(u32, &str)] = &[
+    (100, "InstructionMissing"),
+    (101, "InstructionFallbackNotFound"),
+    (102, "InstructionDidNotDeserialize"),
+    (103, "InstructionDidNotSerialize"),
+    (2000, "ConstraintMut"),
+    (2001, "ConstraintHasOne"),
+    (2002, "ConstraintSigner"),
+    (2003, "ConstraintRaw"),
+    (2004, "ConstraintOwner"),
+    (2005, "ConstraintRentExempt"),
+    (2006, "ConstraintSeeds"),
+    (2007, "ConstraintExecutable"),
+    (2009, "ConstraintAssociated"),
+    (2011, "ConstraintClose"),
+    (2012, "ConstraintAddress"),
+    (2014, "ConstraintTokenMint"),
+    (2015, "ConstraintTokenOwner"),
+    (2019, "ConstraintSpace"),
+    (3000, "AccountDiscriminatorAlreadySet"),
+    (3001, "AccountDiscriminatorNotFound"),
+    (3002, "AccountDiscriminatorMismatch"),
+    (3003, "AccountDidNotDeserialize"),
+    (3004, "AccountDidNotSerialize"),
+    (3005, "AccountNotEnoughKeys"),
+    (3006, "AccountNotMutable"),
+    (3007, "AccountOwnedByWrongProgram"),
+    (3008, "InvalidProgramId"),
+    (3009, "InvalidProgramExecutable"),
+    (3010, "AccountNotSigner"),
+    (3011, "AccountNotSystemOwned"),
+    (3012, "AccountNotInitialized"),
+    (3013, "AccountNotProgramData"),
+    (3014, "AccountNotAssociatedTokenAccount"),
+    (3015, "AccountSysvarMismatch"),
+    (3016, "AccountReallocExceedsLimit"),
+    (3017, "AccountDuplicateReallocs"),
+    (4100, "DeclaredProgramIdMismatch"),
+    (5000, "Deprecated"),
+];
+
+// ==========================================
+// BORSH DECODING
+// ==========================================
+// Walks an IDL type (as JSON) over the data; `types` holds the `defined`
+// ones. Shared with the declarative decoders, whose field types are written
+// in the same vocabulary.
+
+pub(crate) fn decode_fields(reader: &mut DataReader, fields: &[(String, Value)], types: &HashMap<String, Value>) -> Option<Value> {
+    let mut out = Map::new();
+    for (name, ty) in fields {
+        out.insert(name.clone(), decode_type(reader, ty, types)?);
+    }
+    Some(Value::Object(out))
+}
+
+// Borsh: little-endian integers, u32 length prefixes for strings/vecs,
+// a u8 tag for options and enums.
+fn decode_type(reader: &mut DataReader, ty: &Value, types: &HashMap<String, Value>) -> Option<Value> {
+    if let Some(name) = ty.as_str() {
+        return Some(match name {
+            "bool" => json!(reader.read_u8()? != 0),
+            "u8" => json!(reader.read_u8()?),
+            "i8" => json!(reader.read_u8()? as i8),
+            "u16" => json!(u16::from_le_bytes(reader.read_bytes(2)?.try_into().ok()?)),
+            "i16" => json!(i16::from_le_bytes(reader.read_bytes(2)?.try_into().ok()?)),
+            "u32" => json!(reader.read_u32()?),
+            "i32" => json!(reader.read_u32()? as i32),
+            "u64" => json!(reader.read_u64()?),
+            "i64" => json!(reader.read_u64()? as i64),
+            "f32" => json!(f32::from_le_bytes(reader.read_bytes(4)?.try_into().ok()?)),
+            "f64" => json!(f64::from_le_bytes(reader.read_bytes(8)?.try_into().ok()?)),
+            // Too wide for JSON numbers; kept exact as strings.
+            "u128" => json!(u128::from_le_bytes(reader.read_bytes(16)?.try_into().ok()?).to_string()),
+            "i128" => json!(i128::from_le_bytes(reader.read_bytes(16)?.try_into().ok()?).to_string()),
+            "pubkey" | "publicKey" => json!(reader.read_pubkey()?),
+            "string" => {
+                let len = reader.read_u32()? as usize;
+                json!(String::from_utf8(reader.read_bytes(len)?.to_vec()).ok()?)
+            }
+            "bytes" => {
+                let len = reader.read_u32()? as usize;
+                json!(reader.read_bytes(len)?)
+            }
+            _ => return None,
+        });
+    }
+
+    if let Some(inner) = ty.get("vec") {
+        let len = reader.read_u32()? as usize;
+        let items: Option<Vec<Value>> = (0..len).map(|_| decode_type(reader, inner, types)).collect();
+        return Some(Value::Array(items?));
+    }
+    if let Some(inner) = ty.get("option") {
+        return match reader.read_u8()? {
+            0 => Some(Value::Null),
+            _ => decode_type(reader, inner, types),
+        };
+    }
+    // COption is a u32 tag followed by the value (always present).
+    if let Some(inner) = ty.get("coption") {
+        let tag = reader.read_u32()?;
+        let value = decode_type(reader, inner, types)?;
+        return Some(if tag == 0 { Value::Null } else { value });
+    }
+    if let Some(array) = ty.get("array").and_then(Value::as_array) {
+        let len = array.get(1)?.as_u64()? as usize;
+        let items: Option<Vec<Value>> = (0..len).map(|_| decode_type(reader, &array[0], types)).collect();
+        return Some(Value::Array(items?));
+    }
+    if let Some(defined) = ty.get("defined") {
+        let name = defined.as_str().or_else(|| defined["name"].as_str())?;
+        return decode_defined(reader, types.get(name)?, types);
+    }
+    None
+}
+
+fn decode_defined(reader: &mut DataReader, def: &Value, types: &HashMap<String, Value>) -> Option<Value> {
+    match def["kind"].as_str()? {
+        "struct" => decode_struct_fields(reader, &def["fields"], types),
+        "enum" => {
+            let variant = def["variants"].get(reader.read_u8()? as usize)?;
+            let name = variant["name"].as_str()?;
+            if variant.get("fields").is_none() {
+                return Some(json!(name));
+            }
+            Some(json!({ name: decode_struct_fields(reader, &variant["fields"], types)? }))
+        }
+        "alias" => decode_type(reader, &def["value"], types),
+        _ => None,
+    }
+}
+
+// Named fields decode to an object, tuple fields to an array.
+fn decode_struct_fields(reader: &mut DataReader, list: &Value, types: &HashMap<String, Value>) -> Option<Value> {
+    let list = match list.as_array() {
+        Some(list) => list,
+        None => return Some(Value::Object(Map::new())),
+    };
+    if list.iter().all(|f| f.get("name").is_some()) {
+        decode_fields(reader, &fields(&Value::Array(list.clone())).ok()?, types)
+    } else {
+        let items: Option<Vec<Value>> = list.iter().map(|ty| decode_type(reader, ty, types)).collect();
+        Some(Value::Array(items?))
+    }
+}
+
+fn idl_name(item: &Value) -> Result<String, ParseError> {
+    item["name"].as_str()
+        .map(str::to_string)
+        .ok_or_else(|| ParseError::InvalidIdl(format!("entry without a name: {}", item)))
+}
+
+fn discriminator(item: &Value) -> Option<[u8; 8]> {
+    let bytes: Vec<u8> = item["discriminator"].as_array()?
+        .iter()
+        .map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()))
+        .collect::<Option<_>>()?;
+    bytes.try_into().ok()
+}
+
+fn fields(list: &Value) -> Result<Vec<(String, Value)>, ParseError> {
+    list.as_array().into_iter().flatten()
+        .map(|field| Ok((idl_name(field)?, field["type"].clone())))
+        .collect()
+}
+
+// Account groups (composite accounts) are flattened in order.
+fn flatten_accounts(list: &Value, out: &mut Vec<String>) {
+    for account in list.as_array().into_iter().flatten() {
+        match account.get("accounts") {
+            Some(nested) => flatten_accounts(nested, out),
+            None => out.push(account["name"].as_str().unwrap_or_default().to_string()),
+        }
+    }
+}
+
+// Anchor's legacy discriminator: sha256("<namespace>:<name>")[..8].
+pub(crate) fn sighash(namespace: &str, name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("{}:{}", namespace, name).as_bytes());
+    hash[..8].try_into().unwrap()
+}
+
+pub(crate) fn to_snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDL: &str = r#"{
+        "address": "Prog111111111111111111111111111111111111111",
+        "instructions": [{
+            "name": "place_order",
+            "discriminator": [1, 2, 3, 4, 5, 6, 7, 8],
+            "accounts": [{ "name": "market" }, { "name": "user", "signer": true }],
+            "args": [
+                { "name": "amount", "type": "u64" },
+                { "name": "side", "type": { "defined": { "name": "Side" } } },
+                { "name": "memo", "type": { "option": "string" } }
+            ]
+        }],
+        "events": [{ "name": "OrderPlaced", "discriminator": [9, 9, 9, 9, 9, 9, 9, 9] }],
+        "types": [
+            { "name": "Side", "type": { "kind": "enum", "variants": [{ "name": "Bid" }, { "name": "Ask" }] } },
+            { "name": "OrderPlaced", "type": { "kind": "struct", "fields": [{ "name": "price", "type": "u32" }] } }
+        ]
+    }"#;
+
+    #[test]
+    fn decodes_instruction_args_and_events() {
+        let decoder = AnchorDecoder::from_idl_json(IDL).unwrap();
+
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        data.extend(42u64.to_le_bytes());
+        data.push(1);
+        data.extend([1, 2, 0, 0, 0, b'h', b'i']);
+
+        let Some(DecodedInstruction::Anchor(ix)) = decoder.decode(&data, &[Pubkey::default(); 3]) else {
+            panic!("instruction not decoded");
+        };
+        assert_eq!(ix.name, "place_order");
+        assert_eq!(ix.args, json!({ "amount": 42, "side": "Ask", "memo": "hi" }));
+        let names: Vec<&str> = ix.accounts.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["market", "user", "remaining_0"]);
+
+        let mut event = vec![9; 8];
+        event.extend(7u32.to_le_bytes());
+        let decoded = decoder.decode_event(&event).unwrap();
+        assert_eq!((decoded.name.as_str(), decoded.data), ("OrderPlaced", json!({ "price": 7 })));
+    }
+
+    #[test]
+    fn legacy_idl_derives_discriminators() {
+        let idl = r#"{
+            "metadata": { "address": "Prog111111111111111111111111111111111111111" },
+            "instructions": [{ "name": "initializePool", "accounts": [], "args": [{ "name": "fee", "type": "u16" }] }]
+        }"#;
+        let decoder = AnchorDecoder::from_idl_json(idl).unwrap();
+
+        let mut data = sighash("global", "initialize_pool").to_vec();
+        data.extend(30u16.to_le_bytes());
+        let Some(DecodedInstruction::Anchor(ix)) = decoder.decode(&data, &[]) else {
+            panic!("instruction not decoded");
+        };
+        assert_eq!(ix.args, json!({ "fee": 30 }));
+    }
+}
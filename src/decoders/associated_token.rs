@@ -0,0 +1,67 @@
+use super::{account, DecodedInstruction, ProgramDecoder};
+use crate::pubkey::Pubkey;
+use serde::{Deserialize, Serialize};
+
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = Pubkey::from_str_const("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AssociatedTokenInstruction {
+    Create {
+        payer: Pubkey,
+        associated_account: Pubkey,
+        wallet: Pubkey,
+        mint: Pubkey,
+    },
+    CreateIdempotent {
+        payer: Pubkey,
+        associated_account: Pubkey,
+        wallet: Pubkey,
+        mint: Pubkey,
+    },
+    RecoverNested {
+        nested_account: Pubkey,
+        nested_mint: Pubkey,
+        destination: Pubkey,
+        owner_account: Pubkey,
+        owner_mint: Pubkey,
+        wallet: Pubkey,
+    },
+}
+
+pub struct AssociatedTokenDecoder;
+
+impl ProgramDecoder for AssociatedTokenDecoder {
+    fn program_id(&self) -> Pubkey {
+        ASSOCIATED_TOKEN_PROGRAM_ID
+    }
+
+    // The original Create instruction carries no data at all; later variants
+    // are a single tag byte.
+    fn decode(&self, data: &[u8], accounts: &[Pubkey]) -> Option<DecodedInstruction> {
+        let instruction = match data.first().copied().unwrap_or(0) {
+            0 => AssociatedTokenInstruction::Create {
+                payer: account(accounts, 0)?,
+                associated_account: account(accounts, 1)?,
+                wallet: account(accounts, 2)?,
+                mint: account(accounts, 3)?,
+            },
+            1 => AssociatedTokenInstruction::CreateIdempotent {
+                payer: account(accounts, 0)?,
+                associated_account: account(accounts, 1)?,
+                wallet: account(accounts, 2)?,
+                mint: account(accounts, 3)?,
+            },
+            2 => AssociatedTokenInstruction::RecoverNested {
+                nested_account: account(accounts, 0)?,
+                nested_mint: account(accounts, 1)?,
+                destination: account(accounts, 2)?,
+                owner_account: account(accounts, 3)?,
+                owner_mint: account(accounts, 4)?,
+                wallet: account(accounts, 5)?,
+            },
+            _ => return None,
+        };
+        Some(DecodedInstruction::AssociatedToken(instruction))
+    }
+}
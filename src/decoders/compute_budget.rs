@@ -0,0 +1,53 @@
+use super::{DataReader, DecodedInstruction, ProgramDecoder};
+use crate::pubkey::Pubkey;
+use serde::{Deserialize, Serialize};
+
+pub const COMPUTE_BUDGET_PROGRAM_ID: Pubkey = Pubkey::from_str_const("ComputeBudget111111111111111111111111111111");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ComputeBudgetInstruction {
+    // Deprecated combined form of SetComputeUnitLimit and a flat fee.
+    RequestUnits {
+        units: u32,
+        additional_fee: u32,
+    },
+    RequestHeapFrame {
+        bytes: u32,
+    },
+    SetComputeUnitLimit {
+        units: u32,
+    },
+    SetComputeUnitPrice {
+        micro_lamports: u64,
+    },
+    SetLoadedAccountsDataSizeLimit {
+        bytes: u32,
+    },
+}
+
+pub struct ComputeBudgetDecoder;
+
+impl ProgramDecoder for ComputeBudgetDecoder {
+    fn program_id(&self) -> Pubkey {
+        COMPUTE_BUDGET_PROGRAM_ID
+    }
+
+    // The instructions take no accounts; data is a 1-byte tag followed by
+    // little-endian values.
+    fn decode(&self, data: &[u8], _accounts: &[Pubkey]) -> Option<DecodedInstruction> {
+        let mut reader = DataReader::new(data);
+        let instruction = match reader.read_u8()? {
+            0 => ComputeBudgetInstruction::RequestUnits {
+                units: reader.read_u32()?,
+                additional_fee: reader.read_u32()?,
+            },
+            1 => ComputeBudgetInstruction::RequestHeapFrame { bytes: reader.read_u32()? },
+            2 => ComputeBudgetInstruction::SetComputeUnitLimit { units: reader.read_u32()? },
+            3 => ComputeBudgetInstruction::SetComputeUnitPrice { micro_lamports: reader.read_u64()? },
+            4 => ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit { bytes: reader.read_u32()? },
+            _ => return None,
+        };
+        Some(DecodedInstruction::ComputeBudget(instruction))
+    }
+}
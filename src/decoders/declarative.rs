@@ -0,0 +1,216 @@
+use super::anchor::{decode_fields, sighash, to_snake_case};
+use super::{AnchorAccount, DataReader, DecodedInstruction, ProgramDecoder};
+use crate::encoding::DataEncoding;
+use crate::error::ParseError;
+use crate::pubkey::Pubkey;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+// ==========================================
+// DECLARATIVE DECODERS
+// ==========================================
+// Decoders written as data rather than code, for programs without a
+// built-in decoder or an Anchor IDL: a program ID, and per instruction a
+// discriminator, account names and a borsh field layout. Field types use
+// the IDL vocabulary (u8..u128, i8..i128, f32, f64, bool, pubkey, string,
+// bytes) plus option<T>, vec<T> and [T; N]. Loaded from the config file's
+// [[decoders]] tables or a --decoders file:
+//
+//     [[decoders]]
+//     name = "vault"
+//     program_id = "Vau1t11111111111111111111111111111111111111"
+//
+//     [[decoders.instructions]]
+//     name = "deposit"
+//     discriminator = "0x02"
+//     accounts = ["vault", "owner"]
+//     fields = [{ name = "amount", type = "u64" }, { name = "memo", type = "option<string>" }]
+//
+// A discriminator is 0x-prefixed hex, base58, an array of bytes, or
+// "anchor" for the sighash of the instruction name. The longest matching
+// one wins; an empty one matches any data the others don't.
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DecoderSpec {
+    // Shown as `program` on the decoded instructions.
+    pub name: String,
+    pub program_id: Pubkey,
+    #[serde(default)]
+    pub instructions: Vec<InstructionSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstructionSpec {
+    pub name: String,
+    pub discriminator: DiscriminatorSpec,
+    // Accounts in instruction order; any past these are named by position.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    #[serde(default)]
+    pub fields: Vec<FieldSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum DiscriminatorSpec {
+    Bytes(Vec<u8>),
+    // 0x-prefixed hex, base58, or "anchor".
+    Text(String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldSpec {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomInstruction {
+    pub program: String,
+    pub name: String,
+    pub accounts: Vec<AnchorAccount>,
+    pub fields: Value,
+}
+
+struct Layout {
+    name: String,
+    discriminator: Vec<u8>,
+    accounts: Vec<String>,
+    // Field types in IDL JSON form.
+    fields: Vec<(String, Value)>,
+}
+
+pub struct DeclarativeDecoder {
+    name: String,
+    program_id: Pubkey,
+    // Longest discriminator first.
+    instructions: Vec<Layout>,
+}
+
+impl DeclarativeDecoder {
+    pub fn new(spec: DecoderSpec) -> Result<Self, ParseError> {
+        let invalid = |message: String| ParseError::InvalidDecoder(format!("{}: {}", spec.name, message));
+        let mut instructions = Vec::new();
+        for ix in &spec.instructions {
+            let discriminator = match &ix.discriminator {
+                DiscriminatorSpec::Bytes(bytes) => Some(bytes.clone()),
+                DiscriminatorSpec::Text(text) if text == "anchor" => Some(sighash("global", &to_snake_case(&ix.name)).to_vec()),
+                DiscriminatorSpec::Text(text) => match text.strip_prefix("0x") {
+                    Some(hex) => DataEncoding::Hex.decode(hex),
+                    None => DataEncoding::Base58.decode(text),
+                },
+            }
+            .ok_or_else(|| invalid(format!("instruction {}: invalid discriminator", ix.name)))?;
+            let fields = ix
+                .fields
+                .iter()
+                .map(|field| {
+                    let ty = field_type(&field.ty)
+                        .ok_or_else(|| invalid(format!("instruction {}: field {}: unknown type '{}'", ix.name, field.name, field.ty)))?;
+                    Ok((field.name.clone(), ty))
+                })
+                .collect::<Result<_, ParseError>>()?;
+            if instructions.iter().any(|other: &Layout| other.discriminator == discriminator) {
+                return Err(invalid(format!("instruction {}: discriminator already used", ix.name)));
+            }
+            instructions.push(Layout { name: ix.name.clone(), discriminator, accounts: ix.accounts.clone(), fields });
+        }
+        instructions.sort_by_key(|ix| Reverse(ix.discriminator.len()));
+        Ok(DeclarativeDecoder { name: spec.name, program_id: spec.program_id, instructions })
+    }
+}
+
+impl ProgramDecoder for DeclarativeDecoder {
+    fn program_id(&self) -> Pubkey {
+        self.program_id
+    }
+
+    fn decode(&self, data: &[u8], accounts: &[Pubkey]) -> Option<DecodedInstruction> {
+        let ix = self.instructions.iter().find(|ix| data.starts_with(&ix.discriminator))?;
+        let mut reader = DataReader::new(&data[ix.discriminator.len()..]);
+        let fields = decode_fields(&mut reader, &ix.fields, &HashMap::new())?;
+        let accounts = accounts
+            .iter()
+            .enumerate()
+            .map(|(i, pubkey)| AnchorAccount {
+                name: ix.accounts.get(i).cloned().unwrap_or_else(|| format!("remaining_{}", i - ix.accounts.len())),
+                pubkey: *pubkey,
+            })
+            .collect();
+        Some(DecodedInstruction::Custom(CustomInstruction { program: self.name.clone(), name: ix.name.clone(), accounts, fields }))
+    }
+}
+
+const PRIMITIVES: &[&str] =
+    &["bool", "u8", "i8", "u16", "i16", "u32", "i32", "u64", "i64", "u128", "i128", "f32", "f64", "pubkey", "string", "bytes"];
+
+// "option<vec<u64>>" -> {"option": {"vec": "u64"}}, as in an IDL.
+fn field_type(ty: &str) -> Option<Value> {
+    let ty = ty.trim();
+    if let Some(inner) = ty.strip_prefix("option<").and_then(|rest| rest.strip_suffix('>')) {
+        return Some(json!({ "option": field_type(inner)? }));
+    }
+    if let Some(inner) = ty.strip_prefix("vec<").and_then(|rest| rest.strip_suffix('>')) {
+        return Some(json!({ "vec": field_type(inner)? }));
+    }
+    if let Some(inner) = ty.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+        let (item, len) = inner.rsplit_once(';')?;
+        return Some(json!({ "array": [field_type(item)?, len.trim().parse::<usize>().ok()?] }));
+    }
+    PRIMITIVES.contains(&ty).then(|| json!(ty))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_fields_from_a_config_layout() {
+        let spec: DecoderSpec = serde_json::from_value(json!({
+            "name": "vault",
+            "program_id": "Vau1t11111111111111111111111111111111111111",
+            "instructions": [
+                { "name": "deposit", "discriminator": "0x02", "accounts": ["vault", "owner"],
+                  "fields": [{ "name": "amount", "type": "u64" }, { "name": "memo", "type": "option<string>" }] },
+                { "name": "set_keys", "discriminator": [2, 7], "fields": [{ "name": "keys", "type": "vec<[u8; 2]>" }] },
+                { "name": "close", "discriminator": "anchor" }
+            ]
+        }))
+        .unwrap();
+        let decoder = DeclarativeDecoder::new(spec).unwrap();
+
+        let mut data = vec![2];
+        data.extend(5u64.to_le_bytes());
+        data.extend([1, 2, 0, 0, 0, b'h', b'i']);
+        let Some(DecodedInstruction::Custom(ix)) = decoder.decode(&data, &[Pubkey::default(); 3]) else {
+            panic!("instruction not decoded");
+        };
+        assert_eq!((ix.program.as_str(), ix.name.as_str()), ("vault", "deposit"));
+        assert_eq!(ix.fields, json!({ "amount": 5, "memo": "hi" }));
+        let names: Vec<&str> = ix.accounts.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["vault", "owner", "remaining_0"]);
+
+        // The longer discriminator wins over "0x02".
+        let Some(DecodedInstruction::Custom(ix)) = decoder.decode(&[2, 7, 1, 0, 0, 0, 9, 8], &[]) else {
+            panic!("instruction not decoded");
+        };
+        assert_eq!((ix.name.as_str(), ix.fields), ("set_keys", json!({ "keys": [[9, 8]] })));
+        let Some(DecodedInstruction::Custom(ix)) = decoder.decode(&sighash("global", "close"), &[]) else {
+            panic!("instruction not decoded");
+        };
+        assert_eq!(ix.name, "close");
+        // Too short for the layout, or no matching discriminator.
+        assert!(decoder.decode(&[2, 1], &[]).is_none());
+        assert!(decoder.decode(&[9], &[]).is_none());
+
+        let bad = json!({ "name": "x", "program_id": "Vau1t11111111111111111111111111111111111111",
+            "instructions": [{ "name": "a", "discriminator": [1], "fields": [{ "name": "f", "type": "u256" }] }] });
+        assert!(DeclarativeDecoder::new(serde_json::from_value(bad).unwrap()).is_err());
+    }
+}
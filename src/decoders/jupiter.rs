@@ -0,0 +1,398 @@
+use super::anchor::sighash;
+use super::{account, DataReader, DecodedInstruction, ProgramDecoder};
+use crate::pubkey::Pubkey;
+use serde::{Deserialize, Serialize};
+
+pub const JUPITER_V6_PROGRAM_ID: Pubkey = Pubkey::from_str_const("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+
+// Anchor's event CPI: the program invokes itself with this tag, then the
+// event's discriminator and fields, so events survive log truncation.
+const EVENT_IX_TAG: [u8; 8] = [0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JupiterInstruction {
+    // One of the route instructions. Exact-in routes carry in_amount and
+    // the quoted output, exact-out routes out_amount and the quoted input;
+    // token ledger routes take the input amount from the ledger account.
+    Route {
+        name: String,
+        in_amount: Option<u64>,
+        quoted_out_amount: Option<u64>,
+        out_amount: Option<u64>,
+        quoted_in_amount: Option<u64>,
+        slippage_bps: u16,
+        platform_fee_bps: u16,
+        // None when a hop uses a swap variant this decoder can't size.
+        route_plan: Option<Vec<RoutePlanStep>>,
+        user_transfer_authority: Option<Pubkey>,
+        // The plain route instructions name only the output mint.
+        source_mint: Option<Pubkey>,
+        destination_mint: Option<Pubkey>,
+    },
+    // What each hop actually swapped, from the program's event CPI. Older
+    // versions emit one SwapEvent per hop naming the AMM program; newer
+    // ones one SwapsEvent for the whole route, without it.
+    SwapEvent {
+        swaps: Vec<JupiterSwap>,
+    },
+    FeeEvent {
+        account: Pubkey,
+        mint: Pubkey,
+        amount: u64,
+    },
+    Other {
+        name: String,
+    },
+}
+
+// A hop in the quoted route. `input_index` / `output_index` number the
+// token accounts the route passes through, 0 being the user's source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutePlanStep {
+    // The AMM, as Jupiter's `Swap` enum names it ("Whirlpool",
+    // "MeteoraDlmm", ...); None for variants newer than this decoder.
+    pub swap: Option<String>,
+    pub swap_index: u8,
+    // Share of the input routed through this hop, in basis points.
+    pub share_bps: u16,
+    pub input_index: u8,
+    pub output_index: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JupiterSwap {
+    pub amm: Option<Pubkey>,
+    pub input_mint: Pubkey,
+    pub input_amount: u64,
+    pub output_mint: Pubkey,
+    pub output_amount: u64,
+}
+
+pub struct JupiterDecoder;
+
+impl ProgramDecoder for JupiterDecoder {
+    fn program_id(&self) -> Pubkey {
+        JUPITER_V6_PROGRAM_ID
+    }
+
+    fn decode(&self, data: &[u8], accounts: &[Pubkey]) -> Option<DecodedInstruction> {
+        let discriminator = data.get(..8)?;
+        let instruction = if discriminator == EVENT_IX_TAG {
+            decode_event(&data[8..])?
+        } else if let Some(route) = ROUTES.iter().find(|route| sighash("global", route.name) == discriminator) {
+            decode_route(route, &data[8..], accounts)?
+        } else {
+            let name = OTHER_INSTRUCTIONS.iter().find(|name| sighash("global", name) == discriminator)?;
+            JupiterInstruction::Other { name: name.to_string() }
+        };
+        Some(DecodedInstruction::Jupiter(instruction))
+    }
+}
+
+// ==========================================
+// ROUTE INSTRUCTIONS
+// ==========================================
+// The v1 instructions put the route plan first and the amounts after it;
+// the v2 ones put the amounts first, size each hop in basis points rather
+// than percent and charge the platform fee in u16 basis points.
+
+struct Route {
+    name: &'static str,
+    v2: bool,
+    // Shared-accounts routes start with a u8 program authority id.
+    shared: bool,
+    exact_out: bool,
+    token_ledger: bool,
+    authority: usize,
+    source_mint: Option<usize>,
+    destination_mint: usize,
+}
+
+const ROUTES: &[Route] = &[
+    Route { name: "route", v2: false, shared: false, exact_out: false, token_ledger: false, authority: 1, source_mint: None, destination_mint: 5 },
+    Route { name: "route_with_token_ledger", v2: false, shared: false, exact_out: false, token_ledger: true, authority: 1, source_mint: None, destination_mint: 5 },
+    Route { name: "exact_out_route", v2: false, shared: false, exact_out: true, token_ledger: false, authority: 1, source_mint: Some(5), destination_mint: 6 },
+    Route { name: "shared_accounts_route", v2: false, shared: true, exact_out: false, token_ledger: false, authority: 2, source_mint: Some(7), destination_mint: 8 },
+    Route { name: "shared_accounts_route_with_token_ledger", v2: false, shared: true, exact_out: false, token_ledger: true, authority: 2, source_mint: Some(7), destination_mint: 8 },
+    Route { name: "shared_accounts_exact_out_route", v2: false, shared: true, exact_out: true, token_ledger: false, authority: 2, source_mint: Some(7), destination_mint: 8 },
+    Route { name: "route_v2", v2: true, shared: false, exact_out: false, token_ledger: false, authority: 0, source_mint: Some(3), destination_mint: 4 },
+    Route { name: "exact_out_route_v2", v2: true, shared: false, exact_out: true, token_ledger: false, authority: 0, source_mint: Some(3), destination_mint: 4 },
+    Route { name: "shared_accounts_route_v2", v2: true, shared: true, exact_out: false, token_ledger: false, authority: 1, source_mint: Some(6), destination_mint: 7 },
+    Route { name: "shared_accounts_exact_out_route_v2", v2: true, shared: true, exact_out: true, token_ledger: false, authority: 1, source_mint: Some(6), destination_mint: 7 },
+];
+
+const OTHER_INSTRUCTIONS: &[&str] =
+    &["claim", "claim_token", "close_token", "create_token_account", "create_token_ledger", "set_token_ledger"];
+
+fn decode_route(route: &Route, data: &[u8], accounts: &[Pubkey]) -> Option<JupiterInstruction> {
+    let mut reader = DataReader::new(data);
+    if route.shared {
+        reader.read_u8()?;
+    }
+    let (amount, quoted, slippage_bps, platform_fee_bps, route_plan) = if route.v2 {
+        let amount = reader.read_u64()?;
+        let quoted = reader.read_u64()?;
+        let slippage_bps = reader.read_u16()?;
+        let platform_fee_bps = reader.read_u16()?;
+        let _positive_slippage_bps = reader.read_u16()?;
+        let route_plan = read_route_plan(reader.remaining(), true);
+        (Some(amount), quoted, slippage_bps, platform_fee_bps, route_plan)
+    } else {
+        // The amounts are fixed-size, so they're read from the end and
+        // what's left before them is the route plan.
+        let tail = if route.token_ledger { 8 + 2 + 1 } else { 8 + 8 + 2 + 1 };
+        let plan = reader.remaining().len().checked_sub(tail)?;
+        let route_plan = read_route_plan(reader.read_bytes(plan)?, false);
+        let amount = if route.token_ledger { None } else { Some(reader.read_u64()?) };
+        let quoted = reader.read_u64()?;
+        (amount, quoted, reader.read_u16()?, reader.read_u8()? as u16, route_plan)
+    };
+    let (in_amount, quoted_out_amount, out_amount, quoted_in_amount) =
+        if route.exact_out { (None, None, amount, Some(quoted)) } else { (amount, Some(quoted), None, None) };
+    Some(JupiterInstruction::Route {
+        name: route.name.to_string(),
+        in_amount,
+        quoted_out_amount,
+        out_amount,
+        quoted_in_amount,
+        slippage_bps,
+        platform_fee_bps,
+        route_plan,
+        user_transfer_authority: account(accounts, route.authority),
+        source_mint: route.source_mint.and_then(|position| account(accounts, position)),
+        destination_mint: account(accounts, route.destination_mint),
+    })
+}
+
+// `data` must be exactly the serialized Vec<RoutePlanStep>; None if it
+// doesn't parse to the end. A variant this decoder doesn't know is only
+// sized when it's the last hop, from what's left over.
+fn read_route_plan(data: &[u8], v2: bool) -> Option<Vec<RoutePlanStep>> {
+    let mut reader = DataReader::new(data);
+    let len = reader.read_u32()?;
+    let mut steps = Vec::new();
+    for i in 0..len {
+        let swap_index = reader.read_u8()?;
+        let trailer = if v2 { 2 + 1 + 1 } else { 1 + 1 + 1 };
+        let swap = match SWAPS.get(swap_index as usize) {
+            Some((name, payload)) => {
+                skip_payload(&mut reader, *payload)?;
+                Some(name.to_string())
+            }
+            None if i + 1 == len => {
+                reader.read_bytes(reader.remaining().len().checked_sub(trailer)?)?;
+                None
+            }
+            None => return None,
+        };
+        let share_bps = if v2 { reader.read_u16()? } else { reader.read_u8()? as u16 * 100 };
+        steps.push(RoutePlanStep { swap, swap_index, share_bps, input_index: reader.read_u8()?, output_index: reader.read_u8()? });
+    }
+    reader.remaining().is_empty().then_some(steps)
+}
+
+#[derive(Clone, Copy)]
+enum Payload {
+    Bytes(usize),
+    // RemainingAccountsInfo: a Vec of (accounts type, length) byte pairs.
+    RemainingAccounts,
+    // A direction flag followed by an optional RemainingAccountsInfo.
+    FlagAndOptionalRemainingAccounts,
+}
+
+fn skip_payload(reader: &mut DataReader, payload: Payload) -> Option<()> {
+    let skip_remaining_accounts = |reader: &mut DataReader| {
+        let slices = reader.read_u32()? as usize;
+        reader.read_bytes(slices.checked_mul(2)?).map(|_| ())
+    };
+    match payload {
+        Payload::Bytes(len) => reader.read_bytes(len).map(|_| ()),
+        Payload::RemainingAccounts => skip_remaining_accounts(reader),
+        Payload::FlagAndOptionalRemainingAccounts => {
+            reader.read_u8()?;
+            match reader.read_u8()? {
+                0 => Some(()),
+                1 => skip_remaining_accounts(reader),
+                _ => None,
+            }
+        }
+    }
+}
+
+// Jupiter v6's `Swap` enum, by variant index, with the size of each
+// variant's fields. Side and direction flags are one byte.
+const SWAPS: &[(&str, Payload)] = &[
+    ("Saber", Payload::Bytes(0)),
+    ("SaberAddDecimalsDeposit", Payload::Bytes(0)),
+    ("SaberAddDecimalsWithdraw", Payload::Bytes(0)),
+    ("TokenSwap", Payload::Bytes(0)),
+    ("Sencha", Payload::Bytes(0)),
+    ("Step", Payload::Bytes(0)),
+    ("Cropper", Payload::Bytes(0)),
+    ("Raydium", Payload::Bytes(0)),
+    ("Crema", Payload::Bytes(1)),
+    ("Lifinity", Payload::Bytes(0)),
+    ("Mercurial", Payload::Bytes(0)),
+    ("Cykura", Payload::Bytes(0)),
+    ("Serum", Payload::Bytes(1)),
+    ("MarinadeDeposit", Payload::Bytes(0)),
+    ("MarinadeUnstake", Payload::Bytes(0)),
+    ("Aldrin", Payload::Bytes(1)),
+    ("AldrinV2", Payload::Bytes(1)),
+    ("Whirlpool", Payload::Bytes(1)),
+    ("Invariant", Payload::Bytes(1)),
+    ("Meteora", Payload::Bytes(0)),
+    ("GooseFX", Payload::Bytes(0)),
+    ("DeltaFi", Payload::Bytes(1)),
+    ("Balansol", Payload::Bytes(0)),
+    ("MarcoPolo", Payload::Bytes(1)),
+    ("Dradex", Payload::Bytes(1)),
+    ("LifinityV2", Payload::Bytes(0)),
+    ("RaydiumClmm", Payload::Bytes(0)),
+    ("Openbook", Payload::Bytes(1)),
+    ("Phoenix", Payload::Bytes(1)),
+    ("Symmetry", Payload::Bytes(16)),
+    ("TokenSwapV2", Payload::Bytes(0)),
+    ("HeliumTreasuryManagementRedeemV0", Payload::Bytes(0)),
+    ("StakeDexStakeWrappedSol", Payload::Bytes(0)),
+    ("StakeDexSwapViaStake", Payload::Bytes(4)),
+    ("GooseFXV2", Payload::Bytes(0)),
+    ("Perps", Payload::Bytes(0)),
+    ("PerpsAddLiquidity", Payload::Bytes(0)),
+    ("PerpsRemoveLiquidity", Payload::Bytes(0)),
+    ("MeteoraDlmm", Payload::Bytes(0)),
+    ("OpenBookV2", Payload::Bytes(1)),
+    ("RaydiumClmmV2", Payload::Bytes(0)),
+    ("StakeDexPrefundWithdrawStakeAndDepositStake", Payload::Bytes(4)),
+    ("Clone", Payload::Bytes(3)),
+    ("SanctumS", Payload::Bytes(10)),
+    ("SanctumSAddLiquidity", Payload::Bytes(5)),
+    ("SanctumSRemoveLiquidity", Payload::Bytes(5)),
+    ("RaydiumCP", Payload::Bytes(0)),
+    ("WhirlpoolSwapV2", Payload::FlagAndOptionalRemainingAccounts),
+    ("OneIntro", Payload::Bytes(0)),
+    ("PumpdotfunWrappedBuy", Payload::Bytes(0)),
+    ("PumpdotfunWrappedSell", Payload::Bytes(0)),
+    ("PerpsV2", Payload::Bytes(0)),
+    ("PerpsV2AddLiquidity", Payload::Bytes(0)),
+    ("PerpsV2RemoveLiquidity", Payload::Bytes(0)),
+    ("MoonshotWrappedBuy", Payload::Bytes(0)),
+    ("MoonshotWrappedSell", Payload::Bytes(0)),
+    ("StabbleStableSwap", Payload::Bytes(0)),
+    ("StabbleWeightedSwap", Payload::Bytes(0)),
+    ("Obric", Payload::Bytes(1)),
+    ("FoxBuyFromEstimatedCost", Payload::Bytes(0)),
+    ("FoxClaimPartial", Payload::Bytes(1)),
+    ("SolFi", Payload::Bytes(1)),
+    ("SolayerDelegateNoInit", Payload::Bytes(0)),
+    ("SolayerUndelegateNoInit", Payload::Bytes(0)),
+    ("TokenMill", Payload::Bytes(1)),
+    ("DaosFunBuy", Payload::Bytes(0)),
+    ("DaosFunSell", Payload::Bytes(0)),
+    ("ZeroFi", Payload::Bytes(0)),
+    ("StakeDexWithdrawWrappedSol", Payload::Bytes(0)),
+    ("VirtualsBuy", Payload::Bytes(0)),
+    ("VirtualsSell", Payload::Bytes(0)),
+    ("Perena", Payload::Bytes(2)),
+    ("PumpdotfunAmmBuy", Payload::Bytes(0)),
+    ("PumpdotfunAmmSell", Payload::Bytes(0)),
+    ("Gamma", Payload::Bytes(0)),
+    ("MeteoraDlmmSwapV2", Payload::RemainingAccounts),
+    ("Woofi", Payload::Bytes(0)),
+    ("MeteoraDammV2", Payload::Bytes(0)),
+    ("MeteoraDynamicBondingCurveSwap", Payload::Bytes(0)),
+    ("StabbleStableSwapV2", Payload::Bytes(0)),
+    ("StabbleWeightedSwapV2", Payload::Bytes(0)),
+    ("RaydiumLaunchlabBuy", Payload::Bytes(8)),
+    ("RaydiumLaunchlabSell", Payload::Bytes(8)),
+    ("BoopdotfunWrappedBuy", Payload::Bytes(0)),
+    ("BoopdotfunWrappedSell", Payload::Bytes(0)),
+    ("Plasma", Payload::Bytes(1)),
+    ("GoonFi", Payload::Bytes(2)),
+    ("HumidiFi", Payload::Bytes(9)),
+    ("MeteoraDynamicBondingCurveSwapWithRemainingAccounts", Payload::Bytes(0)),
+    ("TesseraV", Payload::Bytes(1)),
+];
+
+// ==========================================
+// EVENTS
+// ==========================================
+
+fn decode_event(data: &[u8]) -> Option<JupiterInstruction> {
+    let mut reader = DataReader::new(data);
+    let discriminator = reader.read_bytes(8)?;
+    let read_swap = |reader: &mut DataReader, amm: Option<Pubkey>| {
+        Some(JupiterSwap {
+            amm,
+            input_mint: reader.read_pubkey()?,
+            input_amount: reader.read_u64()?,
+            output_mint: reader.read_pubkey()?,
+            output_amount: reader.read_u64()?,
+        })
+    };
+    if discriminator == sighash("event", "SwapEvent") {
+        let amm = reader.read_pubkey()?;
+        Some(JupiterInstruction::SwapEvent { swaps: vec![read_swap(&mut reader, Some(amm))?] })
+    } else if discriminator == sighash("event", "SwapsEvent") {
+        let len = reader.read_u32()?;
+        let swaps = (0..len).map(|_| read_swap(&mut reader, None)).collect::<Option<Vec<_>>>()?;
+        Some(JupiterInstruction::SwapEvent { swaps })
+    } else if discriminator == sighash("event", "FeeEvent") {
+        Some(JupiterInstruction::FeeEvent { account: reader.read_pubkey()?, mint: reader.read_pubkey()?, amount: reader.read_u64()? })
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn decodes_routes_and_swap_events_in_a_block() {
+        let block = fixtures::block();
+        let decoded = |signature: &str| -> Vec<JupiterInstruction> {
+            let tx = block.get_transaction(signature).unwrap();
+            let inner = tx.inner_instructions.iter().flat_map(|inner| &inner.instructions);
+            tx.instructions
+                .iter()
+                .chain(inner)
+                .filter_map(|ix| match &ix.decoded {
+                    Some(DecodedInstruction::Jupiter(instruction)) => Some(instruction.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // A v1 route through Meteora DAMM v2, with a SwapEvent naming the pool program.
+        let route = decoded("4ABEoFeVZen9VzVsQJaaS4vPugMYorZDXpwT1Hkmc7PGn4hqjUjrmiVLeDvq2MU5jsrH8VERK1cXtbWXBHJKtfm1");
+        let JupiterInstruction::Route { name, in_amount, route_plan, destination_mint, .. } = &route[0] else {
+            panic!("{:?}", route);
+        };
+        assert_eq!((name.as_str(), *in_amount), ("route", Some(87_951_302)));
+        let step = &route_plan.as_ref().unwrap()[0];
+        assert_eq!((step.swap.as_deref(), step.share_bps, step.input_index, step.output_index), (Some("MeteoraDammV2"), 10_000, 0, 1));
+        let JupiterInstruction::SwapEvent { swaps } = &route[1] else {
+            panic!("{:?}", route);
+        };
+        assert_eq!(swaps[0].amm, Some(crate::dex::METEORA_DAMM_V2_PROGRAM_ID));
+        assert_eq!((swaps[0].input_amount, swaps[0].output_amount), (87_951_302, 17_652_869_070));
+        assert_eq!(Some(swaps[0].output_mint), *destination_mint);
+
+        // route_v2 names both mints and reports the whole route in one SwapsEvent.
+        let tx = block.transactions.iter().find(|tx| tx.signature.starts_with("3XP1xuhenR")).unwrap();
+        let route = decoded(&tx.signature);
+        let JupiterInstruction::Route { name, in_amount, source_mint, destination_mint, slippage_bps, route_plan, .. } = &route[0] else {
+            panic!("{:?}", route);
+        };
+        assert_eq!((name.as_str(), *slippage_bps), ("route_v2", 100));
+        assert_eq!(route_plan.as_ref().unwrap()[0].share_bps, 10_000);
+        let JupiterInstruction::SwapEvent { swaps } = &route[1] else {
+            panic!("{:?}", route);
+        };
+        assert_eq!(Some(swaps[0].input_amount), *in_amount);
+        assert_eq!((Some(swaps[0].input_mint), Some(swaps[0].output_mint)), (*source_mint, *destination_mint));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::pubkey::Pubkey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+pub mod anchor;
+pub mod associated_token;
+pub mod compute_budget;
+pub mod declarative;
+pub mod jupiter;
+pub mod raydium;
+pub mod stake;
+pub mod system;
+pub mod token;
+pub mod token_metadata;
+pub mod vote;
+
+pub use anchor::{AnchorAccount, AnchorDecoder, AnchorInstruction};
+pub use associated_token::{AssociatedTokenDecoder, AssociatedTokenInstruction};
+pub use compute_budget::{ComputeBudgetDecoder, ComputeBudgetInstruction};
+pub use declarative::{CustomInstruction, DecoderSpec, DeclarativeDecoder};
+pub use jupiter::{JupiterDecoder, JupiterInstruction};
+pub use raydium::{RaydiumAmmV4Decoder, RaydiumInstruction, RaydiumSwapAccounts};
+pub use stake::{StakeDecoder, StakeInstruction};
+pub use system::{SystemDecoder, SystemInstruction};
+pub use token::{Token2022Decoder, TokenDecoder, TokenInstruction};
+pub use token_metadata::{TokenMetadataDecoder, TokenMetadataInstruction};
+pub use vote::{Lockout, VoteDecoder, VoteInstruction};
+
+// ==========================================
+// DECODER FRAMEWORK
+// ==========================================
+
+// Typed view of an instruction from a program we know how to decode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecodedInstruction {
+    System(SystemInstruction),
+    Token(TokenInstruction),
+    TokenMetadata(TokenMetadataInstruction),
+    Stake(StakeInstruction),
+    Vote(VoteInstruction),
+    AssociatedToken(AssociatedTokenInstruction),
+    ComputeBudget(ComputeBudgetInstruction),
+    Raydium(RaydiumInstruction),
+    Jupiter(JupiterInstruction),
+    Anchor(AnchorInstruction),
+    // From a declarative decoder (see declarative).
+    Custom(CustomInstruction),
+}
+
+// A program event decoded from a "Program data:" log payload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecodedEvent {
+    pub program_id: Pubkey,
+    pub name: String,
+    pub data: serde_json::Value,
+}
+
+pub trait ProgramDecoder: Send + Sync {
+    fn program_id(&self) -> Pubkey;
+
+    // `data` is the raw instruction data; `accounts` are the instruction's
+    // accounts already resolved to addresses, in instruction order.
+    fn decode(&self, data: &[u8], accounts: &[Pubkey]) -> Option<DecodedInstruction>;
+
+    // `data` is a decoded "Program data:" payload emitted by this program.
+    fn decode_event(&self, _data: &[u8]) -> Option<DecodedEvent> {
+        None
+    }
+
+    fn decodes_events(&self) -> bool {
+        false
+    }
+
+    // Name of a `Custom(code)` instruction error raised by this program.
+    fn error_name(&self, _code: u32) -> Option<String> {
+        None
+    }
+}
+
+// Program ID -> decoder lookup used by the parser.
+#[derive(Default)]
+pub struct DecoderRegistry {
+    decoders: HashMap<Pubkey, Box<dyn ProgramDecoder>>,
+}
+
+impl DecoderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(SystemDecoder));
+        registry.register(Box::new(TokenDecoder));
+        registry.register(Box::new(Token2022Decoder));
+        registry.register(Box::new(StakeDecoder));
+        registry.register(Box::new(VoteDecoder));
+        registry.register(Box::new(AssociatedTokenDecoder));
+        registry.register(Box::new(TokenMetadataDecoder));
+        registry.register(Box::new(ComputeBudgetDecoder));
+        registry.register(Box::new(RaydiumAmmV4Decoder));
+        registry.register(Box::new(JupiterDecoder));
+        registry
+    }
+
+    // Shared registry with the built-in decoders, used by ParseOptions::default().
+    pub fn builtins() -> Arc<DecoderRegistry> {
+        static BUILTINS: OnceLock<Arc<DecoderRegistry>> = OnceLock::new();
+        BUILTINS.get_or_init(|| Arc::new(Self::with_builtins())).clone()
+    }
+
+    // Replaces any decoder already registered for the same program ID.
+    pub fn register(&mut self, decoder: Box<dyn ProgramDecoder>) {
+        self.decoders.insert(decoder.program_id(), decoder);
+    }
+
+    pub fn get(&self, program_id: &Pubkey) -> Option<&dyn ProgramDecoder> {
+        self.decoders.get(program_id).map(|d| d.as_ref())
+    }
+
+    pub fn decode(&self, program_id: &Pubkey, data: &[u8], accounts: &[Pubkey]) -> Option<DecodedInstruction> {
+        self.get(program_id)?.decode(data, accounts)
+    }
+
+    pub fn decode_event(&self, program_id: &Pubkey, data: &[u8]) -> Option<DecodedEvent> {
+        self.get(program_id)?.decode_event(data)
+    }
+
+    pub fn error_name(&self, program_id: &Pubkey, code: u32) -> Option<String> {
+        self.get(program_id)?.error_name(code)
+    }
+
+    // Lets the parser skip log parsing when nobody would consume the events.
+    pub fn decodes_events(&self) -> bool {
+        self.decoders.values().any(|d| d.decodes_events())
+    }
+}
+
+impl fmt::Debug for DecoderRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut programs: Vec<String> = self.decoders.keys().map(|key| key.to_string()).collect();
+        programs.sort();
+        f.debug_struct("DecoderRegistry").field("programs", &programs).finish()
+    }
+}
+
+// ==========================================
+// INSTRUCTION DATA READER
+// ==========================================
+
+// Little-endian cursor over instruction data. Every read returns None once
+// the data runs out, so decoders can bail with `?`.
+pub struct DataReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> DataReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        DataReader { data, pos: 0 }
+    }
+
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos..]
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Option<u8> {
+        Some(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.read_bytes(2)?.try_into().ok()?))
+    }
+
+    pub fn read_u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.read_bytes(4)?.try_into().ok()?))
+    }
+
+    pub fn read_u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.read_bytes(8)?.try_into().ok()?))
+    }
+
+    pub fn read_pubkey(&mut self) -> Option<Pubkey> {
+        Pubkey::try_from(self.read_bytes(32)?).ok()
+    }
+
+    // bincode String: u64 length prefix, then UTF-8 bytes.
+    pub fn read_string(&mut self) -> Option<String> {
+        let len = usize::try_from(self.read_u64()?).ok()?;
+        String::from_utf8(self.read_bytes(len)?.to_vec()).ok()
+    }
+}
+
+// Account at `position` in the instruction's account list.
+pub(crate) fn account(accounts: &[Pubkey], position: usize) -> Option<Pubkey> {
+    accounts.get(position).copied()
+}
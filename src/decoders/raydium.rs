@@ -0,0 +1,110 @@
+use super::{account, DataReader, DecodedInstruction, ProgramDecoder};
+use crate::pubkey::Pubkey;
+use serde::{Deserialize, Serialize};
+
+pub const RAYDIUM_AMM_V4_PROGRAM_ID: Pubkey = Pubkey::from_str_const("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RaydiumInstruction {
+    SwapBaseIn {
+        amount_in: u64,
+        minimum_amount_out: u64,
+        accounts: RaydiumSwapAccounts,
+    },
+    SwapBaseOut {
+        max_amount_in: u64,
+        amount_out: u64,
+        accounts: RaydiumSwapAccounts,
+    },
+    Other {
+        name: String,
+    },
+}
+
+// Named accounts for SwapBaseIn/SwapBaseOut. `target_orders` is absent in
+// the 17-account form of the instruction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RaydiumSwapAccounts {
+    pub amm: Pubkey,
+    pub amm_authority: Pubkey,
+    pub open_orders: Pubkey,
+    pub target_orders: Option<Pubkey>,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub market_program: Pubkey,
+    pub market: Pubkey,
+    pub user_source: Pubkey,
+    pub user_destination: Pubkey,
+    pub user_owner: Pubkey,
+}
+
+pub struct RaydiumAmmV4Decoder;
+
+impl ProgramDecoder for RaydiumAmmV4Decoder {
+    fn program_id(&self) -> Pubkey {
+        RAYDIUM_AMM_V4_PROGRAM_ID
+    }
+
+    fn decode(&self, data: &[u8], accounts: &[Pubkey]) -> Option<DecodedInstruction> {
+        let mut reader = DataReader::new(data);
+        let instruction = match reader.read_u8()? {
+            9 => RaydiumInstruction::SwapBaseIn {
+                amount_in: reader.read_u64()?,
+                minimum_amount_out: reader.read_u64()?,
+                accounts: swap_accounts(accounts)?,
+            },
+            11 => RaydiumInstruction::SwapBaseOut {
+                max_amount_in: reader.read_u64()?,
+                amount_out: reader.read_u64()?,
+                accounts: swap_accounts(accounts)?,
+            },
+            tag => RaydiumInstruction::Other { name: instruction_name(tag)?.to_string() },
+        };
+        Some(DecodedInstruction::Raydium(instruction))
+    }
+}
+
+fn swap_accounts(accounts: &[Pubkey]) -> Option<RaydiumSwapAccounts> {
+    // With 18 accounts target_orders sits at index 4 and pushes the rest up by one.
+    let (target_orders, shift) = match accounts.len() {
+        18 => (account(accounts, 4), 1),
+        17 => (None, 0),
+        _ => return None,
+    };
+    Some(RaydiumSwapAccounts {
+        amm: account(accounts, 1)?,
+        amm_authority: account(accounts, 2)?,
+        open_orders: account(accounts, 3)?,
+        target_orders,
+        coin_vault: account(accounts, 4 + shift)?,
+        pc_vault: account(accounts, 5 + shift)?,
+        market_program: account(accounts, 6 + shift)?,
+        market: account(accounts, 7 + shift)?,
+        user_source: account(accounts, 14 + shift)?,
+        user_destination: account(accounts, 15 + shift)?,
+        user_owner: account(accounts, 16 + shift)?,
+    })
+}
+
+fn instruction_name(tag: u8) -> Option<&'static str> {
+    Some(match tag {
+        0 => "Initialize",
+        1 => "Initialize2",
+        2 => "MonitorStep",
+        3 => "Deposit",
+        4 => "Withdraw",
+        5 => "MigrateToOpenBook",
+        6 => "SetParams",
+        7 => "WithdrawPnl",
+        8 => "WithdrawSrm",
+        9 => "SwapBaseIn",
+        10 => "PreInitialize",
+        11 => "SwapBaseOut",
+        12 => "SimulateInfo",
+        13 => "AdminCancelOrders",
+        14 => "CreateConfigAccount",
+        15 => "UpdateConfigAccount",
+        _ => return None,
+    })
+}
@@ -0,0 +1,256 @@
+use super::{account, DataReader, DecodedInstruction, ProgramDecoder};
+use crate::pubkey::Pubkey;
+use serde::{Deserialize, Serialize};
+
+pub const STAKE_PROGRAM_ID: Pubkey = Pubkey::from_str_const("Stake11111111111111111111111111111111111111");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StakeInstruction {
+    Initialize {
+        stake_account: Pubkey,
+        staker: Pubkey,
+        withdrawer: Pubkey,
+        lockup_unix_timestamp: i64,
+        lockup_epoch: u64,
+        custodian: Pubkey,
+    },
+    // Same as Initialize, with the authorities as signing accounts and no
+    // lockup.
+    InitializeChecked {
+        stake_account: Pubkey,
+        staker: Pubkey,
+        withdrawer: Pubkey,
+    },
+    Authorize {
+        stake_account: Pubkey,
+        authority: Pubkey,
+        new_authority: Pubkey,
+        // "staker" or "withdrawer".
+        authority_type: String,
+    },
+    DelegateStake {
+        stake_account: Pubkey,
+        vote_account: Pubkey,
+        stake_authority: Pubkey,
+    },
+    // Moves `lamports` of the stake into a new, uninitialized account.
+    Split {
+        stake_account: Pubkey,
+        new_stake_account: Pubkey,
+        stake_authority: Pubkey,
+        lamports: u64,
+    },
+    Withdraw {
+        stake_account: Pubkey,
+        recipient: Pubkey,
+        withdraw_authority: Pubkey,
+        lamports: u64,
+    },
+    Deactivate {
+        stake_account: Pubkey,
+        stake_authority: Pubkey,
+    },
+    // Folds `source_stake_account` into `stake_account` and closes it.
+    Merge {
+        stake_account: Pubkey,
+        source_stake_account: Pubkey,
+        stake_authority: Pubkey,
+    },
+    // Anyone may deactivate stake delegated to a vote account that has
+    // stopped voting.
+    DeactivateDelinquent {
+        stake_account: Pubkey,
+        vote_account: Pubkey,
+        reference_vote_account: Pubkey,
+    },
+    // Active stake (MoveStake) or inactive lamports (MoveLamports) moved
+    // between two stake accounts with the same authorities.
+    MoveStake {
+        source_stake_account: Pubkey,
+        destination_stake_account: Pubkey,
+        stake_authority: Pubkey,
+        lamports: u64,
+    },
+    MoveLamports {
+        source_stake_account: Pubkey,
+        destination_stake_account: Pubkey,
+        stake_authority: Pubkey,
+        lamports: u64,
+    },
+    Other {
+        name: String,
+    },
+}
+
+pub struct StakeDecoder;
+
+impl ProgramDecoder for StakeDecoder {
+    fn program_id(&self) -> Pubkey {
+        STAKE_PROGRAM_ID
+    }
+
+    // Stake instructions are bincode-encoded like the System program's: a
+    // u32 tag, then the fields. Sysvar accounts (clock, rent, stake history)
+    // sit between the named ones and are skipped.
+    fn decode(&self, data: &[u8], accounts: &[Pubkey]) -> Option<DecodedInstruction> {
+        let mut reader = DataReader::new(data);
+        let instruction = match reader.read_u32()? {
+            0 => StakeInstruction::Initialize {
+                staker: reader.read_pubkey()?,
+                withdrawer: reader.read_pubkey()?,
+                lockup_unix_timestamp: reader.read_u64()? as i64,
+                lockup_epoch: reader.read_u64()?,
+                custodian: reader.read_pubkey()?,
+                stake_account: account(accounts, 0)?,
+            },
+            1 => StakeInstruction::Authorize {
+                new_authority: reader.read_pubkey()?,
+                authority_type: authority_type(reader.read_u32()?)?,
+                stake_account: account(accounts, 0)?,
+                authority: account(accounts, 2)?,
+            },
+            2 => StakeInstruction::DelegateStake {
+                stake_account: account(accounts, 0)?,
+                vote_account: account(accounts, 1)?,
+                stake_authority: account(accounts, 5)?,
+            },
+            3 => StakeInstruction::Split {
+                lamports: reader.read_u64()?,
+                stake_account: account(accounts, 0)?,
+                new_stake_account: account(accounts, 1)?,
+                stake_authority: account(accounts, 2)?,
+            },
+            4 => StakeInstruction::Withdraw {
+                lamports: reader.read_u64()?,
+                stake_account: account(accounts, 0)?,
+                recipient: account(accounts, 1)?,
+                withdraw_authority: account(accounts, 4)?,
+            },
+            5 => StakeInstruction::Deactivate {
+                stake_account: account(accounts, 0)?,
+                stake_authority: account(accounts, 2)?,
+            },
+            7 => StakeInstruction::Merge {
+                stake_account: account(accounts, 0)?,
+                source_stake_account: account(accounts, 1)?,
+                stake_authority: account(accounts, 4)?,
+            },
+            9 => StakeInstruction::InitializeChecked {
+                stake_account: account(accounts, 0)?,
+                staker: account(accounts, 2)?,
+                withdrawer: account(accounts, 3)?,
+            },
+            14 => StakeInstruction::DeactivateDelinquent {
+                stake_account: account(accounts, 0)?,
+                vote_account: account(accounts, 1)?,
+                reference_vote_account: account(accounts, 2)?,
+            },
+            16 => StakeInstruction::MoveStake {
+                lamports: reader.read_u64()?,
+                source_stake_account: account(accounts, 0)?,
+                destination_stake_account: account(accounts, 1)?,
+                stake_authority: account(accounts, 2)?,
+            },
+            17 => StakeInstruction::MoveLamports {
+                lamports: reader.read_u64()?,
+                source_stake_account: account(accounts, 0)?,
+                destination_stake_account: account(accounts, 1)?,
+                stake_authority: account(accounts, 2)?,
+            },
+            tag => StakeInstruction::Other { name: instruction_name(tag)?.to_string() },
+        };
+        Some(DecodedInstruction::Stake(instruction))
+    }
+
+    fn error_name(&self, code: u32) -> Option<String> {
+        STAKE_ERRORS.get(code as usize).map(|name| name.to_string())
+    }
+}
+
+fn authority_type(value: u32) -> Option<String> {
+    Some(match value {
+        0 => "staker",
+        1 => "withdrawer",
+        _ => return None,
+    }.to_string())
+}
+
+// StakeError, in code order.
+const STAKE_ERRORS: &[&str] = &[
+    "NoCreditsToRedeem",
+    "LockupInForce",
+    "AlreadyDeactivated",
+    "TooSoonToRedelegate",
+    "InsufficientStake",
+    "MergeTransientStake",
+    "MergeMismatch",
+    "CustodianMissing",
+    "CustodianSignatureMissing",
+    "InsufficientReferenceVotes",
+    "VoteAddressMismatch",
+    "MinimumDelinquentEpochsForDeactivationNotMet",
+    "InsufficientDelegation",
+    "RedelegateTransientOrInactiveStake",
+    "RedelegateToSameVoteAccount",
+    "RedelegatedStakeMustFullyActivateBeforeDeactivationIsPermitted",
+    "EpochRewardsActive",
+];
+
+fn instruction_name(tag: u32) -> Option<&'static str> {
+    Some(match tag {
+        0 => "Initialize",
+        1 => "Authorize",
+        2 => "DelegateStake",
+        3 => "Split",
+        4 => "Withdraw",
+        5 => "Deactivate",
+        6 => "SetLockup",
+        7 => "Merge",
+        8 => "AuthorizeWithSeed",
+        9 => "InitializeChecked",
+        10 => "AuthorizeChecked",
+        11 => "AuthorizeCheckedWithSeed",
+        12 => "SetLockupChecked",
+        13 => "GetMinimumDelegation",
+        14 => "DeactivateDelinquent",
+        15 => "Redelegate",
+        16 => "MoveStake",
+        17 => "MoveLamports",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_delegation_lifecycle() {
+        let keys: Vec<Pubkey> = (1..=6u8).map(|i| Pubkey::new([i; 32])).collect();
+        let decode = |data: &[u8], accounts: &[Pubkey]| match StakeDecoder.decode(data, accounts) {
+            Some(DecodedInstruction::Stake(instruction)) => instruction,
+            other => panic!("{:?}", other),
+        };
+
+        // DelegateStake: stake, vote, clock, stake history, config, authority.
+        let StakeInstruction::DelegateStake { stake_account, vote_account, stake_authority } = decode(&2u32.to_le_bytes(), &keys) else {
+            panic!();
+        };
+        assert_eq!((stake_account, vote_account, stake_authority), (keys[0], keys[1], keys[5]));
+
+        let mut withdraw = 4u32.to_le_bytes().to_vec();
+        withdraw.extend(2_500_000_000u64.to_le_bytes());
+        let StakeInstruction::Withdraw { recipient, withdraw_authority, lamports, .. } = decode(&withdraw, &keys[..5]) else {
+            panic!();
+        };
+        assert_eq!((recipient, withdraw_authority, lamports), (keys[1], keys[4], 2_500_000_000));
+
+        let mut split = 3u32.to_le_bytes().to_vec();
+        split.extend(1_000u64.to_le_bytes());
+        assert!(matches!(decode(&split, &keys[..3]), StakeInstruction::Split { lamports: 1_000, .. }));
+        assert!(matches!(decode(&6u32.to_le_bytes(), &keys), StakeInstruction::Other { name } if name == "SetLockup"));
+        // Too few accounts for the instruction.
+        assert!(StakeDecoder.decode(&5u32.to_le_bytes(), &keys[..2]).is_none());
+    }
+}
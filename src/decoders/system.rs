@@ -0,0 +1,288 @@
+use super::{account, DataReader, DecodedInstruction, ProgramDecoder};
+use crate::pubkey::Pubkey;
+use serde::{Deserialize, Serialize};
+
+pub const SYSTEM_PROGRAM_ID: Pubkey = Pubkey::from_str_const("11111111111111111111111111111111");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SystemInstruction {
+    CreateAccount {
+        funding_account: Pubkey,
+        new_account: Pubkey,
+        lamports: u64,
+        space: u64,
+        owner: Pubkey,
+    },
+    Assign {
+        account: Pubkey,
+        owner: Pubkey,
+    },
+    Transfer {
+        from: Pubkey,
+        to: Pubkey,
+        lamports: u64,
+    },
+    CreateAccountWithSeed {
+        funding_account: Pubkey,
+        new_account: Pubkey,
+        base: Pubkey,
+        seed: String,
+        lamports: u64,
+        space: u64,
+        owner: Pubkey,
+    },
+    Allocate {
+        account: Pubkey,
+        space: u64,
+    },
+    AllocateWithSeed {
+        account: Pubkey,
+        base: Pubkey,
+        seed: String,
+        space: u64,
+        owner: Pubkey,
+    },
+    AssignWithSeed {
+        account: Pubkey,
+        base: Pubkey,
+        seed: String,
+        owner: Pubkey,
+    },
+    // The first instruction of a durable nonce transaction: moves the nonce
+    // account on to a new blockhash so the transaction can't be replayed.
+    AdvanceNonceAccount {
+        nonce_account: Pubkey,
+        nonce_authority: Pubkey,
+    },
+    TransferWithSeed {
+        from: Pubkey,
+        from_base: Pubkey,
+        from_seed: String,
+        from_owner: Pubkey,
+        to: Pubkey,
+        lamports: u64,
+    },
+    Other {
+        name: String,
+    },
+}
+
+pub struct SystemDecoder;
+
+impl SystemInstruction {
+    // (from, to, lamports) for instructions that move SOL, including the
+    // initial funding of a newly created account.
+    pub fn lamport_transfer(&self) -> Option<(Pubkey, Pubkey, u64)> {
+        match self {
+            SystemInstruction::Transfer { from, to, lamports }
+            | SystemInstruction::TransferWithSeed { from, to, lamports, .. } => Some((*from, *to, *lamports)),
+            SystemInstruction::CreateAccount { funding_account, new_account, lamports, .. }
+            | SystemInstruction::CreateAccountWithSeed { funding_account, new_account, lamports, .. } => {
+                Some((*funding_account, *new_account, *lamports))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ProgramDecoder for SystemDecoder {
+    fn program_id(&self) -> Pubkey {
+        SYSTEM_PROGRAM_ID
+    }
+
+    // System instructions are bincode-encoded: a u32 tag, then the fields.
+    fn decode(&self, data: &[u8], accounts: &[Pubkey]) -> Option<DecodedInstruction> {
+        let mut reader = DataReader::new(data);
+        let instruction = match reader.read_u32()? {
+            0 => SystemInstruction::CreateAccount {
+                lamports: reader.read_u64()?,
+                space: reader.read_u64()?,
+                owner: reader.read_pubkey()?,
+                funding_account: account(accounts, 0)?,
+                new_account: account(accounts, 1)?,
+            },
+            1 => SystemInstruction::Assign {
+                owner: reader.read_pubkey()?,
+                account: account(accounts, 0)?,
+            },
+            2 => SystemInstruction::Transfer {
+                lamports: reader.read_u64()?,
+                from: account(accounts, 0)?,
+                to: account(accounts, 1)?,
+            },
+            3 => SystemInstruction::CreateAccountWithSeed {
+                base: reader.read_pubkey()?,
+                seed: reader.read_string()?,
+                lamports: reader.read_u64()?,
+                space: reader.read_u64()?,
+                owner: reader.read_pubkey()?,
+                funding_account: account(accounts, 0)?,
+                new_account: account(accounts, 1)?,
+            },
+            // Nonce account, recent blockhashes sysvar, authority.
+            4 => SystemInstruction::AdvanceNonceAccount {
+                nonce_account: account(accounts, 0)?,
+                nonce_authority: account(accounts, 2)?,
+            },
+            8 => SystemInstruction::Allocate {
+                space: reader.read_u64()?,
+                account: account(accounts, 0)?,
+            },
+            9 => SystemInstruction::AllocateWithSeed {
+                base: reader.read_pubkey()?,
+                seed: reader.read_string()?,
+                space: reader.read_u64()?,
+                owner: reader.read_pubkey()?,
+                account: account(accounts, 0)?,
+            },
+            10 => SystemInstruction::AssignWithSeed {
+                base: reader.read_pubkey()?,
+                seed: reader.read_string()?,
+                owner: reader.read_pubkey()?,
+                account: account(accounts, 0)?,
+            },
+            11 => SystemInstruction::TransferWithSeed {
+                lamports: reader.read_u64()?,
+                from_seed: reader.read_string()?,
+                from_owner: reader.read_pubkey()?,
+                from: account(accounts, 0)?,
+                from_base: account(accounts, 1)?,
+                to: account(accounts, 2)?,
+            },
+            tag => SystemInstruction::Other { name: instruction_name(tag)?.to_string() },
+        };
+        Some(DecodedInstruction::System(instruction))
+    }
+
+    fn error_name(&self, code: u32) -> Option<String> {
+        SYSTEM_ERRORS.get(code as usize).map(|name| name.to_string())
+    }
+}
+
+// SystemError, in code order.
+const SYSTEM_ERRORS: &[&str] = &[
+    "AccountAlreadyInUse",
+    "ResultWithNegativeLamports",
+    "InvalidProgramId",
+    "InvalidAccountDataLength",
+    "MaxSeedLengthExceeded",
+    "AddressWithSeedMismatch",
+    "NonceNoRecentBlockhashes",
+    "NonceBlockhashNotExpired",
+    "NonceUnexpectedBlockhashValue",
+];
+
+fn instruction_name(tag: u32) -> Option<&'static str> {
+    Some(match tag {
+        0 => "CreateAccount",
+        1 => "Assign",
+        2 => "Transfer",
+        3 => "CreateAccountWithSeed",
+        4 => "AdvanceNonceAccount",
+        5 => "WithdrawNonceAccount",
+        6 => "InitializeNonceAccount",
+        7 => "AuthorizeNonceAccount",
+        8 => "Allocate",
+        9 => "AllocateWithSeed",
+        10 => "AssignWithSeed",
+        11 => "TransferWithSeed",
+        12 => "UpgradeNonceAccount",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // bincode: u32 tag, then the fields; strings carry a u64 length prefix.
+    fn data(tag: u32, fields: &[&[u8]]) -> Vec<u8> {
+        let mut data = tag.to_le_bytes().to_vec();
+        for field in fields {
+            data.extend_from_slice(field);
+        }
+        data
+    }
+
+    fn seed(seed: &str) -> Vec<u8> {
+        let mut bytes = (seed.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(seed.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn decodes_bincode_instructions() {
+        let keys: Vec<Pubkey> = (1..=4u8).map(|i| Pubkey::new([i; 32])).collect();
+        let owner = Pubkey::new([9; 32]);
+        let decode = |data: &[u8], accounts: &[Pubkey]| match SystemDecoder.decode(data, accounts) {
+            Some(DecodedInstruction::System(instruction)) => instruction,
+            other => panic!("{:?}", other),
+        };
+
+        let transfer = data(2, &[&1_500_000u64.to_le_bytes()]);
+        let instruction = decode(&transfer, &keys[..2]);
+        assert_eq!(instruction.lamport_transfer(), Some((keys[0], keys[1], 1_500_000)));
+
+        let create = data(0, &[&2_039_280u64.to_le_bytes(), &165u64.to_le_bytes(), owner.as_bytes()]);
+        let SystemInstruction::CreateAccount { funding_account, new_account, lamports, space, owner: new_owner } = decode(&create, &keys) else {
+            panic!();
+        };
+        assert_eq!((funding_account, new_account, lamports, space, new_owner), (keys[0], keys[1], 2_039_280, 165, owner));
+
+        let SystemInstruction::Assign { account, owner: new_owner } = decode(&data(1, &[owner.as_bytes()]), &keys[..1]) else {
+            panic!();
+        };
+        assert_eq!((account, new_owner), (keys[0], owner));
+
+        let SystemInstruction::Allocate { account, space } = decode(&data(8, &[&200u64.to_le_bytes()]), &keys[..1]) else {
+            panic!();
+        };
+        assert_eq!((account, space), (keys[0], 200));
+
+        let create_with_seed = data(3, &[keys[2].as_bytes(), &seed("vault"), &1_000u64.to_le_bytes(), &80u64.to_le_bytes(), owner.as_bytes()]);
+        let instruction = decode(&create_with_seed, &keys[..2]);
+        assert_eq!(instruction.lamport_transfer(), Some((keys[0], keys[1], 1_000)));
+        let SystemInstruction::CreateAccountWithSeed { base, seed: name, space, owner: new_owner, .. } = instruction else {
+            panic!();
+        };
+        assert_eq!((base, name.as_str(), space, new_owner), (keys[2], "vault", 80, owner));
+
+        let allocate_with_seed = data(9, &[keys[1].as_bytes(), &seed("stake:0"), &4_096u64.to_le_bytes(), owner.as_bytes()]);
+        let SystemInstruction::AllocateWithSeed { account, base, seed: name, space, owner: new_owner } = decode(&allocate_with_seed, &keys[..2]) else {
+            panic!();
+        };
+        assert_eq!((account, base, name.as_str(), space, new_owner), (keys[0], keys[1], "stake:0", 4_096, owner));
+
+        let assign_with_seed = data(10, &[keys[1].as_bytes(), &seed(""), owner.as_bytes()]);
+        let SystemInstruction::AssignWithSeed { account, base, seed: name, owner: new_owner } = decode(&assign_with_seed, &keys[..2]) else {
+            panic!();
+        };
+        assert_eq!((account, base, name.as_str(), new_owner), (keys[0], keys[1], "", owner));
+
+        // From, from base, to; the seed and owner derive `from`.
+        let transfer_with_seed = data(11, &[&42u64.to_le_bytes(), &seed("fees"), owner.as_bytes()]);
+        let instruction = decode(&transfer_with_seed, &keys[..3]);
+        assert_eq!(instruction.lamport_transfer(), Some((keys[0], keys[2], 42)));
+        let SystemInstruction::TransferWithSeed { from_base, from_seed, from_owner, .. } = instruction else {
+            panic!();
+        };
+        assert_eq!((from_base, from_seed.as_str(), from_owner), (keys[1], "fees", owner));
+
+        let SystemInstruction::AdvanceNonceAccount { nonce_account, nonce_authority } = decode(&data(4, &[]), &keys[..3]) else {
+            panic!();
+        };
+        assert_eq!((nonce_account, nonce_authority), (keys[0], keys[2]));
+        assert!(matches!(decode(&data(5, &[]), &keys), SystemInstruction::Other { name } if name == "WithdrawNonceAccount"));
+        assert!(decode(&data(8, &[&1u64.to_le_bytes()]), &keys).lamport_transfer().is_none());
+
+        // Unknown tags, truncated data, a seed longer than the data, and
+        // missing accounts.
+        assert!(SystemDecoder.decode(&data(13, &[]), &keys).is_none());
+        assert!(SystemDecoder.decode(&data(2, &[&[1, 0, 0]]), &keys).is_none());
+        assert!(SystemDecoder.decode(&data(10, &[keys[1].as_bytes(), &100u64.to_le_bytes(), b"short"]), &keys).is_none());
+        assert!(SystemDecoder.decode(&transfer, &keys[..1]).is_none());
+        assert!(SystemDecoder.decode(&[2, 0], &keys).is_none());
+        assert_eq!(SystemDecoder.error_name(1).as_deref(), Some("ResultWithNegativeLamports"));
+    }
+}
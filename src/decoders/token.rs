@@ -0,0 +1,410 @@
+use super::{account, DataReader, DecodedInstruction, ProgramDecoder};
+use crate::pubkey::Pubkey;
+use serde::{Deserialize, Serialize};
+
+pub const TOKEN_PROGRAM_ID: Pubkey = Pubkey::from_str_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = Pubkey::from_str_const("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+// Token-2022 keeps the legacy instruction layout for tags 0..=24, so both
+// programs decode into the same enum; ParsedInstruction.program_id tells
+// them apart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TokenInstruction {
+    InitializeAccount {
+        account: Pubkey,
+        mint: Pubkey,
+        owner: Pubkey,
+    },
+    Transfer {
+        source: Pubkey,
+        destination: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+    },
+    MintTo {
+        mint: Pubkey,
+        account: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+    },
+    Burn {
+        account: Pubkey,
+        mint: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+    },
+    CloseAccount {
+        account: Pubkey,
+        destination: Pubkey,
+        authority: Pubkey,
+    },
+    TransferChecked {
+        source: Pubkey,
+        mint: Pubkey,
+        destination: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+        decimals: u8,
+    },
+    MintToChecked {
+        mint: Pubkey,
+        account: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+        decimals: u8,
+    },
+    BurnChecked {
+        account: Pubkey,
+        mint: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+        decimals: u8,
+    },
+    // Token-2022 transfer-fee extension.
+    TransferCheckedWithFee {
+        source: Pubkey,
+        mint: Pubkey,
+        destination: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+        decimals: u8,
+        fee: u64,
+    },
+    Other {
+        name: String,
+    },
+}
+
+impl TokenInstruction {
+    pub fn amount(&self) -> Option<u64> {
+        match self {
+            TokenInstruction::Transfer { amount, .. }
+            | TokenInstruction::MintTo { amount, .. }
+            | TokenInstruction::Burn { amount, .. }
+            | TokenInstruction::TransferChecked { amount, .. }
+            | TokenInstruction::MintToChecked { amount, .. }
+            | TokenInstruction::BurnChecked { amount, .. }
+            | TokenInstruction::TransferCheckedWithFee { amount, .. } => Some(*amount),
+            _ => None,
+        }
+    }
+
+    // Only known when the instruction names the mint; plain Transfer doesn't.
+    pub fn mint(&self) -> Option<Pubkey> {
+        match self {
+            TokenInstruction::InitializeAccount { mint, .. }
+            | TokenInstruction::MintTo { mint, .. }
+            | TokenInstruction::Burn { mint, .. }
+            | TokenInstruction::TransferChecked { mint, .. }
+            | TokenInstruction::MintToChecked { mint, .. }
+            | TokenInstruction::BurnChecked { mint, .. }
+            | TokenInstruction::TransferCheckedWithFee { mint, .. } => Some(*mint),
+            _ => None,
+        }
+    }
+
+    pub fn decimals(&self) -> Option<u8> {
+        match self {
+            TokenInstruction::TransferChecked { decimals, .. }
+            | TokenInstruction::MintToChecked { decimals, .. }
+            | TokenInstruction::BurnChecked { decimals, .. }
+            | TokenInstruction::TransferCheckedWithFee { decimals, .. } => Some(*decimals),
+            _ => None,
+        }
+    }
+
+    // The signing owner/authority, or the new owner for InitializeAccount.
+    pub fn owner(&self) -> Option<Pubkey> {
+        match self {
+            TokenInstruction::InitializeAccount { owner, .. } => Some(*owner),
+            TokenInstruction::Transfer { authority, .. }
+            | TokenInstruction::MintTo { authority, .. }
+            | TokenInstruction::Burn { authority, .. }
+            | TokenInstruction::CloseAccount { authority, .. }
+            | TokenInstruction::TransferChecked { authority, .. }
+            | TokenInstruction::MintToChecked { authority, .. }
+            | TokenInstruction::BurnChecked { authority, .. }
+            | TokenInstruction::TransferCheckedWithFee { authority, .. } => Some(*authority),
+            TokenInstruction::Other { .. } => None,
+        }
+    }
+}
+
+pub struct TokenDecoder;
+
+// TokenError, in code order; Token-2022 shares the same codes.
+const TOKEN_ERRORS: &[&str] = &[
+    "NotRentExempt",
+    "InsufficientFunds",
+    "InvalidMint",
+    "MintMismatch",
+    "OwnerMismatch",
+    "FixedSupply",
+    "AlreadyInUse",
+    "InvalidNumberOfProvidedSigners",
+    "InvalidNumberOfRequiredSigners",
+    "UninitializedState",
+    "NativeNotSupported",
+    "NonNativeHasBalance",
+    "InvalidInstruction",
+    "InvalidState",
+    "Overflow",
+    "AuthorityTypeNotSupported",
+    "MintCannotFreeze",
+    "AccountFrozen",
+    "MintDecimalsMismatch",
+    "NonNativeNotSupported",
+];
+
+fn token_error_name(code: u32) -> Option<String> {
+    TOKEN_ERRORS.get(code as usize).map(|name| name.to_string())
+}
+
+impl ProgramDecoder for TokenDecoder {
+    fn program_id(&self) -> Pubkey {
+        TOKEN_PROGRAM_ID
+    }
+
+    fn decode(&self, data: &[u8], accounts: &[Pubkey]) -> Option<DecodedInstruction> {
+        decode_token_instruction(data, accounts, false).map(DecodedInstruction::Token)
+    }
+
+    fn error_name(&self, code: u32) -> Option<String> {
+        token_error_name(code)
+    }
+}
+
+pub struct Token2022Decoder;
+
+impl ProgramDecoder for Token2022Decoder {
+    fn program_id(&self) -> Pubkey {
+        TOKEN_2022_PROGRAM_ID
+    }
+
+    fn decode(&self, data: &[u8], accounts: &[Pubkey]) -> Option<DecodedInstruction> {
+        decode_token_instruction(data, accounts, true).map(DecodedInstruction::Token)
+    }
+
+    fn error_name(&self, code: u32) -> Option<String> {
+        token_error_name(code)
+    }
+}
+
+// SPL Token instructions use a 1-byte tag followed by packed fields.
+fn decode_token_instruction(data: &[u8], accounts: &[Pubkey], token_2022: bool) -> Option<TokenInstruction> {
+    let mut reader = DataReader::new(data);
+    let instruction = match reader.read_u8()? {
+        1 => TokenInstruction::InitializeAccount {
+            account: account(accounts, 0)?,
+            mint: account(accounts, 1)?,
+            owner: account(accounts, 2)?,
+        },
+        3 => TokenInstruction::Transfer {
+            amount: reader.read_u64()?,
+            source: account(accounts, 0)?,
+            destination: account(accounts, 1)?,
+            authority: account(accounts, 2)?,
+        },
+        7 => TokenInstruction::MintTo {
+            amount: reader.read_u64()?,
+            mint: account(accounts, 0)?,
+            account: account(accounts, 1)?,
+            authority: account(accounts, 2)?,
+        },
+        8 => TokenInstruction::Burn {
+            amount: reader.read_u64()?,
+            account: account(accounts, 0)?,
+            mint: account(accounts, 1)?,
+            authority: account(accounts, 2)?,
+        },
+        9 => TokenInstruction::CloseAccount {
+            account: account(accounts, 0)?,
+            destination: account(accounts, 1)?,
+            authority: account(accounts, 2)?,
+        },
+        12 => TokenInstruction::TransferChecked {
+            amount: reader.read_u64()?,
+            decimals: reader.read_u8()?,
+            source: account(accounts, 0)?,
+            mint: account(accounts, 1)?,
+            destination: account(accounts, 2)?,
+            authority: account(accounts, 3)?,
+        },
+        14 => TokenInstruction::MintToChecked {
+            amount: reader.read_u64()?,
+            decimals: reader.read_u8()?,
+            mint: account(accounts, 0)?,
+            account: account(accounts, 1)?,
+            authority: account(accounts, 2)?,
+        },
+        15 => TokenInstruction::BurnChecked {
+            amount: reader.read_u64()?,
+            decimals: reader.read_u8()?,
+            account: account(accounts, 0)?,
+            mint: account(accounts, 1)?,
+            authority: account(accounts, 2)?,
+        },
+        // InitializeAccount2/3 carry the owner in data instead of an account.
+        16 => TokenInstruction::InitializeAccount {
+            owner: reader.read_pubkey()?,
+            account: account(accounts, 0)?,
+            mint: account(accounts, 1)?,
+        },
+        18 => TokenInstruction::InitializeAccount {
+            owner: reader.read_pubkey()?,
+            account: account(accounts, 0)?,
+            mint: account(accounts, 1)?,
+        },
+        // Any other transfer-fee sub-instruction, or none, falls through to
+        // the extension name.
+        26 if token_2022 && reader.read_u8() == Some(1) => TokenInstruction::TransferCheckedWithFee {
+            amount: reader.read_u64()?,
+            decimals: reader.read_u8()?,
+            fee: reader.read_u64()?,
+            source: account(accounts, 0)?,
+            mint: account(accounts, 1)?,
+            destination: account(accounts, 2)?,
+            authority: account(accounts, 3)?,
+        },
+        tag => TokenInstruction::Other { name: instruction_name(tag, token_2022)?.to_string() },
+    };
+    Some(instruction)
+}
+
+fn instruction_name(tag: u8, token_2022: bool) -> Option<&'static str> {
+    Some(match tag {
+        0 => "InitializeMint",
+        1 => "InitializeAccount",
+        2 => "InitializeMultisig",
+        3 => "Transfer",
+        4 => "Approve",
+        5 => "Revoke",
+        6 => "SetAuthority",
+        7 => "MintTo",
+        8 => "Burn",
+        9 => "CloseAccount",
+        10 => "FreezeAccount",
+        11 => "ThawAccount",
+        12 => "TransferChecked",
+        13 => "ApproveChecked",
+        14 => "MintToChecked",
+        15 => "BurnChecked",
+        16 => "InitializeAccount2",
+        17 => "SyncNative",
+        18 => "InitializeAccount3",
+        19 => "InitializeMultisig2",
+        20 => "InitializeMint2",
+        21 => "GetAccountDataSize",
+        22 => "InitializeImmutableOwner",
+        23 => "AmountToUiAmount",
+        24 => "UiAmountToAmount",
+        _ if !token_2022 => return None,
+        25 => "InitializeMintCloseAuthority",
+        26 => "TransferFeeExtension",
+        27 => "ConfidentialTransferExtension",
+        28 => "DefaultAccountStateExtension",
+        29 => "Reallocate",
+        30 => "MemoTransferExtension",
+        31 => "CreateNativeMint",
+        32 => "InitializeNonTransferableMint",
+        33 => "InterestBearingMintExtension",
+        34 => "CpiGuardExtension",
+        35 => "InitializePermanentDelegate",
+        36 => "TransferHookExtension",
+        37 => "ConfidentialTransferFeeExtension",
+        38 => "WithdrawExcessLamports",
+        39 => "MetadataPointerExtension",
+        40 => "GroupPointerExtension",
+        41 => "GroupMemberPointerExtension",
+        42 => "ConfidentialMintBurnExtension",
+        43 => "ScaledUiAmountExtension",
+        44 => "PausableExtension",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tag, then the packed fields.
+    fn data(tag: u8, fields: &[&[u8]]) -> Vec<u8> {
+        let mut data = vec![tag];
+        for field in fields {
+            data.extend_from_slice(field);
+        }
+        data
+    }
+
+    #[test]
+    fn decodes_packed_instructions() {
+        let keys: Vec<Pubkey> = (1..=4u8).map(|i| Pubkey::new([i; 32])).collect();
+        let owner = Pubkey::new([9; 32]);
+        let decode = |data: &[u8], accounts: &[Pubkey]| match TokenDecoder.decode(data, accounts) {
+            Some(DecodedInstruction::Token(instruction)) => instruction,
+            other => panic!("{:?}", other),
+        };
+        let amount = 1_250_000u64.to_le_bytes();
+
+        let TokenInstruction::MintTo { mint, account, authority, amount: minted } = decode(&data(7, &[&amount]), &keys[..3]) else {
+            panic!();
+        };
+        assert_eq!((mint, account, authority, minted), (keys[0], keys[1], keys[2], 1_250_000));
+        let TokenInstruction::Burn { account, mint, authority, amount: burned } = decode(&data(8, &[&amount]), &keys[..3]) else {
+            panic!();
+        };
+        assert_eq!((account, mint, authority, burned), (keys[0], keys[1], keys[2], 1_250_000));
+
+        let transfer = decode(&data(12, &[&amount, &[6]]), &keys);
+        let TokenInstruction::TransferChecked { source, mint, destination, authority, .. } = transfer else {
+            panic!();
+        };
+        assert_eq!((source, mint, destination, authority), (keys[0], keys[1], keys[2], keys[3]));
+        assert_eq!((transfer.amount(), transfer.decimals(), transfer.owner()), (Some(1_250_000), Some(6), Some(keys[3])));
+        let mint_to = decode(&data(14, &[&amount, &[9]]), &keys[..3]);
+        assert!(matches!(mint_to, TokenInstruction::MintToChecked { amount: 1_250_000, decimals: 9, .. }));
+        assert_eq!((mint_to.mint(), mint_to.owner()), (Some(keys[0]), Some(keys[2])));
+        let burn = decode(&data(15, &[&amount, &[2]]), &keys[..3]);
+        assert!(matches!(burn, TokenInstruction::BurnChecked { amount: 1_250_000, decimals: 2, .. }));
+        assert_eq!(burn.mint(), Some(keys[1]));
+
+        // InitializeAccount takes the owner from the accounts, 2 and 3 from
+        // the data.
+        let TokenInstruction::InitializeAccount { owner: from_accounts, .. } = decode(&data(1, &[]), &keys[..4]) else {
+            panic!();
+        };
+        assert_eq!(from_accounts, keys[2]);
+        for tag in [16, 18] {
+            let TokenInstruction::InitializeAccount { account, mint, owner: from_data } = decode(&data(tag, &[owner.as_bytes()]), &keys[..2]) else {
+                panic!();
+            };
+            assert_eq!((account, mint, from_data), (keys[0], keys[1], owner));
+        }
+        assert!(TokenDecoder.decode(&data(18, &[&[1; 31]]), &keys).is_none());
+
+        // TransferCheckedWithFee is tag 26, sub-instruction 1, on Token-2022 only.
+        let with_fee = data(26, &[&[1], &amount, &[6], &3_000u64.to_le_bytes()]);
+        let Some(DecodedInstruction::Token(TokenInstruction::TransferCheckedWithFee { source, mint, destination, authority, amount: sent, decimals, fee })) =
+            Token2022Decoder.decode(&with_fee, &keys)
+        else {
+            panic!();
+        };
+        assert_eq!((source, mint, destination, authority), (keys[0], keys[1], keys[2], keys[3]));
+        assert_eq!((sent, decimals, fee), (1_250_000, 6, 3_000));
+        assert!(TokenDecoder.decode(&with_fee, &keys).is_none());
+        for other in [vec![26], vec![26, 0]] {
+            let Some(DecodedInstruction::Token(TokenInstruction::Other { name })) = Token2022Decoder.decode(&other, &keys) else {
+                panic!("{:?} not decoded", other);
+            };
+            assert_eq!(name, "TransferFeeExtension");
+        }
+        assert!(Token2022Decoder.decode(&with_fee[..with_fee.len() - 1], &keys).is_none());
+
+        // Truncated amounts and missing accounts.
+        assert!(TokenDecoder.decode(&data(7, &[&amount[..7]]), &keys).is_none());
+        assert!(TokenDecoder.decode(&data(12, &[&amount]), &keys).is_none());
+        assert!(TokenDecoder.decode(&data(8, &[&amount]), &keys[..2]).is_none());
+        assert!(matches!(decode(&data(17, &[]), &keys), TokenInstruction::Other { name } if name == "SyncNative"));
+    }
+}
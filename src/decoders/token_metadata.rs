@@ -0,0 +1,358 @@
+use super::{account, DataReader, DecodedInstruction, ProgramDecoder};
+use crate::pubkey::Pubkey;
+use serde::{Deserialize, Serialize};
+
+pub const TOKEN_METADATA_PROGRAM_ID: Pubkey = Pubkey::from_str_const("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TokenMetadataInstruction {
+    // CreateMetadataAccount (v1-v3) and the newer Create, which also
+    // creates the master edition and sets the token standard.
+    CreateMetadata {
+        metadata: Pubkey,
+        mint: Pubkey,
+        mint_authority: Pubkey,
+        payer: Pubkey,
+        update_authority: Pubkey,
+        name: String,
+        symbol: String,
+        uri: String,
+        seller_fee_basis_points: u16,
+        creators: Vec<Creator>,
+        collection: Option<Collection>,
+        is_mutable: bool,
+        token_standard: Option<String>,
+    },
+    // Transfer of a programmable NFT, which has to go through the
+    // metadata program rather than SPL Token.
+    Transfer {
+        amount: u64,
+        token: Pubkey,
+        token_owner: Pubkey,
+        destination: Pubkey,
+        destination_owner: Pubkey,
+        mint: Pubkey,
+        metadata: Pubkey,
+        authority: Pubkey,
+    },
+    // Burn and the older BurnNft.
+    Burn {
+        amount: u64,
+        authority: Pubkey,
+        mint: Pubkey,
+        metadata: Pubkey,
+        token: Pubkey,
+        // The collection's metadata account, when the NFT was in a
+        // verified collection.
+        collection_metadata: Option<Pubkey>,
+    },
+    Other {
+        name: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Creator {
+    pub address: Pubkey,
+    pub verified: bool,
+    // Percent of the royalties.
+    pub share: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Collection {
+    // The collection NFT's mint.
+    pub key: Pubkey,
+    pub verified: bool,
+}
+
+pub struct TokenMetadataDecoder;
+
+impl ProgramDecoder for TokenMetadataDecoder {
+    fn program_id(&self) -> Pubkey {
+        TOKEN_METADATA_PROGRAM_ID
+    }
+
+    // Borsh: a u8 tag, then the arguments. Strings and vectors have u32
+    // length prefixes, options a 0/1 byte. Optional accounts of the newer
+    // instructions are passed as the program ID itself when absent, so the
+    // positions stay fixed.
+    fn decode(&self, data: &[u8], accounts: &[Pubkey]) -> Option<DecodedInstruction> {
+        let mut reader = DataReader::new(data);
+        let instruction = match reader.read_u8()? {
+            tag @ (0 | 16 | 33) => {
+                let data = read_data(&mut reader, tag != 0)?;
+                create_metadata(data, reader.read_u8()? != 0, None, accounts, [0, 1, 2, 3, 4])?
+            }
+            29 => TokenMetadataInstruction::Burn {
+                amount: 1,
+                metadata: account(accounts, 0)?,
+                authority: account(accounts, 1)?,
+                mint: account(accounts, 2)?,
+                token: account(accounts, 3)?,
+                collection_metadata: account(accounts, 6),
+            },
+            41 => {
+                // BurnArgs::V1 { amount }.
+                (reader.read_u8()? == 0).then_some(())?;
+                TokenMetadataInstruction::Burn {
+                    amount: reader.read_u64()?,
+                    authority: account(accounts, 0)?,
+                    collection_metadata: optional_account(accounts, 1),
+                    metadata: account(accounts, 2)?,
+                    mint: account(accounts, 4)?,
+                    token: account(accounts, 5)?,
+                }
+            }
+            42 => {
+                // CreateArgs::V1 { asset_data, .. }.
+                (reader.read_u8()? == 0).then_some(())?;
+                let (data, is_mutable, token_standard) = read_asset_data(&mut reader)?;
+                create_metadata(data, is_mutable, Some(token_standard), accounts, [0, 2, 3, 4, 5])?
+            }
+            49 => {
+                // TransferArgs::V1 { amount, .. }.
+                (reader.read_u8()? == 0).then_some(())?;
+                TokenMetadataInstruction::Transfer {
+                    amount: reader.read_u64()?,
+                    token: account(accounts, 0)?,
+                    token_owner: account(accounts, 1)?,
+                    destination: account(accounts, 2)?,
+                    destination_owner: account(accounts, 3)?,
+                    mint: account(accounts, 4)?,
+                    metadata: account(accounts, 5)?,
+                    authority: account(accounts, 9)?,
+                }
+            }
+            tag => TokenMetadataInstruction::Other { name: instruction_name(tag)?.to_string() },
+        };
+        Some(DecodedInstruction::TokenMetadata(instruction))
+    }
+}
+
+fn optional_account(accounts: &[Pubkey], position: usize) -> Option<Pubkey> {
+    account(accounts, position).filter(|key| *key != TOKEN_METADATA_PROGRAM_ID)
+}
+
+// The fields shared by Data, DataV2 and AssetData.
+struct MetadataData {
+    name: String,
+    symbol: String,
+    uri: String,
+    seller_fee_basis_points: u16,
+    creators: Vec<Creator>,
+    collection: Option<Collection>,
+}
+
+// `positions` are where metadata, mint, mint authority, payer and update
+// authority sit in the instruction's accounts.
+fn create_metadata(
+    data: MetadataData,
+    is_mutable: bool,
+    token_standard: Option<String>,
+    accounts: &[Pubkey],
+    positions: [usize; 5],
+) -> Option<TokenMetadataInstruction> {
+    Some(TokenMetadataInstruction::CreateMetadata {
+        metadata: account(accounts, positions[0])?,
+        mint: account(accounts, positions[1])?,
+        mint_authority: account(accounts, positions[2])?,
+        payer: account(accounts, positions[3])?,
+        update_authority: account(accounts, positions[4])?,
+        name: data.name,
+        symbol: data.symbol,
+        uri: data.uri,
+        seller_fee_basis_points: data.seller_fee_basis_points,
+        creators: data.creators,
+        collection: data.collection,
+        is_mutable,
+        token_standard,
+    })
+}
+
+// Data (v1) ends after the creators; DataV2 adds the collection and uses.
+fn read_data(reader: &mut DataReader, v2: bool) -> Option<MetadataData> {
+    let mut data = MetadataData {
+        name: read_string(reader)?,
+        symbol: read_string(reader)?,
+        uri: read_string(reader)?,
+        seller_fee_basis_points: reader.read_u16()?,
+        creators: read_option(reader, read_creators)?.unwrap_or_default(),
+        collection: None,
+    };
+    if v2 {
+        data.collection = read_option(reader, read_collection)?;
+        read_option(reader, skip_uses)?;
+    }
+    Some(data)
+}
+
+// AssetData, with is_mutable and the token standard inline.
+fn read_asset_data(reader: &mut DataReader) -> Option<(MetadataData, bool, String)> {
+    let name = read_string(reader)?;
+    let symbol = read_string(reader)?;
+    let uri = read_string(reader)?;
+    let seller_fee_basis_points = reader.read_u16()?;
+    let creators = read_option(reader, read_creators)?.unwrap_or_default();
+    let _primary_sale_happened = reader.read_u8()?;
+    let is_mutable = reader.read_u8()? != 0;
+    let token_standard = TOKEN_STANDARDS.get(reader.read_u8()? as usize)?.to_string();
+    let collection = read_option(reader, read_collection)?;
+    let data = MetadataData { name, symbol, uri, seller_fee_basis_points, creators, collection };
+    Some((data, is_mutable, token_standard))
+}
+
+const TOKEN_STANDARDS: &[&str] = &[
+    "NonFungible",
+    "FungibleAsset",
+    "Fungible",
+    "NonFungibleEdition",
+    "ProgrammableNonFungible",
+    "ProgrammableNonFungibleEdition",
+];
+
+// Older metadata pads names and symbols with NULs to a fixed length.
+fn read_string(reader: &mut DataReader) -> Option<String> {
+    let len = reader.read_u32()? as usize;
+    let bytes = reader.read_bytes(len)?;
+    Some(String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string())
+}
+
+// Some(None) for an absent value, None when the data runs out.
+fn read_option<T>(reader: &mut DataReader, read: fn(&mut DataReader) -> Option<T>) -> Option<Option<T>> {
+    match reader.read_u8()? {
+        0 => Some(None),
+        1 => read(reader).map(Some),
+        _ => None,
+    }
+}
+
+fn read_creators(reader: &mut DataReader) -> Option<Vec<Creator>> {
+    let len = reader.read_u32()?;
+    (0..len)
+        .map(|_| Some(Creator { address: reader.read_pubkey()?, verified: reader.read_u8()? != 0, share: reader.read_u8()? }))
+        .collect()
+}
+
+fn read_collection(reader: &mut DataReader) -> Option<Collection> {
+    let verified = reader.read_u8()? != 0;
+    Some(Collection { key: reader.read_pubkey()?, verified })
+}
+
+// Uses: a u8 method, then remaining and total as u64.
+fn skip_uses(reader: &mut DataReader) -> Option<()> {
+    reader.read_bytes(1 + 8 + 8).map(|_| ())
+}
+
+fn instruction_name(tag: u8) -> Option<&'static str> {
+    Some(match tag {
+        1 => "UpdateMetadataAccount",
+        4 => "UpdatePrimarySaleHappenedViaToken",
+        7 => "SignMetadata",
+        10 => "CreateMasterEdition",
+        11 => "MintNewEditionFromMasterEditionViaToken",
+        12 => "ConvertMasterEditionV1ToV2",
+        13 => "MintNewEditionFromMasterEditionViaVaultProxy",
+        14 => "PuffMetadata",
+        15 => "UpdateMetadataAccountV2",
+        17 => "CreateMasterEditionV3",
+        18 => "VerifyCollection",
+        19 => "Utilize",
+        20 => "ApproveUseAuthority",
+        21 => "RevokeUseAuthority",
+        22 => "UnverifyCollection",
+        23 => "ApproveCollectionAuthority",
+        24 => "RevokeCollectionAuthority",
+        25 => "SetAndVerifyCollection",
+        26 => "FreezeDelegatedAccount",
+        27 => "ThawDelegatedAccount",
+        28 => "RemoveCreatorVerification",
+        30 => "VerifySizedCollectionItem",
+        31 => "UnverifySizedCollectionItem",
+        32 => "SetAndVerifySizedCollectionItem",
+        34 => "SetCollectionSize",
+        35 => "SetTokenStandard",
+        36 => "BubblegumSetCollectionSize",
+        37 => "BurnEditionNft",
+        38 => "CreateEscrowAccount",
+        39 => "CloseEscrowAccount",
+        40 => "TransferOutOfEscrow",
+        43 => "Mint",
+        44 => "Delegate",
+        45 => "Revoke",
+        46 => "Lock",
+        47 => "Unlock",
+        48 => "Migrate",
+        50 => "Update",
+        51 => "Use",
+        52 => "Verify",
+        53 => "Unverify",
+        54 => "Collect",
+        55 => "Print",
+        56 => "Resize",
+        57 => "CloseAccounts",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn borsh_string(out: &mut Vec<u8>, value: &str) {
+        out.extend((value.len() as u32).to_le_bytes());
+        out.extend(value.as_bytes());
+    }
+
+    fn decode(data: &[u8], accounts: &[Pubkey]) -> TokenMetadataInstruction {
+        match TokenMetadataDecoder.decode(data, accounts) {
+            Some(DecodedInstruction::TokenMetadata(instruction)) => instruction,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn decodes_create_transfer_and_burn() {
+        let keys: Vec<Pubkey> = (1..=17u8).map(|i| Pubkey::new([i; 32])).collect();
+
+        // CreateMetadataAccountV3 with one creator and an unverified collection.
+        let mut data = vec![33];
+        borsh_string(&mut data, "Mad Lads #1\0\0\0");
+        borsh_string(&mut data, "MAD");
+        borsh_string(&mut data, "https://example.com/1.json");
+        data.extend(500u16.to_le_bytes());
+        data.extend([1, 1, 0, 0, 0]);
+        data.extend(keys[16].as_bytes());
+        data.extend([1, 100]);
+        data.extend([1, 0]);
+        data.extend(keys[15].as_bytes());
+        data.extend([0, 1, 0]);
+        let TokenMetadataInstruction::CreateMetadata { mint, update_authority, name, creators, collection, is_mutable, .. } = decode(&data, &keys[..7]) else {
+            panic!();
+        };
+        assert_eq!((mint, update_authority, name.as_str(), is_mutable), (keys[1], keys[4], "Mad Lads #1", true));
+        assert_eq!(creators, [Creator { address: keys[16], verified: true, share: 100 }]);
+        assert_eq!(collection, Some(Collection { key: keys[15], verified: false }));
+
+        // Transfer V1 of one pNFT, without authorization data.
+        let mut data = vec![49, 0];
+        data.extend(1u64.to_le_bytes());
+        data.push(0);
+        let TokenMetadataInstruction::Transfer { amount, destination_owner, mint, authority, .. } = decode(&data, &keys) else {
+            panic!();
+        };
+        assert_eq!((amount, destination_owner, mint, authority), (1, keys[3], keys[4], keys[9]));
+
+        // Burn V1 outside a collection: the collection slot holds the program ID.
+        let mut accounts = keys[..14].to_vec();
+        accounts[1] = TOKEN_METADATA_PROGRAM_ID;
+        let mut data = vec![41, 0];
+        data.extend(1u64.to_le_bytes());
+        let TokenMetadataInstruction::Burn { mint, collection_metadata, .. } = decode(&data, &accounts) else {
+            panic!();
+        };
+        assert_eq!((mint, collection_metadata), (keys[4], None));
+        assert!(matches!(decode(&[52, 0], &keys), TokenMetadataInstruction::Other { name } if name == "Verify"));
+    }
+}
@@ -0,0 +1,377 @@
+use super::{account, DataReader, DecodedInstruction, ProgramDecoder};
+use crate::pubkey::Pubkey;
+use crate::types::VOTE_PROGRAM_ID;
+use serde::{Deserialize, Serialize};
+
+// A slot in the validator's tower and how many votes have landed on top of
+// it; its lockout is 2^confirmation_count slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockout {
+    pub slot: u64,
+    pub confirmation_count: u32,
+}
+
+// Hashes (bank hash, switch proof, block ID) are base58, like blockhashes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VoteInstruction {
+    InitializeAccount {
+        vote_account: Pubkey,
+        node: Pubkey,
+        authorized_voter: Pubkey,
+        authorized_withdrawer: Pubkey,
+        commission: u8,
+    },
+    Authorize {
+        vote_account: Pubkey,
+        authority: Pubkey,
+        new_authority: Pubkey,
+        // "voter" or "withdrawer".
+        authority_type: String,
+    },
+    // The original vote: the slots voted on, the bank hash of the last one.
+    // Vote and VoteSwitch.
+    Vote {
+        vote_account: Pubkey,
+        vote_authority: Pubkey,
+        slots: Vec<u64>,
+        hash: String,
+        timestamp: Option<i64>,
+        switch_proof_hash: Option<String>,
+    },
+    // The whole tower in one instruction: UpdateVoteState and
+    // CompactUpdateVoteState, with or without a switch proof.
+    VoteStateUpdate {
+        vote_account: Pubkey,
+        vote_authority: Pubkey,
+        lockouts: Vec<Lockout>,
+        root: Option<u64>,
+        hash: String,
+        timestamp: Option<i64>,
+        switch_proof_hash: Option<String>,
+    },
+    // VoteStateUpdate plus the ID of the voted block; TowerSync and
+    // TowerSyncSwitch.
+    TowerSync {
+        vote_account: Pubkey,
+        vote_authority: Pubkey,
+        lockouts: Vec<Lockout>,
+        root: Option<u64>,
+        hash: String,
+        timestamp: Option<i64>,
+        block_id: String,
+        switch_proof_hash: Option<String>,
+    },
+    Withdraw {
+        vote_account: Pubkey,
+        recipient: Pubkey,
+        withdraw_authority: Pubkey,
+        lamports: u64,
+    },
+    UpdateValidatorIdentity {
+        vote_account: Pubkey,
+        node: Pubkey,
+        withdraw_authority: Pubkey,
+    },
+    UpdateCommission {
+        vote_account: Pubkey,
+        withdraw_authority: Pubkey,
+        commission: u8,
+    },
+    Other {
+        name: String,
+    },
+}
+
+impl VoteInstruction {
+    // Slots this instruction votes for, oldest first: a Vote's slots, or the
+    // slots in a tower's lockouts.
+    pub fn voted_slots(&self) -> Vec<u64> {
+        match self {
+            VoteInstruction::Vote { slots, .. } => slots.clone(),
+            VoteInstruction::VoteStateUpdate { lockouts, .. } | VoteInstruction::TowerSync { lockouts, .. } => {
+                lockouts.iter().map(|lockout| lockout.slot).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    // The validator's clock reading, attached to some votes.
+    pub fn timestamp(&self) -> Option<i64> {
+        match self {
+            VoteInstruction::Vote { timestamp, .. }
+            | VoteInstruction::VoteStateUpdate { timestamp, .. }
+            | VoteInstruction::TowerSync { timestamp, .. } => *timestamp,
+            _ => None,
+        }
+    }
+}
+
+pub struct VoteDecoder;
+
+impl ProgramDecoder for VoteDecoder {
+    fn program_id(&self) -> Pubkey {
+        VOTE_PROGRAM_ID
+    }
+
+    // bincode like the System and Stake programs: a u32 tag, then the
+    // fields. Vote and VoteSwitch pass the slot hashes and clock sysvars
+    // before the authority; the tower instructions only the vote account and
+    // authority. The compact encodings (tags 12 to 15) store the root as
+    // u64::MAX when there is none and each lockout as its distance from the
+    // one before, starting from the root.
+    fn decode(&self, data: &[u8], accounts: &[Pubkey]) -> Option<DecodedInstruction> {
+        let mut reader = DataReader::new(data);
+        let tag = reader.read_u32()?;
+        let instruction = match tag {
+            0 => VoteInstruction::InitializeAccount {
+                node: reader.read_pubkey()?,
+                authorized_voter: reader.read_pubkey()?,
+                authorized_withdrawer: reader.read_pubkey()?,
+                commission: reader.read_u8()?,
+                vote_account: account(accounts, 0)?,
+            },
+            1 => VoteInstruction::Authorize {
+                new_authority: reader.read_pubkey()?,
+                authority_type: authority_type(reader.read_u32()?)?,
+                vote_account: account(accounts, 0)?,
+                authority: account(accounts, 2)?,
+            },
+            2 | 6 => {
+                let count = usize::try_from(reader.read_u64()?).ok()?;
+                // Each slot takes 8 bytes; don't trust the count further.
+                let mut slots = Vec::with_capacity(count.min(reader.remaining().len() / 8));
+                for _ in 0..count {
+                    slots.push(reader.read_u64()?);
+                }
+                VoteInstruction::Vote {
+                    slots,
+                    hash: read_hash(&mut reader)?,
+                    timestamp: read_timestamp(&mut reader)?,
+                    switch_proof_hash: if tag == 6 { Some(read_hash(&mut reader)?) } else { None },
+                    vote_account: account(accounts, 0)?,
+                    vote_authority: account(accounts, 3)?,
+                }
+            }
+            3 => VoteInstruction::Withdraw {
+                lamports: reader.read_u64()?,
+                vote_account: account(accounts, 0)?,
+                recipient: account(accounts, 1)?,
+                withdraw_authority: account(accounts, 2)?,
+            },
+            4 => VoteInstruction::UpdateValidatorIdentity {
+                vote_account: account(accounts, 0)?,
+                node: account(accounts, 1)?,
+                withdraw_authority: account(accounts, 2)?,
+            },
+            5 => VoteInstruction::UpdateCommission {
+                commission: reader.read_u8()?,
+                vote_account: account(accounts, 0)?,
+                withdraw_authority: account(accounts, 1)?,
+            },
+            8 | 9 | 12 | 13 => {
+                let (lockouts, root) = if tag < 12 { read_lockouts(&mut reader)? } else { read_compact_lockouts(&mut reader)? };
+                VoteInstruction::VoteStateUpdate {
+                    lockouts,
+                    root,
+                    hash: read_hash(&mut reader)?,
+                    timestamp: read_timestamp(&mut reader)?,
+                    switch_proof_hash: if tag % 2 == 1 { Some(read_hash(&mut reader)?) } else { None },
+                    vote_account: account(accounts, 0)?,
+                    vote_authority: account(accounts, 1)?,
+                }
+            }
+            14 | 15 => {
+                let (lockouts, root) = read_compact_lockouts(&mut reader)?;
+                VoteInstruction::TowerSync {
+                    lockouts,
+                    root,
+                    hash: read_hash(&mut reader)?,
+                    timestamp: read_timestamp(&mut reader)?,
+                    block_id: read_hash(&mut reader)?,
+                    switch_proof_hash: if tag == 15 { Some(read_hash(&mut reader)?) } else { None },
+                    vote_account: account(accounts, 0)?,
+                    vote_authority: account(accounts, 1)?,
+                }
+            }
+            tag => VoteInstruction::Other { name: instruction_name(tag)?.to_string() },
+        };
+        Some(DecodedInstruction::Vote(instruction))
+    }
+
+    fn error_name(&self, code: u32) -> Option<String> {
+        VOTE_ERRORS.get(code as usize).map(|name| name.to_string())
+    }
+}
+
+fn read_hash(reader: &mut DataReader) -> Option<String> {
+    Some(bs58::encode(reader.read_bytes(32)?).into_string())
+}
+
+// bincode Option<i64>: a 0/1 byte, then the value.
+fn read_timestamp(reader: &mut DataReader) -> Option<Option<i64>> {
+    match reader.read_u8()? {
+        0 => Some(None),
+        1 => Some(Some(reader.read_u64()? as i64)),
+        _ => None,
+    }
+}
+
+// UpdateVoteState: a u64 count of (slot, u32 confirmation count), then the
+// root as an Option<u64>.
+fn read_lockouts(reader: &mut DataReader) -> Option<(Vec<Lockout>, Option<u64>)> {
+    let count = usize::try_from(reader.read_u64()?).ok()?;
+    let mut lockouts = Vec::with_capacity(count.min(reader.remaining().len() / 12));
+    for _ in 0..count {
+        lockouts.push(Lockout { slot: reader.read_u64()?, confirmation_count: reader.read_u32()? });
+    }
+    let root = match reader.read_u8()? {
+        0 => None,
+        1 => Some(reader.read_u64()?),
+        _ => return None,
+    };
+    Some((lockouts, root))
+}
+
+// The compact form: the root (u64::MAX for none), then a short_vec of
+// (varint offset, u8 confirmation count).
+fn read_compact_lockouts(reader: &mut DataReader) -> Option<(Vec<Lockout>, Option<u64>)> {
+    let root = Some(reader.read_u64()?).filter(|&root| root != u64::MAX);
+    let count = usize::try_from(read_varint(reader)?).ok()?;
+    let mut lockouts = Vec::with_capacity(count.min(reader.remaining().len() / 2));
+    let mut slot = root.unwrap_or_default();
+    for _ in 0..count {
+        slot = slot.checked_add(read_varint(reader)?)?;
+        lockouts.push(Lockout { slot, confirmation_count: reader.read_u8()? as u32 });
+    }
+    Some((lockouts, root))
+}
+
+// LEB128: seven bits per byte, low groups first, high bit set on all but
+// the last. Also the encoding of short_vec lengths.
+fn read_varint(reader: &mut DataReader) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = reader.read_u8()?;
+        value |= u64::from(byte & 0x7f).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn authority_type(value: u32) -> Option<String> {
+    Some(match value {
+        0 => "voter",
+        1 => "withdrawer",
+        _ => return None,
+    }.to_string())
+}
+
+// VoteError, in code order.
+const VOTE_ERRORS: &[&str] = &[
+    "VoteTooOld",
+    "SlotsMismatch",
+    "SlotHashMismatch",
+    "EmptySlots",
+    "TimestampTooOld",
+    "TooSoonToReauthorize",
+    "LockoutConflict",
+    "NewVoteStateLockoutMismatch",
+    "SlotsNotOrdered",
+    "ConfirmationsNotOrdered",
+    "ZeroConfirmations",
+    "ConfirmationTooLarge",
+    "RootRollBack",
+    "ConfirmationRollBack",
+    "SlotSmallerThanRoot",
+    "TooManyVotes",
+    "VotesTooOldAllFiltered",
+    "RootOnDifferentFork",
+    "ActiveVoteAccountClose",
+    "CommissionUpdateTooLate",
+    "AssertionFailed",
+];
+
+fn instruction_name(tag: u32) -> Option<&'static str> {
+    Some(match tag {
+        0 => "InitializeAccount",
+        1 => "Authorize",
+        2 => "Vote",
+        3 => "Withdraw",
+        4 => "UpdateValidatorIdentity",
+        5 => "UpdateCommission",
+        6 => "VoteSwitch",
+        7 => "AuthorizeChecked",
+        8 => "UpdateVoteState",
+        9 => "UpdateVoteStateSwitch",
+        10 => "AuthorizeWithSeed",
+        11 => "AuthorizeCheckedWithSeed",
+        12 => "CompactUpdateVoteState",
+        13 => "CompactUpdateVoteStateSwitch",
+        14 => "TowerSync",
+        15 => "TowerSyncSwitch",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(data: &[u8], accounts: &[Pubkey]) -> VoteInstruction {
+        match VoteDecoder.decode(data, accounts) {
+            Some(DecodedInstruction::Vote(instruction)) => instruction,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn decodes_votes_and_towers() {
+        let keys: Vec<Pubkey> = (1..=4u8).map(|i| Pubkey::new([i; 32])).collect();
+
+        // Vote: slots 10 and 11, the bank hash, a timestamp.
+        let mut vote = 2u32.to_le_bytes().to_vec();
+        vote.extend(2u64.to_le_bytes());
+        vote.extend(10u64.to_le_bytes());
+        vote.extend(11u64.to_le_bytes());
+        vote.extend([7; 32]);
+        vote.push(1);
+        vote.extend(1_700_000_000i64.to_le_bytes());
+        let instruction = decode(&vote, &keys);
+        assert_eq!((instruction.voted_slots(), instruction.timestamp()), (vec![10, 11], Some(1_700_000_000)));
+        let VoteInstruction::Vote { vote_account, vote_authority, hash, .. } = instruction else { panic!() };
+        assert_eq!((vote_account, vote_authority), (keys[0], keys[3]));
+        assert_eq!(hash, bs58::encode([7; 32]).into_string());
+
+        // TowerSync: root 1000, lockouts at +1 (count 3) and +130 (a two-byte
+        // varint, count 1), no timestamp, then the block ID.
+        let mut tower = 14u32.to_le_bytes().to_vec();
+        tower.extend(1000u64.to_le_bytes());
+        tower.extend([2, 1, 3, 0x82, 0x01, 1]);
+        tower.extend([8; 32]);
+        tower.push(0);
+        tower.extend([9; 32]);
+        let instruction = decode(&tower, &keys[..2]);
+        assert_eq!((instruction.voted_slots(), instruction.timestamp()), (vec![1001, 1131], None));
+        let VoteInstruction::TowerSync { lockouts, root, block_id, switch_proof_hash, .. } = instruction else { panic!() };
+        assert_eq!((lockouts[0].confirmation_count, root), (3, Some(1000)));
+        assert_eq!((block_id, switch_proof_hash), (bs58::encode([9; 32]).into_string(), None));
+
+        // UpdateVoteState stores whole slots, and the root as an Option.
+        let mut update = 8u32.to_le_bytes().to_vec();
+        update.extend(1u64.to_le_bytes());
+        update.extend(42u64.to_le_bytes());
+        update.extend(5u32.to_le_bytes());
+        update.push(0);
+        update.extend([1; 32]);
+        update.push(0);
+        let VoteInstruction::VoteStateUpdate { lockouts, root, .. } = decode(&update, &keys) else { panic!() };
+        assert_eq!((lockouts, root), (vec![Lockout { slot: 42, confirmation_count: 5 }], None));
+
+        assert!(matches!(decode(&7u32.to_le_bytes(), &keys), VoteInstruction::Other { name } if name == "AuthorizeChecked"));
+        // Truncated before the hash.
+        assert!(VoteDecoder.decode(&vote[..30], &keys).is_none());
+    }
+}
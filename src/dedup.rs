@@ -0,0 +1,180 @@
+use crate::metrics::metrics;
+use crate::types::{ParsedBlock, ParsedTransaction};
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
+use std::hash::Hash;
+
+// ==========================================
+// DUPLICATE DETECTION
+// ==========================================
+// The same transaction can come through twice in one run: overlapping slot
+// ranges, a stream restarted a few slots back, or a switch to an RPC
+// provider whose view lags the last one. `DuplicateFilter` remembers the
+// signatures (and blockhashes) seen so far and either drops repeats or lets
+// them through with a warning, counting both.
+//
+// Memory is bounded: only the last `window` signatures are remembered, so
+// a repeat from further back than that goes unnoticed. The database sinks
+// upsert on signature anyway; this is for the append-only outputs and for
+// counting.
+
+// About a thousand blocks' worth of transactions.
+pub const DEFAULT_DEDUP_WINDOW: usize = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    // Leave repeats out of the output.
+    Skip,
+    // Write them anyway, with a warning.
+    Flag,
+}
+
+impl DuplicatePolicy {
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "skip" => Ok(DuplicatePolicy::Skip),
+            "flag" => Ok(DuplicatePolicy::Flag),
+            other => Err(format!("unknown duplicate policy '{}' (expected skip or flag)", other)),
+        }
+    }
+}
+
+// The last `capacity` distinct values seen, oldest forgotten first.
+#[derive(Debug)]
+struct Window<T> {
+    capacity: usize,
+    order: VecDeque<T>,
+    seen: HashSet<T>,
+}
+
+impl<T: Clone + Eq + Hash> Window<T> {
+    fn new(capacity: usize) -> Self {
+        Window { capacity: capacity.max(1), order: VecDeque::new(), seen: HashSet::new() }
+    }
+
+    // True the first time a value is inserted (or once it has been forgotten).
+    fn insert(&mut self, value: &T) -> bool {
+        if self.seen.contains(value) {
+            return false;
+        }
+        if self.order.len() == self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.seen.remove(&oldest);
+        }
+        self.order.push_back(value.clone());
+        self.seen.insert(value.clone());
+        true
+    }
+}
+
+#[derive(Debug)]
+pub struct DuplicateFilter {
+    policy: DuplicatePolicy,
+    signatures: Window<String>,
+    blockhashes: Window<String>,
+    duplicate_blocks: u64,
+    duplicate_transactions: u64,
+}
+
+impl DuplicateFilter {
+    pub fn new(policy: DuplicatePolicy, window: usize) -> Self {
+        DuplicateFilter {
+            policy,
+            signatures: Window::new(window),
+            blockhashes: Window::new(window),
+            duplicate_blocks: 0,
+            duplicate_transactions: 0,
+        }
+    }
+
+    pub fn policy(&self) -> DuplicatePolicy {
+        self.policy
+    }
+
+    /// The block to write: None for a block already seen when skipping
+    /// duplicates, otherwise the block with repeated transactions left out
+    /// (or, when flagging, as is). Transactions of a repeated block count
+    /// as duplicates too.
+    pub fn filter_block<'a>(&mut self, block: &'a ParsedBlock) -> Option<Cow<'a, ParsedBlock>> {
+        let new_block = self.blockhashes.insert(&block.blockhash);
+        let fresh: Vec<bool> = block.transactions.iter().map(|tx| self.signatures.insert(&tx.signature)).collect();
+        let repeats = fresh.iter().filter(|fresh| !**fresh).count();
+        self.duplicate_transactions += repeats as u64;
+        metrics().record_duplicate_transactions(repeats);
+        if !new_block {
+            self.duplicate_blocks += 1;
+            log::warn!(slot = block.slot, blockhash = block.blockhash.as_str(); "block seen before in this run");
+        } else if repeats > 0 {
+            log::warn!(slot = block.slot, transactions = repeats; "transactions seen before in this run");
+        }
+
+        match self.policy {
+            DuplicatePolicy::Flag => Some(Cow::Borrowed(block)),
+            DuplicatePolicy::Skip if !new_block => None,
+            DuplicatePolicy::Skip if repeats == 0 => Some(Cow::Borrowed(block)),
+            DuplicatePolicy::Skip => {
+                let mut fresh = fresh.into_iter();
+                let mut filtered = block.clone();
+                filtered.transactions.retain(|_| fresh.next().unwrap_or(true));
+                Some(Cow::Owned(filtered))
+            }
+        }
+    }
+
+    /// Whether to write a transaction parsed outside a block.
+    pub fn admit(&mut self, tx: &ParsedTransaction) -> bool {
+        if self.signatures.insert(&tx.signature) {
+            return true;
+        }
+        self.duplicate_transactions += 1;
+        metrics().record_duplicate_transactions(1);
+        log::warn!(signature = tx.signature.as_str(); "transaction seen before in this run");
+        self.policy == DuplicatePolicy::Flag
+    }
+
+    pub fn duplicate_blocks(&self) -> u64 {
+        self.duplicate_blocks
+    }
+
+    pub fn duplicate_transactions(&self) -> u64 {
+        self.duplicate_transactions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn skips_repeated_blocks_and_transactions() {
+        let block = fixtures::block();
+        let mut filter = DuplicateFilter::new(DuplicatePolicy::Skip, 100_000);
+
+        assert!(matches!(filter.filter_block(block), Some(Cow::Borrowed(_))));
+        assert!(filter.filter_block(block).is_none());
+        assert_eq!(filter.duplicate_blocks(), 1);
+        assert_eq!(filter.duplicate_transactions(), block.transactions.len() as u64);
+
+        // The same transactions under another blockhash (a refetch from a
+        // fork, say) are dropped from it.
+        let mut other = block.clone();
+        other.blockhash = "other".to_string();
+        other.transactions.truncate(3);
+        other.transactions[1].signature = "new".to_string();
+        let written = filter.filter_block(&other).unwrap();
+        assert_eq!(written.transactions.iter().map(|tx| tx.signature.as_str()).collect::<Vec<_>>(), ["new"]);
+        assert!(!filter.admit(&block.transactions[0]));
+
+        // Flagging counts but writes everything; a small window forgets.
+        let mut filter = DuplicateFilter::new(DuplicatePolicy::Flag, 2);
+        for tx in &block.transactions[..3] {
+            assert!(filter.admit(tx));
+        }
+        assert!(filter.admit(&block.transactions[2]));
+        assert_eq!(filter.duplicate_transactions(), 1);
+        assert!(filter.admit(&block.transactions[0]));
+        assert_eq!(filter.duplicate_transactions(), 1);
+    }
+}
@@ -0,0 +1,37 @@
+use super::{detect_anchor_swap, AnchorSwap, DexAdapter, SwapInstruction, Venue};
+use crate::decoders::jupiter::JUPITER_V6_PROGRAM_ID;
+use crate::pubkey::Pubkey;
+use crate::types::{ParsedInstruction, ParsedTransaction};
+
+// Route instructions name no pool; the hops show up as CPIs into the
+// venues they route through. The shared-accounts variants move tokens
+// through the program's own accounts, which pushes the user's transfer
+// authority down one position.
+pub struct Jupiter;
+
+const ROUTES: &[AnchorSwap] = &[
+    AnchorSwap { name: "route", pool: None, trader: 1 },
+    AnchorSwap { name: "route_with_token_ledger", pool: None, trader: 1 },
+    AnchorSwap { name: "exact_out_route", pool: None, trader: 1 },
+    AnchorSwap { name: "shared_accounts_route", pool: None, trader: 2 },
+    AnchorSwap { name: "shared_accounts_route_with_token_ledger", pool: None, trader: 2 },
+    AnchorSwap { name: "shared_accounts_exact_out_route", pool: None, trader: 2 },
+    AnchorSwap { name: "route_v2", pool: None, trader: 0 },
+    AnchorSwap { name: "exact_out_route_v2", pool: None, trader: 0 },
+    AnchorSwap { name: "shared_accounts_route_v2", pool: None, trader: 1 },
+    AnchorSwap { name: "shared_accounts_exact_out_route_v2", pool: None, trader: 1 },
+];
+
+impl DexAdapter for Jupiter {
+    fn venue(&self) -> Venue {
+        Venue::Jupiter
+    }
+
+    fn program_id(&self) -> Pubkey {
+        JUPITER_V6_PROGRAM_ID
+    }
+
+    fn detect(&self, _tx: &ParsedTransaction, ix: &ParsedInstruction) -> Option<SwapInstruction> {
+        detect_anchor_swap(ix, ROUTES)
+    }
+}
@@ -0,0 +1,72 @@
+use super::{detect_anchor_swap, AnchorSwap, DexAdapter, SwapInstruction, Venue};
+use crate::pubkey::Pubkey;
+use crate::types::{ParsedInstruction, ParsedTransaction};
+
+pub const METEORA_DLMM_PROGRAM_ID: Pubkey = Pubkey::from_str_const("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo");
+pub const METEORA_POOLS_PROGRAM_ID: Pubkey = Pubkey::from_str_const("Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB");
+pub const METEORA_DAMM_V2_PROGRAM_ID: Pubkey = Pubkey::from_str_const("cpamdpZCGKUy5JxQXB4dcpGPiikHawvSWAd6mEn1sGG");
+
+pub struct MeteoraDlmm;
+
+const DLMM_SWAPS: &[AnchorSwap] = &[
+    AnchorSwap { name: "swap", pool: Some(0), trader: 10 },
+    AnchorSwap { name: "swap_exact_out", pool: Some(0), trader: 10 },
+    AnchorSwap { name: "swap_with_price_impact", pool: Some(0), trader: 10 },
+    AnchorSwap { name: "swap2", pool: Some(0), trader: 10 },
+    AnchorSwap { name: "swap_exact_out2", pool: Some(0), trader: 10 },
+    AnchorSwap { name: "swap_with_price_impact2", pool: Some(0), trader: 10 },
+];
+
+impl DexAdapter for MeteoraDlmm {
+    fn venue(&self) -> Venue {
+        Venue::MeteoraDlmm
+    }
+
+    fn program_id(&self) -> Pubkey {
+        METEORA_DLMM_PROGRAM_ID
+    }
+
+    fn detect(&self, _tx: &ParsedTransaction, ix: &ParsedInstruction) -> Option<SwapInstruction> {
+        detect_anchor_swap(ix, DLMM_SWAPS)
+    }
+}
+
+// Dynamic AMM pools.
+pub struct MeteoraPools;
+
+const POOLS_SWAPS: &[AnchorSwap] = &[AnchorSwap { name: "swap", pool: Some(0), trader: 12 }];
+
+impl DexAdapter for MeteoraPools {
+    fn venue(&self) -> Venue {
+        Venue::MeteoraPools
+    }
+
+    fn program_id(&self) -> Pubkey {
+        METEORA_POOLS_PROGRAM_ID
+    }
+
+    fn detect(&self, _tx: &ParsedTransaction, ix: &ParsedInstruction) -> Option<SwapInstruction> {
+        detect_anchor_swap(ix, POOLS_SWAPS)
+    }
+}
+
+pub struct MeteoraDammV2;
+
+const DAMM_V2_SWAPS: &[AnchorSwap] = &[
+    AnchorSwap { name: "swap", pool: Some(1), trader: 8 },
+    AnchorSwap { name: "swap2", pool: Some(1), trader: 8 },
+];
+
+impl DexAdapter for MeteoraDammV2 {
+    fn venue(&self) -> Venue {
+        Venue::MeteoraDammV2
+    }
+
+    fn program_id(&self) -> Pubkey {
+        METEORA_DAMM_V2_PROGRAM_ID
+    }
+
+    fn detect(&self, _tx: &ParsedTransaction, ix: &ParsedInstruction) -> Option<SwapInstruction> {
+        detect_anchor_swap(ix, DAMM_V2_SWAPS)
+    }
+}
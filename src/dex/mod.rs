@@ -0,0 +1,294 @@
+pub mod jupiter;
+pub mod meteora;
+pub mod orca;
+pub mod pool;
+pub mod raydium;
+
+use crate::decoders::anchor::sighash;
+use crate::transfers::{extract_token_transfers, walk_instructions};
+use crate::pubkey::Pubkey;
+use crate::types::{ParsedInstruction, ParsedTransaction};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+pub use crate::decoders::jupiter::JUPITER_V6_PROGRAM_ID;
+pub use jupiter::Jupiter;
+pub use meteora::{MeteoraDammV2, MeteoraDlmm, MeteoraPools, METEORA_DAMM_V2_PROGRAM_ID, METEORA_DLMM_PROGRAM_ID, METEORA_POOLS_PROGRAM_ID};
+pub use orca::{OrcaWhirlpool, ORCA_WHIRLPOOL_PROGRAM_ID};
+pub use pool::{detect_pool_updates, PoolReserve, PoolUpdate};
+pub use raydium::{RaydiumAmmV4, RaydiumClmm, RaydiumCpmm, RAYDIUM_CLMM_PROGRAM_ID, RAYDIUM_CPMM_PROGRAM_ID};
+
+// ==========================================
+// SWAP DETECTION
+// ==========================================
+// Each supported DEX program gets an adapter that recognizes its swap
+// instructions (top-level or CPI) and names the pool and trader where the
+// accounts make that possible. The amounts don't come from instruction
+// arguments, which are limits rather than what executed, but from the
+// token transfers inside the instructions that swap: what the trader's
+// token accounts sent and received, netted per mint. A multi-hop route
+// therefore collapses into one event from the first input to the last
+// output, with each hop kept as a leg. When the transfers can't be
+// attributed to the trader, the trader's token balance deltas are used.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Venue {
+    RaydiumAmmV4,
+    RaydiumCpmm,
+    RaydiumClmm,
+    OrcaWhirlpool,
+    MeteoraDlmm,
+    MeteoraPools,
+    MeteoraDammV2,
+    Jupiter,
+}
+
+impl Venue {
+    // Aggregators route through the other venues rather than holding
+    // liquidity themselves.
+    pub fn is_aggregator(self) -> bool {
+        matches!(self, Venue::Jupiter)
+    }
+}
+
+impl fmt::Display for Venue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Venue::RaydiumAmmV4 => "Raydium AMM v4",
+            Venue::RaydiumCpmm => "Raydium CPMM",
+            Venue::RaydiumClmm => "Raydium CLMM",
+            Venue::OrcaWhirlpool => "Orca Whirlpool",
+            Venue::MeteoraDlmm => "Meteora DLMM",
+            Venue::MeteoraPools => "Meteora Pools",
+            Venue::MeteoraDammV2 => "Meteora DAMM v2",
+            Venue::Jupiter => "Jupiter",
+        })
+    }
+}
+
+// What an adapter can tell from a single swap instruction.
+#[derive(Debug, Clone, Default)]
+pub struct SwapInstruction {
+    pub pool: Option<Pubkey>,
+    // Wallet whose token accounts the swap debits and credits.
+    pub trader: Option<Pubkey>,
+}
+
+pub trait DexAdapter: Send + Sync {
+    fn venue(&self) -> Venue;
+
+    fn program_id(&self) -> Pubkey;
+
+    // Some if `ix` (already known to target this program) is a swap.
+    fn detect(&self, tx: &ParsedTransaction, ix: &ParsedInstruction) -> Option<SwapInstruction>;
+}
+
+static ADAPTERS: &[&dyn DexAdapter] = &[
+    &RaydiumAmmV4,
+    &RaydiumCpmm,
+    &RaydiumClmm,
+    &OrcaWhirlpool,
+    &MeteoraDlmm,
+    &MeteoraPools,
+    &MeteoraDammV2,
+    &Jupiter,
+];
+
+pub fn adapter_for(program_id: &Pubkey) -> Option<&'static dyn DexAdapter> {
+    ADAPTERS.iter().copied().find(|adapter| adapter.program_id() == *program_id)
+}
+
+// One pool-level swap within the transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwapLeg {
+    pub venue: Venue,
+    pub program_id: Pubkey,
+    pub pool: Option<Pubkey>,
+    pub instruction_index: usize,
+    pub inner_index: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwapEvent {
+    // The aggregator when the swap was routed through one, otherwise the
+    // venue of the first leg.
+    pub venue: Venue,
+    pub trader: Pubkey,
+    pub in_mint: Pubkey,
+    // Raw amounts in base units.
+    pub in_amount: u64,
+    pub in_decimals: Option<u8>,
+    pub out_mint: Pubkey,
+    pub out_amount: u64,
+    pub out_decimals: Option<u8>,
+    // Empty when an aggregator route went only through venues without an
+    // adapter.
+    pub legs: Vec<SwapLeg>,
+}
+
+#[derive(Default)]
+struct Flow {
+    sent: u64,
+    received: u64,
+    decimals: Option<u8>,
+}
+
+// None for failed transactions, transactions without a swap on a known
+// venue, and swaps whose input or output couldn't be established.
+pub fn detect_swap(tx: &ParsedTransaction) -> Option<SwapEvent> {
+    if !tx.is_success {
+        return None;
+    }
+
+    let mut legs = Vec::new();
+    let mut aggregator = None;
+    let mut trader = None;
+    let mut instructions = HashSet::new();
+    walk_instructions(tx, |ix, instruction_index, inner_index| {
+        let Some(adapter) = adapter_for(&ix.program_id) else {
+            return;
+        };
+        let Some(swap) = adapter.detect(tx, ix) else {
+            return;
+        };
+        // The outermost swap names the trader when the user called it
+        // directly. A swap made through another program (an aggregator or
+        // a bot) sees that program's authority, so it goes to the fee payer.
+        trader.get_or_insert(if inner_index.is_none() { swap.trader } else { None });
+        instructions.insert(instruction_index);
+        if adapter.venue().is_aggregator() {
+            aggregator.get_or_insert(adapter.venue());
+        } else {
+            legs.push(SwapLeg {
+                venue: adapter.venue(),
+                program_id: ix.program_id,
+                pool: swap.pool,
+                instruction_index,
+                inner_index,
+            });
+        }
+    });
+    let venue = aggregator.or_else(|| legs.first().map(|leg| leg.venue))?;
+    let trader = trader.flatten().unwrap_or(tx.fee_payer);
+
+    let mut flows: BTreeMap<Pubkey, Flow> = BTreeMap::new();
+    for transfer in extract_token_transfers(tx) {
+        if !instructions.contains(&transfer.instruction_index) {
+            continue;
+        }
+        let Some(mint) = transfer.mint else {
+            continue;
+        };
+        let sent = transfer.source_owner == Some(trader);
+        let received = transfer.destination_owner == Some(trader);
+        if sent == received {
+            continue;
+        }
+        let flow = flows.entry(mint).or_default();
+        flow.decimals = flow.decimals.or(transfer.decimals);
+        if sent {
+            flow.sent += transfer.amount;
+        }
+        if received {
+            flow.received += transfer.amount.saturating_sub(transfer.fee);
+        }
+    }
+    if flows.is_empty() {
+        for change in tx.token_balance_changes.iter().filter(|change| change.owner == trader) {
+            let flow = flows.entry(change.mint).or_default();
+            flow.decimals = Some(change.decimals);
+            if change.delta < 0 {
+                flow.sent += change.delta.unsigned_abs() as u64;
+            } else {
+                flow.received += change.delta as u64;
+            }
+        }
+    }
+
+    let (in_mint, in_amount, out_mint, out_amount) = net_flows(&flows)?;
+    Some(SwapEvent {
+        venue,
+        trader,
+        in_decimals: flows[&in_mint].decimals,
+        out_decimals: flows[&out_mint].decimals,
+        in_mint,
+        in_amount,
+        out_mint,
+        out_amount,
+        legs,
+    })
+}
+
+// The mint the trader gave up the most of and the one they gained the most
+// of. A round trip (arbitrage back into the starting mint) nets out
+// everywhere but that mint, so it's both the input and the output, with the
+// gross amounts.
+fn net_flows(flows: &BTreeMap<Pubkey, Flow>) -> Option<(Pubkey, u64, Pubkey, u64)> {
+    let net = |flow: &Flow| flow.received as i128 - flow.sent as i128;
+    let input = flows.iter().filter(|(_, flow)| net(flow) < 0).min_by_key(|(_, flow)| net(flow));
+    let output = flows.iter().filter(|(_, flow)| net(flow) > 0).max_by_key(|(_, flow)| net(flow));
+    match (input, output) {
+        (Some((in_mint, input)), Some((out_mint, output))) => {
+            Some((*in_mint, input.sent - input.received, *out_mint, output.received - output.sent))
+        }
+        (Some((mint, flow)), None) | (None, Some((mint, flow))) => Some((*mint, flow.sent, *mint, flow.received)),
+        (None, None) => None,
+    }
+}
+
+// ==========================================
+// ANCHOR SWAP INSTRUCTIONS
+// ==========================================
+// Most venues are Anchor programs, so their swaps are recognized by the
+// 8-byte discriminator of the instruction name, with the pool and trader
+// at fixed positions in the instruction's accounts.
+
+pub struct AnchorSwap {
+    pub name: &'static str,
+    pub pool: Option<usize>,
+    pub trader: usize,
+}
+
+pub(crate) fn detect_anchor_swap(ix: &ParsedInstruction, swaps: &[AnchorSwap]) -> Option<SwapInstruction> {
+    let data = ix.data_bytes()?;
+    let discriminator = data.get(..8)?;
+    let swap = swaps.iter().find(|swap| sighash("global", swap.name) == discriminator)?;
+    Some(SwapInstruction {
+        pool: swap.pool.and_then(|index| ix.account(index)),
+        trader: ix.account(swap.trader),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn detects_swaps_across_venues_in_a_block() {
+        let block = fixtures::block();
+        let swaps: Vec<SwapEvent> = block.transactions.iter().filter_map(detect_swap).collect();
+
+        let venues: HashSet<Venue> = swaps.iter().map(|swap| swap.venue).collect();
+        for venue in [Venue::RaydiumClmm, Venue::MeteoraDlmm, Venue::MeteoraDammV2, Venue::Jupiter] {
+            assert!(venues.contains(&venue), "no {} swap detected", venue);
+        }
+        for swap in &swaps {
+            assert!(swap.in_amount > 0 && swap.out_amount > 0, "{:?}", swap);
+        }
+        // A Jupiter route through a DAMM v2 pool: tagged with the aggregator,
+        // with the hop kept as a leg.
+        let tx = block
+            .get_transaction("4ABEoFeVZen9VzVsQJaaS4vPugMYorZDXpwT1Hkmc7PGn4hqjUjrmiVLeDvq2MU5jsrH8VERK1cXtbWXBHJKtfm1")
+            .unwrap();
+        let routed = detect_swap(tx).unwrap();
+        assert_eq!(routed.venue, Venue::Jupiter);
+        assert_eq!(routed.trader, tx.fee_payer);
+        assert_eq!((routed.in_mint, routed.in_amount), (crate::transfers::NATIVE_MINT, 87_951_302));
+        assert_eq!(routed.out_amount, 17_652_869_070);
+        assert_eq!(routed.legs.len(), 1);
+        assert_eq!(routed.legs[0].venue, Venue::MeteoraDammV2);
+    }
+}
@@ -0,0 +1,29 @@
+use super::{detect_anchor_swap, AnchorSwap, DexAdapter, SwapInstruction, Venue};
+use crate::pubkey::Pubkey;
+use crate::types::{ParsedInstruction, ParsedTransaction};
+
+pub const ORCA_WHIRLPOOL_PROGRAM_ID: Pubkey = Pubkey::from_str_const("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc");
+
+pub struct OrcaWhirlpool;
+
+// Two-hop swaps go through two whirlpools; the first one is reported.
+const WHIRLPOOL_SWAPS: &[AnchorSwap] = &[
+    AnchorSwap { name: "swap", pool: Some(2), trader: 1 },
+    AnchorSwap { name: "swap_v2", pool: Some(4), trader: 3 },
+    AnchorSwap { name: "two_hop_swap", pool: Some(2), trader: 1 },
+    AnchorSwap { name: "two_hop_swap_v2", pool: Some(0), trader: 14 },
+];
+
+impl DexAdapter for OrcaWhirlpool {
+    fn venue(&self) -> Venue {
+        Venue::OrcaWhirlpool
+    }
+
+    fn program_id(&self) -> Pubkey {
+        ORCA_WHIRLPOOL_PROGRAM_ID
+    }
+
+    fn detect(&self, _tx: &ParsedTransaction, ix: &ParsedInstruction) -> Option<SwapInstruction> {
+        detect_anchor_swap(ix, WHIRLPOOL_SWAPS)
+    }
+}
@@ -0,0 +1,167 @@
+use super::{adapter_for, Venue};
+use crate::pubkey::Pubkey;
+use crate::transfers::walk_instructions;
+use crate::types::{ParsedTransaction, TokenBalance};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+// ==========================================
+// POOL STATE CHANGES
+// ==========================================
+// What each swap did to the pool it went through, read from the pool's
+// vaults in the transaction's token balances. A pool's vaults are the token
+// accounts among the swap instruction's accounts that belong to neither the
+// trader, the fee payer nor another signer; they're grouped by their owner
+// (the pool, or the program authority holding its vaults), so a two-hop
+// instruction yields one update per pool. A group needs at least two mints
+// and a balance that moved to count as a pool, which leaves out an
+// aggregator's intermediate accounts and accounts the swap only names
+// (Raydium AMM v4's OpenBook market vaults).
+//
+// Balances are per transaction, so a pool swapped against twice in one
+// transaction reports the combined movement, once.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolReserve {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub decimals: u8,
+    pub pre_amount: u64,
+    pub post_amount: u64,
+    pub delta: i128,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolUpdate {
+    pub venue: Venue,
+    pub program_id: Pubkey,
+    pub pool: Option<Pubkey>,
+    // Owner of the vaults.
+    pub vault_authority: Pubkey,
+    pub instruction_index: usize,
+    pub inner_index: Option<usize>,
+    // By mint.
+    pub reserves: Vec<PoolReserve>,
+    // For a two-mint pool whose reserves moved in opposite directions: the
+    // second reserve's tokens per token of the first that the swap
+    // implied, in UI units.
+    pub price: Option<f64>,
+}
+
+// One update per pool a successful transaction swapped against, in
+// execution order.
+pub fn detect_pool_updates(tx: &ParsedTransaction) -> Vec<PoolUpdate> {
+    let mut updates = Vec::new();
+    if !tx.is_success {
+        return updates;
+    }
+
+    let pre: BTreeMap<Pubkey, &TokenBalance> = tx.pre_token_balances.iter().map(|balance| (balance.account, balance)).collect();
+    let post: BTreeMap<Pubkey, &TokenBalance> = tx.post_token_balances.iter().map(|balance| (balance.account, balance)).collect();
+    let signers: HashSet<Pubkey> = tx.accounts.iter().filter(|account| account.is_signer).map(|account| account.pubkey).collect();
+    let mut seen_vaults = HashSet::new();
+
+    walk_instructions(tx, |ix, instruction_index, inner_index| {
+        let Some(adapter) = adapter_for(&ix.program_id) else {
+            return;
+        };
+        if adapter.venue().is_aggregator() {
+            return;
+        }
+        let Some(swap) = adapter.detect(tx, ix) else {
+            return;
+        };
+        let trader = swap.trader.unwrap_or(tx.fee_payer);
+
+        let mut groups: BTreeMap<Pubkey, Vec<PoolReserve>> = BTreeMap::new();
+        for vault in ix.account_keys().filter(|account| seen_vaults.insert(*account)) {
+            let (before, after) = (pre.get(&vault), post.get(&vault));
+            let Some(balance) = after.or(before) else {
+                continue;
+            };
+            let Some(owner) = balance.owner else {
+                continue;
+            };
+            if owner == trader || owner == tx.fee_payer || signers.contains(&owner) {
+                continue;
+            }
+            let pre_amount = before.map_or(0, |balance| balance.amount);
+            let post_amount = after.map_or(0, |balance| balance.amount);
+            groups.entry(owner).or_default().push(PoolReserve {
+                vault,
+                mint: balance.mint,
+                decimals: balance.decimals,
+                pre_amount,
+                post_amount,
+                delta: post_amount as i128 - pre_amount as i128,
+            });
+        }
+
+        let pools = groups.values().filter(|reserves| is_pool(reserves)).count();
+        for (owner, mut reserves) in groups.into_iter().filter(|(_, reserves)| is_pool(reserves)) {
+            reserves.sort_by_key(|reserve| reserve.mint);
+            updates.push(PoolUpdate {
+                venue: adapter.venue(),
+                program_id: ix.program_id,
+                // With several pools in one instruction, the vault owner is
+                // the pool itself (Whirlpool two-hop swaps).
+                pool: if pools == 1 { swap.pool } else { Some(owner) },
+                vault_authority: owner,
+                instruction_index,
+                inner_index,
+                price: implied_price(&reserves),
+                reserves,
+            });
+        }
+    });
+    updates
+}
+
+fn is_pool(reserves: &[PoolReserve]) -> bool {
+    reserves.iter().any(|reserve| reserve.delta != 0) && reserves.iter().map(|reserve| reserve.mint).collect::<HashSet<_>>().len() >= 2
+}
+
+fn implied_price(reserves: &[PoolReserve]) -> Option<f64> {
+    let [first, second] = reserves else {
+        return None;
+    };
+    if first.delta.signum() * second.delta.signum() != -1 {
+        return None;
+    }
+    let ui = |reserve: &PoolReserve| reserve.delta.unsigned_abs() as f64 / 10f64.powi(reserve.decimals as i32);
+    Some(ui(second) / ui(first))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::detect_swap;
+    use crate::fixtures;
+
+    #[test]
+    fn pool_reserves_mirror_the_swaps() {
+        let block = fixtures::block();
+        let mut checked = 0;
+        for tx in &block.transactions {
+            let updates = detect_pool_updates(tx);
+            for update in &updates {
+                assert!(update.reserves.len() >= 2, "{:?}", update);
+                assert!(update.reserves.iter().all(|reserve| reserve.delta == reserve.post_amount as i128 - reserve.pre_amount as i128));
+            }
+            // A single-pool swap called directly: the pool took in what the
+            // trader paid and gave out what they received.
+            let (Some(swap), [update]) = (detect_swap(tx), updates.as_slice()) else {
+                continue;
+            };
+            if !matches!(swap.legs.as_slice(), [leg] if leg.inner_index.is_none()) || swap.in_mint == swap.out_mint {
+                continue;
+            }
+            let delta = |mint: Pubkey| update.reserves.iter().find(|reserve| reserve.mint == mint).map(|reserve| reserve.delta);
+            assert_eq!(delta(swap.in_mint), Some(swap.in_amount as i128), "{}", tx.signature);
+            assert_eq!(delta(swap.out_mint), Some(-(swap.out_amount as i128)), "{}", tx.signature);
+            assert!(update.price.is_some_and(|price| price > 0.0));
+            checked += 1;
+        }
+        assert!(checked > 0);
+    }
+}
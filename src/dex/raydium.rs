@@ -0,0 +1,81 @@
+use super::{detect_anchor_swap, AnchorSwap, DexAdapter, SwapInstruction, Venue};
+use crate::decoders::raydium::RAYDIUM_AMM_V4_PROGRAM_ID;
+use crate::decoders::{DecodedInstruction, ProgramDecoder, RaydiumAmmV4Decoder, RaydiumInstruction};
+use crate::pubkey::Pubkey;
+use crate::types::{ParsedInstruction, ParsedTransaction};
+use std::borrow::Cow;
+
+pub const RAYDIUM_CPMM_PROGRAM_ID: Pubkey = Pubkey::from_str_const("CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C");
+pub const RAYDIUM_CLMM_PROGRAM_ID: Pubkey = Pubkey::from_str_const("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK");
+
+// The legacy AMM isn't an Anchor program; its swaps come from the
+// built-in decoder.
+pub struct RaydiumAmmV4;
+
+impl DexAdapter for RaydiumAmmV4 {
+    fn venue(&self) -> Venue {
+        Venue::RaydiumAmmV4
+    }
+
+    fn program_id(&self) -> Pubkey {
+        RAYDIUM_AMM_V4_PROGRAM_ID
+    }
+
+    fn detect(&self, tx: &ParsedTransaction, ix: &ParsedInstruction) -> Option<SwapInstruction> {
+        let decoded = match &ix.decoded {
+            Some(decoded) => Cow::Borrowed(decoded),
+            None => {
+                let accounts: Vec<Pubkey> = ix.resolved_accounts(tx).collect::<Option<_>>()?;
+                Cow::Owned(RaydiumAmmV4Decoder.decode(&ix.data_bytes()?, &accounts)?)
+            }
+        };
+        match decoded.as_ref() {
+            DecodedInstruction::Raydium(
+                RaydiumInstruction::SwapBaseIn { accounts, .. } | RaydiumInstruction::SwapBaseOut { accounts, .. },
+            ) => Some(SwapInstruction { pool: Some(accounts.amm), trader: Some(accounts.user_owner) }),
+            _ => None,
+        }
+    }
+}
+
+pub struct RaydiumCpmm;
+
+const CPMM_SWAPS: &[AnchorSwap] = &[
+    AnchorSwap { name: "swap_base_input", pool: Some(3), trader: 0 },
+    AnchorSwap { name: "swap_base_output", pool: Some(3), trader: 0 },
+];
+
+impl DexAdapter for RaydiumCpmm {
+    fn venue(&self) -> Venue {
+        Venue::RaydiumCpmm
+    }
+
+    fn program_id(&self) -> Pubkey {
+        RAYDIUM_CPMM_PROGRAM_ID
+    }
+
+    fn detect(&self, _tx: &ParsedTransaction, ix: &ParsedInstruction) -> Option<SwapInstruction> {
+        detect_anchor_swap(ix, CPMM_SWAPS)
+    }
+}
+
+pub struct RaydiumClmm;
+
+const CLMM_SWAPS: &[AnchorSwap] = &[
+    AnchorSwap { name: "swap", pool: Some(2), trader: 0 },
+    AnchorSwap { name: "swap_v2", pool: Some(2), trader: 0 },
+];
+
+impl DexAdapter for RaydiumClmm {
+    fn venue(&self) -> Venue {
+        Venue::RaydiumClmm
+    }
+
+    fn program_id(&self) -> Pubkey {
+        RAYDIUM_CLMM_PROGRAM_ID
+    }
+
+    fn detect(&self, _tx: &ParsedTransaction, ix: &ParsedInstruction) -> Option<SwapInstruction> {
+        detect_anchor_swap(ix, CLMM_SWAPS)
+    }
+}
@@ -0,0 +1,200 @@
+use crate::pubkey::Pubkey;
+use crate::types::{BlockReward, ParsedBlock, ParsedTransaction, RewardType};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// ==========================================
+// BLOCK DIFF
+// ==========================================
+// Compares two parsed blocks: the same slot before and after a reorg, or as
+// returned by two RPC providers. Transactions are matched by signature and
+// rewards by (pubkey, reward type). A transaction present on both sides is
+// compared field by field through its serialized form, so every output
+// field is covered and a difference names the fields rather than the
+// values. "left" and "right" are the blocks in the order given.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldDiff {
+    pub field: String,
+    pub left: Value,
+    pub right: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionDiff {
+    pub signature: String,
+    // Top-level ParsedTransaction fields whose values differ.
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RewardDiff {
+    pub pubkey: Pubkey,
+    pub reward_type: RewardType,
+    pub fields: Vec<FieldDiff>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BlockDiff {
+    // Block-level fields (blockhash, parent, height, time, ...).
+    pub header: Vec<FieldDiff>,
+    pub left_transactions: usize,
+    pub right_transactions: usize,
+    // Signatures in one block but not the other, in block order.
+    pub only_left: Vec<String>,
+    pub only_right: Vec<String>,
+    pub changed: Vec<TransactionDiff>,
+    // True when the shared transactions appear in a different order.
+    pub reordered: bool,
+    pub rewards_only_left: Vec<BlockReward>,
+    pub rewards_only_right: Vec<BlockReward>,
+    pub rewards_changed: Vec<RewardDiff>,
+}
+
+impl BlockDiff {
+    pub fn is_empty(&self) -> bool {
+        self.header.is_empty()
+            && self.only_left.is_empty()
+            && self.only_right.is_empty()
+            && self.changed.is_empty()
+            && !self.reordered
+            && self.rewards_only_left.is_empty()
+            && self.rewards_only_right.is_empty()
+            && self.rewards_changed.is_empty()
+    }
+}
+
+pub fn diff_blocks(left: &ParsedBlock, right: &ParsedBlock) -> BlockDiff {
+    let mut header = Vec::new();
+    let mut field = |name: &str, l: Value, r: Value| {
+        if l != r {
+            header.push(FieldDiff { field: name.to_string(), left: l, right: r });
+        }
+    };
+    field("slot", left.slot.into(), right.slot.into());
+    field("blockhash", left.blockhash.as_str().into(), right.blockhash.as_str().into());
+    field("previous_blockhash", left.previous_blockhash.as_str().into(), right.previous_blockhash.as_str().into());
+    field("parent_slot", left.parent_slot.into(), right.parent_slot.into());
+    field("block_height", left.block_height.into(), right.block_height.into());
+    field("block_time", left.block_time.into(), right.block_time.into());
+
+    let left_index = left.index_by_signature();
+    let right_index = right.index_by_signature();
+    let only_left = signatures_missing(&left.transactions, &right_index);
+    let only_right = signatures_missing(&right.transactions, &left_index);
+
+    let mut changed = Vec::new();
+    for tx in &left.transactions {
+        if let Some(other) = right_index.get(tx.signature.as_str()) {
+            let fields = transaction_fields_differing(tx, other);
+            if !fields.is_empty() {
+                changed.push(TransactionDiff { signature: tx.signature.clone(), fields });
+            }
+        }
+    }
+    let shared_left = left.transactions.iter().filter(|tx| right_index.contains_key(tx.signature.as_str()));
+    let shared_right = right.transactions.iter().filter(|tx| left_index.contains_key(tx.signature.as_str()));
+    let reordered = !shared_left.map(|tx| &tx.signature).eq(shared_right.map(|tx| &tx.signature));
+
+    let (rewards_only_left, rewards_only_right, rewards_changed) = diff_rewards(&left.rewards, &right.rewards);
+
+    BlockDiff {
+        header,
+        left_transactions: left.transactions.len(),
+        right_transactions: right.transactions.len(),
+        only_left,
+        only_right,
+        changed,
+        reordered,
+        rewards_only_left,
+        rewards_only_right,
+        rewards_changed,
+    }
+}
+
+fn signatures_missing(transactions: &[ParsedTransaction], other: &HashMap<&str, &ParsedTransaction>) -> Vec<String> {
+    transactions
+        .iter()
+        .filter(|tx| !other.contains_key(tx.signature.as_str()))
+        .map(|tx| tx.signature.clone())
+        .collect()
+}
+
+fn transaction_fields_differing(left: &ParsedTransaction, right: &ParsedTransaction) -> Vec<String> {
+    let (Ok(Value::Object(left)), Ok(Value::Object(right))) = (serde_json::to_value(left), serde_json::to_value(right)) else {
+        return Vec::new();
+    };
+    let names: HashSet<&String> = left.keys().chain(right.keys()).collect();
+    let mut fields: Vec<String> = names.into_iter().filter(|name| left.get(*name) != right.get(*name)).cloned().collect();
+    fields.sort();
+    fields
+}
+
+type RewardKey<'a> = (Pubkey, &'a str);
+
+fn reward_key(reward: &BlockReward) -> RewardKey<'_> {
+    (reward.pubkey, reward.reward_type.as_str())
+}
+
+fn diff_rewards(left: &[BlockReward], right: &[BlockReward]) -> (Vec<BlockReward>, Vec<BlockReward>, Vec<RewardDiff>) {
+    let left_by_key: BTreeMap<RewardKey, &BlockReward> = left.iter().map(|reward| (reward_key(reward), reward)).collect();
+    let right_by_key: BTreeMap<RewardKey, &BlockReward> = right.iter().map(|reward| (reward_key(reward), reward)).collect();
+
+    let only_left = left.iter().filter(|reward| !right_by_key.contains_key(&reward_key(reward))).cloned().collect();
+    let only_right = right.iter().filter(|reward| !left_by_key.contains_key(&reward_key(reward))).cloned().collect();
+    let mut changed = Vec::new();
+    for (reward_key, l) in &left_by_key {
+        let Some(r) = right_by_key.get(reward_key) else {
+            continue;
+        };
+        let mut fields = Vec::new();
+        let mut field = |name: &str, lv: Value, rv: Value| {
+            if lv != rv {
+                fields.push(FieldDiff { field: name.to_string(), left: lv, right: rv });
+            }
+        };
+        field("lamports", l.lamports.into(), r.lamports.into());
+        field("post_balance", l.post_balance.into(), r.post_balance.into());
+        field("commission", l.commission.into(), r.commission.into());
+        if !fields.is_empty() {
+            changed.push(RewardDiff { pubkey: l.pubkey, reward_type: l.reward_type.clone(), fields });
+        }
+    }
+    (only_left, only_right, changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn block() -> ParsedBlock {
+        fixtures::block().clone()
+    }
+
+    #[test]
+    fn reports_missing_changed_and_reordered_transactions() {
+        let left = block();
+        assert!(diff_blocks(&left, &block()).is_empty());
+
+        let mut right = block();
+        right.blockhash = "other".to_string();
+        let dropped = right.transactions.remove(0).signature;
+        right.transactions[0].fee += 1;
+        right.transactions.swap(1, 2);
+        right.rewards[0].lamports += 1;
+
+        let diff = diff_blocks(&left, &right);
+        assert_eq!(diff.header.len(), 1);
+        assert_eq!(diff.header[0].field, "blockhash");
+        assert_eq!(diff.only_left, [dropped]);
+        assert!(diff.only_right.is_empty());
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].signature, right.transactions[0].signature);
+        assert_eq!(diff.changed[0].fields, ["fee"]);
+        assert!(diff.reordered);
+        assert_eq!(diff.rewards_changed.len(), 1);
+        assert_eq!(diff.rewards_changed[0].fields[0].field, "lamports");
+    }
+}
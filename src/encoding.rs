@@ -0,0 +1,53 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+
+// ==========================================
+// BINARY DATA ENCODINGS
+// ==========================================
+// Instruction data arrives base58 (encoding: json) or base64, and can be
+// re-encoded for output in any of the three.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataEncoding {
+    #[default]
+    Base58,
+    Base64,
+    Hex,
+}
+
+impl DataEncoding {
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "base58" => Ok(DataEncoding::Base58),
+            "base64" => Ok(DataEncoding::Base64),
+            "hex" => Ok(DataEncoding::Hex),
+            other => Err(format!("unknown data encoding '{}' (expected base58, base64 or hex)", other)),
+        }
+    }
+
+    pub fn encode(&self, bytes: &[u8]) -> String {
+        match self {
+            DataEncoding::Base58 => bs58::encode(bytes).into_string(),
+            DataEncoding::Base64 => BASE64.encode(bytes),
+            DataEncoding::Hex => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+
+    pub fn decode(&self, text: &str) -> Option<Vec<u8>> {
+        match self {
+            DataEncoding::Base58 => bs58::decode(text).into_vec().ok(),
+            DataEncoding::Base64 => BASE64.decode(text).ok(),
+            DataEncoding::Hex => {
+                if !text.len().is_multiple_of(2) {
+                    return None;
+                }
+                (0..text.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+                    .collect()
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+
+// ==========================================
+// EPOCH SCHEDULE
+// ==========================================
+// Mirrors the runtime's EpochSchedule. With warmup, epochs start at
+// MINIMUM_SLOTS_PER_EPOCH slots and double until they reach
+// slots_per_epoch; mainnet-beta has warmup disabled.
+
+pub const DEFAULT_SLOTS_PER_EPOCH: u64 = 432_000;
+const MINIMUM_SLOTS_PER_EPOCH: u64 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochSchedule {
+    pub slots_per_epoch: u64,
+    pub first_normal_epoch: u64,
+    pub first_normal_slot: u64,
+}
+
+impl Default for EpochSchedule {
+    fn default() -> Self {
+        EpochSchedule::without_warmup(DEFAULT_SLOTS_PER_EPOCH)
+    }
+}
+
+impl EpochSchedule {
+    pub fn without_warmup(slots_per_epoch: u64) -> Self {
+        EpochSchedule {
+            slots_per_epoch: slots_per_epoch.max(MINIMUM_SLOTS_PER_EPOCH),
+            first_normal_epoch: 0,
+            first_normal_slot: 0,
+        }
+    }
+
+    pub fn with_warmup(slots_per_epoch: u64) -> Self {
+        let slots_per_epoch = slots_per_epoch.max(MINIMUM_SLOTS_PER_EPOCH);
+        let first_normal_epoch = (slots_per_epoch.next_power_of_two().trailing_zeros()
+            - MINIMUM_SLOTS_PER_EPOCH.trailing_zeros()) as u64;
+        let first_normal_slot = (2u64.pow(first_normal_epoch as u32) - 1) * MINIMUM_SLOTS_PER_EPOCH;
+        EpochSchedule { slots_per_epoch, first_normal_epoch, first_normal_slot }
+    }
+
+    // (epoch, slot index within the epoch)
+    pub fn epoch_and_slot_index(&self, slot: u64) -> (u64, u64) {
+        if slot < self.first_normal_slot {
+            let epoch = (slot + MINIMUM_SLOTS_PER_EPOCH + 1).next_power_of_two().trailing_zeros()
+                - MINIMUM_SLOTS_PER_EPOCH.trailing_zeros()
+                - 1;
+            let epoch_len = 2u64.pow(epoch + MINIMUM_SLOTS_PER_EPOCH.trailing_zeros());
+            (epoch as u64, slot - (epoch_len - MINIMUM_SLOTS_PER_EPOCH))
+        } else {
+            let normal_slot_index = slot - self.first_normal_slot;
+            (
+                self.first_normal_epoch + normal_slot_index / self.slots_per_epoch,
+                normal_slot_index % self.slots_per_epoch,
+            )
+        }
+    }
+}
+
+// ==========================================
+// TIMESTAMP FORMATTING
+// ==========================================
+
+// Unix seconds as an RFC 3339 UTC timestamp, e.g. 2025-11-22T14:53:05Z.
+pub fn format_rfc3339(unix_seconds: i64) -> String {
+    let days = unix_seconds.div_euclid(86_400);
+    let secs = unix_seconds.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+// Howard Hinnant's days-since-epoch -> proleptic Gregorian date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warmup_schedule_matches_runtime() {
+        let schedule = EpochSchedule::with_warmup(8192);
+        assert_eq!((schedule.first_normal_epoch, schedule.first_normal_slot), (8, 8160));
+        assert_eq!(schedule.epoch_and_slot_index(0), (0, 0));
+        assert_eq!(schedule.epoch_and_slot_index(32), (1, 0));
+        assert_eq!(schedule.epoch_and_slot_index(8159), (7, 4095));
+        assert_eq!(schedule.epoch_and_slot_index(8160 + 8192), (9, 0));
+
+        assert_eq!(EpochSchedule::default().epoch_and_slot_index(381_785_271), (883, 329_271));
+    }
+
+    #[test]
+    fn formats_rfc3339() {
+        assert_eq!(format_rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_rfc3339(1_763_823_185), "2025-11-22T14:53:05Z");
+        assert_eq!(format_rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+    }
+}
@@ -0,0 +1,48 @@
+use thiserror::Error;
+
+// ==========================================
+// PARSE ERRORS
+// ==========================================
+// Hard failures. Recoverable problems (unknown indices, unresolved lookup
+// tables, ...) are reported as ParseWarnings instead, unless
+// ParseOptions::strict promotes them.
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("transaction has no signatures")]
+    MissingSignature,
+
+    #[error("transaction {signature} is malformed: {reason}")]
+    MalformedTransaction { signature: String, reason: String },
+
+    #[error("block {blockhash} is malformed: {reason}")]
+    MalformedBlock { blockhash: String, reason: String },
+
+    #[error("transaction {signature}: account index {index} out of range ({len} accounts)")]
+    AccountIndexOutOfRange { signature: String, index: usize, len: usize },
+
+    #[error("failed to read {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("output schema version {version} is newer than this build supports ({supported})")]
+    UnsupportedSchemaVersion { version: u32, supported: u32 },
+
+    #[error("invalid IDL: {0}")]
+    InvalidIdl(String),
+
+    #[error("invalid decoder: {0}")]
+    InvalidDecoder(String),
+
+    #[error("unrecognized input: {0}")]
+    UnrecognizedInput(String),
+
+    #[error("invalid binary output: {0}")]
+    InvalidBinary(String),
+
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
@@ -0,0 +1,96 @@
+use crate::compress::{self, Compression};
+use crate::reorg::ChainEvent;
+use crate::sink::Sink;
+use crate::types::{ParsedBlock, ParsedTransaction};
+use crate::wallet::{account_events, AccountEvent};
+use std::error::Error;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// ==========================================
+// ACCOUNT EVENT EXPORT
+// ==========================================
+// NDJSON keyed by address rather than by transaction: one line per
+// (account, transaction), see wallet::account_events. Flushed after every
+// block like the NDJSON exporter, and chain events get the same lines of
+// their own.
+
+pub struct AccountEventExporter {
+    out: BufWriter<compress::Writer>,
+}
+
+impl AccountEventExporter {
+    pub fn create(path: &Path, compression: Compression) -> io::Result<Self> {
+        Ok(Self::new(compress::create(path, compression, false)?))
+    }
+
+    // Continues an existing file, e.g. when resuming from a checkpoint.
+    pub fn append(path: &Path, compression: Compression) -> io::Result<Self> {
+        Ok(Self::new(compress::create(path, compression, true)?))
+    }
+
+    pub fn stdout() -> Self {
+        Self::from_writer(Box::new(io::stdout()))
+    }
+
+    pub fn from_writer(out: Box<dyn Write + Send>) -> Self {
+        Self::new(out.into())
+    }
+
+    fn new(out: compress::Writer) -> Self {
+        AccountEventExporter { out: BufWriter::new(out) }
+    }
+
+    // Fails if a compressor didn't finish the file.
+    pub fn finish(self) -> io::Result<()> {
+        self.out.into_inner().map_err(io::IntoInnerError::into_error)?.finish()
+    }
+
+    pub fn write_block(&mut self, block: &ParsedBlock) -> io::Result<()> {
+        for (index, tx) in block.transactions.iter().enumerate() {
+            self.write_events(&account_events(Some((block, index)), tx))?;
+        }
+        self.out.flush()
+    }
+
+    // A standalone transaction; the block fields are null.
+    pub fn write_transaction(&mut self, tx: &ParsedTransaction) -> io::Result<()> {
+        self.write_events(&account_events(None, tx))?;
+        self.out.flush()
+    }
+
+    fn write_events(&mut self, events: &[AccountEvent]) -> io::Result<()> {
+        for event in events {
+            serde_json::to_writer(&mut self.out, event)?;
+            self.out.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl Sink for AccountEventExporter {
+    fn name(&self) -> &str {
+        "account_events"
+    }
+
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        Ok(AccountEventExporter::write_block(self, block)?)
+    }
+
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        for tx in txs {
+            self.write_transaction(tx)?;
+        }
+        Ok(())
+    }
+
+    fn write_chain_event(&mut self, event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(&mut self.out, event)?;
+        self.out.write_all(b"\n")?;
+        Ok(self.out.flush()?)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        Ok(AccountEventExporter::finish(*self)?)
+    }
+}
@@ -0,0 +1,79 @@
+use crate::binary::{BinaryFormat, BinaryWriter};
+use crate::compress::{self, Compression};
+use crate::reorg::ChainEvent;
+use crate::sink::Sink;
+use crate::types::{ParsedBlock, ParsedTransaction};
+use std::error::Error;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// ==========================================
+// BINARY EXPORT
+// ==========================================
+// Blocks, standalone transactions and chain events as bincode or borsh
+// records (see binary), for `read-parsed` or another program to load back
+// without re-parsing JSON. Flushed after every record, like NDJSON.
+
+pub struct BinaryExporter {
+    writer: BinaryWriter<BufWriter<compress::Writer>>,
+}
+
+impl BinaryExporter {
+    pub fn create(path: &Path, format: BinaryFormat, compression: Compression) -> io::Result<Self> {
+        Self::new(compress::create(path, compression, false)?, format)
+    }
+
+    // Continues an existing file; the header is only written if it's empty.
+    pub fn append(path: &Path, format: BinaryFormat, compression: Compression) -> io::Result<Self> {
+        let existing = path.metadata().map(|metadata| metadata.len() > 0).unwrap_or(false);
+        let out = BufWriter::new(compress::create(path, compression, true)?);
+        if existing {
+            return Ok(BinaryExporter { writer: BinaryWriter::append(out, format) });
+        }
+        Ok(BinaryExporter { writer: BinaryWriter::new(out, format)? })
+    }
+
+    pub fn stdout(format: BinaryFormat) -> io::Result<Self> {
+        Self::from_writer(Box::new(io::stdout()), format)
+    }
+
+    pub fn from_writer(out: Box<dyn Write + Send>, format: BinaryFormat) -> io::Result<Self> {
+        Self::new(out.into(), format)
+    }
+
+    fn new(out: compress::Writer, format: BinaryFormat) -> io::Result<Self> {
+        Ok(BinaryExporter { writer: BinaryWriter::new(BufWriter::new(out), format)? })
+    }
+
+    // Fails if a compressor didn't finish the file.
+    pub fn finish(self) -> io::Result<()> {
+        self.writer.into_inner().into_inner().map_err(io::IntoInnerError::into_error)?.finish()
+    }
+}
+
+impl Sink for BinaryExporter {
+    fn name(&self) -> &str {
+        self.writer.format().as_str()
+    }
+
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        self.writer.write_block(block)?;
+        Ok(self.writer.flush()?)
+    }
+
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        for tx in txs {
+            self.writer.write_transaction(tx)?;
+        }
+        Ok(self.writer.flush()?)
+    }
+
+    fn write_chain_event(&mut self, event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        self.writer.write_chain_event(event)?;
+        Ok(self.writer.flush()?)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        Ok(BinaryExporter::finish(*self)?)
+    }
+}
@@ -0,0 +1,316 @@
+use crate::compress::{self, Compression};
+use crate::sink::Sink;
+use crate::types::{ParsedBlock, ParsedInstruction, ParsedTransaction, SCHEMA_VERSION};
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+// ==========================================
+// CSV EXPORT
+// ==========================================
+// Flattens parsed blocks into two files in the output directory:
+// transactions.csv (one row per transaction) and instructions.csv (one row
+// per top-level or inner instruction). Headers are written once, so the
+// same exporter can be fed any number of blocks.
+
+const TRANSACTION_HEADER: &[&str] = &[
+    "blockhash",
+    "block_height",
+    "block_time",
+    "slot",
+    "index_in_block",
+    "timestamp_ms",
+    "signature",
+    "fee_payer",
+    "is_success",
+    "fee",
+    "base_fee",
+    "priority_fee",
+    "compute_units_consumed",
+    "compute_unit_limit",
+    "unit_price_micro_lamports",
+    "num_accounts",
+    "num_instructions",
+    "num_inner_instructions",
+];
+
+// parent_index is empty for top-level instructions; for inner instructions
+// it is the top-level instruction that made the CPI. execution is only set
+// in failed transactions (see failure::mark_execution).
+const INSTRUCTION_HEADER: &[&str] = &[
+    "signature",
+    "parent_index",
+    "index",
+    "program_id",
+    "accounts",
+    "data",
+    "execution",
+];
+
+pub struct CsvExporter {
+    transactions: BufWriter<compress::Writer>,
+    instructions: BufWriter<compress::Writer>,
+}
+
+impl CsvExporter {
+    // transactions.csv and instructions.csv, plus .gz / .zst when compressed.
+    pub fn create(dir: &Path, compression: Compression) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(CsvExporter {
+            transactions: open_file(&dir.join(file_name("transactions", compression)), compression, false, TRANSACTION_HEADER)?,
+            instructions: open_file(&dir.join(file_name("instructions", compression)), compression, false, INSTRUCTION_HEADER)?,
+        })
+    }
+
+    // Adds rows to existing files, writing headers only for new ones. Files
+    // with other headers (from another schema version) are refused.
+    pub fn append(dir: &Path, compression: Compression) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(CsvExporter {
+            transactions: open_file(&dir.join(file_name("transactions", compression)), compression, true, TRANSACTION_HEADER)?,
+            instructions: open_file(&dir.join(file_name("instructions", compression)), compression, true, INSTRUCTION_HEADER)?,
+        })
+    }
+
+    pub fn write_block(&mut self, block: &ParsedBlock) -> io::Result<()> {
+        for tx in &block.transactions {
+            self.write_transaction(Some(block), tx)?;
+        }
+        Ok(())
+    }
+
+    // `block` is None for standalone transactions; the block columns are left empty.
+    pub fn write_transaction(&mut self, block: Option<&ParsedBlock>, tx: &ParsedTransaction) -> io::Result<()> {
+        let inner_count: usize = tx.inner_instructions.iter().map(|inner| inner.instructions.len()).sum();
+        write_row(&mut self.transactions, &[
+            block.map(|b| b.blockhash.clone()).unwrap_or_default(),
+            block.and_then(|b| b.block_height).map(|h| h.to_string()).unwrap_or_default(),
+            block.and_then(|b| b.block_time).map(|t| t.to_string()).unwrap_or_default(),
+            optional(tx.slot),
+            optional(tx.index_in_block),
+            optional(tx.timestamp_ms),
+            tx.signature.clone(),
+            tx.fee_payer.to_string(),
+            tx.is_success.to_string(),
+            tx.fee.to_string(),
+            tx.base_fee.to_string(),
+            tx.priority_fee.to_string(),
+            optional(tx.compute_units_consumed),
+            optional(tx.compute_unit_limit),
+            optional(tx.unit_price_micro_lamports),
+            tx.account_keys.len().to_string(),
+            tx.instructions.len().to_string(),
+            inner_count.to_string(),
+        ])?;
+
+        for (index, ix) in tx.instructions.iter().enumerate() {
+            self.write_instruction(tx, None, index, ix)?;
+        }
+        for inner in &tx.inner_instructions {
+            for (index, ix) in inner.instructions.iter().enumerate() {
+                self.write_instruction(tx, Some(inner.index), index, ix)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.transactions.flush()?;
+        self.instructions.flush()
+    }
+
+    // Fails if a compressor didn't finish either file.
+    pub fn finish(self) -> io::Result<()> {
+        let transactions = self.transactions.into_inner().map_err(io::IntoInnerError::into_error)?.finish();
+        let instructions = self.instructions.into_inner().map_err(io::IntoInnerError::into_error)?.finish();
+        transactions.and(instructions)
+    }
+
+    fn write_instruction(
+        &mut self,
+        tx: &ParsedTransaction,
+        parent_index: Option<usize>,
+        index: usize,
+        ix: &ParsedInstruction,
+    ) -> io::Result<()> {
+        // Accounts come from the transaction key list so this works whether
+        // or not the parser materialized ParsedInstruction.accounts.
+        let accounts: Vec<String> = ix.resolved_accounts(tx).map(|a| a.map(|key| key.to_string()).unwrap_or_default()).collect();
+        write_row(&mut self.instructions, &[
+            tx.signature.clone(),
+            optional(parent_index),
+            index.to_string(),
+            ix.program_id.to_string(),
+            accounts.join(";"),
+            ix.data.clone(),
+            ix.execution.as_ref().map(|execution| execution.as_str().to_string()).unwrap_or_default(),
+        ])
+    }
+}
+
+impl Sink for CsvExporter {
+    fn name(&self) -> &str {
+        "csv"
+    }
+
+    // Flushed after every write, like the other file outputs.
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        CsvExporter::write_block(self, block)?;
+        Ok(CsvExporter::flush(self)?)
+    }
+
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        for tx in txs {
+            self.write_transaction(None, tx)?;
+        }
+        Ok(CsvExporter::flush(self)?)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        Ok(CsvExporter::finish(*self)?)
+    }
+}
+
+fn file_name(table: &str, compression: Compression) -> String {
+    format!("{}.csv{}", table, compression.extension())
+}
+
+fn open_file(path: &Path, compression: Compression, append: bool, header: &[&str]) -> io::Result<BufWriter<compress::Writer>> {
+    let is_new = !append || fs::metadata(path).map_or(true, |metadata| metadata.len() == 0);
+    if !is_new {
+        check_header(path, header)?;
+    }
+    let mut out = BufWriter::new(compress::create(path, compression, append)?);
+    if is_new {
+        write_row(&mut out, header)?;
+    }
+    Ok(out)
+}
+
+// Rows appended under another header would land in the wrong columns.
+fn check_header(path: &Path, header: &[&str]) -> io::Result<()> {
+    let mut existing = String::new();
+    BufReader::new(compress::open(path)?).read_line(&mut existing)?;
+    let mut expected = Vec::new();
+    write_row(&mut expected, header)?;
+    if existing.as_bytes() != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} has other columns than schema version {} writes; write to a new directory", path.display(), SCHEMA_VERSION),
+        ));
+    }
+    Ok(())
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn write_row<W: Write, S: AsRef<str>>(out: &mut W, fields: &[S]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        write_field(out, field.as_ref())?;
+    }
+    out.write_all(b"\n")
+}
+
+// RFC 4180 quoting: only when needed, with embedded quotes doubled.
+fn write_field<W: Write>(out: &mut W, field: &str) -> io::Result<()> {
+    if field.contains([',', '"', '\n', '\r']) {
+        write!(out, "\"{}\"", field.replace('"', "\"\""))
+    } else {
+        out.write_all(field.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    // A strict RFC 4180 reader, to check the output round-trips.
+    fn parse_csv(text: &str) -> Vec<Vec<String>> {
+        let (mut rows, mut row, mut field) = (Vec::new(), Vec::new(), String::new());
+        let mut chars = text.chars().peekable();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match (quoted, c) {
+                (true, '"') if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                (true, '"') => quoted = false,
+                (true, c) => field.push(c),
+                (false, '"') if field.is_empty() => quoted = true,
+                (false, ',') => row.push(std::mem::take(&mut field)),
+                (false, '\n') => {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                (false, c) => {
+                    assert!(c != '"' && c != '\r', "unquoted {:?} in {:?}", c, text);
+                    field.push(c);
+                }
+            }
+        }
+        assert!(!quoted && field.is_empty() && row.is_empty(), "unterminated row in {:?}", text);
+        rows
+    }
+
+    #[test]
+    fn quotes_fields_and_appends_without_repeating_headers() {
+        let fields = ["plain", "a,b", "say \"hi\"", "Program log: line one\nline two", "cr\r\n", "", "\"", "memo: ✓"];
+        let mut out = Vec::new();
+        write_row(&mut out, &fields).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("plain,\"a,b\",\"say \"\"hi\"\"\",\"Program log: line one\nline two\","));
+        assert_eq!(parse_csv(&text), [fields.map(String::from).to_vec()]);
+
+        let block = fixtures::block();
+        let dir = std::env::temp_dir().join(format!("phase1-csv-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let read = |table: &str| parse_csv(&fs::read_to_string(dir.join(format!("{}.csv", table))).unwrap());
+        let instruction_rows = |txs: &[ParsedTransaction]| -> usize {
+            txs.iter().map(|tx| tx.instructions.len() + tx.inner_instructions.iter().map(|inner| inner.instructions.len()).sum::<usize>()).sum()
+        };
+
+        // append creates missing files with their headers.
+        let mut exporter = CsvExporter::append(&dir, Compression::None).unwrap();
+        exporter.write_block(block).unwrap();
+        exporter.flush().unwrap();
+        drop(exporter);
+        let mut exporter = CsvExporter::append(&dir, Compression::None).unwrap();
+        exporter.write_transaction(None, &block.transactions[0]).unwrap();
+        exporter.flush().unwrap();
+        drop(exporter);
+
+        let transactions = read("transactions");
+        assert_eq!(transactions[0], TRANSACTION_HEADER);
+        assert_eq!(transactions.len(), 1 + block.transactions.len() + 1);
+        assert_eq!(transactions.iter().filter(|row| row[0] == "blockhash").count(), 1);
+        assert!(transactions.iter().all(|row| row.len() == TRANSACTION_HEADER.len()));
+        // The standalone transaction has no block columns.
+        let last = transactions.last().unwrap();
+        assert_eq!((last[0].as_str(), last[6].as_str()), ("", block.transactions[0].signature.as_str()));
+        let instructions = read("instructions");
+        assert_eq!(instructions[0], INSTRUCTION_HEADER);
+        assert_eq!(instructions.len(), 1 + instruction_rows(&block.transactions) + instruction_rows(&block.transactions[..1]));
+
+        // create starts the files over.
+        let mut exporter = CsvExporter::create(&dir, Compression::None).unwrap();
+        exporter.write_transaction(None, &block.transactions[0]).unwrap();
+        exporter.flush().unwrap();
+        drop(exporter);
+        assert_eq!(read("transactions").len(), 2);
+
+        // Files from an older version aren't appended to.
+        fs::write(dir.join("instructions.csv"), "signature,parent_index,index,program_id,accounts,data\n").unwrap();
+        let error = CsvExporter::append(&dir, Compression::None).err().unwrap();
+        assert!(error.to_string().contains("instructions.csv has other columns"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
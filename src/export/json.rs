@@ -0,0 +1,77 @@
+use super::Projection;
+use crate::reorg::ChainEvent;
+use crate::sink::Sink;
+use crate::types::{ParsedBlock, ParsedTransaction};
+use serde::Serialize;
+use std::error::Error;
+use std::io::{self, BufWriter, Write};
+
+// ==========================================
+// JSON EXPORT
+// ==========================================
+// Whole blocks (or standalone transactions) as JSON documents, the format
+// `load_parsed_block` reads back. One-shot commands pretty-print; streaming
+// ones write one compact document per line, with chain events as their own
+// {"event": ...} documents in between. With a projection, transactions
+// carry only the selected fields.
+
+pub struct JsonExporter {
+    out: BufWriter<Box<dyn Write + Send>>,
+    pretty: bool,
+    projection: Option<Projection>,
+}
+
+impl JsonExporter {
+    pub fn stdout(pretty: bool) -> Self {
+        Self::from_writer(Box::new(io::stdout()), pretty)
+    }
+
+    pub fn from_writer(out: Box<dyn Write + Send>, pretty: bool) -> Self {
+        JsonExporter { out: BufWriter::new(out), pretty, projection: None }
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = Some(projection);
+        self
+    }
+
+}
+
+// A free function rather than a method, so the projection can stay borrowed
+// while the output is written.
+fn write_value<T: Serialize>(out: &mut BufWriter<Box<dyn Write + Send>>, pretty: bool, value: &T) -> io::Result<()> {
+    if pretty {
+        serde_json::to_writer_pretty(&mut *out, value)?;
+    } else {
+        serde_json::to_writer(&mut *out, value)?;
+    }
+    out.write_all(b"\n")?;
+    out.flush()
+}
+
+impl Sink for JsonExporter {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        match &self.projection {
+            Some(projection) => Ok(write_value(&mut self.out, self.pretty, &projection.project_block(block))?),
+            None => Ok(write_value(&mut self.out, self.pretty, block)?),
+        }
+    }
+
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        for tx in txs {
+            match &self.projection {
+                Some(projection) => write_value(&mut self.out, self.pretty, &projection.project(tx))?,
+                None => write_value(&mut self.out, self.pretty, tx)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_chain_event(&mut self, event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        Ok(write_value(&mut self.out, self.pretty, event)?)
+    }
+}
@@ -0,0 +1,15 @@
+pub mod account_events;
+pub mod binary;
+pub mod csv;
+pub mod json;
+pub mod ndjson;
+pub mod parquet;
+pub mod projection;
+
+pub use account_events::AccountEventExporter;
+pub use binary::BinaryExporter;
+pub use csv::CsvExporter;
+pub use json::JsonExporter;
+pub use ndjson::NdjsonExporter;
+pub use parquet::ParquetExporter;
+pub use projection::Projection;
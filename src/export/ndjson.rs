@@ -0,0 +1,118 @@
+use super::Projection;
+use crate::compress::{self, Compression};
+use crate::reorg::ChainEvent;
+use crate::sink::Sink;
+use crate::types::{ParsedBlock, ParsedTransaction};
+use serde::Serialize;
+use std::error::Error;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// ==========================================
+// NDJSON EXPORT
+// ==========================================
+// One JSON object per line per transaction, tagged with its block's slot,
+// time and hash so lines stand on their own. Output is flushed after every
+// block (or standalone transaction), so consumers like jq or
+// kafka-console-producer see each block as soon as it's parsed. Chain events
+// get a line of their own, {"event": "finalized"|"retracted", "slot", "blockhash"}.
+// A projection trims the transaction's fields; the block tags stay.
+
+#[derive(Serialize)]
+struct NdjsonLine<'a, T> {
+    slot: Option<u64>,
+    block_time: Option<i64>,
+    blockhash: Option<&'a str>,
+    #[serde(flatten)]
+    transaction: T,
+}
+
+pub struct NdjsonExporter {
+    out: BufWriter<compress::Writer>,
+    projection: Option<Projection>,
+}
+
+impl NdjsonExporter {
+    pub fn create(path: &Path, compression: Compression) -> io::Result<Self> {
+        Ok(Self::new(compress::create(path, compression, false)?))
+    }
+
+    // Continues an existing file, e.g. when resuming from a checkpoint.
+    pub fn append(path: &Path, compression: Compression) -> io::Result<Self> {
+        Ok(Self::new(compress::create(path, compression, true)?))
+    }
+
+    pub fn stdout() -> Self {
+        Self::from_writer(Box::new(io::stdout()))
+    }
+
+    pub fn from_writer(out: Box<dyn Write + Send>) -> Self {
+        Self::new(out.into())
+    }
+
+    fn new(out: compress::Writer) -> Self {
+        NdjsonExporter { out: BufWriter::new(out), projection: None }
+    }
+
+    // Fails if a compressor didn't finish the file.
+    pub fn finish(self) -> io::Result<()> {
+        self.out.into_inner().map_err(io::IntoInnerError::into_error)?.finish()
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = Some(projection);
+        self
+    }
+
+    pub fn write_block(&mut self, block: &ParsedBlock) -> io::Result<()> {
+        for tx in &block.transactions {
+            self.write_line(Some(block), tx)?;
+        }
+        self.out.flush()
+    }
+
+    // `block` is None for standalone transactions; the block fields are null.
+    pub fn write_transaction(&mut self, block: Option<&ParsedBlock>, tx: &ParsedTransaction) -> io::Result<()> {
+        self.write_line(block, tx)?;
+        self.out.flush()
+    }
+
+    fn write_line(&mut self, block: Option<&ParsedBlock>, tx: &ParsedTransaction) -> io::Result<()> {
+        let (slot, block_time, blockhash) = (block.map(|b| b.slot), block.and_then(|b| b.block_time), block.map(|b| b.blockhash.as_str()));
+        match &self.projection {
+            Some(projection) => {
+                let transaction = projection.project(tx);
+                serde_json::to_writer(&mut self.out, &NdjsonLine { slot, block_time, blockhash, transaction })?;
+            }
+            None => serde_json::to_writer(&mut self.out, &NdjsonLine { slot, block_time, blockhash, transaction: tx })?,
+        }
+        self.out.write_all(b"\n")
+    }
+}
+
+impl Sink for NdjsonExporter {
+    fn name(&self) -> &str {
+        "ndjson"
+    }
+
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        Ok(NdjsonExporter::write_block(self, block)?)
+    }
+
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        for tx in txs {
+            self.write_transaction(None, tx)?;
+        }
+        Ok(())
+    }
+
+    fn write_chain_event(&mut self, event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(&mut self.out, event)?;
+        self.out.write_all(b"\n")?;
+        Ok(self.out.flush()?)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        Ok(NdjsonExporter::finish(*self)?)
+    }
+}
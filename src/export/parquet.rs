@@ -0,0 +1,787 @@
+use crate::sink::Sink;
+use crate::types::{ParsedBlock, ParsedInstruction, ParsedTransaction};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// ==========================================
+// PARQUET EXPORT
+// ==========================================
+// Writes transactions.parquet, instructions.parquet and rewards.parquet with
+// one row group per block. Pages are uncompressed, PLAIN-encoded data page
+// v1 with RLE definition levels, which every Parquet reader (arrow, duckdb,
+// spark, pandas) understands. The writer is self-contained so the crate
+// doesn't need the arrow/parquet stack; the footer is only written by
+// `finish`, so an exporter that is dropped without it leaves invalid files.
+
+pub struct ParquetExporter {
+    transactions: ParquetFileWriter,
+    instructions: ParquetFileWriter,
+    rewards: ParquetFileWriter,
+}
+
+impl ParquetExporter {
+    pub fn create(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(ParquetExporter {
+            transactions: ParquetFileWriter::create(&dir.join("transactions.parquet"), transaction_schema())?,
+            instructions: ParquetFileWriter::create(&dir.join("instructions.parquet"), instruction_schema())?,
+            rewards: ParquetFileWriter::create(&dir.join("rewards.parquet"), reward_schema())?,
+        })
+    }
+
+    pub fn write_block(&mut self, block: &ParsedBlock) -> io::Result<()> {
+        self.write_transactions(Some(block), &block.transactions)?;
+
+        let mut rewards = ColumnBuilder::new(&self.rewards.schema);
+        for reward in &block.rewards {
+            rewards.push_str(&block.blockhash);
+            rewards.push_opt_i64(block.block_height.map(|h| h as i64));
+            rewards.push_str(&reward.pubkey.to_string());
+            rewards.push_i64(reward.lamports);
+            rewards.push_i64(reward.post_balance as i64);
+            rewards.push_str(reward.reward_type.as_str());
+            rewards.push_opt_i32(reward.commission.map(i32::from));
+        }
+        self.rewards.write_row_group(rewards.finish())
+    }
+
+    // Standalone transactions go into a row group of their own with the
+    // block columns left null.
+    pub fn write_transactions(&mut self, block: Option<&ParsedBlock>, txs: &[ParsedTransaction]) -> io::Result<()> {
+        let mut transactions = ColumnBuilder::new(&self.transactions.schema);
+        let mut instructions = ColumnBuilder::new(&self.instructions.schema);
+
+        for tx in txs {
+            let inner_count: usize = tx.inner_instructions.iter().map(|inner| inner.instructions.len()).sum();
+            transactions.push_opt_str(block.map(|b| b.blockhash.as_str()));
+            transactions.push_opt_i64(block.and_then(|b| b.block_height).map(|h| h as i64));
+            transactions.push_opt_i64(block.and_then(|b| b.block_time));
+            transactions.push_opt_i64(tx.slot.map(|s| s as i64));
+            transactions.push_opt_i64(tx.index_in_block.map(|i| i as i64));
+            transactions.push_opt_i64(tx.timestamp_ms);
+            transactions.push_str(&tx.signature);
+            transactions.push_str(&tx.fee_payer.to_string());
+            transactions.push_bool(tx.is_success);
+            transactions.push_i64(tx.fee as i64);
+            transactions.push_i64(tx.base_fee as i64);
+            transactions.push_i64(tx.priority_fee as i64);
+            transactions.push_opt_i64(tx.compute_units_consumed.map(|v| v as i64));
+            transactions.push_opt_i64(tx.compute_unit_limit.map(i64::from));
+            transactions.push_opt_i64(tx.unit_price_micro_lamports.map(|v| v as i64));
+            transactions.push_i64(tx.account_keys.len() as i64);
+            transactions.push_i64(tx.instructions.len() as i64);
+            transactions.push_i64(inner_count as i64);
+
+            for (index, ix) in tx.instructions.iter().enumerate() {
+                push_instruction(&mut instructions, tx, None, index, ix);
+            }
+            for inner in &tx.inner_instructions {
+                for (index, ix) in inner.instructions.iter().enumerate() {
+                    push_instruction(&mut instructions, tx, Some(inner.index), index, ix);
+                }
+            }
+        }
+
+        self.transactions.write_row_group(transactions.finish())?;
+        self.instructions.write_row_group(instructions.finish())
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        self.transactions.finish()?;
+        self.instructions.finish()?;
+        self.rewards.finish()
+    }
+}
+
+impl Sink for ParquetExporter {
+    fn name(&self) -> &str {
+        "parquet"
+    }
+
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        Ok(ParquetExporter::write_block(self, block)?)
+    }
+
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        Ok(ParquetExporter::write_transactions(self, None, txs)?)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        Ok(ParquetExporter::finish(*self)?)
+    }
+}
+
+fn push_instruction(
+    columns: &mut ColumnBuilder,
+    tx: &ParsedTransaction,
+    parent_index: Option<usize>,
+    index: usize,
+    ix: &ParsedInstruction,
+) {
+    let accounts: Vec<String> = ix.resolved_accounts(tx).map(|a| a.map(|key| key.to_string()).unwrap_or_default()).collect();
+    columns.push_str(&tx.signature);
+    columns.push_opt_i64(parent_index.map(|i| i as i64));
+    columns.push_i64(index as i64);
+    columns.push_str(&ix.program_id.to_string());
+    columns.push_str(&accounts.join(";"));
+    columns.push_str(&ix.data);
+    columns.push_opt_str(ix.execution.as_ref().map(|execution| execution.as_str()));
+}
+
+// ==========================================
+// SCHEMAS
+// ==========================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhysicalType {
+    Boolean,
+    Int32,
+    Int64,
+    Utf8,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnSpec {
+    pub name: &'static str,
+    pub physical: PhysicalType,
+    pub optional: bool,
+}
+
+const fn column(name: &'static str, physical: PhysicalType, optional: bool) -> ColumnSpec {
+    ColumnSpec { name, physical, optional }
+}
+
+pub fn transaction_schema() -> Vec<ColumnSpec> {
+    use PhysicalType::*;
+    vec![
+        column("blockhash", Utf8, true),
+        column("block_height", Int64, true),
+        column("block_time", Int64, true),
+        column("slot", Int64, true),
+        column("index_in_block", Int64, true),
+        column("timestamp_ms", Int64, true),
+        column("signature", Utf8, false),
+        column("fee_payer", Utf8, false),
+        column("is_success", Boolean, false),
+        column("fee", Int64, false),
+        column("base_fee", Int64, false),
+        column("priority_fee", Int64, false),
+        column("compute_units_consumed", Int64, true),
+        column("compute_unit_limit", Int64, true),
+        column("unit_price_micro_lamports", Int64, true),
+        column("num_accounts", Int64, false),
+        column("num_instructions", Int64, false),
+        column("num_inner_instructions", Int64, false),
+    ]
+}
+
+pub fn instruction_schema() -> Vec<ColumnSpec> {
+    use PhysicalType::*;
+    vec![
+        column("signature", Utf8, false),
+        column("parent_index", Int64, true),
+        column("index", Int64, false),
+        column("program_id", Utf8, false),
+        column("accounts", Utf8, false),
+        column("data", Utf8, false),
+        column("execution", Utf8, true),
+    ]
+}
+
+// The Arrow schema readers get for a file with these columns, in Arrow's
+// JSON form (as used by its integration tests), for consumers that set up
+// their tables before the first file arrives.
+pub fn arrow_schema(columns: &[ColumnSpec]) -> Value {
+    let fields: Vec<Value> = columns.iter().map(|column| {
+        let data_type = match column.physical {
+            PhysicalType::Boolean => json!({ "name": "bool" }),
+            PhysicalType::Int32 => json!({ "name": "int", "bitWidth": 32, "isSigned": true }),
+            PhysicalType::Int64 => json!({ "name": "int", "bitWidth": 64, "isSigned": true }),
+            PhysicalType::Utf8 => json!({ "name": "utf8" }),
+        };
+        json!({ "name": column.name, "nullable": column.optional, "type": data_type, "children": [] })
+    }).collect();
+    json!({ "fields": fields })
+}
+
+pub fn reward_schema() -> Vec<ColumnSpec> {
+    use PhysicalType::*;
+    vec![
+        column("blockhash", Utf8, false),
+        column("block_height", Int64, true),
+        column("pubkey", Utf8, false),
+        column("lamports", Int64, false),
+        column("post_balance", Int64, false),
+        column("reward_type", Utf8, false),
+        column("commission", Int32, true),
+    ]
+}
+
+// ==========================================
+// COLUMN BUFFERS
+// ==========================================
+
+#[derive(Debug)]
+enum ColumnValues {
+    Boolean(Vec<Option<bool>>),
+    Int32(Vec<Option<i32>>),
+    Int64(Vec<Option<i64>>),
+    Utf8(Vec<Option<String>>),
+}
+
+impl ColumnValues {
+    fn len(&self) -> usize {
+        match self {
+            ColumnValues::Boolean(v) => v.len(),
+            ColumnValues::Int32(v) => v.len(),
+            ColumnValues::Int64(v) => v.len(),
+            ColumnValues::Utf8(v) => v.len(),
+        }
+    }
+
+    fn definition_levels(&self) -> Vec<bool> {
+        match self {
+            ColumnValues::Boolean(v) => v.iter().map(Option::is_some).collect(),
+            ColumnValues::Int32(v) => v.iter().map(Option::is_some).collect(),
+            ColumnValues::Int64(v) => v.iter().map(Option::is_some).collect(),
+            ColumnValues::Utf8(v) => v.iter().map(Option::is_some).collect(),
+        }
+    }
+
+    // PLAIN encoding of the non-null values.
+    fn encode_plain(&self, out: &mut Vec<u8>) {
+        match self {
+            ColumnValues::Boolean(v) => {
+                let bits: Vec<bool> = v.iter().flatten().copied().collect();
+                for chunk in bits.chunks(8) {
+                    let byte = chunk.iter().enumerate().fold(0u8, |acc, (i, &b)| acc | ((b as u8) << i));
+                    out.push(byte);
+                }
+            }
+            ColumnValues::Int32(v) => v.iter().flatten().for_each(|x| out.extend_from_slice(&x.to_le_bytes())),
+            ColumnValues::Int64(v) => v.iter().flatten().for_each(|x| out.extend_from_slice(&x.to_le_bytes())),
+            ColumnValues::Utf8(v) => v.iter().flatten().for_each(|s| {
+                out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                out.extend_from_slice(s.as_bytes());
+            }),
+        }
+    }
+}
+
+// Row-major push API over column buffers; values are pushed left to right
+// across the schema, one row at a time.
+struct ColumnBuilder {
+    columns: Vec<ColumnValues>,
+    next: usize,
+}
+
+impl ColumnBuilder {
+    fn new(schema: &[ColumnSpec]) -> Self {
+        let columns = schema.iter().map(|spec| match spec.physical {
+            PhysicalType::Boolean => ColumnValues::Boolean(Vec::new()),
+            PhysicalType::Int32 => ColumnValues::Int32(Vec::new()),
+            PhysicalType::Int64 => ColumnValues::Int64(Vec::new()),
+            PhysicalType::Utf8 => ColumnValues::Utf8(Vec::new()),
+        }).collect();
+        ColumnBuilder { columns, next: 0 }
+    }
+
+    fn column(&mut self) -> &mut ColumnValues {
+        let index = self.next;
+        self.next = (self.next + 1) % self.columns.len();
+        &mut self.columns[index]
+    }
+
+    fn push_bool(&mut self, value: bool) {
+        match self.column() {
+            ColumnValues::Boolean(v) => v.push(Some(value)),
+            other => panic!("expected boolean column, found {:?}", other),
+        }
+    }
+
+    fn push_opt_i32(&mut self, value: Option<i32>) {
+        match self.column() {
+            ColumnValues::Int32(v) => v.push(value),
+            other => panic!("expected int32 column, found {:?}", other),
+        }
+    }
+
+    fn push_i64(&mut self, value: i64) {
+        self.push_opt_i64(Some(value));
+    }
+
+    fn push_opt_i64(&mut self, value: Option<i64>) {
+        match self.column() {
+            ColumnValues::Int64(v) => v.push(value),
+            other => panic!("expected int64 column, found {:?}", other),
+        }
+    }
+
+    fn push_str(&mut self, value: &str) {
+        self.push_opt_str(Some(value));
+    }
+
+    fn push_opt_str(&mut self, value: Option<&str>) {
+        match self.column() {
+            ColumnValues::Utf8(v) => v.push(value.map(str::to_string)),
+            other => panic!("expected utf8 column, found {:?}", other),
+        }
+    }
+
+    fn finish(self) -> Vec<ColumnValues> {
+        debug_assert_eq!(self.next, 0, "row left partially filled");
+        self.columns
+    }
+}
+
+// ==========================================
+// FILE WRITER
+// ==========================================
+
+const MAGIC: &[u8] = b"PAR1";
+
+struct ColumnChunkMeta {
+    physical: PhysicalType,
+    name: &'static str,
+    num_values: i64,
+    size: i64,
+    data_page_offset: i64,
+}
+
+struct RowGroupMeta {
+    columns: Vec<ColumnChunkMeta>,
+    num_rows: i64,
+    total_byte_size: i64,
+}
+
+struct ParquetFileWriter {
+    out: BufWriter<File>,
+    offset: u64,
+    schema: Vec<ColumnSpec>,
+    row_groups: Vec<RowGroupMeta>,
+}
+
+impl ParquetFileWriter {
+    fn create(path: &Path, schema: Vec<ColumnSpec>) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        Ok(ParquetFileWriter { out, offset: MAGIC.len() as u64, schema, row_groups: Vec::new() })
+    }
+
+    // Empty row groups are skipped rather than written.
+    fn write_row_group(&mut self, columns: Vec<ColumnValues>) -> io::Result<()> {
+        let num_rows = columns.first().map(ColumnValues::len).unwrap_or(0);
+        if num_rows == 0 {
+            return Ok(());
+        }
+
+        let mut chunks = Vec::with_capacity(columns.len());
+        let mut total_byte_size = 0;
+        for (spec, values) in self.schema.iter().zip(&columns) {
+            let page = encode_data_page(spec, values);
+            let data_page_offset = self.offset as i64;
+            self.out.write_all(&page)?;
+            self.offset += page.len() as u64;
+            total_byte_size += page.len() as i64;
+            chunks.push(ColumnChunkMeta {
+                physical: spec.physical,
+                name: spec.name,
+                num_values: values.len() as i64,
+                size: page.len() as i64,
+                data_page_offset,
+            });
+        }
+
+        self.row_groups.push(RowGroupMeta { columns: chunks, num_rows: num_rows as i64, total_byte_size });
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        let metadata = encode_file_metadata(&self.schema, &self.row_groups);
+        self.out.write_all(&metadata)?;
+        self.out.write_all(&(metadata.len() as u32).to_le_bytes())?;
+        self.out.write_all(MAGIC)?;
+        self.out.flush()
+    }
+}
+
+// Parquet enum values used below.
+const TYPE_BOOLEAN: i32 = 0;
+const TYPE_INT32: i32 = 1;
+const TYPE_INT64: i32 = 2;
+const TYPE_BYTE_ARRAY: i32 = 6;
+const REPETITION_REQUIRED: i32 = 0;
+const REPETITION_OPTIONAL: i32 = 1;
+const CONVERTED_TYPE_UTF8: i32 = 0;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const CODEC_UNCOMPRESSED: i32 = 0;
+const PAGE_TYPE_DATA: i32 = 0;
+
+fn physical_type_id(physical: PhysicalType) -> i32 {
+    match physical {
+        PhysicalType::Boolean => TYPE_BOOLEAN,
+        PhysicalType::Int32 => TYPE_INT32,
+        PhysicalType::Int64 => TYPE_INT64,
+        PhysicalType::Utf8 => TYPE_BYTE_ARRAY,
+    }
+}
+
+// Page header followed by page body: definition levels (optional columns
+// only, length-prefixed RLE with bit width 1) then the PLAIN values.
+fn encode_data_page(spec: &ColumnSpec, values: &ColumnValues) -> Vec<u8> {
+    let mut body = Vec::new();
+    if spec.optional {
+        let levels = encode_rle_bits(&values.definition_levels());
+        body.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+        body.extend_from_slice(&levels);
+    }
+    values.encode_plain(&mut body);
+
+    let mut header = CompactWriter::new();
+    header.field_i32(1, PAGE_TYPE_DATA);
+    header.field_i32(2, body.len() as i32);
+    header.field_i32(3, body.len() as i32);
+    header.field_struct_begin(5);
+    header.field_i32(1, values.len() as i32);
+    header.field_i32(2, ENCODING_PLAIN);
+    header.field_i32(3, ENCODING_RLE);
+    header.field_i32(4, ENCODING_RLE);
+    header.struct_end();
+    header.struct_end();
+
+    let mut page = header.into_bytes();
+    page.extend_from_slice(&body);
+    page
+}
+
+// RLE runs of 0/1 values: varint(run_length << 1) then one value byte.
+fn encode_rle_bits(levels: &[bool]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < levels.len() {
+        let value = levels[i];
+        let run = levels[i..].iter().take_while(|&&l| l == value).count();
+        write_varint(&mut out, (run as u64) << 1);
+        out.push(value as u8);
+        i += run;
+    }
+    out
+}
+
+fn encode_file_metadata(schema: &[ColumnSpec], row_groups: &[RowGroupMeta]) -> Vec<u8> {
+    let mut w = CompactWriter::new();
+    w.field_i32(1, 1);
+
+    w.field_list_begin(2, COMPACT_STRUCT, schema.len() + 1);
+    w.struct_begin();
+    w.field_string(4, "schema");
+    w.field_i32(5, schema.len() as i32);
+    w.struct_end();
+    for spec in schema {
+        w.struct_begin();
+        w.field_i32(1, physical_type_id(spec.physical));
+        w.field_i32(3, if spec.optional { REPETITION_OPTIONAL } else { REPETITION_REQUIRED });
+        w.field_string(4, spec.name);
+        if spec.physical == PhysicalType::Utf8 {
+            w.field_i32(6, CONVERTED_TYPE_UTF8);
+        }
+        w.struct_end();
+    }
+
+    let num_rows: i64 = row_groups.iter().map(|rg| rg.num_rows).sum();
+    w.field_i64(3, num_rows);
+
+    w.field_list_begin(4, COMPACT_STRUCT, row_groups.len());
+    for rg in row_groups {
+        w.struct_begin();
+        w.field_list_begin(1, COMPACT_STRUCT, rg.columns.len());
+        for chunk in &rg.columns {
+            w.struct_begin();
+            w.field_i64(2, chunk.data_page_offset);
+            w.field_struct_begin(3);
+            w.field_i32(1, physical_type_id(chunk.physical));
+            w.field_list_begin(2, COMPACT_I32, 2);
+            w.list_i32(ENCODING_PLAIN);
+            w.list_i32(ENCODING_RLE);
+            w.field_list_begin(3, COMPACT_BINARY, 1);
+            w.list_string(chunk.name);
+            w.field_i32(4, CODEC_UNCOMPRESSED);
+            w.field_i64(5, chunk.num_values);
+            w.field_i64(6, chunk.size);
+            w.field_i64(7, chunk.size);
+            w.field_i64(9, chunk.data_page_offset);
+            w.struct_end();
+            w.struct_end();
+        }
+        w.field_i64(2, rg.total_byte_size);
+        w.field_i64(3, rg.num_rows);
+        w.struct_end();
+    }
+
+    w.field_string(6, concat!("phase-1-connect ", env!("CARGO_PKG_VERSION")));
+    w.struct_end();
+    w.into_bytes()
+}
+
+// ==========================================
+// THRIFT COMPACT PROTOCOL
+// ==========================================
+// Just the subset the Parquet footer and page headers need.
+
+const COMPACT_I32: u8 = 5;
+const COMPACT_I64: u8 = 6;
+const COMPACT_BINARY: u8 = 8;
+const COMPACT_LIST: u8 = 9;
+const COMPACT_STRUCT: u8 = 12;
+
+struct CompactWriter {
+    buf: Vec<u8>,
+    // Last field id written at each open struct depth.
+    last_field: Vec<i16>,
+}
+
+impl CompactWriter {
+    fn new() -> Self {
+        CompactWriter { buf: Vec::new(), last_field: vec![0] }
+    }
+
+    fn field_header(&mut self, id: i16, kind: u8) {
+        let last = self.last_field.last_mut().expect("no open struct");
+        let delta = id - *last;
+        if (1..=15).contains(&delta) {
+            self.buf.push(((delta as u8) << 4) | kind);
+        } else {
+            self.buf.push(kind);
+            write_varint(&mut self.buf, zigzag(id as i64));
+        }
+        *last = id;
+    }
+
+    fn field_i32(&mut self, id: i16, value: i32) {
+        self.field_header(id, COMPACT_I32);
+        write_varint(&mut self.buf, zigzag(value as i64));
+    }
+
+    fn field_i64(&mut self, id: i16, value: i64) {
+        self.field_header(id, COMPACT_I64);
+        write_varint(&mut self.buf, zigzag(value));
+    }
+
+    fn field_string(&mut self, id: i16, value: &str) {
+        self.field_header(id, COMPACT_BINARY);
+        self.list_string(value);
+    }
+
+    fn field_struct_begin(&mut self, id: i16) {
+        self.field_header(id, COMPACT_STRUCT);
+        self.struct_begin();
+    }
+
+    fn field_list_begin(&mut self, id: i16, element: u8, len: usize) {
+        self.field_header(id, COMPACT_LIST);
+        if len < 15 {
+            self.buf.push(((len as u8) << 4) | element);
+        } else {
+            self.buf.push(0xF0 | element);
+            write_varint(&mut self.buf, len as u64);
+        }
+    }
+
+    fn list_i32(&mut self, value: i32) {
+        write_varint(&mut self.buf, zigzag(value as i64));
+    }
+
+    fn list_string(&mut self, value: &str) {
+        write_varint(&mut self.buf, value.len() as u64);
+        self.buf.extend_from_slice(value.as_bytes());
+    }
+
+    // Structs nested inside lists have no field header of their own.
+    fn struct_begin(&mut self) {
+        self.last_field.push(0);
+    }
+
+    fn struct_end(&mut self) {
+        self.buf.push(0);
+        self.last_field.pop();
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    // Just enough of a compact protocol reader to walk the footer back.
+    #[derive(Debug)]
+    enum Thrift {
+        Int(i64),
+        Binary(Vec<u8>),
+        List(Vec<Thrift>),
+        Struct(Vec<(i16, Thrift)>),
+    }
+
+    impl Thrift {
+        fn field(&self, id: i16) -> &Thrift {
+            let Thrift::Struct(fields) = self else { panic!("not a struct: {:?}", self) };
+            &fields.iter().find(|(field, _)| *field == id).unwrap_or_else(|| panic!("no field {}", id)).1
+        }
+
+        fn int(&self) -> i64 {
+            let Thrift::Int(value) = self else { panic!("not an int: {:?}", self) };
+            *value
+        }
+
+        fn string(&self) -> &str {
+            let Thrift::Binary(bytes) = self else { panic!("not a string: {:?}", self) };
+            std::str::from_utf8(bytes).unwrap()
+        }
+
+        fn list(&self) -> &[Thrift] {
+            let Thrift::List(items) = self else { panic!("not a list: {:?}", self) };
+            items
+        }
+    }
+
+    fn read_varint(bytes: &mut &[u8]) -> u64 {
+        let (mut value, mut shift) = (0, 0);
+        loop {
+            let byte = bytes[0];
+            *bytes = &bytes[1..];
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    fn read_value(bytes: &mut &[u8], kind: u8) -> Thrift {
+        match kind {
+            COMPACT_I32 | COMPACT_I64 => {
+                let raw = read_varint(bytes);
+                Thrift::Int((raw >> 1) as i64 ^ -((raw & 1) as i64))
+            }
+            COMPACT_BINARY => {
+                let len = read_varint(bytes) as usize;
+                let (value, rest) = bytes.split_at(len);
+                *bytes = rest;
+                Thrift::Binary(value.to_vec())
+            }
+            COMPACT_LIST => {
+                let header = bytes[0];
+                *bytes = &bytes[1..];
+                let len = match header >> 4 {
+                    0xF => read_varint(bytes) as usize,
+                    len => len as usize,
+                };
+                Thrift::List((0..len).map(|_| read_value(bytes, header & 0x0F)).collect())
+            }
+            COMPACT_STRUCT => {
+                let (mut fields, mut last) = (Vec::new(), 0i16);
+                loop {
+                    let header = bytes[0];
+                    *bytes = &bytes[1..];
+                    if header == 0 {
+                        return Thrift::Struct(fields);
+                    }
+                    last = match header >> 4 {
+                        0 => panic!("long field header"),
+                        delta => last + i16::from(delta),
+                    };
+                    fields.push((last, read_value(bytes, header & 0x0F)));
+                }
+            }
+            kind => panic!("unexpected compact type {}", kind),
+        }
+    }
+
+    // Checks the framing and returns the decoded FileMetaData.
+    fn read_footer(path: &Path) -> Thrift {
+        let file = fs::read(path).unwrap();
+        assert_eq!(&file[..4], MAGIC);
+        assert_eq!(&file[file.len() - 4..], MAGIC);
+        let footer_len = u32::from_le_bytes(file[file.len() - 8..file.len() - 4].try_into().unwrap()) as usize;
+        let mut footer = &file[file.len() - 8 - footer_len..file.len() - 8];
+        let metadata = read_value(&mut footer, COMPACT_STRUCT);
+        assert!(footer.is_empty(), "footer length doesn't match its contents");
+
+        // Every column chunk points at a page inside the file.
+        for row_group in metadata.field(4).list() {
+            for chunk in row_group.field(1).list() {
+                let offset = chunk.field(2).int() as usize;
+                let size = chunk.field(3).field(6).int() as usize;
+                assert!(offset >= MAGIC.len() && offset + size <= file.len() - 8 - footer_len);
+            }
+        }
+        metadata
+    }
+
+    fn column_names(metadata: &Thrift) -> Vec<&str> {
+        // The first schema element is the root.
+        metadata.field(2).list()[1..].iter().map(|element| element.field(4).string()).collect()
+    }
+
+    #[test]
+    fn writes_readable_footers() {
+        let block = fixtures::block();
+        let dir = std::env::temp_dir().join(format!("phase1-parquet-{}", std::process::id()));
+        let mut exporter = ParquetExporter::create(&dir).unwrap();
+        exporter.write_block(block).unwrap();
+        exporter.write_transactions(None, &block.transactions[..2]).unwrap();
+        exporter.finish().unwrap();
+
+        let transactions = read_footer(&dir.join("transactions.parquet"));
+        assert_eq!(transactions.field(1).int(), 1);
+        let names: Vec<&str> = transaction_schema().iter().map(|spec| spec.name).collect();
+        assert_eq!(column_names(&transactions), names);
+        assert_eq!(transactions.field(3).int() as usize, block.transactions.len() + 2);
+        let row_groups = transactions.field(4).list();
+        assert_eq!(row_groups.len(), 2);
+        assert_eq!(row_groups[0].field(3).int() as usize, block.transactions.len());
+        assert_eq!(row_groups[1].field(3).int(), 2);
+        for chunk in row_groups[0].field(1).list() {
+            let meta = chunk.field(3);
+            assert_eq!(meta.field(5).int() as usize, block.transactions.len());
+            assert_eq!(meta.field(4).int(), i64::from(CODEC_UNCOMPRESSED));
+        }
+        let signature = &row_groups[0].field(1).list()[names.iter().position(|name| *name == "signature").unwrap()];
+        assert_eq!(signature.field(3).field(1).int(), i64::from(TYPE_BYTE_ARRAY));
+        assert_eq!(signature.field(3).field(3).list()[0].string(), "signature");
+
+        let instructions = read_footer(&dir.join("instructions.parquet"));
+        let instruction_rows = |txs: &[ParsedTransaction]| -> usize {
+            txs.iter().map(|tx| tx.instructions.len() + tx.inner_instructions.iter().map(|inner| inner.instructions.len()).sum::<usize>()).sum()
+        };
+        let expected = instruction_rows(&block.transactions) + instruction_rows(&block.transactions[..2]);
+        assert_eq!(instructions.field(3).int() as usize, expected);
+        assert_eq!(column_names(&instructions), instruction_schema().iter().map(|spec| spec.name).collect::<Vec<_>>());
+
+        // Standalone transactions add no reward rows.
+        let rewards = read_footer(&dir.join("rewards.parquet"));
+        assert_eq!(rewards.field(3).int() as usize, block.rewards.len());
+        assert!(rewards.field(4).list().len() <= 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
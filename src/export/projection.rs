@@ -0,0 +1,470 @@
+use crate::types::ParsedBlock;
+use serde::ser::{
+    self, Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple, SerializeTupleStruct,
+    SerializeTupleVariant, Serializer,
+};
+use serde_json::value::Serializer as ValueSerializer;
+use std::collections::BTreeMap;
+
+// ==========================================
+// FIELD PROJECTION
+// ==========================================
+// Trims transactions down to selected fields on their way out, e.g.
+// `signature,fee,instructions.program_id`: a dotted path descends into
+// objects, and into every element of the arrays along the way. A field
+// listed whole wins over paths inside it. Fields are dropped as the value is
+// serialized, so any field a transaction serializes can be picked, and
+// fields absent from a transaction (None values, or names it doesn't have)
+// are left out rather than reported. Applied to a block, the header is kept
+// and each of its transactions is projected.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Selection {
+    All,
+    Fields(BTreeMap<String, Selection>),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Projection {
+    fields: BTreeMap<String, Selection>,
+}
+
+impl Projection {
+    // Selects nothing until fields are added.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A comma-separated list of dotted paths.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        spec.split(',').map(str::trim).try_fold(Self::new(), |projection, path| projection.with_field(path))
+    }
+
+    pub fn with_field(mut self, path: &str) -> Result<Self, String> {
+        let segments: Vec<&str> = path.split('.').collect();
+        if segments.iter().any(|segment| segment.is_empty()) {
+            return Err(format!("invalid field path '{}'", path));
+        }
+        let (last, parents) = segments.split_last().expect("split yields a segment");
+        let mut fields = &mut self.fields;
+        for segment in parents {
+            let selection = fields.entry(segment.to_string()).or_insert_with(|| Selection::Fields(BTreeMap::new()));
+            match selection {
+                Selection::All => return Ok(self),
+                Selection::Fields(children) => fields = children,
+            }
+        }
+        fields.insert(last.to_string(), Selection::All);
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    // `value` serialized with only the selected fields.
+    pub fn project<'a, T: Serialize + ?Sized>(&'a self, value: &'a T) -> impl Serialize + 'a {
+        Projected { value, keep: Keep::Fields(&self.fields) }
+    }
+
+    // The block with its header whole and its transactions projected.
+    pub fn project_block<'a>(&'a self, block: &'a ParsedBlock) -> impl Serialize + 'a {
+        Projected { value: block, keep: Keep::Inside("transactions", &self.fields) }
+    }
+}
+
+// What to keep of each object met while serializing.
+#[derive(Clone, Copy)]
+enum Keep<'a> {
+    Fields(&'a BTreeMap<String, Selection>),
+    // Every field, with this one's contents trimmed to the fields.
+    Inside(&'static str, &'a BTreeMap<String, Selection>),
+}
+
+enum Field<'a> {
+    Skip,
+    Whole,
+    Trimmed(Keep<'a>),
+}
+
+impl<'a> Keep<'a> {
+    fn field(self, key: &str) -> Field<'a> {
+        match self {
+            Keep::Fields(fields) => match fields.get(key) {
+                None => Field::Skip,
+                Some(Selection::All) => Field::Whole,
+                Some(Selection::Fields(children)) => Field::Trimmed(Keep::Fields(children)),
+            },
+            Keep::Inside(inside, fields) if key == inside => Field::Trimmed(Keep::Fields(fields)),
+            Keep::Inside(..) => Field::Whole,
+        }
+    }
+}
+
+struct Projected<'a, T: ?Sized> {
+    value: &'a T,
+    keep: Keep<'a>,
+}
+
+impl<T: Serialize + ?Sized> Serialize for Projected<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(Trim { inner: serializer, keep: self.keep })
+    }
+}
+
+// Passes a value through to `inner`, leaving out the fields `keep` doesn't
+// select. Structs go out as maps, since how many fields remain is only known
+// at the end, and the elements of sequences are trimmed alike. Enum variants
+// holding fields are rare enough to be built as a serde_json::Value first.
+struct Trim<'a, S> {
+    inner: S,
+    keep: Keep<'a>,
+}
+
+impl<'a, S: Serializer> Serializer for Trim<'a, S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = TrimSeq<'a, S::SerializeSeq>;
+    type SerializeTuple = TrimSeq<'a, S::SerializeTuple>;
+    type SerializeTupleStruct = TrimSeq<'a, S::SerializeTupleStruct>;
+    type SerializeTupleVariant = TrimVariant<'a, S, <ValueSerializer as Serializer>::SerializeTupleVariant>;
+    type SerializeMap = TrimMap<'a, S::SerializeMap>;
+    type SerializeStruct = TrimStruct<'a, S::SerializeMap>;
+    type SerializeStructVariant = TrimVariant<'a, S, <ValueSerializer as Serializer>::SerializeStructVariant>;
+
+    fn serialize_bool(self, v: bool) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i64(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i128(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u64(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u128(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_none()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_some(&Projected { value, keep: self.keep })
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(self, name: &'static str, index: u32, variant: &'static str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit_variant(name, index, variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_newtype_struct(name, &Projected { value, keep: self.keep })
+    }
+
+    // {"variant": value}, trimmed like any other object.
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        match self.keep.field(variant) {
+            Field::Skip => self.inner.serialize_map(Some(0))?.end(),
+            Field::Whole => self.inner.serialize_newtype_variant(name, index, variant, value),
+            Field::Trimmed(keep) => self.inner.serialize_newtype_variant(name, index, variant, &Projected { value, keep }),
+        }
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        Ok(TrimSeq { inner: self.inner.serialize_seq(len)?, keep: self.keep })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        Ok(TrimSeq { inner: self.inner.serialize_tuple(len)?, keep: self.keep })
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct, S::Error> {
+        Ok(TrimSeq { inner: self.inner.serialize_tuple_struct(name, len)?, keep: self.keep })
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        let buffer = ValueSerializer.serialize_tuple_variant(name, index, variant, len).map_err(ser::Error::custom)?;
+        Ok(TrimVariant { inner: self.inner, keep: self.keep, buffer })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        Ok(TrimMap { inner: self.inner.serialize_map(None)?, keep: self.keep, next: Field::Skip })
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, S::Error> {
+        Ok(TrimStruct { inner: self.inner.serialize_map(None)?, keep: self.keep })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        let buffer = ValueSerializer.serialize_struct_variant(name, index, variant, len).map_err(ser::Error::custom)?;
+        Ok(TrimVariant { inner: self.inner, keep: self.keep, buffer })
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+struct TrimSeq<'a, I> {
+    inner: I,
+    keep: Keep<'a>,
+}
+
+impl<I: SerializeSeq> SerializeSeq for TrimSeq<'_, I> {
+    type Ok = I::Ok;
+    type Error = I::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), I::Error> {
+        self.inner.serialize_element(&Projected { value, keep: self.keep })
+    }
+
+    fn end(self) -> Result<I::Ok, I::Error> {
+        self.inner.end()
+    }
+}
+
+impl<I: SerializeTuple> SerializeTuple for TrimSeq<'_, I> {
+    type Ok = I::Ok;
+    type Error = I::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), I::Error> {
+        self.inner.serialize_element(&Projected { value, keep: self.keep })
+    }
+
+    fn end(self) -> Result<I::Ok, I::Error> {
+        self.inner.end()
+    }
+}
+
+impl<I: SerializeTupleStruct> SerializeTupleStruct for TrimSeq<'_, I> {
+    type Ok = I::Ok;
+    type Error = I::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), I::Error> {
+        self.inner.serialize_field(&Projected { value, keep: self.keep })
+    }
+
+    fn end(self) -> Result<I::Ok, I::Error> {
+        self.inner.end()
+    }
+}
+
+// `next` is what to do with the value of the key just seen.
+struct TrimMap<'a, M> {
+    inner: M,
+    keep: Keep<'a>,
+    next: Field<'a>,
+}
+
+impl<M: SerializeMap> SerializeMap for TrimMap<'_, M> {
+    type Ok = M::Ok;
+    type Error = M::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), M::Error> {
+        let name = match serde_json::to_value(key).map_err(ser::Error::custom)? {
+            serde_json::Value::String(name) => name,
+            other => other.to_string(),
+        };
+        self.next = self.keep.field(&name);
+        match self.next {
+            Field::Skip => Ok(()),
+            _ => self.inner.serialize_key(key),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), M::Error> {
+        match self.next {
+            Field::Skip => Ok(()),
+            Field::Whole => self.inner.serialize_value(value),
+            Field::Trimmed(keep) => self.inner.serialize_value(&Projected { value, keep }),
+        }
+    }
+
+    fn end(self) -> Result<M::Ok, M::Error> {
+        self.inner.end()
+    }
+}
+
+struct TrimStruct<'a, M> {
+    inner: M,
+    keep: Keep<'a>,
+}
+
+impl<M: SerializeMap> SerializeStruct for TrimStruct<'_, M> {
+    type Ok = M::Ok;
+    type Error = M::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), M::Error> {
+        match self.keep.field(key) {
+            Field::Skip => Ok(()),
+            Field::Whole => self.inner.serialize_entry(key, value),
+            Field::Trimmed(keep) => self.inner.serialize_entry(key, &Projected { value, keep }),
+        }
+    }
+
+    fn end(self) -> Result<M::Ok, M::Error> {
+        self.inner.end()
+    }
+}
+
+struct TrimVariant<'a, S, V> {
+    inner: S,
+    keep: Keep<'a>,
+    buffer: V,
+}
+
+impl<S: Serializer, V: SerializeTupleVariant<Ok = serde_json::Value, Error = serde_json::Error>> SerializeTupleVariant
+    for TrimVariant<'_, S, V>
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S::Error> {
+        self.buffer.serialize_field(value).map_err(ser::Error::custom)
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        let value = self.buffer.end().map_err(ser::Error::custom)?;
+        value.serialize(Trim { inner: self.inner, keep: self.keep })
+    }
+}
+
+impl<S: Serializer, V: SerializeStructVariant<Ok = serde_json::Value, Error = serde_json::Error>> SerializeStructVariant
+    for TrimVariant<'_, S, V>
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), S::Error> {
+        self.buffer.serialize_field(key, value).map_err(ser::Error::custom)
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        let value = self.buffer.end().map_err(ser::Error::custom)?;
+        value.serialize(Trim { inner: self.inner, keep: self.keep })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn keeps_only_the_selected_paths() {
+        let tx = json!({
+            "signature": "abc",
+            "fee": 5000,
+            "log_messages": ["Program log: hi"],
+            "instructions": [
+                {"program_id": "p1", "data": "00", "accounts": ["a"]},
+                {"program_id": "p2", "data": "01", "accounts": []}
+            ],
+            "meta": {"compute_units": 10, "err": null}
+        });
+        let projection = Projection::parse("signature, fee,instructions.program_id,meta.compute_units,missing").unwrap();
+        let project = |projection: &Projection, tx: &Value| serde_json::to_value(projection.project(tx)).unwrap();
+        assert_eq!(
+            project(&projection, &tx),
+            json!({
+                "signature": "abc",
+                "fee": 5000,
+                "instructions": [{"program_id": "p1"}, {"program_id": "p2"}],
+                "meta": {"compute_units": 10}
+            })
+        );
+        // A whole field wins over a path inside it, in either order.
+        let whole = Projection::new().with_field("meta.err").and_then(|p| p.with_field("meta")).and_then(|p| p.with_field("meta.compute_units"));
+        assert_eq!(project(&whole.unwrap(), &tx)["meta"], json!({"compute_units": 10, "err": null}));
+        assert!(Projection::parse("fee,,signature").is_err());
+        assert!(Projection::new().with_field("meta..err").is_err());
+        assert!(Projection::parse("instructions.").is_err());
+    }
+
+    #[test]
+    fn keeps_the_block_header_and_projects_its_transactions() {
+        let block = fixtures::block();
+        let projection = Projection::parse("signature,fee").unwrap();
+        let projected = serde_json::to_value(projection.project_block(block)).unwrap();
+        assert_eq!(projected["blockhash"], json!(block.blockhash));
+        assert_eq!(projected["transactions"][0], json!({"signature": block.transactions[0].signature, "fee": block.transactions[0].fee}));
+    }
+}
@@ -0,0 +1,408 @@
+use crate::decoders::jupiter::JUPITER_V6_PROGRAM_ID;
+use crate::decoders::raydium::RAYDIUM_AMM_V4_PROGRAM_ID;
+use crate::decoders::DecoderRegistry;
+use crate::dex::meteora::METEORA_DLMM_PROGRAM_ID;
+use crate::dex::orca::ORCA_WHIRLPOOL_PROGRAM_ID;
+use crate::dex::raydium::{RAYDIUM_CLMM_PROGRAM_ID, RAYDIUM_CPMM_PROGRAM_ID};
+use crate::logs::parse_logs;
+use crate::pubkey::Pubkey;
+use crate::types::{ParsedInnerInstructions, ParsedInstruction, ParsedTransaction};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+// ==========================================
+// TRANSACTION FAILURES
+// ==========================================
+// `meta.err` is a serialized TransactionError: a bare string for variants
+// without data ("InsufficientFundsForFee"), an object otherwise
+// ({"InstructionError": [2, {"Custom": 6001}]}). The variants that carry
+// something worth reading are typed; the rest keep their name, so errors
+// added to the runtime later still come through.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionError {
+    // `index` is the top-level instruction that failed; the error may have
+    // been raised by a program it invoked.
+    InstructionError { index: u8, error: InstructionError },
+    DuplicateInstruction { index: u8 },
+    InsufficientFundsForRent { account_index: u8 },
+    ProgramExecutionTemporarilyRestricted { account_index: u8 },
+    // The variant name, or its JSON for unknown variants that carry data.
+    Other(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InstructionError {
+    // A program-defined error code.
+    Custom(u32),
+    // The variant name (IllegalOwner, ProgramFailedToComplete, ...), or its
+    // JSON when it carries data.
+    Other(String),
+}
+
+impl TransactionError {
+    pub fn from_value(value: &Value) -> Self {
+        let index = |value: &Value| value.as_u64().and_then(|n| u8::try_from(n).ok());
+        let account_index = |value: &Value| index(&value["account_index"]);
+        let typed = match value {
+            Value::String(name) => Some(TransactionError::Other(name.clone())),
+            Value::Object(map) if map.len() == 1 => {
+                let (name, data) = map.iter().next().unwrap();
+                match name.as_str() {
+                    "InstructionError" => match data.as_array().map(Vec::as_slice) {
+                        Some([ix, error]) => index(ix).map(|index| TransactionError::InstructionError {
+                            index,
+                            error: InstructionError::from_value(error),
+                        }),
+                        _ => None,
+                    },
+                    "DuplicateInstruction" => index(data).map(|index| TransactionError::DuplicateInstruction { index }),
+                    "InsufficientFundsForRent" => {
+                        account_index(data).map(|account_index| TransactionError::InsufficientFundsForRent { account_index })
+                    }
+                    "ProgramExecutionTemporarilyRestricted" => account_index(data)
+                        .map(|account_index| TransactionError::ProgramExecutionTemporarilyRestricted { account_index }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        typed.unwrap_or_else(|| TransactionError::Other(value.to_string()))
+    }
+}
+
+impl InstructionError {
+    pub fn from_value(value: &Value) -> Self {
+        match value {
+            Value::String(name) => InstructionError::Other(name.clone()),
+            _ => match value["Custom"].as_u64().and_then(|code| u32::try_from(code).ok()) {
+                Some(code) => InstructionError::Custom(code),
+                None => InstructionError::Other(value.to_string()),
+            },
+        }
+    }
+}
+
+// How far a failed transaction got with an instruction. Everything it did
+// was rolled back either way; this says which instruction the error came
+// from, and which never ran.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum InstructionExecution {
+    // Ran before the failure.
+    Executed,
+    // The top-level instruction meta.err names.
+    Failed { error: InstructionError },
+    // Came after the failed instruction.
+    NotExecuted,
+}
+
+impl InstructionExecution {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstructionExecution::Executed => "executed",
+            InstructionExecution::Failed { .. } => "failed",
+            InstructionExecution::NotExecuted => "not_executed",
+        }
+    }
+}
+
+// Sets `execution` on the instructions of a transaction that failed with an
+// InstructionError; other errors leave them unset. Inner instructions take
+// their top-level instruction's status, except that those of the failed one
+// did run (one of them may have raised the error) and count as executed.
+pub fn mark_execution(
+    error: &TransactionError,
+    instructions: &mut [ParsedInstruction],
+    inner_instructions: &mut [ParsedInnerInstructions],
+) {
+    let TransactionError::InstructionError { index, error } = error else {
+        return;
+    };
+    let failed = *index as usize;
+    let status = |position: usize| match position.cmp(&failed) {
+        Ordering::Less => InstructionExecution::Executed,
+        Ordering::Equal => InstructionExecution::Failed { error: error.clone() },
+        Ordering::Greater => InstructionExecution::NotExecuted,
+    };
+    for (position, ix) in instructions.iter_mut().enumerate() {
+        ix.execution = Some(status(position));
+    }
+    for inner in inner_instructions {
+        let execution = match status(inner.index) {
+            InstructionExecution::Failed { .. } => InstructionExecution::Executed,
+            other => other,
+        };
+        for ix in &mut inner.instructions {
+            ix.execution = Some(execution.clone());
+        }
+    }
+}
+
+// What a failure comes down to, without the instruction it happened in, so
+// failures with the same root cause can be grouped.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FailureCause {
+    // The program that raised an instruction error.
+    pub program_id: Option<Pubkey>,
+    // "custom program error 0x1", or the runtime error's name.
+    pub error: String,
+    // A custom error's name, from the program's decoder or the table of
+    // well-known DEX errors below.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub category: FailureCategory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum FailureCategory {
+    // A swap's output fell below (or input rose above) the user's limit.
+    Slippage,
+    InsufficientFunds,
+    ComputeBudgetExceeded,
+    Other,
+}
+
+// Slippage errors of the DEX programs whose decoders don't name their
+// errors: (program, code, name).
+const SLIPPAGE_ERRORS: &[(Pubkey, u32, &str)] = &[
+    (JUPITER_V6_PROGRAM_ID, 6001, "SlippageToleranceExceeded"),
+    (RAYDIUM_AMM_V4_PROGRAM_ID, 30, "ExceededSlippage"),
+    (RAYDIUM_CPMM_PROGRAM_ID, 6005, "ExceededSlippage"),
+    (RAYDIUM_CLMM_PROGRAM_ID, 6022, "TooLittleOutputReceived"),
+    (RAYDIUM_CLMM_PROGRAM_ID, 6023, "TooMuchInputPaid"),
+    (ORCA_WHIRLPOOL_PROGRAM_ID, 6017, "AmountOutBelowMinimum"),
+    (ORCA_WHIRLPOOL_PROGRAM_ID, 6018, "AmountInAboveMaximum"),
+    (METEORA_DLMM_PROGRAM_ID, 6003, "ExceededAmountSlippageTolerance"),
+    (PUMP_FUN_PROGRAM_ID, 6002, "TooMuchSolRequired"),
+    (PUMP_FUN_PROGRAM_ID, 6003, "TooLittleSolReceived"),
+    (PUMP_SWAP_PROGRAM_ID, 6004, "ExceededSlippage"),
+];
+
+const PUMP_FUN_PROGRAM_ID: Pubkey = Pubkey::from_str_const("6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P");
+const PUMP_SWAP_PROGRAM_ID: Pubkey = Pubkey::from_str_const("pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA");
+
+// A custom error is attributed to the program that raised it, found in the
+// logs (the deepest failed invocation), falling back to the failing
+// top-level instruction's program; its name comes from that program's
+// decoder, when it has one.
+pub fn failure_cause(
+    error: &TransactionError,
+    instructions: &[ParsedInstruction],
+    log_messages: &[String],
+    decoders: &DecoderRegistry,
+) -> FailureCause {
+    let raised_by = |index: u8| {
+        parse_logs(log_messages)
+            .walk()
+            .into_iter()
+            .rfind(|invocation| invocation.success == Some(false))
+            .and_then(|invocation| invocation.program_id.parse::<Pubkey>().ok())
+            .or_else(|| instructions.get(index as usize).map(|ix| ix.program_id))
+    };
+    match error {
+        TransactionError::InstructionError { index, error: InstructionError::Custom(code) } => {
+            let program_id = raised_by(*index);
+            let slippage = SLIPPAGE_ERRORS.iter().find(|(program, known, _)| Some(*program) == program_id && known == code);
+            let name = program_id
+                .and_then(|program_id| decoders.error_name(&program_id, *code))
+                .or_else(|| slippage.map(|(_, _, name)| name.to_string()));
+            let category = match (slippage, name.as_deref()) {
+                (Some(_), _) => FailureCategory::Slippage,
+                (None, Some(name)) => FailureCategory::of(name),
+                (None, None) => FailureCategory::Other,
+            };
+            FailureCause { program_id, error: format!("custom program error {:#x}", code), name, category }
+        }
+        TransactionError::InstructionError { index, error: InstructionError::Other(name) } => FailureCause {
+            program_id: raised_by(*index),
+            error: name.clone(),
+            name: None,
+            category: FailureCategory::of(name),
+        },
+        TransactionError::DuplicateInstruction { .. } => FailureCause::runtime("DuplicateInstruction"),
+        TransactionError::InsufficientFundsForRent { .. } => FailureCause::runtime("InsufficientFundsForRent"),
+        TransactionError::ProgramExecutionTemporarilyRestricted { .. } => {
+            FailureCause::runtime("ProgramExecutionTemporarilyRestricted")
+        }
+        TransactionError::Other(name) => FailureCause::runtime(name),
+    }
+}
+
+impl FailureCause {
+    fn runtime(error: &str) -> Self {
+        FailureCause { program_id: None, error: error.to_string(), name: None, category: FailureCategory::of(error) }
+    }
+}
+
+impl FailureCategory {
+    // From an error's name.
+    fn of(name: &str) -> Self {
+        if name.to_ascii_lowercase().contains("slippage") {
+            FailureCategory::Slippage
+        } else if name.starts_with("InsufficientFunds") {
+            FailureCategory::InsufficientFunds
+        } else if name == "ComputationalBudgetExceeded" {
+            FailureCategory::ComputeBudgetExceeded
+        } else {
+            FailureCategory::Other
+        }
+    }
+}
+
+// One line saying what went wrong, e.g. "instruction 4 failed: Tokenkeg...
+// custom program error 0x1 (InsufficientFunds)".
+pub fn failure_reason(
+    error: &TransactionError,
+    instructions: &[ParsedInstruction],
+    log_messages: &[String],
+    decoders: &DecoderRegistry,
+) -> String {
+    describe_failure(error, &failure_cause(error, instructions, log_messages, decoders))
+}
+
+// `failure_reason` for a cause already worked out.
+pub fn describe_failure(error: &TransactionError, cause: &FailureCause) -> String {
+    match error {
+        TransactionError::InstructionError { index, error: InstructionError::Custom(code) } => {
+            let program = cause.program_id.map(|program_id| format!("{} ", program_id)).unwrap_or_default();
+            match &cause.name {
+                Some(name) => format!("instruction {} failed: {}custom program error {:#x} ({})", index, program, code, name),
+                None => format!("instruction {} failed: {}custom program error {:#x}", index, program, code),
+            }
+        }
+        TransactionError::InstructionError { index, error: InstructionError::Other(name) } => {
+            format!("instruction {} failed: {}", index, name)
+        }
+        TransactionError::DuplicateInstruction { index } => format!("instruction {} is duplicated", index),
+        TransactionError::InsufficientFundsForRent { account_index } => {
+            format!("account {} would be left with insufficient funds for rent", account_index)
+        }
+        TransactionError::ProgramExecutionTemporarilyRestricted { account_index } => {
+            format!("execution of the program at account {} is temporarily restricted", account_index)
+        }
+        TransactionError::Other(name) => name.clone(),
+    }
+}
+
+// Failed transactions sharing a cause.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureGroup {
+    #[serde(flatten)]
+    pub cause: FailureCause,
+    pub count: usize,
+    // In block order.
+    pub signatures: Vec<String>,
+}
+
+// Most common cause first; ties by program and error. Transactions without
+// a failure_cause (successful, or read back from older output) are left
+// out.
+pub fn group_failures(transactions: &[ParsedTransaction]) -> Vec<FailureGroup> {
+    let mut groups: Vec<FailureGroup> = Vec::new();
+    for tx in transactions {
+        let Some(cause) = &tx.failure_cause else {
+            continue;
+        };
+        match groups.iter_mut().find(|group| group.cause == *cause) {
+            Some(group) => {
+                group.count += 1;
+                group.signatures.push(tx.signature.clone());
+            }
+            None => groups.push(FailureGroup { cause: cause.clone(), count: 1, signatures: vec![tx.signature.clone()] }),
+        }
+    }
+    groups.sort_by(|a, b| {
+        b.count.cmp(&a.count).then_with(|| (a.cause.program_id, &a.cause.error).cmp(&(b.cause.program_id, &b.cause.error)))
+    });
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoders::token::TOKEN_PROGRAM_ID;
+    use crate::fixtures;
+    use serde_json::json;
+
+    #[test]
+    fn types_rpc_errors() {
+        assert_eq!(
+            TransactionError::from_value(&json!({"InstructionError": [2, {"Custom": 6001}]})),
+            TransactionError::InstructionError { index: 2, error: InstructionError::Custom(6001) }
+        );
+        assert_eq!(
+            TransactionError::from_value(&json!({"InstructionError": [3, "IllegalOwner"]})),
+            TransactionError::InstructionError { index: 3, error: InstructionError::Other("IllegalOwner".to_string()) }
+        );
+        assert_eq!(
+            TransactionError::from_value(&json!({"InsufficientFundsForRent": {"account_index": 4}})),
+            TransactionError::InsufficientFundsForRent { account_index: 4 }
+        );
+        assert_eq!(
+            TransactionError::from_value(&json!("BlockhashNotFound")),
+            TransactionError::Other("BlockhashNotFound".to_string())
+        );
+        assert_eq!(TransactionError::from_value(&json!({"NewError": 1})), TransactionError::Other("{\"NewError\":1}".to_string()));
+    }
+
+    #[test]
+    fn attributes_custom_errors_to_the_failing_cpi() {
+        let block = fixtures::block();
+        assert!(block.transactions.iter().all(|tx| tx.is_success == tx.error.is_none() && tx.is_success == tx.failure_reason.is_none()));
+
+        // A program that calls the token program, which runs out of funds.
+        let logs: Vec<String> = [
+            "Program 4kmzZnRhGBr96GD6Gd7w8AYgg8nTqYQcCwVVVZZhxgTN invoke [1]",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [2]",
+            "Program log: Error: insufficient funds",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA failed: custom program error: 0x1",
+            "Program 4kmzZnRhGBr96GD6Gd7w8AYgg8nTqYQcCwVVVZZhxgTN failed: custom program error: 0x1",
+        ]
+        .map(String::from)
+        .to_vec();
+        let error = TransactionError::InstructionError { index: 0, error: InstructionError::Custom(1) };
+        assert_eq!(
+            failure_reason(&error, &[], &logs, &DecoderRegistry::with_builtins()),
+            format!("instruction 0 failed: {} custom program error 0x1 (InsufficientFunds)", TOKEN_PROGRAM_ID)
+        );
+        assert_eq!(failure_cause(&error, &[], &logs, &DecoderRegistry::with_builtins()).category, FailureCategory::InsufficientFunds);
+    }
+
+    #[test]
+    fn marks_the_failed_instruction_and_those_after_it() {
+        let block = fixtures::block();
+        assert!(block.transactions.iter().filter(|tx| tx.is_success).all(|tx| tx.instructions.iter().all(|ix| ix.execution.is_none())));
+
+        let tx = block.transactions.iter().find(|tx| tx.instructions.len() > 2).unwrap();
+        let (mut instructions, mut inner_instructions) = (tx.instructions.clone(), tx.inner_instructions.clone());
+        let error = TransactionError::InstructionError { index: 1, error: InstructionError::Custom(1) };
+        mark_execution(&error, &mut instructions, &mut inner_instructions);
+        let statuses: Vec<&str> = instructions.iter().map(|ix| ix.execution.as_ref().unwrap().as_str()).collect();
+        assert_eq!(statuses[..3], ["executed", "failed", "not_executed"]);
+        assert_eq!(instructions[1].execution, Some(InstructionExecution::Failed { error: InstructionError::Custom(1) }));
+        for inner in &inner_instructions {
+            let expected = if inner.index > 1 { "not_executed" } else { "executed" };
+            assert!(inner.instructions.iter().all(|ix| ix.execution.as_ref().unwrap().as_str() == expected));
+        }
+    }
+
+    #[test]
+    fn groups_failures_by_root_cause() {
+        let block = fixtures::block();
+        let groups = group_failures(&block.transactions);
+        let failed = block.transactions.iter().filter(|tx| !tx.is_success).count();
+        assert_eq!(groups.iter().map(|group| group.count).sum::<usize>(), failed);
+        assert!(groups.windows(2).all(|pair| pair[0].count >= pair[1].count));
+        // Failures in different instructions of the same program still group.
+        assert_eq!(groups[0].count, 6);
+        assert_eq!(groups[0].cause.error, "custom program error 0x1");
+
+        let slippage: Vec<&FailureGroup> = groups.iter().filter(|group| group.cause.category == FailureCategory::Slippage).collect();
+        assert_eq!(slippage.len(), 2);
+        assert_eq!(slippage[1].cause.program_id, Some(RAYDIUM_CLMM_PROGRAM_ID));
+        assert_eq!(slippage[1].cause.name.as_deref(), Some("TooLittleOutputReceived"));
+        assert!(block.transactions.iter().any(|tx| tx.failure_reason.as_deref().is_some_and(|reason| reason.ends_with("0x1786 (TooLittleOutputReceived)"))));
+    }
+}
@@ -0,0 +1,420 @@
+use crate::bloom::AddressBloom;
+use crate::encoding::DataEncoding;
+use crate::pubkey::Pubkey;
+use crate::types::{ParsedInstruction, ParsedTransaction, RpcBlockResult, RpcBlockTransaction, RpcMeta, RpcTransactionContainer};
+use serde_json::value::RawValue;
+use std::collections::HashSet;
+
+// ==========================================
+// TRANSACTION FILTERING
+// ==========================================
+// Criteria are ANDed together; the program and account lists match if any
+// entry matches. An empty filter keeps everything. During block parsing the
+// filter is checked against the raw transaction first, so rejected
+// transactions are never materialized. With the pre-filter on, the account
+// list is checked even earlier, against the transaction's JSON text before
+// it is deserialized at all.
+
+// VOTE_PROGRAM_ID as the RPC writes it.
+const VOTE_PROGRAM: &str = "Vote111111111111111111111111111111111111111";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStatus {
+    Success,
+    Failed,
+}
+
+impl TxStatus {
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "success" => Ok(TxStatus::Success),
+            "failed" => Ok(TxStatus::Failed),
+            other => Err(format!("unknown status '{}' (expected success or failed)", other)),
+        }
+    }
+}
+
+// An instruction type without a decoder for it: the program plus the first
+// bytes of the instruction data, such as an Anchor discriminator or a native
+// program's instruction tag. An empty prefix matches any instruction of the
+// program. Instructions the node returned as jsonParsed carry no data, so
+// only an empty prefix matches them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionPattern {
+    pub program_id: Pubkey,
+    pub prefix: Vec<u8>,
+}
+
+impl InstructionPattern {
+    pub fn new(program_id: Pubkey, prefix: Vec<u8>) -> Self {
+        InstructionPattern { program_id, prefix }
+    }
+
+    // <program>[:<prefix>], the prefix in hex when it starts with 0x and in
+    // base58 otherwise.
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        let (program, prefix) = value.split_once(':').unwrap_or((value, ""));
+        let program_id = program.parse().map_err(|_| format!("invalid program id '{}'", program))?;
+        let prefix = match prefix.strip_prefix("0x") {
+            Some(hex) => DataEncoding::Hex.decode(hex),
+            None => DataEncoding::Base58.decode(prefix),
+        }
+        .ok_or_else(|| format!("invalid instruction data prefix '{}' (expected 0x-prefixed hex or base58)", prefix))?;
+        Ok(InstructionPattern { program_id, prefix })
+    }
+
+    pub fn matches(&self, ix: &ParsedInstruction) -> bool {
+        ix.program_id == self.program_id
+            && (self.prefix.is_empty() || ix.data_bytes().is_some_and(|data| data.starts_with(&self.prefix)))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TxFilter {
+    programs: HashSet<Pubkey>,
+    instructions: Vec<InstructionPattern>,
+    accounts: HashSet<Pubkey>,
+    status: Option<TxStatus>,
+    min_fee: Option<u64>,
+    min_compute_units: Option<u64>,
+    exclude_votes: bool,
+    // The accounts, once the pre-filter is on.
+    bloom: Option<AddressBloom>,
+}
+
+impl TxFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Invoked directly or through CPI.
+    pub fn program(mut self, program_id: Pubkey) -> Self {
+        self.programs.insert(program_id);
+        self
+    }
+
+    // An instruction matching the pattern, top-level or inner.
+    pub fn instruction(mut self, pattern: InstructionPattern) -> Self {
+        if !self.instructions.contains(&pattern) {
+            self.instructions.push(pattern);
+        }
+        self
+    }
+
+    // Anywhere in the transaction's account list, including loaded addresses.
+    pub fn account(mut self, address: Pubkey) -> Self {
+        self.accounts.insert(address);
+        if self.bloom.is_some() {
+            self.bloom = Some(AddressBloom::new(&self.accounts));
+        }
+        self
+    }
+
+    // Checks block transactions' JSON against the accounts before
+    // deserializing them (see may_match_raw). Worth it when the accounts are
+    // in few of the transactions, e.g. a backfill for a handful of wallets:
+    // the ones that pass are read twice.
+    pub fn prefilter(mut self) -> Self {
+        self.bloom = Some(AddressBloom::new(&self.accounts));
+        self
+    }
+
+    // True when may_match_raw can reject anything.
+    pub fn prefilters(&self) -> bool {
+        self.bloom.is_some() && !self.accounts.is_empty()
+    }
+
+    pub fn status(mut self, status: TxStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn min_fee(mut self, lamports: u64) -> Self {
+        self.min_fee = Some(lamports);
+        self
+    }
+
+    // Transactions that didn't report consumed units never match.
+    pub fn min_compute_units(mut self, units: u64) -> Self {
+        self.min_compute_units = Some(units);
+        self
+    }
+
+    // Drops transactions calling the Vote program.
+    pub fn exclude_votes(mut self) -> Self {
+        self.exclude_votes = true;
+        self
+    }
+
+    pub fn excludes_votes(&self) -> bool {
+        self.exclude_votes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+            && self.instructions.is_empty()
+            && self.accounts.is_empty()
+            && self.status.is_none()
+            && self.min_fee.is_none()
+            && self.min_compute_units.is_none()
+            && !self.exclude_votes
+    }
+
+    pub fn matches(&self, tx: &ParsedTransaction) -> bool {
+        let programs_match = self.programs.is_empty()
+            || tx.instructions.iter()
+                .chain(tx.inner_instructions.iter().flat_map(|inner| &inner.instructions))
+                .any(|ix| self.programs.contains(&ix.program_id));
+        let instructions_match = self.instructions.is_empty()
+            || tx.instructions.iter()
+                .chain(tx.inner_instructions.iter().flat_map(|inner| &inner.instructions))
+                .any(|ix| self.instructions.iter().any(|pattern| pattern.matches(ix)));
+        let accounts_match = self.accounts.is_empty()
+            || tx.account_keys.iter().any(|key| self.accounts.contains(key));
+
+        self.meta_matches(tx.is_success, tx.fee, tx.compute_units_consumed)
+            && !(self.exclude_votes && tx.is_vote())
+            && programs_match
+            && instructions_match
+            && accounts_match
+    }
+
+    // Cheap check on the raw transaction. Returns false only when the
+    // transaction definitely fails the filter; account and program criteria
+    // are skipped when v0 lookup tables leave the account list incomplete.
+    // Instruction patterns are checked by program only, since the raw data's
+    // encoding depends on the parse options.
+    pub fn may_match(&self, tx: &RpcTransactionContainer, meta: &RpcMeta) -> bool {
+        if !self.meta_matches(meta.err.is_none(), meta.fee, meta.compute_units_consumed) || (self.exclude_votes && tx.is_vote()) {
+            return false;
+        }
+        if self.programs.is_empty() && self.instructions.is_empty() && self.accounts.is_empty() {
+            return true;
+        }
+
+        let has_lookups = tx.message.address_table_lookups.as_ref().is_some_and(|l| !l.is_empty());
+        let loaded = meta.loaded_addresses.as_ref();
+        if has_lookups && loaded.is_none_or(|l| l.writable.is_empty() && l.readonly.is_empty()) {
+            return true;
+        }
+        let keys: Vec<&Pubkey> = tx.message.account_keys.iter()
+            .chain(loaded.into_iter().flat_map(|l| l.writable.iter().chain(&l.readonly)))
+            .collect();
+
+        let program_ids: Vec<&Pubkey> = tx.message.instructions.iter()
+            .chain(meta.inner_instructions.iter().flatten().flat_map(|inner| &inner.instructions))
+            .filter_map(|ix| keys.get(ix.program_id_index).copied())
+            .collect();
+        let programs_match = self.programs.is_empty() || program_ids.iter().any(|id| self.programs.contains(*id));
+        let instructions_match = self.instructions.is_empty()
+            || program_ids.iter().any(|id| self.instructions.iter().any(|pattern| pattern.program_id == **id));
+        let accounts_match = self.accounts.is_empty() || keys.iter().any(|key| self.accounts.contains(*key));
+
+        programs_match && instructions_match && accounts_match
+    }
+
+    // Cheaper still: `raw` is a block transaction's JSON, as in getBlock's
+    // transactions array. Returns false only when none of the strings in it
+    // could be one of the accounts, so the account criterion definitely
+    // fails. Transactions whose keys aren't all in the text pass: wire
+    // encodings (base64, base58) and v0 transactions with lookups but no
+    // loadedAddresses.
+    pub fn may_match_raw(&self, raw: &str) -> bool {
+        let Some(bloom) = self.bloom.as_ref().filter(|_| !self.accounts.is_empty()) else {
+            return true;
+        };
+        let (mut lookups, mut loaded) = (false, false);
+        for (literal, rest) in json_strings(raw) {
+            match literal {
+                "base64" | "base58" => return true,
+                "addressTableLookups" => lookups = value_after(rest).strip_prefix('[').is_some_and(|list| !list.trim_start().starts_with(']')),
+                // {"readonly": [...], "writable": [...]}: any quote past
+                // the two keys is an address.
+                "loadedAddresses" => {
+                    let value = value_after(rest);
+                    loaded = value.starts_with('{') && value[..value.find('}').unwrap_or(value.len())].matches('"').count() > 4;
+                }
+                _ if (32..=44).contains(&literal.len()) && bloom.may_contain(literal) => return true,
+                _ => {}
+            }
+        }
+        lookups && !loaded
+    }
+
+    // A block read with its transactions left as JSON, with the ones
+    // may_match_raw rejects dropped and the rest deserialized.
+    pub fn prefilter_block(&self, block: RpcBlockResult<Box<RawValue>>) -> serde_json::Result<RpcBlockResult> {
+        let mut prefiltered_votes = block.prefiltered_votes;
+        let mut transactions = Vec::new();
+        for (index, raw) in block.transactions.iter().enumerate() {
+            if self.may_match_raw(raw.get()) {
+                transactions.push(RpcBlockTransaction { index: Some(index), ..serde_json::from_str(raw.get())? });
+            } else if self.exclude_votes && is_raw_vote(raw.get()) {
+                prefiltered_votes += 1;
+            }
+        }
+        Ok(RpcBlockResult {
+            block_height: block.block_height,
+            block_time: block.block_time,
+            blockhash: block.blockhash,
+            parent_slot: block.parent_slot,
+            previous_blockhash: block.previous_blockhash,
+            rewards: block.rewards,
+            transactions,
+            signatures: block.signatures,
+            prefiltered_votes,
+            endpoint: block.endpoint,
+        })
+    }
+
+    fn meta_matches(&self, is_success: bool, fee: u64, compute_units: Option<u64>) -> bool {
+        let status_match = match self.status {
+            Some(TxStatus::Success) => is_success,
+            Some(TxStatus::Failed) => !is_success,
+            None => true,
+        };
+        let fee_match = self.min_fee.is_none_or(|min| fee >= min);
+        let units_match = self.min_compute_units.is_none_or(|min| compute_units.is_some_and(|cu| cu >= min));
+        status_match && fee_match && units_match
+    }
+}
+
+// For counting the votes among transactions the pre-filter rejected, which
+// are never deserialized: the Vote program among a transaction's strings.
+// Unlike is_vote this also counts transactions that list it without
+// calling it, which votes aside are rare.
+pub(crate) fn is_raw_vote(raw: &str) -> bool {
+    json_strings(raw).any(|(literal, _)| literal == VOTE_PROGRAM)
+}
+
+// The string literals in a JSON text, each with the text after it. Escapes
+// are left in; addresses have none.
+fn json_strings(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let start = rest.find('"')? + 1;
+        let bytes = rest.as_bytes();
+        let mut end = start;
+        while end < bytes.len() && bytes[end] != b'"' {
+            end += if bytes[end] == b'\\' { 2 } else { 1 };
+        }
+        let literal = rest.get(start..end.min(bytes.len()))?;
+        rest = rest.get(end + 1..).unwrap_or("");
+        Some((literal, rest))
+    })
+}
+
+// The value following an object key: `rest` is the text after the key.
+fn value_after(rest: &str) -> &str {
+    rest.trim_start().strip_prefix(':').unwrap_or(rest).trim_start()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::parser::{parse_block_with_options, parse_transaction, ParseOptions};
+    use std::sync::Arc;
+
+    #[test]
+    fn raw_precheck_agrees_with_parsed_match() {
+        let raw = fixtures::raw_block();
+        let filter = TxFilter::new()
+            .program(crate::decoders::token::TOKEN_PROGRAM_ID)
+            .status(TxStatus::Success);
+
+        let mut kept = 0;
+        for tx in &raw.result.transactions {
+            let parsed = parse_transaction(&tx.transaction, &tx.meta).unwrap();
+            assert_eq!(filter.may_match(&tx.transaction, &tx.meta), filter.matches(&parsed));
+            kept += filter.matches(&parsed) as usize;
+        }
+        assert!(kept > 0 && kept < raw.result.transactions.len());
+        assert!(TxFilter::new().is_empty());
+    }
+
+    #[test]
+    fn prefilter_only_rejects_transactions_that_cant_match() {
+        let text = std::fs::read_to_string("src/json/block.json").unwrap();
+        let response: RawBlockResponse = serde_json::from_str(&text).unwrap();
+        let trader = Pubkey::from_str_const("8qjB4NibFqqRyECMUMWw7X4are73dms6pfM9gBEJGNAP");
+        let filter = TxFilter::new().account(trader).prefilter().exclude_votes();
+        assert!(filter.prefilters() && !TxFilter::new().prefilter().prefilters());
+
+        let mut rejected = 0;
+        for json in &response.result.transactions {
+            let tx: crate::types::RpcBlockTransaction = serde_json::from_str(json.get()).unwrap();
+            let parsed = parse_transaction(&tx.transaction, &tx.meta).unwrap();
+            let passes = filter.may_match_raw(json.get());
+            assert!(passes || !filter.matches(&parsed));
+            rejected += usize::from(!passes);
+        }
+        assert!(rejected > response.result.transactions.len() * 9 / 10);
+
+        // Keys the text doesn't hold: lookups without loadedAddresses, and
+        // wire encodings.
+        let lookups = r#"{"meta": {"err": null}, "transaction": {"message": {"accountKeys": [], "addressTableLookups": [{"accountKey": "x"}]}}}"#;
+        assert!(filter.may_match_raw(lookups));
+        assert!(!filter.may_match_raw(&lookups.replace(r#"[{"accountKey": "x"}]"#, "[ ]")));
+        assert!(filter.may_match_raw(r#"{"transaction": ["AQID", "base64"]}"#));
+
+        // The same block through the streaming parser and the RPC path.
+        let options = |filter: TxFilter| ParseOptions { filter: Some(Arc::new(filter)), ..ParseOptions::default() };
+        let raw = fixtures::raw_block();
+        let (expected, _) = parse_block_with_options(raw.result, &options(TxFilter::new().account(trader).exclude_votes())).unwrap();
+        assert!(!expected.transactions.is_empty());
+        let (streamed, _) = crate::incremental::parse_block_reader(text.as_bytes(), &options(filter.clone())).unwrap();
+        let (fetched, _) = parse_block_with_options(filter.prefilter_block(response.result).unwrap(), &options(filter)).unwrap();
+        for block in [streamed, fetched] {
+            assert_eq!(block.excluded_votes, expected.excluded_votes);
+            // Pre-filtered transactions keep their position in the block.
+            let placed = |block: &crate::types::ParsedBlock| {
+                block.transactions.iter().map(|tx| (tx.signature.clone(), tx.slot, tx.index_in_block)).collect::<Vec<_>>()
+            };
+            assert_eq!(placed(&block), placed(&expected));
+        }
+    }
+
+    #[derive(serde::Deserialize)]
+    struct RawBlockResponse {
+        result: RpcBlockResult<Box<RawValue>>,
+    }
+
+    #[test]
+    fn excludes_vote_transactions() {
+        let raw = fixtures::raw_block();
+        let votes = raw.result.transactions.iter().filter(|tx| tx.transaction.is_vote()).count();
+        assert!(votes > 0);
+
+        let options = ParseOptions { filter: Some(Arc::new(TxFilter::new().exclude_votes())), ..ParseOptions::default() };
+        let (block, _) = parse_block_with_options(raw.result, &options).unwrap();
+        assert_eq!(block.excluded_votes, Some(votes));
+        assert!(block.transactions.iter().all(|tx| !tx.is_vote()));
+        assert_eq!(block.transactions.len() + votes, 1127);
+    }
+
+    #[test]
+    fn instruction_patterns_match_program_and_data_prefix() {
+        // SetComputeUnitPrice is Compute Budget instruction 3.
+        let pattern = InstructionPattern::from_arg("ComputeBudget111111111111111111111111111111:0x03").unwrap();
+        assert_eq!(InstructionPattern::from_arg("ComputeBudget111111111111111111111111111111:4"), Ok(pattern.clone()));
+        assert!(InstructionPattern::from_arg("ComputeBudget111111111111111111111111111111:0xzz").is_err());
+        let filter = TxFilter::new().instruction(pattern);
+        assert!(!filter.is_empty());
+
+        let raw = fixtures::raw_block();
+        let mut kept = 0;
+        for tx in &raw.result.transactions {
+            let parsed = parse_transaction(&tx.transaction, &tx.meta).unwrap();
+            assert_eq!(filter.matches(&parsed), parsed.unit_price_micro_lamports.is_some());
+            assert!(filter.may_match(&tx.transaction, &tx.meta) || !filter.matches(&parsed));
+            kept += filter.matches(&parsed) as usize;
+        }
+        assert!(kept > 0);
+        let any = TxFilter::new().instruction(InstructionPattern::from_arg("ComputeBudget111111111111111111111111111111").unwrap());
+        assert!(raw.result.transactions.iter().any(|tx| {
+            let parsed = parse_transaction(&tx.transaction, &tx.meta).unwrap();
+            any.matches(&parsed) && !filter.matches(&parsed)
+        }));
+    }
+}
@@ -0,0 +1,23 @@
+use crate::io::load_from_json;
+use crate::parser::parse_block;
+use crate::types::{ParsedBlock, RpcBlockResponse};
+use std::sync::OnceLock;
+
+// ==========================================
+// TEST FIXTURES
+// ==========================================
+// src/json/block.json, a full mainnet block, is what most tests run on. It's
+// parsed once per test binary and shared; tests that change it clone it.
+
+const BLOCK_PATH: &str = "src/json/block.json";
+
+// The getBlock response, for tests that parse it their own way.
+pub fn raw_block() -> RpcBlockResponse {
+    load_from_json(BLOCK_PATH).unwrap()
+}
+
+// The block parsed with default options.
+pub fn block() -> &'static ParsedBlock {
+    static BLOCK: OnceLock<ParsedBlock> = OnceLock::new();
+    BLOCK.get_or_init(|| parse_block(raw_block().result).unwrap())
+}
@@ -0,0 +1,235 @@
+pub mod proto;
+
+use crate::http2::Http2Stream;
+use crate::parser::{parse_transaction_with_options, ParseOptions};
+use crate::rpc::Commitment;
+use crate::source::{BlockSource, RawBlock};
+use crate::stream::StreamedBlock;
+use crate::types::{ParseReport, ParsedTransaction};
+use proto::{decode_update, ProtoWriter, Update};
+use std::error::Error;
+use std::io::{ErrorKind, Read};
+
+// ==========================================
+// YELLOWSTONE GEYSER SOURCE
+// ==========================================
+// Subscribes to a Yellowstone gRPC endpoint (geyser.Geyser/Subscribe) and
+// yields blocks or transactions converted into the crate's Rpc* structs.
+// gRPC runs over the crate's own h2c client, so endpoints must be plaintext
+// (Yellowstone's default port 10000 is). The request stream is left open
+// for the life of the subscription since Yellowstone ends a subscription
+// when the client closes it.
+
+// Full blocks can be several megabytes; a longer frame is a broken or
+// hostile peer, refused before its buffer is allocated.
+const MAX_MESSAGE_LEN: usize = 256 * 1024 * 1024;
+
+pub struct StreamedTransaction {
+    pub slot: u64,
+    pub transaction: ParsedTransaction,
+    pub report: ParseReport,
+}
+
+pub struct GeyserClient {
+    endpoint: String,
+    x_token: Option<String>,
+    commitment: Commitment,
+}
+
+impl GeyserClient {
+    pub fn new(endpoint: &str) -> Self {
+        GeyserClient {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            x_token: None,
+            commitment: Commitment::Finalized,
+        }
+    }
+
+    // Sent as the x-token header most providers authenticate with.
+    pub fn with_x_token(mut self, token: &str) -> Self {
+        self.x_token = Some(token.to_string());
+        self
+    }
+
+    pub fn with_commitment(mut self, commitment: Commitment) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    // Full blocks with transactions, optionally only those with a
+    // transaction touching `mentions`.
+    pub fn block_subscribe(&self, mentions: Option<&str>, options: ParseOptions) -> Result<GeyserBlocks, Box<dyn Error>> {
+        let mut request = ProtoWriter::new();
+        request.message(4, |entry| {
+            entry.string(1, "blocks").message(2, |filter| {
+                if let Some(account) = mentions {
+                    filter.string(1, account);
+                }
+                filter.varint(2, 1).varint(3, 0).varint(4, 0);
+            });
+        });
+        self.add_commitment(&mut request);
+        Ok(GeyserBlocks { stream: self.subscribe(request)?, options })
+    }
+
+    // Transactions touching `mentions`, or every non-vote transaction.
+    pub fn transaction_subscribe(&self, mentions: Option<&str>, options: ParseOptions) -> Result<GeyserTransactions, Box<dyn Error>> {
+        let mut request = ProtoWriter::new();
+        request.message(3, |entry| {
+            entry.string(1, "transactions").message(2, |filter| {
+                match mentions {
+                    Some(account) => filter.string(3, account),
+                    None => filter.varint(1, 0),
+                };
+            });
+        });
+        self.add_commitment(&mut request);
+        Ok(GeyserTransactions { stream: self.subscribe(request)?, options })
+    }
+
+    fn add_commitment(&self, request: &mut ProtoWriter) {
+        let level = match self.commitment {
+            Commitment::Processed => 0,
+            Commitment::Confirmed => 1,
+            Commitment::Finalized => 2,
+        };
+        request.varint(6, level);
+    }
+
+    fn subscribe(&self, request: ProtoWriter) -> Result<GrpcStream, Box<dyn Error>> {
+        let url = format!("{}/geyser.Geyser/Subscribe", self.endpoint);
+        let mut headers = vec![("content-type", "application/grpc"), ("te", "trailers")];
+        if let Some(token) = &self.x_token {
+            headers.push(("x-token", token));
+        }
+        let mut stream = Http2Stream::post(&url, &headers)?;
+        stream.send_data(&grpc_frame(&request.into_bytes()), false)?;
+        Ok(GrpcStream { stream, done: false })
+    }
+}
+
+// 1-byte compression flag, 4-byte big-endian length, message.
+fn grpc_frame(message: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(message.len() + 5);
+    frame.push(0);
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(message);
+    frame
+}
+
+struct GrpcStream {
+    stream: Http2Stream,
+    done: bool,
+}
+
+impl GrpcStream {
+    fn next_update(&mut self) -> Option<Result<Update, Box<dyn Error>>> {
+        while !self.done {
+            match self.read_message() {
+                Ok(Some(message)) => match decode_update(&message) {
+                    Ok(Update::Other) => continue,
+                    result => return Some(result),
+                },
+                Ok(None) => {
+                    self.done = true;
+                    return self.status_error().map(Err);
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+
+    fn read_message(&mut self) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        let mut header = [0u8; 5];
+        match self.stream.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        if header[0] != 0 {
+            return Err("compressed gRPC messages are not supported".into());
+        }
+        let len = u32::from_be_bytes(header[1..].try_into()?) as usize;
+        if len > MAX_MESSAGE_LEN {
+            return Err(format!("gRPC message of {} bytes is over the {} byte limit", len, MAX_MESSAGE_LEN).into());
+        }
+        let mut message = vec![0u8; len];
+        self.stream.read_exact(&mut message)?;
+        Ok(Some(message))
+    }
+
+    // Once the response ends: the HTTP or gRPC failure, if there was one.
+    // A trailers-only response carries grpc-status in its headers.
+    fn status_error(&self) -> Option<Box<dyn Error>> {
+        if let Some(status) = self.stream.status().filter(|&status| status != 200) {
+            return Some(format!("geyser endpoint returned HTTP {}", status).into());
+        }
+        let grpc = |name| self.stream.trailer(name).or_else(|| self.stream.header(name));
+        match grpc("grpc-status") {
+            Some("0") | None => None,
+            Some(code) => Some(format!("geyser stream failed (grpc-status {}): {}", code, grpc("grpc-message").unwrap_or_default()).into()),
+        }
+    }
+}
+
+pub struct GeyserBlocks {
+    stream: GrpcStream,
+    options: ParseOptions,
+}
+
+impl BlockSource for GeyserBlocks {
+    fn next_block(&mut self) -> Option<Result<RawBlock, Box<dyn Error>>> {
+        loop {
+            match self.stream.next_update()? {
+                Ok(Update::Block { slot, block }) => return Some(Ok(RawBlock { slot: Some(slot), block })),
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl Iterator for GeyserBlocks {
+    type Item = Result<StreamedBlock, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (slot, block) = match self.next_block()? {
+            Ok(RawBlock { slot, block }) => (slot.expect("block updates carry their slot"), block),
+            Err(e) => return Some(Err(e)),
+        };
+        Some(StreamedBlock::parse(slot, block, &self.options).map_err(Into::into))
+    }
+}
+
+pub struct GeyserTransactions {
+    stream: GrpcStream,
+    options: ParseOptions,
+}
+
+impl Iterator for GeyserTransactions {
+    type Item = Result<StreamedTransaction, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stream.next_update()? {
+                Ok(Update::Transaction { slot, transaction }) => {
+                    return Some(
+                        parse_transaction_with_options(&transaction.transaction, &transaction.meta, &self.options)
+                            .map(|(parsed, report)| StreamedTransaction {
+                                slot,
+                                transaction: ParsedTransaction { slot: Some(slot), index_in_block: transaction.index, ..parsed },
+                                report,
+                            })
+                            .map_err(Into::into),
+                    );
+                }
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
@@ -0,0 +1,580 @@
+use crate::pubkey::Pubkey;
+use crate::types::*;
+use solana_sdk::transaction::TransactionError;
+use std::error::Error;
+
+// ==========================================
+// PROTOBUF WIRE FORMAT
+// ==========================================
+// Just enough of the encoding to build a SubscribeRequest and walk the
+// fields of a SubscribeUpdate. Unknown fields are skipped, so newer
+// Yellowstone versions that add fields still decode.
+
+pub enum Field<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+impl<'a> Field<'a> {
+    fn varint(&self) -> u64 {
+        match self {
+            Field::Varint(v) | Field::Fixed64(v) => *v,
+            Field::Fixed32(v) => *v as u64,
+            Field::Bytes(_) => 0,
+        }
+    }
+
+    fn bytes(&self) -> &'a [u8] {
+        match self {
+            Field::Bytes(b) => b,
+            _ => &[],
+        }
+    }
+
+    fn string(&self) -> String {
+        String::from_utf8_lossy(self.bytes()).into_owned()
+    }
+
+    // Account keys are raw 32-byte fields; token balance owners, mints and
+    // reward pubkeys are base58 strings.
+    fn pubkey(&self) -> Result<Pubkey, Box<dyn Error>> {
+        Ok(Pubkey::try_from(self.bytes())?)
+    }
+
+    fn pubkey_string(&self) -> Result<Pubkey, Box<dyn Error>> {
+        Ok(self.string().parse()?)
+    }
+
+    // proto3 packs repeated scalars, but unpacked encodings are valid too.
+    fn packed_varints(&self) -> Result<Vec<u64>, Box<dyn Error>> {
+        match self {
+            Field::Bytes(b) => {
+                let mut reader = ProtoReader::new(b);
+                let mut values = Vec::new();
+                while !reader.is_empty() {
+                    values.push(reader.read_varint()?);
+                }
+                Ok(values)
+            }
+            other => Ok(vec![other.varint()]),
+        }
+    }
+}
+
+pub struct ProtoReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ProtoReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        ProtoReader { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn read_varint(&mut self) -> Result<u64, Box<dyn Error>> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.data.get(self.pos).ok_or("truncated protobuf varint")?;
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("protobuf varint too long".into())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len()).ok_or("truncated protobuf field")?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub fn next_field(&mut self) -> Result<Option<(u32, Field<'a>)>, Box<dyn Error>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let key = self.read_varint()?;
+        let field = match key & 7 {
+            0 => Field::Varint(self.read_varint()?),
+            1 => Field::Fixed64(u64::from_le_bytes(self.take(8)?.try_into()?)),
+            2 => {
+                let len = self.read_varint()? as usize;
+                Field::Bytes(self.take(len)?)
+            }
+            5 => Field::Fixed32(u32::from_le_bytes(self.take(4)?.try_into()?)),
+            wire_type => return Err(format!("unsupported protobuf wire type {}", wire_type).into()),
+        };
+        Ok(Some(((key >> 3) as u32, field)))
+    }
+}
+
+#[derive(Default)]
+pub struct ProtoWriter {
+    buf: Vec<u8>,
+}
+
+impl ProtoWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    fn put_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+
+    pub fn varint(&mut self, field: u32, value: u64) -> &mut Self {
+        self.put_varint((field as u64) << 3);
+        self.put_varint(value);
+        self
+    }
+
+    pub fn bytes(&mut self, field: u32, value: &[u8]) -> &mut Self {
+        self.put_varint(((field as u64) << 3) | 2);
+        self.put_varint(value.len() as u64);
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    pub fn string(&mut self, field: u32, value: &str) -> &mut Self {
+        self.bytes(field, value.as_bytes())
+    }
+
+    pub fn message(&mut self, field: u32, build: impl FnOnce(&mut ProtoWriter)) -> &mut Self {
+        let mut inner = ProtoWriter::new();
+        build(&mut inner);
+        self.bytes(field, &inner.buf)
+    }
+}
+
+// ==========================================
+// GEYSER MESSAGES
+// ==========================================
+// Field numbers follow yellowstone-grpc's geyser.proto and Agave's
+// solana-storage-proto confirmed_block.proto. Everything is converted into
+// the same Rpc* structs a getBlock / getTransaction json response
+// deserializes into, with base58 instruction data, so the regular parser
+// handles it unchanged.
+
+pub enum Update {
+    Block { slot: u64, block: RpcBlockResult },
+    Transaction { slot: u64, transaction: Box<RpcBlockTransaction> },
+    // Pings, slot updates and anything else we didn't subscribe to.
+    Other,
+}
+
+pub fn decode_update(data: &[u8]) -> Result<Update, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            4 => return decode_transaction_update(field.bytes()),
+            5 => return decode_block(field.bytes()),
+            _ => {}
+        }
+    }
+    Ok(Update::Other)
+}
+
+fn decode_transaction_update(data: &[u8]) -> Result<Update, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut slot = 0;
+    let mut transaction = None;
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            1 => transaction = Some(decode_transaction_info(field.bytes())?.1),
+            2 => slot = field.varint(),
+            _ => {}
+        }
+    }
+    let transaction = transaction.ok_or("transaction update without a transaction")?;
+    Ok(Update::Transaction { slot, transaction: Box::new(transaction) })
+}
+
+fn decode_block(data: &[u8]) -> Result<Update, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut slot = 0;
+    let mut block = RpcBlockResult {
+        block_height: None,
+        block_time: None,
+        blockhash: String::new(),
+        parent_slot: 0,
+        previous_blockhash: String::new(),
+        rewards: Vec::new(),
+        transactions: Vec::new(),
+        signatures: Vec::new(),
+        prefiltered_votes: 0,
+        endpoint: None,
+    };
+    let mut transactions = Vec::new();
+
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            1 => slot = field.varint(),
+            2 => block.blockhash = field.string(),
+            3 => block.rewards = decode_rewards(field.bytes())?,
+            4 => block.block_time = Some(single_varint(field.bytes())? as i64),
+            5 => block.block_height = Some(single_varint(field.bytes())?),
+            6 => transactions.push(decode_transaction_info(field.bytes())?),
+            7 => block.parent_slot = field.varint(),
+            8 => block.previous_blockhash = field.string(),
+            _ => {}
+        }
+    }
+
+    transactions.sort_by_key(|(index, _)| *index);
+    block.transactions = transactions.into_iter().map(|(_, tx)| tx).collect();
+    Ok(Update::Block { slot, block })
+}
+
+// UnixTimestamp / BlockHeight wrappers: a message with one varint field.
+fn single_varint(data: &[u8]) -> Result<u64, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut value = 0;
+    while let Some((number, field)) = reader.next_field()? {
+        if number == 1 {
+            value = field.varint();
+        }
+    }
+    Ok(value)
+}
+
+fn decode_rewards(data: &[u8]) -> Result<Vec<RpcReward>, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut rewards = Vec::new();
+    while let Some((number, field)) = reader.next_field()? {
+        if number == 1 {
+            rewards.push(decode_reward(field.bytes())?);
+        }
+    }
+    Ok(rewards)
+}
+
+fn decode_reward(data: &[u8]) -> Result<RpcReward, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut reward = RpcReward {
+        pubkey: Pubkey::default(),
+        lamports: 0,
+        post_balance: 0,
+        reward_type: RewardType::default(),
+        commission: None,
+    };
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            1 => reward.pubkey = field.pubkey_string()?,
+            2 => reward.lamports = field.varint() as i64,
+            3 => reward.post_balance = field.varint(),
+            4 => {
+                reward.reward_type = match field.varint() {
+                    1 => RewardType::Fee,
+                    2 => RewardType::Rent,
+                    3 => RewardType::Staking,
+                    4 => RewardType::Voting,
+                    _ => RewardType::default(),
+                }
+            }
+            5 => reward.commission = field.string().parse().ok(),
+            _ => {}
+        }
+    }
+    Ok(reward)
+}
+
+// (position in block, transaction)
+fn decode_transaction_info(data: &[u8]) -> Result<(u64, RpcBlockTransaction), Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut index = 0;
+    let mut transaction = None;
+    let mut meta = None;
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            3 => transaction = Some(decode_transaction(field.bytes())?),
+            4 => meta = Some(decode_meta(field.bytes())?),
+            5 => index = field.varint(),
+            _ => {}
+        }
+    }
+    Ok((
+        index,
+        RpcBlockTransaction {
+            transaction: transaction.ok_or("transaction info without a transaction")?,
+            meta: meta.ok_or("transaction info without meta")?,
+            index: Some(index as usize),
+        },
+    ))
+}
+
+fn decode_transaction(data: &[u8]) -> Result<RpcTransactionContainer, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut signatures = Vec::new();
+    let mut message = RpcMessage {
+        header: RpcMessageHeader::default(),
+        account_keys: Vec::new(),
+        recent_blockhash: None,
+        instructions: Vec::new(),
+        address_table_lookups: None,
+        header_from_flags: false,
+        lookup_keys: None,
+    };
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            1 => signatures.push(bs58::encode(field.bytes()).into_string()),
+            2 => message = decode_message(field.bytes())?,
+            _ => {}
+        }
+    }
+    Ok(RpcTransactionContainer { signatures, message })
+}
+
+fn decode_message(data: &[u8]) -> Result<RpcMessage, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut header = RpcMessageHeader::default();
+    let mut account_keys = Vec::new();
+    let mut recent_blockhash = None;
+    let mut instructions = Vec::new();
+    let mut lookups = Vec::new();
+    let mut versioned = false;
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            1 => header = decode_header(field.bytes())?,
+            2 => account_keys.push(field.pubkey()?),
+            3 => recent_blockhash = Some(bs58::encode(field.bytes()).into_string()),
+            // Top-level instructions carry no stack height; it's always 1.
+            4 => instructions.push(RpcInstruction { stack_height: Some(1), ..decode_instruction(field.bytes())? }),
+            5 => versioned = field.varint() != 0,
+            6 => lookups.push(decode_lookup(field.bytes())?),
+            _ => {}
+        }
+    }
+    Ok(RpcMessage {
+        header,
+        account_keys,
+        recent_blockhash,
+        instructions,
+        address_table_lookups: versioned.then_some(lookups),
+        header_from_flags: false,
+        lookup_keys: None,
+    })
+}
+
+fn decode_header(data: &[u8]) -> Result<RpcMessageHeader, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut header = RpcMessageHeader::default();
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            1 => header.num_required_signatures = field.varint() as u8,
+            2 => header.num_readonly_signed_accounts = field.varint() as u8,
+            3 => header.num_readonly_unsigned_accounts = field.varint() as u8,
+            _ => {}
+        }
+    }
+    Ok(header)
+}
+
+// CompiledInstruction and InnerInstruction share fields 1-3.
+fn decode_instruction(data: &[u8]) -> Result<RpcInstruction, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut instruction = RpcInstruction::new(0, Vec::new(), String::new());
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            1 => instruction.program_id_index = field.varint() as usize,
+            2 => instruction.accounts = field.bytes().iter().map(|&i| i as usize).collect(),
+            3 => instruction.data = bs58::encode(field.bytes()).into_string(),
+            // InnerInstruction only.
+            4 => instruction.stack_height = Some(field.varint() as u32),
+            _ => {}
+        }
+    }
+    Ok(instruction)
+}
+
+fn decode_lookup(data: &[u8]) -> Result<RpcAddressTableLookup, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut lookup = RpcAddressTableLookup { account_key: Pubkey::default(), writable_indexes: Vec::new(), readonly_indexes: Vec::new() };
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            1 => lookup.account_key = field.pubkey()?,
+            2 => lookup.writable_indexes = field.bytes().to_vec(),
+            3 => lookup.readonly_indexes = field.bytes().to_vec(),
+            _ => {}
+        }
+    }
+    Ok(lookup)
+}
+
+fn decode_meta(data: &[u8]) -> Result<RpcMeta, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut meta = RpcMeta {
+        err: None,
+        log_messages: Vec::new(),
+        pre_balances: Vec::new(),
+        post_balances: Vec::new(),
+        loaded_addresses: None,
+        inner_instructions: Some(Vec::new()),
+        pre_token_balances: Some(Vec::new()),
+        post_token_balances: Some(Vec::new()),
+        fee: 0,
+        compute_units_consumed: None,
+    };
+    let mut loaded = RpcLoadedAddresses { writable: Vec::new(), readonly: Vec::new() };
+
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            1 => meta.err = Some(decode_error(field.bytes())?),
+            2 => meta.fee = field.varint(),
+            3 => meta.pre_balances.extend(field.packed_varints()?),
+            4 => meta.post_balances.extend(field.packed_varints()?),
+            5 => meta.inner_instructions.get_or_insert_default().push(decode_inner_instructions(field.bytes())?),
+            6 => meta.log_messages.push(field.string()),
+            7 => meta.pre_token_balances.get_or_insert_default().push(decode_token_balance(field.bytes())?),
+            8 => meta.post_token_balances.get_or_insert_default().push(decode_token_balance(field.bytes())?),
+            10 if field.varint() != 0 => meta.inner_instructions = None,
+            12 => loaded.writable.push(field.pubkey()?),
+            13 => loaded.readonly.push(field.pubkey()?),
+            16 => meta.compute_units_consumed = Some(field.varint()),
+            _ => {}
+        }
+    }
+    meta.loaded_addresses = Some(loaded);
+    Ok(meta)
+}
+
+// TransactionError { bytes err = 1 } holds the bincode-serialized error;
+// re-serialized as json it matches what getBlock returns.
+fn decode_error(data: &[u8]) -> Result<serde_json::Value, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut raw: &[u8] = &[];
+    while let Some((number, field)) = reader.next_field()? {
+        if number == 1 {
+            raw = field.bytes();
+        }
+    }
+    let error: TransactionError = bincode::deserialize(raw)?;
+    Ok(serde_json::to_value(error)?)
+}
+
+fn decode_inner_instructions(data: &[u8]) -> Result<RpcInnerInstructions, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut inner = RpcInnerInstructions { index: 0, instructions: Vec::new() };
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            1 => inner.index = field.varint() as usize,
+            2 => inner.instructions.push(decode_instruction(field.bytes())?),
+            _ => {}
+        }
+    }
+    Ok(inner)
+}
+
+fn decode_token_balance(data: &[u8]) -> Result<RpcTokenBalance, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut balance = RpcTokenBalance {
+        account_index: 0,
+        mint: Pubkey::default(),
+        owner: None,
+        program_id: None,
+        ui_token_amount: RpcUiTokenAmount { amount: String::new(), decimals: 0, ui_amount: None, ui_amount_string: None },
+    };
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            1 => balance.account_index = field.varint() as usize,
+            2 => balance.mint = field.pubkey_string()?,
+            3 => balance.ui_token_amount = decode_ui_token_amount(field.bytes())?,
+            4 if !field.bytes().is_empty() => balance.owner = Some(field.pubkey_string()?),
+            5 if !field.bytes().is_empty() => balance.program_id = Some(field.pubkey_string()?),
+            _ => {}
+        }
+    }
+    Ok(balance)
+}
+
+fn decode_ui_token_amount(data: &[u8]) -> Result<RpcUiTokenAmount, Box<dyn Error>> {
+    let mut reader = ProtoReader::new(data);
+    let mut amount = RpcUiTokenAmount { amount: String::new(), decimals: 0, ui_amount: None, ui_amount_string: None };
+    while let Some((number, field)) = reader.next_field()? {
+        match number {
+            1 => amount.ui_amount = Some(f64::from_bits(field.varint())),
+            2 => amount.decimals = field.varint() as u8,
+            3 => amount.amount = field.string(),
+            4 => amount.ui_amount_string = Some(field.string()),
+            _ => {}
+        }
+    }
+    Ok(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_block;
+
+    #[test]
+    fn decodes_block_update_into_rpc_structs() {
+        let payer = [1u8; 32];
+        let program = [0u8; 32];
+        let mut update = ProtoWriter::new();
+        update.message(5, |block| {
+            block
+                .varint(1, 101)
+                .string(2, "hash")
+                .message(4, |t| { t.varint(1, 1_700_000_000); })
+                .message(5, |h| { h.varint(1, 90); })
+                .varint(7, 100)
+                .string(8, "parent")
+                .message(6, |info| {
+                    info.varint(5, 0)
+                        .message(3, |tx| {
+                            tx.bytes(1, &[7u8; 64]).message(2, |msg| {
+                                msg.message(1, |header| {
+                                    header.varint(1, 1).varint(3, 1);
+                                })
+                                .bytes(2, &payer).bytes(2, &program).message(4, |ix| {
+                                    ix.varint(1, 1).bytes(2, &[0]).bytes(3, &[2, 0, 0, 0]);
+                                });
+                            });
+                        })
+                        .message(4, |meta| {
+                            // InstructionError(0, Custom(1)), bincode-encoded
+                            let err = [8, 0, 0, 0, 0, 25, 0, 0, 0, 1, 0, 0, 0];
+                            meta.message(1, |e| { e.bytes(1, &err); })
+                                .varint(2, 5000)
+                                .bytes(3, &[0x80, 0x2d, 1])
+                                .string(6, "Program log: hi")
+                                .varint(16, 150);
+                        });
+                });
+        });
+
+        let Update::Block { slot, block } = decode_update(&update.into_bytes()).unwrap() else {
+            panic!("expected a block update");
+        };
+        assert_eq!((slot, block.parent_slot, block.block_height, block.block_time), (101, 100, Some(90), Some(1_700_000_000)));
+
+        let tx = &block.transactions[0];
+        assert_eq!(tx.meta.pre_balances, vec![5760, 1]);
+        assert_eq!(tx.meta.err, Some(serde_json::json!({ "InstructionError": [0, { "Custom": 1 }] })));
+        assert_eq!(tx.transaction.message.account_keys[0], bs58::encode(payer).into_string());
+
+        let parsed = parse_block(block).unwrap();
+        let parsed_tx = &parsed.transactions[0];
+        assert!(!parsed_tx.is_success);
+        assert_eq!(parsed_tx.compute_units_consumed, Some(150));
+        assert_eq!(parsed_tx.instructions[0].data_bytes(), Some(vec![2, 0, 0, 0]));
+        assert!(parsed_tx.accounts[0].is_signer && parsed_tx.accounts[0].is_writable);
+        assert!(!parsed_tx.accounts[1].is_signer && !parsed_tx.accounts[1].is_writable);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::io::load_from_json;
+use crate::parser::{parse_block, parse_transaction_result, ParseOptions};
+use crate::types::{RpcBlockResponse, RpcResponse};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+// ==========================================
+// GOLDEN FILES
+// ==========================================
+// Snapshot tests over the RPC fixtures in src/json: each one is parsed and
+// its JSON output compared, field by field, with the golden file of the same
+// name in src/json/golden. Any change to what the parser emits shows up
+// here. When the change is intended, regenerate the goldens with
+//
+//     UPDATE_GOLDEN=1 cargo test golden
+//
+// and review their diff like any other.
+
+const GOLDEN_DIR: &str = "src/json/golden";
+
+#[derive(Clone, Copy)]
+enum Fixture {
+    // A getBlock response.
+    Block,
+    // A getTransaction response.
+    Transaction,
+}
+
+const FIXTURES: &[(&str, Fixture)] = &[
+    // Legacy message.
+    ("swap", Fixture::Transaction),
+    // v0 message with loaded addresses.
+    ("transaction", Fixture::Transaction),
+    // A CLMM swap that failed on slippage.
+    ("failed_transaction", Fixture::Transaction),
+    // Mostly votes, one swap.
+    ("vote_block", Fixture::Block),
+    // Token transfers, v0 and legacy.
+    ("token_block", Fixture::Block),
+    ("empty_block", Fixture::Block),
+];
+
+fn parse_fixture(name: &str, fixture: Fixture) -> Value {
+    let path = format!("src/json/{}.json", name);
+    let parsed = match fixture {
+        Fixture::Block => {
+            let raw: RpcBlockResponse = load_from_json(&path).unwrap();
+            serde_json::to_value(parse_block(raw.result).unwrap())
+        }
+        Fixture::Transaction => {
+            let raw: RpcResponse = load_from_json(&path).unwrap();
+            serde_json::to_value(parse_transaction_result(&raw.result, &ParseOptions::default()).unwrap().0)
+        }
+    };
+    parsed.unwrap()
+}
+
+// Compares `actual` with the golden file `name`, or rewrites the file when
+// UPDATE_GOLDEN is set.
+fn assert_golden(name: &str, actual: &Value) {
+    let path = Path::new(GOLDEN_DIR).join(format!("{}.json", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(GOLDEN_DIR).unwrap();
+        fs::write(&path, serde_json::to_string_pretty(actual).unwrap() + "\n").unwrap();
+        return;
+    }
+    let contents = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("no golden {} ({}); run with UPDATE_GOLDEN=1 to create it", path.display(), e));
+    let expected: Value = serde_json::from_str(&contents).unwrap();
+    if let Some(difference) = first_difference(&expected, actual, "$") {
+        panic!("{} doesn't match {}: {}; run with UPDATE_GOLDEN=1 if the change is intended", name, path.display(), difference);
+    }
+}
+
+// Where the two first disagree, as a JSON path; None when they're equal.
+fn first_difference(expected: &Value, actual: &Value, path: &str) -> Option<String> {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            let missing = expected.keys().find(|key| !actual.contains_key(*key));
+            if let Some(key) = missing {
+                return Some(format!("{}.{} is missing", path, key));
+            }
+            let added = actual.keys().find(|key| !expected.contains_key(*key));
+            if let Some(key) = added {
+                return Some(format!("{}.{} is new", path, key));
+            }
+            expected.iter().find_map(|(key, value)| first_difference(value, &actual[key], &format!("{}.{}", path, key)))
+        }
+        (Value::Array(expected), Value::Array(actual)) if expected.len() == actual.len() => expected
+            .iter()
+            .zip(actual)
+            .enumerate()
+            .find_map(|(i, (expected, actual))| first_difference(expected, actual, &format!("{}[{}]", path, i))),
+        (Value::Array(expected), Value::Array(actual)) => {
+            Some(format!("{} has {} elements, expected {}", path, actual.len(), expected.len()))
+        }
+        _ if expected == actual => None,
+        _ => Some(format!("{} is {}, expected {}", path, actual, expected)),
+    }
+}
+
+#[test]
+fn parsed_fixtures_match_their_goldens() {
+    for (name, fixture) in FIXTURES {
+        assert_golden(name, &parse_fixture(name, *fixture));
+    }
+}
+
+#[test]
+fn differences_are_reported_by_path() {
+    let expected = serde_json::json!({"fee": 5000, "accounts": [{"writable": true}], "logs": ["a"]});
+    assert_eq!(first_difference(&expected, &expected, "$"), None);
+    let mut actual = expected.clone();
+    actual["accounts"][0]["writable"] = false.into();
+    assert_eq!(first_difference(&expected, &actual, "$").unwrap(), "$.accounts[0].writable is false, expected true");
+    actual = expected.clone();
+    actual["logs"] = serde_json::json!(["a", "b"]);
+    assert_eq!(first_difference(&expected, &actual, "$").unwrap(), "$.logs has 2 elements, expected 1");
+    actual.as_object_mut().unwrap().remove("fee");
+    assert_eq!(first_difference(&expected, &actual, "$").unwrap(), "$.fee is missing");
+}
@@ -0,0 +1,857 @@
+use crate::shutdown;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::process::{self, Command, Stdio};
+use std::thread;
+
+// ==========================================
+// GRAPHQL QUERIES
+// ==========================================
+// Read-only GraphQL over what the SQLite and Postgres sinks stored. The root
+// fields are lists:
+//
+//   blocks         slot blockhash blockHeight blockTime parentSlot
+//                  previousBlockhash transactions
+//   transactions   signature slot blockhash feePayer isSuccess fee baseFee
+//                  priorityFee computeUnitsConsumed computeUnitLimit
+//                  unitPriceMicroLamports instructions transfers
+//   instructions   signature slot parentIndex index programId accounts data
+//   transfers      signature slot kind source destination sourceOwner
+//                  destinationOwner mint amount instructionIndex innerIndex
+//
+// Every list takes slotFrom / slotTo (inclusive); the transaction-level ones
+// also signature, blockhash, isSuccess, program (for transactions and
+// transfers: the transaction calls it, at any depth) and account, and
+// transfers mint and kind. Root fields take a limit (DEFAULT_LIMIT, at most
+// MAX_LIMIT) and come back in slot order. A nested list is loaded with one
+// query per KEY_BATCH of its parents.
+//
+// Only queries are understood: fields, aliases, and arguments given as
+// literals or $variables. Fragments, directives and introspection are
+// rejected rather than half-supported. As with the sinks, the SQL goes
+// through the sqlite3 / psql binaries rather than a driver, on stdin so its
+// length isn't bounded by the argument list.
+
+pub const DEFAULT_LIMIT: u64 = 100;
+pub const MAX_LIMIT: u64 = 1000;
+
+// Parent keys per nested-list query, keeping each IN (...) list bounded.
+const KEY_BATCH: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Database {
+    // Path of a file written by the SQLite sink.
+    Sqlite(String),
+    // Connection URL of a database written by the Postgres sink.
+    Postgres(String),
+}
+
+impl Database {
+    fn program(&self) -> &'static str {
+        match self {
+            Database::Sqlite(_) => "sqlite3",
+            Database::Postgres(_) => "psql",
+        }
+    }
+
+    // Rows as JSON objects keyed by column name.
+    fn query(&self, sql: &str) -> Result<Vec<Map<String, Value>>, QueryError> {
+        let (mut command, script) = match self {
+            Database::Sqlite(path) => {
+                let mut command = Command::new("sqlite3");
+                command.args(["-readonly", "-bail", "-json", path]);
+                (command, format!("{};\n", sql))
+            }
+            Database::Postgres(url) => {
+                let mut command = Command::new("psql");
+                command.args(["-X", "-q", "-A", "-t", "-v", "ON_ERROR_STOP=1", url]);
+                (command, format!("SELECT COALESCE(json_agg(q), '[]') FROM ({}) q;\n", sql))
+            }
+        };
+        let output = run_with_input(&mut command, script)
+            .map_err(|e| QueryError::Database(format!("failed to run {}: {}", self.program(), e)))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(QueryError::Database(format!("{} failed: {}", self.program(), stderr.trim())));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        // sqlite3 prints nothing at all for an empty result.
+        if stdout.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        serde_json::from_str(&stdout).map_err(|e| QueryError::Database(format!("unreadable {} output: {}", self.program(), e)))
+    }
+
+    fn instruction_index(&self) -> &'static str {
+        match self {
+            Database::Sqlite(_) => "i.position",
+            Database::Postgres(_) => "i.index",
+        }
+    }
+}
+
+// Written from a thread so a large result can't block the child on a full
+// stdout pipe while it still has input to read.
+fn run_with_input(command: &mut Command, input: String) -> io::Result<process::Output> {
+    let mut child = shutdown::own_process_group(command).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    // A child that exits early closes the pipe; its status says why.
+    let _ = writer.join();
+    Ok(output)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    // The query doesn't parse or asks for something the schema lacks.
+    Invalid(String),
+    // The database couldn't answer it.
+    Database(String),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::Invalid(message) | QueryError::Database(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+fn invalid<T>(message: impl Into<String>) -> Result<T, QueryError> {
+    Err(QueryError::Invalid(message.into()))
+}
+
+// ==========================================
+// RESULTS
+// ==========================================
+// Objects keep their keys in selection order, as GraphQL asks; a
+// serde_json::Map would sort them.
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Object(pub Vec<(String, Output)>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    Value(Value),
+    List(Vec<Object>),
+}
+
+impl Serialize for Object {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for Output {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Output::Value(value) => value.serialize(serializer),
+            Output::List(objects) => objects.serialize(serializer),
+        }
+    }
+}
+
+// ==========================================
+// SCHEMA
+// ==========================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Table {
+    Blocks,
+    Transactions,
+    Instructions,
+    Transfers,
+}
+
+// How a column comes back: SQLite has no booleans, and stores instruction
+// accounts as JSON text where Postgres has an array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Scalar,
+    Bool,
+    Json,
+}
+
+struct Column {
+    name: &'static str,
+    sql: &'static str,
+    kind: Kind,
+}
+
+const fn column(name: &'static str, sql: &'static str) -> Column {
+    Column { name, sql, kind: Kind::Scalar }
+}
+
+const SLOT: Column = column("slot", "b.slot");
+
+const BLOCK_COLUMNS: &[Column] = &[
+    SLOT,
+    column("blockhash", "b.blockhash"),
+    column("blockHeight", "b.block_height"),
+    column("blockTime", "b.block_time"),
+    column("parentSlot", "b.parent_slot"),
+    column("previousBlockhash", "b.previous_blockhash"),
+];
+
+const TRANSACTION_COLUMNS: &[Column] = &[
+    column("signature", "t.signature"),
+    SLOT,
+    column("indexInBlock", "t.index_in_block"),
+    column("blockhash", "t.blockhash"),
+    column("feePayer", "t.fee_payer"),
+    Column { name: "isSuccess", sql: "t.is_success", kind: Kind::Bool },
+    column("fee", "t.fee"),
+    column("baseFee", "t.base_fee"),
+    column("priorityFee", "t.priority_fee"),
+    column("computeUnitsConsumed", "t.compute_units_consumed"),
+    column("computeUnitLimit", "t.compute_unit_limit"),
+    column("unitPriceMicroLamports", "t.unit_price_micro_lamports"),
+];
+
+// `index` is filled in per database (Database::instruction_index).
+const INSTRUCTION_COLUMNS: &[Column] = &[
+    column("signature", "i.signature"),
+    SLOT,
+    column("parentIndex", "i.parent_index"),
+    column("index", ""),
+    column("programId", "i.program_id"),
+    Column { name: "accounts", sql: "i.accounts", kind: Kind::Json },
+    column("data", "i.data"),
+];
+
+const TRANSFER_COLUMNS: &[Column] = &[
+    column("signature", "x.signature"),
+    SLOT,
+    column("kind", "x.kind"),
+    column("source", "x.source"),
+    column("destination", "x.destination"),
+    column("sourceOwner", "x.source_owner"),
+    column("destinationOwner", "x.destination_owner"),
+    column("mint", "x.mint"),
+    column("amount", "x.amount"),
+    column("instructionIndex", "x.instruction_index"),
+    column("innerIndex", "x.inner_index"),
+];
+
+impl Table {
+    fn root(name: &str) -> Option<Table> {
+        match name {
+            "blocks" => Some(Table::Blocks),
+            "transactions" => Some(Table::Transactions),
+            "instructions" => Some(Table::Instructions),
+            "transfers" => Some(Table::Transfers),
+            _ => None,
+        }
+    }
+
+    fn type_name(self) -> &'static str {
+        match self {
+            Table::Blocks => "Block",
+            Table::Transactions => "Transaction",
+            Table::Instructions => "Instruction",
+            Table::Transfers => "Transfer",
+        }
+    }
+
+    fn columns(self) -> &'static [Column] {
+        match self {
+            Table::Blocks => BLOCK_COLUMNS,
+            Table::Transactions => TRANSACTION_COLUMNS,
+            Table::Instructions => INSTRUCTION_COLUMNS,
+            Table::Transfers => TRANSFER_COLUMNS,
+        }
+    }
+
+    fn from(self) -> &'static str {
+        match self {
+            Table::Blocks => "blocks b",
+            Table::Transactions => "transactions t LEFT JOIN blocks b ON b.blockhash = t.blockhash",
+            Table::Instructions => {
+                "instructions i JOIN transactions t ON t.signature = i.signature LEFT JOIN blocks b ON b.blockhash = t.blockhash"
+            }
+            Table::Transfers => {
+                "transfers x JOIN transactions t ON t.signature = x.signature LEFT JOIN blocks b ON b.blockhash = t.blockhash"
+            }
+        }
+    }
+
+    // Instructions in execution order: each top-level one, then its CPIs.
+    fn order(self, db: &Database) -> String {
+        match self {
+            Table::Blocks => "b.slot".to_string(),
+            Table::Transactions => "b.slot, t.signature".to_string(),
+            Table::Instructions => {
+                let index = db.instruction_index();
+                format!("b.slot, i.signature, COALESCE(i.parent_index, {index}), i.parent_index IS NOT NULL, {index}")
+            }
+            Table::Transfers => "b.slot, x.signature, x.position".to_string(),
+        }
+    }
+
+    // What nested lists hang off: the column they join on here, and there.
+    fn key(self) -> &'static str {
+        match self {
+            Table::Blocks => "b.blockhash",
+            _ => "t.signature",
+        }
+    }
+
+    fn child(self, name: &str) -> Option<(Table, &'static str)> {
+        match (self, name) {
+            (Table::Blocks, "transactions") => Some((Table::Transactions, "t.blockhash")),
+            (Table::Transactions, "instructions") => Some((Table::Instructions, "i.signature")),
+            (Table::Transactions, "transfers") => Some((Table::Transfers, "x.signature")),
+            _ => None,
+        }
+    }
+
+    fn filter(self, argument: &str, value: &Value, db: &Database) -> Result<String, QueryError> {
+        let condition = match (self, argument) {
+            (_, "slotFrom") => format!("b.slot >= {}", integer(argument, value)?),
+            (_, "slotTo") => format!("b.slot <= {}", integer(argument, value)?),
+            (Table::Blocks, "blockhash") => format!("b.blockhash = {}", string(argument, value)?),
+            (Table::Blocks, _) => return invalid(format!("unknown argument '{}' on blocks", argument)),
+            (_, "signature") => format!("t.signature = {}", string(argument, value)?),
+            (_, "blockhash") => format!("t.blockhash = {}", string(argument, value)?),
+            (_, "isSuccess") => match value {
+                Value::Bool(success) => format!("t.is_success = {}", if *success { "TRUE" } else { "FALSE" }),
+                _ => return invalid("isSuccess must be a boolean"),
+            },
+            (Table::Instructions, "program") => format!("i.program_id = {}", string(argument, value)?),
+            (_, "program") => format!(
+                "EXISTS (SELECT 1 FROM instructions p WHERE p.signature = t.signature AND p.program_id = {})",
+                string(argument, value)?
+            ),
+            (Table::Transactions, "account") => format!(
+                "EXISTS (SELECT 1 FROM transaction_accounts a WHERE a.signature = t.signature AND a.account = {})",
+                string(argument, value)?
+            ),
+            (Table::Instructions, "account") => match db {
+                Database::Sqlite(_) => {
+                    format!("EXISTS (SELECT 1 FROM json_each(i.accounts) WHERE value = {})", string(argument, value)?)
+                }
+                Database::Postgres(_) => format!("{} = ANY(i.accounts)", string(argument, value)?),
+            },
+            (Table::Transfers, "account") => {
+                format!("{} IN (x.source, x.destination, x.source_owner, x.destination_owner)", string(argument, value)?)
+            }
+            (Table::Transfers, "mint") => format!("x.mint = {}", string(argument, value)?),
+            (Table::Transfers, "kind") => format!("x.kind = {}", string(argument, value)?),
+            _ => return invalid(format!("unknown argument '{}' on {}", argument, self.type_name())),
+        };
+        Ok(condition)
+    }
+}
+
+fn integer(argument: &str, value: &Value) -> Result<u64, QueryError> {
+    match value.as_u64() {
+        Some(n) => Ok(n),
+        None => invalid(format!("{} must be a non-negative integer", argument)),
+    }
+}
+
+fn string(argument: &str, value: &Value) -> Result<String, QueryError> {
+    match value {
+        Value::String(s) => Ok(text(s)),
+        _ => invalid(format!("{} must be a string", argument)),
+    }
+}
+
+// SQL string literal, as in the sinks.
+fn text(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// ==========================================
+// EXECUTION
+// ==========================================
+
+pub struct GraphQl {
+    db: Database,
+}
+
+impl GraphQl {
+    pub fn new(db: Database) -> Self {
+        GraphQl { db }
+    }
+
+    // The `data` of a response. `variables` supplies $variables, over the
+    // query's own defaults.
+    pub fn execute(&self, query: &str, variables: &Map<String, Value>) -> Result<Object, QueryError> {
+        let operation = Parser::new(query)?.document()?;
+        let mut variables_in_scope = operation.defaults;
+        variables_in_scope.extend(variables.iter().map(|(name, value)| (name.clone(), value.clone())));
+
+        let mut data = Object::default();
+        for field in &operation.selection {
+            let output = match field.name.as_str() {
+                "__typename" => Output::Value(Value::String("Query".to_string())),
+                name => match Table::root(name) {
+                    Some(table) => {
+                        let rows = self.resolve(table, field, None, &variables_in_scope)?;
+                        Output::List(rows.into_iter().map(|(_, object)| object).collect())
+                    }
+                    None => return invalid(format!("unknown field '{}' on Query", name)),
+                },
+            };
+            data.0.push((field.response_key().to_string(), output));
+        }
+        Ok(data)
+    }
+
+    // `parent` restricts the rows to those whose `link` column is one of
+    // the keys; each object comes back with the key it belongs to.
+    fn resolve(
+        &self,
+        table: Table,
+        field: &Field,
+        parent: Option<(&str, &[String])>,
+        variables: &Map<String, Value>,
+    ) -> Result<Vec<(Option<String>, Object)>, QueryError> {
+        if field.selection.is_empty() {
+            return invalid(format!("{} needs a selection of fields", field.name));
+        }
+
+        let mut conditions = Vec::new();
+        let mut limit = parent.is_none().then_some(DEFAULT_LIMIT);
+        for (argument, input) in &field.arguments {
+            let value = input.value(variables)?;
+            if argument == "limit" {
+                if parent.is_some() {
+                    return invalid("limit only applies to root fields");
+                }
+                match integer(argument, &value)? {
+                    n if n > MAX_LIMIT => return invalid(format!("limit is at most {}", MAX_LIMIT)),
+                    n => limit = Some(n),
+                }
+            } else if !value.is_null() {
+                conditions.push(table.filter(argument, &value, &self.db)?);
+            }
+        }
+        if parent.is_some_and(|(_, keys)| keys.is_empty()) {
+            return Ok(Vec::new());
+        }
+
+        // Columns are selected as c<position in the selection>.
+        let mut select = vec![format!("{} AS k", table.key())];
+        if let Some((link, _)) = parent {
+            select.push(format!("{} AS p", link));
+        }
+        let mut children = Vec::new();
+        for (position, selected) in field.selection.iter().enumerate() {
+            if selected.name == "__typename" {
+                continue;
+            }
+            if let Some(column) = table.columns().iter().find(|column| column.name == selected.name) {
+                let sql = if column.name == "index" { self.db.instruction_index() } else { column.sql };
+                select.push(format!("{} AS c{}", sql, position));
+            } else if let Some((child, link)) = table.child(&selected.name) {
+                children.push((position, child, link));
+            } else {
+                return invalid(format!("unknown field '{}' on {}", selected.name, table.type_name()));
+            }
+        }
+
+        // A parent's rows all come from one batch, so they stay in order.
+        let batches: Vec<Option<(&str, &[String])>> = match parent {
+            Some((link, keys)) => keys.chunks(KEY_BATCH).map(|batch| Some((link, batch))).collect(),
+            None => vec![None],
+        };
+        let mut rows = Vec::new();
+        for batch in batches {
+            let mut conditions = conditions.clone();
+            if let Some((link, keys)) = batch {
+                let keys: Vec<String> = keys.iter().map(|key| text(key)).collect();
+                conditions.push(format!("{} IN ({})", link, keys.join(", ")));
+            }
+            let mut sql = format!("SELECT {} FROM {}", select.join(", "), table.from());
+            if !conditions.is_empty() {
+                sql += &format!(" WHERE {}", conditions.join(" AND "));
+            }
+            sql += &format!(" ORDER BY {}", table.order(&self.db));
+            if let Some(limit) = limit {
+                sql += &format!(" LIMIT {}", limit);
+            }
+            rows.extend(self.db.query(&sql)?);
+        }
+
+        let keys: Vec<String> = rows.iter().filter_map(|row| row.get("k").and_then(Value::as_str).map(str::to_string)).collect();
+        let mut nested: HashMap<usize, HashMap<String, Vec<Object>>> = HashMap::new();
+        for (position, child, link) in children {
+            let mut by_parent: HashMap<String, Vec<Object>> = HashMap::new();
+            for (key, object) in self.resolve(child, &field.selection[position], Some((link, &keys)), variables)? {
+                by_parent.entry(key.unwrap_or_default()).or_default().push(object);
+            }
+            nested.insert(position, by_parent);
+        }
+
+        let mut objects = Vec::with_capacity(rows.len());
+        for mut row in rows {
+            let key = row.get("k").and_then(Value::as_str).unwrap_or_default().to_string();
+            let mut object = Object::default();
+            for (position, selected) in field.selection.iter().enumerate() {
+                let output = if selected.name == "__typename" {
+                    Output::Value(Value::String(table.type_name().to_string()))
+                } else if let Some(by_parent) = nested.get_mut(&position) {
+                    Output::List(by_parent.remove(&key).unwrap_or_default())
+                } else {
+                    let value = row.remove(&format!("c{}", position)).unwrap_or(Value::Null);
+                    let kind = table.columns().iter().find(|column| column.name == selected.name).map_or(Kind::Scalar, |column| column.kind);
+                    Output::Value(convert(value, kind))
+                };
+                object.0.push((selected.response_key().to_string(), output));
+            }
+            let parent_key = row.get("p").and_then(Value::as_str).map(str::to_string);
+            objects.push((parent_key, object));
+        }
+        Ok(objects)
+    }
+}
+
+fn convert(value: Value, kind: Kind) -> Value {
+    match (kind, value) {
+        (Kind::Bool, Value::Number(n)) => Value::Bool(n.as_i64() != Some(0)),
+        (Kind::Json, Value::String(s)) => serde_json::from_str(&s).unwrap_or(Value::String(s)),
+        (_, value) => value,
+    }
+}
+
+// ==========================================
+// QUERY PARSING
+// ==========================================
+
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    alias: Option<String>,
+    name: String,
+    arguments: Vec<(String, Input)>,
+    selection: Vec<Field>,
+}
+
+impl Field {
+    fn response_key(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Input {
+    Literal(Value),
+    Variable(String),
+}
+
+impl Input {
+    fn value(&self, variables: &Map<String, Value>) -> Result<Value, QueryError> {
+        match self {
+            Input::Literal(value) => Ok(value.clone()),
+            Input::Variable(name) => match variables.get(name) {
+                Some(value) => Ok(value.clone()),
+                None => invalid(format!("variable ${} isn't defined", name)),
+            },
+        }
+    }
+}
+
+struct Operation {
+    defaults: Map<String, Value>,
+    selection: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Punct(char),
+    Spread,
+    Name(String),
+    Value(Value),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Self, QueryError> {
+        Ok(Parser { tokens: tokenize(source)?, position: 0 })
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), QueryError> {
+        if self.eat(c) { Ok(()) } else { invalid(format!("expected '{}'", c)) }
+    }
+
+    fn name(&mut self) -> Result<String, QueryError> {
+        match self.next() {
+            Some(Token::Name(name)) => Ok(name),
+            _ => invalid("expected a name"),
+        }
+    }
+
+    fn document(mut self) -> Result<Operation, QueryError> {
+        let mut defaults = Map::new();
+        match self.peek() {
+            Some(Token::Name(keyword)) if keyword == "query" => {
+                self.position += 1;
+                if let Some(Token::Name(_)) = self.peek() {
+                    self.position += 1;
+                }
+                if self.eat('(') {
+                    while !self.eat(')') {
+                        self.expect('$')?;
+                        let name = self.name()?;
+                        self.expect(':')?;
+                        self.skip_type()?;
+                        if self.eat('=') {
+                            defaults.insert(name, self.literal()?);
+                        }
+                    }
+                }
+            }
+            Some(Token::Name(keyword)) if keyword == "mutation" || keyword == "subscription" => {
+                return invalid(format!("{} operations aren't supported", keyword));
+            }
+            Some(Token::Name(keyword)) if keyword == "fragment" => return invalid("fragments aren't supported"),
+            _ => {}
+        }
+        let selection = self.selection_set()?;
+        if self.peek().is_some() {
+            return invalid("only one operation per document is supported");
+        }
+        Ok(Operation { defaults, selection })
+    }
+
+    // Variable types aren't checked; the arguments check their values.
+    fn skip_type(&mut self) -> Result<(), QueryError> {
+        if self.eat('[') {
+            self.skip_type()?;
+            self.expect(']')?;
+        } else {
+            self.name()?;
+        }
+        self.eat('!');
+        Ok(())
+    }
+
+    fn selection_set(&mut self) -> Result<Vec<Field>, QueryError> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        while !self.eat('}') {
+            if self.peek() == Some(&Token::Spread) {
+                return invalid("fragments aren't supported");
+            }
+            fields.push(self.field()?);
+        }
+        Ok(fields)
+    }
+
+    fn field(&mut self) -> Result<Field, QueryError> {
+        let mut name = self.name()?;
+        let mut alias = None;
+        if self.eat(':') {
+            alias = Some(name);
+            name = self.name()?;
+        }
+        if name.starts_with("__") && name != "__typename" {
+            return invalid("introspection isn't supported");
+        }
+        let mut arguments = Vec::new();
+        if self.eat('(') {
+            while !self.eat(')') {
+                let argument = self.name()?;
+                self.expect(':')?;
+                let input = if self.eat('$') { Input::Variable(self.name()?) } else { Input::Literal(self.literal()?) };
+                arguments.push((argument, input));
+            }
+        }
+        if self.peek() == Some(&Token::Punct('@')) {
+            return invalid("directives aren't supported");
+        }
+        let selection = if self.peek() == Some(&Token::Punct('{')) { self.selection_set()? } else { Vec::new() };
+        Ok(Field { alias, name, arguments, selection })
+    }
+
+    // Enum values come through as strings.
+    fn literal(&mut self) -> Result<Value, QueryError> {
+        match self.next() {
+            Some(Token::Value(value)) => Ok(value),
+            Some(Token::Name(name)) => Ok(match name.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                _ => Value::String(name),
+            }),
+            Some(Token::Punct('[')) => {
+                let mut items = Vec::new();
+                while !self.eat(']') {
+                    items.push(self.literal()?);
+                }
+                Ok(Value::Array(items))
+            }
+            Some(Token::Punct('$')) => invalid("variables can't appear inside lists or defaults"),
+            Some(Token::Punct('{')) => invalid("input objects aren't supported"),
+            _ => invalid("expected a value"),
+        }
+    }
+}
+
+// Commas are insignificant in GraphQL, like whitespace. Numbers and strings
+// share JSON's syntax, so serde_json reads them.
+fn tokenize(source: &str) -> Result<Vec<Token>, QueryError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        match c {
+            b' ' | b'\t' | b'\n' | b'\r' | b',' => i += 1,
+            // Byte order mark.
+            0xef if source[i..].starts_with('\u{feff}') => i += 3,
+            b'#' => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'.' if source[i..].starts_with("...") => {
+                tokens.push(Token::Spread);
+                i += 3;
+            }
+            b'{' | b'}' | b'(' | b')' | b'[' | b']' | b':' | b'$' | b'!' | b'=' | b'@' => {
+                tokens.push(Token::Punct(c as char));
+                i += 1;
+            }
+            b'"' => {
+                if source[i..].starts_with("\"\"\"") {
+                    return invalid("block strings aren't supported");
+                }
+                let start = i;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                let literal = source.get(start..=i).ok_or_else(|| QueryError::Invalid("unterminated string".to_string()))?;
+                let value: String = serde_json::from_str(literal).map_err(|e| QueryError::Invalid(format!("invalid string: {}", e)))?;
+                tokens.push(Token::Value(Value::String(value)));
+                i += 1;
+            }
+            b'-' | b'0'..=b'9' => {
+                let start = i;
+                while i < bytes.len() && matches!(bytes[i], b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') {
+                    i += 1;
+                }
+                let value: Value = serde_json::from_str(&source[start..i])
+                    .map_err(|_| QueryError::Invalid(format!("invalid number '{}'", &source[start..i])))?;
+                tokens.push(Token::Value(value));
+            }
+            b'_' | b'a'..=b'z' | b'A'..=b'Z' => {
+                let start = i;
+                while i < bytes.len() && (bytes[i] == b'_' || bytes[i].is_ascii_alphanumeric()) {
+                    i += 1;
+                }
+                tokens.push(Token::Name(source[start..i].to_string()));
+            }
+            _ => {
+                let unexpected = source[i..].chars().next().unwrap_or_default();
+                return invalid(format!("unexpected character '{}'", unexpected));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::sink::SqliteSink;
+    use serde_json::json;
+
+    #[test]
+    fn answers_queries_from_a_sqlite_database() {
+        let mut block = fixtures::block().clone();
+        block.slot = 42;
+        let path = std::env::temp_dir().join(format!("phase1-graphql-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut sink = SqliteSink::open(path).unwrap();
+        sink.write_block(&block).unwrap();
+        sink.finish().unwrap();
+
+        let graphql = GraphQl::new(Database::Sqlite(path.to_string()));
+        let query = r#"
+            query Recent($from: Int = 40, $program: String) {
+                blocks(slotFrom: $from, slotTo: 42) { slot, txs: transactions(program: $program) { signature isSuccess instructions { programId accounts } } }
+                transfers(slotFrom: 42, limit: 3) { __typename kind amount }
+            }
+        "#;
+        let program = block.transactions[0].instructions[0].program_id;
+        let variables = json!({ "program": program.to_string() }).as_object().unwrap().clone();
+        let data = serde_json::to_value(graphql.execute(query, &variables).unwrap()).unwrap();
+
+        let blocks = data["blocks"].as_array().unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0]["slot"], 42);
+        let calling = block.transactions.iter().filter(|tx| tx.instructions.iter().any(|ix| ix.program_id == program)).count();
+        let txs = blocks[0]["txs"].as_array().unwrap();
+        assert!(calling > 0 && txs.len() <= calling, "{} of {}", txs.len(), calling);
+        assert!(txs[0]["isSuccess"].is_boolean());
+        assert!(txs[0]["instructions"][0]["accounts"].is_array());
+        let transfers = data["transfers"].as_array().unwrap();
+        assert!(transfers.len() <= 3);
+        assert!(transfers.iter().all(|transfer| transfer["__typename"] == "Transfer"));
+        // Keys follow the selection, not the alphabet.
+        let text = serde_json::to_string(&graphql.execute("{ blocks { slot blockhash } }", &Map::new()).unwrap()).unwrap();
+        assert!(text.starts_with(r#"{"blocks":[{"slot":42,"blockhash":"#), "{}", text);
+
+        // More parents than fit in one IN (...) list.
+        assert!(block.transactions.len() > KEY_BATCH);
+        let data = serde_json::to_value(graphql.execute("{ blocks { transactions { signature instructions { index } } } }", &Map::new()).unwrap()).unwrap();
+        let txs = data["blocks"][0]["transactions"].as_array().unwrap();
+        assert_eq!(txs.len(), block.transactions.len());
+        for tx in &block.transactions {
+            let found = txs.iter().find(|found| found["signature"] == tx.signature.as_str()).unwrap();
+            let instructions = tx.instructions.len() + tx.inner_instructions.iter().map(|inner| inner.instructions.len()).sum::<usize>();
+            assert_eq!(found["instructions"].as_array().unwrap().len(), instructions);
+        }
+
+        for (query, error) in [
+            ("{ blocks { nope } }", "unknown field 'nope' on Block"),
+            ("{ blocks(program: \"x\") { slot } }", "unknown argument 'program' on blocks"),
+            ("{ blocks { transactions(limit: 1) { fee } } }", "limit only applies to root fields"),
+            ("{ transactions(limit: 5000) { fee } }", "limit is at most 1000"),
+            ("{ blocks { ...F } }", "fragments aren't supported"),
+            ("mutation { blocks { slot } }", "mutation operations aren't supported"),
+            ("{ transactions(account: $who) { fee } }", "variable $who isn't defined"),
+        ] {
+            assert_eq!(graphql.execute(query, &Map::new()), Err(QueryError::Invalid(error.to_string())), "{}", query);
+        }
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+    }
+}
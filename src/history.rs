@@ -0,0 +1,182 @@
+use crate::parser::{parse_transaction_result, ParseOptions};
+use crate::pubkey::Pubkey;
+use crate::rpc::{RpcClient, SignatureInfo};
+use crate::types::{ParseReport, ParsedTransaction, RpcResult};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::thread;
+
+// ==========================================
+// ADDRESS HISTORY
+// ==========================================
+// Walks the transactions of one address, newest first: a page of
+// getSignaturesForAddress at a time, then getTransaction for each signature,
+// parsed with the given options. `cursor` is the last signature handed out
+// (or reported as an error), so a later run can carry on from it with
+// `with_before`; `with_until` stops at a signature an earlier run already
+// covered. With a concurrency above one, the transactions of a page are
+// fetched that many at a time; they still come out in order.
+
+// The most getSignaturesForAddress returns per request.
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+pub struct HistoryTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub transaction: ParsedTransaction,
+    pub report: ParseReport,
+}
+
+pub struct AddressHistory {
+    client: RpcClient,
+    address: Pubkey,
+    options: ParseOptions,
+    before: Option<String>,
+    until: Option<String>,
+    page_size: usize,
+    concurrency: usize,
+    // Transactions left to hand out, None for no limit.
+    remaining: Option<usize>,
+    // Signatures of the current page not fetched yet.
+    page: VecDeque<SignatureInfo>,
+    fetched: VecDeque<(SignatureInfo, Result<Option<RpcResult>, String>)>,
+    cursor: Option<String>,
+    last_page: bool,
+}
+
+impl AddressHistory {
+    pub fn new(client: RpcClient, address: Pubkey) -> Self {
+        AddressHistory {
+            client,
+            address,
+            options: ParseOptions::default(),
+            before: None,
+            until: None,
+            page_size: MAX_PAGE_SIZE,
+            concurrency: 1,
+            remaining: None,
+            page: VecDeque::new(),
+            fetched: VecDeque::new(),
+            cursor: None,
+            last_page: false,
+        }
+    }
+
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // Start with the transaction before this signature.
+    pub fn with_before(mut self, signature: String) -> Self {
+        self.cursor = Some(signature.clone());
+        self.before = Some(signature);
+        self
+    }
+
+    // Stop at this signature, not including it.
+    pub fn with_until(mut self, signature: String) -> Self {
+        self.until = Some(signature);
+        self
+    }
+
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    // Stop after this many transactions.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.remaining = Some(limit);
+        self
+    }
+
+    // The last signature handed out, or the one given to `with_before`.
+    pub fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+
+    fn next_page(&mut self) -> Result<(), Box<dyn Error>> {
+        let limit = self.remaining.map_or(self.page_size, |remaining| remaining.min(self.page_size));
+        let page = self.client.get_signatures_for_address(
+            &self.address.to_string(),
+            self.before.as_deref(),
+            self.until.as_deref(),
+            limit,
+        )?;
+        self.last_page = page.len() < limit;
+        if let Some(oldest) = page.last() {
+            self.before = Some(oldest.signature.clone());
+        }
+        self.page = page.into();
+        Ok(())
+    }
+
+    // The next `concurrency` transactions of the page.
+    fn fetch_batch(&mut self) {
+        let batch: Vec<SignatureInfo> = self.page.drain(..self.concurrency.min(self.page.len())).collect();
+        let client = &self.client;
+        // Errors are strings here to cross the thread boundary.
+        let fetch = |info: &SignatureInfo| client.fetch_transaction(&info.signature).map_err(|e| e.to_string());
+        let results: Vec<_> = if batch.len() == 1 {
+            vec![fetch(&batch[0])]
+        } else {
+            thread::scope(|scope| {
+                let handles: Vec<_> = batch.iter().map(|info| scope.spawn(move || fetch(info))).collect();
+                handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+            })
+        };
+        self.fetched.extend(batch.into_iter().zip(results));
+    }
+}
+
+impl Iterator for AddressHistory {
+    type Item = Result<HistoryTransaction, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        if self.fetched.is_empty() {
+            if self.page.is_empty() {
+                if self.last_page {
+                    return None;
+                }
+                if let Err(e) = self.next_page() {
+                    // Retrying the same page forever wouldn't help.
+                    self.last_page = true;
+                    return Some(Err(e));
+                }
+                if self.page.is_empty() {
+                    return None;
+                }
+            }
+            self.fetch_batch();
+        }
+
+        let (info, fetched) = self.fetched.pop_front()?;
+        self.cursor = Some(info.signature.clone());
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= 1;
+        }
+        let parsed = match fetched {
+            Ok(Some(raw)) => parse_transaction_result(&raw, &self.options)
+                .map_err(|e| format!("transaction {}: {}", info.signature, e).into()),
+            Ok(None) => Err(format!("transaction {} not found", info.signature).into()),
+            Err(e) => Err(format!("transaction {}: {}", info.signature, e).into()),
+        };
+        Some(parsed.map(|(transaction, report)| HistoryTransaction {
+            signature: info.signature,
+            slot: info.slot,
+            block_time: info.block_time,
+            transaction,
+            report,
+        }))
+    }
+}
@@ -0,0 +1,82 @@
+use crate::shutdown;
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+// ==========================================
+// MINIMAL HTTP CLIENT
+// ==========================================
+// Requests go through the system `curl` so https endpoints work without
+// pulling a TLS stack into the crate.
+
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+pub fn post(url: &str, content_type: &str, body: &[u8]) -> Result<HttpResponse, Box<dyn Error>> {
+    post_command(&mut Command::new("curl"), url, content_type, body)
+}
+
+// Gives up connecting after `connect` and on the whole request after `total`.
+pub fn post_with_timeouts(
+    url: &str,
+    content_type: &str,
+    body: &[u8],
+    connect: Duration,
+    total: Duration,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let mut command = Command::new("curl");
+    command
+        .arg("--connect-timeout")
+        .arg(connect.as_secs_f64().to_string())
+        .arg("--max-time")
+        .arg(total.as_secs_f64().to_string());
+    post_command(&mut command, url, content_type, body)
+}
+
+fn post_command(command: &mut Command, url: &str, content_type: &str, body: &[u8]) -> Result<HttpResponse, Box<dyn Error>> {
+    let mut child = shutdown::own_process_group(command)
+        .args(["-sS", "-X", "POST", "--data-binary", "@-", "-w", "\n%{http_code}"])
+        .arg("-H")
+        .arg(format!("Content-Type: {}", content_type))
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run curl: {}", e))?;
+
+    child
+        .stdin
+        .take()
+        .ok_or("curl stdin unavailable")?
+        .write_all(body)?;
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("POST {} failed: {}", url, stderr.trim()).into());
+    }
+
+    parse_curl_output(&String::from_utf8(output.stdout)?)
+}
+
+// curl is asked to append "\n<status>" after the body.
+fn parse_curl_output(stdout: &str) -> Result<HttpResponse, Box<dyn Error>> {
+    let (body, status) = stdout
+        .rsplit_once('\n')
+        .ok_or("curl output missing status line")?;
+    Ok(HttpResponse {
+        status: status.trim().parse()?,
+        body: body.to_string(),
+    })
+}
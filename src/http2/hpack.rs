@@ -0,0 +1,329 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::sync::OnceLock;
+
+// ==========================================
+// HPACK (RFC 7541)
+// ==========================================
+// Full decoding, since servers are free to use the dynamic table and
+// Huffman strings. Encoding sticks to literals without indexing, which
+// every decoder accepts and leaves no table state to track.
+
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+// Huffman code length of every symbol (256 = EOS), Appendix B. The code is
+// canonical, so the codes themselves follow from the lengths.
+#[rustfmt::skip]
+const HUFFMAN_LENGTHS: [u8; 257] = [
+    13, 23, 28, 28, 28, 28, 28, 28, 28, 24, 30, 28, 28, 30, 28, 28,
+    28, 28, 28, 28, 28, 28, 30, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+     6, 10, 10, 12, 13,  6,  8, 11, 10, 10,  8, 11,  8,  6,  6,  6,
+     5,  5,  5,  6,  6,  6,  6,  6,  6,  6,  7,  8, 15,  6, 12, 10,
+    13,  6,  7,  7,  7,  7,  7,  7,  7,  7,  7,  7,  7,  7,  7,  7,
+     7,  7,  7,  7,  7,  7,  7,  7,  8,  7,  8, 13, 19, 13, 14,  6,
+    15,  5,  6,  5,  6,  5,  6,  6,  6,  5,  7,  7,  6,  6,  6,  5,
+     6,  7,  6,  5,  5,  6,  7,  7,  7,  7,  7, 15, 11, 14, 13, 28,
+    20, 22, 20, 20, 22, 22, 22, 23, 22, 23, 23, 23, 23, 23, 24, 23,
+    24, 24, 22, 23, 24, 23, 23, 23, 23, 21, 22, 23, 22, 23, 23, 24,
+    22, 21, 20, 22, 22, 23, 23, 21, 23, 22, 22, 24, 21, 22, 23, 23,
+    21, 21, 22, 21, 23, 22, 23, 23, 20, 22, 22, 22, 23, 22, 22, 23,
+    26, 26, 20, 19, 22, 23, 22, 25, 26, 26, 26, 27, 27, 26, 24, 25,
+    19, 21, 26, 27, 27, 26, 27, 24, 21, 21, 26, 26, 28, 27, 27, 27,
+    20, 24, 20, 21, 22, 21, 21, 23, 22, 22, 25, 25, 24, 24, 26, 23,
+    26, 27, 26, 26, 27, 27, 27, 27, 27, 28, 27, 27, 27, 27, 27, 26,
+    30,
+];
+
+const MAX_HUFFMAN_LENGTH: usize = 30;
+
+// Canonical decoding tables: for each length, the first code, and where
+// that length's symbols start in the (length, symbol)-sorted list.
+struct HuffmanTable {
+    first_code: [u32; MAX_HUFFMAN_LENGTH + 1],
+    count: [u32; MAX_HUFFMAN_LENGTH + 1],
+    offset: [usize; MAX_HUFFMAN_LENGTH + 1],
+    symbols: Vec<u16>,
+}
+
+fn huffman_table() -> &'static HuffmanTable {
+    static TABLE: OnceLock<HuffmanTable> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut symbols: Vec<u16> = (0..=256).collect();
+        symbols.sort_by_key(|&s| (HUFFMAN_LENGTHS[s as usize], s));
+
+        let mut table = HuffmanTable {
+            first_code: [0; MAX_HUFFMAN_LENGTH + 1],
+            count: [0; MAX_HUFFMAN_LENGTH + 1],
+            offset: [0; MAX_HUFFMAN_LENGTH + 1],
+            symbols,
+        };
+        for &len in &HUFFMAN_LENGTHS {
+            table.count[len as usize] += 1;
+        }
+        let mut code = 0u32;
+        let mut offset = 0usize;
+        for len in 1..=MAX_HUFFMAN_LENGTH {
+            table.first_code[len] = code;
+            table.offset[len] = offset;
+            code = (code + table.count[len]) << 1;
+            offset += table.count[len] as usize;
+        }
+        table
+    })
+}
+
+fn huffman_decode(data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let table = huffman_table();
+    let mut out = Vec::with_capacity(data.len() * 8 / 5);
+    let mut code = 0u32;
+    let mut len = 0usize;
+
+    for byte in data {
+        for bit in (0..8).rev() {
+            code = (code << 1) | ((byte >> bit) & 1) as u32;
+            len += 1;
+            let index = code.wrapping_sub(table.first_code[len]);
+            if index < table.count[len] {
+                match table.symbols[table.offset[len] + index as usize] {
+                    256 => return Err("EOS symbol in HPACK Huffman string".into()),
+                    symbol => out.push(symbol as u8),
+                }
+                code = 0;
+                len = 0;
+            } else if len == MAX_HUFFMAN_LENGTH {
+                return Err("invalid HPACK Huffman code".into());
+            }
+        }
+    }
+    // Padding is the most significant bits of EOS (all ones), under a byte.
+    if len >= 8 || code != (1 << len) - 1 {
+        return Err("invalid HPACK Huffman padding".into());
+    }
+    Ok(out)
+}
+
+pub struct Decoder {
+    // Newest entry first, as HPACK indexes it.
+    dynamic: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Decoder { dynamic: VecDeque::new(), size: 0, max_size: 4096 }
+    }
+
+    pub fn decode(&mut self, block: &[u8]) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let mut headers = Vec::new();
+        let mut pos = 0;
+
+        while pos < block.len() {
+            let first = block[pos];
+            if first & 0x80 != 0 {
+                // Indexed header field
+                let index = read_integer(block, &mut pos, 7)?;
+                headers.push(self.entry(index)?);
+            } else if first & 0x40 != 0 {
+                // Literal with incremental indexing
+                let (name, value) = self.read_literal(block, &mut pos, 6)?;
+                self.insert(name.clone(), value.clone());
+                headers.push((name, value));
+            } else if first & 0x20 != 0 {
+                // Dynamic table size update
+                self.max_size = read_integer(block, &mut pos, 5)?;
+                self.evict(0);
+            } else {
+                // Literal without indexing / never indexed
+                headers.push(self.read_literal(block, &mut pos, 4)?);
+            }
+        }
+        Ok(headers)
+    }
+
+    fn entry(&self, index: usize) -> Result<(String, String), Box<dyn Error>> {
+        match index {
+            0 => Err("HPACK index 0".into()),
+            1..=61 => {
+                let (name, value) = STATIC_TABLE[index - 1];
+                Ok((name.to_string(), value.to_string()))
+            }
+            _ => self.dynamic.get(index - 62).cloned().ok_or_else(|| format!("HPACK index {} out of range", index).into()),
+        }
+    }
+
+    fn read_literal(&self, block: &[u8], pos: &mut usize, prefix: u8) -> Result<(String, String), Box<dyn Error>> {
+        let name = match read_integer(block, pos, prefix)? {
+            0 => read_string(block, pos)?,
+            index => self.entry(index)?.0,
+        };
+        Ok((name, read_string(block, pos)?))
+    }
+
+    fn insert(&mut self, name: String, value: String) {
+        let entry_size = name.len() + value.len() + 32;
+        self.evict(entry_size);
+        // An entry larger than the table empties it and isn't added.
+        if entry_size <= self.max_size {
+            self.size += entry_size;
+            self.dynamic.push_front((name, value));
+        }
+    }
+
+    // Make room for `incoming` bytes.
+    fn evict(&mut self, incoming: usize) {
+        while self.size + incoming > self.max_size {
+            let Some((name, value)) = self.dynamic.pop_back() else {
+                break;
+            };
+            self.size -= name.len() + value.len() + 32;
+        }
+    }
+}
+
+fn read_integer(block: &[u8], pos: &mut usize, prefix: u8) -> Result<usize, Box<dyn Error>> {
+    let mask = (1u16 << prefix) as usize - 1;
+    let mut value = *block.get(*pos).ok_or("truncated HPACK integer")? as usize & mask;
+    *pos += 1;
+    if value < mask {
+        return Ok(value);
+    }
+    let mut shift = 0;
+    loop {
+        let byte = *block.get(*pos).ok_or("truncated HPACK integer")?;
+        *pos += 1;
+        value += ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+        if shift > 28 {
+            return Err("HPACK integer too large".into());
+        }
+    }
+}
+
+fn read_string(block: &[u8], pos: &mut usize) -> Result<String, Box<dyn Error>> {
+    let huffman = block.get(*pos).ok_or("truncated HPACK string")? & 0x80 != 0;
+    let len = read_integer(block, pos, 7)?;
+    let raw = block.get(*pos..*pos + len).ok_or("truncated HPACK string")?;
+    *pos += len;
+    let bytes = if huffman { huffman_decode(raw)? } else { raw.to_vec() };
+    Ok(String::from_utf8(bytes)?)
+}
+
+// Literal header field without indexing, new name, no Huffman.
+pub fn encode_header(out: &mut Vec<u8>, name: &str, value: &str) {
+    out.push(0);
+    for s in [name, value] {
+        encode_integer(out, s.len(), 7);
+        out.extend_from_slice(s.as_bytes());
+    }
+}
+
+fn encode_integer(out: &mut Vec<u8>, value: usize, prefix: u8) {
+    let mask = (1usize << prefix) - 1;
+    if value < mask {
+        out.push(value as u8);
+        return;
+    }
+    out.push(mask as u8);
+    let mut rest = value - mask;
+    while rest >= 0x80 {
+        out.push((rest as u8 & 0x7f) | 0x80);
+        rest >>= 7;
+    }
+    out.push(rest as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7541 C.4: requests with Huffman coding, sharing a dynamic table.
+    #[test]
+    fn decodes_rfc_huffman_examples() {
+        let mut decoder = Decoder::new();
+        let first = [
+            0x82, 0x86, 0x84, 0x41, 0x8c, 0xf1, 0xe3, 0xc2, 0xe5, 0xf2, 0x3a, 0x6b, 0xa0, 0xab, 0x90, 0xf4, 0xff,
+        ];
+        assert_eq!(decoder.decode(&first).unwrap()[3], (":authority".to_string(), "www.example.com".to_string()));
+
+        let second = [0x82, 0x86, 0x84, 0xbe, 0x58, 0x86, 0xa8, 0xeb, 0x10, 0x64, 0x9c, 0xbf];
+        let headers = decoder.decode(&second).unwrap();
+        assert_eq!(headers[3], (":authority".to_string(), "www.example.com".to_string()));
+        assert_eq!(headers[4], ("cache-control".to_string(), "no-cache".to_string()));
+
+        let mut encoded = Vec::new();
+        encode_header(&mut encoded, "grpc-status", "0");
+        assert_eq!(Decoder::new().decode(&encoded).unwrap(), vec![("grpc-status".to_string(), "0".to_string())]);
+    }
+}
@@ -0,0 +1,228 @@
+pub mod hpack;
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io::{self, BufReader, Read, Write};
+use std::net::TcpStream;
+
+// ==========================================
+// MINIMAL HTTP/2 CLIENT
+// ==========================================
+// One streaming request per connection over cleartext HTTP/2 with prior
+// knowledge (h2c), which is what a gRPC server speaks on a plain port.
+// The request body stays open for as long as the caller wants, and the
+// response body is read incrementally through `Read`. Like the websocket
+// client there is no TLS; https endpoints need a local proxy.
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+const STREAM_ID: u32 = 1;
+
+const FRAME_DATA: u8 = 0x0;
+const FRAME_HEADERS: u8 = 0x1;
+const FRAME_RST_STREAM: u8 = 0x3;
+const FRAME_SETTINGS: u8 = 0x4;
+const FRAME_PING: u8 = 0x6;
+const FRAME_GOAWAY: u8 = 0x7;
+const FRAME_WINDOW_UPDATE: u8 = 0x8;
+const FRAME_CONTINUATION: u8 = 0x9;
+
+const FLAG_END_STREAM: u8 = 0x1;
+const FLAG_ACK: u8 = 0x1;
+const FLAG_END_HEADERS: u8 = 0x4;
+const FLAG_PADDED: u8 = 0x8;
+const FLAG_PRIORITY: u8 = 0x20;
+
+const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+
+// Receive window for the stream and the connection. Consumed bytes are
+// handed back as soon as they're read, so this only bounds what's in flight.
+const WINDOW_SIZE: u32 = 16 * 1024 * 1024;
+const DEFAULT_WINDOW_SIZE: u32 = 65_535;
+
+struct Frame {
+    kind: u8,
+    flags: u8,
+    stream_id: u32,
+    payload: Vec<u8>,
+}
+
+pub struct Http2Stream {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    hpack: hpack::Decoder,
+    headers: Vec<(String, String)>,
+    trailers: Vec<(String, String)>,
+    body: VecDeque<u8>,
+    ended: bool,
+}
+
+impl Http2Stream {
+    // Sends the request headers; the body follows through `send_data`.
+    pub fn post(url: &str, headers: &[(&str, &str)]) -> Result<Self, Box<dyn Error>> {
+        let rest = match url.split_once("://") {
+            Some(("http", rest)) => rest,
+            Some(("https", _)) => return Err("https:// is not supported; connect through a plaintext (h2c) proxy".into()),
+            _ => return Err(format!("invalid url '{}'", url).into()),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(pos) => (&rest[..pos], &rest[pos..]),
+            None => (rest, "/"),
+        };
+        let address = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+
+        let writer = TcpStream::connect(&address)?;
+        writer.set_nodelay(true)?;
+        let mut stream = Http2Stream {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+            hpack: hpack::Decoder::new(),
+            headers: Vec::new(),
+            trailers: Vec::new(),
+            body: VecDeque::new(),
+            ended: false,
+        };
+
+        let mut settings = Vec::new();
+        for (id, value) in [(SETTINGS_ENABLE_PUSH, 0), (SETTINGS_INITIAL_WINDOW_SIZE, WINDOW_SIZE)] {
+            settings.extend_from_slice(&id.to_be_bytes());
+            settings.extend_from_slice(&value.to_be_bytes());
+        }
+        stream.writer.write_all(PREFACE)?;
+        stream.write_frame(FRAME_SETTINGS, 0, 0, &settings)?;
+        stream.write_frame(FRAME_WINDOW_UPDATE, 0, 0, &(WINDOW_SIZE - DEFAULT_WINDOW_SIZE).to_be_bytes())?;
+
+        let mut block = Vec::new();
+        for (name, value) in [(":method", "POST"), (":scheme", "http"), (":authority", authority), (":path", path)] {
+            hpack::encode_header(&mut block, name, value);
+        }
+        for (name, value) in headers {
+            hpack::encode_header(&mut block, &name.to_ascii_lowercase(), value);
+        }
+        stream.write_frame(FRAME_HEADERS, FLAG_END_HEADERS, STREAM_ID, &block)?;
+        Ok(stream)
+    }
+
+    // Request bodies here are small messages, so the peer's flow control
+    // window isn't tracked.
+    pub fn send_data(&mut self, data: &[u8], end_stream: bool) -> Result<(), Box<dyn Error>> {
+        self.write_frame(FRAME_DATA, if end_stream { FLAG_END_STREAM } else { 0 }, STREAM_ID, data)
+    }
+
+    // Empty until the response headers arrive (after the first read).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find(&self.headers, name)
+    }
+
+    // Only set once the response has ended.
+    pub fn trailer(&self, name: &str) -> Option<&str> {
+        find(&self.trailers, name)
+    }
+
+    pub fn status(&self) -> Option<u16> {
+        self.header(":status")?.parse().ok()
+    }
+
+    fn write_frame(&mut self, kind: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Result<(), Box<dyn Error>> {
+        let mut frame = Vec::with_capacity(payload.len() + 9);
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+        frame.push(kind);
+        frame.push(flags);
+        frame.extend_from_slice(&stream_id.to_be_bytes());
+        frame.extend_from_slice(payload);
+        self.writer.write_all(&frame)?;
+        Ok(())
+    }
+
+    fn read_frame(&mut self) -> Result<Frame, Box<dyn Error>> {
+        let mut header = [0u8; 9];
+        self.reader.read_exact(&mut header)?;
+        let len = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+        let stream_id = u32::from_be_bytes(header[5..9].try_into()?) & 0x7fff_ffff;
+        let mut payload = vec![0u8; len];
+        self.reader.read_exact(&mut payload)?;
+        Ok(Frame { kind: header[3], flags: header[4], stream_id, payload })
+    }
+
+    // Handles one frame from the server, queuing any body bytes.
+    fn process_frame(&mut self) -> Result<(), Box<dyn Error>> {
+        let Frame { kind, flags, stream_id, payload } = self.read_frame()?;
+        match kind {
+            FRAME_DATA if stream_id == STREAM_ID => {
+                if !payload.is_empty() {
+                    // Return the credit right away for both windows.
+                    let increment = (payload.len() as u32).to_be_bytes();
+                    self.write_frame(FRAME_WINDOW_UPDATE, 0, 0, &increment)?;
+                    if flags & FLAG_END_STREAM == 0 {
+                        self.write_frame(FRAME_WINDOW_UPDATE, 0, STREAM_ID, &increment)?;
+                    }
+                }
+                self.body.extend(strip_padding(&payload, flags)?);
+                self.ended |= flags & FLAG_END_STREAM != 0;
+            }
+            FRAME_HEADERS if stream_id == STREAM_ID => {
+                let mut fragment = strip_padding(&payload, flags)?;
+                if flags & FLAG_PRIORITY != 0 {
+                    fragment = fragment.get(5..).ok_or("truncated HTTP/2 HEADERS priority")?;
+                }
+                let mut block = fragment.to_vec();
+                let mut end_headers = flags & FLAG_END_HEADERS != 0;
+                while !end_headers {
+                    let frame = self.read_frame()?;
+                    if frame.kind != FRAME_CONTINUATION {
+                        return Err("expected HTTP/2 CONTINUATION frame".into());
+                    }
+                    block.extend_from_slice(&frame.payload);
+                    end_headers = frame.flags & FLAG_END_HEADERS != 0;
+                }
+
+                let decoded = self.hpack.decode(&block)?;
+                if self.headers.is_empty() {
+                    self.headers = decoded;
+                } else {
+                    self.trailers = decoded;
+                }
+                self.ended |= flags & FLAG_END_STREAM != 0;
+            }
+            FRAME_SETTINGS if flags & FLAG_ACK == 0 => self.write_frame(FRAME_SETTINGS, FLAG_ACK, 0, &[])?,
+            FRAME_PING if flags & FLAG_ACK == 0 => self.write_frame(FRAME_PING, FLAG_ACK, 0, &payload)?,
+            FRAME_RST_STREAM if stream_id == STREAM_ID => {
+                let code = payload.get(..4).map(|b| u32::from_be_bytes(b.try_into().unwrap())).unwrap_or_default();
+                return Err(format!("HTTP/2 stream reset by server (error code {})", code).into());
+            }
+            FRAME_GOAWAY => {
+                let code = payload.get(4..8).map(|b| u32::from_be_bytes(b.try_into().unwrap())).unwrap_or_default();
+                let debug = String::from_utf8_lossy(payload.get(8..).unwrap_or_default());
+                return Err(format!("HTTP/2 connection closed by server (error code {}) {}", code, debug).trim().to_string().into());
+            }
+            // Window updates, priority, and frames for other streams.
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Read for Http2Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.body.is_empty() && !self.ended {
+            self.process_frame().map_err(|e| io::Error::other(e.to_string()))?;
+        }
+        let n = buf.len().min(self.body.len());
+        for (dst, src) in buf.iter_mut().zip(self.body.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+fn strip_padding(payload: &[u8], flags: u8) -> Result<&[u8], Box<dyn Error>> {
+    if flags & FLAG_PADDED == 0 {
+        return Ok(payload);
+    }
+    let pad = *payload.first().ok_or("truncated HTTP/2 padding")? as usize;
+    payload.get(1..payload.len().saturating_sub(pad)).filter(|_| pad < payload.len()).ok_or_else(|| "invalid HTTP/2 padding".into())
+}
+
+fn find<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+}
@@ -0,0 +1,303 @@
+use crate::error::ParseError;
+use crate::parser::{assemble_block, parse_transaction_chunks, record_block_metrics, ParseOptions};
+use crate::span;
+use crate::filter::{is_raw_vote, TxFilter};
+use crate::types::{ParseReport, ParsedBlock, ParsedTransaction, RpcBlockResult, RpcBlockTransaction, RpcReward};
+use log::Level;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_json::value::RawValue;
+use std::fmt;
+use std::io::Read;
+
+// ==========================================
+// INCREMENTAL BLOCK PARSING
+// ==========================================
+// A getBlock response with base64 data can run past 50MB, and deserializing
+// it whole holds every raw transaction in memory at once. Here the
+// transactions array is read one element at a time and parsed in batches,
+// so only a batch of raw transactions is alive at any point. Each parsed
+// transaction goes to a callback as soon as its batch is done.
+//
+// The RPC sorts keys alphabetically, so the header fields after
+// "transactions" (parentSlot, previousBlockhash, rewards) are only known once
+// the whole array has been read: the block comes back at the end. The
+// transactions handed to the callback have no slot for the same reason.
+//
+// With a pre-filtering TxFilter (TxFilter::prefilter), each transaction is
+// first read as JSON text and only deserialized when may_match_raw lets it
+// through. Positions in warnings then count only the transactions let
+// through.
+
+const BATCH_SIZE: usize = 1024;
+
+// Parses a getBlock response (the full `{"jsonrpc": ..., "result": ...}`
+// envelope) from `reader`, handing each transaction to `on_transaction` in
+// block order. The returned block has no transactions. Pass a buffered
+// reader; serde_json reads a byte at a time.
+pub fn stream_block<R: Read>(
+    reader: R,
+    options: &ParseOptions,
+    on_transaction: impl FnMut(ParsedTransaction),
+) -> Result<(ParsedBlock, ParseReport), ParseError> {
+    let _span = span!(Level::Debug, "stream_block");
+    let mut streamer = Streamer {
+        options,
+        on_transaction,
+        report: ParseReport::default(),
+        batch: Vec::with_capacity(BATCH_SIZE),
+        offset: 0,
+        seen: 0,
+        votes: 0,
+        error: None,
+    };
+
+    let mut de = serde_json::Deserializer::from_reader(reader);
+    let header = ResponseSeed(&mut streamer)
+        .deserialize(&mut de)
+        .and_then(|header| de.end().map(|_| header))
+        .map_err(|e| streamer.error.take().unwrap_or(ParseError::Json(e)))?;
+
+    let excluded_votes = options.filter.as_ref().filter(|f| f.excludes_votes()).map(|_| streamer.votes);
+    let block = assemble_block(header, Vec::new(), excluded_votes, options)?;
+    record_block_metrics(&streamer.report, streamer.offset);
+    Ok((block, streamer.report))
+}
+
+// `stream_block`, with the transactions collected back into the block.
+pub fn parse_block_reader<R: Read>(reader: R, options: &ParseOptions) -> Result<(ParsedBlock, ParseReport), ParseError> {
+    let mut transactions = Vec::new();
+    let (mut block, report) = stream_block(reader, options, |tx| transactions.push(tx))?;
+    for tx in &mut transactions {
+        tx.slot = Some(block.slot);
+    }
+    block.transactions = transactions;
+    Ok((block, report))
+}
+
+struct Streamer<'a, F> {
+    options: &'a ParseOptions,
+    on_transaction: F,
+    report: ParseReport,
+    batch: Vec<RpcBlockTransaction>,
+    // Position in the block of the first transaction in `batch`.
+    offset: usize,
+    // Transactions read so far, pre-filtered ones included.
+    seen: usize,
+    votes: usize,
+    // A parse failure (strict mode) that aborted deserialization.
+    error: Option<ParseError>,
+}
+
+impl<F: FnMut(ParsedTransaction)> Streamer<'_, F> {
+    fn push<E: de::Error>(&mut self, tx: RpcBlockTransaction) -> Result<(), E> {
+        if self.options.filter.as_ref().is_some_and(|f| f.excludes_votes()) && tx.transaction.is_vote() {
+            self.votes += 1;
+        }
+        self.batch.push(RpcBlockTransaction { index: Some(self.seen), ..tx });
+        self.seen += 1;
+        if self.batch.len() >= BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    // A transaction's JSON, deserialized only if the pre-filter lets it
+    // through.
+    fn push_raw<E: de::Error>(&mut self, filter: &TxFilter, raw: &str) -> Result<(), E> {
+        if filter.may_match_raw(raw) {
+            return self.push(serde_json::from_str(raw).map_err(E::custom)?);
+        }
+        if filter.excludes_votes() && is_raw_vote(raw) {
+            self.votes += 1;
+        }
+        self.seen += 1;
+        Ok(())
+    }
+
+    fn flush<E: de::Error>(&mut self) -> Result<(), E> {
+        let (transactions, report) = match parse_transaction_chunks(&self.batch, self.offset, self.options) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                let message = e.to_string();
+                self.error = Some(e);
+                return Err(E::custom(message));
+            }
+        };
+        self.offset += self.batch.len();
+        self.batch.clear();
+        self.report.warnings.extend(report.warnings);
+        transactions.into_iter().for_each(&mut self.on_transaction);
+        Ok(())
+    }
+}
+
+// The response envelope: only "result" is read.
+struct ResponseSeed<'s, 'a, F>(&'s mut Streamer<'a, F>);
+
+impl<'de, F: FnMut(ParsedTransaction)> DeserializeSeed<'de> for ResponseSeed<'_, '_, F> {
+    type Value = RpcBlockResult;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, F: FnMut(ParsedTransaction)> Visitor<'de> for ResponseSeed<'_, '_, F> {
+    type Value = RpcBlockResult;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a getBlock response")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut result = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "result" && result.is_none() {
+                result = Some(map.next_value_seed(ResultSeed(&mut *self.0))?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        result.ok_or_else(|| de::Error::missing_field("result"))
+    }
+}
+
+// The block itself. Header fields are read as usual; "transactions" goes
+// through the streamer, leaving the returned block's list empty.
+struct ResultSeed<'s, 'a, F>(&'s mut Streamer<'a, F>);
+
+impl<'de, F: FnMut(ParsedTransaction)> DeserializeSeed<'de> for ResultSeed<'_, '_, F> {
+    type Value = RpcBlockResult;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, F: FnMut(ParsedTransaction)> Visitor<'de> for ResultSeed<'_, '_, F> {
+    type Value = RpcBlockResult;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a block")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut block_height: Option<u64> = None;
+        let mut block_time: Option<i64> = None;
+        let mut blockhash: Option<String> = None;
+        let mut parent_slot: Option<u64> = None;
+        let mut previous_blockhash: Option<String> = None;
+        let mut rewards: Option<Vec<RpcReward>> = None;
+        let mut signatures: Option<Vec<String>> = None;
+        let mut transactions = false;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "blockHeight" => block_height = map.next_value()?,
+                "blockTime" => block_time = map.next_value()?,
+                "blockhash" => blockhash = Some(map.next_value()?),
+                "parentSlot" => parent_slot = Some(map.next_value()?),
+                "previousBlockhash" => previous_blockhash = Some(map.next_value()?),
+                "rewards" => rewards = Some(map.next_value()?),
+                "signatures" => signatures = Some(map.next_value()?),
+                "transactions" if !transactions => {
+                    map.next_value_seed(TransactionsSeed(&mut *self.0))?;
+                    transactions = true;
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        // Blocks fetched with transactionDetails signatures or none have no
+        // transactions.
+        Ok(RpcBlockResult {
+            block_height,
+            block_time,
+            blockhash: blockhash.ok_or_else(|| de::Error::missing_field("blockhash"))?,
+            parent_slot: parent_slot.ok_or_else(|| de::Error::missing_field("parentSlot"))?,
+            previous_blockhash: previous_blockhash.ok_or_else(|| de::Error::missing_field("previousBlockhash"))?,
+            rewards: rewards.unwrap_or_default(),
+            transactions: Vec::new(),
+            signatures: signatures.unwrap_or_default(),
+            prefiltered_votes: 0,
+            endpoint: None,
+        })
+    }
+}
+
+struct TransactionsSeed<'s, 'a, F>(&'s mut Streamer<'a, F>);
+
+impl<'de, F: FnMut(ParsedTransaction)> DeserializeSeed<'de> for TransactionsSeed<'_, '_, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F: FnMut(ParsedTransaction)> Visitor<'de> for TransactionsSeed<'_, '_, F> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of transactions")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        match self.0.options.filter.as_deref().filter(|f| f.prefilters()) {
+            Some(filter) => {
+                while let Some(raw) = seq.next_element::<Box<RawValue>>()? {
+                    self.0.push_raw(filter, raw.get())?;
+                }
+            }
+            None => {
+                while let Some(tx) = seq.next_element::<RpcBlockTransaction>()? {
+                    self.0.push(tx)?;
+                }
+            }
+        }
+        if !self.0.batch.is_empty() {
+            self.0.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::TxFilter;
+    use crate::fixtures;
+    use crate::parser::parse_block_with_options;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::sync::Arc;
+
+    #[test]
+    fn matches_the_whole_block_parse() {
+        let filters = [None, Some(Arc::new(TxFilter::new().exclude_votes()))];
+        for filter in filters {
+            let options = ParseOptions { filter, ..ParseOptions::default() };
+            let raw = fixtures::raw_block();
+            let (expected, _) = parse_block_with_options(raw.result, &options).unwrap();
+
+            let reader = BufReader::new(File::open("src/json/block.json").unwrap());
+            let mut signatures = Vec::new();
+            let (header, _) = stream_block(reader, &options, |tx| signatures.push(tx.signature)).unwrap();
+            assert!(header.transactions.is_empty());
+            assert_eq!(signatures, expected.transactions.iter().map(|tx| tx.signature.clone()).collect::<Vec<_>>());
+
+            let reader = BufReader::new(File::open("src/json/block.json").unwrap());
+            let (streamed, _) = parse_block_reader(reader, &options).unwrap();
+            assert_eq!(serde_json::to_value(&streamed).unwrap(), serde_json::to_value(&expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        let json = std::fs::read_to_string("src/json/block.json").unwrap();
+        let truncated = &json[..json.len() / 2];
+        assert!(matches!(parse_block_reader(truncated.as_bytes(), &ParseOptions::default()), Err(ParseError::Json(_))));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::binary::BinaryReader;
+use crate::compress;
+use crate::error::ParseError;
+use crate::incremental::parse_block_reader;
+use crate::parser::{parse_block_with_options, parse_transaction_result, ParseOptions};
+use crate::types::{ParseReport, ParsedBlock, ParsedTransaction, RpcBlockResult, RpcResponse, RpcResult};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+pub fn load_from_json<T>(path: &str) -> Result<T, ParseError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    // gzip and zstd files are decompressed on the fly.
+    let file = compress::open(Path::new(path)).map_err(|source| ParseError::Io { path: path.to_string(), source })?;
+    load_from_reader(BufReader::new(file))
+}
+
+// `load_from_json` for JSON that isn't in a file. Pass a buffered reader.
+pub fn load_from_reader<T, R>(reader: R) -> Result<T, ParseError>
+where
+    T: serde::de::DeserializeOwned,
+    R: Read,
+{
+    Ok(serde_json::from_reader(reader)?)
+}
+
+// Opens `--output bincode|borsh` output, compressed or not, for reading
+// its records back.
+pub fn read_parsed(path: &str) -> Result<BinaryReader<BufReader<Box<dyn Read + Send>>>, ParseError> {
+    let file = compress::open(Path::new(path)).map_err(|source| ParseError::Io { path: path.to_string(), source })?;
+    BinaryReader::new(BufReader::new(file))
+}
+
+// Parses an archived getBlock response without holding every raw
+// transaction in memory at once.
+pub fn load_block_file(path: &str, options: &ParseOptions) -> Result<(ParsedBlock, ParseReport), ParseError> {
+    let file = compress::open(Path::new(path)).map_err(|source| ParseError::Io { path: path.to_string(), source })?;
+    parse_block_reader(BufReader::new(file), options)
+}
+
+// Reads a block this crate wrote earlier (`--output json`), upgraded to the
+// current schema.
+pub fn load_parsed_block(path: &str) -> Result<ParsedBlock, ParseError> {
+    let mut block: ParsedBlock = load_from_json(path)?;
+    block.upgrade()?;
+    Ok(block)
+}
+
+// ==========================================
+// INPUT SNIFFING
+// ==========================================
+// For JSON piped in (`parse-block -`, `parse-tx -`), whatever it turns out to
+// be: a getBlock result has a blockhash, a getTransaction result has a
+// transaction. Either the whole JSON-RPC response or just its result
+// (`jq .result`) is accepted. The input is read twice, once skipping over
+// everything but those fields.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    Block,
+    Transaction,
+}
+
+pub enum ParsedInput {
+    Block(Box<ParsedBlock>, ParseReport),
+    Transaction(Box<ParsedTransaction>, ParseReport),
+}
+
+impl ParsedInput {
+    pub fn kind(&self) -> InputKind {
+        match self {
+            ParsedInput::Block(..) => InputKind::Block,
+            ParsedInput::Transaction(..) => InputKind::Transaction,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Probe {
+    result: Option<ProbeFields>,
+    error: Option<ProbeError>,
+    blockhash: Option<IgnoredAny>,
+    transaction: Option<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+struct ProbeFields {
+    blockhash: Option<IgnoredAny>,
+    transaction: Option<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+struct ProbeError {
+    #[serde(default)]
+    code: i64,
+    #[serde(default)]
+    message: String,
+}
+
+pub fn parse_input(json: &[u8], options: &ParseOptions) -> Result<ParsedInput, ParseError> {
+    let probe: Probe = serde_json::from_slice(json)?;
+    if let Some(error) = probe.error {
+        return Err(ParseError::UnrecognizedInput(format!("the RPC returned an error ({}): {}", error.code, error.message)));
+    }
+    let (wrapped, blockhash, transaction) = match probe.result {
+        Some(fields) => (true, fields.blockhash, fields.transaction),
+        None => (false, probe.blockhash, probe.transaction),
+    };
+    match (blockhash.is_some(), transaction.is_some(), wrapped) {
+        (true, _, true) => {
+            let (block, report) = parse_block_reader(json, options)?;
+            Ok(ParsedInput::Block(Box::new(block), report))
+        }
+        (true, _, false) => {
+            let (block, report) = parse_block_with_options(serde_json::from_slice::<RpcBlockResult>(json)?, options)?;
+            Ok(ParsedInput::Block(Box::new(block), report))
+        }
+        (false, true, _) => {
+            let raw = match wrapped {
+                true => serde_json::from_slice::<RpcResponse>(json)?.result,
+                false => serde_json::from_slice::<RpcResult>(json)?,
+            };
+            let (tx, report) = parse_transaction_result(&raw, options)?;
+            Ok(ParsedInput::Transaction(Box::new(tx), report))
+        }
+        (false, false, _) => Err(ParseError::UnrecognizedInput(
+            "neither a getBlock nor a getTransaction response (a null result means no block for the slot, or no such transaction)"
+                .to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_blocks_and_transactions_wrapped_or_not() {
+        let options = ParseOptions::default();
+        let block = std::fs::read("src/json/block.json").unwrap();
+        let transaction = std::fs::read("src/json/transaction.json").unwrap();
+        assert_eq!(parse_input(&block, &options).unwrap().kind(), InputKind::Block);
+        assert_eq!(parse_input(&transaction, &options).unwrap().kind(), InputKind::Transaction);
+
+        let result = |json: &[u8]| serde_json::to_vec(&serde_json::from_slice::<serde_json::Value>(json).unwrap()["result"]).unwrap();
+        let (Ok(ParsedInput::Block(wrapped, _)), Ok(ParsedInput::Block(bare, _))) =
+            (parse_input(&block, &options), parse_input(&result(&block), &options))
+        else {
+            panic!("expected blocks");
+        };
+        assert_eq!(serde_json::to_value(&wrapped).unwrap(), serde_json::to_value(&bare).unwrap());
+        assert_eq!(parse_input(&result(&transaction), &options).unwrap().kind(), InputKind::Transaction);
+
+        for json in [r#"{"jsonrpc":"2.0","result":null,"id":1}"#, r#"{"error":{"code":-32009,"message":"Slot 5 was skipped"}}"#] {
+            assert!(matches!(parse_input(json.as_bytes(), &options), Err(ParseError::UnrecognizedInput(_))));
+        }
+    }
+}
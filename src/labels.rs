@@ -0,0 +1,160 @@
+#[cfg(feature = "native")]
+use crate::config::toml_to_json;
+use crate::decoders::associated_token::ASSOCIATED_TOKEN_PROGRAM_ID;
+use crate::decoders::compute_budget::COMPUTE_BUDGET_PROGRAM_ID;
+use crate::decoders::jupiter::JUPITER_V6_PROGRAM_ID;
+use crate::decoders::raydium::RAYDIUM_AMM_V4_PROGRAM_ID;
+use crate::decoders::stake::STAKE_PROGRAM_ID;
+use crate::decoders::system::SYSTEM_PROGRAM_ID;
+use crate::decoders::token::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+use crate::decoders::token_metadata::TOKEN_METADATA_PROGRAM_ID;
+use crate::dex::meteora::{METEORA_DAMM_V2_PROGRAM_ID, METEORA_DLMM_PROGRAM_ID, METEORA_POOLS_PROGRAM_ID};
+use crate::dex::orca::ORCA_WHIRLPOOL_PROGRAM_ID;
+use crate::dex::raydium::{RAYDIUM_CLMM_PROGRAM_ID, RAYDIUM_CPMM_PROGRAM_ID};
+use crate::pubkey::Pubkey;
+use crate::transfers::NATIVE_MINT;
+use crate::types::{MEMO_PROGRAM_ID, VOTE_PROGRAM_ID};
+use std::collections::HashMap;
+#[cfg(feature = "native")]
+use std::error::Error;
+#[cfg(feature = "native")]
+use std::fs;
+#[cfg(feature = "native")]
+use std::path::Path;
+
+// ==========================================
+// ADDRESS LABELS
+// ==========================================
+// Human-readable names for well-known addresses: programs, mints and the
+// hot wallets of the larger exchanges. The built-in list is a starting
+// point; a user file (a flat JSON object or TOML table of address = label)
+// adds to it or overrides entries, e.g.
+//
+//     "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM" = "Binance 2"
+
+const BUILTIN_LABELS: &[(Pubkey, &str)] = &[
+    (SYSTEM_PROGRAM_ID, "System Program"),
+    (VOTE_PROGRAM_ID, "Vote Program"),
+    (STAKE_PROGRAM_ID, "Stake Program"),
+    (COMPUTE_BUDGET_PROGRAM_ID, "Compute Budget Program"),
+    (Pubkey::from_str_const("AddressLookupTab1e1111111111111111111111111"), "Address Lookup Table Program"),
+    (Pubkey::from_str_const("BPFLoaderUpgradeab1e11111111111111111111111"), "BPF Upgradeable Loader"),
+    (TOKEN_PROGRAM_ID, "Token Program"),
+    (TOKEN_2022_PROGRAM_ID, "Token-2022 Program"),
+    (ASSOCIATED_TOKEN_PROGRAM_ID, "Associated Token Account Program"),
+    (MEMO_PROGRAM_ID, "Memo Program"),
+    (TOKEN_METADATA_PROGRAM_ID, "Metaplex Token Metadata"),
+    (NATIVE_MINT, "Wrapped SOL"),
+    (Pubkey::from_str_const("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"), "USDC"),
+    (Pubkey::from_str_const("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB"), "USDT"),
+    (JUPITER_V6_PROGRAM_ID, "Jupiter v6"),
+    (RAYDIUM_AMM_V4_PROGRAM_ID, "Raydium AMM v4"),
+    (RAYDIUM_CPMM_PROGRAM_ID, "Raydium CPMM"),
+    (RAYDIUM_CLMM_PROGRAM_ID, "Raydium CLMM"),
+    (ORCA_WHIRLPOOL_PROGRAM_ID, "Orca Whirlpool"),
+    (METEORA_DLMM_PROGRAM_ID, "Meteora DLMM"),
+    (METEORA_POOLS_PROGRAM_ID, "Meteora Pools"),
+    (METEORA_DAMM_V2_PROGRAM_ID, "Meteora DAMM v2"),
+    (Pubkey::from_str_const("6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P"), "Pump.fun"),
+    (Pubkey::from_str_const("pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA"), "PumpSwap AMM"),
+    (Pubkey::from_str_const("5tzFkiKscXHK5ZXCGbXZxdw7gTjjD1mBwuoFbhUvuAi9"), "Binance 1"),
+    (Pubkey::from_str_const("9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM"), "Binance 2"),
+    (Pubkey::from_str_const("H8sMJSCQxfKiFTCfDR3DUMLPwcRbM61LGFJ8N4dK3WjS"), "Coinbase 1"),
+    (Pubkey::from_str_const("2AQdpHJ2JpcEgPiATUXjQxA8QmafFegfQwSLWSprPicm"), "Coinbase 2"),
+    (Pubkey::from_str_const("FWznbcNXWQuHTawe9RxvQ2LdCENssh12dsznf4RiouN5"), "Kraken"),
+    (Pubkey::from_str_const("5VCwKtCXgCJ6kit5FybXjvriW3xELsFDhYrPSqtJNmcD"), "OKX"),
+    (Pubkey::from_str_const("AC5RDfQFmDS1deWZos921JfqscXdByf8BKHs5ACWjtW2"), "Bybit"),
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct LabelRegistry {
+    labels: HashMap<Pubkey, String>,
+}
+
+impl LabelRegistry {
+    // No labels at all; see `builtins` for the usual starting point.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn builtins() -> Self {
+        let mut registry = Self::new();
+        for (address, label) in BUILTIN_LABELS {
+            registry.insert(*address, label);
+        }
+        registry
+    }
+
+    // Replaces any label already set for `address`.
+    pub fn insert(&mut self, address: Pubkey, label: &str) {
+        self.labels.insert(address, label.to_string());
+    }
+
+    pub fn get(&self, address: &Pubkey) -> Option<&str> {
+        self.labels.get(address).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    // Adds the labels in a .toml file, or a JSON file for any other
+    // extension. Entries there win over ones already registered.
+    #[cfg(feature = "native")]
+    pub fn with_file(mut self, path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("failed to read labels {}: {}", path.display(), e))?;
+        let labels = parse_labels(path, &contents).map_err(|e| format!("invalid labels {}: {}", path.display(), e))?;
+        self.labels.extend(labels);
+        Ok(self)
+    }
+
+    // "label (address)" when the address is known, the bare address
+    // otherwise.
+    pub fn describe(&self, address: &Pubkey) -> String {
+        match self.get(address) {
+            Some(label) => format!("{} ({})", label, address),
+            None => address.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+fn parse_labels(path: &Path, contents: &str) -> Result<HashMap<Pubkey, String>, Box<dyn Error>> {
+    let value = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => toml_to_json(contents)?,
+        _ => serde_json::from_str(contents)?,
+    };
+    Ok(serde_json::from_value(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_files_extend_and_override_the_builtins() {
+        let registry = LabelRegistry::builtins();
+        assert_eq!(registry.get(&TOKEN_PROGRAM_ID), Some("Token Program"));
+        assert_eq!(registry.describe(&RAYDIUM_AMM_V4_PROGRAM_ID), "Raydium AMM v4 (675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8)");
+
+        let dir = std::env::temp_dir().join(format!("phase1-labels-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let toml = dir.join("labels.toml");
+        fs::write(&toml, "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA = \"SPL Token\"\n").unwrap();
+        let json = dir.join("labels.json");
+        fs::write(&json, r#"{"8qjB4NibFqqRyECMUMWw7X4are73dms6pfM9gBEJGNAP": "Whale"}"#).unwrap();
+        let bad = dir.join("bad.json");
+        fs::write(&bad, r#"{"not a key": "x"}"#).unwrap();
+
+        let registry = registry.with_file(&toml).unwrap().with_file(&json).unwrap();
+        assert_eq!(registry.get(&TOKEN_PROGRAM_ID), Some("SPL Token"));
+        assert_eq!(registry.get(&Pubkey::from_str_const("8qjB4NibFqqRyECMUMWw7X4are73dms6pfM9gBEJGNAP")), Some("Whale"));
+        assert_eq!(registry.len(), BUILTIN_LABELS.len() + 1);
+        assert!(LabelRegistry::new().with_file(&bad).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+pub mod aggregate;
+#[cfg(feature = "native")]
+pub mod backfill;
+pub mod binary;
+pub mod bloom;
+#[cfg(feature = "native")]
+pub mod checkpoint;
+#[cfg(feature = "native")]
+pub mod compress;
+#[cfg(feature = "native")]
+pub mod config;
+#[cfg(feature = "native")]
+pub mod consensus;
+pub mod cost;
+pub mod decoders;
+pub mod dedup;
+pub mod dex;
+pub mod diff;
+pub mod encoding;
+pub mod epoch;
+pub mod error;
+#[cfg(feature = "native")]
+pub mod export;
+pub mod failure;
+pub mod filter;
+#[cfg(all(test, feature = "native"))]
+mod fixtures;
+#[cfg(feature = "native")]
+pub mod geyser;
+#[cfg(all(test, feature = "native"))]
+mod golden;
+#[cfg(feature = "native")]
+pub mod graphql;
+#[cfg(feature = "native")]
+pub mod history;
+#[cfg(feature = "native")]
+pub mod http;
+#[cfg(feature = "native")]
+pub mod http2;
+pub mod incremental;
+#[cfg(feature = "native")]
+pub mod io;
+pub mod labels;
+pub mod logging;
+pub mod logs;
+pub mod metrics;
+pub mod mev;
+pub mod lookup_tables;
+pub mod parser;
+pub mod pubkey;
+#[cfg(feature = "native")]
+pub mod pubsub;
+pub mod reorg;
+#[cfg(feature = "native")]
+pub mod rpc;
+pub mod schema;
+#[cfg(feature = "native")]
+pub mod server;
+#[cfg(feature = "native")]
+pub mod shutdown;
+#[cfg(feature = "native")]
+pub mod sink;
+#[cfg(feature = "native")]
+pub mod source;
+#[cfg(feature = "native")]
+pub mod spill;
+pub mod stats;
+#[cfg(feature = "native")]
+pub mod summary;
+#[cfg(feature = "native")]
+pub mod stream;
+pub mod timestamps;
+pub mod token_owners;
+pub mod tokens;
+pub mod transfers;
+pub mod types;
+pub mod validate;
+pub mod wallet;
+#[cfg(feature = "native")]
+pub mod websocket;
+pub mod wire;
+
+pub use decoders::{DecodedEvent, DecodedInstruction, DecoderRegistry, ProgramDecoder};
+pub use encoding::DataEncoding;
+pub use error::ParseError;
+pub use filter::{InstructionPattern, TxFilter, TxStatus};
+pub use incremental::{parse_block_reader, stream_block};
+#[cfg(feature = "native")]
+pub use io::{load_block_file, load_from_json, load_parsed_block, parse_input, read_parsed, InputKind, ParsedInput};
+pub use labels::LabelRegistry;
+pub use parser::{
+    parse_block, parse_block_with_options, parse_block_with_report, parse_transaction,
+    parse_transaction_result, parse_transaction_with_options, ParseOptions,
+};
+pub use pubkey::Pubkey;
+#[cfg(feature = "native")]
+pub use rpc::{AccountSnapshots, BlockFetch, Commitment, RpcClient, TransactionDetails, TransactionEncoding};
+pub use stats::{block_stats, BlockStats};
+pub use types::*;
@@ -0,0 +1,218 @@
+use crate::epoch::format_rfc3339;
+use log::kv::{self, Key, Value, VisitSource};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// ==========================================
+// DIAGNOSTIC LOGGING
+// ==========================================
+// Diagnostics (errors, warnings, progress, timings) go through the `log`
+// facade with key-value fields, so library code stays quiet unless the
+// binary installs a logger. `init` installs one writing to stderr, as text
+// or one JSON object per line; stdout is left to the parsed output and the
+// summaries. `span!` times a unit of work (block parse, transaction parse,
+// RPC call) and logs when it finishes.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("unknown log format '{}' (expected text or json)", other)),
+        }
+    }
+}
+
+pub fn level_from_arg(value: &str) -> Result<LevelFilter, String> {
+    value.parse().map_err(|_| format!("unknown log level '{}' (expected off, error, warn, info, debug or trace)", value))
+}
+
+struct StderrLogger {
+    level: LevelFilter,
+    format: LogFormat,
+}
+
+// Installs the stderr logger; only the first call in a process takes effect.
+pub fn init(level: LevelFilter, format: LogFormat) {
+    if log::set_boxed_logger(Box::new(StderrLogger { level, format })).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = match self.format {
+            LogFormat::Text => text_line(record),
+            LogFormat::Json => json_line(record),
+        };
+        let _ = writeln!(std::io::stderr().lock(), "{}", line);
+    }
+
+    fn flush(&self) {}
+}
+
+// `WARN skipped transaction tx_sig=5h3k...`: errors and warnings keep the
+// level prefix; the timestamp is left to whatever collects stderr.
+fn text_line(record: &Record) -> String {
+    let mut line = format!("{} {}", record.level(), record.args());
+    let mut fields = TextFields(&mut line);
+    let _ = record.key_values().visit(&mut fields);
+    line
+}
+
+struct TextFields<'a>(&'a mut String);
+
+impl<'kvs> VisitSource<'kvs> for TextFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let _ = write!(self.0, " {}={}", key, value);
+        Ok(())
+    }
+}
+
+fn json_line(record: &Record) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut object = serde_json::Map::new();
+    object.insert("time".to_string(), format_rfc3339(now.as_secs() as i64).into());
+    object.insert("level".to_string(), record.level().as_str().into());
+    object.insert("target".to_string(), record.target().into());
+    object.insert("message".to_string(), record.args().to_string().into());
+    let mut fields = JsonFields(&mut object);
+    let _ = record.key_values().visit(&mut fields);
+    serde_json::Value::Object(object).to_string()
+}
+
+struct JsonFields<'a>(&'a mut serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        // Numbers stay numbers; everything else is written as its Display.
+        let value = match (value.to_u64(), value.to_i64(), value.to_f64()) {
+            (Some(n), _, _) => n.into(),
+            (_, Some(n), _) => n.into(),
+            (_, _, Some(n)) => n.into(),
+            _ => value.to_string().into(),
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+// ==========================================
+// SPANS
+// ==========================================
+
+pub struct Span {
+    level: Level,
+    // Module the span was entered in.
+    target: &'static str,
+    name: &'static str,
+    // Empty when the level is disabled, so a span costs one check.
+    fields: Vec<(&'static str, FieldValue)>,
+    started: Option<Instant>,
+}
+
+// Owned so spans can hold fields past the expression that produced them;
+// numbers stay numbers in JSON output.
+pub enum FieldValue {
+    Unsigned(u64),
+    Signed(i64),
+    Text(String),
+}
+
+impl FieldValue {
+    fn to_value(&self) -> Value<'_> {
+        match self {
+            FieldValue::Unsigned(n) => Value::from(*n),
+            FieldValue::Signed(n) => Value::from(*n),
+            FieldValue::Text(text) => Value::from(text.as_str()),
+        }
+    }
+}
+
+impl From<u64> for FieldValue {
+    fn from(n: u64) -> Self {
+        FieldValue::Unsigned(n)
+    }
+}
+
+impl From<usize> for FieldValue {
+    fn from(n: usize) -> Self {
+        FieldValue::Unsigned(n as u64)
+    }
+}
+
+impl From<i64> for FieldValue {
+    fn from(n: i64) -> Self {
+        FieldValue::Signed(n)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(text: &str) -> Self {
+        FieldValue::Text(text.to_string())
+    }
+}
+
+impl From<&String> for FieldValue {
+    fn from(text: &String) -> Self {
+        FieldValue::Text(text.clone())
+    }
+}
+
+impl Span {
+    pub fn enter(
+        level: Level,
+        target: &'static str,
+        name: &'static str,
+        fields: impl FnOnce() -> Vec<(&'static str, FieldValue)>,
+    ) -> Self {
+        let started = log::log_enabled!(target: target, level).then(Instant::now);
+        let fields = if started.is_some() { fields() } else { Vec::new() };
+        Span { level, target, name, fields, started }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(started) = self.started else {
+            return;
+        };
+        // Microsecond precision is plenty.
+        let elapsed_ms = (started.elapsed().as_secs_f64() * 1e6).round() / 1e3;
+        let mut pairs: Vec<(&str, Value)> = self.fields.iter().map(|(key, value)| (*key, value.to_value())).collect();
+        pairs.push(("elapsed_ms", Value::from(elapsed_ms)));
+        log::logger().log(
+            &Record::builder()
+                .level(self.level)
+                .target(self.target)
+                .args(format_args!("{}", self.name))
+                .key_values(&pairs)
+                .build(),
+        );
+    }
+}
+
+// `let _span = span!(Level::Debug, "parse_block", slot = slot);` logs
+// "parse_block slot=... elapsed_ms=..." when the guard drops. The fields are
+// only evaluated when the level is enabled.
+#[macro_export]
+macro_rules! span {
+    ($level:expr, $name:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        $crate::logging::Span::enter($level, module_path!(), $name, || vec![$((stringify!($key), $crate::logging::FieldValue::from($value))),*])
+    };
+}
@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+
+// ==========================================
+// LOG MESSAGE PARSER
+// ==========================================
+// Rebuilds the program invocation tree from a transaction's log_messages.
+// The runtime brackets every invocation with "Program <id> invoke [depth]"
+// and "Program <id> success" / "Program <id> failed: <err>"; everything
+// logged in between belongs to the innermost open invocation.
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProgramInvocation {
+    pub program_id: String,
+    // 1 for top-level instructions, 2+ for CPIs.
+    pub depth: usize,
+    // None when the logs end before the invocation closes (truncation).
+    pub success: Option<bool>,
+    pub error: Option<String>,
+    pub compute_units_consumed: Option<u64>,
+    pub compute_units_available: Option<u64>,
+    // "Program log: ..." messages.
+    pub logs: Vec<String>,
+    // "Program data: ..." payloads, still base64 (Anchor events live here).
+    pub data: Vec<String>,
+    // "Program return: <id> <base64>" payload.
+    pub return_data: Option<String>,
+    pub invocations: Vec<ProgramInvocation>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ParsedLogs {
+    // One entry per top-level instruction that logged an invoke.
+    pub invocations: Vec<ProgramInvocation>,
+    // The runtime stops recording after its log limit ("Log truncated").
+    pub truncated: bool,
+}
+
+// A "Program data:" payload and the program that emitted it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgramEvent {
+    pub program_id: String,
+    pub depth: usize,
+    pub data: String,
+}
+
+impl ProgramInvocation {
+    // Units spent in this program itself: the runtime's "consumed" count
+    // includes every CPI made, so what those consumed is taken off. Builtins
+    // that log no count are left in their caller's share. None when the
+    // invocation logged no count either.
+    pub fn own_compute_units(&self) -> Option<u64> {
+        let nested: u64 = self.invocations.iter().map(|child| child.nested_compute_units()).sum();
+        Some(self.compute_units_consumed?.saturating_sub(nested))
+    }
+
+    // What this invocation consumed, or when it didn't say, what the ones it
+    // invoked did.
+    fn nested_compute_units(&self) -> u64 {
+        self.compute_units_consumed.unwrap_or_else(|| self.invocations.iter().map(|child| child.nested_compute_units()).sum())
+    }
+
+    // This invocation followed by all nested ones, depth first.
+    pub fn walk(&self) -> Vec<&ProgramInvocation> {
+        let mut out = vec![self];
+        for child in &self.invocations {
+            out.extend(child.walk());
+        }
+        out
+    }
+}
+
+impl ParsedLogs {
+    pub fn walk(&self) -> Vec<&ProgramInvocation> {
+        self.invocations.iter().flat_map(|i| i.walk()).collect()
+    }
+
+    pub fn events(&self) -> Vec<ProgramEvent> {
+        self.walk()
+            .into_iter()
+            .flat_map(|invocation| {
+                invocation.data.iter().map(|data| ProgramEvent {
+                    program_id: invocation.program_id.clone(),
+                    depth: invocation.depth,
+                    data: data.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+pub fn parse_logs(log_messages: &[String]) -> ParsedLogs {
+    let mut parsed = ParsedLogs::default();
+    let mut stack: Vec<ProgramInvocation> = Vec::new();
+
+    for line in log_messages {
+        if line == "Log truncated" {
+            parsed.truncated = true;
+            continue;
+        }
+        if let Some(message) = line.strip_prefix("Program log: ") {
+            if let Some(current) = stack.last_mut() {
+                current.logs.push(message.to_string());
+            }
+            continue;
+        }
+        if let Some(data) = line.strip_prefix("Program data: ") {
+            if let Some(current) = stack.last_mut() {
+                current.data.push(data.to_string());
+            }
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("Program return: ") {
+            if let Some(current) = stack.last_mut() {
+                current.return_data = rest.split_once(' ').map(|(_, data)| data.to_string());
+            }
+            continue;
+        }
+        let Some(rest) = line.strip_prefix("Program ") else {
+            continue;
+        };
+        let Some((program_id, event)) = rest.split_once(' ') else {
+            continue;
+        };
+
+        if let Some(depth) = event.strip_prefix("invoke [").and_then(|d| d.strip_suffix(']')) {
+            stack.push(ProgramInvocation {
+                program_id: program_id.to_string(),
+                depth: depth.parse().unwrap_or(stack.len() + 1),
+                ..ProgramInvocation::default()
+            });
+        } else if let Some(units) = event.strip_prefix("consumed ") {
+            // "<n> of <m> compute units"
+            if let Some(current) = stack.last_mut() {
+                let mut numbers = units.split(' ').filter_map(|word| word.parse::<u64>().ok());
+                current.compute_units_consumed = numbers.next();
+                current.compute_units_available = numbers.next();
+            }
+        } else if event == "success" {
+            close(&mut stack, &mut parsed, true, None);
+        } else if let Some(error) = event.strip_prefix("failed: ") {
+            close(&mut stack, &mut parsed, false, Some(error.to_string()));
+        }
+    }
+
+    // Invocations still open were cut off by truncation.
+    while let Some(invocation) = stack.pop() {
+        attach(&mut stack, &mut parsed, invocation);
+    }
+    parsed
+}
+
+fn close(stack: &mut Vec<ProgramInvocation>, parsed: &mut ParsedLogs, success: bool, error: Option<String>) {
+    if let Some(mut invocation) = stack.pop() {
+        invocation.success = Some(success);
+        invocation.error = error;
+        attach(stack, parsed, invocation);
+    }
+}
+
+fn attach(stack: &mut [ProgramInvocation], parsed: &mut ParsedLogs, invocation: ProgramInvocation) {
+    match stack.last_mut() {
+        Some(parent) => parent.invocations.push(invocation),
+        None => parsed.invocations.push(invocation),
+    }
+}
+
+// ==========================================
+// LOG RETENTION
+// ==========================================
+// How much of log_messages parsed output keeps. Logs are often most of a
+// transaction's size and many consumers never read them. Events and
+// compute_by_program are taken from the full logs before this applies, so
+// they're unaffected; `parsed_logs()` on the output only sees what was kept.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogRetention {
+    #[default]
+    Keep,
+    Drop,
+    // The first n lines, followed by the runtime's own "Log truncated"
+    // marker when any were cut.
+    First(usize),
+    // Without the "Program <id> consumed <n> of <m> compute units" lines,
+    // which compute_by_program already carries.
+    Compact,
+}
+
+impl LogRetention {
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "keep" => Ok(LogRetention::Keep),
+            "drop" => Ok(LogRetention::Drop),
+            "compact" => Ok(LogRetention::Compact),
+            other => other
+                .parse()
+                .map(LogRetention::First)
+                .map_err(|_| format!("unknown log retention '{}' (expected keep, drop, compact or a line count)", other)),
+        }
+    }
+
+    pub fn apply(self, log_messages: &[String]) -> Vec<String> {
+        match self {
+            LogRetention::Keep => log_messages.to_vec(),
+            LogRetention::Drop => Vec::new(),
+            LogRetention::First(n) if log_messages.len() <= n => log_messages.to_vec(),
+            LogRetention::First(n) => {
+                let mut kept = log_messages[..n].to_vec();
+                kept.push("Log truncated".to_string());
+                kept
+            }
+            LogRetention::Compact => log_messages.iter().filter(|line| !is_compute_line(line)).cloned().collect(),
+        }
+    }
+}
+
+fn is_compute_line(line: &str) -> bool {
+    line.strip_prefix("Program ")
+        .and_then(|rest| rest.split_once(' '))
+        .is_some_and(|(program_id, event)| !program_id.ends_with(':') && event.starts_with("consumed ") && event.ends_with(" compute units"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_invocation_tree_with_events_and_failures() {
+        let logs: Vec<String> = [
+            "Program A invoke [1]",
+            "Program log: hello",
+            "Program B invoke [2]",
+            "Program data: ZXZlbnQ=",
+            "Program B consumed 100 of 200 compute units",
+            "Program return: B AQ==",
+            "Program B success",
+            "Program A consumed 300 of 400 compute units",
+            "Program A failed: custom program error: 0x1",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let parsed = parse_logs(&logs);
+        assert_eq!(parsed.invocations.len(), 1);
+
+        let a = &parsed.invocations[0];
+        assert_eq!(a.success, Some(false));
+        assert_eq!(a.error.as_deref(), Some("custom program error: 0x1"));
+        assert_eq!(a.compute_units_consumed, Some(300));
+        assert_eq!(a.logs, vec!["hello"]);
+
+        let b = &a.invocations[0];
+        assert_eq!((b.depth, b.success, b.compute_units_consumed), (2, Some(true), Some(100)));
+        assert_eq!(b.return_data.as_deref(), Some("AQ=="));
+        assert_eq!((a.own_compute_units(), b.own_compute_units()), (Some(200), Some(100)));
+
+        let events = parsed.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].program_id.as_str(), events[0].data.as_str()), ("B", "ZXZlbnQ="));
+    }
+
+    #[test]
+    fn log_retention_drops_truncates_or_compacts() {
+        let logs: Vec<String> =
+            ["Program A invoke [1]", "Program log: consumed 5 of 6 compute units", "Program A consumed 300 of 400 compute units", "Program A success"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect();
+        assert_eq!(LogRetention::from_arg("keep").unwrap().apply(&logs), logs);
+        assert!(LogRetention::from_arg("drop").unwrap().apply(&logs).is_empty());
+        assert_eq!(LogRetention::from_arg("4").unwrap().apply(&logs), logs);
+        let first = LogRetention::from_arg("1").unwrap().apply(&logs);
+        assert_eq!(first, ["Program A invoke [1]", "Log truncated"]);
+        assert!(parse_logs(&first).truncated);
+        let compact = LogRetention::from_arg("compact").unwrap().apply(&logs);
+        assert_eq!(compact, ["Program A invoke [1]", "Program log: consumed 5 of 6 compute units", "Program A success"]);
+        assert!(LogRetention::from_arg("some").is_err());
+    }
+}
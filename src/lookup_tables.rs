@@ -0,0 +1,321 @@
+use crate::metrics::metrics;
+use crate::pubkey::Pubkey;
+#[cfg(feature = "native")]
+use crate::rpc::RpcClient;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+#[cfg(feature = "native")]
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "native")]
+use std::fs::{self, File, OpenOptions};
+#[cfg(feature = "native")]
+use std::io::{ErrorKind, Write};
+#[cfg(feature = "native")]
+use std::path::Path;
+use std::sync::Mutex;
+
+// ==========================================
+// ADDRESS LOOKUP TABLE RESOLUTION
+// ==========================================
+// v0 transactions reference accounts through lookup tables. When the RPC
+// response includes meta.loadedAddresses those are used as-is; otherwise the
+// parser asks a LookupTableResolver for the table contents.
+
+// Serialized lookup tables start with a fixed 56-byte metadata header
+// (type tag, deactivation slot, last extended slot, authority); the
+// addresses follow as packed 32-byte keys.
+const LOOKUP_TABLE_META_SIZE: usize = 56;
+
+pub trait LookupTableResolver: Send + Sync + fmt::Debug {
+    // Full address list of the table, or None if it can't be found.
+    fn resolve(&self, table: &Pubkey) -> Option<Vec<Pubkey>>;
+
+    // `resolve` for a caller that needs the first `min_len` entries. Tables
+    // only grow, so a cache can keep serving a copy that long.
+    fn resolve_prefix(&self, table: &Pubkey, _min_len: usize) -> Option<Vec<Pubkey>> {
+        self.resolve(table)
+    }
+
+    // The table with its metadata; resolvers that don't have it report the
+    // table as active.
+    fn resolve_table(&self, table: &Pubkey) -> Option<LookupTable> {
+        self.resolve(table).map(|addresses| LookupTable { address: *table, deactivation_slot: u64::MAX, addresses })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LookupTable {
+    pub address: Pubkey,
+    // u64::MAX while the table is active.
+    pub deactivation_slot: u64,
+    pub addresses: Vec<Pubkey>,
+}
+
+impl LookupTable {
+    pub fn is_active(&self) -> bool {
+        self.deactivation_slot == u64::MAX
+    }
+}
+
+// Fixed set of tables, e.g. loaded from a previous run or a test fixture.
+#[derive(Debug, Default, Clone)]
+pub struct StaticLookupTables {
+    tables: HashMap<Pubkey, Vec<Pubkey>>,
+}
+
+impl StaticLookupTables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, table: Pubkey, addresses: Vec<Pubkey>) {
+        self.tables.insert(table, addresses);
+    }
+}
+
+impl LookupTableResolver for StaticLookupTables {
+    fn resolve(&self, table: &Pubkey) -> Option<Vec<Pubkey>> {
+        self.tables.get(table).cloned()
+    }
+}
+
+// Fetches each table's account data with getAccountInfo.
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct RpcLookupTableResolver {
+    client: RpcClient,
+}
+
+#[cfg(feature = "native")]
+impl RpcLookupTableResolver {
+    pub fn new(client: RpcClient) -> Self {
+        RpcLookupTableResolver { client }
+    }
+}
+
+#[cfg(feature = "native")]
+impl LookupTableResolver for RpcLookupTableResolver {
+    fn resolve(&self, table: &Pubkey) -> Option<Vec<Pubkey>> {
+        self.resolve_table(table).map(|table| table.addresses)
+    }
+
+    fn resolve_table(&self, table: &Pubkey) -> Option<LookupTable> {
+        let data = self.client.get_account_data(&table.to_string()).ok()??;
+        decode_lookup_table_account(*table, &data)
+    }
+}
+
+// The address list and deactivation slot (bytes 4..12 of the header).
+pub fn decode_lookup_table_account(address: Pubkey, data: &[u8]) -> Option<LookupTable> {
+    let deactivation_slot = u64::from_le_bytes(data.get(4..12)?.try_into().ok()?);
+    Some(LookupTable { address, deactivation_slot, addresses: decode_lookup_table(data)? })
+}
+
+// Decode the address list out of raw lookup table account data.
+pub fn decode_lookup_table(data: &[u8]) -> Option<Vec<Pubkey>> {
+    let addresses = data.get(LOOKUP_TABLE_META_SIZE..)?;
+    if addresses.len() % 32 != 0 {
+        return None;
+    }
+    addresses
+        .chunks_exact(32)
+        .map(|key| Pubkey::try_from(key).ok())
+        .collect()
+}
+
+// ==========================================
+// LOOKUP TABLE CACHE
+// ==========================================
+// Each fetch is a getAccountInfo round trip, and the same few hundred
+// tables come up block after block, so uncached they dominate parse
+// latency. CachedLookupTables keeps the `capacity` most recently used
+// tables in memory and, with `with_file`, appends every table it fetches to
+// a JSON-lines file that seeds the cache on the next run. A table a lookup
+// needs more entries of than the cached copy has was extended since, and is
+// fetched again; one that can no longer be fetched (deactivated, then
+// closed) keeps being served from the cache, which is what makes the file
+// worth keeping for older blocks. Hits and misses are counted in metrics.
+
+pub const DEFAULT_CACHE_CAPACITY: usize = 1024;
+
+#[derive(Debug)]
+pub struct CachedLookupTables<R> {
+    inner: R,
+    capacity: usize,
+    cache: Mutex<LruTables>,
+    #[cfg(feature = "native")]
+    file: Option<Mutex<File>>,
+}
+
+// Least recently used goes first; `clock` stamps each use.
+#[derive(Debug, Default)]
+struct LruTables {
+    tables: HashMap<Pubkey, (LookupTable, u64)>,
+    clock: u64,
+}
+
+impl LruTables {
+    fn get(&mut self, table: &Pubkey) -> Option<&LookupTable> {
+        self.clock += 1;
+        let (table, used) = self.tables.get_mut(table)?;
+        *used = self.clock;
+        Some(table)
+    }
+
+    fn insert(&mut self, table: LookupTable, capacity: usize) {
+        self.clock += 1;
+        self.tables.insert(table.address, (table, self.clock));
+        while self.tables.len() > capacity {
+            let Some(oldest) = self.tables.iter().min_by_key(|(_, (_, used))| *used).map(|(address, _)| *address) else {
+                break;
+            };
+            self.tables.remove(&oldest);
+        }
+    }
+}
+
+impl<R: LookupTableResolver> CachedLookupTables<R> {
+    pub fn new(inner: R, capacity: usize) -> Self {
+        CachedLookupTables {
+            inner,
+            capacity: capacity.max(1),
+            cache: Mutex::new(LruTables::default()),
+            #[cfg(feature = "native")]
+            file: None,
+        }
+    }
+
+    // Loads the tables saved in `path` (later lines win) and appends newly
+    // fetched ones to it.
+    #[cfg(feature = "native")]
+    pub fn with_file(mut self, path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("failed to read lookup table cache {}: {}", path.display(), e).into()),
+        };
+        let cache = self.cache.get_mut().unwrap();
+        for (number, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let table: LookupTable = serde_json::from_str(line)
+                .map_err(|e| format!("invalid lookup table cache {} line {}: {}", path.display(), number + 1, e))?;
+            cache.insert(table, self.capacity);
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("failed to open lookup table cache {}: {}", path.display(), e))?;
+        self.file = Some(Mutex::new(file));
+        Ok(self)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // A failed write only costs a refetch next run.
+    #[cfg(feature = "native")]
+    fn save(&self, table: &LookupTable) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let result = serde_json::to_string(table)
+            .map_err(std::io::Error::from)
+            .and_then(|line| writeln!(file.lock().unwrap(), "{}", line));
+        if let Err(e) = result {
+            log::warn!(table:% = table.address; "failed to save lookup table: {}", e);
+        }
+    }
+
+    #[cfg(not(feature = "native"))]
+    fn save(&self, _table: &LookupTable) {}
+}
+
+impl<R: LookupTableResolver> LookupTableResolver for CachedLookupTables<R> {
+    fn resolve(&self, table: &Pubkey) -> Option<Vec<Pubkey>> {
+        self.resolve_prefix(table, 0)
+    }
+
+    fn resolve_prefix(&self, table: &Pubkey, min_len: usize) -> Option<Vec<Pubkey>> {
+        self.resolve_table_prefix(table, min_len).map(|table| table.addresses)
+    }
+
+    fn resolve_table(&self, table: &Pubkey) -> Option<LookupTable> {
+        self.resolve_table_prefix(table, 0)
+    }
+}
+
+impl<R: LookupTableResolver> CachedLookupTables<R> {
+    fn resolve_table_prefix(&self, table: &Pubkey, min_len: usize) -> Option<LookupTable> {
+        // The lock isn't held across the fetch, so parser threads don't wait
+        // on each other's round trips.
+        let cached = self.cache.lock().unwrap().get(table).cloned();
+        if let Some(cached) = cached.as_ref().filter(|cached| cached.addresses.len() >= min_len) {
+            metrics().record_lookup_table_cache(true);
+            return Some(cached.clone());
+        }
+        metrics().record_lookup_table_cache(false);
+        let Some(fetched) = self.inner.resolve_table(table) else {
+            return cached;
+        };
+        self.save(&fetched);
+        self.cache.lock().unwrap().insert(fetched.clone(), self.capacity);
+        Some(fetched)
+    }
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Serves tables of `len` placeholder addresses, counting fetches.
+    #[derive(Debug, Default)]
+    struct CountingResolver {
+        fetches: AtomicUsize,
+        len: AtomicUsize,
+    }
+
+    impl LookupTableResolver for CountingResolver {
+        fn resolve(&self, _table: &Pubkey) -> Option<Vec<Pubkey>> {
+            self.fetches.fetch_add(1, Ordering::Relaxed);
+            Some(vec![Pubkey::default(); self.len.load(Ordering::Relaxed)])
+        }
+    }
+
+    #[test]
+    fn caches_recent_tables_and_refetches_extended_ones() {
+        let (a, b) = (Pubkey::new([1; 32]), Pubkey::new([2; 32]));
+        let inner = CountingResolver::default();
+        inner.len.store(2, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("phase1-lookup-tables-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let cache = CachedLookupTables::new(inner, 1).with_file(&path).unwrap();
+        let fetches = |cache: &CachedLookupTables<CountingResolver>| cache.inner.fetches.load(Ordering::Relaxed);
+
+        assert_eq!(cache.resolve(&a).map(|table| table.len()), Some(2));
+        assert_eq!(cache.resolve_prefix(&a, 2).map(|table| table.len()), Some(2));
+        assert_eq!(fetches(&cache), 1);
+        // The table grew; a lookup past the cached copy refetches it.
+        cache.inner.len.store(3, Ordering::Relaxed);
+        assert_eq!(cache.resolve_prefix(&a, 3).map(|table| table.len()), Some(3));
+        assert_eq!(fetches(&cache), 2);
+        // Capacity 1: b evicts a.
+        cache.resolve(&b);
+        cache.resolve(&a);
+        assert_eq!((fetches(&cache), cache.len()), (4, 1));
+
+        // The next run starts with the saved tables, the latest copy of a
+        // last and so kept.
+        let reloaded = CachedLookupTables::new(CountingResolver::default(), 8).with_file(&path).unwrap();
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(reloaded.resolve_table(&a).map(|table| (table.addresses.len(), table.is_active())), Some((3, true)));
+        assert_eq!(fetches(&reloaded), 0);
+        fs::remove_file(&path).unwrap();
+    }
+}
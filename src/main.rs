@@ -1,342 +1,1825 @@
-use serde::{Deserialize, Serialize};
+mod cli;
+
+use cli::{BlockSource, Cli, Command, InputSource, OutputFormat, SchemaKind, SubscriptionKind, BlockInputs, USAGE};
+use phase_1_connect::aggregate::Aggregator;
+use phase_1_connect::backfill::{Backfill, BackfillItem, GapReport};
+use phase_1_connect::binary::{BinaryReader, ParsedRecord};
+use phase_1_connect::checkpoint::Checkpoint;
+use phase_1_connect::compress;
+use phase_1_connect::consensus::block_consensus;
+use phase_1_connect::dedup::{DuplicateFilter, DuplicatePolicy};
+use phase_1_connect::config;
+use phase_1_connect::decoders::{AnchorDecoder, DeclarativeDecoder};
+use phase_1_connect::dex::{detect_pool_updates, detect_swap};
+use phase_1_connect::diff::{diff_blocks, BlockDiff};
+use phase_1_connect::failure::FailureCategory;
+use phase_1_connect::export::{parquet, AccountEventExporter, BinaryExporter, CsvExporter, JsonExporter, NdjsonExporter, ParquetExporter};
+use phase_1_connect::geyser::GeyserClient;
+use phase_1_connect::graphql::{Database, GraphQl};
+use phase_1_connect::history::AddressHistory;
+use phase_1_connect::logging;
+use phase_1_connect::lookup_tables::{CachedLookupTables, DEFAULT_CACHE_CAPACITY, RpcLookupTableResolver};
+use phase_1_connect::metrics;
+use phase_1_connect::mev::{detect_sandwiches, Sandwich, SandwichSwap};
+use phase_1_connect::pubkey::Pubkey;
+use phase_1_connect::pubsub::{ws_url_for, PubsubClient};
+use phase_1_connect::reorg::{ChainEvent, ConfirmationTracker};
+use phase_1_connect::sink::{ClickHouseSink, FanOut, Idempotent, KafkaSink, Manifest, PostgresSink, Sink, SqliteSink, WebhookSink};
+use phase_1_connect::rpc::{AccountSnapshots, Commitment, SlotLeaders};
+use phase_1_connect::schema;
+use phase_1_connect::server::ApiServer;
+use phase_1_connect::shutdown;
+use phase_1_connect::spill::SpillDir;
+use phase_1_connect::source::{block_files, BlockFile, BlockSource as _, DirectorySource, FileSource, ReaderSource, RpcSource};
+use phase_1_connect::cost::{block_cost, BlockLimits};
+use phase_1_connect::stats::{block_stats, LeaderTally, Percentiles, ProgramLeaderboard, ProgramTally};
+use phase_1_connect::stream::{BlockStream, SlotOrder, StreamedBlock};
+use phase_1_connect::summary::RunSummary;
+use phase_1_connect::timestamps::TimestampMode;
+use phase_1_connect::token_owners::RpcTokenOwnerResolver;
+use phase_1_connect::tokens::TokenRegistry;
+use phase_1_connect::transfers::format_ui_amount;
+use phase_1_connect::validate::{ValidationReport, Validator};
+use phase_1_connect::wallet::WalletReport;
+use phase_1_connect::{
+    load_block_file, load_from_json, parse_block_with_options, parse_input, read_parsed, parse_transaction_result,
+    BlockFetch, DecoderRegistry, InputKind, LabelRegistry, ParseOptions, ParseReport, ParsedBlock, ParsedInput, ParsedTransaction,
+    RpcClient, RpcResponse, TransactionEncoding,
+};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::env;
 use std::error::Error;
-use std::fs::File;
-use std::io::BufReader;
+use std::fs;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 // ==========================================
-// DESTINATION STRUCTS
+// MAIN LOGIC
 // ==========================================
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ParsedBlock {
-    pub block_height: u64,
-    pub block_time: i64,
-    pub blockhash: String,
-    pub parent_slot: u64,
-    pub previous_blockhash: String,
-    pub rewards: Vec<BlockReward>,
-    pub transactions: Vec<ParsedTransaction>,
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct BlockReward {
-    pub pubkey: String,
-    pub lamports: i64,
-    pub post_balance: u64,
-    pub reward_type: String,
-    pub commission: Option<u8>,
+fn main() {
+    let cli = match Cli::parse(env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    logging::init(cli.log_level, cli.log_format);
+    shutdown::install();
+    let (started_at, started) = (SystemTime::now(), Instant::now());
+
+    let result = match &cli.command {
+        Command::ParseTx { path } => run_parse_tx(path, &cli),
+        Command::ParseBlock { path } => run_parse_block(path, &cli),
+        Command::ReadParsed { path } => run_read_parsed(path, &cli),
+        Command::ParseDir { dir } => run_parse_dir(dir, &cli),
+        Command::Parse { input } => run_parse(input, &cli),
+        Command::Validate { input } => run_validate(input, &cli),
+        Command::FetchBlock { slot } => run_fetch_block(*slot, &cli),
+        Command::ParseSig { signature } => run_parse_sig(signature, &cli),
+        Command::AddressHistory { address } => run_address_history(*address, &cli),
+        Command::Stream { start, end } => run_stream(*start, *end, &cli),
+        Command::Backfill { start, end } => run_backfill(*start, *end, &cli),
+        Command::Subscribe { kind, mentions } => run_subscribe(*kind, mentions.as_deref(), &cli),
+        Command::Diff { left, right } => run_diff(left, right, &cli),
+        Command::WalletReport { address, blocks } => run_wallet_report(*address, blocks, &cli),
+        Command::TopPrograms { blocks } => run_top_programs(blocks, &cli),
+        Command::Sandwiches { blocks } => run_sandwiches(blocks, &cli),
+        Command::Serve { addr } => run_serve(addr, &cli),
+        Command::Schema { kind } => run_schema(*kind),
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+    };
+
+    if let Some(path) = &cli.summary {
+        let summary = RunSummary::collect(cli.command.name(), metrics::metrics(), started_at, started.elapsed(), &result);
+        if let Err(e) = summary.write(path) {
+            log::error!("{}", e);
+        }
+    }
+    if let Err(e) = result {
+        log::error!("{}", e);
+        process::exit(1);
+    }
+    // Stopped early, even if cleanly: the range or input wasn't covered.
+    if shutdown::requested() {
+        log::warn!("interrupted; outputs flushed");
+        process::exit(shutdown::INTERRUPTED_EXIT_STATUS);
+    }
+}
+
+fn rpc_client(cli: &Cli) -> RpcClient {
+    let mut client = RpcClient::new(&cli.rpc_url)
+        .with_commitment(cli.commitment)
+        .with_max_supported_transaction_version(cli.max_transaction_version)
+        .with_transaction_details(cli.transaction_details)
+        .with_rewards(cli.rewards)
+        .with_retry(cli.retry);
+    // Verify against the bytes the node holds rather than a rebuilt message.
+    match cli.encoding {
+        Some(encoding) => client = client.with_transaction_encoding(encoding),
+        None if cli.verify_signatures => client = client.with_transaction_encoding(TransactionEncoding::Base64),
+        None => {}
+    }
+    if let Some(filter) = &cli.filter {
+        client = client.with_prefilter(Arc::new(filter.clone()));
+    }
+    if let Some(pool) = &cli.endpoint_pool {
+        client = client.with_pool(pool.clone());
+    }
+    match &cli.rate_limiter {
+        Some(limiter) => client.with_rate_limiter(limiter.clone()),
+        None => client,
+    }
+}
+
+// Only with --fetch-leaders.
+fn slot_leaders(cli: &Cli) -> Option<SlotLeaders> {
+    cli.fetch_leaders.then(|| SlotLeaders::new(rpc_client(cli)))
+}
+
+// Blocks carrying no fee reward (rewards disabled, or no fees collected)
+// get their leader from getSlotLeaders.
+fn fill_leader(block: &mut ParsedBlock, slot: u64, leaders: &mut Option<SlotLeaders>) {
+    if block.leader.is_some() {
+        return;
+    }
+    if let Some(leaders) = leaders {
+        match leaders.leader(slot) {
+            Ok(leader) => block.leader = leader,
+            Err(e) => log::warn!(slot, error:% = e; "failed to look up the slot leader"),
+        }
+    }
+}
+
+// Only with --snapshot-account.
+fn account_snapshots(cli: &Cli) -> Option<AccountSnapshots> {
+    (!cli.snapshot_accounts.is_empty()).then(|| {
+        AccountSnapshots::new(rpc_client(cli), cli.snapshot_accounts.iter().copied()).with_data_encoding(cli.data_encoding)
+    })
+}
+
+// Freshly fetched or streamed blocks get the state of the selected accounts
+// they wrote. A failed read leaves the block without snapshots.
+fn fill_snapshots(block: &mut ParsedBlock, snapshots: &mut Option<AccountSnapshots>) {
+    if let Some(snapshots) = snapshots
+        && let Err(e) = snapshots.snapshot(block)
+    {
+        log::warn!(slot = block.slot, error:% = e; "failed to snapshot accounts");
+    }
+}
+
+fn parse_options(cli: &Cli) -> Result<ParseOptions, Box<dyn Error>> {
+    let mut options = ParseOptions {
+        threads: cli.threads,
+        strict: cli.strict,
+        verify_signatures: cli.verify_signatures,
+        data_encoding: cli.data_encoding,
+        log_retention: cli.log_retention,
+        input_data_encoding: cli.input_data_encoding,
+        epoch_schedule: cli.epoch_schedule,
+        filter: cli.filter.clone().map(Arc::new),
+        ..ParseOptions::default()
+    };
+    if cli.fetch_lookup_tables {
+        let mut cache = CachedLookupTables::new(RpcLookupTableResolver::new(rpc_client(cli)), DEFAULT_CACHE_CAPACITY);
+        if let Some(path) = &cli.lookup_table_cache {
+            cache = cache.with_file(path)?;
+        }
+        options.lookup_tables = Some(Arc::new(cache));
+    }
+    if cli.fetch_token_owners {
+        options.token_owners = Some(Arc::new(RpcTokenOwnerResolver::new(rpc_client(cli))));
+    }
+    if !cli.idl_paths.is_empty() || !cli.decoders.is_empty() || !cli.decoder_files.is_empty() {
+        let mut decoders = DecoderRegistry::with_builtins();
+        for path in &cli.idl_paths {
+            decoders.register(Box::new(AnchorDecoder::from_idl_file(path)?));
+        }
+        // After the IDLs, so a declarative decoder replaces one for the same program.
+        let mut specs = cli.decoders.clone();
+        for path in &cli.decoder_files {
+            specs.extend(config::load_decoders(Path::new(path))?);
+        }
+        for spec in specs {
+            decoders.register(Box::new(DeclarativeDecoder::new(spec)?));
+        }
+        options.decoders = Arc::new(decoders);
+    }
+    if cli.labels {
+        options.labels = Some(Arc::new(label_registry(cli)?));
+    }
+    Ok(options)
+}
+
+// The built-in labels plus --labels-file.
+fn label_registry(cli: &Cli) -> Result<LabelRegistry, Box<dyn Error>> {
+    match &cli.labels_file {
+        Some(path) => LabelRegistry::builtins().with_file(path),
+        None => Ok(LabelRegistry::builtins()),
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ParsedTransaction {
-    pub signature: String,
-    pub fee_payer: String,
-    pub is_success: bool,
-    pub account_keys: Vec<String>,
-    pub instructions: Vec<ParsedInstruction>,
-    pub log_messages: Vec<String>,
-    pub pre_balances: Vec<u64>,
-    pub post_balances: Vec<u64>,
-    pub fee: u64,
-    pub compute_units_consumed: Option<u64>,
+// The built-in token list plus --token-list, reading mint accounts with
+// --fetch-token-decimals.
+fn token_registry(cli: &Cli) -> Result<TokenRegistry, Box<dyn Error>> {
+    let mut tokens = TokenRegistry::builtins();
+    if let Some(path) = &cli.token_list {
+        tokens = tokens.with_file(path)?;
+    }
+    if cli.fetch_token_decimals {
+        tokens = tokens.with_rpc(rpc_client(cli));
+    }
+    Ok(tokens)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ParsedInstruction {
-    pub program_id: String,
-    pub accounts: Vec<String>,
-    pub data: String,
+fn log_warnings(report: &ParseReport) {
+    if !report.warnings.is_empty() {
+        log::warn!(count = report.warnings.len(); "parse warnings");
+    }
+    for warning in &report.warnings {
+        log::debug!("{:?}", warning);
+    }
 }
 
 // ==========================================
-// RAW BLOCK STRUCTS (RPC Input Format)
+// OUTPUT PIPELINE
 // ==========================================
-#[derive(Debug, Deserialize)]
-pub struct RpcBlockResponse {
-    pub result: RpcBlockResult,
+// The selected output format plus any database sinks, written to together
+// through a FanOut. Pretty summaries are printed here; everything else is a
+// Sink. One-shot commands print pretty JSON; streaming commands print one
+// compact object per line.
+
+struct Outputs {
+    format: OutputFormat,
+    sinks: FanOut,
+    // Names shown next to addresses in pretty summaries.
+    labels: LabelRegistry,
+    leaders: LeaderTally,
+    leader_stats: Option<PathBuf>,
+    // Only for in-order streams below finalized commitment.
+    finality: Option<Finality>,
+    rollup: Option<RollupOutput>,
+    // --dedup: applied before anything else sees a block.
+    duplicates: Option<DuplicateFilter>,
+}
+
+impl Outputs {
+    fn new(cli: &Cli, streaming: bool) -> Result<Self, Box<dyn Error>> {
+        let mut sinks = FanOut::new();
+        // A manifest only makes sense for output that keeps what's there.
+        let append = cli.resume || cli.manifest.is_some();
+        let format_sink: Option<Box<dyn Sink>> = match (cli.output, &cli.out_file) {
+            (OutputFormat::Json, _) => Some(Box::new(projected_json(JsonExporter::stdout(!streaming), cli))),
+            (OutputFormat::Ndjson, Some(path)) if append => Some(Box::new(projected_ndjson(NdjsonExporter::append(path, cli.compression)?, cli))),
+            (OutputFormat::Ndjson, Some(path)) => Some(Box::new(projected_ndjson(NdjsonExporter::create(path, cli.compression)?, cli))),
+            (OutputFormat::Ndjson, None) => Some(Box::new(projected_ndjson(NdjsonExporter::stdout(), cli))),
+            (OutputFormat::Csv, _) if append => Some(Box::new(CsvExporter::append(&cli.out_dir, cli.compression)?)),
+            (OutputFormat::Csv, _) => Some(Box::new(CsvExporter::create(&cli.out_dir, cli.compression)?)),
+            (OutputFormat::Parquet, _) => Some(Box::new(ParquetExporter::create(&cli.out_dir)?)),
+            (OutputFormat::Binary(format), Some(path)) if append => Some(Box::new(BinaryExporter::append(path, format, cli.compression)?)),
+            (OutputFormat::Binary(format), Some(path)) => Some(Box::new(BinaryExporter::create(path, format, cli.compression)?)),
+            (OutputFormat::Binary(format), None) => Some(Box::new(BinaryExporter::stdout(format)?)),
+            (OutputFormat::AccountEvents, Some(path)) if append => Some(Box::new(AccountEventExporter::append(path, cli.compression)?)),
+            (OutputFormat::AccountEvents, Some(path)) => Some(Box::new(AccountEventExporter::create(path, cli.compression)?)),
+            (OutputFormat::AccountEvents, None) => Some(Box::new(AccountEventExporter::stdout())),
+            (OutputFormat::Pretty, _) => None,
+        };
+        match (format_sink, &cli.manifest) {
+            (Some(sink), Some(path)) => sinks.push_timestamped(Box::new(Idempotent::new(sink, Manifest::open(path)?)), timestamps(cli, "output")),
+            (Some(sink), None) => sinks.push_timestamped(sink, timestamps(cli, "output")),
+            (None, _) => {}
+        }
+        if let Some(sink) = postgres_sink(cli)? {
+            sinks.push_timestamped(Box::new(sink), timestamps(cli, "postgres"));
+        }
+        if let Some(sink) = sqlite_sink(cli)? {
+            sinks.push_timestamped(Box::new(sink), timestamps(cli, "sqlite"));
+        }
+        if let Some(sink) = clickhouse_sink(cli)? {
+            sinks.push_timestamped(Box::new(sink), timestamps(cli, "clickhouse"));
+        }
+        if let Some(sink) = kafka_sink(cli) {
+            sinks.push_timestamped(Box::new(sink), timestamps(cli, "kafka"));
+        }
+        if let Some(sink) = webhook_sink(cli) {
+            sinks.push_timestamped(Box::new(sink), timestamps(cli, "webhook"));
+        }
+        let labels = match cli.output {
+            OutputFormat::Pretty => label_registry(cli)?,
+            _ => LabelRegistry::new(),
+        };
+        Ok(Outputs { format: cli.output, sinks, labels, leaders: LeaderTally::new(), leader_stats: cli.leader_stats.clone(),
+            finality: None,
+            rollup: RollupOutput::new(cli)?,
+            duplicates: cli.dedup.map(|policy| DuplicateFilter::new(policy, cli.dedup_window)),
+        })
+    }
+
+    // Tracks written blocks per --confirmation-depth; blocks must then be
+    // written in slot order.
+    fn with_finality(mut self, cli: &Cli) -> Result<Self, Box<dyn Error>> {
+        self.finality = Finality::new(cli)?;
+        Ok(self)
+    }
+
+    fn write_transaction(&mut self, tx: &ParsedTransaction) -> Result<(), Box<dyn Error>> {
+        if self.duplicates.as_mut().is_some_and(|duplicates| !duplicates.admit(tx)) {
+            return Ok(());
+        }
+        if self.format == OutputFormat::Pretty {
+            print_transaction_summary(tx, &self.labels);
+        }
+        self.sinks.write_transactions(std::slice::from_ref(tx))
+    }
+
+    // With finality tracking, retractions the block implies are written
+    // before it and blocks it settles after it, each retracted block
+    // followed by the finalized block that replaced it, if any.
+    fn write_block(&mut self, slot: Option<u64>, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        let block = match &mut self.duplicates {
+            Some(duplicates) => match duplicates.filter_block(block) {
+                Some(block) => block,
+                None => return Ok(()),
+            },
+            None => Cow::Borrowed(block),
+        };
+        let block = block.as_ref();
+        let retracted = self.finality.as_mut().map(|finality| finality.tracker.record(block)).unwrap_or_default();
+        for event in &retracted {
+            self.write_chain_event(event)?;
+        }
+        if self.format == OutputFormat::Pretty {
+            if let Some(slot) = slot {
+                println!("Slot {}", slot);
+            }
+            print_block_summary(block, &self.labels);
+        }
+        self.record_block(block)?;
+        let settled = self.finality.as_mut().map(Finality::settle).unwrap_or_default();
+        for (event, replacement) in &settled {
+            self.write_chain_event(event)?;
+            if let Some(replacement) = replacement {
+                log::info!(slot = replacement.slot, blockhash = replacement.blockhash.as_str(); "writing the finalized block");
+                if self.format == OutputFormat::Pretty {
+                    println!("Slot {} (finalized replacement)", replacement.slot);
+                    print_block_summary(replacement, &self.labels);
+                }
+                self.record_block(replacement)?;
+                self.write_chain_event(&ChainEvent::Finalized { slot: replacement.slot, blockhash: replacement.blockhash.clone() })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn record_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        self.leaders.record(block);
+        if let Some(rollup) = &mut self.rollup {
+            rollup.record(block)?;
+        }
+        self.sinks.write_block(block)
+    }
+
+    fn write_chain_event(&mut self, event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        match event {
+            ChainEvent::Finalized { slot, blockhash } => log::debug!(slot = *slot, blockhash = blockhash.as_str(); "block finalized"),
+            ChainEvent::Retracted { slot, blockhash } => log::warn!(slot = *slot, blockhash = blockhash.as_str(); "block orphaned, retracting"),
+        }
+        if self.format == OutputFormat::Pretty {
+            match event {
+                ChainEvent::Finalized { slot, .. } => println!("Slot {} finalized", slot),
+                ChainEvent::Retracted { slot, blockhash } => println!("Slot {} retracted: block {} was orphaned", slot, blockhash),
+            }
+        }
+        self.sinks.write_chain_event(event)
+    }
+
+    // Pushes out anything the batching sinks are holding; the other outputs
+    // already flush after every block.
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.sinks.flush()
+    }
+
+    fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(finality) = &self.finality
+            && finality.tracker.pending() > 0
+        {
+            log::info!(blocks = finality.tracker.pending(); "stopping with blocks not yet finalized");
+        }
+        if let Some(duplicates) = &self.duplicates
+            && duplicates.duplicate_transactions() + duplicates.duplicate_blocks() > 0
+        {
+            let (blocks, transactions) = (duplicates.duplicate_blocks(), duplicates.duplicate_transactions());
+            let action = match duplicates.policy() {
+                DuplicatePolicy::Skip => "skipped",
+                DuplicatePolicy::Flag => "written anyway",
+            };
+            log::info!(blocks, transactions; "duplicates seen in this run, {}", action);
+            if self.format == OutputFormat::Pretty {
+                println!("Duplicates: {} transactions, {} whole blocks ({})", transactions, blocks, action);
+            }
+        }
+        self.leaders.sort();
+        if self.format == OutputFormat::Pretty && self.leaders.blocks() > 1 {
+            print_leader_stats(&self.leaders, &self.labels);
+        }
+        if let Some(path) = &self.leader_stats {
+            fs::write(path, serde_json::to_string_pretty(&self.leaders)? + "\n")
+                .map_err(|e| format!("failed to write leader stats {}: {}", path.display(), e))?;
+        }
+        if let Some(rollup) = &self.rollup {
+            rollup.write()?;
+        }
+        Box::new(self.sinks).finish()
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct RpcBlockResult {
-    #[serde(rename = "blockHeight")]
-    pub block_height: u64,
-    #[serde(rename = "blockTime")]
-    pub block_time: i64,
-    pub blockhash: String,
-    #[serde(rename = "parentSlot")]
-    pub parent_slot: u64,
-    #[serde(rename = "previousBlockhash")]
-    pub previous_blockhash: String,
-    pub rewards: Vec<RpcReward>,
-    pub transactions: Vec<RpcBlockTransaction>,
+// --timestamps for the sink named `name` (see cli::TIMESTAMP_SINKS).
+fn timestamps(cli: &Cli, name: &str) -> TimestampMode {
+    cli.timestamps.get(name).copied().unwrap_or_default()
 }
 
-#[derive(Debug, Deserialize)]
-pub struct RpcReward {
-    pub pubkey: String,
-    pub lamports: i64,
-    #[serde(rename = "postBalance")]
-    pub post_balance: u64,
-    #[serde(rename = "rewardType")]
-    pub reward_type: String,
-    pub commission: Option<u8>,
+// ==========================================
+// ROLLUPS
+// ==========================================
+// --rollup: periodic totals for the run (see aggregate). Stream and
+// subscribe see blocks roughly in block time order, so their periods close
+// a little behind the newest block and the file is rewritten as each one
+// does; other commands write it once at the end, holding every period open
+// until then, so --memory-budget lets their fee payer sets spill to disk.
+
+const ROLLUP_GRACE_SECS: i64 = 120;
+
+struct RollupOutput {
+    aggregator: Aggregator,
+    path: PathBuf,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct RpcBlockTransaction {
-    pub meta: RpcMeta,
-    pub transaction: RpcTransactionContainer,
+impl RollupOutput {
+    fn new(cli: &Cli) -> Result<Option<Self>, Box<dyn Error>> {
+        let Some(path) = cli.rollup.clone() else {
+            return Ok(None);
+        };
+        let mut aggregator = Aggregator::new(cli.rollup_period);
+        if matches!(cli.command, Command::Stream { .. } | Command::Subscribe { .. }) {
+            aggregator = aggregator.with_grace(ROLLUP_GRACE_SECS);
+        }
+        if let Some(budget) = cli.memory_budget {
+            let parent = cli.spill_dir.clone().unwrap_or_else(env::temp_dir);
+            let spill = SpillDir::create(&parent).map_err(|e| format!("spill directory {}: {}", parent.display(), e))?;
+            log::debug!(dir:% = spill.path().display(), budget; "rollup fee payers spill past the memory budget");
+            aggregator = aggregator.with_memory_budget(budget, Box::new(spill));
+        }
+        Ok(Some(RollupOutput { aggregator, path }))
+    }
+
+    fn record(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        if self.aggregator.record_block(block).map_err(|e| format!("rollup spill: {}", e))? {
+            self.write()?;
+        }
+        Ok(())
+    }
+
+    // CSV for a .csv path, with the program rows in <name>.programs.csv;
+    // JSON otherwise.
+    fn write(&self) -> Result<(), Box<dyn Error>> {
+        let report = self.aggregator.report().map_err(|e| format!("rollup spill: {}", e))?;
+        let failed = |e: std::io::Error| format!("failed to write rollups {}: {}", self.path.display(), e);
+        if self.path.extension().is_some_and(|extension| extension == "csv") {
+            let (mut periods, mut programs) = (Vec::new(), Vec::new());
+            report.write_csv(&mut periods, &mut programs)?;
+            fs::write(&self.path, periods).map_err(failed)?;
+            fs::write(self.path.with_extension("programs.csv"), programs).map_err(failed)?;
+        } else {
+            fs::write(&self.path, serde_json::to_string_pretty(&report)? + "\n").map_err(failed)?;
+        }
+        Ok(())
+    }
 }
 
 // ==========================================
-// RAW TRANSACTION STRUCTS (RPC Input)
+// CONFIRMATION DEPTH
 // ==========================================
+// Below finalized commitment, every block written is tracked (see reorg).
+// Once the oldest is --confirmation-depth slots deep, pending blocks up to
+// the finalized slot are looked up at finalized commitment and settled. A
+// failed lookup leaves the rest for the next block. Where a different block
+// was finalized, it's fetched and parsed so it can be written in place of
+// the retracted one; if that fails, the retraction is written alone.
+
+struct Finality {
+    tracker: ConfirmationTracker,
+    // At finalized commitment.
+    client: RpcClient,
+    options: ParseOptions,
+    leaders: Option<SlotLeaders>,
+}
+
+impl Finality {
+    fn new(cli: &Cli) -> Result<Option<Self>, Box<dyn Error>> {
+        let Some(depth) = cli.confirmation_depth else {
+            return Ok(None);
+        };
+        if cli.commitment == Commitment::Finalized {
+            log::warn!("--confirmation-depth has no effect at finalized commitment");
+            return Ok(None);
+        }
+        Ok(Some(Finality {
+            tracker: ConfirmationTracker::new(depth),
+            client: rpc_client(cli).with_commitment(Commitment::Finalized),
+            options: parse_options(cli)?,
+            leaders: slot_leaders(cli),
+        }))
+    }
 
-#[derive(Debug, Deserialize)]
-pub struct RpcResponse {
-    pub result: RpcResult,
+    // Settled blocks' events, each with the finalized block replacing it.
+    fn settle(&mut self) -> Vec<(ChainEvent, Option<ParsedBlock>)> {
+        let mut events = Vec::new();
+        if !self.tracker.is_due() {
+            return events;
+        }
+        let finalized_slot = match self.client.get_slot() {
+            Ok(slot) => slot,
+            Err(e) => {
+                log::warn!("failed to get the finalized slot: {}", e);
+                return events;
+            }
+        };
+        for slot in self.tracker.due_slots(finalized_slot) {
+            match self.client.get_blockhash(slot) {
+                Ok(blockhash) => {
+                    let Some(settlement) = self.tracker.resolve(slot, blockhash.as_deref()) else {
+                        continue;
+                    };
+                    let replacement = settlement.replacement.and_then(|blockhash| self.replacement(slot, &blockhash));
+                    events.push((settlement.event, replacement));
+                }
+                Err(e) => {
+                    log::warn!(slot; "failed to check the finalized chain: {}", e);
+                    break;
+                }
+            }
+        }
+        events
+    }
+
+    // The finalized block at `slot`, if it's still the one named `blockhash`.
+    fn replacement(&mut self, slot: u64, blockhash: &str) -> Option<ParsedBlock> {
+        let fetched = match self.client.fetch_block(slot) {
+            Ok(BlockFetch::Block(raw_block)) => parse_block_with_options(raw_block, &self.options).map_err(|e| e.to_string()),
+            Ok(_) => Err("no block at finalized commitment".to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match fetched {
+            Ok((mut block, report)) if block.blockhash == blockhash => {
+                block.set_slot(slot, &self.options.epoch_schedule);
+                fill_leader(&mut block, slot, &mut self.leaders);
+                log_warnings(&report);
+                Some(block)
+            }
+            Ok((block, _)) => {
+                log::warn!(slot, blockhash = block.blockhash.as_str(); "finalized block changed while fetching it, not writing it");
+                None
+            }
+            Err(e) => {
+                log::warn!(slot; "failed to fetch the finalized block: {}", e);
+                None
+            }
+        }
+    }
+}
+
+fn projected_json(exporter: JsonExporter, cli: &Cli) -> JsonExporter {
+    match &cli.fields {
+        Some(projection) => exporter.with_projection(projection.clone()),
+        None => exporter,
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct RpcResult {
-    pub meta: RpcMeta,
-    pub transaction: RpcTransactionContainer,
+fn projected_ndjson(exporter: NdjsonExporter, cli: &Cli) -> NdjsonExporter {
+    match &cli.fields {
+        Some(projection) => exporter.with_projection(projection.clone()),
+        None => exporter,
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct RpcMeta {
-    pub err: Option<serde_json::Value>,
-    #[serde(rename = "logMessages")]
-    pub log_messages: Vec<String>,
-    #[serde(rename = "preBalances")]
-    pub pre_balances: Vec<u64>,
-    #[serde(rename = "postBalances")]
-    pub post_balances: Vec<u64>,
-    #[serde(rename = "loadedAddresses")]
-    pub loaded_addresses: Option<RpcLoadedAddresses>,
-    pub fee: u64,
-    #[serde(rename = "computeUnitsConsumed")]
-    pub compute_units_consumed: Option<u64>,
+fn postgres_sink(cli: &Cli) -> Result<Option<PostgresSink>, Box<dyn Error>> {
+    let sink = match cli.postgres_url.as_deref() {
+        None => return Ok(None),
+        Some("-") => PostgresSink::from_writer(Box::new(std::io::stdout()))?,
+        Some(url) => PostgresSink::connect(url)?,
+    };
+    Ok(Some(match cli.batch_size {
+        Some(batch_size) => sink.with_batch_size(batch_size),
+        None => sink,
+    }))
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct RpcLoadedAddresses {
-    pub writable: Vec<String>,
-    pub readonly: Vec<String>,
+fn sqlite_sink(cli: &Cli) -> Result<Option<SqliteSink>, Box<dyn Error>> {
+    let sink = match cli.sqlite_path.as_deref() {
+        None => return Ok(None),
+        Some("-") => SqliteSink::from_writer(Box::new(std::io::stdout()))?,
+        Some(path) => SqliteSink::open(path)?,
+    };
+    Ok(Some(match cli.batch_size {
+        Some(batch_size) => sink.with_batch_size(batch_size),
+        None => sink,
+    }))
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct RpcTransactionContainer {
-    pub signatures: Vec<String>,
-    pub message: RpcMessage,
+fn clickhouse_sink(cli: &Cli) -> Result<Option<ClickHouseSink>, Box<dyn Error>> {
+    let Some(url) = cli.clickhouse_url.as_deref() else {
+        return Ok(None);
+    };
+    let mut sink = ClickHouseSink::new(url);
+    if let Some(batch_size) = cli.batch_size {
+        sink = sink.with_batch_size(batch_size);
+    }
+    if let Some(flush_interval) = cli.flush_interval {
+        sink = sink.with_flush_interval(flush_interval);
+    }
+    sink.create_tables()?;
+    Ok(Some(sink))
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct RpcMessage {
-    #[serde(rename = "accountKeys")]
-    pub account_keys: Vec<String>,
-    pub instructions: Vec<RpcInstruction>,
+fn kafka_sink(cli: &Cli) -> Option<KafkaSink> {
+    let mut sink = KafkaSink::new(cli.kafka_url.as_deref()?);
+    if let Some(topic) = cli.kafka_topic.as_deref() {
+        sink = sink.with_topic(topic);
+    }
+    if let Some(topic) = cli.kafka_block_topic.as_deref() {
+        sink = sink.with_block_topic(topic);
+    }
+    if let Some(batch_size) = cli.batch_size {
+        sink = sink.with_batch_size(batch_size);
+    }
+    if let Some(flush_interval) = cli.flush_interval {
+        sink = sink.with_flush_interval(flush_interval);
+    }
+    Some(sink)
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct RpcInstruction {
-    #[serde(rename = "programIdIndex")]
-    pub program_id_index: usize,
-    pub accounts: Vec<usize>,
-    pub data: String,
+fn webhook_sink(cli: &Cli) -> Option<WebhookSink> {
+    let sink = WebhookSink::new(cli.webhook_url.as_deref()?).with_filter(cli.webhook_filter.clone());
+    Some(match cli.webhook_min_swap {
+        Some(threshold) => sink.with_min_swap(threshold),
+        None => sink,
+    })
 }
 
 // ==========================================
-// MAIN LOGIC
+// SINGLE TRANSACTION PARSER
 // ==========================================
 
-fn main() {
-    // println!("=== Testing Single Transaction ===\n");
-    // test_transaction();
-    
-    println!("\n\n=== Testing Block ===\n");
-    test_block();
+fn run_parse_tx(path: &str, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    if path == "-" {
+        return run_parse_stdin(InputKind::Transaction, cli);
+    }
+    let raw_data: RpcResponse = load_from_json(path)
+        .map_err(|e| format!("failed to parse transaction JSON {}: {}", path, e))?;
+
+    let (clean_tx, report) = parse_transaction_result(&raw_data.result, &parse_options(cli)?)?;
+
+    emit_transaction(clean_tx, report, cli)
+}
+
+fn run_parse_sig(signature: &str, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let raw = rpc_client(cli)
+        .fetch_transaction(signature)?
+        .ok_or_else(|| format!("transaction {} not found at {} commitment", signature, cli.commitment.as_str()))?;
+    let (clean_tx, report) = parse_transaction_result(&raw, &parse_options(cli)?)?;
+    emit_transaction(clean_tx, report, cli)
+}
+
+// Interrupted one-shot commands exit without writing anything.
+fn emit_transaction(clean_tx: ParsedTransaction, report: ParseReport, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    if shutdown::requested() {
+        return Ok(());
+    }
+    let mut outputs = Outputs::new(cli, false)?;
+    outputs.write_transaction(&clean_tx)?;
+    outputs.finish()?;
+    log_warnings(&report);
+    Ok(())
 }
 
 // ==========================================
-// SINGLE TRANSACTION PARSER
+// BLOCK PARSER
 // ==========================================
 
-pub fn test_transaction() {
-    let path = "src/json/genesis.json";
-    println!("Loading raw RPC JSON from: {}", path);
+fn run_parse_block(path: &str, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    if path == "-" {
+        return run_parse_stdin(InputKind::Block, cli);
+    }
+    let (parsed_block, report) = load_block_file(path, &parse_options(cli)?)
+        .map_err(|e| format!("failed to parse block JSON {}: {}", path, e))?;
 
-    let raw_data: RpcResponse = match load_from_json(path) {
-        Ok(d) => d,
-        Err(e) => {
-            eprintln!("Failed to parse Raw JSON: {}", e);
-            return;
+    emit_block(parsed_block, report, cli)
+}
+
+fn run_fetch_block(slot: u64, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    if !cli.consensus_rpc_urls.is_empty() {
+        return run_consensus_fetch(slot, cli);
+    }
+    match rpc_client(cli).fetch_block(slot)? {
+        BlockFetch::Block(raw_block) => {
+            let options = parse_options(cli)?;
+            let (mut parsed_block, report) = parse_block_with_options(raw_block, &options)?;
+            parsed_block.set_slot(slot, &options.epoch_schedule);
+            fill_leader(&mut parsed_block, slot, &mut slot_leaders(cli));
+            fill_snapshots(&mut parsed_block, &mut account_snapshots(cli));
+            emit_block(parsed_block, report, cli)
         }
-    };
+        BlockFetch::SlotSkipped => {
+            log::warn!(slot; "slot was skipped, no block was produced");
+            Ok(())
+        }
+        BlockFetch::BlockUnavailable => Err(format!("block for slot {} is not available yet at this commitment", slot).into()),
+    }
+}
+
+fn emit_block(parsed_block: ParsedBlock, report: ParseReport, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    if shutdown::requested() {
+        return Ok(());
+    }
+    let mut outputs = Outputs::new(cli, false)?;
+    outputs.write_block(None, &parsed_block)?;
+    outputs.finish()?;
+    log_warnings(&report);
+    Ok(())
+}
 
-    println!("-> Raw JSON loaded successfully.");
+// ==========================================
+// PIPED INPUT
+// ==========================================
+// `parse-block -` and `parse-tx -` read one response from stdin and parse it
+// as whichever it turns out to be, so either command works after curl.
 
-    let clean_tx = parse_single_transaction(
-        raw_data.result.transaction,
-        raw_data.result.meta,
-    );
+fn run_parse_stdin(expected: InputKind, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let mut json = Vec::new();
+    io::stdin().read_to_end(&mut json).map_err(|e| format!("failed to read stdin: {}", e))?;
+    let parsed = parse_input(&json, &parse_options(cli)?).map_err(|e| format!("failed to parse stdin: {}", e))?;
+    if parsed.kind() != expected {
+        log::info!(kind:? = parsed.kind(); "stdin holds a different kind of response; parsing it as that");
+    }
+    match parsed {
+        ParsedInput::Block(block, report) => emit_block(*block, report, cli),
+        ParsedInput::Transaction(tx, report) => emit_transaction(*tx, report, cli),
+    }
+}
+
+// ==========================================
+// PARSED OUTPUT REPLAY
+// ==========================================
+// Records written with --output bincode or borsh go back through the
+// outputs as they are, e.g. into a database or out as JSON; the parse
+// options and filters don't apply again.
 
-    print_transaction_summary(&clean_tx);
+fn run_read_parsed(path: &str, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let records = match path {
+        "-" => BinaryReader::new(BufReader::new(Box::new(io::stdin()) as Box<dyn Read + Send>)),
+        path => read_parsed(path),
+    }
+    .map_err(|e| format!("failed to read {}: {}", path, e))?;
+    let mut outputs = Outputs::new(cli, true)?;
+    for record in records.take_while(|_| !shutdown::requested()) {
+        match record.map_err(|e| format!("failed to read {}: {}", path, e))? {
+            ParsedRecord::Block(mut block) => {
+                for tx in &mut block.transactions {
+                    tx.log_messages = cli.log_retention.apply(&tx.log_messages);
+                }
+                outputs.write_block(None, &block)?
+            }
+            ParsedRecord::Transaction(mut tx) => {
+                tx.log_messages = cli.log_retention.apply(&tx.log_messages);
+                outputs.write_transaction(&tx)?
+            }
+            ParsedRecord::ChainEvent(event) => outputs.write_chain_event(&event)?,
+        }
+    }
+    outputs.finish()
 }
 
 // ==========================================
-// BLOCK PARSER
+// DIRECTORY REPLAY
 // ==========================================
+// Archived getBlock responses are parsed by a pool of worker threads, each
+// taking the next file off a shared queue as soon as it's done with its
+// last one, so a slow file holds up one worker rather than a whole batch.
+// Results are written by this thread alone, in file order: parsed files wait
+// in a reorder buffer, and files are only queued up to PARSE_DIR_WINDOW per
+// worker past the next one to write, which bounds what's held in memory. A
+// file that fails to load or parse (or panics the parser), or whose
+// --per-block output can't be written, is logged and recorded, and the run
+// goes on; it fails at the end, after writing the --gap-report. Progress is
+// logged every PROGRESS_INTERVAL, as a log line with the rate and ETA rather
+// than a redrawn progress bar: it reads the same in --log-format json and in
+// a log file, and can't garble output going to the same terminal.
 
-pub fn test_block() {
-    let path = "src/json/block.json";
-    println!("Loading raw Block JSON from: {}", path);
+const PARSE_DIR_WINDOW: usize = 4;
 
-    let raw_block: RpcBlockResponse = match load_from_json(path) {
-        Ok(d) => d,
-        Err(e) => {
-            eprintln!("Failed to parse Block JSON: {}", e);
+#[derive(Debug, Serialize)]
+struct FileFailure {
+    path: PathBuf,
+    error: String,
+}
+
+#[derive(Debug, Serialize)]
+struct DirReport {
+    dir: String,
+    files: usize,
+    parsed: usize,
+    // In file order.
+    failed: Vec<FileFailure>,
+}
+
+fn run_parse_dir(dir: &str, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let files = block_files(Path::new(dir))?;
+    if files.is_empty() {
+        return Err(format!("no .json files in {}", dir).into());
+    }
+    if cli.per_block && cli.output == OutputFormat::Json && fs::canonicalize(&cli.out_dir).is_ok_and(|out_dir| fs::canonicalize(dir).is_ok_and(|dir| dir == out_dir))
+    {
+        return Err("--per-block json output would overwrite the input files; pass a different --out-dir".into());
+    }
+
+    // Blocks are parsed side by side, so each one gets a single thread.
+    let options = ParseOptions { threads: 1, ..parse_options(cli)? };
+    let workers = match cli.threads {
+        0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        n => n,
+    }
+    .min(files.len());
+    let mut outputs = if cli.per_block { None } else { Some(Outputs::new(cli, true)?) };
+    let mut report = DirReport { dir: dir.to_string(), files: files.len(), parsed: 0, failed: Vec::new() };
+    let started = Instant::now();
+    let mut last_progress = started;
+
+    let (jobs, job_queue) = mpsc::channel::<usize>();
+    let job_queue = Mutex::new(job_queue);
+    let result = thread::scope(|scope| -> Result<(), Box<dyn Error>> {
+        // Owned here, so the queue closes and the workers finish however
+        // this returns.
+        let jobs = jobs;
+        let (results, parsed) = mpsc::sync_channel(workers * 2);
+        for _ in 0..workers {
+            let (job_queue, results, files, options) = (&job_queue, results.clone(), &files, &options);
+            scope.spawn(move || parse_dir_worker(files, options, job_queue, &results));
+        }
+        drop(results);
+
+        let window = workers * PARSE_DIR_WINDOW;
+        let mut queued = window.min(files.len());
+        (0..queued).for_each(|index| jobs.send(index).expect("workers outlive the queue"));
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        while next < files.len() && !shutdown::requested() {
+            let (index, result) = parsed.recv().map_err(|_| "parse-dir workers stopped early")?;
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&next) {
+                let file = &files[next];
+                let written = result.map_err(ParseDirError::File).and_then(|(block, parse_report)| {
+                    log_warnings(&parse_report);
+                    match outputs.as_mut() {
+                        // The outputs are shared: a failure there isn't the file's.
+                        Some(outputs) => outputs.write_block(file.slot, &block).map_err(ParseDirError::Output),
+                        None => write_block_file(cli, file, &block).map_err(|e| ParseDirError::File(format!("failed to write output: {}", e))),
+                    }
+                });
+                match written {
+                    Ok(()) => report.parsed += 1,
+                    Err(ParseDirError::File(error)) => {
+                        log::error!(file:% = file.path.display(); "{}", error);
+                        report.failed.push(FileFailure { path: file.path.clone(), error });
+                    }
+                    Err(ParseDirError::Output(e)) => return Err(e),
+                }
+                next += 1;
+                if queued < files.len() {
+                    jobs.send(queued).expect("workers outlive the queue");
+                    queued += 1;
+                }
+            }
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                last_progress = Instant::now();
+                let rate = next as f64 / started.elapsed().as_secs_f64();
+                let eta_secs = ((files.len() - next) as f64 / rate) as u64;
+                log::info!(done = next, total = files.len(), failed = report.failed.len(), files_per_sec = rate.round() as u64, eta_secs; "parse-dir progress");
+            }
+        }
+        Ok(())
+    });
+    result?;
+
+    if let Some(outputs) = outputs {
+        outputs.finish()?;
+    }
+    log::info!(files = report.files, parsed = report.parsed, failed = report.failed.len(), elapsed_secs = started.elapsed().as_secs(); "parse-dir finished");
+    if let Some(path) = &cli.gap_report {
+        fs::write(path, serde_json::to_string_pretty(&report)? + "\n")
+            .map_err(|e| format!("failed to write gap report {}: {}", path.display(), e))?;
+    }
+    match report.failed.first() {
+        None => Ok(()),
+        Some(first) => Err(format!("{} of {} files failed, first {}: {}", report.failed.len(), report.files, first.path.display(), first.error).into()),
+    }
+}
+
+// A file's place in the directory listing, and how parsing it went.
+type ParsedFile = (usize, Result<(ParsedBlock, ParseReport), String>);
+
+enum ParseDirError {
+    // Recorded against the file, and the run goes on.
+    File(String),
+    Output(Box<dyn Error>),
+}
+
+// Parses files off the queue until it closes. A panic in the parser is
+// caught and reported as the file's failure.
+fn parse_dir_worker(
+    files: &[BlockFile],
+    options: &ParseOptions,
+    job_queue: &Mutex<Receiver<usize>>,
+    results: &SyncSender<ParsedFile>,
+) {
+    loop {
+        let Ok(index) = job_queue.lock().unwrap().recv() else {
+            return;
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| parse_block_file(&files[index], options)))
+            .unwrap_or_else(|_| Err("the parser panicked".to_string()));
+        if results.send((index, result)).is_err() {
             return;
         }
-    };
+    }
+}
 
-    println!("-> Block JSON loaded successfully.");
-
-    let block = raw_block.result;
-
-    // Parse rewards
-    let rewards: Vec<BlockReward> = block.rewards.iter().map(|r| {
-        BlockReward {
-            pubkey: r.pubkey.clone(),
-            lamports: r.lamports,
-            post_balance: r.post_balance,
-            reward_type: r.reward_type.clone(),
-            commission: r.commission,
-        }
-    }).collect();
-
-    // Parse all transactions in the block
-    let parsed_txs: Vec<ParsedTransaction> = block.transactions.iter().map(|tx| {
-        parse_single_transaction(tx.transaction.clone(), tx.meta.clone())
-    }).collect();
-
-    let parsed_block = ParsedBlock {
-        block_height: block.block_height,
-        block_time: block.block_time,
-        blockhash: block.blockhash,
-        parent_slot: block.parent_slot,
-        previous_blockhash: block.previous_blockhash,
-        rewards,
-        transactions: parsed_txs,
-    };
+fn parse_block_file(file: &BlockFile, options: &ParseOptions) -> Result<(ParsedBlock, ParseReport), String> {
+    let (mut block, report) =
+        load_block_file(&file.path.to_string_lossy(), options).map_err(|e| format!("failed to parse block JSON: {}", e))?;
+    if let Some(slot) = file.slot {
+        block.set_slot(slot, &options.epoch_schedule);
+    }
+    Ok((block, report))
+}
 
-    print_block_summary(&parsed_block);
+// --per-block: <out-dir>/<name>.json, .ndjson, .bincode, .borsh or
+// .accounts.ndjson (plus any
+// .gz / .zst), or
+// <out-dir>/<name>/ for the formats that write several files.
+fn write_block_file(cli: &Cli, input: &BlockFile, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+    let name = &input.name;
+    let extension = cli.compression.extension();
+    fs::create_dir_all(&cli.out_dir)?;
+    match cli.output {
+        OutputFormat::Json => {
+            let path = cli.out_dir.join(format!("{}.json{}", name, extension));
+            let mut out = compress::create(&path, cli.compression, false)?;
+            match &cli.fields {
+                Some(projection) => serde_json::to_writer_pretty(&mut out, &projection.project_block(block))?,
+                None => serde_json::to_writer_pretty(&mut out, block)?,
+            }
+            out.write_all(b"\n")?;
+            out.finish()?;
+        }
+        OutputFormat::Ndjson => {
+            let path = cli.out_dir.join(format!("{}.ndjson{}", name, extension));
+            let mut exporter = projected_ndjson(NdjsonExporter::create(&path, cli.compression)?, cli);
+            exporter.write_block(block)?;
+            exporter.finish()?;
+        }
+        OutputFormat::Csv => {
+            let mut exporter = CsvExporter::create(&cli.out_dir.join(name), cli.compression)?;
+            exporter.write_block(block)?;
+            exporter.finish()?;
+        }
+        OutputFormat::Parquet => {
+            let mut exporter = ParquetExporter::create(&cli.out_dir.join(name))?;
+            exporter.write_block(block)?;
+            exporter.finish()?;
+        }
+        OutputFormat::Binary(format) => {
+            let path = cli.out_dir.join(format!("{}.{}{}", name, format.as_str(), extension));
+            let mut exporter = BinaryExporter::create(&path, format, cli.compression)?;
+            exporter.write_block(block)?;
+            exporter.finish()?;
+        }
+        OutputFormat::AccountEvents => {
+            let path = cli.out_dir.join(format!("{}.accounts.ndjson{}", name, extension));
+            let mut exporter = AccountEventExporter::create(&path, cli.compression)?;
+            exporter.write_block(block)?;
+            exporter.finish()?;
+        }
+        OutputFormat::Pretty => return Err("--per-block needs a file output format".into()),
+    }
+    Ok(())
 }
 
 // ==========================================
-// SHARED TRANSACTION PARSING LOGIC
+// ANY BLOCK SOURCE
 // ==========================================
-fn parse_single_transaction(
-    tx: RpcTransactionContainer,
-    meta: RpcMeta,
-) -> ParsedTransaction {
-    let message = tx.message;
+// One loop for every source: it only yields raw blocks, and they're parsed
+// and written the same way whichever it is. A block that can't be read or
+// parsed is logged and the run goes on; it fails at the end.
+
+fn run_parse(input: &InputSource, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    start_metrics(cli)?;
+    let options = parse_options(cli)?;
+    let source = block_source(input, &options, cli)?;
+    let mut outputs = Outputs::new(cli, true)?;
+    let mut leaders = slot_leaders(cli);
+    // Files and stdin hold past blocks; the accounts' state now says little
+    // about them.
+    let live = matches!(input, InputSource::Slots { .. } | InputSource::WebSocket | InputSource::Geyser);
+    let mut snapshots = account_snapshots(cli).filter(|_| live);
+    let (mut parsed, mut failed) = (0, 0);
+
+    for block in source.parsed(options).take_while(|_| !shutdown::requested()) {
+        match block {
+            Ok(mut block) => {
+                if let Some(slot) = block.slot {
+                    fill_leader(&mut block.block, slot, &mut leaders);
+                }
+                fill_snapshots(&mut block.block, &mut snapshots);
+                outputs.write_block(block.slot, &block.block)?;
+                log_warnings(&block.report);
+                parsed += 1;
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                failed += 1;
+            }
+        }
+    }
 
-    // Build the full account list (static + loaded addresses)
-    let mut all_account_keys = message.account_keys.clone();
-    if let Some(loaded) = &meta.loaded_addresses {
-        all_account_keys.extend(loaded.writable.clone());
-        all_account_keys.extend(loaded.readonly.clone());
+    outputs.finish()?;
+    log::info!(parsed, failed; "parse finished");
+    match failed {
+        0 => Ok(()),
+        n => Err(format!("{} of {} blocks could not be read or parsed", n, parsed + n).into()),
     }
+}
 
-    // Parse instructions
-    let parsed_instructions: Vec<ParsedInstruction> = message.instructions.iter().map(|ix| {
-        // Resolve Program ID
-        let program_id = if ix.program_id_index < all_account_keys.len() {
-            all_account_keys[ix.program_id_index].clone()
-        } else {
-            "UNKNOWN_PROGRAM_INDEX".to_string()
+fn block_source(
+    input: &InputSource,
+    options: &ParseOptions,
+    cli: &Cli,
+) -> Result<Box<dyn phase_1_connect::source::BlockSource>, Box<dyn Error>> {
+    Ok(match input {
+        InputSource::Stdin => Box::new(ReaderSource::stdin()),
+        InputSource::Path(path) if Path::new(path).is_dir() => Box::new(DirectorySource::open(Path::new(path))?),
+        InputSource::Path(path) => Box::new(FileSource::new(vec![PathBuf::from(path)])),
+        InputSource::Slots { start, end } => Box::new(RpcSource::new(rpc_client(cli), *start, *end)),
+        InputSource::WebSocket => {
+            let ws_url = match &cli.ws_url {
+                Some(ws_url) => ws_url.clone(),
+                None => ws_url_for(&cli.rpc_url)?,
+            };
+            Box::new(PubsubClient::connect(&ws_url)?.with_commitment(cli.commitment).block_subscribe(None, options.clone())?)
+        }
+        InputSource::Geyser => {
+            let url = cli.geyser_url.as_deref().ok_or("parse geyser needs --geyser-url")?;
+            let mut geyser = GeyserClient::new(url).with_commitment(cli.commitment);
+            if let Some(token) = cli.x_token.as_deref() {
+                geyser = geyser.with_x_token(token);
+            }
+            Box::new(geyser.block_subscribe(None, options.clone())?)
+        }
+    })
+}
+
+// ==========================================
+// VALIDATION
+// ==========================================
+// parse without the outputs: every block goes through the validator, and
+// the run fails when any block was unreadable or anomalous.
+
+fn run_validate(input: &InputSource, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    if matches!(cli.output, OutputFormat::Csv | OutputFormat::Parquet | OutputFormat::Binary(_) | OutputFormat::AccountEvents) {
+        return Err("validate supports pretty, json and ndjson output".into());
+    }
+    let options = parse_options(cli)?;
+    let mut validator = Validator::new(options.decoders.clone());
+    for block in block_source(input, &options, cli)?.parsed(options).take_while(|_| !shutdown::requested()) {
+        match block {
+            Ok(block) => validator.record_block(block.slot, &block.block, &block.report),
+            Err(e) => validator.record_failure(e.to_string()),
+        }
+    }
+    let report = validator.finish();
+
+    match cli.output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Ndjson => println!("{}", serde_json::to_string(&report)?),
+        _ => print_validation_report(&report, &label_registry(cli)?),
+    }
+    match report.errors() {
+        0 => Ok(()),
+        n => Err(format!("validation found {} errors in {} blocks", n, report.blocks + report.failures.len()).into()),
+    }
+}
+
+// ==========================================
+// SLOT RANGE STREAMING
+// ==========================================
+
+// With a checkpoint, outputs are flushed and the slot recorded after every
+// block, so a crash re-processes at most the block being written.
+fn run_stream(start: u64, end: Option<u64>, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let checkpoint = cli.checkpoint.as_deref().map(Checkpoint::new);
+    let mut start = start;
+    if let Some(checkpoint) = checkpoint.as_ref().filter(|_| cli.resume)
+        && let Some(last_slot) = checkpoint.load()?
+    {
+        log::info!(slot = last_slot, checkpoint:% = checkpoint.path().display(); "resuming after checkpoint");
+        start = last_slot + 1;
+    }
+
+    start_metrics(cli)?;
+    let mut stream =
+        BlockStream::new(rpc_client(cli), start, end).with_options(parse_options(cli)?).with_concurrency(cli.concurrency);
+    let mut outputs = Outputs::new(cli, true)?.with_finality(cli)?;
+    let mut leaders = slot_leaders(cli);
+    let mut snapshots = account_snapshots(cli);
+
+    while !shutdown::requested()
+        && let Some(streamed) = stream.next()
+    {
+        let mut streamed = match streamed {
+            Ok(streamed) => streamed,
+            // Stop rather than skip, so --resume retries the slot.
+            Err(e) if checkpoint.is_some() => {
+                outputs.finish()?;
+                return Err(format!("slot {}: {}", stream.next_slot() - 1, e).into());
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                continue;
+            }
         };
+        fill_leader(&mut streamed.block, streamed.slot, &mut leaders);
+        fill_snapshots(&mut streamed.block, &mut snapshots);
+        outputs.write_block(Some(streamed.slot), &streamed.block)?;
+        log_warnings(&streamed.report);
 
-        // Resolve Accounts
-        let account_addresses: Vec<String> = ix.accounts.iter()
-            .map(|&idx| {
-                if idx < all_account_keys.len() {
-                    all_account_keys[idx].clone()
-                } else {
-                    format!("UNKNOWN_IDX_{}", idx)
-                }
-            })
-            .collect();
+        if let Some(checkpoint) = &checkpoint {
+            outputs.flush()?;
+            checkpoint.save(streamed.slot)?;
+        }
+    }
+
+    outputs.finish()?;
+    // Covers skipped slots at the end of the range.
+    if let Some(checkpoint) = &checkpoint
+        && let Some(last_slot) = stream.next_slot().checked_sub(1)
+    {
+        checkpoint.save(last_slot)?;
+    }
+    Ok(())
+}
+
+// ==========================================
+// ADDRESS HISTORY
+// ==========================================
+
+// Like stream: with a checkpoint the outputs are flushed and the signature
+// recorded after every transaction, and a transaction that can't be fetched
+// stops the run so --resume retries it.
+fn run_address_history(address: Pubkey, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let checkpoint = cli.checkpoint.as_deref().map(Checkpoint::new);
+    let mut before = cli.before.clone();
+    if let Some(checkpoint) = checkpoint.as_ref().filter(|_| cli.resume)
+        && let Some(signature) = checkpoint.load_signature()?
+    {
+        log::info!(signature:% = signature, checkpoint:% = checkpoint.path().display(); "resuming after checkpoint");
+        before = Some(signature);
+    }
+
+    start_metrics(cli)?;
+    let options = parse_options(cli)?;
+    let filter = options.filter.clone();
+    let mut history = AddressHistory::new(rpc_client(cli), address).with_options(options).with_concurrency(cli.concurrency);
+    if let Some(before) = before {
+        history = history.with_before(before);
+    }
+    if let Some(until) = &cli.until {
+        history = history.with_until(until.clone());
+    }
+    if let Some(limit) = cli.limit {
+        history = history.with_limit(limit);
+    }
+    let mut outputs = Outputs::new(cli, true)?;
+    let mut written = 0;
+
+    for item in history.by_ref().take_while(|_| !shutdown::requested()) {
+        let item = match item {
+            Ok(item) => item,
+            Err(e) if checkpoint.is_some() => {
+                outputs.finish()?;
+                return Err(e);
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                continue;
+            }
+        };
+        if filter.as_ref().is_none_or(|f| f.matches(&item.transaction)) {
+            outputs.write_transaction(&item.transaction)?;
+            log_warnings(&item.report);
+            written += 1;
+        }
+        if let Some(checkpoint) = &checkpoint {
+            outputs.flush()?;
+            checkpoint.save_signature(item.slot, &item.signature)?;
+        }
+    }
+
+    outputs.finish()?;
+    log::info!(address:% = address, transactions = written, cursor = history.cursor(); "address history done");
+    Ok(())
+}
+
+// ==========================================
+// HISTORICAL BACKFILL
+// ==========================================
 
-        ParsedInstruction {
-            program_id,
-            accounts: account_addresses,
-            data: ix.data.clone(),
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+// A failed slot doesn't stop the run: it goes into the gap report, and the
+// command fails once everything else has been written.
+fn run_backfill(start: u64, end: u64, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    start_metrics(cli)?;
+    let backfill = Backfill::new(rpc_client(cli), start, end).with_options(parse_options(cli)?).with_workers(cli.concurrency);
+    let total = backfill.slots();
+    let mut outputs = Outputs::new(cli, true)?;
+    let mut report = GapReport::new(start, end);
+    let mut leaders = slot_leaders(cli);
+    let started = Instant::now();
+    let mut last_progress = started;
+
+    for item in backfill.start().into_iter().take_while(|_| !shutdown::requested()) {
+        report.record(&item);
+        match item {
+            BackfillItem::Block(mut streamed) => {
+                fill_leader(&mut streamed.block, streamed.slot, &mut leaders);
+                outputs.write_block(Some(streamed.slot), &streamed.block)?;
+                log_warnings(&streamed.report);
+            }
+            BackfillItem::Skipped(slot) => log::debug!(slot; "slot skipped"),
+            BackfillItem::Failed(slot, error) => log::warn!(slot, error = error.as_str(); "slot failed"),
         }
-    }).collect();
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            last_progress = Instant::now();
+            let done = report.processed();
+            let rate = done as f64 / started.elapsed().as_secs_f64();
+            let eta_secs = ((total - done) as f64 / rate) as u64;
+            log::info!(done, total, failed = report.failed.len(), slots_per_sec = rate.round() as u64, eta_secs; "backfill progress");
+        }
+    }
+    outputs.finish()?;
+    if shutdown::requested() {
+        log::warn!(done = report.processed(), total; "backfill interrupted; the gap report covers only the slots handled");
+    }
+
+    report.sort();
+    log::info!(
+        blocks = report.blocks,
+        skipped = report.skipped.len(),
+        failed = report.failed.len(),
+        elapsed_secs = started.elapsed().as_secs();
+        "backfill finished"
+    );
+    if let Some(path) = &cli.gap_report {
+        fs::write(path, serde_json::to_string_pretty(&report)? + "\n")
+            .map_err(|e| format!("failed to write gap report {}: {}", path.display(), e))?;
+    }
+    match report.failed.first() {
+        None => Ok(()),
+        Some(first) => Err(format!("{} of {} slots failed, first {}: {}", report.failed.len(), total, first.slot, first.error).into()),
+    }
+}
 
-    ParsedTransaction {
-        signature: tx.signatures[0].clone(),
-        fee_payer: message.account_keys[0].clone(),
-        is_success: meta.err.is_none(),
-        account_keys: all_account_keys,
-        instructions: parsed_instructions,
-        log_messages: meta.log_messages,
-        pre_balances: meta.pre_balances,
-        post_balances: meta.post_balances,
-        fee: meta.fee,
-        compute_units_consumed: meta.compute_units_consumed,
+fn start_metrics(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    if let Some(addr) = cli.metrics_addr.as_deref() {
+        metrics::serve(addr)?;
+        log::info!(addr; "serving metrics at /metrics");
     }
+    Ok(())
 }
 
 // ==========================================
-// UTILITY FUNCTIONS
+// REALTIME SUBSCRIPTIONS
 // ==========================================
-pub fn load_from_json<T>(path: &str) -> Result<T, Box<dyn Error>>
+// Block notifications carry the full block. Log notifications only carry the
+// signature and logs, so each matching transaction is fetched over RPC and
+// parsed like parse-tx. A Geyser stream carries full transactions itself.
+
+fn run_subscribe(kind: SubscriptionKind, mentions: Option<&str>, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    start_metrics(cli)?;
+    if let Some(url) = cli.geyser_url.as_deref() {
+        return run_geyser_subscribe(url, kind, mentions, cli);
+    }
+    let ws_url = match &cli.ws_url {
+        Some(ws_url) => ws_url.clone(),
+        None => ws_url_for(&cli.rpc_url)?,
+    };
+    let pubsub = PubsubClient::connect(&ws_url)?.with_commitment(cli.commitment);
+    let mut outputs = Outputs::new(cli, true)?.with_finality(cli)?;
+
+    match kind {
+        SubscriptionKind::Blocks => {
+            let options = parse_options(cli)?;
+            let blocks = pubsub.block_subscribe(mentions, options.clone())?;
+            let mut snapshots = account_snapshots(cli);
+            for streamed in in_slot_order(blocks, mentions, options, cli).take_while(|_| !shutdown::requested()) {
+                match streamed {
+                    Ok(mut streamed) => {
+                        fill_snapshots(&mut streamed.block, &mut snapshots);
+                        outputs.write_block(Some(streamed.slot), &streamed.block)?;
+                        log_warnings(&streamed.report);
+                    }
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+        }
+        SubscriptionKind::Logs => {
+            let client = rpc_client(cli);
+            let options = parse_options(cli)?;
+            for notification in pubsub.logs_subscribe(mentions)?.take_while(|_| !shutdown::requested()) {
+                let parsed = notification
+                    .and_then(|n| client.get_transaction(&n.signature))
+                    .and_then(|raw| Ok(parse_transaction_result(&raw, &options)?));
+                match parsed {
+                    Ok((tx, _)) if options.filter.as_ref().is_some_and(|f| !f.matches(&tx)) => {}
+                    Ok((tx, report)) => {
+                        outputs.write_transaction(&tx)?;
+                        log_warnings(&report);
+                    }
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+        }
+    }
+
+    outputs.finish()
+}
+
+fn run_geyser_subscribe(url: &str, kind: SubscriptionKind, mentions: Option<&str>, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let mut geyser = GeyserClient::new(url).with_commitment(cli.commitment);
+    if let Some(token) = cli.x_token.as_deref() {
+        geyser = geyser.with_x_token(token);
+    }
+    let options = parse_options(cli)?;
+    let mut outputs = Outputs::new(cli, true)?.with_finality(cli)?;
+
+    match kind {
+        SubscriptionKind::Blocks => {
+            let blocks = geyser.block_subscribe(mentions, options.clone())?;
+            let mut snapshots = account_snapshots(cli);
+            for streamed in in_slot_order(blocks, mentions, options, cli).take_while(|_| !shutdown::requested()) {
+                match streamed {
+                    Ok(mut streamed) => {
+                        fill_snapshots(&mut streamed.block, &mut snapshots);
+                        outputs.write_block(Some(streamed.slot), &streamed.block)?;
+                        log_warnings(&streamed.report);
+                    }
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+        }
+        SubscriptionKind::Logs => {
+            let filter = options.filter.clone();
+            for streamed in geyser.transaction_subscribe(mentions, options)?.take_while(|_| !shutdown::requested()) {
+                match streamed {
+                    Ok(streamed) if filter.as_ref().is_some_and(|f| !f.matches(&streamed.transaction)) => {}
+                    Ok(streamed) => {
+                        outputs.write_transaction(&streamed.transaction)?;
+                        log_warnings(&streamed.report);
+                    }
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+        }
+    }
+
+    outputs.finish()
+}
+
+// With an account filter only some slots have a block, so there's no gap to
+// repair and blocks are passed on as they come.
+fn in_slot_order<I>(
+    blocks: I,
+    mentions: Option<&str>,
+    options: ParseOptions,
+    cli: &Cli,
+) -> Box<dyn Iterator<Item = Result<StreamedBlock, Box<dyn Error>>>>
 where
-    T: serde::de::DeserializeOwned,
+    I: Iterator<Item = Result<StreamedBlock, Box<dyn Error>>> + 'static,
 {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let parsed_data = serde_json::from_reader(reader)?;
-    Ok(parsed_data)
+    match mentions {
+        Some(_) => Box::new(blocks),
+        None => Box::new(SlotOrder::new(blocks, rpc_client(cli)).with_options(options).with_window(cli.reorder_window)),
+    }
+}
+
+// ==========================================
+// BLOCK DIFF
+// ==========================================
+
+fn run_diff(left: &BlockSource, right: &BlockSource, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let left = load_block(left, &cli.rpc_url, cli)?;
+    let right = load_block(right, cli.diff_rpc_url.as_deref().unwrap_or(&cli.rpc_url), cli)?;
+    if shutdown::requested() {
+        return Ok(());
+    }
+    let diff = diff_blocks(&left, &right);
+    match cli.output {
+        OutputFormat::Pretty => print_diff_summary(&diff),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+        OutputFormat::Ndjson => println!("{}", serde_json::to_string(&diff)?),
+        OutputFormat::Csv | OutputFormat::Parquet | OutputFormat::Binary(_) | OutputFormat::AccountEvents => {
+            return Err("diff supports pretty, json and ndjson output".into())
+        }
+    }
+    Ok(())
+}
+
+fn load_block(source: &BlockSource, rpc_url: &str, cli: &Cli) -> Result<ParsedBlock, Box<dyn Error>> {
+    let options = parse_options(cli)?;
+    let (block, report) = match source {
+        BlockSource::File(path) => {
+            load_block_file(path, &options).map_err(|e| format!("failed to parse block JSON {}: {}", path, e))?
+        }
+        BlockSource::Slot(slot) => {
+            // Comparing endpoints means asking this one, not the pool.
+            let mut client = rpc_client(cli);
+            client.url = rpc_url.to_string();
+            client.pool = None;
+            let raw_block = match client.fetch_block(*slot)? {
+                BlockFetch::Block(raw_block) => raw_block,
+                BlockFetch::SlotSkipped => return Err(format!("slot {} was skipped", slot).into()),
+                BlockFetch::BlockUnavailable => return Err(format!("block for slot {} is not available yet", slot).into()),
+            };
+            let (mut block, report) = parse_block_with_options(raw_block, &options)?;
+            block.set_slot(*slot, &options.epoch_schedule);
+            (block, report)
+        }
+    };
+    log_warnings(&report);
+    Ok(block)
+}
+
+// ==========================================
+// WALLET REPORT
+// ==========================================
+// Blocks are read one at a time and folded into the report, so a long
+// range never has more than one parsed block in memory. A block that can't
+// be read is logged and left out; the command fails after printing the
+// report.
+
+fn run_wallet_report(address: Pubkey, blocks: &BlockInputs, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    if matches!(cli.output, OutputFormat::Csv | OutputFormat::Parquet | OutputFormat::Binary(_) | OutputFormat::AccountEvents) {
+        return Err("wallet-report supports pretty, json and ndjson output".into());
+    }
+    let mut report = WalletReport::new(address);
+    let failed = scan_blocks(blocks, cli, |block| report.record_block(block))?;
+    report.sort();
+    report.enrich(&token_registry(cli)?);
+
+    match cli.output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Ndjson => println!("{}", serde_json::to_string(&report)?),
+        _ => print_wallet_report(&report, &label_registry(cli)?),
+    }
+    if failed > 0 {
+        return Err(format!("{} blocks couldn't be read and are missing from the report", failed).into());
+    }
+    Ok(())
+}
+
+// Feeds every block of `blocks` to `record`, returning how many couldn't be
+// read or parsed (logged as they happen). An interrupted scan stops early and
+// the report covers the blocks read so far.
+fn scan_blocks(blocks: &BlockInputs, cli: &Cli, mut record: impl FnMut(&ParsedBlock)) -> Result<usize, Box<dyn Error>> {
+    let options = parse_options(cli)?;
+    let mut failed = 0;
+    match blocks {
+        BlockInputs::Files(paths) => {
+            for path in paths.iter().map(PathBuf::from) {
+                let files = match path.is_dir() {
+                    true => block_files(&path)?,
+                    false => vec![BlockFile::from_path(path.clone()).unwrap_or(BlockFile { name: String::new(), slot: None, path })],
+                };
+                for file in files.into_iter().take_while(|_| !shutdown::requested()) {
+                    match parse_block_file(&file, &options) {
+                        Ok((block, parse_report)) => {
+                            record(&block);
+                            log_warnings(&parse_report);
+                        }
+                        Err(e) => {
+                            log::error!(file:% = file.path.display(); "{}", e);
+                            failed += 1;
+                        }
+                    }
+                }
+            }
+        }
+        BlockInputs::Slots { start, end } => {
+            let stream = BlockStream::new(rpc_client(cli), *start, Some(*end)).with_options(options).with_concurrency(cli.concurrency);
+            for streamed in stream.take_while(|_| !shutdown::requested()) {
+                match streamed {
+                    Ok(streamed) => {
+                        record(&streamed.block);
+                        log_warnings(&streamed.report);
+                    }
+                    Err(e) => {
+                        log::error!("{}", e);
+                        failed += 1;
+                    }
+                }
+            }
+        }
+    }
+    Ok(failed)
+}
+
+// ==========================================
+// PROGRAM LEADERBOARD
+// ==========================================
+
+fn run_top_programs(blocks: &BlockInputs, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    if matches!(cli.output, OutputFormat::Csv | OutputFormat::Parquet | OutputFormat::Binary(_) | OutputFormat::AccountEvents) {
+        return Err("top-programs supports pretty, json and ndjson output".into());
+    }
+    let mut tally = ProgramTally::new();
+    let failed = scan_blocks(blocks, cli, |block| tally.record_block(block))?;
+    let leaderboard = tally.leaderboard(cli.rank_by, Some(cli.top), &label_registry(cli)?);
+
+    match cli.output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&leaderboard)?),
+        OutputFormat::Ndjson => println!("{}", serde_json::to_string(&leaderboard)?),
+        _ => print_program_leaderboard(&leaderboard),
+    }
+    if failed > 0 {
+        return Err(format!("{} blocks couldn't be read and are missing from the leaderboard", failed).into());
+    }
+    Ok(())
+}
+
+// ==========================================
+// SANDWICH DETECTION
+// ==========================================
+
+fn run_sandwiches(blocks: &BlockInputs, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    if matches!(cli.output, OutputFormat::Csv | OutputFormat::Parquet | OutputFormat::Binary(_) | OutputFormat::AccountEvents) {
+        return Err("sandwiches supports pretty, json and ndjson output".into());
+    }
+    let mut findings = Vec::new();
+    let failed = scan_blocks(blocks, cli, |block| findings.extend(detect_sandwiches(block, cli.max_span)))?;
+
+    match cli.output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&findings)?),
+        OutputFormat::Ndjson => {
+            for sandwich in &findings {
+                println!("{}", serde_json::to_string(sandwich)?);
+            }
+        }
+        _ => print_sandwiches(&findings, &token_registry(cli)?),
+    }
+    if failed > 0 {
+        return Err(format!("{} blocks couldn't be read and weren't checked", failed).into());
+    }
+    Ok(())
+}
+
+// ==========================================
+// HTTP API
+// ==========================================
+
+fn run_serve(addr: &str, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    start_metrics(cli)?;
+    // Requests are served side by side, so each one gets a single thread.
+    let options = ParseOptions { threads: 1, ..parse_options(cli)? };
+    let workers = match cli.threads {
+        0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        n => n,
+    };
+    let mut server = ApiServer::new(options).with_rpc_client(rpc_client(cli)).with_workers(workers);
+    // The database a sink run filled in, queried through POST /graphql.
+    let database = match (cli.sqlite_path.as_deref(), cli.postgres_url.as_deref()) {
+        (Some("-"), _) | (_, Some("-")) => return Err("serve reads --sqlite / --postgres as a database, not '-'".into()),
+        (Some(path), _) => Some(Database::Sqlite(path.to_string())),
+        (None, Some(url)) => Some(Database::Postgres(url.to_string())),
+        (None, None) => None,
+    };
+    if let Some(database) = database {
+        server = server.with_graphql(GraphQl::new(database));
+    }
+    server.serve(addr)
+}
+
+// ==========================================
+// OUTPUT SCHEMAS
+// ==========================================
+
+fn run_schema(kind: SchemaKind) -> Result<(), Box<dyn Error>> {
+    let schema = match kind {
+        SchemaKind::Block => schema::block_schema(),
+        SchemaKind::Transaction => schema::transaction_schema(),
+        // Keyed by file, as --output parquet writes them.
+        SchemaKind::Arrow => serde_json::json!({
+            "transactions.parquet": parquet::arrow_schema(&parquet::transaction_schema()),
+            "instructions.parquet": parquet::arrow_schema(&parquet::instruction_schema()),
+            "rewards.parquet": parquet::arrow_schema(&parquet::reward_schema()),
+        }),
+    };
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+// ==========================================
+// MULTI-RPC CONSENSUS
+// ==========================================
+// fetch-block with --consensus-rpc-url: every endpoint is fetched and parsed
+// on its own thread, and the majority view is written out. An endpoint that
+// fails or disagrees is logged; the run only fails when none return a block.
+
+fn run_consensus_fetch(slot: u64, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let endpoints: Vec<&String> = std::iter::once(&cli.rpc_url).chain(&cli.consensus_rpc_urls).collect();
+    let results: Vec<Result<ParsedBlock, String>> = thread::scope(|scope| {
+        let handles: Vec<_> = endpoints
+            .iter()
+            .map(|url| scope.spawn(move || load_block(&BlockSource::Slot(slot), url, cli).map_err(|e| e.to_string())))
+            .collect();
+        handles.into_iter().map(|handle| handle.join().expect("block fetch thread panicked")).collect()
+    });
+
+    let mut views = Vec::new();
+    for (endpoint, result) in endpoints.into_iter().zip(results) {
+        match result {
+            Ok(block) => views.push((endpoint.clone(), block)),
+            Err(e) => log::warn!(endpoint = endpoint.as_str(), error:% = e; "endpoint left out of consensus"),
+        }
+    }
+    let consensus = block_consensus(&views).ok_or_else(|| format!("no endpoint returned a block for slot {}", slot))?;
+    for endpoint in consensus.disagreeing() {
+        let diff = &endpoint.diff;
+        log::warn!(
+            endpoint = endpoint.endpoint.as_str(),
+            missing = diff.only_left.len(),
+            extra = diff.only_right.len(),
+            differing = diff.changed.len(),
+            header = diff.header.len(),
+            reordered = diff.reordered,
+            rewards = diff.rewards_only_left.len() + diff.rewards_only_right.len() + diff.rewards_changed.len();
+            "endpoint disagrees with the majority"
+        );
+    }
+    emit_block(consensus.block, ParseReport::default(), cli)
 }
 
-fn print_transaction_summary(tx: &ParsedTransaction) {
+// ==========================================
+// SUMMARY PRINTERS
+// ==========================================
+fn print_transaction_summary(tx: &ParsedTransaction, labels: &LabelRegistry) {
     println!("--------------------------------");
     println!("Signature: {}", tx.signature);
     println!("Success:   {}", tx.is_success);
-    println!("Fee Payer: {}", tx.fee_payer);
-    println!("Fee:       {} lamports", tx.fee);
-    if let Some(cu) = tx.compute_units_consumed {
-        println!("Compute:   {} CU", cu);
+    if let Some(reason) = &tx.failure_reason {
+        println!("Failure:   {}", reason);
+    }
+    println!("Fee Payer: {}", labels.describe(&tx.fee_payer));
+    for memo in &tx.memos {
+        println!("Memo:      {}", memo);
     }
-    println!("Total Accounts Resolved: {}", tx.account_keys.len());
+    if let Some(verified) = tx.signatures_verified {
+        println!("Signatures: {}", if verified { "valid" } else { "INVALID" });
+    }
+    let fees = &tx.fee_breakdown;
+    println!("Fee:       {} lamports ({} base + {} priority)", tx.fee, fees.base_fee, fees.priority_fee);
+    match tx.compute_units_consumed {
+        Some(cu) => println!("Compute:   {} of {} CU at {} micro-lamports/CU", cu, fees.compute_unit_limit, fees.compute_unit_price_micro_lamports),
+        None => println!("Compute:   limit {} CU at {} micro-lamports/CU", fees.compute_unit_limit, fees.compute_unit_price_micro_lamports),
+    }
+    if let Some(priority) = fees.consumed_priority_fee.filter(|&priority| priority > 0) {
+        println!("Priority:  {} lamports for the units consumed", priority);
+    }
+    let signers = tx.accounts.iter().filter(|account| account.is_signer).count();
+    let writable = tx.accounts.iter().filter(|account| account.is_writable).count();
+    println!("Total Accounts Resolved: {} ({} signers, {} writable)", tx.account_keys.len(), signers, writable);
+    let inner_count: usize = tx.inner_instructions.iter().map(|inner| inner.instructions.len()).sum();
+    println!("Inner Instructions: {}", inner_count);
     println!("--------------------------------");
 
-    // Detect Raydium interactions
+    let balance_changes = tx.balance_changes();
+    if !balance_changes.is_empty() {
+        println!("SOL Balance Changes:");
+        for change in &balance_changes {
+            println!("  {} {:+} lamports", labels.describe(&change.address), change.delta);
+        }
+    }
+
+    if !tx.token_balance_changes.is_empty() {
+        println!("Token Balance Changes:");
+        for change in &tx.token_balance_changes {
+            println!("  {} {:+} of {} (decimals {})", labels.describe(&change.owner), change.delta, labels.describe(&change.mint), change.decimals);
+        }
+    }
+
+    for event in &tx.events {
+        println!("Event: {} {}", event.name, event.data);
+    }
+
+    let logs = tx.parsed_logs();
+    if !logs.invocations.is_empty() {
+        println!("Program Invocations:");
+        for invocation in logs.walk() {
+            let status = match invocation.success {
+                Some(true) => "ok",
+                Some(false) => "failed",
+                None => "truncated",
+            };
+            let units = invocation.compute_units_consumed.map(|cu| format!(" {} CU", cu)).unwrap_or_default();
+            let program = match invocation.program_id.parse() {
+                Ok(program_id) => labels.describe(&program_id),
+                Err(_) => invocation.program_id.clone(),
+            };
+            println!("  {}{} [{}]{}", "  ".repeat(invocation.depth.saturating_sub(1)), program, status, units);
+        }
+    }
+
+    if let Some(swap) = detect_swap(tx) {
+        println!("Swap ({}): {} {} -> {} {} by {}", swap.venue, swap.in_amount, swap.in_mint, swap.out_amount, swap.out_mint, swap.trader);
+        for leg in &swap.legs {
+            let position = match leg.inner_index {
+                Some(inner) => format!("#{}.{}", leg.instruction_index, inner),
+                None => format!("#{}", leg.instruction_index),
+            };
+            println!("  {} {} on pool {}", position, leg.venue, leg.pool.map_or("unknown".to_string(), |pool| pool.to_string()));
+        }
+    }
+    for update in detect_pool_updates(tx) {
+        let reserves: Vec<String> = update
+            .reserves
+            .iter()
+            .map(|reserve| format!("{:+} {}", reserve.delta, labels.describe(&reserve.mint)))
+            .collect();
+        let price = update.price.map(|price| format!(", price {}", price)).unwrap_or_default();
+        let pool = update.pool.map_or("unknown".to_string(), |pool| pool.to_string());
+        println!("Pool {} ({}): {}{}", pool, update.venue, reserves.join(", "), price);
+    }
+
     for (index, ix) in tx.instructions.iter().enumerate() {
-        if ix.program_id == "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8" {
-            println!("Instruction #{}: Raydium Interaction Detected!", index);
-            println!("  Data (Base58): {}", ix.data);
-            println!("  -> Potential Swap instruction found.");
+        if let Some(decoded) = &ix.decoded {
+            println!("Instruction #{}: {:?}", index, decoded);
         }
     }
 }
 
-fn print_block_summary(block: &ParsedBlock) {
+fn print_block_summary(block: &ParsedBlock, labels: &LabelRegistry) {
     println!("================================");
     println!("BLOCK SUMMARY");
     println!("================================");
-    println!("Block Height:  {}", block.block_height);
-    println!("Block Time:    {}", block.block_time);
+    println!("Slot:          {}", block.slot);
+    println!("Epoch:         {} (slot index {})", block.epoch, block.slot_index);
+    match block.block_height {
+        Some(height) => println!("Block Height:  {}", height),
+        None => println!("Block Height:  unknown"),
+    }
+    match (block.block_time, &block.block_time_rfc3339) {
+        (Some(time), Some(rfc3339)) => println!("Block Time:    {} ({})", time, rfc3339),
+        _ => println!("Block Time:    unknown"),
+    }
     println!("Blockhash:     {}", block.blockhash);
     println!("Parent Slot:   {}", block.parent_slot);
     println!("Prev Hash:     {}", block.previous_blockhash);
+    match &block.leader {
+        Some(leader) => println!("Leader:        {}", labels.describe(leader)),
+        None => println!("Leader:        unknown"),
+    }
+    if let Some(endpoint) = &block.endpoint {
+        println!("Served By:     {}", endpoint);
+    }
     println!("Rewards:       {} entries", block.rewards.len());
     println!("Transactions:  {} total", block.transactions.len());
+    if let Some(votes) = block.excluded_votes {
+        println!("Votes Skipped: {}", votes);
+    }
     println!("================================\n");
 
+    let stats = block_stats(block);
+
     // Print rewards
     if !block.rewards.is_empty() {
         println!("Rewards:");
@@ -347,17 +1830,351 @@ fn print_block_summary(block: &ParsedBlock) {
                 reward.reward_type
             );
         }
+        for total in &stats.rewards {
+            let name = match total.reward_type.as_str() {
+                "" => "Unknown",
+                name => name,
+            };
+            println!("  Total {}: {} lamports over {} entries", name, total.lamports, total.count);
+        }
+        println!();
+    }
+
+    if block.transactions.is_empty() {
+        println!("Empty block (no transactions)");
         println!();
+        return;
     }
 
     // Analyze transactions
-    let successful = block.transactions.iter().filter(|tx| tx.is_success).count();
-    let failed = block.transactions.len() - successful;
-    let total_fees: u64 = block.transactions.iter().map(|tx| tx.fee).sum();
 
     println!("Transaction Stats:");
-    println!("  Successful: {}", successful);
-    println!("  Failed:     {}", failed);
-    println!("  Total Fees: {} lamports", total_fees);
+    println!("  Successful: {}", stats.successful);
+    println!("  Failed:     {}", stats.failed);
+    println!("  Votes:      {} ({} non-vote)", stats.vote_transactions, stats.non_vote_transactions);
+    if stats.durable_nonce_transactions > 0 {
+        println!("  Durable Nonce: {}", stats.durable_nonce_transactions);
+    }
+    println!("  Total Fees: {} lamports", stats.total_fees);
+    print_percentiles("Fees (lamports)", stats.fees);
+    print_percentiles("Compute Units", stats.compute_units);
+    let market = &stats.priority_fees;
+    print_percentiles("CU Price (micro-lamports)", market.prices);
+    match market.min_price {
+        Some(price) => println!("  Priced:     {} of {} non-vote (min {} micro-lamports/CU)", market.priced_transactions, market.transactions, price),
+        None => println!("  Priced:     0 of {} non-vote", market.transactions),
+    }
+    let invalid: Vec<&str> = block.transactions.iter()
+        .filter(|tx| tx.signatures_verified == Some(false))
+        .map(|tx| tx.signature.as_str())
+        .collect();
+    if block.transactions.iter().any(|tx| tx.signatures_verified.is_some()) {
+        println!("  Invalid Signatures: {}", invalid.len());
+        for signature in invalid {
+            println!("    {}", signature);
+        }
+    }
+    println!();
+
+    let cost = block_cost(block, &BlockLimits::default());
+    println!("Block Cost:");
+    println!("  Total:      {} of {} CU ({:.1}%)", cost.total_units, cost.limits.block_units, cost.block_utilization);
+    println!("  Votes:      {} of {} CU ({:.1}%)", cost.vote_units, cost.limits.vote_units, cost.vote_utilization);
+    if let Some(hottest) = cost.hottest_accounts.first() {
+        println!("  Hottest Account: {} CU over {} transactions ({:.1}% of {} CU)",
+            hottest.units, hottest.transactions, cost.account_utilization, cost.limits.writable_account_units);
+        println!("    {}", labels.describe(&hottest.pubkey));
+    }
+    println!();
+
+    if !block.account_snapshots.is_empty() {
+        println!("Account Snapshots:");
+        for snapshot in &block.account_snapshots {
+            let data_len = snapshot.data_encoding.decode(&snapshot.data).map_or(0, |data| data.len());
+            let state = match snapshot.owner {
+                Some(owner) => format!("{} lamports, {} data bytes, owner {}", snapshot.lamports, data_len, labels.describe(&owner)),
+                None => "closed".to_string(),
+            };
+            let changed = match snapshot.changed {
+                Some(true) => " (changed)",
+                Some(false) => " (unchanged)",
+                None => "",
+            };
+            println!("  {} at slot {}: {}{}", labels.describe(&snapshot.pubkey), snapshot.context_slot, state, changed);
+        }
+        println!();
+    }
+
+    if !stats.failures.is_empty() {
+        println!("Top Failure Reasons:");
+        for group in stats.failures.iter().take(10) {
+            let cause = &group.cause;
+            let program = cause.program_id.map(|program_id| format!("{}: ", labels.describe(&program_id))).unwrap_or_default();
+            let name = cause.name.as_ref().map(|name| format!(" ({})", name)).unwrap_or_default();
+            let category = match cause.category {
+                FailureCategory::Other => String::new(),
+                category => format!(" [{:?}]", category),
+            };
+            println!("  {:>6}  {}{}{}{}", group.count, program, cause.error, name, category);
+        }
+        println!();
+    }
+
+    println!("Top Programs:");
+    for program in stats.programs.iter().take(10) {
+        println!("  {:>6}  {}", program.invocations, labels.describe(&program.program_id));
+    }
+    println!();
+
+    if !stats.compute_by_program.is_empty() {
+        let total: u64 = stats.compute_by_program.iter().map(|program| program.compute_units).sum();
+        println!("Top Programs by Compute:");
+        for program in stats.compute_by_program.iter().take(10) {
+            let share = program.compute_units as f64 * 100.0 / total.max(1) as f64;
+            println!("  {:>10} CU {:>5.1}%  {}", program.compute_units, share, labels.describe(&program.program_id));
+        }
+        println!();
+    }
+}
+
+fn print_wallet_report(report: &WalletReport, labels: &LabelRegistry) {
+    let slot = |slot: Option<u64>| slot.map_or("never".to_string(), |slot| slot.to_string());
+    println!("================================");
+    println!("WALLET REPORT");
+    println!("================================");
+    println!("Address:       {}", labels.describe(&report.address));
+    println!("Blocks:        {} scanned", report.blocks_scanned);
+    println!("Transactions:  {} ({} failed)", report.transactions, report.failed_transactions);
+    println!("First Seen:    {}", slot(report.first_seen_slot));
+    println!("Last Seen:     {}", slot(report.last_seen_slot));
+    println!("SOL Change:    {:+} lamports", report.sol_delta);
+    println!("Fees Paid:     {} lamports over {} transactions", report.fees_paid, report.fee_payer_transactions);
+    println!("================================\n");
+
+    if !report.tokens.is_empty() {
+        println!("Token Changes:");
+        for token in &report.tokens {
+            println!(
+                "  {} of {} (decimals {}, {} in, {} out)",
+                format_token_delta(token.delta, token.decimals),
+                token.symbol.as_deref().map_or_else(|| labels.describe(&token.mint), |symbol| format!("{} ({})", symbol, token.mint)),
+                token.decimals,
+                token.transfers_in,
+                token.transfers_out
+            );
+        }
+        println!();
+    }
+
+    if !report.programs.is_empty() {
+        println!("Programs:");
+        for program in &report.programs {
+            println!("  {:>6}  {}", program.transactions, labels.describe(&program.program_id));
+        }
+        println!();
+    }
+
+    if !report.token_transfers.is_empty() {
+        println!("Token Transfers:");
+        for entry in &report.token_transfers {
+            let transfer = &entry.transfer;
+            let wallet = |owner: Option<Pubkey>, account: Pubkey| labels.describe(&owner.unwrap_or(account));
+            println!(
+                "  {} {} {} {} {} -> {}",
+                entry.slot,
+                entry.signature,
+                transfer.ui_amount.clone().unwrap_or_else(|| transfer.amount.to_string()),
+                match (&transfer.symbol, transfer.mint) {
+                    (Some(symbol), _) => symbol.clone(),
+                    (None, Some(mint)) => labels.describe(&mint),
+                    (None, None) => "unknown mint".to_string(),
+                },
+                wallet(transfer.source_owner, transfer.source),
+                wallet(transfer.destination_owner, transfer.destination)
+            );
+        }
+        println!();
+    }
+}
+
+// A signed token amount in UI units, e.g. "+1.5".
+fn format_token_delta(delta: i128, decimals: u8) -> String {
+    match u64::try_from(delta.unsigned_abs()) {
+        Ok(amount) => format!("{}{}", if delta < 0 { "-" } else { "+" }, format_ui_amount(amount, decimals)),
+        Err(_) => format!("{:+} base units", delta),
+    }
+}
+
+fn print_sandwiches(findings: &[Sandwich], tokens: &TokenRegistry) {
+    println!("================================");
+    println!("SANDWICHES ({})", findings.len());
+    println!("================================");
+    for sandwich in findings {
+        println!("\nSlot {}: pool {} ({})", sandwich.slot, sandwich.pool, sandwich.venue);
+        println!("  Attacker:  {}", sandwich.attacker);
+        let swap = |role: &str, swap: &SandwichSwap| {
+            println!(
+                "  {:<10} #{:<5} {} -> {}  {}",
+                role,
+                swap.index,
+                tokens.format_amount(&swap.in_mint, swap.in_amount, None),
+                tokens.format_amount(&swap.out_mint, swap.out_amount, None),
+                swap.signature
+            );
+        };
+        swap("Front-run", &sandwich.front_run);
+        for victim in &sandwich.victims {
+            swap("Victim", victim);
+        }
+        swap("Back-run", &sandwich.back_run);
+        let profit = sandwich.profit.unsigned_abs().min(u64::MAX as u128) as u64;
+        let sign = if sandwich.profit < 0 { "-" } else { "" };
+        println!("  Profit:    {}{}", sign, tokens.format_amount(&sandwich.profit_mint, profit, None));
+    }
+}
+
+fn print_validation_report(report: &ValidationReport, labels: &LabelRegistry) {
+    println!("================================");
+    println!("VALIDATION ({} errors)", report.errors());
+    println!("================================");
+    println!("Blocks:        {} parsed, {} failed", report.blocks, report.failures.len());
+    println!("Transactions:  {}", report.transactions);
+    println!("================================\n");
+
+    if !report.failures.is_empty() {
+        println!("Failures:");
+        for failure in &report.failures {
+            println!("  {}", failure);
+        }
+        println!();
+    }
+    if !report.anomalies.is_empty() {
+        println!("Anomalies:");
+        for anomaly in &report.anomalies {
+            match anomaly.slot {
+                Some(slot) => println!("  slot {}: {}", slot, anomaly.message),
+                None => println!("  {}", anomaly.message),
+            }
+        }
+        println!();
+    }
+    if !report.warnings.is_empty() {
+        println!("Warnings:");
+        for warning in &report.warnings {
+            println!("  {:>6}  {}", warning.count, warning.kind);
+            for sample in &warning.samples {
+                println!("          {}", sample);
+            }
+        }
+        println!();
+    }
+    if !report.unknown_programs.is_empty() {
+        println!("Programs Without a Decoder:");
+        println!("  {:>10} {:>10}  Program", "Calls", "Txs");
+        for program in &report.unknown_programs {
+            println!("  {:>10} {:>10}  {}", program.instructions, program.transactions, labels.describe(&program.program_id));
+        }
+        println!();
+    }
+}
+
+fn print_program_leaderboard(leaderboard: &ProgramLeaderboard) {
+    println!("================================");
+    println!("TOP PROGRAMS ({} of {})", leaderboard.programs.len(), leaderboard.programs_seen);
+    println!("================================");
+    println!("Blocks:        {}", leaderboard.blocks);
+    println!("Transactions:  {}", leaderboard.transactions);
+    println!("Total Fees:    {} lamports", leaderboard.total_fees);
+    println!("================================\n");
+    println!("  {:>10} {:>10} {:>10} {:>14} {:>9}  Program", "Calls", "CPI", "Txs", "Compute Units", "Fee Share");
+    for usage in &leaderboard.programs {
+        let program = match &usage.label {
+            Some(label) => format!("{} ({})", label, usage.program_id),
+            None => usage.program_id.to_string(),
+        };
+        println!(
+            "  {:>10} {:>10} {:>10} {:>14} {:>8.1}%  {}",
+            usage.invocations,
+            usage.cpi_invocations,
+            usage.transactions,
+            usage.compute_units,
+            usage.fee_share * 100.0,
+            program
+        );
+    }
+    println!();
+}
+
+fn print_leader_stats(tally: &LeaderTally, labels: &LabelRegistry) {
+    println!("================================");
+    println!("LEADERS ({} blocks)", tally.blocks());
+    println!("================================");
+    println!("  {:>6} {:>8} {:>8} {:>14} {:>14}  Leader", "Blocks", "Txs", "Votes", "Tx Fees", "Fees Earned");
+    for stats in &tally.leaders {
+        println!(
+            "  {:>6} {:>8} {:>8} {:>14} {:>14}  {}",
+            stats.blocks,
+            stats.transactions,
+            stats.vote_transactions,
+            stats.transaction_fees,
+            stats.fees_earned,
+            labels.describe(&stats.leader)
+        );
+    }
+    if tally.unattributed_blocks > 0 {
+        println!("  {:>6} blocks with an unknown leader", tally.unattributed_blocks);
+    }
     println!();
-}
\ No newline at end of file
+}
+
+fn print_percentiles(label: &str, percentiles: Option<Percentiles>) {
+    match percentiles {
+        Some(p) => println!("  {}: min {} / p50 {} / p90 {} / p99 {} / max {}", label, p.min, p.p50, p.p90, p.p99, p.max),
+        None => println!("  {}: n/a", label),
+    }
+}
+
+fn print_diff_summary(diff: &BlockDiff) {
+    println!("================================");
+    println!("BLOCK DIFF");
+    println!("================================");
+    if diff.is_empty() {
+        println!("Blocks are identical ({} transactions)", diff.left_transactions);
+        return;
+    }
+    for field in &diff.header {
+        println!("{:<20}{} -> {}", format!("{}:", field.field), field.left, field.right);
+    }
+    println!("Transactions:       {} -> {}", diff.left_transactions, diff.right_transactions);
+    println!("Only in first:      {}", diff.only_left.len());
+    println!("Only in second:     {}", diff.only_right.len());
+    println!("Changed:            {}", diff.changed.len());
+    println!("Reordered:          {}", diff.reordered);
+    println!("================================\n");
+
+    for signature in &diff.only_left {
+        println!("- {}", signature);
+    }
+    for signature in &diff.only_right {
+        println!("+ {}", signature);
+    }
+    for tx in &diff.changed {
+        println!("~ {} ({})", tx.signature, tx.fields.join(", "));
+    }
+
+    if !diff.rewards_only_left.is_empty() || !diff.rewards_only_right.is_empty() || !diff.rewards_changed.is_empty() {
+        println!();
+        println!("Rewards:");
+        for reward in &diff.rewards_only_left {
+            println!("- {} {} lamports ({})", reward.pubkey, reward.lamports, reward.reward_type);
+        }
+        for reward in &diff.rewards_only_right {
+            println!("+ {} {} lamports ({})", reward.pubkey, reward.lamports, reward.reward_type);
+        }
+        for reward in &diff.rewards_changed {
+            let fields: Vec<String> =
+                reward.fields.iter().map(|field| format!("{} {} -> {}", field.field, field.left, field.right)).collect();
+            println!("~ {} ({}): {}", reward.pubkey, reward.reward_type, fields.join(", "));
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "native")]
+use std::error::Error;
+use std::fmt::Write as _;
+#[cfg(feature = "native")]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(feature = "native")]
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "native")]
+use std::thread;
+use std::time::{Duration, Instant};
+
+// ==========================================
+// METRICS
+// ==========================================
+// Process-wide counters for long-running ingestion, updated by the parser,
+// the RPC client and the output pipeline, and served in the Prometheus text
+// format by `serve`. Counting is always on: a few atomic adds per block.
+// Latencies are histograms labelled by RPC method or sink name.
+
+// Upper bounds, in seconds, of the latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+pub struct Metrics {
+    blocks_parsed: AtomicU64,
+    transactions_parsed: AtomicU64,
+    // Transactions skipped as malformed and blocks that failed to parse.
+    parse_errors: AtomicU64,
+    // Transactions seen earlier in the run (see dedup).
+    duplicate_transactions: AtomicU64,
+    lookup_table_cache_hits: AtomicU64,
+    lookup_table_cache_misses: AtomicU64,
+    // Transactions per second between the last two parsed blocks.
+    throughput: Mutex<Throughput>,
+    rpc_errors: Mutex<BTreeMap<String, u64>>,
+    rpc_latency: Mutex<BTreeMap<String, Histogram>>,
+    sink_errors: Mutex<BTreeMap<String, u64>>,
+    sink_latency: Mutex<BTreeMap<String, Histogram>>,
+    written: Mutex<WrittenBlocks>,
+}
+
+// Blocks handed to the outputs, and the slots and block times they span.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WrittenBlocks {
+    pub count: u64,
+    pub first_slot: Option<u64>,
+    pub last_slot: Option<u64>,
+    pub first_block_time: Option<i64>,
+    pub last_block_time: Option<i64>,
+}
+
+// Writes to one sink: how many, how many failed, and the time they took.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SinkWrites {
+    pub writes: u64,
+    pub errors: u64,
+    pub seconds: f64,
+}
+
+struct Throughput {
+    last_block: Option<Instant>,
+    transactions_per_second: f64,
+}
+
+#[derive(Default)]
+struct Histogram {
+    // Cumulative: counts[i] is the number of observations <= LATENCY_BUCKETS[i].
+    counts: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&mut self.counts) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+static METRICS: Metrics = Metrics::new();
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    pub(crate) const fn new() -> Self {
+        Metrics {
+            blocks_parsed: AtomicU64::new(0),
+            transactions_parsed: AtomicU64::new(0),
+            parse_errors: AtomicU64::new(0),
+            duplicate_transactions: AtomicU64::new(0),
+            lookup_table_cache_hits: AtomicU64::new(0),
+            lookup_table_cache_misses: AtomicU64::new(0),
+            throughput: Mutex::new(Throughput { last_block: None, transactions_per_second: 0.0 }),
+            rpc_errors: Mutex::new(BTreeMap::new()),
+            rpc_latency: Mutex::new(BTreeMap::new()),
+            sink_errors: Mutex::new(BTreeMap::new()),
+            sink_latency: Mutex::new(BTreeMap::new()),
+            written: Mutex::new(WrittenBlocks {
+                count: 0,
+                first_slot: None,
+                last_slot: None,
+                first_block_time: None,
+                last_block_time: None,
+            }),
+        }
+    }
+
+    pub fn record_block(&self, transactions: usize) {
+        self.blocks_parsed.fetch_add(1, Ordering::Relaxed);
+        self.transactions_parsed.fetch_add(transactions as u64, Ordering::Relaxed);
+        let mut throughput = lock(&self.throughput);
+        let now = Instant::now();
+        if let Some(last) = throughput.last_block.replace(now) {
+            let elapsed = now.duration_since(last).as_secs_f64();
+            if elapsed > 0.0 {
+                throughput.transactions_per_second = transactions as f64 / elapsed;
+            }
+        }
+    }
+
+    pub fn record_parse_errors(&self, count: usize) {
+        self.parse_errors.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_duplicate_transactions(&self, count: usize) {
+        self.duplicate_transactions.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_lookup_table_cache(&self, hit: bool) {
+        let counter = if hit { &self.lookup_table_cache_hits } else { &self.lookup_table_cache_misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // One HTTP round trip, retried attempts included individually.
+    pub fn record_rpc(&self, method: &str, elapsed: Duration, ok: bool) {
+        lock(&self.rpc_latency).entry(method.to_string()).or_default().observe(elapsed.as_secs_f64());
+        if !ok {
+            *lock(&self.rpc_errors).entry(method.to_string()).or_default() += 1;
+        }
+    }
+
+    pub fn record_sink_write(&self, sink: &str, elapsed: Duration, ok: bool) {
+        lock(&self.sink_latency).entry(sink.to_string()).or_default().observe(elapsed.as_secs_f64());
+        if !ok {
+            *lock(&self.sink_errors).entry(sink.to_string()).or_default() += 1;
+        }
+    }
+
+    // A block passed to the outputs; `slot` as the block has it.
+    pub fn record_written_block(&self, slot: u64, block_time: Option<i64>) {
+        let mut written = lock(&self.written);
+        written.count += 1;
+        written.first_slot = Some(written.first_slot.map_or(slot, |first| first.min(slot)));
+        written.last_slot = Some(written.last_slot.map_or(slot, |last| last.max(slot)));
+        if let Some(time) = block_time {
+            written.first_block_time = Some(written.first_block_time.map_or(time, |first| first.min(time)));
+            written.last_block_time = Some(written.last_block_time.map_or(time, |last| last.max(time)));
+        }
+    }
+
+    pub fn blocks_parsed(&self) -> u64 {
+        self.blocks_parsed.load(Ordering::Relaxed)
+    }
+
+    pub fn transactions_parsed(&self) -> u64 {
+        self.transactions_parsed.load(Ordering::Relaxed)
+    }
+
+    pub fn parse_errors(&self) -> u64 {
+        self.parse_errors.load(Ordering::Relaxed)
+    }
+
+    pub fn duplicate_transactions(&self) -> u64 {
+        self.duplicate_transactions.load(Ordering::Relaxed)
+    }
+
+    // Failed requests by RPC method.
+    pub fn rpc_errors(&self) -> BTreeMap<String, u64> {
+        lock(&self.rpc_errors).clone()
+    }
+
+    pub fn sink_writes(&self) -> BTreeMap<String, SinkWrites> {
+        let errors = lock(&self.sink_errors);
+        lock(&self.sink_latency)
+            .iter()
+            .map(|(sink, histogram)| {
+                let writes = SinkWrites {
+                    writes: histogram.count,
+                    errors: errors.get(sink).copied().unwrap_or(0),
+                    seconds: histogram.sum,
+                };
+                (sink.clone(), writes)
+            })
+            .collect()
+    }
+
+    pub fn written_blocks(&self) -> WrittenBlocks {
+        *lock(&self.written)
+    }
+
+    // Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        counter(&mut out, "phase1_blocks_parsed_total", "Blocks parsed.", self.blocks_parsed());
+        counter(&mut out, "phase1_transactions_parsed_total", "Transactions parsed.", self.transactions_parsed());
+        counter(&mut out, "phase1_parse_errors_total", "Transactions skipped as malformed and blocks that failed to parse.", self.parse_errors());
+        let duplicates = self.duplicate_transactions.load(Ordering::Relaxed);
+        counter(&mut out, "phase1_duplicate_transactions_total", "Transactions seen earlier in the run (--dedup).", duplicates);
+        let hits = self.lookup_table_cache_hits.load(Ordering::Relaxed);
+        counter(&mut out, "phase1_lookup_table_cache_hits_total", "Lookup tables served from the cache.", hits);
+        let misses = self.lookup_table_cache_misses.load(Ordering::Relaxed);
+        counter(&mut out, "phase1_lookup_table_cache_misses_total", "Lookup tables fetched because they weren't cached.", misses);
+        let _ = writeln!(out, "# HELP phase1_transactions_per_second Transactions per second between the last two parsed blocks.");
+        let _ = writeln!(out, "# TYPE phase1_transactions_per_second gauge");
+        let _ = writeln!(out, "phase1_transactions_per_second {}", lock(&self.throughput).transactions_per_second);
+
+        let _ = writeln!(out, "# HELP phase1_rpc_errors_total Failed RPC requests, retries included.");
+        let _ = writeln!(out, "# TYPE phase1_rpc_errors_total counter");
+        for (method, count) in lock(&self.rpc_errors).iter() {
+            let _ = writeln!(out, "phase1_rpc_errors_total{{method=\"{}\"}} {}", method, count);
+        }
+        histograms(&mut out, "phase1_rpc_request_duration_seconds", "RPC request latency.", "method", &lock(&self.rpc_latency));
+        let _ = writeln!(out, "# HELP phase1_sink_errors_total Failed output and sink writes.");
+        let _ = writeln!(out, "# TYPE phase1_sink_errors_total counter");
+        for (sink, count) in lock(&self.sink_errors).iter() {
+            let _ = writeln!(out, "phase1_sink_errors_total{{sink=\"{}\"}} {}", sink, count);
+        }
+        histograms(&mut out, "phase1_sink_write_duration_seconds", "Output and sink write latency.", "sink", &lock(&self.sink_latency));
+        out
+    }
+}
+
+// A panic while a lock was held can at worst leave one update half applied,
+// which is no reason to stop counting or serving metrics.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn histograms(out: &mut String, name: &str, help: &str, label: &str, histograms: &BTreeMap<String, Histogram>) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    for (value, histogram) in histograms {
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.counts) {
+            let _ = writeln!(out, "{}_bucket{{{}=\"{}\",le=\"{}\"}} {}", name, label, value, bound, count);
+        }
+        let _ = writeln!(out, "{}_bucket{{{}=\"{}\",le=\"+Inf\"}} {}", name, label, value, histogram.count);
+        let _ = writeln!(out, "{}_sum{{{}=\"{}\"}} {}", name, label, value, histogram.sum);
+        let _ = writeln!(out, "{}_count{{{}=\"{}\"}} {}", name, label, value, histogram.count);
+    }
+}
+
+// ==========================================
+// HTTP ENDPOINT
+// ==========================================
+// Answers GET /metrics on a background thread for the life of the process.
+// One connection at a time is plenty for a scraper.
+
+#[cfg(feature = "native")]
+pub fn serve(addr: &str) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(addr).map_err(|e| format!("metrics endpoint {}: {}", addr, e))?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = respond(stream);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(feature = "native")]
+fn respond(mut stream: TcpStream) -> Result<(), Box<dyn Error>> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the headers so the client sees a clean close.
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("");
+    let (status, body) = match path {
+        "/metrics" => ("200 OK", metrics().render()),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_cumulative_latency_buckets() {
+        let mut histogram = Histogram::default();
+        histogram.observe(0.02);
+        histogram.observe(3.0);
+        let mut out = String::new();
+        histograms(&mut out, "latency", "Latency.", "method", &BTreeMap::from([("getBlock".to_string(), histogram)]));
+
+        assert!(out.contains("latency_bucket{method=\"getBlock\",le=\"0.01\"} 0\n"));
+        assert!(out.contains("latency_bucket{method=\"getBlock\",le=\"0.025\"} 1\n"));
+        assert!(out.contains("latency_bucket{method=\"getBlock\",le=\"5\"} 2\n"));
+        assert!(out.contains("latency_bucket{method=\"getBlock\",le=\"+Inf\"} 2\n"));
+        assert!(out.contains("latency_count{method=\"getBlock\"} 2\n"));
+    }
+    #[test]
+    fn keeps_counting_after_a_panic_while_locked() {
+        let metrics = Metrics::new();
+        let _ = std::panic::catch_unwind(|| {
+            let _guard = metrics.rpc_errors.lock().unwrap();
+            panic!("poison the lock");
+        });
+        metrics.record_rpc("getBlock", Duration::from_millis(5), false);
+        assert_eq!(metrics.rpc_errors()["getBlock"], 1);
+        assert!(metrics.render().contains("getBlock"));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::dex::{detect_swap, SwapEvent, Venue};
+use crate::pubkey::Pubkey;
+use crate::types::ParsedBlock;
+use serde::{Deserialize, Serialize};
+
+// ==========================================
+// SANDWICH DETECTION
+// ==========================================
+// Candidate sandwiches in one block's swaps, in block order: a trader swaps
+// on a pool (front-run), one or more other traders swap the same way on the
+// same pool, and the first trader swaps back on it (back-run), all within
+// `max_span` transaction indices. These are patterns, not proof: a
+// market maker rebalancing around unrelated flow matches too, so the
+// findings carry everything needed to judge them. Each swap belongs to at
+// most one finding.
+
+// Front-run to back-run distance checked by default.
+pub const DEFAULT_MAX_SPAN: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SandwichSwap {
+    // Position in the block's transactions.
+    pub index: usize,
+    pub signature: String,
+    pub trader: Pubkey,
+    pub in_mint: Pubkey,
+    pub in_amount: u64,
+    pub out_mint: Pubkey,
+    pub out_amount: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sandwich {
+    pub slot: u64,
+    pub venue: Venue,
+    pub pool: Pubkey,
+    pub attacker: Pubkey,
+    pub front_run: SandwichSwap,
+    pub victims: Vec<SandwichSwap>,
+    pub back_run: SandwichSwap,
+    // Back-run output less front-run input, in the mint the front-run
+    // spent; negative for a loss.
+    pub profit_mint: Pubkey,
+    pub profit: i128,
+}
+
+// A block's swap, with where it sits.
+struct IndexedSwap<'a> {
+    index: usize,
+    signature: &'a str,
+    swap: SwapEvent,
+}
+
+impl IndexedSwap<'_> {
+    fn touches(&self, pool: &Pubkey) -> bool {
+        self.swap.legs.iter().any(|leg| leg.pool.as_ref() == Some(pool))
+    }
+
+    fn summary(&self) -> SandwichSwap {
+        SandwichSwap {
+            index: self.index,
+            signature: self.signature.to_string(),
+            trader: self.swap.trader,
+            in_mint: self.swap.in_mint,
+            in_amount: self.swap.in_amount,
+            out_mint: self.swap.out_mint,
+            out_amount: self.swap.out_amount,
+        }
+    }
+}
+
+pub fn detect_sandwiches(block: &ParsedBlock, max_span: usize) -> Vec<Sandwich> {
+    let swaps: Vec<IndexedSwap> = block
+        .transactions
+        .iter()
+        .enumerate()
+        .filter_map(|(index, tx)| Some(IndexedSwap { index, signature: &tx.signature, swap: detect_swap(tx)? }))
+        .collect();
+    find_sandwiches(block.slot, &swaps, max_span)
+}
+
+fn find_sandwiches(slot: u64, swaps: &[IndexedSwap], max_span: usize) -> Vec<Sandwich> {
+    let mut findings = Vec::new();
+    let mut used = vec![false; swaps.len()];
+    for (front_at, front) in swaps.iter().enumerate() {
+        if used[front_at] || front.swap.in_mint == front.swap.out_mint {
+            continue;
+        }
+        let attacker = front.swap.trader;
+        for leg in &front.swap.legs {
+            let Some(pool) = leg.pool else {
+                continue;
+            };
+            let window = swaps[front_at + 1..].iter().enumerate().take_while(|(_, swap)| swap.index - front.index <= max_span);
+            let mut victims = Vec::new();
+            let mut back = None;
+            for (offset, candidate) in window {
+                let at = front_at + 1 + offset;
+                if used[at] || !candidate.touches(&pool) {
+                    continue;
+                }
+                let same_way = candidate.swap.in_mint == front.swap.in_mint && candidate.swap.out_mint == front.swap.out_mint;
+                let reverse = candidate.swap.in_mint == front.swap.out_mint && candidate.swap.out_mint == front.swap.in_mint;
+                if candidate.swap.trader == attacker {
+                    if reverse {
+                        back = Some(at);
+                        break;
+                    }
+                } else if same_way {
+                    victims.push(at);
+                }
+            }
+            let Some(back_at) = back.filter(|_| !victims.is_empty()) else {
+                continue;
+            };
+            used[front_at] = true;
+            used[back_at] = true;
+            let back = &swaps[back_at];
+            findings.push(Sandwich {
+                slot,
+                venue: leg.venue,
+                pool,
+                attacker,
+                front_run: front.summary(),
+                victims: victims.iter().map(|&at| swaps[at].summary()).collect(),
+                back_run: back.summary(),
+                profit_mint: front.swap.in_mint,
+                profit: back.swap.out_amount as i128 - front.swap.in_amount as i128,
+            });
+            break;
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::SwapLeg;
+
+    fn swap(index: usize, trader: u8, pool: u8, (in_mint, in_amount): (u8, u64), (out_mint, out_amount): (u8, u64)) -> IndexedSwap<'static> {
+        let leg = SwapLeg {
+            venue: Venue::RaydiumAmmV4,
+            program_id: Pubkey::default(),
+            pool: Some(Pubkey::new([pool; 32])),
+            instruction_index: 0,
+            inner_index: None,
+        };
+        IndexedSwap {
+            index,
+            signature: "sig",
+            swap: SwapEvent {
+                venue: Venue::RaydiumAmmV4,
+                trader: Pubkey::new([trader; 32]),
+                in_mint: Pubkey::new([in_mint; 32]),
+                in_amount,
+                in_decimals: None,
+                out_mint: Pubkey::new([out_mint; 32]),
+                out_amount,
+                out_decimals: None,
+                legs: vec![leg],
+            },
+        }
+    }
+
+    #[test]
+    fn flags_buy_victim_sell_on_one_pool() {
+        let (sol, token) = (1, 2);
+        let swaps = [
+            swap(3, 9, 7, (sol, 1_000), (token, 500)),
+            // Another pool, and the victim of the first pool.
+            swap(4, 5, 8, (sol, 10), (token, 5)),
+            swap(5, 6, 7, (sol, 2_000), (token, 900)),
+            swap(6, 9, 7, (token, 500), (sol, 1_100)),
+            // Same shape, but the back-run is past the span.
+            swap(20, 9, 7, (sol, 1_000), (token, 500)),
+            swap(21, 6, 7, (sol, 2_000), (token, 900)),
+            swap(40, 9, 7, (token, 500), (sol, 1_100)),
+        ];
+        let findings = find_sandwiches(100, &swaps, DEFAULT_MAX_SPAN);
+        assert_eq!(findings.len(), 1);
+        let sandwich = &findings[0];
+        assert_eq!((sandwich.pool, sandwich.attacker), (Pubkey::new([7; 32]), Pubkey::new([9; 32])));
+        assert_eq!((sandwich.front_run.index, sandwich.back_run.index), (3, 6));
+        assert_eq!(sandwich.victims.iter().map(|victim| victim.index).collect::<Vec<_>>(), [5]);
+        assert_eq!((sandwich.profit_mint, sandwich.profit), (Pubkey::new([sol; 32]), 100));
+    }
+}
@@ -0,0 +1,1304 @@
+use crate::decoders::stake::STAKE_PROGRAM_ID;
+use crate::decoders::system::SYSTEM_PROGRAM_ID;
+use crate::decoders::{
+    ComputeBudgetDecoder, ComputeBudgetInstruction, DecodedEvent, DecodedInstruction, DecoderRegistry, ProgramDecoder, SystemDecoder, SystemInstruction,
+};
+use crate::encoding::DataEncoding;
+use crate::epoch::EpochSchedule;
+use crate::error::ParseError;
+use crate::failure::{describe_failure, failure_cause, mark_execution, TransactionError};
+use crate::filter::TxFilter;
+use crate::labels::LabelRegistry;
+use crate::logs::{parse_logs, LogRetention};
+use crate::lookup_tables::LookupTableResolver;
+use crate::token_owners::{resolve_token_owners, TokenOwnerResolver};
+use crate::metrics::metrics;
+use crate::pubkey::Pubkey;
+use crate::span;
+use crate::types::*;
+use crate::wire::verify_signatures;
+use log::Level;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::thread;
+
+pub use crate::decoders::compute_budget::COMPUTE_BUDGET_PROGRAM_ID;
+const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+
+// Compute unit limit the runtime assumes without SetComputeUnitLimit: a
+// fixed allowance per top-level instruction, smaller for builtin programs,
+// capped per transaction.
+const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+const BUILTIN_INSTRUCTION_COMPUTE_UNIT_LIMIT: u32 = 3_000;
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+const BUILTIN_PROGRAM_IDS: &[Pubkey] = &[
+    Pubkey::from_str_const("11111111111111111111111111111111"),
+    VOTE_PROGRAM_ID,
+    STAKE_PROGRAM_ID,
+    Pubkey::from_str_const("Config1111111111111111111111111111111111111"),
+    Pubkey::from_str_const("AddressLookupTab1e1111111111111111111111111"),
+    Pubkey::from_str_const("BPFLoader1111111111111111111111111111111111"),
+    Pubkey::from_str_const("BPFLoader2111111111111111111111111111111111"),
+    Pubkey::from_str_const("BPFLoaderUpgradeab1e11111111111111111111111"),
+    Pubkey::from_str_const("LoaderV411111111111111111111111111111111111"),
+    COMPUTE_BUDGET_PROGRAM_ID,
+    ED25519_PROGRAM_ID,
+    SECP256K1_PROGRAM_ID,
+    SECP256R1_PROGRAM_ID,
+];
+
+// Signature verification precompiles; each signature they check is billed
+// like a transaction signature. Their data starts with the signature count.
+pub(crate) const ED25519_PROGRAM_ID: Pubkey = Pubkey::from_str_const("Ed25519SigVerify111111111111111111111111111");
+pub(crate) const SECP256K1_PROGRAM_ID: Pubkey = Pubkey::from_str_const("KeccakSecp256k11111111111111111111111111111");
+pub(crate) const SECP256R1_PROGRAM_ID: Pubkey = Pubkey::from_str_const("Secp256r1SigVerify1111111111111111111111111");
+
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    // When false, ParsedInstruction.accounts is left empty and callers go
+    // through account_indices / resolved_accounts instead.
+    pub resolve_accounts: bool,
+    // Decoders consulted for every instruction; pass an empty registry to
+    // skip decoding entirely.
+    pub decoders: Arc<DecoderRegistry>,
+    // Consulted for v0 transactions whose response lacks loadedAddresses.
+    pub lookup_tables: Option<Arc<dyn LookupTableResolver>>,
+    // Consulted for token accounts a transfer uses whose owner the
+    // transaction doesn't name; see ParsedTransaction.token_owners.
+    pub token_owners: Option<Arc<dyn TokenOwnerResolver>>,
+    // Worker threads for block parsing; 0 means one per available core.
+    // Output order and report contents don't depend on this.
+    pub threads: usize,
+    // Fail with a ParseError where the parser would otherwise substitute a
+    // placeholder (out-of-range account indices) or skip a transaction.
+    pub strict: bool,
+    // How instruction data is encoded in the input (base58 for
+    // encoding: json responses) and how ParsedInstruction.data is written.
+    pub input_data_encoding: DataEncoding,
+    pub data_encoding: DataEncoding,
+    // Used to derive ParsedBlock.epoch / slot_index.
+    pub epoch_schedule: EpochSchedule,
+    // Block transactions that don't match are dropped, mostly before being
+    // parsed. Single-transaction parsing ignores it.
+    pub filter: Option<Arc<TxFilter>>,
+    // Check every transaction's signatures against its message and record
+    // the outcome in ParsedTransaction.signatures_verified.
+    pub verify_signatures: bool,
+    // When set, each transaction's account keys are looked up here and the
+    // ones with a label are listed in ParsedTransaction.labels.
+    pub labels: Option<Arc<LabelRegistry>>,
+    // How much of each transaction's log_messages to keep in the output.
+    pub log_retention: LogRetention,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            resolve_accounts: true,
+            decoders: DecoderRegistry::builtins(),
+            lookup_tables: None,
+            token_owners: None,
+            threads: 0,
+            strict: false,
+            input_data_encoding: DataEncoding::Base58,
+            data_encoding: DataEncoding::Base58,
+            epoch_schedule: EpochSchedule::default(),
+            filter: None,
+            verify_signatures: false,
+            labels: None,
+            log_retention: LogRetention::Keep,
+        }
+    }
+}
+
+// ==========================================
+// BLOCK PARSER
+// ==========================================
+pub fn parse_block(block: RpcBlockResult) -> Result<ParsedBlock, ParseError> {
+    Ok(parse_block_with_report(block)?.0)
+}
+
+pub fn parse_block_with_report(block: RpcBlockResult) -> Result<(ParsedBlock, ParseReport), ParseError> {
+    parse_block_with_options(block, &ParseOptions::default())
+}
+
+// Malformed transactions are skipped with a warning, so this only fails in
+// strict mode.
+pub fn parse_block_with_options(
+    block: RpcBlockResult,
+    options: &ParseOptions,
+) -> Result<(ParsedBlock, ParseReport), ParseError> {
+    let _span = span!(Level::Debug, "parse_block", parent_slot = block.parent_slot, transactions = block.transactions.len());
+    let (transactions, report) = parse_transaction_chunks(&block.transactions, 0, options)?;
+    let excluded_votes = options
+        .filter
+        .as_ref()
+        .filter(|f| f.excludes_votes())
+        .map(|_| block.prefiltered_votes + block.transactions.iter().filter(|tx| tx.transaction.is_vote()).count());
+    let parsed_block = assemble_block(block, transactions, excluded_votes, options)?;
+    record_block_metrics(&report, parsed_block.transactions.len());
+    Ok((parsed_block, report))
+}
+
+// Parse a run of block transactions, split into contiguous chunks across
+// worker threads. Chunks are stitched back together in order. `offset` is
+// the position of the first one in the block, used in warnings.
+pub(crate) fn parse_transaction_chunks(
+    transactions: &[RpcBlockTransaction],
+    offset: usize,
+    options: &ParseOptions,
+) -> Result<(Vec<ParsedTransaction>, ParseReport), ParseError> {
+    let workers = worker_count(options.threads, transactions.len());
+    let chunk_size = transactions.len().div_ceil(workers).max(1);
+    let chunk_results: Vec<Result<(Vec<ParsedTransaction>, ParseReport), ParseError>> = if workers <= 1 {
+        vec![parse_transactions(transactions, offset, options)]
+    } else {
+        thread::scope(|scope| {
+            let handles: Vec<_> = transactions
+                .chunks(chunk_size)
+                .enumerate()
+                .map(|(i, chunk)| scope.spawn(move || parse_transactions(chunk, offset + i * chunk_size, options)))
+                .collect();
+            handles.into_iter()
+                .map(|handle| handle.join().expect("transaction parser thread panicked"))
+                .collect()
+        })
+    };
+
+    let mut report = ParseReport::default();
+    let mut parsed_txs: Vec<ParsedTransaction> = Vec::with_capacity(transactions.len());
+    for chunk_result in chunk_results {
+        let (txs, chunk_report) = chunk_result.inspect_err(|_| metrics().record_parse_errors(1))?;
+        parsed_txs.extend(txs);
+        report.warnings.extend(chunk_report.warnings);
+    }
+    Ok((parsed_txs, report))
+}
+
+// The block around already parsed transactions. `block.transactions` isn't
+// looked at, so an incremental parse can pass the header alone.
+pub(crate) fn assemble_block(
+    block: RpcBlockResult,
+    transactions: Vec<ParsedTransaction>,
+    excluded_votes: Option<usize>,
+    options: &ParseOptions,
+) -> Result<ParsedBlock, ParseError> {
+    let Some(slot) = block.parent_slot.checked_add(1) else {
+        return Err(ParseError::MalformedBlock {
+            blockhash: block.blockhash,
+            reason: format!("parent slot {} has no successor", block.parent_slot),
+        });
+    };
+    let rewards: Vec<BlockReward> = block.rewards.iter().map(|r| {
+        BlockReward {
+            pubkey: r.pubkey,
+            lamports: r.lamports,
+            post_balance: r.post_balance,
+            reward_type: r.reward_type.clone(),
+            commission: r.commission,
+        }
+    }).collect();
+    let leader = rewards.iter().find(|reward| reward.reward_type == RewardType::Fee).map(|reward| reward.pubkey);
+
+    let mut parsed_block = ParsedBlock {
+        schema_version: SCHEMA_VERSION,
+        slot: 0,
+        epoch: 0,
+        slot_index: 0,
+        block_height: block.block_height,
+        block_time: None,
+        block_time_rfc3339: None,
+        blockhash: block.blockhash,
+        parent_slot: block.parent_slot,
+        previous_blockhash: block.previous_blockhash,
+        leader,
+        rewards,
+        transactions,
+        signatures: block.signatures,
+        excluded_votes,
+        account_snapshots: Vec::new(),
+        endpoint: block.endpoint,
+    };
+
+    parsed_block.set_slot(slot, &options.epoch_schedule);
+    parsed_block.set_block_time(block.block_time);
+    Ok(parsed_block)
+}
+
+pub(crate) fn record_block_metrics(report: &ParseReport, transactions: usize) {
+    let skipped = report.warnings.iter().filter(|w| matches!(w, ParseWarning::SkippedTransaction { .. })).count();
+    metrics().record_parse_errors(skipped);
+    metrics().record_block(transactions);
+}
+
+fn worker_count(requested: usize, transactions: usize) -> usize {
+    // Small blocks aren't worth the thread spawn.
+    const MIN_TRANSACTIONS_PER_WORKER: usize = 64;
+
+    let available = match requested {
+        0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        n => n,
+    };
+    available.min(transactions / MIN_TRANSACTIONS_PER_WORKER).max(1)
+}
+
+// Parse a run of block transactions, skipping ones that have no signature or
+// fee payer to anchor them. `offset` is the position of the first one in the
+// block, used in warnings and for index_in_block.
+fn parse_transactions(
+    transactions: &[RpcBlockTransaction],
+    offset: usize,
+    options: &ParseOptions,
+) -> Result<(Vec<ParsedTransaction>, ParseReport), ParseError> {
+    let mut report = ParseReport::default();
+    let mut parsed = Vec::with_capacity(transactions.len());
+
+    for (i, tx) in transactions.iter().enumerate() {
+        // The filter drops what it can before parsing, the rest after.
+        let filter = options.filter.as_deref();
+        if filter.is_some_and(|f| !f.may_match(&tx.transaction, &tx.meta)) {
+            continue;
+        }
+        match parse_transaction_with_report(&tx.transaction, &tx.meta, options, &mut report) {
+            Ok(parsed_tx) if filter.is_some_and(|f| !f.matches(&parsed_tx)) => {}
+            Ok(parsed_tx) => parsed.push(ParsedTransaction { index_in_block: Some(tx.index.unwrap_or(offset + i)), ..parsed_tx }),
+            Err(e) if options.strict => return Err(e),
+            Err(ParseError::MissingSignature) => report.warnings.push(ParseWarning::SkippedTransaction {
+                reason: format!("transaction #{} has no signatures", offset + i),
+            }),
+            Err(e) => report.warnings.push(ParseWarning::SkippedTransaction { reason: e.to_string() }),
+        }
+    }
+
+    Ok((parsed, report))
+}
+
+// ==========================================
+// SHARED TRANSACTION PARSING LOGIC
+// ==========================================
+pub fn parse_transaction(
+    tx: &RpcTransactionContainer,
+    meta: &RpcMeta,
+) -> Result<ParsedTransaction, ParseError> {
+    parse_transaction_with_report(tx, meta, &ParseOptions::default(), &mut ParseReport::default())
+}
+
+pub fn parse_transaction_with_options(
+    tx: &RpcTransactionContainer,
+    meta: &RpcMeta,
+    options: &ParseOptions,
+) -> Result<(ParsedTransaction, ParseReport), ParseError> {
+    let mut report = ParseReport::default();
+    let parsed = parse_transaction_with_report(tx, meta, options, &mut report)?;
+    Ok((parsed, report))
+}
+
+// A getTransaction result, with the slot it reports.
+pub fn parse_transaction_result(raw: &RpcResult, options: &ParseOptions) -> Result<(ParsedTransaction, ParseReport), ParseError> {
+    let (parsed, report) = parse_transaction_with_options(&raw.transaction, &raw.meta, options)?;
+    Ok((ParsedTransaction { slot: raw.slot, ..parsed }, report))
+}
+
+fn parse_transaction_with_report(
+    tx: &RpcTransactionContainer,
+    meta: &RpcMeta,
+    options: &ParseOptions,
+    report: &mut ParseReport,
+) -> Result<ParsedTransaction, ParseError> {
+    let message = &tx.message;
+    let signature = tx.signatures.first().ok_or(ParseError::MissingSignature)?.clone();
+    let _span = span!(Level::Trace, "parse_transaction", tx_sig = &signature);
+    let fee_payer = message.account_keys.first()
+        .ok_or_else(|| ParseError::MalformedTransaction {
+            signature: signature.clone(),
+            reason: "no account keys".to_string(),
+        })?;
+    let fee_payer = *fee_payer;
+    let mut out_of_range: Vec<usize> = Vec::new();
+
+    // Build the full account list (static + loaded addresses). Prefer the
+    // node's loadedAddresses; fall back to resolving the lookup tables
+    // ourselves when it is missing or empty.
+    let lookups = message.address_table_lookups.as_deref().unwrap_or_default();
+    let loaded_count = meta.loaded_addresses.as_ref()
+        .map(|loaded| loaded.writable.len() + loaded.readonly.len())
+        .unwrap_or(0);
+    let mut account_warnings = Vec::new();
+    let (loaded_writable, loaded_readonly) = if loaded_count > 0 || lookups.is_empty() {
+        match &meta.loaded_addresses {
+            Some(loaded) => {
+                let (mut writable, mut readonly) = (loaded.writable.clone(), loaded.readonly.clone());
+                account_warnings.extend(dedup_loaded_addresses(&message.account_keys, &mut writable, &mut readonly, meta));
+                (writable, readonly)
+            }
+            None => (Vec::new(), Vec::new()),
+        }
+    } else {
+        resolve_lookup_tables(lookups, options, &signature, report)
+    };
+    let accounts = transaction_accounts(message, &loaded_writable, &loaded_readonly);
+    let mut all_account_keys = message.account_keys.clone();
+    all_account_keys.extend(loaded_writable);
+    all_account_keys.extend(loaded_readonly);
+    if meta.pre_balances.len() != all_account_keys.len() || meta.post_balances.len() != all_account_keys.len() {
+        account_warnings.push(AccountWarning::BalanceCountMismatch {
+            account_keys: all_account_keys.len(),
+            pre_balances: meta.pre_balances.len(),
+            post_balances: meta.post_balances.len(),
+        });
+    }
+
+    account_warnings.extend(validate_message(tx, all_account_keys.len()));
+
+    // Parse instructions
+    let mut parsed_instructions: Vec<ParsedInstruction> = message.instructions.iter()
+        .map(|ix| resolve_instruction(ix, &all_account_keys, options, &mut out_of_range))
+        .collect();
+
+    // Parse inner (CPI) instructions, grouped by the top-level instruction
+    // that triggered them
+    let mut inner_instructions: Vec<ParsedInnerInstructions> = meta.inner_instructions.iter()
+        .flatten()
+        .map(|inner| {
+            let mut instructions: Vec<ParsedInstruction> = inner.instructions.iter()
+                .map(|ix| resolve_instruction(ix, &all_account_keys, options, &mut out_of_range))
+                .collect();
+            link_invocations(&mut instructions);
+            ParsedInnerInstructions { index: inner.index, instructions }
+        })
+        .collect();
+
+    // Token balances, and the per-owner/per-mint deltas between them
+    let pre_token_balances = resolve_token_balances(meta.pre_token_balances.as_deref(), &all_account_keys, &mut out_of_range);
+    let post_token_balances = resolve_token_balances(meta.post_token_balances.as_deref(), &all_account_keys, &mut out_of_range);
+    let token_balance_changes = token_balance_changes(&pre_token_balances, &post_token_balances);
+
+    if options.strict && let Some(&index) = out_of_range.first() {
+        return Err(ParseError::AccountIndexOutOfRange { signature, index, len: all_account_keys.len() });
+    }
+    for &index in &out_of_range {
+        report.warnings.push(ParseWarning::AccountIndexOutOfRange {
+            tx_sig: signature.clone(),
+            index,
+        });
+    }
+    if !out_of_range.is_empty() {
+        out_of_range.sort_unstable();
+        out_of_range.dedup();
+        account_warnings.push(AccountWarning::AccountIndexOutOfRange { indices: out_of_range, account_keys: all_account_keys.len() });
+    }
+    // Out-of-range indices were reported one by one above.
+    for warning in account_warnings.iter().filter(|warning| !matches!(warning, AccountWarning::AccountIndexOutOfRange { .. })) {
+        report.warnings.push(ParseWarning::InconsistentAccounts { tx_sig: signature.clone(), warning: warning.clone() });
+    }
+    if meta.compute_units_consumed.is_none() {
+        report.warnings.push(ParseWarning::MissingComputeUnits {
+            tx_sig: signature.clone(),
+        });
+    }
+
+    let signatures_verified = options.verify_signatures.then(|| verify_signatures(tx, options.input_data_encoding));
+    match signatures_verified {
+        Some(Some(false)) => report.warnings.push(ParseWarning::InvalidSignature { tx_sig: signature.clone() }),
+        Some(None) => report.warnings.push(ParseWarning::UnverifiedSignature { tx_sig: signature.clone() }),
+        _ => {}
+    }
+
+    let error = meta.err.as_ref().map(TransactionError::from_value);
+    let failure_cause =
+        error.as_ref().map(|error| failure_cause(error, &parsed_instructions, &meta.log_messages, &options.decoders));
+    let failure_reason = error.as_ref().zip(failure_cause.as_ref()).map(|(error, cause)| describe_failure(error, cause));
+    if let Some(error) = &error {
+        mark_execution(error, &mut parsed_instructions, &mut inner_instructions);
+    }
+
+    let budget = decode_compute_budget(&parsed_instructions);
+    let fee_breakdown = fee_breakdown(&parsed_instructions, &budget, tx.signatures.len() as u64, meta.compute_units_consumed);
+
+    // Base fee is a flat charge per signature; anything above it is priority.
+    // Saturate so malformed data with fee < base_fee reports 0, not a panic.
+    let base_fee = LAMPORTS_PER_SIGNATURE * tx.signatures.len() as u64;
+    let priority_fee = meta.fee.saturating_sub(base_fee);
+
+    let memos = extract_memos(&parsed_instructions, &inner_instructions);
+    let durable_nonce = durable_nonce(&parsed_instructions, message.recent_blockhash.as_deref());
+    let labels = options.labels.as_ref().map(|registry| account_labels(&all_account_keys, registry)).unwrap_or_default();
+
+    let mut parsed = ParsedTransaction {
+        schema_version: SCHEMA_VERSION,
+        signature,
+        slot: None,
+        index_in_block: None,
+        timestamp_ms: None,
+        fee_payer,
+        is_success: meta.err.is_none(),
+        error,
+        failure_reason,
+        failure_cause,
+        account_keys: all_account_keys,
+        accounts,
+        instructions: parsed_instructions,
+        inner_instructions,
+        log_messages: options.log_retention.apply(&meta.log_messages),
+        events: decode_events(&meta.log_messages, &options.decoders),
+        memos,
+        pre_balances: meta.pre_balances.clone(),
+        post_balances: meta.post_balances.clone(),
+        pre_token_balances,
+        post_token_balances,
+        token_balance_changes,
+        fee: meta.fee,
+        base_fee,
+        priority_fee,
+        compute_units_consumed: meta.compute_units_consumed,
+        compute_unit_limit: budget.limit,
+        unit_price_micro_lamports: budget.price,
+        compute_by_program: compute_by_program(&meta.log_messages),
+        fee_breakdown,
+        signatures_verified: signatures_verified.flatten(),
+        labels,
+        account_warnings,
+        durable_nonce,
+        token_owners: BTreeMap::new(),
+    };
+    if let Some(resolver) = &options.token_owners {
+        resolve_token_owners(&mut parsed, resolver.as_ref());
+    }
+    Ok(parsed)
+}
+
+fn account_labels(account_keys: &[Pubkey], registry: &LabelRegistry) -> BTreeMap<Pubkey, String> {
+    account_keys
+        .iter()
+        .filter_map(|key| registry.get(key).map(|label| (*key, label.to_string())))
+        .collect()
+}
+
+// Static keys take their roles from the header: signers first, then
+// non-signers, each group ending with its read-only accounts. Loaded
+// addresses are never signers.
+fn transaction_accounts(message: &RpcMessage, writable: &[Pubkey], readonly: &[Pubkey]) -> Vec<TransactionAccount> {
+    let header = &message.header;
+    let signers = header.num_required_signatures as usize;
+    let writable_signers = signers.saturating_sub(header.num_readonly_signed_accounts as usize);
+    let writable_unsigned_end = message.account_keys.len().saturating_sub(header.num_readonly_unsigned_accounts as usize);
+
+    let statics = message.account_keys.iter().enumerate().map(|(i, pubkey)| TransactionAccount {
+        pubkey: *pubkey,
+        is_signer: i < signers,
+        is_writable: if i < signers { i < writable_signers } else { i < writable_unsigned_end },
+        source: AccountSource::Static,
+    });
+    let loaded = |keys: &[Pubkey], is_writable: bool| {
+        keys.iter()
+            .map(move |pubkey| TransactionAccount { pubkey: *pubkey, is_signer: false, is_writable, source: AccountSource::Lookup })
+            .collect::<Vec<_>>()
+    };
+    statics.chain(loaded(writable, true)).chain(loaded(readonly, false)).collect()
+}
+
+// Invariants the runtime would have enforced on the message, checked
+// against what the RPC response claims. Index ranges are checked while the
+// instructions and token balances are resolved.
+fn validate_message(tx: &RpcTransactionContainer, account_keys: usize) -> Vec<AccountWarning> {
+    let mut warnings = Vec::new();
+    let header = &tx.message.header;
+    let (static_keys, required) = (tx.message.account_keys.len(), header.num_required_signatures as usize);
+    if tx.signatures.len() != required {
+        warnings.push(AccountWarning::SignatureCountMismatch { signatures: tx.signatures.len(), required });
+    }
+    if required > static_keys
+        || header.num_readonly_signed_accounts as usize > required
+        || header.num_readonly_unsigned_accounts as usize > static_keys.saturating_sub(required)
+    {
+        warnings.push(AccountWarning::InvalidHeader {
+            static_keys,
+            required_signatures: header.num_required_signatures,
+            readonly_signed: header.num_readonly_signed_accounts,
+            readonly_unsigned: header.num_readonly_unsigned_accounts,
+        });
+    }
+    if account_keys > MAX_ACCOUNT_KEYS {
+        warnings.push(AccountWarning::TooManyAccountKeys { account_keys });
+    }
+    warnings
+}
+
+// Buggy RPC responses sometimes repeat a static key (or an earlier loaded
+// address) in loadedAddresses, shifting every account after it. A
+// transaction can't load an account twice, so the later copies are dropped,
+// but only when the balances arrays agree: they hold one entry per account
+// the node actually loaded. Otherwise the keys are left as they are and the
+// warning is all that's said.
+fn dedup_loaded_addresses(
+    static_keys: &[Pubkey],
+    writable: &mut Vec<Pubkey>,
+    readonly: &mut Vec<Pubkey>,
+    meta: &RpcMeta,
+) -> Option<AccountWarning> {
+    let loaded: Vec<Pubkey> = writable.iter().chain(readonly.iter()).copied().collect();
+    let repeated = |(i, key): &(usize, &Pubkey)| static_keys.contains(key) || loaded[..*i].contains(key);
+    let keys: Vec<Pubkey> = loaded.iter().enumerate().filter(repeated).map(|(_, key)| *key).collect();
+    if keys.is_empty() {
+        return None;
+    }
+    let unique = static_keys.len() + loaded.len() - keys.len();
+    let removed = meta.pre_balances.len() == unique && meta.post_balances.len() == unique;
+    if removed {
+        let mut seen: Vec<Pubkey> = static_keys.to_vec();
+        for list in [writable, readonly] {
+            list.retain(|key| {
+                let first = !seen.contains(key);
+                seen.push(*key);
+                first
+            });
+        }
+    }
+    Some(AccountWarning::DuplicateAccountKeys { keys, removed })
+}
+
+// Returns (writable, readonly) addresses in message order: every table's
+// writable entries first, then every table's readonly entries. Entries that
+// can't be resolved keep their slot as a placeholder (the default, all-zero
+// key) so later indices stay aligned.
+fn resolve_lookup_tables(
+    lookups: &[RpcAddressTableLookup],
+    options: &ParseOptions,
+    signature: &str,
+    report: &mut ParseReport,
+) -> (Vec<Pubkey>, Vec<Pubkey>) {
+    let mut writable = Vec::new();
+    let mut readonly = Vec::new();
+
+    for lookup in lookups {
+        // Entries up to the highest index used must be there.
+        let min_len = lookup.writable_indexes.iter().chain(&lookup.readonly_indexes).max().map_or(0, |idx| *idx as usize + 1);
+        let table = options.lookup_tables.as_ref().and_then(|r| r.resolve_prefix(&lookup.account_key, min_len));
+        if table.is_none() {
+            report.warnings.push(ParseWarning::UnresolvedLookupTable {
+                tx_sig: signature.to_string(),
+                table: lookup.account_key,
+            });
+        }
+
+        let entry = |idx: &u8| {
+            table.as_ref()
+                .and_then(|addresses| addresses.get(*idx as usize).copied())
+                .unwrap_or_default()
+        };
+        writable.extend(lookup.writable_indexes.iter().map(entry));
+        readonly.extend(lookup.readonly_indexes.iter().map(entry));
+    }
+
+    (writable, readonly)
+}
+
+// Resolve an instruction's program and account indices against the combined
+// key list. Out-of-range indices are recorded for the parse report and
+// resolve to the default (all-zero) key.
+fn resolve_instruction(
+    ix: &RpcInstruction,
+    all_account_keys: &[Pubkey],
+    options: &ParseOptions,
+    out_of_range: &mut Vec<usize>,
+) -> ParsedInstruction {
+    // Resolve Program ID
+    let program_id = if ix.program_id_index < all_account_keys.len() {
+        all_account_keys[ix.program_id_index]
+    } else {
+        out_of_range.push(ix.program_id_index);
+        Pubkey::default()
+    };
+
+    for &idx in &ix.accounts {
+        if idx >= all_account_keys.len() {
+            out_of_range.push(idx);
+        }
+    }
+
+    // Resolve Accounts
+    let account_addresses: Vec<AccountRef> = if options.resolve_accounts {
+        ix.accounts.iter()
+            .map(|&index| all_account_keys.get(index).map_or(AccountRef::Unresolved { index }, |key| AccountRef::Resolved(*key)))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // The data is only decoded when something needs the bytes: a decoder
+    // for the program, or re-encoding for output. Base58 decoding is
+    // quadratic in the data length, and decoding every instruction (votes
+    // above all) used to dominate block parsing.
+    // Undecodable data is passed through untouched, tagged with the input
+    // encoding so data_bytes() still reports it as invalid.
+    let decoder = options.decoders.get(&program_id);
+    let bytes = if decoder.is_some() || options.data_encoding != options.input_data_encoding {
+        options.input_data_encoding.decode(&ix.data)
+    } else {
+        None
+    };
+    let decoded = decoder
+        .zip(bytes.as_deref())
+        .and_then(|(decoder, data)| decode_instruction(decoder, ix, data, all_account_keys));
+    let (data, data_encoding) = match &bytes {
+        Some(bytes) if options.data_encoding != options.input_data_encoding => {
+            (options.data_encoding.encode(bytes), options.data_encoding)
+        }
+        _ => (ix.data.clone(), options.input_data_encoding),
+    };
+
+    ParsedInstruction {
+        program_id,
+        accounts: account_addresses,
+        account_indices: ix.accounts.clone(),
+        data,
+        data_encoding,
+        decoded,
+        parsed: ix.parsed.clone(),
+        stack_height: ix.stack_height,
+        invoked_by: None,
+        execution: None,
+    }
+}
+
+// Points each inner instruction at the one that invoked it: the closest
+// earlier instruction one stack level up.
+fn link_invocations(instructions: &mut [ParsedInstruction]) {
+    // (stack height, position) of the open invocations.
+    let mut open: Vec<(u32, usize)> = Vec::new();
+    for (position, ix) in instructions.iter_mut().enumerate() {
+        let Some(height) = ix.stack_height else {
+            continue;
+        };
+        while open.last().is_some_and(|&(open_height, _)| open_height >= height) {
+            open.pop();
+        }
+        ix.invoked_by = open.last().filter(|&&(open_height, _)| open_height + 1 == height).map(|&(_, caller)| caller);
+        open.push((height, position));
+    }
+}
+
+// Only attempted when a decoder is registered for the program and every
+// account index resolves.
+fn decode_instruction(
+    decoder: &dyn ProgramDecoder,
+    ix: &RpcInstruction,
+    data: &[u8],
+    all_account_keys: &[Pubkey],
+) -> Option<DecodedInstruction> {
+    let accounts: Vec<Pubkey> = ix.accounts.iter()
+        .map(|&idx| all_account_keys.get(idx).copied())
+        .collect::<Option<_>>()?;
+    decoder.decode(data, &accounts)
+}
+
+fn decode_events(log_messages: &[String], decoders: &DecoderRegistry) -> Vec<DecodedEvent> {
+    if !decoders.decodes_events() {
+        return Vec::new();
+    }
+    parse_logs(log_messages).events().into_iter()
+        .filter_map(|event| {
+            let data = DataEncoding::Base64.decode(&event.data)?;
+            decoders.decode_event(&event.program_id.parse().ok()?, &data)
+        })
+        .collect()
+}
+
+fn compute_by_program(log_messages: &[String]) -> Vec<ProgramComputeUnits> {
+    let mut units: HashMap<Pubkey, u64> = HashMap::new();
+    for invocation in parse_logs(log_messages).walk() {
+        if let (Ok(program_id), Some(own)) = (invocation.program_id.parse(), invocation.own_compute_units()) {
+            *units.entry(program_id).or_default() += own;
+        }
+    }
+    ProgramComputeUnits::ranked(units)
+}
+
+// A memo instruction's data is the memo itself. The Memo program rejects
+// invalid UTF-8, but a failed transaction may still carry some, so it's
+// decoded lossily.
+fn extract_memos(instructions: &[ParsedInstruction], inner_instructions: &[ParsedInnerInstructions]) -> Vec<String> {
+    let memo = |ix: &ParsedInstruction| {
+        if ix.program_id != MEMO_PROGRAM_ID {
+            return None;
+        }
+        // jsonParsed input has the memo already decoded, and no data.
+        if let Some(parsed) = &ix.parsed {
+            return parsed.parsed.as_str().map(str::to_string);
+        }
+        Some(String::from_utf8_lossy(&ix.data_bytes()?).into_owned())
+    };
+    instructions
+        .iter()
+        .enumerate()
+        .flat_map(|(index, ix)| {
+            let cpis = inner_instructions.iter().filter(move |inner| inner.index == index).flat_map(|inner| &inner.instructions);
+            std::iter::once(ix).chain(cpis)
+        })
+        .filter_map(memo)
+        .collect()
+}
+
+fn resolve_token_balances(
+    balances: Option<&[RpcTokenBalance]>,
+    all_account_keys: &[Pubkey],
+    out_of_range: &mut Vec<usize>,
+) -> Vec<TokenBalance> {
+    balances.unwrap_or_default().iter().map(|b| {
+        if b.account_index >= all_account_keys.len() {
+            out_of_range.push(b.account_index);
+        }
+        TokenBalance {
+            account_index: b.account_index,
+            account: all_account_keys.get(b.account_index).copied().unwrap_or_default(),
+            mint: b.mint,
+            owner: b.owner,
+            program_id: b.program_id,
+            amount: b.ui_token_amount.amount.parse().unwrap_or(0),
+            decimals: b.ui_token_amount.decimals,
+        }
+    }).collect()
+}
+
+// Sum pre and post amounts per (owner, mint) and keep the pairs that moved.
+// A token account missing from one side (created or closed in this tx)
+// counts as zero on that side.
+fn token_balance_changes(pre: &[TokenBalance], post: &[TokenBalance]) -> Vec<TokenBalanceChange> {
+    let mut totals: BTreeMap<(Pubkey, Pubkey), (u64, u64, u8)> = BTreeMap::new();
+
+    for b in pre {
+        let owner = b.owner.unwrap_or(b.account);
+        let entry = totals.entry((owner, b.mint)).or_insert((0, 0, b.decimals));
+        entry.0 = entry.0.saturating_add(b.amount);
+    }
+    for b in post {
+        let owner = b.owner.unwrap_or(b.account);
+        let entry = totals.entry((owner, b.mint)).or_insert((0, 0, b.decimals));
+        entry.1 = entry.1.saturating_add(b.amount);
+    }
+
+    let mut changes: Vec<TokenBalanceChange> = totals.into_iter()
+        .filter(|(_, (pre_amount, post_amount, _))| pre_amount != post_amount)
+        .map(|((owner, mint), (pre_amount, post_amount, decimals))| TokenBalanceChange {
+            owner,
+            mint,
+            decimals,
+            pre_amount,
+            post_amount,
+            delta: post_amount as i128 - pre_amount as i128,
+        })
+        .collect();
+    // Ordered by the base58 strings, as when the keys were Strings.
+    changes.sort_by_cached_key(|change| (change.owner.to_string(), change.mint.to_string()));
+    changes
+}
+
+#[derive(Default)]
+struct ComputeBudget {
+    limit: Option<u32>,
+    price: Option<u64>,
+    // Flat priority fee from the deprecated RequestUnits.
+    additional_fee: Option<u64>,
+}
+
+// Pull the compute unit limit and price out of the Compute Budget
+// instructions. Decoded here rather than read from `decoded`, so fees come
+// out the same when the parser runs without the built-in decoders.
+fn decode_compute_budget(instructions: &[ParsedInstruction]) -> ComputeBudget {
+    let mut budget = ComputeBudget::default();
+    for ix in instructions.iter().filter(|ix| ix.program_id == COMPUTE_BUDGET_PROGRAM_ID) {
+        let Some(DecodedInstruction::ComputeBudget(instruction)) =
+            ix.data_bytes().and_then(|data| ComputeBudgetDecoder.decode(&data, &[]))
+        else {
+            continue;
+        };
+        match instruction {
+            ComputeBudgetInstruction::RequestUnits { units, additional_fee } => {
+                budget.limit = Some(units);
+                budget.additional_fee = Some(additional_fee as u64);
+            }
+            ComputeBudgetInstruction::SetComputeUnitLimit { units } => budget.limit = Some(units),
+            ComputeBudgetInstruction::SetComputeUnitPrice { micro_lamports } => budget.price = Some(micro_lamports),
+            _ => {}
+        }
+    }
+    budget
+}
+
+// Decoded here rather than through options.decoders, which may leave the
+// System program out.
+fn durable_nonce(instructions: &[ParsedInstruction], recent_blockhash: Option<&str>) -> Option<DurableNonce> {
+    let ix = instructions.first().filter(|ix| ix.program_id == SYSTEM_PROGRAM_ID)?;
+    let accounts: Vec<Pubkey> = ix.accounts.iter().map(AccountRef::pubkey).collect::<Option<_>>()?;
+    match SystemDecoder.decode(&ix.data_bytes()?, &accounts)? {
+        DecodedInstruction::System(SystemInstruction::AdvanceNonceAccount { nonce_account, nonce_authority }) => {
+            Some(DurableNonce { nonce_account, nonce_authority, nonce: recent_blockhash.map(str::to_string) })
+        }
+        _ => None,
+    }
+}
+
+fn fee_breakdown(instructions: &[ParsedInstruction], budget: &ComputeBudget, signatures: u64, consumed: Option<u64>) -> FeeBreakdown {
+    let precompile_signatures: u64 = instructions
+        .iter()
+        .filter(|ix| [ED25519_PROGRAM_ID, SECP256K1_PROGRAM_ID, SECP256R1_PROGRAM_ID].contains(&ix.program_id))
+        .filter_map(|ix| ix.data_bytes()?.first().copied())
+        .map(u64::from)
+        .sum();
+    let signatures = signatures + precompile_signatures;
+
+    let default_limit = || {
+        instructions
+            .iter()
+            .map(|ix| match BUILTIN_PROGRAM_IDS.contains(&ix.program_id) {
+                true => BUILTIN_INSTRUCTION_COMPUTE_UNIT_LIMIT,
+                false => DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT,
+            })
+            .fold(0u32, u32::saturating_add)
+    };
+    let compute_unit_limit = budget.limit.unwrap_or_else(default_limit).min(MAX_COMPUTE_UNIT_LIMIT);
+    let price = budget.price.unwrap_or(0);
+    let priority_fee = match budget.additional_fee {
+        Some(fee) => fee,
+        None => (price as u128 * compute_unit_limit as u128).div_ceil(MICRO_LAMPORTS_PER_LAMPORT) as u64,
+    };
+
+    FeeBreakdown {
+        signatures,
+        base_fee: LAMPORTS_PER_SIGNATURE * signatures,
+        priority_fee,
+        compute_unit_limit,
+        compute_unit_price_micro_lamports: price,
+        consumed_priority_fee: consumed.map(|units| (price as u128 * units as u128 / MICRO_LAMPORTS_PER_LAMPORT) as u64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::io::load_from_json;
+
+    #[test]
+    fn empty_block_parses_with_zeroed_stats() {
+        let raw: RpcBlockResponse = load_from_json("src/json/empty_block.json").unwrap();
+        let block = parse_block(raw.result).unwrap();
+
+        assert!(block.is_empty());
+        assert_eq!(block.transaction_stats(), (0, 0, 0));
+    }
+
+    #[test]
+    fn null_block_height_and_time_parse_as_none() {
+        let mut raw: serde_json::Value = serde_json::from_str(&std::fs::read_to_string("src/json/empty_block.json").unwrap()).unwrap();
+        raw["result"]["blockHeight"] = serde_json::Value::Null;
+        raw["result"]["blockTime"] = serde_json::Value::Null;
+        let raw: RpcBlockResponse = serde_json::from_value(raw).unwrap();
+        let block = parse_block(raw.result).unwrap();
+
+        assert_eq!((block.block_height, block.block_time, block.block_time_rfc3339), (None, None, None));
+    }
+
+    #[test]
+    fn rewards_may_be_omitted_or_of_unknown_type() {
+        let mut raw: serde_json::Value = serde_json::from_str(&std::fs::read_to_string("src/json/block.json").unwrap()).unwrap();
+        let reward = raw["result"]["rewards"][0].clone();
+        raw["result"].as_object_mut().unwrap().remove("rewards");
+        let block = parse_block(serde_json::from_value::<RpcBlockResponse>(raw.clone()).unwrap().result).unwrap();
+        assert!(block.rewards.is_empty());
+
+        let mut rewards = Vec::new();
+        for reward_type in [serde_json::json!("Rent"), serde_json::json!("Inflation"), serde_json::Value::Null] {
+            let mut reward = reward.clone();
+            reward["rewardType"] = reward_type;
+            rewards.push(reward);
+        }
+        raw["result"]["rewards"] = rewards.into();
+        let block = parse_block(serde_json::from_value::<RpcBlockResponse>(raw).unwrap().result).unwrap();
+        let types: Vec<&RewardType> = block.rewards.iter().map(|reward| &reward.reward_type).collect();
+        assert_eq!(types, [&RewardType::Rent, &RewardType::Unknown("Inflation".to_string()), &RewardType::default()]);
+        assert_eq!(serde_json::to_value(&block.rewards[1]).unwrap()["reward_type"], "Inflation");
+    }
+
+    #[test]
+    fn sample_block_is_not_empty() {
+        let block = fixtures::block();
+
+        assert!(!block.is_empty());
+    }
+
+    #[test]
+    fn durable_nonce_transactions_are_flagged() {
+        let block = fixtures::block();
+        let flagged: Vec<&ParsedTransaction> = block.transactions.iter().filter(|tx| tx.durable_nonce.is_some()).collect();
+        assert_eq!(flagged.len(), 10);
+        assert_eq!(crate::stats::block_stats(block).durable_nonce_transactions, 10);
+        for tx in flagged {
+            let nonce = tx.durable_nonce.as_ref().unwrap();
+            let advance = &tx.instructions[0];
+            assert_eq!(advance.program_id, SYSTEM_PROGRAM_ID);
+            assert_eq!(advance.accounts[0].pubkey(), Some(nonce.nonce_account));
+            assert_eq!(advance.accounts[2].pubkey(), Some(nonce.nonce_authority));
+            assert!(nonce.nonce.as_deref().is_some_and(|value| value != block.previous_blockhash));
+        }
+        // An AdvanceNonceAccount anywhere but first doesn't count.
+        let late = block.transactions.iter().filter(|tx| tx.durable_nonce.is_none()).find(|tx| tx.instructions.len() > 1).unwrap();
+        let mut moved = late.instructions.clone();
+        moved.push(block.transactions.iter().find_map(|tx| tx.durable_nonce.as_ref().map(|_| tx.instructions[0].clone())).unwrap());
+        assert!(durable_nonce(&moved, None).is_none());
+    }
+
+    #[test]
+    fn transactions_carry_their_slot_and_position() {
+        let raw = fixtures::raw_block();
+        let signatures: Vec<String> = raw.result.transactions.iter().map(|tx| tx.transaction.signatures[0].clone()).collect();
+        let options = ParseOptions { filter: Some(Arc::new(TxFilter::new().exclude_votes())), threads: 4, ..ParseOptions::default() };
+        let (block, _) = parse_block_with_options(raw.result, &options).unwrap();
+        assert!(block.excluded_votes.is_some_and(|votes| votes > 0));
+        for tx in &block.transactions {
+            assert_eq!(tx.slot, Some(block.slot));
+            assert_eq!(signatures[tx.index_in_block.unwrap()], tx.signature);
+        }
+
+        let raw: RpcResponse = load_from_json("src/json/transaction.json").unwrap();
+        let (tx, _) = parse_transaction_result(&raw.result, &ParseOptions::default()).unwrap();
+        assert_eq!((tx.slot, tx.index_in_block), (Some(382606676), None));
+        assert_eq!(parse_transaction(&raw.result.transaction, &raw.result.meta).unwrap().slot, None);
+    }
+
+    #[test]
+    fn transaction_without_signatures_is_an_error() {
+        let mut raw: RpcResponse = load_from_json("src/json/transfer.json").unwrap();
+        raw.result.transaction.signatures.clear();
+
+        let err = parse_transaction(&raw.result.transaction, &raw.result.meta).unwrap_err();
+        assert!(matches!(err, ParseError::MissingSignature));
+    }
+
+    #[test]
+    fn blocks_fetched_with_less_transaction_detail_parse() {
+        let header = serde_json::json!({"blockHeight": 10, "blockTime": 20, "blockhash": "h", "parentSlot": 9, "previousBlockhash": "p"});
+        let mut signatures = header.clone();
+        signatures["signatures"] = serde_json::json!(["s1", "s2"]);
+        let block = parse_block(serde_json::from_value(signatures.clone()).unwrap()).unwrap();
+        assert!(block.transactions.is_empty());
+        assert_eq!(block.signatures, ["s1", "s2"]);
+        let envelope = serde_json::json!({"jsonrpc": "2.0", "result": signatures, "id": 1}).to_string();
+        let (streamed, _) = crate::incremental::parse_block_reader(envelope.as_bytes(), &ParseOptions::default()).unwrap();
+        assert_eq!(streamed.signatures, ["s1", "s2"]);
+        assert!(parse_block(serde_json::from_value(header.clone()).unwrap()).unwrap().signatures.is_empty());
+
+        // accounts: flagged keys, lookup table ones last, and no
+        // instructions, logs or loadedAddresses.
+        let [payer, program, loaded] = [1, 2, 3].map(|byte| Pubkey::new([byte; 32]));
+        let key = |pubkey: Pubkey, signer: bool, writable: bool, source: &str| {
+            serde_json::json!({"pubkey": pubkey.to_string(), "signer": signer, "writable": writable, "source": source})
+        };
+        let mut accounts = header;
+        accounts["transactions"] = serde_json::json!([{
+            "meta": {"err": null, "fee": 5000, "preBalances": [10, 1, 0], "postBalances": [5, 1, 5], "status": {"Ok": null}},
+            "transaction": {
+                "signatures": ["s1"],
+                "accountKeys": [key(payer, true, true, "transaction"), key(program, false, false, "transaction"), key(loaded, false, true, "lookupTable")]
+            }
+        }]);
+        let block = parse_block(serde_json::from_value(accounts).unwrap()).unwrap();
+        let tx = &block.transactions[0];
+        assert_eq!(tx.account_keys, [payer, program, loaded]);
+        assert!(tx.instructions.is_empty() && tx.log_messages.is_empty());
+        assert_eq!(tx.accounts[2].source, AccountSource::Lookup);
+        assert!(tx.accounts[2].is_writable && !tx.accounts[1].is_writable && tx.accounts[0].is_signer);
+        assert!(tx.account_warnings.is_empty());
+    }
+
+    #[test]
+    fn block_without_a_successor_slot_is_an_error() {
+        let mut raw: serde_json::Value = serde_json::from_str(&std::fs::read_to_string("src/json/empty_block.json").unwrap()).unwrap();
+        raw["result"]["parentSlot"] = u64::MAX.into();
+        let block = serde_json::from_value::<RpcBlockResponse>(raw.clone()).unwrap().result;
+        assert!(matches!(parse_block(block), Err(ParseError::MalformedBlock { .. })));
+        let streamed = crate::incremental::parse_block_reader(raw.to_string().as_bytes(), &ParseOptions::default());
+        assert!(matches!(streamed, Err(ParseError::MalformedBlock { .. })));
+    }
+
+    #[test]
+    fn loaded_addresses_repeating_static_keys_are_dropped_when_the_balances_agree() {
+        let raw: RpcResponse = load_from_json("src/json/transaction.json").unwrap();
+        let expected = parse_transaction(&raw.result.transaction, &raw.result.meta).unwrap();
+        assert!(expected.account_warnings.is_empty());
+        let fee_payer = raw.result.transaction.message.account_keys[0];
+        let mut meta = raw.result.meta.clone();
+        meta.loaded_addresses.as_mut().unwrap().readonly.push(fee_payer);
+
+        // One balance per unique account: the copy goes.
+        let (parsed, report) = parse_transaction_with_options(&raw.result.transaction, &meta, &ParseOptions::default()).unwrap();
+        assert_eq!(parsed.account_keys, expected.account_keys);
+        assert_eq!(parsed.accounts.len(), expected.accounts.len());
+        let duplicate = AccountWarning::DuplicateAccountKeys { keys: vec![fee_payer], removed: true };
+        assert_eq!(parsed.account_warnings, std::slice::from_ref(&duplicate));
+        assert!(matches!(&report.warnings[..], [ParseWarning::InconsistentAccounts { warning, .. }] if *warning == duplicate));
+
+        // A balance for the copy too: kept, and the counts still agree.
+        meta.pre_balances.push(0);
+        meta.post_balances.push(0);
+        let parsed = parse_transaction(&raw.result.transaction, &meta).unwrap();
+        assert_eq!(parsed.account_keys.len(), expected.account_keys.len() + 1);
+        assert_eq!(parsed.account_warnings, [AccountWarning::DuplicateAccountKeys { keys: vec![fee_payer], removed: false }]);
+
+        let mut meta = raw.result.meta.clone();
+        meta.post_balances.pop();
+        let parsed = parse_transaction(&raw.result.transaction, &meta).unwrap();
+        let accounts = expected.account_keys.len();
+        assert_eq!(
+            parsed.account_warnings,
+            [AccountWarning::BalanceCountMismatch { account_keys: accounts, pre_balances: accounts, post_balances: accounts - 1 }]
+        );
+    }
+
+    #[test]
+    fn strict_mode_rejects_out_of_range_account_index() {
+        let mut raw: RpcResponse = load_from_json("src/json/transfer.json").unwrap();
+        raw.result.transaction.message.instructions[0].accounts.push(250);
+
+        let lenient = parse_transaction_with_options(&raw.result.transaction, &raw.result.meta, &ParseOptions::default());
+        assert!(lenient.is_ok());
+
+        let strict = ParseOptions { strict: true, ..ParseOptions::default() };
+        let err = parse_transaction_with_options(&raw.result.transaction, &raw.result.meta, &strict).unwrap_err();
+        assert!(matches!(err, ParseError::AccountIndexOutOfRange { index: 250, .. }));
+    }
+
+    #[test]
+    fn broken_invariants_are_recorded_on_the_transaction() {
+        let mut raw: RpcResponse = load_from_json("src/json/transfer.json").unwrap();
+        let expected = parse_transaction(&raw.result.transaction, &raw.result.meta).unwrap();
+        assert!(expected.account_warnings.is_empty());
+        let accounts = expected.account_keys.len();
+        let tx = &mut raw.result.transaction;
+        tx.signatures.push(tx.signatures[0].clone());
+        tx.message.header.num_readonly_signed_accounts = 2;
+        tx.message.instructions[0].accounts.extend([250, 70, 250]);
+
+        let (parsed, report) = parse_transaction_with_options(tx, &raw.result.meta, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            parsed.account_warnings,
+            [
+                AccountWarning::SignatureCountMismatch { signatures: 2, required: 1 },
+                AccountWarning::InvalidHeader {
+                    static_keys: tx.message.account_keys.len(),
+                    required_signatures: 1,
+                    readonly_signed: 2,
+                    readonly_unsigned: tx.message.header.num_readonly_unsigned_accounts,
+                },
+                AccountWarning::AccountIndexOutOfRange { indices: vec![70, 250], account_keys: accounts },
+            ]
+        );
+        let out_of_range = report.warnings.iter().filter(|warning| matches!(warning, ParseWarning::AccountIndexOutOfRange { .. })).count();
+        assert_eq!(out_of_range, 3);
+        let unresolved = AccountRef::Unresolved { index: 250 };
+        assert!(parsed.instructions[0].accounts.ends_with(&[unresolved, AccountRef::Unresolved { index: 70 }, unresolved]));
+        let fee_payer = AccountRef::Resolved(parsed.account_keys[0]);
+        assert_eq!(serde_json::to_value([fee_payer, unresolved]).unwrap(), serde_json::json!([parsed.account_keys[0].to_string(), {"index": 250}]));
+    }
+
+    #[test]
+    fn fee_breakdown_accounts_for_the_whole_fee() {
+        let block = fixtures::block();
+        for tx in &block.transactions {
+            assert_eq!(tx.fee_breakdown.total(), tx.fee, "{}: {:?}", tx.signature, tx.fee_breakdown);
+        }
+
+        // A limit and price set explicitly are charged in full, rounded up.
+        let tx = block.transactions.iter().find(|tx| tx.compute_unit_limit.is_some() && tx.fee_breakdown.priority_fee > 0).unwrap();
+        let breakdown = &tx.fee_breakdown;
+        assert_eq!(breakdown.compute_unit_limit, tx.compute_unit_limit.unwrap());
+        let charged = breakdown.compute_unit_price_micro_lamports as u128 * breakdown.compute_unit_limit as u128;
+        assert_eq!(breakdown.priority_fee as u128, charged.div_ceil(1_000_000));
+    }
+
+    #[test]
+    fn account_roles_follow_the_header_and_lookups() {
+        let block = fixtures::block();
+        for tx in &block.transactions {
+            assert_eq!(tx.accounts.len(), tx.account_keys.len());
+            assert!(tx.accounts[0].is_signer && tx.accounts[0].is_writable, "{}", tx.signature);
+            // Only writable accounts can change balance.
+            for (account, (pre, post)) in tx.accounts.iter().zip(tx.pre_balances.iter().zip(&tx.post_balances)) {
+                assert!(pre == post || account.is_writable, "{}: {}", tx.signature, account.pubkey);
+            }
+        }
+        assert!(block.transactions.iter().flat_map(|tx| &tx.accounts).any(|account| account.source == AccountSource::Lookup));
+    }
+
+    #[test]
+    fn account_index_covers_every_listed_account() {
+        let block = fixtures::block();
+        let index = block.index_by_account();
+
+        for (i, tx) in block.transactions.iter().enumerate() {
+            for account in &tx.accounts {
+                let involvement = index[&account.pubkey].iter().find(|inv| inv.transaction_index == i && inv.via.is_none()).unwrap();
+                assert_eq!(involvement.signature, tx.signature);
+                assert_eq!((involvement.is_signer, involvement.is_writable), (account.is_signer, account.is_writable));
+            }
+            for (position, ix) in tx.instructions.iter().enumerate() {
+                assert!(index[&ix.program_id].iter().any(|inv| inv.transaction_index == i && inv.instructions.contains(&position)));
+            }
+        }
+        // Token owners that sign nothing are reachable through their token accounts.
+        let owned = index.values().flatten().find(|inv| inv.via.is_some()).unwrap();
+        assert!(index[&owned.via.unwrap()].iter().any(|inv| inv.transaction_index == owned.transaction_index));
+    }
+
+    #[test]
+    fn parsed_output_round_trips() {
+        for path in ["src/json/block.json", "src/json/empty_block.json"] {
+            let raw: RpcBlockResponse = load_from_json(path).unwrap();
+            let block = parse_block(raw.result).unwrap();
+            let json = serde_json::to_value(&block).unwrap();
+            assert_eq!(json["schema_version"], SCHEMA_VERSION);
+            let mut read_back: ParsedBlock = serde_json::from_value(json.clone()).unwrap();
+            read_back.upgrade().unwrap();
+            assert_eq!(serde_json::to_value(&read_back).unwrap(), json, "{}", path);
+        }
+        for path in ["src/json/swap.json", "src/json/transfer.json", "src/json/transaction.json"] {
+            let raw: RpcResponse = load_from_json(path).unwrap();
+            let json = serde_json::to_value(parse_transaction(&raw.result.transaction, &raw.result.meta).unwrap()).unwrap();
+            let read_back: ParsedTransaction = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&read_back).unwrap(), json, "{}", path);
+        }
+    }
+
+    #[test]
+    fn unversioned_output_is_upgraded_and_newer_output_rejected() {
+        let raw = fixtures::raw_block();
+        let mut json = serde_json::to_value(parse_block(raw.result).unwrap()).unwrap();
+        json.as_object_mut().unwrap().remove("schema_version");
+        json.as_object_mut().unwrap().remove("block_time_rfc3339");
+        json["transactions"][0].as_object_mut().unwrap().remove("schema_version");
+
+        let mut block: ParsedBlock = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!((block.schema_version, block.transactions[0].schema_version), (0, 0));
+        block.upgrade().unwrap();
+        assert_eq!((block.schema_version, block.transactions[0].schema_version), (SCHEMA_VERSION, SCHEMA_VERSION));
+        assert!(block.block_time_rfc3339.is_some());
+
+        json["schema_version"] = (SCHEMA_VERSION + 1).into();
+        let mut newer: ParsedBlock = serde_json::from_value(json).unwrap();
+        assert!(matches!(newer.upgrade(), Err(ParseError::UnsupportedSchemaVersion { .. })));
+    }
+
+    #[test]
+    fn memos_are_decoded_in_execution_order() {
+        let mut raw: RpcResponse = load_from_json("src/json/transfer.json").unwrap();
+        let message = &mut raw.result.transaction.message;
+        message.account_keys.push(MEMO_PROGRAM_ID);
+        let memo_program = message.account_keys.len() - 1;
+        let memo = |text: &str| RpcInstruction::new(memo_program, Vec::new(), bs58::encode(text).into_string());
+        message.instructions.insert(0, memo("deposit 4521"));
+        message.instructions.push(memo("ünïcode ✓"));
+        raw.result.meta.inner_instructions = Some(vec![RpcInnerInstructions { index: 0, instructions: vec![memo("via cpi")] }]);
+
+        let tx = parse_transaction(&raw.result.transaction, &raw.result.meta).unwrap();
+        assert_eq!(tx.memos, ["deposit 4521", "via cpi", "ünïcode ✓"]);
+    }
+
+    // Rewrites an encoding: json getTransaction response the way jsonParsed
+    // returns it: keys with flags (lookup table keys included) and
+    // instructions naming their program and accounts by key.
+    fn as_json_parsed(path: &str) -> serde_json::Value {
+        let mut raw: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let result = &mut raw["result"];
+        let message = &result["transaction"]["message"];
+        let header = &message["header"];
+        let count = |field: &str| header[field].as_u64().unwrap() as usize;
+        let (signers, readonly_signed, readonly_unsigned) =
+            (count("numRequiredSignatures"), count("numReadonlySignedAccounts"), count("numReadonlyUnsignedAccounts"));
+        let static_keys = message["accountKeys"].as_array().unwrap().clone();
+        let loaded = &result["meta"]["loadedAddresses"];
+        let mut keys: Vec<serde_json::Value> = static_keys.iter().enumerate().map(|(i, key)| {
+            let writable = if i < signers { i < signers - readonly_signed } else { i < static_keys.len() - readonly_unsigned };
+            serde_json::json!({ "pubkey": key, "signer": i < signers, "writable": writable, "source": "transaction" })
+        }).collect();
+        for (field, writable) in [("writable", true), ("readonly", false)] {
+            for key in loaded[field].as_array().into_iter().flatten() {
+                keys.push(serde_json::json!({ "pubkey": key, "signer": false, "writable": writable, "source": "lookupTable" }));
+            }
+        }
+        let key_at = |index: &serde_json::Value| keys[index.as_u64().unwrap() as usize]["pubkey"].clone();
+        let keyed = |ix: &serde_json::Value| serde_json::json!({
+            "programId": key_at(&ix["programIdIndex"]),
+            "accounts": ix["accounts"].as_array().unwrap().iter().map(key_at).collect::<Vec<_>>(),
+            "data": ix["data"],
+            "stackHeight": ix["stackHeight"],
+        });
+        let instructions: Vec<_> = message["instructions"].as_array().unwrap().iter().map(keyed).collect();
+        let inner: Vec<_> = result["meta"]["innerInstructions"].as_array().into_iter().flatten().map(|inner| serde_json::json!({
+            "index": inner["index"],
+            "instructions": inner["instructions"].as_array().unwrap().iter().map(keyed).collect::<Vec<_>>(),
+        })).collect();
+
+        let message = &mut result["transaction"]["message"];
+        message.as_object_mut().unwrap().remove("header");
+        message["accountKeys"] = keys.into();
+        message["instructions"] = instructions.into();
+        result["meta"]["innerInstructions"] = inner.into();
+        raw
+    }
+
+    #[test]
+    fn json_parsed_input_matches_json() {
+        for path in ["src/json/swap.json", "src/json/transfer.json"] {
+            let json: RpcResponse = load_from_json(path).unwrap();
+            let expected = parse_transaction(&json.result.transaction, &json.result.meta).unwrap();
+            let parsed: RpcResponse = serde_json::from_value(as_json_parsed(path)).unwrap();
+            let tx = parse_transaction(&parsed.result.transaction, &parsed.result.meta).unwrap();
+            assert_eq!(serde_json::to_value(&tx).unwrap(), serde_json::to_value(&expected).unwrap(), "{}", path);
+        }
+
+        // Instructions the node parsed keep its decoding, without accounts or data.
+        let mut raw = as_json_parsed("src/json/swap.json");
+        let ix = &mut raw["result"]["transaction"]["message"]["instructions"][0];
+        let program_id = ix["programId"].clone();
+        *ix = serde_json::json!({ "programId": program_id, "program": "spl-memo", "parsed": "gm", "stackHeight": null });
+        let raw: RpcResponse = serde_json::from_value(raw).unwrap();
+        let tx = parse_transaction(&raw.result.transaction, &raw.result.meta).unwrap();
+        assert_eq!(tx.instructions[0].program_id, program_id.as_str().unwrap());
+        assert_eq!(tx.instructions[0].parsed, Some(JsonParsedInstruction { program: "spl-memo".to_string(), parsed: "gm".into() }));
+        assert!(tx.instructions[0].accounts.is_empty());
+    }
+
+    #[test]
+    fn inner_instructions_link_to_their_caller_by_stack_height() {
+        let block = fixtures::block();
+        let mut nested = 0;
+        for tx in &block.transactions {
+            assert!(tx.instructions.iter().all(|ix| ix.stack_height == Some(1) && ix.invoked_by.is_none()));
+            for inner in &tx.inner_instructions {
+                for (position, ix) in inner.instructions.iter().enumerate() {
+                    match ix.invoked_by {
+                        Some(caller) => {
+                            nested += 1;
+                            assert!(caller < position);
+                            assert_eq!(inner.instructions[caller].stack_height.unwrap() + 1, ix.stack_height.unwrap());
+                        }
+                        None => assert_eq!(ix.stack_height, Some(2)),
+                    }
+                }
+                let direct = inner.invoked_by(None).count();
+                let indirect: usize = (0..inner.instructions.len()).map(|caller| inner.invoked_by(Some(caller)).count()).sum();
+                assert_eq!(direct + indirect, inner.instructions.len());
+            }
+        }
+        assert_eq!(nested, 102);
+
+        let mut instructions: Vec<ParsedInstruction> = [2, 3, 4, 3, 2, 3]
+            .into_iter()
+            .map(|height| ParsedInstruction { stack_height: Some(height), ..block.transactions[0].instructions[0].clone() })
+            .collect();
+        link_invocations(&mut instructions);
+        let callers: Vec<Option<usize>> = instructions.iter().map(|ix| ix.invoked_by).collect();
+        assert_eq!(callers, [None, Some(0), Some(1), Some(0), None, Some(4)]);
+    }
+}
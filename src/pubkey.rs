@@ -0,0 +1,172 @@
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+// ==========================================
+// PUBKEY
+// ==========================================
+// A 32-byte account address. Parsed from base58 when responses are
+// deserialized, so malformed keys are rejected there instead of travelling
+// through as strings, and written back as the same base58 string, so
+// serialized output is unchanged. Being `Copy`, it costs no allocation to
+// pass around. Program IDs are declared as constants via `from_str_const`.
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Pubkey([u8; 32]);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPubkey(String);
+
+impl fmt::Display for InvalidPubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid pubkey '{}'", self.0)
+    }
+}
+
+impl std::error::Error for InvalidPubkey {}
+
+impl Pubkey {
+    pub const fn new(bytes: [u8; 32]) -> Self {
+        Pubkey(bytes)
+    }
+
+    pub const fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    // Compile-time base58 decoding for constants; panics (failing the
+    // build) on anything that isn't a 32-byte key.
+    pub const fn from_str_const(s: &str) -> Self {
+        let input = s.as_bytes();
+        let mut bytes = [0u8; 32];
+        let mut i = 0;
+        while i < input.len() {
+            let mut digit = 0;
+            while digit < 58 && ALPHABET[digit] != input[i] {
+                digit += 1;
+            }
+            assert!(digit < 58, "invalid base58 character in pubkey");
+            // bytes = bytes * 58 + digit, big-endian.
+            let mut carry = digit as u32;
+            let mut j = 32;
+            while j > 0 {
+                j -= 1;
+                carry += bytes[j] as u32 * 58;
+                bytes[j] = carry as u8;
+                carry >>= 8;
+            }
+            assert!(carry == 0, "pubkey longer than 32 bytes");
+            i += 1;
+        }
+        Pubkey(bytes)
+    }
+}
+
+impl FromStr for Pubkey {
+    type Err = InvalidPubkey;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0u8; 32];
+        // A 32-byte key is at most 44 characters; the check also keeps
+        // oversized input from reaching the (quadratic) decoder.
+        match s.len() <= 44 && bs58::decode(s).onto(&mut bytes) == Ok(32) {
+            true => Ok(Pubkey(bytes)),
+            false => Err(InvalidPubkey(s.to_string())),
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for Pubkey {
+    type Error = InvalidPubkey;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        bytes.try_into().map(Pubkey).map_err(|_| InvalidPubkey(format!("{} bytes", bytes.len())))
+    }
+}
+
+impl fmt::Display for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&bs58::encode(self.0).into_string())
+    }
+}
+
+// Quoted, as the String addresses were, so Debug-printed decoded
+// instructions read the same.
+impl fmt::Debug for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self)
+    }
+}
+
+// Lets code compare against addresses held as strings (decoded
+// instruction fields, CLI arguments) without parsing them first.
+impl PartialEq<str> for Pubkey {
+    fn eq(&self, other: &str) -> bool {
+        other.parse::<Pubkey>().is_ok_and(|key| key == *self)
+    }
+}
+
+impl PartialEq<&str> for Pubkey {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+impl PartialEq<String> for Pubkey {
+    fn eq(&self, other: &String) -> bool {
+        *self == *other.as_str()
+    }
+}
+
+impl Serialize for Pubkey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pubkey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PubkeyVisitor;
+
+        impl Visitor<'_> for PubkeyVisitor {
+            type Value = Pubkey;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a base58-encoded 32-byte pubkey")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Pubkey, E> {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(PubkeyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_base58_and_rejects_bad_keys() {
+        let text = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+        let key: Pubkey = text.parse().unwrap();
+        assert_eq!(key.to_string(), text);
+        assert_eq!(key, Pubkey::from_str_const(text));
+        assert_eq!(Pubkey::from_str_const("11111111111111111111111111111111"), Pubkey::default());
+        assert_eq!(serde_json::to_string(&key).unwrap(), format!("\"{}\"", text));
+        assert_eq!(serde_json::from_str::<Pubkey>(&format!("\"{}\"", text)).unwrap(), key);
+
+        assert!("not-a-key".parse::<Pubkey>().is_err());
+        assert!("3yZe7d".parse::<Pubkey>().is_err());
+        assert!(serde_json::from_str::<Pubkey>("\"UNKNOWN_IDX_3\"").is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use crate::parser::ParseOptions;
+use crate::rpc::{Commitment, RpcError};
+use crate::source::{BlockSource, RawBlock};
+use crate::stream::StreamedBlock;
+use crate::types::RpcBlockResult;
+use crate::websocket::WebSocket;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::error::Error;
+
+// ==========================================
+// WEBSOCKET SUBSCRIPTIONS
+// ==========================================
+// blockSubscribe and logsSubscribe over the node's pubsub endpoint. Each
+// subscription owns its connection and is consumed as an iterator of
+// notifications; the iterator ends when the server closes the socket. A
+// block subscription is also a `BlockSource` of the raw blocks.
+
+// The pubsub endpoint for an RPC url: same host with a ws scheme, and for
+// explicit ports the next port up (8899 -> 8900), as solana-validator does.
+// An https endpoint's would be wss://, which WebSocket can't connect to.
+pub fn ws_url_for(rpc_url: &str) -> Result<String, Box<dyn Error>> {
+    let rest = match rpc_url.split_once("://") {
+        Some(("https", _)) => {
+            return Err(format!("can't derive a ws:// pubsub url from {} (wss:// is not supported); pass one with --ws-url", rpc_url).into());
+        }
+        Some((_, rest)) => rest,
+        None => rpc_url,
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(pos) => (&rest[..pos], &rest[pos..]),
+        None => (rest, ""),
+    };
+    let authority = match authority.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>() {
+            Ok(port) => format!("{}:{}", host, port.saturating_add(1)),
+            Err(_) => authority.to_string(),
+        },
+        None => authority.to_string(),
+    };
+    Ok(format!("ws://{}{}", authority, path))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogNotification {
+    pub slot: u64,
+    pub signature: String,
+    pub err: Option<Value>,
+    pub logs: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Notification<T> {
+    params: NotificationParams<T>,
+}
+
+#[derive(Deserialize)]
+struct NotificationParams<T> {
+    result: NotificationResult<T>,
+}
+
+#[derive(Deserialize)]
+struct NotificationResult<T> {
+    context: NotificationContext,
+    value: T,
+}
+
+#[derive(Deserialize)]
+struct NotificationContext {
+    slot: u64,
+}
+
+#[derive(Deserialize)]
+struct BlockUpdate {
+    slot: u64,
+    block: Option<RpcBlockResult>,
+    err: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct LogsUpdate {
+    signature: String,
+    err: Option<Value>,
+    logs: Vec<String>,
+}
+
+pub struct PubsubClient {
+    socket: WebSocket,
+    commitment: Commitment,
+    subscription: Option<u64>,
+}
+
+impl PubsubClient {
+    pub fn connect(ws_url: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(PubsubClient {
+            socket: WebSocket::connect(ws_url)?,
+            commitment: Commitment::Finalized,
+            subscription: None,
+        })
+    }
+
+    pub fn with_commitment(mut self, commitment: Commitment) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    // Every block, or only blocks with transactions mentioning `mentions`.
+    pub fn block_subscribe(mut self, mentions: Option<&str>, options: ParseOptions) -> Result<BlockSubscription, Box<dyn Error>> {
+        let filter = match mentions {
+            Some(account) => json!({ "mentionsAccountOrProgram": account }),
+            None => json!("all"),
+        };
+        let config = json!({
+            "commitment": self.commitment.as_str(),
+            "encoding": "json",
+            "transactionDetails": "full",
+            "showRewards": true,
+            "maxSupportedTransactionVersion": 0,
+        });
+        self.subscribe("blockSubscribe", json!([filter, config]))?;
+        Ok(BlockSubscription { client: self, options, done: false })
+    }
+
+    // Logs of every transaction mentioning `mentions` (or all non-vote ones).
+    pub fn logs_subscribe(mut self, mentions: Option<&str>) -> Result<LogsSubscription, Box<dyn Error>> {
+        let filter = match mentions {
+            Some(account) => json!({ "mentions": [account] }),
+            None => json!("all"),
+        };
+        self.subscribe("logsSubscribe", json!([filter, { "commitment": self.commitment.as_str() }]))?;
+        Ok(LogsSubscription { client: self, done: false })
+    }
+
+    fn subscribe(&mut self, method: &str, params: Value) -> Result<(), Box<dyn Error>> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        self.socket.send_text(&request.to_string())?;
+
+        // Notifications can't arrive before the confirmation, but skip
+        // anything without our id to be safe.
+        loop {
+            let message = self.socket.read_message()?.ok_or("websocket closed before subscription was confirmed")?;
+            let response: Value = serde_json::from_slice(&message)?;
+            if response.get("id") != Some(&json!(1)) {
+                continue;
+            }
+            if let Some(err) = response.get("error") {
+                return Err(Box::new(RpcError {
+                    method: method.to_string(),
+                    code: err["code"].as_i64().unwrap_or_default(),
+                    message: err["message"].as_str().unwrap_or_default().to_string(),
+                }));
+            }
+            let id = response["result"].as_u64().ok_or_else(|| format!("{} returned no subscription id", method))?;
+            self.subscription = Some(id);
+            return Ok(());
+        }
+    }
+
+    fn next_notification<T: DeserializeOwned>(&mut self) -> Result<Option<(u64, T)>, Box<dyn Error>> {
+        loop {
+            let Some(message) = self.socket.read_message()? else {
+                return Ok(None);
+            };
+            let raw: Value = serde_json::from_slice(&message)?;
+            if raw["params"]["subscription"].as_u64() != self.subscription {
+                continue;
+            }
+            let notification: Notification<T> = serde_json::from_value(raw)?;
+            let result = notification.params.result;
+            return Ok(Some((result.context.slot, result.value)));
+        }
+    }
+}
+
+pub struct BlockSubscription {
+    client: PubsubClient,
+    options: ParseOptions,
+    done: bool,
+}
+
+impl BlockSource for BlockSubscription {
+    fn next_block(&mut self) -> Option<Result<RawBlock, Box<dyn Error>>> {
+        while !self.done {
+            match self.client.next_notification::<BlockUpdate>() {
+                Ok(Some((_, update))) => match (update.block, update.err) {
+                    (Some(block), _) => return Some(Ok(RawBlock { slot: Some(update.slot), block })),
+                    (None, Some(err)) => return Some(Err(format!("block {} notification error: {}", update.slot, err).into())),
+                    (None, None) => continue,
+                },
+                Ok(None) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Iterator for BlockSubscription {
+    type Item = Result<StreamedBlock, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (slot, block) = match self.next_block()? {
+            Ok(RawBlock { slot, block }) => (slot.expect("notifications carry their slot"), block),
+            Err(e) => return Some(Err(e)),
+        };
+        Some(StreamedBlock::parse(slot, block, &self.options).map_err(Into::into))
+    }
+}
+
+pub struct LogsSubscription {
+    client: PubsubClient,
+    done: bool,
+}
+
+impl Iterator for LogsSubscription {
+    type Item = Result<LogNotification, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.client.next_notification::<LogsUpdate>() {
+            Ok(Some((slot, update))) => Some(Ok(LogNotification {
+                slot,
+                signature: update.signature,
+                err: update.err,
+                logs: update.logs,
+            })),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::types::ParsedBlock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// ==========================================
+// CONFIRMATION DEPTH
+// ==========================================
+// Blocks read below finalized commitment can still be orphaned by a fork
+// switch. ConfirmationTracker holds every block written that way until its
+// fate is known and turns it into a ChainEvent for the sinks:
+//
+// - retracted straight away when a later block's parent skips over it, or
+//   names a different hash for its slot;
+// - otherwise, once the newest block is `depth` slots past it, checked
+//   against the finalized chain: finalized if the hashes match, retracted
+//   if the finalized chain has another block or none for the slot. When it
+//   has another block, the settlement names it so it can be written in
+//   place of the retracted one.
+//
+// Blocks must be recorded in slot order.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ChainEvent {
+    // A block already written is now on the finalized chain.
+    Finalized { slot: u64, blockhash: String },
+    // A block already written was orphaned; undo it downstream.
+    Retracted { slot: u64, blockhash: String },
+}
+
+impl ChainEvent {
+    pub fn slot(&self) -> u64 {
+        match self {
+            ChainEvent::Finalized { slot, .. } | ChainEvent::Retracted { slot, .. } => *slot,
+        }
+    }
+
+    pub fn blockhash(&self) -> &str {
+        match self {
+            ChainEvent::Finalized { blockhash, .. } | ChainEvent::Retracted { blockhash, .. } => blockhash,
+        }
+    }
+}
+
+// A pending block checked against the finalized chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settlement {
+    pub event: ChainEvent,
+    // The finalized chain's blockhash for the slot when a different block
+    // is finalized there.
+    pub replacement: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfirmationTracker {
+    depth: u64,
+    // Unsettled blocks' hashes by slot.
+    pending: BTreeMap<u64, String>,
+    newest_slot: u64,
+}
+
+impl ConfirmationTracker {
+    pub fn new(depth: u64) -> Self {
+        ConfirmationTracker { depth, pending: BTreeMap::new(), newest_slot: 0 }
+    }
+
+    // Blocks still waiting to be settled.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    // Tracks `block`, returning the retractions its parent link implies.
+    pub fn record(&mut self, block: &ParsedBlock) -> Vec<ChainEvent> {
+        let mut events = Vec::new();
+        let skipped: Vec<u64> =
+            self.pending.range(..=block.slot).rev().map(|(slot, _)| *slot).take_while(|slot| *slot > block.parent_slot).collect();
+        for slot in skipped.into_iter().rev() {
+            let blockhash = self.pending.remove(&slot).unwrap();
+            events.push(ChainEvent::Retracted { slot, blockhash });
+        }
+        if let Some(parent) = self.pending.get(&block.parent_slot)
+            && *parent != block.previous_blockhash
+        {
+            let blockhash = self.pending.remove(&block.parent_slot).unwrap();
+            events.push(ChainEvent::Retracted { slot: block.parent_slot, blockhash });
+        }
+        self.pending.insert(block.slot, block.blockhash.clone());
+        self.newest_slot = self.newest_slot.max(block.slot);
+        events
+    }
+
+    // Whether the oldest pending block is `depth` slots deep, so checking
+    // the finalized chain is worthwhile.
+    pub fn is_due(&self) -> bool {
+        self.pending.keys().next().is_some_and(|slot| slot + self.depth <= self.newest_slot)
+    }
+
+    // Pending blocks at or before `finalized_slot` that are `depth` deep,
+    // oldest first, ready to be looked up on the finalized chain.
+    pub fn due_slots(&self, finalized_slot: u64) -> Vec<u64> {
+        let last = finalized_slot.min(self.newest_slot.saturating_sub(self.depth));
+        self.pending.range(..=last).map(|(slot, _)| *slot).collect()
+    }
+
+    // Settles a pending block given the finalized chain's hash for its slot
+    // (None when the finalized chain has no block there).
+    pub fn resolve(&mut self, slot: u64, finalized_blockhash: Option<&str>) -> Option<Settlement> {
+        let blockhash = self.pending.remove(&slot)?;
+        Some(match finalized_blockhash {
+            Some(finalized) if finalized == blockhash => Settlement { event: ChainEvent::Finalized { slot, blockhash }, replacement: None },
+            Some(finalized) => Settlement { event: ChainEvent::Retracted { slot, blockhash }, replacement: Some(finalized.to_string()) },
+            None => Settlement { event: ChainEvent::Retracted { slot, blockhash }, replacement: None },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(slot: u64, parent_slot: u64, blockhash: &str, previous_blockhash: &str) -> ParsedBlock {
+        let mut block: ParsedBlock = serde_json::from_str(include_str!("json/golden/empty_block.json")).unwrap();
+        block.slot = slot;
+        block.parent_slot = parent_slot;
+        block.blockhash = blockhash.to_string();
+        block.previous_blockhash = previous_blockhash.to_string();
+        block
+    }
+
+    #[test]
+    fn orphaned_blocks_are_retracted_and_deep_ones_finalized() {
+        let mut tracker = ConfirmationTracker::new(2);
+        assert!(tracker.record(&block(10, 9, "a", "z")).is_empty());
+        assert!(tracker.record(&block(11, 10, "b", "a")).is_empty());
+        assert!(tracker.record(&block(12, 11, "c", "b")).is_empty());
+        // 14 builds on 11, so 12 was on a fork.
+        assert_eq!(tracker.record(&block(14, 11, "d", "b")), [ChainEvent::Retracted { slot: 12, blockhash: "c".into() }]);
+        // 15 names another block for 14.
+        assert_eq!(tracker.record(&block(15, 14, "e", "x")), [ChainEvent::Retracted { slot: 14, blockhash: "d".into() }]);
+        assert!(tracker.is_due());
+
+        // 15 isn't deep enough yet, and only 10 is finalized.
+        assert_eq!(tracker.due_slots(20), [10, 11]);
+        assert_eq!(tracker.due_slots(10), [10]);
+        // The finalized chain has "a" at 10 and a different block at 11.
+        let settled = tracker.resolve(10, Some("a")).unwrap();
+        assert_eq!(settled.event, ChainEvent::Finalized { slot: 10, blockhash: "a".into() });
+        assert_eq!(settled.replacement, None);
+        assert_eq!(tracker.resolve(11, Some("other")).unwrap().event, ChainEvent::Retracted { slot: 11, blockhash: "b".into() });
+        assert_eq!(tracker.resolve(11, None), None);
+        assert_eq!(tracker.pending(), 1);
+        assert!(!tracker.is_due());
+    }
+
+    #[test]
+    fn a_different_finalized_block_replaces_the_written_one() {
+        let mut tracker = ConfirmationTracker::new(1);
+        tracker.record(&block(10, 9, "a", "z"));
+        tracker.record(&block(11, 10, "b", "a"));
+        tracker.record(&block(12, 11, "c", "b"));
+        assert_eq!(tracker.due_slots(12), [10, 11]);
+
+        let settled = tracker.resolve(10, Some("other")).unwrap();
+        assert_eq!(settled.event, ChainEvent::Retracted { slot: 10, blockhash: "a".into() });
+        assert_eq!(settled.replacement.as_deref(), Some("other"));
+        // No block at all: nothing to write instead.
+        assert_eq!(tracker.resolve(11, None).unwrap().replacement, None);
+        assert_eq!(tracker.pending(), 1);
+    }
+}
@@ -0,0 +1,983 @@
+use crate::encoding::DataEncoding;
+use crate::filter::TxFilter;
+use crate::http;
+use crate::metrics::metrics;
+use crate::pubkey::Pubkey;
+use crate::span;
+use crate::types::{AccountSnapshot, ParsedBlock, RpcBlockResult, RpcResult};
+use log::Level;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_RPC_URL: &str = "https://api.mainnet-beta.solana.com";
+
+// JSON-RPC server error codes the node uses for block lookups.
+pub const BLOCK_NOT_AVAILABLE: i64 = -32004;
+pub const SLOT_SKIPPED: i64 = -32007;
+pub const LONG_TERM_STORAGE_SLOT_SKIPPED: i64 = -32009;
+
+// ==========================================
+// JSON-RPC CLIENT
+// ==========================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl Commitment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Commitment::Processed => "processed",
+            Commitment::Confirmed => "confirmed",
+            Commitment::Finalized => "finalized",
+        }
+    }
+
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "processed" => Ok(Commitment::Processed),
+            "confirmed" => Ok(Commitment::Confirmed),
+            "finalized" => Ok(Commitment::Finalized),
+            other => Err(format!("unknown commitment '{}'", other)),
+        }
+    }
+}
+
+// How getBlock / getTransaction return each transaction. Base64 carries
+// the signed wire bytes, jsonParsed the node's own instruction decoding;
+// all deserialize into RpcTransactionContainer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionEncoding {
+    Json,
+    JsonParsed,
+    Base64,
+}
+
+impl TransactionEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionEncoding::Json => "json",
+            TransactionEncoding::JsonParsed => "jsonParsed",
+            TransactionEncoding::Base64 => "base64",
+        }
+    }
+
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "json" => Ok(TransactionEncoding::Json),
+            "jsonParsed" | "json-parsed" => Ok(TransactionEncoding::JsonParsed),
+            "base64" => Ok(TransactionEncoding::Base64),
+            other => Err(format!("unknown transaction encoding '{}' (expected json, jsonParsed or base64)", other)),
+        }
+    }
+}
+
+// How much of each transaction getBlock returns. Accounts gives the keys
+// and balances without instructions or logs; signatures and none leave the
+// transactions out (ParsedBlock.signatures holds the former).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionDetails {
+    Full,
+    Accounts,
+    Signatures,
+    None,
+}
+
+impl TransactionDetails {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionDetails::Full => "full",
+            TransactionDetails::Accounts => "accounts",
+            TransactionDetails::Signatures => "signatures",
+            TransactionDetails::None => "none",
+        }
+    }
+
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "full" => Ok(TransactionDetails::Full),
+            "accounts" => Ok(TransactionDetails::Accounts),
+            "signatures" => Ok(TransactionDetails::Signatures),
+            "none" => Ok(TransactionDetails::None),
+            other => Err(format!("unknown transaction details '{}' (expected full, accounts, signatures or none)", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RpcClient {
+    pub url: String,
+    pub commitment: Commitment,
+    pub transaction_encoding: TransactionEncoding,
+    // getBlock only.
+    pub transaction_details: TransactionDetails,
+    pub rewards: bool,
+    // Without this the node rejects blocks containing v0 transactions.
+    pub max_supported_transaction_version: Option<u8>,
+    pub retry: RetryPolicy,
+    // Shared between clones so every client built from it draws on the
+    // same budget.
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    // getBlock drops the transactions this rejects on their JSON text,
+    // before deserializing them; see TxFilter::prefilter.
+    pub prefilter: Option<Arc<TxFilter>>,
+    // Requests go to the pool's endpoints instead of `url` when set.
+    pub pool: Option<Arc<EndpointPool>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcEnvelope<T> {
+    result: Option<T>,
+    error: Option<RpcErrorObject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcErrorObject {
+    code: i64,
+    message: String,
+}
+
+// Error object returned by the node, kept typed so callers can downcast and
+// react to specific codes.
+#[derive(Debug, Clone)]
+pub struct RpcError {
+    pub method: String,
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn is_slot_skipped(&self) -> bool {
+        matches!(self.code, SLOT_SKIPPED | LONG_TERM_STORAGE_SLOT_SKIPPED)
+    }
+
+    pub fn is_block_not_available(&self) -> bool {
+        self.code == BLOCK_NOT_AVAILABLE
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed ({}): {}", self.method, self.code, self.message)
+    }
+}
+
+impl Error for RpcError {}
+
+// What getBlock has for a slot. Skipped and unavailable slots come back
+// as JSON-RPC errors; these are the ones callers expect to handle.
+#[derive(Debug)]
+pub enum BlockFetch {
+    Block(RpcBlockResult),
+    // No block was produced for the slot.
+    SlotSkipped,
+    // Produced but not yet confirmed at this commitment, even after
+    // retrying.
+    BlockUnavailable,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcAccountInfoResponse {
+    value: Option<RpcAccount>,
+}
+
+// getBlock with transactionDetails "none" and no rewards.
+#[derive(Debug, Deserialize)]
+struct RpcBlockHeader {
+    blockhash: String,
+}
+
+// One getSignaturesForAddress entry.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignatureInfo {
+    pub signature: String,
+    pub slot: u64,
+    #[serde(rename = "blockTime")]
+    pub block_time: Option<i64>,
+    // Set when the transaction failed.
+    pub err: Option<Value>,
+}
+
+// Only the data is needed; it arrives as [payload, "base64"].
+#[derive(Debug, Deserialize)]
+struct RpcAccount {
+    data: (String, String),
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcMultipleAccountsResponse {
+    context: RpcContext,
+    value: Vec<Option<RpcAccountState>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcContext {
+    slot: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcAccountState {
+    lamports: u64,
+    owner: Pubkey,
+    data: (String, String),
+}
+
+// An account as getMultipleAccounts returned it, data decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountState {
+    pub lamports: u64,
+    pub owner: Pubkey,
+    pub data: Vec<u8>,
+}
+
+pub struct MultipleAccounts {
+    // The slot the accounts were read at.
+    pub context_slot: u64,
+    // In request order, None for accounts that don't exist.
+    pub accounts: Vec<Option<AccountState>>,
+}
+
+// getMultipleAccounts takes at most this many addresses per request.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+impl RpcClient {
+    pub fn new(url: &str) -> Self {
+        RpcClient {
+            url: url.to_string(),
+            commitment: Commitment::Finalized,
+            transaction_encoding: TransactionEncoding::Json,
+            transaction_details: TransactionDetails::Full,
+            rewards: true,
+            max_supported_transaction_version: Some(0),
+            retry: RetryPolicy::default(),
+            rate_limiter: None,
+            prefilter: None,
+            pool: None,
+        }
+    }
+
+    pub fn with_commitment(mut self, commitment: Commitment) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn with_transaction_encoding(mut self, encoding: TransactionEncoding) -> Self {
+        self.transaction_encoding = encoding;
+        self
+    }
+
+    pub fn with_transaction_details(mut self, details: TransactionDetails) -> Self {
+        self.transaction_details = details;
+        self
+    }
+
+    pub fn with_rewards(mut self, rewards: bool) -> Self {
+        self.rewards = rewards;
+        self
+    }
+
+    // None asks for legacy transactions only.
+    pub fn with_max_supported_transaction_version(mut self, version: Option<u8>) -> Self {
+        self.max_supported_transaction_version = version;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    // Shared between clones like the rate limiter, so endpoint health is
+    // tracked once for every worker.
+    pub fn with_pool(mut self, pool: Arc<EndpointPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    // Only used when the filter pre-filters (TxFilter::prefilters); the
+    // full filter still applies when the block is parsed.
+    pub fn with_prefilter(mut self, filter: Arc<TxFilter>) -> Self {
+        self.prefilter = Some(filter);
+        self
+    }
+
+    pub fn get_block(&self, slot: u64) -> Result<RpcBlockResult, Box<dyn Error>> {
+        let mut config = json!({
+            "encoding": self.transaction_encoding.as_str(),
+            "transactionDetails": self.transaction_details.as_str(),
+            "rewards": self.rewards,
+            "commitment": self.commitment.as_str(),
+        });
+        self.apply_max_version(&mut config);
+        let (block, endpoint) = match self.prefilter.as_deref().filter(|filter| filter.prefilters()) {
+            Some(filter) => {
+                let (block, endpoint) = self.call_endpoint("getBlock", json!([slot, config]))?;
+                (filter.prefilter_block(block)?, endpoint)
+            }
+            None => self.call_endpoint("getBlock", json!([slot, config]))?,
+        };
+        Ok(RpcBlockResult { endpoint, ..block })
+    }
+
+    // Like get_block, with skipped and unavailable slots as outcomes rather
+    // than errors.
+    pub fn fetch_block(&self, slot: u64) -> Result<BlockFetch, Box<dyn Error>> {
+        match self.get_block(slot) {
+            Ok(block) => Ok(BlockFetch::Block(block)),
+            Err(e) => match e.downcast_ref::<RpcError>() {
+                Some(rpc_err) if rpc_err.is_slot_skipped() => Ok(BlockFetch::SlotSkipped),
+                Some(rpc_err) if rpc_err.is_block_not_available() => Ok(BlockFetch::BlockUnavailable),
+                _ => Err(e),
+            },
+        }
+    }
+
+    // Just the hash of the slot's block, None when the slot was skipped.
+    // Cheap enough to check many slots against the finalized chain.
+    pub fn get_blockhash(&self, slot: u64) -> Result<Option<String>, Box<dyn Error>> {
+        let config = json!({
+            "transactionDetails": "none",
+            "rewards": false,
+            "commitment": self.commitment.as_str(),
+        });
+        match self.call::<RpcBlockHeader>("getBlock", json!([slot, config])) {
+            Ok(header) => Ok(Some(header.blockhash)),
+            Err(e) if e.downcast_ref::<RpcError>().is_some_and(RpcError::is_slot_skipped) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn get_transaction(&self, signature: &str) -> Result<RpcResult, Box<dyn Error>> {
+        let mut config = json!({
+            "encoding": self.transaction_encoding.as_str(),
+            "commitment": self.commitment.as_str(),
+        });
+        self.apply_max_version(&mut config);
+        self.call("getTransaction", json!([signature, config]))
+    }
+
+    // Like get_transaction, with None when the node doesn't know the
+    // signature (at this commitment) rather than an error.
+    pub fn fetch_transaction(&self, signature: &str) -> Result<Option<RpcResult>, Box<dyn Error>> {
+        match self.get_transaction(signature) {
+            Ok(tx) => Ok(Some(tx)),
+            Err(e) if e.downcast_ref::<NullResult>().is_some() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Signatures of transactions touching `address`, newest first: at most
+    // `limit` (up to 1000) older than `before` and newer than `until`.
+    // Nodes only serve confirmed and finalized commitment here.
+    pub fn get_signatures_for_address(
+        &self,
+        address: &str,
+        before: Option<&str>,
+        until: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<SignatureInfo>, Box<dyn Error>> {
+        let mut config = json!({
+            "limit": limit,
+            "commitment": self.commitment.as_str(),
+        });
+        if let Some(before) = before {
+            config["before"] = json!(before);
+        }
+        if let Some(until) = until {
+            config["until"] = json!(until);
+        }
+        self.call("getSignaturesForAddress", json!([address, config]))
+    }
+
+    pub fn get_slot(&self) -> Result<u64, Box<dyn Error>> {
+        self.call("getSlot", json!([{ "commitment": self.commitment.as_str() }]))
+    }
+
+    // Raw account data, or None if the account does not exist.
+    pub fn get_account_data(&self, address: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        let config = json!({
+            "encoding": "base64",
+            "commitment": self.commitment.as_str(),
+        });
+        let response: RpcAccountInfoResponse = self.call("getAccountInfo", json!([address, config]))?;
+        match response.value {
+            Some(account) => Ok(Some(BASE64.decode(&account.data.0)?)),
+            None => Ok(None),
+        }
+    }
+
+    // The accounts, read at `min_context_slot` or later. More than
+    // MAX_MULTIPLE_ACCOUNTS take several requests, which may be answered at
+    // different slots; the earliest is returned.
+    pub fn get_multiple_accounts(&self, addresses: &[Pubkey], min_context_slot: u64) -> Result<MultipleAccounts, Box<dyn Error>> {
+        let config = json!({
+            "encoding": "base64",
+            "commitment": self.commitment.as_str(),
+            "minContextSlot": min_context_slot,
+        });
+        let mut context_slot: Option<u64> = None;
+        let mut accounts = Vec::with_capacity(addresses.len());
+        for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let keys: Vec<String> = chunk.iter().map(Pubkey::to_string).collect();
+            let response: RpcMultipleAccountsResponse = self.call("getMultipleAccounts", json!([keys, config]))?;
+            context_slot = Some(context_slot.map_or(response.context.slot, |slot| slot.min(response.context.slot)));
+            for account in response.value {
+                accounts.push(match account {
+                    Some(account) => Some(AccountState {
+                        lamports: account.lamports,
+                        owner: account.owner,
+                        data: BASE64.decode(&account.data.0)?,
+                    }),
+                    None => None,
+                });
+            }
+        }
+        Ok(MultipleAccounts { context_slot: context_slot.unwrap_or(min_context_slot), accounts })
+    }
+
+    // Leaders of `limit` consecutive slots from `start_slot`. Nodes only
+    // know the schedule of the current and next epochs, give or take.
+    pub fn get_slot_leaders(&self, start_slot: u64, limit: u64) -> Result<Vec<Pubkey>, Box<dyn Error>> {
+        self.call("getSlotLeaders", json!([start_slot, limit]))
+    }
+
+    fn apply_max_version(&self, config: &mut Value) {
+        if let Some(version) = self.max_supported_transaction_version {
+            config["maxSupportedTransactionVersion"] = json!(version);
+        }
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, Box<dyn Error>> {
+        Ok(self.call_endpoint(method, params)?.0)
+    }
+
+    // Transport failures, HTTP 429/5xx and block-not-available are retried
+    // with exponential backoff; the last error is returned once the policy
+    // gives up. With a pool, a failing endpoint is benched and the retry
+    // goes straight to the next one, backing off only when none is left;
+    // the URL of the endpoint that answered comes back with the result.
+    fn call_endpoint<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<(T, Option<String>), Box<dyn Error>> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        })
+        .to_string();
+
+        let _span = span!(Level::Debug, "rpc_call", method = method);
+        let mut attempt = 0;
+        loop {
+            if let Some(limiter) = &self.rate_limiter {
+                limiter.acquire();
+            }
+            let endpoint = self.pool.as_ref().and_then(|pool| pool.pick());
+            let url = match endpoint {
+                Some(endpoint) => endpoint.acquire(),
+                None => &self.url,
+            };
+            let started = Instant::now();
+            let attempt_result = self.send(url, method, &request);
+            metrics().record_rpc(method, started.elapsed(), matches!(attempt_result, Attempt::Done(Ok(_))));
+            let mut failed_over = false;
+            if let (Some(pool), Some(endpoint)) = (&self.pool, endpoint) {
+                match &attempt_result {
+                    // A JSON-RPC error means the node answered; it's no
+                    // fault of the endpoint.
+                    Attempt::Retry(e) if e.downcast_ref::<RpcError>().is_none() => {
+                        pool.failed(endpoint);
+                        failed_over = pool.has_healthy();
+                    }
+                    _ => pool.succeeded(endpoint),
+                }
+            }
+            match attempt_result {
+                Attempt::Done(result) => return result.map(|value| (value, endpoint.map(|endpoint| endpoint.url.clone()))),
+                Attempt::Retry(e) if attempt >= self.retry.max_retries => return Err(e),
+                Attempt::Retry(e) => {
+                    log::warn!(method, url, attempt = attempt + 1, error:% = e; "retrying RPC request");
+                    if !failed_over {
+                        thread::sleep(self.retry.backoff(attempt));
+                    }
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    fn send<T: DeserializeOwned>(&self, url: &str, method: &str, request: &str) -> Attempt<T> {
+        let response = match http::post(url, "application/json", request.as_bytes()) {
+            Ok(response) => response,
+            Err(e) => return Attempt::Retry(e),
+        };
+        if !response.is_success() {
+            let e = format!("{} returned HTTP {}: {}", method, response.status, response.body).into();
+            return match response.status {
+                429 | 500.. => Attempt::Retry(e),
+                _ => Attempt::Done(Err(e)),
+            };
+        }
+
+        let envelope: RpcEnvelope<T> = match serde_json::from_str(&response.body) {
+            Ok(envelope) => envelope,
+            Err(e) => return Attempt::Done(Err(e.into())),
+        };
+        match (envelope.result, envelope.error) {
+            (_, Some(err)) => {
+                let e = RpcError {
+                    method: method.to_string(),
+                    code: err.code,
+                    message: err.message,
+                };
+                if e.is_block_not_available() {
+                    Attempt::Retry(Box::new(e))
+                } else {
+                    Attempt::Done(Err(Box::new(e)))
+                }
+            }
+            (Some(result), None) => Attempt::Done(Ok(result)),
+            (None, None) => Attempt::Done(Err(Box::new(NullResult { method: method.to_string() }))),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct NullResult {
+    method: String,
+}
+
+impl fmt::Display for NullResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} returned a null result", self.method)
+    }
+}
+
+impl Error for NullResult {}
+
+enum Attempt<T> {
+    Done(Result<T, Box<dyn Error>>),
+    Retry(Box<dyn Error>),
+}
+
+// ==========================================
+// ENDPOINT POOL
+// ==========================================
+// Several RPC endpoints behind one client. Requests go round-robin in
+// proportion to each endpoint's weight, each within the endpoint's own
+// rate limit. An endpoint that fails (transport error, HTTP 429/5xx) is
+// benched for a cooldown that doubles with every consecutive failure while
+// the others take its requests; once the cooldown is over it has to pass a
+// getHealth check before it gets traffic again. With every endpoint
+// benched, the one due back soonest is tried anyway.
+
+pub const DEFAULT_ENDPOINT_COOLDOWN: Duration = Duration::from_secs(5);
+const MAX_ENDPOINT_COOLDOWN: Duration = Duration::from_secs(300);
+
+#[derive(Debug)]
+pub struct Endpoint {
+    pub url: String,
+    pub weight: u32,
+    rate_limiter: Option<RateLimiter>,
+    health: Mutex<Health>,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    consecutive_failures: u32,
+    // Set while benched, and after until the endpoint passes a check.
+    benched_until: Option<Instant>,
+}
+
+impl Endpoint {
+    pub fn new(url: &str) -> Self {
+        Endpoint { url: url.to_string(), weight: 1, rate_limiter: None, health: Mutex::new(Health::default()) }
+    }
+
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.weight = weight.max(1);
+        self
+    }
+
+    pub fn with_rate_limit(mut self, requests_per_second: f64) -> Self {
+        self.rate_limiter = Some(RateLimiter::new(requests_per_second));
+        self
+    }
+
+    // `<url>[,weight=<n>][,rate-limit=<requests per second>]`, as given to
+    // --rpc-endpoint.
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        let mut parts = value.split(',');
+        let url = parts.next().filter(|url| !url.is_empty()).ok_or_else(|| format!("invalid RPC endpoint '{}'", value))?;
+        let mut endpoint = Endpoint::new(url);
+        for option in parts {
+            match option.split_once('=') {
+                Some(("weight", weight)) => {
+                    endpoint = endpoint.with_weight(weight.parse().map_err(|_| format!("invalid endpoint weight '{}'", weight))?);
+                }
+                Some(("rate-limit", rate)) => match rate.parse::<f64>() {
+                    Ok(rate) if rate > 0.0 => endpoint = endpoint.with_rate_limit(rate),
+                    _ => return Err(format!("invalid endpoint rate limit '{}'", rate)),
+                },
+                _ => return Err(format!("unknown endpoint option '{}' (expected weight=<n> or rate-limit=<n>)", option)),
+            }
+        }
+        Ok(endpoint)
+    }
+
+    pub fn is_benched(&self) -> bool {
+        self.health().benched_until.is_some_and(|until| until > Instant::now())
+    }
+
+    // Waits for the endpoint's own rate limit.
+    fn acquire(&self) -> &str {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire();
+        }
+        &self.url
+    }
+
+    fn health(&self) -> std::sync::MutexGuard<'_, Health> {
+        self.health.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[derive(Debug)]
+pub struct EndpointPool {
+    endpoints: Vec<Endpoint>,
+    cooldown: Duration,
+    // Smooth weighted round robin: every pick adds each available
+    // endpoint's weight to its counter and takes the highest, which gives
+    // back the total, so picks interleave rather than come in runs.
+    counters: Mutex<Vec<i64>>,
+}
+
+impl EndpointPool {
+    pub fn new(endpoints: Vec<Endpoint>) -> Self {
+        let counters = Mutex::new(vec![0; endpoints.len()]);
+        EndpointPool { endpoints, cooldown: DEFAULT_ENDPOINT_COOLDOWN, counters }
+    }
+
+    // How long an endpoint is first benched for.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    pub fn endpoints(&self) -> &[Endpoint] {
+        &self.endpoints
+    }
+
+    pub fn has_healthy(&self) -> bool {
+        self.endpoints.iter().any(|endpoint| !endpoint.is_benched())
+    }
+
+    // The next endpoint in the rotation, checking one just back from the
+    // bench first. None only for an empty pool.
+    fn pick(&self) -> Option<&Endpoint> {
+        loop {
+            let index = {
+                let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+                let available: Vec<usize> = (0..self.endpoints.len()).filter(|&i| !self.endpoints[i].is_benched()).collect();
+                if available.is_empty() {
+                    return self.endpoints.iter().min_by_key(|endpoint| endpoint.health().benched_until);
+                }
+                let total: i64 = available.iter().map(|&i| self.endpoints[i].weight as i64).sum();
+                for &i in &available {
+                    counters[i] += self.endpoints[i].weight as i64;
+                }
+                let chosen = available.iter().copied().max_by_key(|&i| (counters[i], std::cmp::Reverse(i)))?;
+                counters[chosen] -= total;
+                chosen
+            };
+            let endpoint = &self.endpoints[index];
+            if endpoint.health().benched_until.is_none() || self.check(endpoint) {
+                return Some(endpoint);
+            }
+        }
+    }
+
+    // getHealth answers "ok" from a node that's caught up.
+    fn check(&self, endpoint: &Endpoint) -> bool {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "getHealth" }).to_string();
+        let healthy = http::post(endpoint.acquire(), "application/json", request.as_bytes()).is_ok_and(|response| {
+            response.is_success()
+                && serde_json::from_str::<RpcEnvelope<String>>(&response.body).is_ok_and(|envelope| envelope.result.as_deref() == Some("ok"))
+        });
+        match healthy {
+            true => {
+                log::info!(url = endpoint.url.as_str(); "RPC endpoint is healthy again");
+                self.succeeded(endpoint);
+            }
+            false => self.failed(endpoint),
+        }
+        healthy
+    }
+
+    fn succeeded(&self, endpoint: &Endpoint) {
+        *endpoint.health() = Health::default();
+    }
+
+    fn failed(&self, endpoint: &Endpoint) {
+        let mut health = endpoint.health();
+        health.consecutive_failures += 1;
+        let cooldown =
+            self.cooldown.saturating_mul(2u32.saturating_pow(health.consecutive_failures - 1)).min(MAX_ENDPOINT_COOLDOWN);
+        health.benched_until = Some(Instant::now() + cooldown);
+        log::warn!(url = endpoint.url.as_str(), failures = health.consecutive_failures, cooldown_secs = cooldown.as_secs_f64(); "benching RPC endpoint");
+    }
+}
+
+// ==========================================
+// SLOT LEADERS
+// ==========================================
+// getSlotLeaders answers for a window of slots at a time, so leaders are
+// fetched LEADER_WINDOW slots at once and cached per window. Only the most
+// recent windows are kept; a backfill's workers each stay within a few.
+
+const LEADER_WINDOW: u64 = 1000;
+const CACHED_LEADER_WINDOWS: usize = 64;
+
+pub struct SlotLeaders {
+    client: RpcClient,
+    // Keyed by window start.
+    windows: BTreeMap<u64, Vec<Pubkey>>,
+}
+
+impl SlotLeaders {
+    pub fn new(client: RpcClient) -> Self {
+        SlotLeaders { client, windows: BTreeMap::new() }
+    }
+
+    pub fn leader(&mut self, slot: u64) -> Result<Option<Pubkey>, Box<dyn Error>> {
+        let start = slot - slot % LEADER_WINDOW;
+        if !self.windows.contains_key(&start) {
+            let leaders = self.client.get_slot_leaders(start, LEADER_WINDOW)?;
+            if self.windows.len() >= CACHED_LEADER_WINDOWS {
+                self.windows.pop_first();
+            }
+            self.windows.insert(start, leaders);
+        }
+        Ok(self.windows[&start].get((slot - start) as usize).copied())
+    }
+}
+
+// ==========================================
+// ACCOUNT SNAPSHOTS
+// ==========================================
+// Reads back selected accounts (a pool, an order book) once a block that
+// wrote them has been parsed, for consumers that need the resulting state
+// and not only the instructions. It's the node's current state, so the
+// fresher the block the closer it is to the state right after it; each
+// snapshot carries the slot it was actually read at.
+
+pub struct AccountSnapshots {
+    client: RpcClient,
+    accounts: HashSet<Pubkey>,
+    data_encoding: DataEncoding,
+    // The last state seen of each account, None if it didn't exist, to
+    // flag changes.
+    previous: HashMap<Pubkey, Option<AccountState>>,
+}
+
+impl AccountSnapshots {
+    pub fn new(client: RpcClient, accounts: impl IntoIterator<Item = Pubkey>) -> Self {
+        AccountSnapshots {
+            client,
+            accounts: accounts.into_iter().collect(),
+            data_encoding: DataEncoding::Base64,
+            previous: HashMap::new(),
+        }
+    }
+
+    pub fn with_data_encoding(mut self, encoding: DataEncoding) -> Self {
+        self.data_encoding = encoding;
+        self
+    }
+
+    // Snapshots the selected accounts written by one of the block's
+    // transactions onto `block.account_snapshots`, in pubkey order. A block
+    // that wrote none of them costs no request.
+    pub fn snapshot(&mut self, block: &mut ParsedBlock) -> Result<(), Box<dyn Error>> {
+        let written: BTreeSet<Pubkey> = block
+            .transactions
+            .iter()
+            .flat_map(|tx| &tx.accounts)
+            .filter(|account| account.is_writable && self.accounts.contains(&account.pubkey))
+            .map(|account| account.pubkey)
+            .collect();
+        if written.is_empty() {
+            return Ok(());
+        }
+
+        let written: Vec<Pubkey> = written.into_iter().collect();
+        let MultipleAccounts { context_slot, accounts } = self.client.get_multiple_accounts(&written, block.slot)?;
+        for (pubkey, state) in written.into_iter().zip(accounts) {
+            let changed = self.previous.get(&pubkey).map(|previous| *previous != state);
+            block.account_snapshots.push(AccountSnapshot {
+                pubkey,
+                context_slot,
+                owner: state.as_ref().map(|state| state.owner),
+                lamports: state.as_ref().map_or(0, |state| state.lamports),
+                data: self.data_encoding.encode(state.as_ref().map_or(&[][..], |state| &state.data)),
+                data_encoding: self.data_encoding,
+                changed,
+            });
+            self.previous.insert(pubkey, state);
+        }
+        Ok(())
+    }
+}
+
+// ==========================================
+// RETRY AND RATE LIMITING
+// ==========================================
+
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    // Retries after the first attempt; 0 disables retrying.
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        RetryPolicy { max_retries: 0, ..Self::default() }
+    }
+
+    // Doubles with each attempt, capped at `max_backoff`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+// Token bucket: refills at `requests_per_second` and holds at most one
+// second's worth, so short bursts are allowed but the average rate is not
+// exceeded.
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests_per_second: f64,
+    capacity: f64,
+    state: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64) -> Self {
+        let capacity = requests_per_second.max(1.0);
+        RateLimiter {
+            requests_per_second,
+            capacity,
+            state: Mutex::new(Bucket { tokens: capacity, refilled_at: Instant::now() }),
+        }
+    }
+
+    // Takes a token, sleeping until one is available.
+    pub fn acquire(&self) {
+        let wait = {
+            let mut bucket = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.refilled_at).as_secs_f64() * self.requests_per_second;
+            bucket.tokens = (bucket.tokens + refill).min(self.capacity);
+            bucket.refilled_at = now;
+            // Going negative reserves the token, so concurrent callers queue
+            // up behind each other instead of all waking at once.
+            bucket.tokens -= 1.0;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-bucket.tokens / self.requests_per_second)
+        };
+        thread::sleep(wait);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        };
+        let delays: Vec<u64> = (0..6).map(|attempt| policy.backoff(attempt).as_millis() as u64).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+    }
+
+    #[test]
+    fn pool_rotates_by_weight_and_fails_over() {
+        let pool = EndpointPool::new(vec![
+            Endpoint::from_arg("http://a,weight=2").unwrap(),
+            Endpoint::from_arg("http://b,rate-limit=100").unwrap(),
+        ]);
+        let picks = |n| (0..n).map(|_| pool.pick().unwrap().url.as_str()).collect::<Vec<_>>();
+        assert_eq!(picks(6), ["http://a", "http://b", "http://a", "http://a", "http://b", "http://a"]);
+
+        pool.failed(&pool.endpoints()[0]);
+        assert!(pool.has_healthy());
+        assert_eq!(picks(3), ["http://b"; 3]);
+
+        // With both benched, a was benched first and is due back first.
+        pool.failed(&pool.endpoints()[1]);
+        assert!(!pool.has_healthy());
+        assert_eq!(picks(1), ["http://a"]);
+        assert!(Endpoint::from_arg("http://a,weight=x").is_err());
+    }
+
+    #[test]
+    fn snapshots_are_only_requested_for_written_accounts() {
+        let mut block = crate::fixtures::block().clone();
+        let written = block.transactions.iter().flat_map(|tx| &tx.accounts).find(|account| account.is_writable).unwrap().pubkey;
+        let program = block.transactions[0].instructions[0].program_id;
+        // Nothing listens here, so any request fails.
+        let client = RpcClient::new("http://127.0.0.1:9").with_retry(RetryPolicy::none());
+
+        let mut untouched = AccountSnapshots::new(client.clone(), [Pubkey::new([7; 32]), program]);
+        assert!(untouched.snapshot(&mut block).is_ok());
+        assert!(block.account_snapshots.is_empty());
+
+        let mut touched = AccountSnapshots::new(client, [written]);
+        assert!(touched.snapshot(&mut block).is_err());
+    }
+}
@@ -0,0 +1,435 @@
+use serde_json::{json, Map, Value};
+
+// ==========================================
+// JSON SCHEMA
+// ==========================================
+// JSON Schema (draft 2020-12) for the ParsedBlock and ParsedTransaction
+// output, for consumers that generate their types from it. It's written out
+// by hand rather than derived, and the test below checks the golden
+// fixtures against it, failing on any field the schema doesn't declare.
+//
+// Fields written only when set are left out of `required`; fields written
+// as null are required and nullable. Amounts are plain JSON numbers, so
+// u64 values past 2^53 need a reader that doesn't go through doubles.
+// Decoded instructions, events and transaction errors vary per program and
+// are only described down to their outer shape.
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The schema of a ParsedBlock, as written by `--output json`.
+pub fn block_schema() -> Value {
+    document("ParsedBlock")
+}
+
+/// The schema of a ParsedTransaction: `parse-tx` output, and each line of
+/// `--output ndjson`.
+pub fn transaction_schema() -> Value {
+    document("ParsedTransaction")
+}
+
+fn document(root: &str) -> Value {
+    json!({
+        "$schema": DRAFT,
+        "title": root,
+        "$ref": format!("#/$defs/{}", root),
+        "$defs": definitions(),
+    })
+}
+
+fn definitions() -> Map<String, Value> {
+    let mut defs = Map::new();
+    let mut define = |name: &str, schema: Value| {
+        defs.insert(name.to_string(), schema);
+    };
+
+    define("Pubkey", json!({
+        "type": "string",
+        "description": "Base58 address",
+        "pattern": "^[1-9A-HJ-NP-Za-km-z]{32,44}$",
+    }));
+
+    define("ParsedBlock", object(vec![
+        required("schema_version", unsigned()),
+        required("slot", described(unsigned(), "parent_slot + 1 unless the slot was known")),
+        required("epoch", unsigned()),
+        required("slot_index", unsigned()),
+        required("block_height", nullable(unsigned())),
+        required("block_time", nullable(integer())),
+        required("block_time_rfc3339", nullable(string())),
+        required("blockhash", string()),
+        required("parent_slot", unsigned()),
+        required("previous_blockhash", string()),
+        required("leader", nullable(reference("Pubkey"))),
+        required("rewards", array(reference("BlockReward"))),
+        required("transactions", array(reference("ParsedTransaction"))),
+        optional("signatures", described(array(string()), "Only for blocks fetched with transactionDetails: signatures")),
+        optional("excluded_votes", described(unsigned(), "Vote transactions left out by a vote-excluding filter")),
+        optional("account_snapshots", described(array(reference("AccountSnapshot")), "Only with --snapshot-account")),
+        optional("endpoint", described(string(), "The RPC endpoint that served the block, with --rpc-endpoint")),
+    ]));
+
+    define("AccountSnapshot", object(vec![
+        required("pubkey", reference("Pubkey")),
+        required("context_slot", described(unsigned(), "The slot the account was read at")),
+        required("owner", described(nullable(reference("Pubkey")), "Null once the account no longer exists")),
+        required("lamports", unsigned()),
+        required("data", string()),
+        required("data_encoding", one_of_strings(&["base58", "base64", "hex"])),
+        required("changed", described(nullable(boolean()), "Against the previous snapshot of the account")),
+    ]));
+
+    define("BlockReward", object(vec![
+        required("pubkey", reference("Pubkey")),
+        required("lamports", integer()),
+        required("post_balance", unsigned()),
+        required("reward_type", described(string(), "Fee, Rent, Staking, Voting, or the RPC's name for a newer type")),
+        required("commission", nullable(unsigned())),
+    ]));
+
+    define("ParsedTransaction", object(vec![
+        required("schema_version", unsigned()),
+        required("signature", string()),
+        required("slot", nullable(unsigned())),
+        required("index_in_block", described(nullable(unsigned()), "Position in the block before any filter")),
+        optional("timestamp_ms", described(integer(), "Milliseconds since the epoch, with --timestamps")),
+        required("fee_payer", reference("Pubkey")),
+        required("is_success", boolean()),
+        optional("error", described(json!({ "type": ["object", "string"] }), "meta.err, keyed by the error's name")),
+        optional("failure_reason", string()),
+        optional("failure_cause", reference("FailureCause")),
+        required("account_keys", array(reference("Pubkey"))),
+        required("accounts", array(reference("TransactionAccount"))),
+        required("instructions", array(reference("ParsedInstruction"))),
+        required("inner_instructions", array(reference("ParsedInnerInstructions"))),
+        required("log_messages", array(string())),
+        required("events", array(reference("DecodedEvent"))),
+        required("memos", array(string())),
+        required("pre_balances", array(unsigned())),
+        required("post_balances", array(unsigned())),
+        required("pre_token_balances", array(reference("TokenBalance"))),
+        required("post_token_balances", array(reference("TokenBalance"))),
+        required("token_balance_changes", array(reference("TokenBalanceChange"))),
+        required("fee", unsigned()),
+        required("base_fee", unsigned()),
+        required("priority_fee", unsigned()),
+        required("compute_units_consumed", nullable(unsigned())),
+        required("compute_unit_limit", nullable(unsigned())),
+        required("unit_price_micro_lamports", nullable(unsigned())),
+        required("compute_by_program", array(reference("ProgramComputeUnits"))),
+        required("fee_breakdown", reference("FeeBreakdown")),
+        optional("signatures_verified", boolean()),
+        optional("labels", json!({ "type": "object", "additionalProperties": string() })),
+        optional("account_warnings", array(reference("AccountWarning"))),
+        optional("durable_nonce", reference("DurableNonce")),
+        optional("token_owners", described(
+            json!({ "type": "object", "additionalProperties": reference("Pubkey") }),
+            "Owners of token accounts the token balances don't name, with --fetch-token-owners",
+        )),
+    ]));
+
+    define("FailureCause", object(vec![
+        required("program_id", nullable(reference("Pubkey"))),
+        required("error", string()),
+        optional("name", string()),
+        required("category", one_of_strings(&["Slippage", "InsufficientFunds", "ComputeBudgetExceeded", "Other"])),
+    ]));
+
+    define("TransactionAccount", object(vec![
+        required("pubkey", reference("Pubkey")),
+        required("is_signer", boolean()),
+        required("is_writable", boolean()),
+        required("source", one_of_strings(&["static", "lookup"])),
+    ]));
+
+    define("ParsedInstruction", object(vec![
+        required("program_id", reference("Pubkey")),
+        required("accounts", array(reference("AccountRef"))),
+        required("account_indices", array(unsigned())),
+        required("data", string()),
+        required("data_encoding", one_of_strings(&["base58", "base64", "hex"])),
+        required("decoded", nullable(reference("DecodedInstruction"))),
+        optional("parsed", reference("JsonParsedInstruction")),
+        optional("stack_height", unsigned()),
+        optional("invoked_by", unsigned()),
+        optional("execution", reference("InstructionExecution")),
+    ]));
+
+    define("InstructionExecution", object(vec![
+        required("status", one_of_strings(&["executed", "failed", "not_executed"])),
+        optional("error", described(json!({ "type": ["object", "string"] }), "The instruction error, for the failed one")),
+    ]));
+
+    define("AccountRef", json!({
+        "description": "The account's address, or {\"index\": n} for an index past the account keys",
+        "anyOf": [
+            reference("Pubkey"),
+            object(vec![required("index", unsigned())]),
+        ],
+    }));
+
+    define("DecodedInstruction", json!({
+        "type": "object",
+        "description": "Keyed by decoder; the value's shape is the decoder's own",
+        "propertyNames": one_of_strings(&[
+            "system", "token", "token_metadata", "stake", "vote", "associated_token", "compute_budget", "raydium",
+            "jupiter", "anchor", "custom",
+        ]),
+        "minProperties": 1,
+        "maxProperties": 1,
+    }));
+
+    define("JsonParsedInstruction", object(vec![
+        required("program", string()),
+        required("parsed", json!({})),
+    ]));
+
+    define("ParsedInnerInstructions", object(vec![
+        required("index", unsigned()),
+        required("instructions", array(reference("ParsedInstruction"))),
+    ]));
+
+    define("DecodedEvent", object(vec![
+        required("program_id", reference("Pubkey")),
+        required("name", string()),
+        required("data", json!({})),
+    ]));
+
+    define("TokenBalance", object(vec![
+        required("account_index", unsigned()),
+        required("account", reference("Pubkey")),
+        required("mint", reference("Pubkey")),
+        required("owner", nullable(reference("Pubkey"))),
+        required("program_id", nullable(reference("Pubkey"))),
+        required("amount", unsigned()),
+        required("decimals", unsigned()),
+    ]));
+
+    define("TokenBalanceChange", object(vec![
+        required("owner", reference("Pubkey")),
+        required("mint", reference("Pubkey")),
+        required("decimals", unsigned()),
+        required("pre_amount", unsigned()),
+        required("post_amount", unsigned()),
+        required("delta", integer()),
+    ]));
+
+    define("ProgramComputeUnits", object(vec![
+        required("program_id", reference("Pubkey")),
+        required("compute_units", unsigned()),
+    ]));
+
+    define("FeeBreakdown", object(vec![
+        required("signatures", unsigned()),
+        required("base_fee", unsigned()),
+        required("priority_fee", unsigned()),
+        required("compute_unit_limit", unsigned()),
+        required("compute_unit_price_micro_lamports", unsigned()),
+        required("consumed_priority_fee", nullable(unsigned())),
+    ]));
+
+    define("AccountWarning", json!({
+        "oneOf": [
+            variant("DuplicateAccountKeys", vec![
+                required("keys", array(reference("Pubkey"))),
+                required("removed", boolean()),
+            ]),
+            variant("BalanceCountMismatch", vec![
+                required("account_keys", unsigned()),
+                required("pre_balances", unsigned()),
+                required("post_balances", unsigned()),
+            ]),
+            variant("SignatureCountMismatch", vec![
+                required("signatures", unsigned()),
+                required("required", unsigned()),
+            ]),
+            variant("InvalidHeader", vec![
+                required("static_keys", unsigned()),
+                required("required_signatures", unsigned()),
+                required("readonly_signed", unsigned()),
+                required("readonly_unsigned", unsigned()),
+            ]),
+            variant("TooManyAccountKeys", vec![required("account_keys", unsigned())]),
+            variant("AccountIndexOutOfRange", vec![
+                required("indices", array(unsigned())),
+                required("account_keys", unsigned()),
+            ]),
+        ],
+    }));
+
+    define("DurableNonce", object(vec![
+        required("nonce_account", reference("Pubkey")),
+        required("nonce_authority", reference("Pubkey")),
+        required("nonce", nullable(string())),
+    ]));
+
+    defs
+}
+
+// ==========================================
+// VALIDATION
+// ==========================================
+// Just enough of a validator for the keywords used above. Objects with
+// `properties` are treated as closed, so a field added to the output
+// without a schema entry fails here.
+
+/// Where `value` departs from `schema` (one of the documents above), one
+/// message per problem, each starting with the JSON path; empty when it
+/// conforms.
+pub fn schema_errors(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(schema, &schema["$defs"], value, "$", &mut errors);
+    errors
+}
+
+fn check(schema: &Value, defs: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(target) = schema["$ref"].as_str() {
+        let name = target.trim_start_matches("#/$defs/");
+        return check(&defs[name], defs, value, path, errors);
+    }
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(options) = schema[keyword].as_array() {
+            let matched = options.iter().any(|option| {
+                let mut option_errors = Vec::new();
+                check(option, defs, value, path, &mut option_errors);
+                option_errors.is_empty()
+            });
+            if !matched {
+                errors.push(format!("{}: matches none of {}", path, keyword));
+            }
+            return;
+        }
+    }
+    let type_matches = |name: &str| match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "null" => value.is_null(),
+        _ => false,
+    };
+    let types: Vec<&str> = match &schema["type"] {
+        Value::String(name) => vec![name],
+        Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|name| type_matches(name)) {
+        errors.push(format!("{}: expected {:?}, got {}", path, types, value));
+        return;
+    }
+    if let Some(allowed) = schema["enum"].as_array().filter(|allowed| !allowed.contains(value)) {
+        errors.push(format!("{}: {} not in {:?}", path, value, allowed));
+    }
+    if schema["minimum"].as_i64().is_some_and(|min| value.as_i64().is_some_and(|v| v < min)) {
+        errors.push(format!("{}: below minimum", path));
+    }
+    if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+        for (i, item) in values.iter().enumerate() {
+            check(items, defs, item, &format!("{}[{}]", path, i), errors);
+        }
+    }
+    if let Some(fields) = value.as_object() {
+        if let Some(names) = schema.get("propertyNames") {
+            for name in fields.keys() {
+                check(names, defs, &json!(name), path, errors);
+            }
+        }
+        for name in schema["required"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+            if !fields.contains_key(name) {
+                errors.push(format!("{}: missing {}", path, name));
+            }
+        }
+        for (name, field) in fields {
+            let field_path = format!("{}.{}", path, name);
+            if let Some(property) = schema["properties"].get(name) {
+                check(property, defs, field, &field_path, errors);
+            } else if let Some(additional) = schema.get("additionalProperties") {
+                check(additional, defs, field, &field_path, errors);
+            } else if schema.get("properties").is_some() {
+                errors.push(format!("{}: not in the schema", field_path));
+            }
+        }
+    }
+}
+
+// ==========================================
+// SCHEMA BUILDERS
+// ==========================================
+
+// (name, schema, always written)
+type Property = (&'static str, Value, bool);
+
+fn required(name: &'static str, schema: Value) -> Property {
+    (name, schema, true)
+}
+
+fn optional(name: &'static str, schema: Value) -> Property {
+    (name, schema, false)
+}
+
+fn object(properties: Vec<Property>) -> Value {
+    let required: Vec<&str> = properties.iter().filter(|(_, _, required)| *required).map(|(name, _, _)| *name).collect();
+    let properties: Map<String, Value> = properties.into_iter().map(|(name, schema, _)| (name.to_string(), schema)).collect();
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+// An externally tagged enum variant: {"Name": {...}}.
+fn variant(name: &'static str, fields: Vec<Property>) -> Value {
+    object(vec![required(name, object(fields))])
+}
+
+fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("#/$defs/{}", name) })
+}
+
+fn nullable(schema: Value) -> Value {
+    json!({ "anyOf": [schema, { "type": "null" }] })
+}
+
+fn described(mut schema: Value, description: &str) -> Value {
+    schema["description"] = json!(description);
+    schema
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn one_of_strings(values: &[&str]) -> Value {
+    json!({ "type": "string", "enum": values })
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer" })
+}
+
+fn unsigned() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn golden_outputs_match_the_schema() {
+        let mut checked = 0;
+        for entry in std::fs::read_dir("src/json/golden").unwrap() {
+            let path = entry.unwrap().path();
+            let output: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            let schema = if output.get("transactions").is_some() { block_schema() } else { transaction_schema() };
+            let errors = schema_errors(&schema, &output);
+            assert!(errors.is_empty(), "{}: {:#?}", path.display(), &errors[..errors.len().min(10)]);
+            checked += 1;
+        }
+        assert!(checked >= 5);
+    }
+}
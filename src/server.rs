@@ -0,0 +1,473 @@
+use crate::graphql::{GraphQl, Object, QueryError};
+use crate::incremental::parse_block_reader;
+use crate::parser::{parse_block_with_options, parse_transaction_result, ParseOptions};
+use crate::rpc::{BlockFetch, RpcClient};
+use crate::shutdown;
+use crate::types::{ParseReport, RpcResponse};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::error::Error;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// ==========================================
+// HTTP API
+// ==========================================
+// The parser behind a small HTTP/1.1 server, for services that would rather
+// call it than link the crate:
+//
+//   POST /parse/transaction   a getTransaction response -> ParsedTransaction
+//   POST /parse/block         a getBlock response -> ParsedBlock; ?slot=<n>
+//                             sets the slot, parent_slot + 1 otherwise
+//   GET  /block/<slot>        fetched over RPC, then parsed
+//   POST /graphql             {"query": ..., "variables": {...}} against the
+//                             sink database (see graphql), with_graphql only
+//   GET  /health              "ok"
+//
+// Parse bodies are the RPC responses as saved for parse-tx and parse-block,
+// envelope included. Answers are the parsed JSON, or {"error": "..."} with
+// a 4xx/5xx status. Each worker thread serves one connection at a time, and
+// every connection carries a single request.
+//
+// Requests are read defensively, since a worker is tied up until its
+// request is in: header lines and their number are capped, the body (sized
+// by Content-Length or sent chunked) is read as it arrives rather than
+// allocated up front, and the whole request has REQUEST_TIMEOUT to come in,
+// so a client trickling bytes can't hold a worker. The server is this much
+// std::net rather than axum or hyper because it answers one blocking call
+// per request, and a web framework would bring in tokio for that alone.
+
+// Full getBlock responses run to tens of megabytes.
+const MAX_BODY: usize = 50 * 1024 * 1024;
+const MAX_HEADER_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+// Body bytes read, and allocated, at a time.
+const BODY_CHUNK: usize = 64 * 1024;
+// How often an idle worker checks for shutdown.
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn json<T: Serialize>(value: &T) -> Self {
+        match serde_json::to_string(value) {
+            Ok(body) => Response { status: 200, body },
+            Err(e) => Response::error(500, format!("failed to serialize the response: {}", e)),
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Response { status, body: json!({ "error": message.into() }).to_string() }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            413 => "Payload Too Large",
+            431 => "Request Header Fields Too Large",
+            501 => "Not Implemented",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+pub struct ApiServer {
+    options: ParseOptions,
+    // None leaves GET /block/<slot> unavailable.
+    client: Option<RpcClient>,
+    // None leaves POST /graphql unavailable.
+    graphql: Option<GraphQl>,
+    workers: usize,
+}
+
+impl ApiServer {
+    pub fn new(options: ParseOptions) -> Self {
+        ApiServer { options, client: None, graphql: None, workers: 4 }
+    }
+
+    pub fn with_rpc_client(mut self, client: RpcClient) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn with_graphql(mut self, graphql: GraphQl) -> Self {
+        self.graphql = Some(graphql);
+        self
+    }
+
+    // Connections served at once.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    // Serves on `addr` until shutdown is requested. The listener is polled
+    // so idle workers notice; a request being answered is finished first.
+    pub fn serve(self, addr: &str) -> Result<(), Box<dyn Error>> {
+        let listener = TcpListener::bind(addr).map_err(|e| format!("api server {}: {}", addr, e))?;
+        listener.set_nonblocking(true)?;
+        log::info!(addr, workers = self.workers; "serving the parser over HTTP");
+        let server = Arc::new(self);
+        let handles: Vec<_> = (0..server.workers)
+            .map(|_| {
+                let (server, listener) = (server.clone(), listener.try_clone());
+                thread::spawn(move || -> Result<(), std::io::Error> {
+                    let listener = listener?;
+                    while !shutdown::requested() {
+                        let stream = match listener.accept() {
+                            Ok((stream, _)) => stream,
+                            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                                thread::sleep(ACCEPT_POLL);
+                                continue;
+                            }
+                            Err(e) => return Err(e),
+                        };
+                        stream.set_nonblocking(false)?;
+                        if let Err(e) = server.respond(stream) {
+                            log::warn!(error:% = e; "api request failed");
+                        }
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().map_err(|_| "api worker panicked")??;
+        }
+        Ok(())
+    }
+
+    // Answers one request; `path` may carry a query string.
+    pub fn handle(&self, method: &str, path: &str, body: &[u8]) -> Response {
+        let (route, query) = path.split_once('?').unwrap_or((path, ""));
+        match (method, route) {
+            ("POST", "/parse/transaction") => self.parse_transaction(body),
+            ("POST", "/parse/block") => self.parse_block(body, query),
+            ("POST", "/graphql") => self.graphql(body),
+            ("GET", "/health") => Response { status: 200, body: "\"ok\"".to_string() },
+            ("GET", route) if route.starts_with("/block/") => self.fetch_block(&route["/block/".len()..]),
+            (_, "/parse/transaction" | "/parse/block" | "/graphql" | "/health") => Response::error(405, format!("{} isn't allowed on {}", method, route)),
+            _ => Response::error(404, format!("no route for {} {}", method, route)),
+        }
+    }
+
+    fn parse_transaction(&self, body: &[u8]) -> Response {
+        let raw: RpcResponse = match serde_json::from_slice(body) {
+            Ok(raw) => raw,
+            Err(e) => return Response::error(400, format!("invalid getTransaction response: {}", e)),
+        };
+        match parse_transaction_result(&raw.result, &self.options) {
+            Ok((tx, report)) => {
+                log_warnings(&report);
+                Response::json(&tx)
+            }
+            Err(e) => Response::error(400, e.to_string()),
+        }
+    }
+
+    fn parse_block(&self, body: &[u8], query: &str) -> Response {
+        let slot = match query.split('&').find_map(|pair| pair.strip_prefix("slot=")).map(str::parse::<u64>).transpose() {
+            Ok(slot) => slot,
+            Err(_) => return Response::error(400, "slot must be a number"),
+        };
+        match parse_block_reader(body, &self.options) {
+            Ok((mut block, report)) => {
+                if let Some(slot) = slot {
+                    block.set_slot(slot, &self.options.epoch_schedule);
+                }
+                log_warnings(&report);
+                Response::json(&block)
+            }
+            Err(e) => Response::error(400, e.to_string()),
+        }
+    }
+
+    fn fetch_block(&self, slot: &str) -> Response {
+        let Ok(slot) = slot.parse::<u64>() else {
+            return Response::error(400, format!("invalid slot '{}'", slot));
+        };
+        let Some(client) = &self.client else {
+            return Response::error(503, "no RPC endpoint configured");
+        };
+        match client.fetch_block(slot) {
+            Ok(BlockFetch::Block(raw_block)) => match parse_block_with_options(raw_block, &self.options) {
+                Ok((mut block, report)) => {
+                    block.set_slot(slot, &self.options.epoch_schedule);
+                    log_warnings(&report);
+                    Response::json(&block)
+                }
+                Err(e) => Response::error(502, e.to_string()),
+            },
+            Ok(BlockFetch::SlotSkipped) => Response::error(404, format!("slot {} was skipped", slot)),
+            Ok(BlockFetch::BlockUnavailable) => Response::error(503, format!("block for slot {} is not available yet", slot)),
+            Err(e) => Response::error(502, e.to_string()),
+        }
+    }
+
+    // GraphQL errors come back as {"errors": [{"message": ...}]}.
+    fn graphql(&self, body: &[u8]) -> Response {
+        #[derive(serde::Deserialize)]
+        struct Request {
+            query: String,
+            #[serde(default)]
+            variables: Option<Map<String, Value>>,
+        }
+        // Through json! the result's keys would come out sorted.
+        #[derive(Serialize)]
+        struct Answer {
+            data: Object,
+        }
+
+        let Some(graphql) = &self.graphql else {
+            return Response::error(503, "no database configured");
+        };
+        let request: Request = match serde_json::from_slice(body) {
+            Ok(request) => request,
+            Err(e) => return Response::error(400, format!("invalid GraphQL request: {}", e)),
+        };
+        match graphql.execute(&request.query, &request.variables.unwrap_or_default()) {
+            Ok(data) => Response::json(&Answer { data }),
+            Err(e) => {
+                let status = match e {
+                    QueryError::Invalid(_) => 400,
+                    QueryError::Database(_) => 502,
+                };
+                Response { status, body: json!({ "errors": [{ "message": e.to_string() }] }).to_string() }
+            }
+        }
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> Result<(), Box<dyn Error>> {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let reader = RequestReader { reader: BufReader::new(stream.try_clone()?), deadline: Instant::now() + REQUEST_TIMEOUT };
+        let (method, path, response) = match reader.read() {
+            Ok(request) => {
+                let response = self.handle(&request.method, &request.path, &request.body);
+                (request.method, request.path, response)
+            }
+            Err(response) => (String::new(), String::new(), response),
+        };
+        log::debug!(method = method.as_str(), path = path.as_str(), status = response.status; "api request");
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            response.status,
+            response.reason(),
+            response.body.len(),
+            response.body
+        )?;
+        Ok(())
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+// Reads one request, or fails with the response to send instead.
+struct RequestReader {
+    reader: BufReader<TcpStream>,
+    deadline: Instant,
+}
+
+impl RequestReader {
+    fn read(mut self) -> Result<Request, Response> {
+        let request_line = self.line()?;
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (parts.next().unwrap_or("").to_string(), parts.next().unwrap_or("").to_string());
+
+        let (mut content_length, mut chunked) = (None, false);
+        let mut headers = 0;
+        loop {
+            let line = self.line()?;
+            if line.is_empty() {
+                break;
+            }
+            headers += 1;
+            if headers > MAX_HEADERS {
+                return Err(Response::error(431, format!("requests are limited to {} headers", MAX_HEADERS)));
+            }
+            let Some((name, value)) = line.split_once(':') else {
+                return Err(Response::error(400, "malformed header line"));
+            };
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = Some(value.parse::<usize>().map_err(|_| Response::error(400, "invalid Content-Length"))?);
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                if !value.eq_ignore_ascii_case("chunked") {
+                    return Err(Response::error(501, format!("Transfer-Encoding {} isn't supported", value)));
+                }
+                chunked = true;
+            }
+        }
+
+        let mut body = Vec::new();
+        if chunked {
+            loop {
+                let line = self.line()?;
+                let size = line.split(';').next().unwrap_or("").trim();
+                let size = usize::from_str_radix(size, 16).map_err(|_| Response::error(400, "invalid chunk size"))?;
+                if size == 0 {
+                    break;
+                }
+                self.body(size, &mut body)?;
+                if !self.line()?.is_empty() {
+                    return Err(Response::error(400, "malformed chunk"));
+                }
+            }
+            // Trailers, ignored.
+            while !self.line()?.is_empty() {}
+        } else if let Some(len) = content_length {
+            self.body(len, &mut body)?;
+        }
+        Ok(Request { method, path, body })
+    }
+
+    // A line without its CRLF.
+    fn line(&mut self) -> Result<String, Response> {
+        self.arm()?;
+        let mut line = Vec::new();
+        (&mut self.reader).take(MAX_HEADER_LINE as u64 + 1).read_until(b'\n', &mut line).map_err(read_error)?;
+        if line.len() > MAX_HEADER_LINE {
+            return Err(Response::error(431, format!("header lines are limited to {} bytes", MAX_HEADER_LINE)));
+        }
+        if !line.ends_with(b"\n") {
+            return Err(Response::error(400, "connection closed mid-request"));
+        }
+        let line = String::from_utf8(line).map_err(|_| Response::error(400, "headers must be UTF-8"))?;
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    // Appends `len` more body bytes, a chunk at a time.
+    fn body(&mut self, len: usize, body: &mut Vec<u8>) -> Result<(), Response> {
+        if body.len().saturating_add(len) > MAX_BODY {
+            return Err(Response::error(413, format!("bodies are limited to {} bytes", MAX_BODY)));
+        }
+        let mut left = len;
+        while left > 0 {
+            self.arm()?;
+            let start = body.len();
+            body.resize(start + left.min(BODY_CHUNK), 0);
+            self.reader.read_exact(&mut body[start..]).map_err(read_error)?;
+            left -= body.len() - start;
+        }
+        Ok(())
+    }
+
+    // Gives the next read what's left of the request's time.
+    fn arm(&self) -> Result<(), Response> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(Response::error(408, "request timed out"));
+        }
+        self.reader.get_ref().set_read_timeout(Some(remaining)).map_err(|e| Response::error(500, e.to_string()))
+    }
+}
+
+fn read_error(e: std::io::Error) -> Response {
+    match e.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => Response::error(408, "request timed out"),
+        _ => Response::error(400, format!("couldn't read the request: {}", e)),
+    }
+}
+
+fn log_warnings(report: &ParseReport) {
+    if !report.warnings.is_empty() {
+        log::warn!(count = report.warnings.len(); "parse warnings");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn routes_parse_requests() {
+        let server = ApiServer::new(ParseOptions::default());
+        let tx = std::fs::read("src/json/transaction.json").unwrap();
+        let response = server.handle("POST", "/parse/transaction", &tx);
+        assert_eq!(response.status, 200);
+        let parsed: Value = serde_json::from_str(&response.body).unwrap();
+        assert!(parsed["signature"].is_string());
+
+        let block = std::fs::read("src/json/block.json").unwrap();
+        let response = server.handle("POST", "/parse/block?slot=42", &block);
+        assert_eq!(response.status, 200);
+        let parsed: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(parsed["slot"], 42);
+        assert!(!parsed["transactions"].as_array().unwrap().is_empty());
+
+        assert_eq!(server.handle("POST", "/parse/block", b"{").status, 400);
+        assert_eq!(server.handle("GET", "/parse/block", b"").status, 405);
+        assert_eq!(server.handle("GET", "/block/abc", b"").status, 400);
+        // No RPC client configured.
+        assert_eq!(server.handle("GET", "/block/42", b"").status, 503);
+        assert_eq!(server.handle("POST", "/graphql", br#"{"query": "{ blocks { slot } }"}"#).status, 503);
+        assert_eq!(server.handle("GET", "/nowhere", b"").status, 404);
+    }
+
+    // Sends raw request bytes over loopback, returning the status and body.
+    fn exchange(server: &ApiServer, request: Vec<u8>) -> (u16, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(&request).unwrap();
+            stream.shutdown(std::net::Shutdown::Write).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+        server.respond(listener.accept().unwrap().0).unwrap();
+        let response = client.join().unwrap();
+        let status = response.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, response.split_once("\r\n\r\n").unwrap().1.to_string())
+    }
+
+    #[test]
+    fn reads_requests_defensively() {
+        let server = ApiServer::new(ParseOptions::default());
+        let tx = std::fs::read("src/json/transaction.json").unwrap();
+        let mut chunked = b"POST /parse/transaction HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+        for chunk in tx.chunks(1000) {
+            chunked.extend(format!("{:x};ext=1\r\n", chunk.len()).as_bytes());
+            chunked.extend(chunk);
+            chunked.extend(b"\r\n");
+        }
+        chunked.extend(b"0\r\nX-Trailer: y\r\n\r\n");
+        let (status, body) = exchange(&server, chunked);
+        assert_eq!(status, 200, "{}", body);
+        assert!(serde_json::from_str::<Value>(&body).unwrap()["signature"].is_string());
+
+        // Refused before any of the body is read or allocated.
+        let (status, _) = exchange(&server, format!("POST /parse/block HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY + 1).into_bytes());
+        assert_eq!(status, 413);
+        let long = format!("GET /health HTTP/1.1\r\nX-Long: {}\r\n\r\n", "a".repeat(MAX_HEADER_LINE));
+        assert_eq!(exchange(&server, long.into_bytes()).0, 431);
+        let many = format!("GET /health HTTP/1.1\r\n{}\r\n", "X-A: b\r\n".repeat(MAX_HEADERS + 1));
+        assert_eq!(exchange(&server, many.into_bytes()).0, 431);
+        let gzip = b"POST /parse/block HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n".to_vec();
+        assert_eq!(exchange(&server, gzip).0, 501);
+        let short = b"POST /parse/block HTTP/1.1\r\nContent-Length: 100\r\n\r\n{\"a\"".to_vec();
+        assert_eq!(exchange(&server, short).0, 400);
+        assert_eq!(exchange(&server, b"GET /health HTTP/1.1\r\n\r\n".to_vec()), (200, "\"ok\"".to_string()));
+    }
+}
@@ -0,0 +1,58 @@
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// ==========================================
+// GRACEFUL SHUTDOWN
+// ==========================================
+// The long-running commands (stream, backfill, subscribe, parse over live
+// sources) check `requested` between blocks, so Ctrl-C or SIGTERM ends the
+// run the way reaching the end of the range would: outputs flushed and
+// finished, checkpoint saved, run summary written. The reports stop reading
+// blocks and print what they have, one-shot commands skip their output, and
+// serve stops accepting connections. A second signal exits on the spot, for
+// a run stuck waiting on the network.
+//
+// Helper processes (compressors, psql, sqlite3, curl) run in process groups
+// of their own, so a Ctrl-C at the terminal doesn't kill them while the
+// outputs are still being finished through them.
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// Exit status of an interrupted run, as for a process killed by SIGINT.
+pub const INTERRUPTED_EXIT_STATUS: i32 = 130;
+
+#[cfg(unix)]
+extern "C" fn handle_signal(_signal: libc::c_int) {
+    if REQUESTED.swap(true, Ordering::SeqCst) {
+        // Only async-signal-safe calls here.
+        unsafe { libc::_exit(INTERRUPTED_EXIT_STATUS) };
+    }
+}
+
+// Installs the SIGINT and SIGTERM handlers; elsewhere than Unix the signals
+// keep their default behavior.
+pub fn install() {
+    #[cfg(unix)]
+    unsafe {
+        let handler = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+// True once a shutdown signal has arrived.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+// For embedders stopping a run for their own reasons.
+pub fn request() {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+// Keeps terminal signals away from a helper process; see above.
+pub fn own_process_group(command: &mut Command) -> &mut Command {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+    command
+}
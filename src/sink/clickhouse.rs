@@ -0,0 +1,355 @@
+use super::Sink;
+use crate::http;
+use crate::pubkey::Pubkey;
+use crate::types::{ParsedBlock, ParsedInstruction, ParsedTransaction};
+use serde::Serialize;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+// ==========================================
+// CLICKHOUSE SINK
+// ==========================================
+// Buffers rows and sends them through ClickHouse's HTTP interface as
+// `INSERT ... FORMAT JSONEachRow`. A buffer is flushed once it holds
+// `batch_size` rows or `flush_interval` has passed since the last flush.
+
+pub const DEFAULT_CLICKHOUSE_URL: &str = "http://localhost:8123";
+pub const DEFAULT_BATCH_SIZE: usize = 10_000;
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+const TRANSACTIONS_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS {db}.transactions (
+    signature                  String,
+    block_height               Nullable(UInt64),
+    block_time                 Nullable(Int64),
+    blockhash                  Nullable(String),
+    fee_payer                  String,
+    is_success                 Bool,
+    fee                        UInt64,
+    base_fee                   UInt64,
+    priority_fee               UInt64,
+    compute_units_consumed     Nullable(UInt64),
+    compute_unit_limit         Nullable(UInt32),
+    unit_price_micro_lamports  Nullable(UInt64),
+    slot                       Nullable(UInt64),
+    index_in_block             Nullable(UInt32),
+    timestamp_ms               Nullable(Int64)
+) ENGINE = ReplacingMergeTree ORDER BY signature";
+
+// For transactions tables created before the column.
+const ADD_TIMESTAMP_COLUMN: &str = "ALTER TABLE {db}.transactions ADD COLUMN IF NOT EXISTS timestamp_ms Nullable(Int64)";
+
+const INSTRUCTIONS_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS {db}.instructions (
+    signature     String,
+    block_time    Nullable(Int64),
+    parent_index  Nullable(UInt32),
+    index         UInt32,
+    program_id    LowCardinality(String),
+    accounts      Array(String),
+    data          String
+) ENGINE = ReplacingMergeTree ORDER BY (program_id, signature, index)";
+
+#[derive(Serialize)]
+struct TransactionRow<'a> {
+    signature: &'a str,
+    block_height: Option<u64>,
+    block_time: Option<i64>,
+    blockhash: Option<&'a str>,
+    fee_payer: &'a Pubkey,
+    is_success: bool,
+    fee: u64,
+    base_fee: u64,
+    priority_fee: u64,
+    compute_units_consumed: Option<u64>,
+    compute_unit_limit: Option<u32>,
+    unit_price_micro_lamports: Option<u64>,
+    slot: Option<u64>,
+    index_in_block: Option<usize>,
+    timestamp_ms: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct InstructionRow<'a> {
+    signature: &'a str,
+    block_time: Option<i64>,
+    parent_index: Option<usize>,
+    index: usize,
+    program_id: &'a Pubkey,
+    accounts: Vec<String>,
+    data: &'a str,
+}
+
+#[derive(Debug)]
+pub struct ClickHouseSink {
+    url: String,
+    database: String,
+    batch_size: usize,
+    flush_interval: Duration,
+    transactions: String,
+    transaction_rows: usize,
+    instructions: String,
+    instruction_rows: usize,
+    last_flush: Instant,
+}
+
+impl ClickHouseSink {
+    pub fn new(url: &str) -> Self {
+        ClickHouseSink {
+            url: url.trim_end_matches('/').to_string(),
+            database: "default".to_string(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            transactions: String::new(),
+            transaction_rows: 0,
+            instructions: String::new(),
+            instruction_rows: 0,
+            last_flush: Instant::now(),
+        }
+    }
+
+    pub fn with_database(mut self, database: &str) -> Self {
+        self.database = database.to_string();
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    pub fn create_tables(&self) -> Result<(), Box<dyn Error>> {
+        for ddl in [TRANSACTIONS_TABLE, ADD_TIMESTAMP_COLUMN, INSTRUCTIONS_TABLE] {
+            self.execute(&ddl.replace("{db}", &self.database), b"")?;
+        }
+        Ok(())
+    }
+
+    pub fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        self.write_transactions(Some(block), &block.transactions)
+    }
+
+    pub fn write_transactions(&mut self, block: Option<&ParsedBlock>, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        for tx in txs {
+            self.push_transaction(block, tx)?;
+            if self.batch_full() {
+                self.flush()?;
+            }
+        }
+
+        if self.flush_due() {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.transaction_rows > 0 {
+            self.insert("transactions", &self.transactions)?;
+            self.transactions.clear();
+            self.transaction_rows = 0;
+        }
+        if self.instruction_rows > 0 {
+            self.insert("instructions", &self.instructions)?;
+            self.instructions.clear();
+            self.instruction_rows = 0;
+        }
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.flush()
+    }
+
+    // Buffers the transaction's row and its instruction rows.
+    fn push_transaction(&mut self, block: Option<&ParsedBlock>, tx: &ParsedTransaction) -> Result<(), Box<dyn Error>> {
+        let block_time = block.and_then(|b| b.block_time);
+        let row = TransactionRow {
+            signature: &tx.signature,
+            block_height: block.and_then(|b| b.block_height),
+            block_time,
+            blockhash: block.map(|b| b.blockhash.as_str()),
+            fee_payer: &tx.fee_payer,
+            is_success: tx.is_success,
+            fee: tx.fee,
+            base_fee: tx.base_fee,
+            priority_fee: tx.priority_fee,
+            compute_units_consumed: tx.compute_units_consumed,
+            compute_unit_limit: tx.compute_unit_limit,
+            unit_price_micro_lamports: tx.unit_price_micro_lamports,
+            slot: tx.slot,
+            index_in_block: tx.index_in_block,
+            timestamp_ms: tx.timestamp_ms,
+        };
+        push_row(&mut self.transactions, &row)?;
+        self.transaction_rows += 1;
+
+        for (index, ix) in tx.instructions.iter().enumerate() {
+            self.push_instruction(tx, block_time, None, index, ix)?;
+        }
+        for inner in &tx.inner_instructions {
+            for (index, ix) in inner.instructions.iter().enumerate() {
+                self.push_instruction(tx, block_time, Some(inner.index), index, ix)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn batch_full(&self) -> bool {
+        self.transaction_rows >= self.batch_size || self.instruction_rows >= self.batch_size
+    }
+
+    fn flush_due(&self) -> bool {
+        self.last_flush.elapsed() >= self.flush_interval
+    }
+
+    fn push_instruction(
+        &mut self,
+        tx: &ParsedTransaction,
+        block_time: Option<i64>,
+        parent_index: Option<usize>,
+        index: usize,
+        ix: &ParsedInstruction,
+    ) -> Result<(), Box<dyn Error>> {
+        let row = InstructionRow {
+            signature: &tx.signature,
+            block_time,
+            parent_index,
+            index,
+            program_id: &ix.program_id,
+            accounts: ix.resolved_accounts(tx).map(|a| a.map(|key| key.to_string()).unwrap_or_default()).collect(),
+            data: &ix.data,
+        };
+        push_row(&mut self.instructions, &row)?;
+        self.instruction_rows += 1;
+        Ok(())
+    }
+
+    fn insert(&self, table: &str, rows: &str) -> Result<(), Box<dyn Error>> {
+        let query = format!("INSERT INTO {}.{} FORMAT JSONEachRow", self.database, table);
+        self.execute(&query, rows.as_bytes())
+    }
+
+    // The statement goes in the query string; the body carries the data.
+    fn execute(&self, query: &str, body: &[u8]) -> Result<(), Box<dyn Error>> {
+        let url = format!("{}/?query={}", self.url, percent_encode(query));
+        let response = http::post(&url, "application/x-ndjson", body)?;
+        if !response.is_success() {
+            return Err(format!("ClickHouse returned HTTP {}: {}", response.status, response.body.trim()).into());
+        }
+        Ok(())
+    }
+}
+
+impl Sink for ClickHouseSink {
+    fn name(&self) -> &str {
+        "clickhouse"
+    }
+
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        ClickHouseSink::write_block(self, block)
+    }
+
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        ClickHouseSink::write_transactions(self, None, txs)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        ClickHouseSink::flush(self)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        ClickHouseSink::finish(*self)
+    }
+}
+
+fn push_row<T: Serialize>(buffer: &mut String, row: &T) -> Result<(), Box<dyn Error>> {
+    buffer.push_str(&serde_json::to_string(row)?);
+    buffer.push('\n');
+    Ok(())
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use serde_json::Value;
+
+    fn rows(buffer: &str) -> Vec<Value> {
+        buffer.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    #[test]
+    fn buffers_json_each_row_batches() {
+        let block = fixtures::block();
+        let mut sink = ClickHouseSink::new("http://localhost:8123/").with_flush_interval(Duration::from_secs(3600));
+        for tx in &block.transactions {
+            sink.push_transaction(Some(block), tx).unwrap();
+        }
+
+        let transactions = rows(&sink.transactions);
+        assert_eq!((transactions.len(), sink.transaction_rows), (block.transactions.len(), block.transactions.len()));
+        assert_eq!(transactions[0]["signature"], block.transactions[0].signature.as_str());
+        assert_eq!(transactions[0]["blockhash"], block.blockhash.as_str());
+        assert_eq!(transactions[0]["slot"], block.slot);
+
+        // Instruction rows follow each transaction's instructions, then its
+        // inner instructions under their parent's index.
+        let mut expected = Vec::new();
+        for tx in &block.transactions {
+            for (index, ix) in tx.instructions.iter().enumerate() {
+                expected.push((tx, None, index, ix));
+            }
+            for inner in &tx.inner_instructions {
+                for (index, ix) in inner.instructions.iter().enumerate() {
+                    expected.push((tx, Some(inner.index), index, ix));
+                }
+            }
+        }
+        let instructions = rows(&sink.instructions);
+        assert_eq!((instructions.len(), sink.instruction_rows), (expected.len(), expected.len()));
+        assert!(expected.iter().any(|(_, parent_index, _, _)| parent_index.is_some()));
+        for (row, (tx, parent_index, index, ix)) in instructions.iter().zip(&expected) {
+            assert_eq!(row["signature"], tx.signature.as_str());
+            assert_eq!(row["parent_index"], serde_json::json!(parent_index));
+            assert_eq!(row["index"], *index);
+            assert_eq!(row["program_id"], ix.program_id.to_string());
+            let accounts: Vec<String> = ix.resolved_accounts(tx).map(|a| a.unwrap().to_string()).collect();
+            assert_eq!(row["accounts"], serde_json::json!(accounts));
+            assert_eq!(row["data"], ix.data.as_str());
+        }
+
+        // Either buffer reaching the batch size triggers a flush.
+        assert!(!sink.batch_full());
+        sink.batch_size = block.transactions.len();
+        assert!(sink.batch_full());
+        sink.batch_size = block.transactions.len() + 1;
+        assert_eq!(sink.batch_full(), expected.len() > block.transactions.len());
+        assert!(!sink.flush_due());
+        sink.flush_interval = Duration::ZERO;
+        assert!(sink.flush_due());
+
+        assert_eq!(
+            percent_encode("INSERT INTO default.transactions FORMAT JSONEachRow"),
+            "INSERT%20INTO%20default.transactions%20FORMAT%20JSONEachRow"
+        );
+        assert_eq!(percent_encode("a-b_c.d~e\n(x)=1&y/é"), "a-b_c.d~e%0A%28x%29%3D1%26y%2F%C3%A9");
+    }
+}
@@ -0,0 +1,365 @@
+use super::Sink;
+use crate::reorg::ChainEvent;
+use crate::http;
+use crate::pubkey::Pubkey;
+use crate::types::{ParsedBlock, ParsedTransaction};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+// ==========================================
+// KAFKA SINK
+// ==========================================
+// Publishes through a Kafka REST Proxy (Confluent API v2) rather than the
+// native protocol, so no librdkafka is needed. Every transaction becomes one
+// JSON message keyed by its signature; block headers optionally go to a
+// second topic keyed by slot, along with chain events (finalized and
+// retracted blocks) for those headers. Messages are buffered and produced once
+// `batch_size` are waiting or `flush_interval` has passed since the last
+// flush.
+
+pub const DEFAULT_KAFKA_URL: &str = "http://localhost:8082";
+pub const DEFAULT_TRANSACTION_TOPIC: &str = "solana.transactions";
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+const CONTENT_TYPE: &str = "application/vnd.kafka.json.v2+json";
+
+#[derive(Serialize)]
+struct Record<'a, T: Serialize> {
+    key: &'a str,
+    value: T,
+}
+
+// The transaction carries its own slot.
+#[derive(Serialize)]
+struct TransactionMessage<'a> {
+    block_time: Option<i64>,
+    blockhash: Option<&'a str>,
+    #[serde(flatten)]
+    transaction: &'a ParsedTransaction,
+}
+
+#[derive(Serialize)]
+struct BlockHeaderMessage<'a> {
+    slot: u64,
+    epoch: u64,
+    block_height: Option<u64>,
+    block_time: Option<i64>,
+    blockhash: &'a str,
+    parent_slot: u64,
+    previous_blockhash: &'a str,
+    leader: Option<Pubkey>,
+    transaction_count: usize,
+}
+
+// The proxy answers 200 even when individual records fail, reporting them
+// per offset.
+#[derive(Deserialize)]
+struct ProduceResponse {
+    #[serde(default)]
+    offsets: Vec<ProduceOffset>,
+}
+
+#[derive(Deserialize)]
+struct ProduceOffset {
+    error_code: Option<i64>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Batch {
+    Blocks,
+    Transactions,
+}
+
+#[derive(Debug)]
+pub struct KafkaSink {
+    url: String,
+    transaction_topic: String,
+    block_topic: Option<String>,
+    batch_size: usize,
+    flush_interval: Duration,
+    // Comma-separated JSON records, ready to drop into {"records":[...]}.
+    transactions: String,
+    transaction_count: usize,
+    blocks: String,
+    block_count: usize,
+    last_flush: Instant,
+}
+
+impl KafkaSink {
+    pub fn new(url: &str) -> Self {
+        KafkaSink {
+            url: url.trim_end_matches('/').to_string(),
+            transaction_topic: DEFAULT_TRANSACTION_TOPIC.to_string(),
+            block_topic: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            transactions: String::new(),
+            transaction_count: 0,
+            blocks: String::new(),
+            block_count: 0,
+            last_flush: Instant::now(),
+        }
+    }
+
+    pub fn with_topic(mut self, topic: &str) -> Self {
+        self.transaction_topic = topic.to_string();
+        self
+    }
+
+    // Block headers are only published when a topic is set.
+    pub fn with_block_topic(mut self, topic: &str) -> Self {
+        self.block_topic = Some(topic.to_string());
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    pub fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        if self.block_topic.is_some() {
+            let header = BlockHeaderMessage {
+                slot: block.slot,
+                epoch: block.epoch,
+                block_height: block.block_height,
+                block_time: block.block_time,
+                blockhash: &block.blockhash,
+                parent_slot: block.parent_slot,
+                previous_blockhash: &block.previous_blockhash,
+                leader: block.leader,
+                transaction_count: block.transactions.len(),
+            };
+            push_record(&mut self.blocks, &block.slot.to_string(), &header)?;
+            self.block_count += 1;
+        }
+        if self.block_count >= self.batch_size {
+            self.flush()?;
+        }
+        self.write_transactions(Some(block), &block.transactions)
+    }
+
+    // Ignored without a block topic.
+    pub fn write_chain_event(&mut self, event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        if self.block_topic.is_none() {
+            return Ok(());
+        }
+        push_record(&mut self.blocks, &event.slot().to_string(), event)?;
+        self.block_count += 1;
+        if self.block_count >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn write_transactions(&mut self, block: Option<&ParsedBlock>, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        for tx in txs {
+            let message = TransactionMessage {
+                block_time: block.and_then(|b| b.block_time),
+                blockhash: block.map(|b| b.blockhash.as_str()),
+                transaction: tx,
+            };
+            push_record(&mut self.transactions, &tx.signature, &message)?;
+            self.transaction_count += 1;
+
+            if self.transaction_count >= self.batch_size {
+                self.flush()?;
+            }
+        }
+
+        if self.last_flush.elapsed() >= self.flush_interval {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    // A batch is only dropped once produced, so a failed flush leaves it
+    // for the next one.
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        let pending: Vec<(Batch, String, String)> =
+            self.pending().into_iter().map(|(batch, topic, body)| (batch, topic.to_string(), body)).collect();
+        for (batch, topic, body) in pending {
+            self.produce(&topic, &body)?;
+            match batch {
+                Batch::Blocks => {
+                    self.blocks.clear();
+                    self.block_count = 0;
+                }
+                Batch::Transactions => {
+                    self.transactions.clear();
+                    self.transaction_count = 0;
+                }
+            }
+        }
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    // The produce requests a flush sends, with their topics. Block headers
+    // go out first so consumers never see a block's transactions before
+    // the block itself.
+    fn pending(&self) -> Vec<(Batch, &str, String)> {
+        let mut pending = Vec::new();
+        if self.block_count > 0 && let Some(topic) = &self.block_topic {
+            pending.push((Batch::Blocks, topic.as_str(), records_body(&self.blocks)));
+        }
+        if self.transaction_count > 0 {
+            pending.push((Batch::Transactions, self.transaction_topic.as_str(), records_body(&self.transactions)));
+        }
+        pending
+    }
+
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.flush()
+    }
+
+    fn produce(&self, topic: &str, body: &str) -> Result<(), Box<dyn Error>> {
+        let url = format!("{}/topics/{}", self.url, topic);
+        let response = http::post(&url, CONTENT_TYPE, body.as_bytes())?;
+        if !response.is_success() {
+            return Err(format!("Kafka REST proxy returned HTTP {}: {}", response.status, response.body.trim()).into());
+        }
+
+        let produced: ProduceResponse = serde_json::from_str(&response.body)?;
+        let failed: Vec<&ProduceOffset> = produced.offsets.iter().filter(|o| o.error_code.is_some()).collect();
+        if let Some(first) = failed.first() {
+            return Err(format!(
+                "Kafka rejected {} record(s) for topic {}: {}",
+                failed.len(),
+                topic,
+                first.error.as_deref().unwrap_or("unknown error")
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl Sink for KafkaSink {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        KafkaSink::write_block(self, block)
+    }
+
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        KafkaSink::write_transactions(self, None, txs)
+    }
+
+    fn write_chain_event(&mut self, event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        KafkaSink::write_chain_event(self, event)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        KafkaSink::flush(self)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        KafkaSink::finish(*self)
+    }
+}
+
+fn records_body(records: &str) -> String {
+    format!("{{\"records\":[{}]}}", records)
+}
+
+fn push_record<T: Serialize>(buffer: &mut String, key: &str, value: &T) -> Result<(), Box<dyn Error>> {
+    if !buffer.is_empty() {
+        buffer.push(',');
+    }
+    buffer.push_str(&serde_json::to_string(&Record { key, value })?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use serde_json::Value;
+
+    // Deserializing into a struct rejects a repeated field, unlike Value.
+    #[derive(Deserialize)]
+    struct ProducedTransaction {
+        slot: u64,
+        blockhash: Option<String>,
+        signature: String,
+    }
+
+    #[derive(Deserialize)]
+    struct Produced<T> {
+        records: Vec<ProducedRecord<T>>,
+    }
+
+    #[derive(Deserialize)]
+    struct ProducedRecord<T> {
+        key: String,
+        value: T,
+    }
+
+    #[test]
+    fn transaction_records_carry_one_slot() {
+        let block = fixtures::block();
+        let mut sink = KafkaSink::new(DEFAULT_KAFKA_URL).with_batch_size(usize::MAX).with_flush_interval(Duration::from_secs(3600));
+        sink.write_block(block).unwrap();
+        sink.write_transactions(None, &block.transactions[..1]).unwrap();
+
+        let pending = sink.pending();
+        assert_eq!(pending.len(), 1);
+        let (batch, topic, body) = &pending[0];
+        assert_eq!((*batch, *topic), (Batch::Transactions, DEFAULT_TRANSACTION_TOPIC));
+        let produced: Produced<ProducedTransaction> = serde_json::from_str(body).unwrap();
+        assert_eq!(produced.records.len(), block.transactions.len() + 1);
+        for (record, tx) in produced.records.iter().zip(block.transactions.iter().chain(&block.transactions[..1])) {
+            assert_eq!((record.key.as_str(), record.value.signature.as_str()), (tx.signature.as_str(), tx.signature.as_str()));
+            assert_eq!(record.value.slot, block.slot);
+        }
+        assert_eq!(produced.records[0].value.blockhash.as_deref(), Some(block.blockhash.as_str()));
+        // Without a block the slot still comes from the transaction.
+        assert_eq!(produced.records.last().unwrap().value.blockhash, None);
+    }
+
+    #[test]
+    fn block_headers_are_produced_before_transactions() {
+        let block = fixtures::block();
+        let retracted = ChainEvent::Retracted { slot: block.slot, blockhash: block.blockhash.clone() };
+        let sink = || KafkaSink::new(DEFAULT_KAFKA_URL).with_batch_size(usize::MAX).with_flush_interval(Duration::from_secs(3600));
+
+        let mut with_blocks = sink().with_block_topic("solana.blocks");
+        with_blocks.write_block(block).unwrap();
+        with_blocks.write_chain_event(&retracted).unwrap();
+        let pending = with_blocks.pending();
+        let order: Vec<(Batch, &str)> = pending.iter().map(|(batch, topic, _)| (*batch, *topic)).collect();
+        assert_eq!(order, [(Batch::Blocks, "solana.blocks"), (Batch::Transactions, DEFAULT_TRANSACTION_TOPIC)]);
+        let blocks: Produced<Value> = serde_json::from_str(&pending[0].2).unwrap();
+        assert_eq!(blocks.records.len(), 2);
+        let header = &blocks.records[0];
+        assert_eq!(header.key, block.slot.to_string());
+        assert_eq!(header.value["slot"], block.slot);
+        assert_eq!(header.value["blockhash"], block.blockhash.as_str());
+        assert_eq!(header.value["transaction_count"], block.transactions.len());
+        let event: ChainEvent = serde_json::from_value(blocks.records[1].value.clone()).unwrap();
+        assert_eq!(event, retracted);
+        let transactions: Produced<Value> = serde_json::from_str(&pending[1].2).unwrap();
+        assert_eq!(transactions.records.len(), block.transactions.len());
+
+        // Without a block topic headers and chain events are dropped.
+        let mut without_blocks = sink();
+        without_blocks.write_block(block).unwrap();
+        without_blocks.write_chain_event(&retracted).unwrap();
+        let pending = without_blocks.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0, Batch::Transactions);
+        let transactions: Produced<Value> = serde_json::from_str(&pending[0].2).unwrap();
+        assert_eq!(transactions.records.len(), block.transactions.len());
+    }
+}
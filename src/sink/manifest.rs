@@ -0,0 +1,190 @@
+use super::Sink;
+use crate::reorg::ChainEvent;
+use crate::types::{ParsedBlock, ParsedTransaction};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+// ==========================================
+// WRITE MANIFEST
+// ==========================================
+// The databases upsert, so writing a block twice leaves one copy; an
+// appended file can't take anything back. `Idempotent` wraps such a sink
+// with a manifest of the blocks it has written, one "<slot> <blockhash>"
+// line each, and passes a block on only if it isn't listed. A block is
+// listed once the inner sink has flushed it, so a crash in between can
+// still repeat that one block on retry, but never lose it.
+//
+// Blocks are keyed by blockhash: the same slot with another hash (a fork)
+// is a different block. Transactions without a block and chain events
+// always pass through.
+
+#[derive(Debug)]
+pub struct Manifest {
+    path: PathBuf,
+    file: File,
+    blockhashes: HashSet<String>,
+}
+
+impl Manifest {
+    // Loads the blocks already listed in `path`, creating it if needed.
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("failed to read manifest {}: {}", path.display(), e).into()),
+        };
+        let mut blockhashes = HashSet::new();
+        for (number, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [slot, blockhash] if slot.parse::<u64>().is_ok() => {
+                    blockhashes.insert(blockhash.to_string());
+                }
+                _ => return Err(format!("invalid manifest {} line {}: {:?}", path.display(), number + 1, line).into()),
+            }
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("failed to open manifest {}: {}", path.display(), e))?;
+        Ok(Manifest { path: path.to_path_buf(), file, blockhashes })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.blockhashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blockhashes.is_empty()
+    }
+
+    pub fn contains(&self, block: &ParsedBlock) -> bool {
+        self.blockhashes.contains(&block.blockhash)
+    }
+
+    pub fn record(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        if self.blockhashes.insert(block.blockhash.clone()) {
+            writeln!(self.file, "{} {}", block.slot, block.blockhash)
+                .map_err(|e| format!("failed to write manifest {}: {}", self.path.display(), e))?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Idempotent {
+    inner: Box<dyn Sink>,
+    manifest: Manifest,
+    skipped: usize,
+}
+
+impl Idempotent {
+    pub fn new(inner: Box<dyn Sink>, manifest: Manifest) -> Self {
+        Idempotent { inner, manifest, skipped: 0 }
+    }
+
+    // Blocks passed over because the manifest already listed them.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+}
+
+impl Sink for Idempotent {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        if self.manifest.contains(block) {
+            log::debug!(slot = block.slot, blockhash = block.blockhash.as_str(); "block already in manifest, skipping");
+            self.skipped += 1;
+            return Ok(());
+        }
+        self.inner.write_block(block)?;
+        self.inner.flush()?;
+        self.manifest.record(block)
+    }
+
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        self.inner.write_transactions(txs)
+    }
+
+    fn write_chain_event(&mut self, event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        self.inner.write_chain_event(event)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.inner.flush()
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        if self.skipped > 0 {
+            log::info!(blocks = self.skipped, manifest = self.manifest.path().display().to_string().as_str(); "skipped blocks already written");
+        }
+        self.inner.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // Logs the slots written into a list shared with the test.
+    struct Counter {
+        blocks: Rc<RefCell<Vec<u64>>>,
+    }
+
+    impl Sink for Counter {
+        fn name(&self) -> &str {
+            "counter"
+        }
+
+        fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+            self.blocks.borrow_mut().push(block.slot);
+            Ok(())
+        }
+
+        fn write_transactions(&mut self, _txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn blocks_in_the_manifest_are_written_once() {
+        let block = fixtures::block();
+        let mut fork = block.clone();
+        fork.blockhash = "fork".to_string();
+        let path = std::env::temp_dir().join(format!("phase1-manifest-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let blocks = Rc::new(RefCell::new(Vec::new()));
+        let counter = || Box::new(Counter { blocks: blocks.clone() });
+
+        let mut sink = Idempotent::new(counter(), Manifest::open(&path).unwrap());
+        sink.write_block(block).unwrap();
+        sink.write_block(block).unwrap();
+        assert_eq!((blocks.borrow().len(), sink.skipped()), (1, 1));
+
+        // A retried run picks up the manifest; the same slot on a fork is
+        // still written.
+        let mut sink = Idempotent::new(counter(), Manifest::open(&path).unwrap());
+        assert_eq!(sink.manifest.len(), 1);
+        sink.write_block(block).unwrap();
+        sink.write_block(&fork).unwrap();
+        assert_eq!(*blocks.borrow(), [block.slot, block.slot]);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        fs::write(&path, "not a slot\n").unwrap();
+        assert!(Manifest::open(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
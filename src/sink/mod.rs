@@ -0,0 +1,299 @@
+pub mod clickhouse;
+pub mod kafka;
+pub mod manifest;
+pub mod postgres;
+pub mod sqlite;
+pub mod webhook;
+
+pub use clickhouse::ClickHouseSink;
+pub use kafka::KafkaSink;
+pub use manifest::{Idempotent, Manifest};
+pub use postgres::PostgresSink;
+pub use sqlite::SqliteSink;
+pub use webhook::WebhookSink;
+
+use crate::metrics::metrics;
+use crate::pubkey::Pubkey;
+use crate::reorg::ChainEvent;
+use crate::timestamps::{stamp_transactions, TimestampMode};
+use crate::transfers::{extract_sol_transfers, extract_token_transfers, SolTransferKind};
+use crate::types::{ParsedBlock, ParsedTransaction};
+use std::error::Error;
+use std::time::Instant;
+
+// ==========================================
+// SINK TRAIT
+// ==========================================
+// Anywhere parsed output can go: the export formats, the databases and
+// Kafka all implement `Sink`, so a new destination is one more impl rather
+// than a change to every command. `FanOut` sends one parse run to several
+// sinks at once.
+//
+// Writes should be idempotent, since a retried backfill or a resumed stream
+// repeats blocks: the databases upsert on signature and blockhash, and
+// `Idempotent` skips blocks an appending file sink already has.
+
+pub trait Sink {
+    // Short name labelling the sink's write latency in metrics.
+    fn name(&self) -> &str;
+
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>>;
+
+    // Transactions parsed outside a block (parse-tx, log subscriptions).
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>>;
+
+    // A block written earlier was finalized or orphaned (see reorg). Sinks
+    // with nothing to update ignore it.
+    fn write_chain_event(&mut self, _event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    // Pushes out anything held back for batching.
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    // Called once, after the last write.
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+// Writes go to every sink in the order added, timed per sink. A failing
+// write stops there; `finish` still finishes every sink and returns the
+// first error.
+//
+// Each sink can take its own TimestampMode. A block is stamped once per mode
+// in use and every sink with that mode gets the same copy; sinks without
+// one get the block as it came. Transactions written without a block have
+// no blockTime to go by and pass through unchanged.
+#[derive(Default)]
+pub struct FanOut {
+    sinks: Vec<(Box<dyn Sink>, TimestampMode)>,
+}
+
+impl FanOut {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_sink(mut self, sink: impl Sink + 'static) -> Self {
+        self.push(Box::new(sink));
+        self
+    }
+
+    pub fn push(&mut self, sink: Box<dyn Sink>) {
+        self.push_timestamped(sink, TimestampMode::None);
+    }
+
+    pub fn push_timestamped(&mut self, sink: Box<dyn Sink>, mode: TimestampMode) {
+        self.sinks.push((sink, mode));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    fn each(&mut self, mut write: impl FnMut(&mut dyn Sink, TimestampMode) -> Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+        for (sink, mode) in &mut self.sinks {
+            let started = Instant::now();
+            let result = write(sink.as_mut(), *mode);
+            metrics().record_sink_write(sink.name(), started.elapsed(), result.is_ok());
+            result?;
+        }
+        Ok(())
+    }
+}
+
+impl Sink for FanOut {
+    fn name(&self) -> &str {
+        "fan-out"
+    }
+
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        metrics().record_written_block(block.slot, block.block_time);
+        let mut stamped: Vec<(TimestampMode, ParsedBlock)> = Vec::new();
+        if block.block_time.is_some() {
+            for &(_, mode) in &self.sinks {
+                if mode != TimestampMode::None && !stamped.iter().any(|(stamped_mode, _)| *stamped_mode == mode) {
+                    let mut copy = block.clone();
+                    stamp_transactions(&mut copy, mode);
+                    stamped.push((mode, copy));
+                }
+            }
+        }
+        self.each(|sink, mode| {
+            let block = stamped.iter().find(|(stamped_mode, _)| *stamped_mode == mode).map_or(block, |(_, copy)| copy);
+            sink.write_block(block)
+        })
+    }
+
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        self.each(|sink, _| sink.write_transactions(txs))
+    }
+
+    fn write_chain_event(&mut self, event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        self.each(|sink, _| sink.write_chain_event(event))
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.each(|sink, _| sink.flush())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        let mut first_error = None;
+        for (sink, _) in self.sinks {
+            if let Err(e) = sink.finish() {
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+// ==========================================
+// TRANSFER ROWS
+// ==========================================
+// What the database sinks store in `transfers`: a transaction's SOL and
+// token transfers in one list, in execution order. `kind` is the
+// SolTransferKind ("transfer", "create_account", "wrapped_sol",
+// "close_account") or "token"; a wrapped SOL transfer shows up twice, as the
+// lamports moved and as the token transfer. SOL rows have no mint or owners.
+
+pub(crate) struct TransferRow {
+    pub kind: &'static str,
+    pub source: Pubkey,
+    pub destination: Pubkey,
+    pub source_owner: Option<Pubkey>,
+    pub destination_owner: Option<Pubkey>,
+    pub mint: Option<Pubkey>,
+    pub amount: u64,
+    pub instruction_index: usize,
+    pub inner_index: Option<usize>,
+}
+
+pub(crate) fn transfer_rows(tx: &ParsedTransaction) -> Vec<TransferRow> {
+    let sol = extract_sol_transfers(tx).transfers.into_iter().map(|transfer| TransferRow {
+        kind: match transfer.kind {
+            SolTransferKind::Transfer => "transfer",
+            SolTransferKind::CreateAccount => "create_account",
+            SolTransferKind::WrappedSol => "wrapped_sol",
+            SolTransferKind::CloseAccount => "close_account",
+        },
+        source: transfer.from,
+        destination: transfer.to,
+        source_owner: None,
+        destination_owner: None,
+        mint: None,
+        amount: transfer.lamports,
+        instruction_index: transfer.instruction_index,
+        inner_index: transfer.inner_index,
+    });
+    let tokens = extract_token_transfers(tx).into_iter().map(|transfer| TransferRow {
+        kind: "token",
+        source: transfer.source,
+        destination: transfer.destination,
+        source_owner: transfer.source_owner,
+        destination_owner: transfer.destination_owner,
+        mint: transfer.mint,
+        amount: transfer.amount,
+        instruction_index: transfer.instruction_index,
+        inner_index: transfer.inner_index,
+    });
+    let mut rows: Vec<TransferRow> = sol.chain(tokens).collect();
+    // Stable, so a wrapped SOL transfer keeps its lamports row first.
+    rows.sort_by_key(|row| (row.instruction_index, row.inner_index.map_or(0, |index| index + 1)));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // Records calls into a log shared with the test; fails on finish when
+    // `broken`.
+    struct Recorder {
+        name: &'static str,
+        log: Rc<RefCell<Vec<String>>>,
+        broken: bool,
+    }
+
+    impl Sink for Recorder {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+            self.log.borrow_mut().push(format!("{} block {}", self.name, block.transactions.len()));
+            Ok(())
+        }
+
+        fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+            self.log.borrow_mut().push(format!("{} txs {}", self.name, txs.len()));
+            Ok(())
+        }
+
+        fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+            self.log.borrow_mut().push(format!("{} finish", self.name));
+            if self.broken {
+                return Err(format!("{} failed", self.name).into());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn fan_out_writes_to_every_sink_in_order() {
+        let block = fixtures::block();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let recorder = |name, broken| Recorder { name, log: log.clone(), broken };
+
+        let mut fan_out = FanOut::new().with_sink(recorder("a", true)).with_sink(recorder("b", true));
+        fan_out.write_block(block).unwrap();
+        fan_out.write_transactions(&block.transactions[..2]).unwrap();
+        // Both sinks are finished; the first error comes back.
+        assert_eq!(Box::new(fan_out).finish().unwrap_err().to_string(), "a failed");
+        assert_eq!(
+            *log.borrow(),
+            ["a block 1127", "b block 1127", "a txs 2", "b txs 2", "a finish", "b finish"]
+        );
+    }
+    // Records the timestamp_ms of the last transaction of each block.
+    struct LastTimestamp(Rc<RefCell<Vec<Option<i64>>>>);
+
+    impl Sink for LastTimestamp {
+        fn name(&self) -> &str {
+            "last-timestamp"
+        }
+
+        fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+            self.0.borrow_mut().push(block.transactions.last().unwrap().timestamp_ms);
+            Ok(())
+        }
+
+        fn write_transactions(&mut self, _txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn fan_out_stamps_each_sink_with_its_own_mode() {
+        let block = fixtures::block();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut fan_out = FanOut::new();
+        for mode in [TimestampMode::Interpolated, TimestampMode::None, TimestampMode::BlockTime, TimestampMode::Interpolated] {
+            fan_out.push_timestamped(Box::new(LastTimestamp(seen.clone())), mode);
+        }
+        fan_out.write_block(block).unwrap();
+
+        let mut interpolated = block.clone();
+        stamp_transactions(&mut interpolated, TimestampMode::Interpolated);
+        let interpolated = interpolated.transactions.last().unwrap().timestamp_ms;
+        let block_time = block.block_time.map(|time| time * 1000);
+        assert_ne!(interpolated, block_time);
+        assert_eq!(*seen.borrow(), [interpolated, None, block_time, interpolated]);
+    }
+}
@@ -0,0 +1,480 @@
+use super::{transfer_rows, Sink};
+use crate::reorg::ChainEvent;
+use crate::shutdown;
+use crate::types::{ParsedBlock, ParsedInstruction, ParsedTransaction};
+use std::error::Error;
+use std::io::{BufWriter, Write};
+use std::process::{Child, Command, Stdio};
+
+// ==========================================
+// POSTGRES SINK
+// ==========================================
+// Streams SQL into `psql` (or any writer, e.g. a .sql file): the embedded
+// migrations first, then one transaction per block with batched
+// multi-row INSERTs. Rows are upserted on their keys (blockhash,
+// signature, instruction position), so re-running a slot range leaves one,
+// current copy of each. Going through psql keeps the crate free of a database
+// driver and async runtime. transaction_accounts and transfers hold the same
+// rows as in the SQLite sink. A retracted block is deleted along with
+// everything hanging off it; finalized blocks are listed in
+// finalized_blocks.
+
+// Applied in order; each runs once per database, tracked in schema_migrations.
+const MIGRATIONS: &[(i32, &str)] = &[
+    (1, "
+CREATE TABLE blocks (
+    blockhash           TEXT PRIMARY KEY,
+    block_height        BIGINT NOT NULL,
+    block_time          BIGINT NOT NULL,
+    parent_slot         BIGINT NOT NULL,
+    previous_blockhash  TEXT NOT NULL
+);
+CREATE TABLE transactions (
+    signature                  TEXT PRIMARY KEY,
+    blockhash                  TEXT REFERENCES blocks (blockhash),
+    fee_payer                  TEXT NOT NULL,
+    is_success                 BOOLEAN NOT NULL,
+    fee                        BIGINT NOT NULL,
+    base_fee                   BIGINT NOT NULL,
+    priority_fee               BIGINT NOT NULL,
+    compute_units_consumed     BIGINT,
+    compute_unit_limit         BIGINT,
+    unit_price_micro_lamports  BIGINT
+);
+CREATE TABLE instructions (
+    id            BIGSERIAL PRIMARY KEY,
+    signature     TEXT NOT NULL REFERENCES transactions (signature),
+    parent_index  INTEGER,
+    index         INTEGER NOT NULL,
+    program_id    TEXT NOT NULL,
+    accounts      TEXT[] NOT NULL,
+    data          TEXT NOT NULL
+);
+CREATE UNIQUE INDEX instructions_position_idx ON instructions (signature, COALESCE(parent_index, -1), index);
+CREATE INDEX instructions_program_id_idx ON instructions (program_id);
+"),
+    // getBlock can return a null blockHeight or blockTime.
+    (2, "
+ALTER TABLE blocks ALTER COLUMN block_height DROP NOT NULL;
+ALTER TABLE blocks ALTER COLUMN block_time DROP NOT NULL;
+"),
+    (3, "
+CREATE TABLE finalized_blocks (
+    blockhash  TEXT PRIMARY KEY,
+    slot       BIGINT NOT NULL
+);
+"),
+    // NULL for blocks written before the column existed.
+    (4, "
+ALTER TABLE blocks ADD COLUMN slot BIGINT;
+CREATE INDEX blocks_slot_idx ON blocks (slot);
+"),
+    // Empty for transactions written before the tables existed.
+    (5, "
+CREATE TABLE transaction_accounts (
+    signature    TEXT NOT NULL REFERENCES transactions (signature),
+    position     INTEGER NOT NULL,
+    account      TEXT NOT NULL,
+    is_signer    BOOLEAN NOT NULL,
+    is_writable  BOOLEAN NOT NULL,
+    PRIMARY KEY (signature, position)
+);
+CREATE INDEX transaction_accounts_account_idx ON transaction_accounts (account);
+CREATE TABLE transfers (
+    signature          TEXT NOT NULL REFERENCES transactions (signature),
+    position           INTEGER NOT NULL,
+    kind               TEXT NOT NULL,
+    source             TEXT NOT NULL,
+    destination        TEXT NOT NULL,
+    source_owner       TEXT,
+    destination_owner  TEXT,
+    mint               TEXT,
+    amount             NUMERIC(20) NOT NULL,
+    instruction_index  INTEGER NOT NULL,
+    inner_index        INTEGER,
+    PRIMARY KEY (signature, position)
+);
+CREATE INDEX transfers_source_idx ON transfers (source);
+CREATE INDEX transfers_destination_idx ON transfers (destination);
+CREATE INDEX transfers_mint_idx ON transfers (mint);
+"),
+    // NULL for transactions written before the columns existed.
+    (6, "
+ALTER TABLE transactions ADD COLUMN slot BIGINT, ADD COLUMN index_in_block INTEGER;
+"),
+    // Only filled in with --timestamps.
+    (7, "
+ALTER TABLE transactions ADD COLUMN timestamp_ms BIGINT;
+"),
+];
+
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+pub struct PostgresSink {
+    out: BufWriter<Box<dyn Write + Send>>,
+    child: Option<Child>,
+    batch_size: usize,
+}
+
+impl PostgresSink {
+    // Spawns `psql <database_url>` and applies pending migrations.
+    pub fn connect(database_url: &str) -> Result<Self, Box<dyn Error>> {
+        let mut child = shutdown::own_process_group(&mut Command::new("psql"))
+            .args(["-q", "-v", "ON_ERROR_STOP=1", database_url])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("failed to run psql: {}", e))?;
+        let stdin = child.stdin.take().ok_or("psql stdin unavailable")?;
+
+        let mut sink = PostgresSink::from_writer(Box::new(stdin))?;
+        sink.child = Some(child);
+        Ok(sink)
+    }
+
+    // Writes the same SQL script to an arbitrary writer instead of psql.
+    pub fn from_writer(out: Box<dyn Write + Send>) -> Result<Self, Box<dyn Error>> {
+        let mut sink = PostgresSink {
+            out: BufWriter::new(out),
+            child: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+        };
+        sink.write_migrations()?;
+        Ok(sink)
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        writeln!(self.out, "BEGIN;")?;
+        let row = format!(
+            "({}, {}, {}, {}, {}, {})",
+            text(&block.blockhash),
+            block.slot,
+            nullable(block.block_height),
+            nullable(block.block_time),
+            block.parent_slot,
+            text(&block.previous_blockhash),
+        );
+        self.upsert("blocks", "blockhash, slot, block_height, block_time, parent_slot, previous_blockhash", "blockhash", &[row])?;
+        self.write_transactions(Some(&block.blockhash), &block.transactions)?;
+        writeln!(self.out, "COMMIT;")?;
+        self.out.flush()?;
+        Ok(())
+    }
+
+    // Transactions outside a block context are stored with a NULL blockhash.
+    pub fn write_transactions(&mut self, blockhash: Option<&str>, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        let blockhash = blockhash.map(text).unwrap_or_else(|| "NULL".to_string());
+
+        for batch in txs.chunks(self.batch_size) {
+            let rows: Vec<String> = batch.iter().map(|tx| {
+                format!(
+                    "({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {})",
+                    text(&tx.signature),
+                    blockhash,
+                    text(&tx.fee_payer.to_string()),
+                    tx.is_success,
+                    tx.fee,
+                    tx.base_fee,
+                    tx.priority_fee,
+                    nullable(tx.compute_units_consumed),
+                    nullable(tx.compute_unit_limit),
+                    nullable(tx.unit_price_micro_lamports),
+                    nullable(tx.slot),
+                    nullable(tx.index_in_block),
+                    nullable(tx.timestamp_ms),
+                )
+            }).collect();
+            self.upsert(
+                "transactions",
+                "signature, blockhash, fee_payer, is_success, fee, base_fee, priority_fee, compute_units_consumed, compute_unit_limit, unit_price_micro_lamports, slot, index_in_block, timestamp_ms",
+                "signature",
+                &rows,
+            )?;
+        }
+
+        let rows: Vec<String> = txs.iter().flat_map(|tx| {
+            tx.accounts.iter().enumerate().map(move |(position, account)| {
+                format!(
+                    "({}, {}, {}, {}, {})",
+                    text(&tx.signature),
+                    position,
+                    text(&account.pubkey.to_string()),
+                    account.is_signer,
+                    account.is_writable,
+                )
+            })
+        }).collect();
+        for batch in rows.chunks(self.batch_size) {
+            self.upsert("transaction_accounts", "signature, position, account, is_signer, is_writable", "signature, position", batch)?;
+        }
+
+        let mut rows: Vec<String> = Vec::new();
+        for tx in txs {
+            for (index, ix) in tx.instructions.iter().enumerate() {
+                rows.push(instruction_row(tx, None, index, ix));
+            }
+            for inner in &tx.inner_instructions {
+                for (index, ix) in inner.instructions.iter().enumerate() {
+                    rows.push(instruction_row(tx, Some(inner.index), index, ix));
+                }
+            }
+        }
+        for batch in rows.chunks(self.batch_size) {
+            self.upsert(
+                "instructions",
+                "signature, parent_index, index, program_id, accounts, data",
+                "signature, COALESCE(parent_index, -1), index",
+                batch,
+            )?;
+        }
+
+        let rows: Vec<String> = txs.iter().flat_map(|tx| {
+            transfer_rows(tx).into_iter().enumerate().map(move |(position, row)| {
+                format!(
+                    "({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {})",
+                    text(&tx.signature),
+                    position,
+                    text(row.kind),
+                    text(&row.source.to_string()),
+                    text(&row.destination.to_string()),
+                    nullable_text(row.source_owner),
+                    nullable_text(row.destination_owner),
+                    nullable_text(row.mint),
+                    row.amount,
+                    row.instruction_index,
+                    nullable(row.inner_index),
+                )
+            })
+        }).collect();
+        for batch in rows.chunks(self.batch_size) {
+            self.upsert(
+                "transfers",
+                "signature, position, kind, source, destination, source_owner, destination_owner, mint, amount, instruction_index, inner_index",
+                "signature, position",
+                batch,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn write_chain_event(&mut self, event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        let blockhash = text(event.blockhash());
+        match event {
+            ChainEvent::Finalized { slot, .. } => {
+                writeln!(self.out, "INSERT INTO finalized_blocks (blockhash, slot) VALUES ({}, {}) ON CONFLICT DO NOTHING;", blockhash, slot)?;
+            }
+            ChainEvent::Retracted { .. } => {
+                let signatures = format!("(SELECT signature FROM transactions WHERE blockhash = {})", blockhash);
+                writeln!(self.out, "BEGIN;")?;
+                for table in ["instructions", "transaction_accounts", "transfers"] {
+                    writeln!(self.out, "DELETE FROM {} WHERE signature IN {};", table, signatures)?;
+                }
+                writeln!(self.out, "DELETE FROM transactions WHERE blockhash = {};", blockhash)?;
+                writeln!(self.out, "DELETE FROM blocks WHERE blockhash = {};", blockhash)?;
+                writeln!(self.out, "COMMIT;")?;
+            }
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+
+    // Flushes remaining SQL and, when connected through psql, waits for it
+    // to exit so failures surface here.
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        drop(self.out);
+        if let Some(mut child) = self.child.take() {
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(format!("psql exited with {}", status).into());
+            }
+        }
+        Ok(())
+    }
+
+    fn upsert(&mut self, table: &str, columns: &str, key: &str, rows: &[String]) -> Result<(), Box<dyn Error>> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        writeln!(self.out, "{}", upsert_sql(table, columns, key, rows))?;
+        Ok(())
+    }
+
+    fn write_migrations(&mut self) -> Result<(), Box<dyn Error>> {
+        writeln!(self.out, "SET client_min_messages = warning;")?;
+        writeln!(self.out, "CREATE TABLE IF NOT EXISTS schema_migrations (version INTEGER PRIMARY KEY);")?;
+        for (version, sql) in MIGRATIONS {
+            writeln!(
+                self.out,
+                "DO $migration$ BEGIN\nIF NOT EXISTS (SELECT 1 FROM schema_migrations WHERE version = {version}) THEN\n{sql}\nINSERT INTO schema_migrations (version) VALUES ({version});\nEND IF;\nEND $migration$;",
+            )?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl Sink for PostgresSink {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        PostgresSink::write_block(self, block)
+    }
+
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        PostgresSink::write_transactions(self, None, txs)
+    }
+
+    fn write_chain_event(&mut self, event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        PostgresSink::write_chain_event(self, event)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        PostgresSink::finish(*self)
+    }
+}
+
+fn instruction_row(tx: &ParsedTransaction, parent_index: Option<usize>, index: usize, ix: &ParsedInstruction) -> String {
+    let accounts: Vec<String> = ix.resolved_accounts(tx).map(|a| text(&a.map(|key| key.to_string()).unwrap_or_default())).collect();
+    format!(
+        "({}, {}, {}, {}, ARRAY[{}]::TEXT[], {})",
+        text(&tx.signature),
+        nullable(parent_index),
+        index,
+        text(&ix.program_id.to_string()),
+        accounts.join(", "),
+        text(&ix.data),
+    )
+}
+
+// Multi-row INSERT overwriting the row already stored under `key` (the
+// columns or expressions of a unique index). A NULL doesn't replace a value:
+// a transaction first seen in a log subscription picks up its blockhash when
+// the block is written, and keeps it if the subscription repeats it.
+fn upsert_sql(table: &str, columns: &str, key: &str, rows: &[String]) -> String {
+    let key_columns: Vec<&str> = key.split(|c: char| !c.is_alphanumeric() && c != '_').collect();
+    let updates: Vec<String> = columns
+        .split(", ")
+        .filter(|column| !key_columns.contains(column))
+        .map(|column| format!("{column} = COALESCE(EXCLUDED.{column}, {table}.{column})"))
+        .collect();
+    format!(
+        "INSERT INTO {} ({}) VALUES\n{}\nON CONFLICT ({}) DO UPDATE SET {};",
+        table,
+        columns,
+        rows.join(",\n"),
+        key,
+        updates.join(", ")
+    )
+}
+
+// SQL string literal; relies on standard_conforming_strings (the default).
+fn text(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn nullable<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_else(|| "NULL".to_string())
+}
+
+fn nullable_text<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| text(&v.to_string())).unwrap_or_else(|| "NULL".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use std::sync::{Arc, Mutex};
+
+    // Keeps the script readable after the sink takes the writer.
+    #[derive(Clone, Default)]
+    struct Script(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Script {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Script {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    #[test]
+    fn writes_migrations_upserts_and_retractions() {
+        let block = fixtures::block();
+        let script = Script::default();
+        let mut sink = PostgresSink::from_writer(Box::new(script.clone())).unwrap().with_batch_size(100);
+
+        // Every migration is guarded and recorded, in order.
+        let migrations = script.take();
+        assert!(migrations.starts_with("SET client_min_messages = warning;\nCREATE TABLE IF NOT EXISTS schema_migrations"));
+        let mut last = 0;
+        for (version, _) in MIGRATIONS {
+            let guard = format!("IF NOT EXISTS (SELECT 1 FROM schema_migrations WHERE version = {version}) THEN");
+            let at = migrations.find(&guard).unwrap_or_else(|| panic!("migration {version} not guarded"));
+            assert!(at > last);
+            last = at;
+            assert!(migrations.contains(&format!("INSERT INTO schema_migrations (version) VALUES ({version});")));
+        }
+        assert_eq!(migrations.matches("DO $migration$ BEGIN").count(), MIGRATIONS.len());
+        assert_eq!(migrations.matches("END $migration$;").count(), MIGRATIONS.len());
+
+        sink.write_block(block).unwrap();
+        let sql = script.take();
+        assert!(sql.starts_with("BEGIN;\nINSERT INTO blocks ("));
+        assert!(sql.ends_with("COMMIT;\n"));
+        let batches = block.transactions.len().div_ceil(100);
+        assert_eq!(sql.matches("INSERT INTO transactions (").count(), batches);
+        assert!(sql.contains(&format!("'{}'", block.transactions[0].signature)));
+        // A NULL never overwrites a stored value, and key columns aren't updated.
+        assert!(sql.contains("ON CONFLICT (signature) DO UPDATE SET blockhash = COALESCE(EXCLUDED.blockhash, transactions.blockhash), "));
+        assert!(!sql.contains("signature = COALESCE"));
+        assert!(sql.contains("ON CONFLICT (signature, COALESCE(parent_index, -1), index) DO UPDATE SET program_id = "));
+
+        assert_eq!(
+            upsert_sql("t", "a, b, c", "a, b", &["(1, NULL, 'it''s')".to_string()]),
+            "INSERT INTO t (a, b, c) VALUES\n(1, NULL, 'it''s')\nON CONFLICT (a, b) DO UPDATE SET c = COALESCE(EXCLUDED.c, t.c);"
+        );
+
+        sink.write_chain_event(&ChainEvent::Finalized { slot: block.slot, blockhash: block.blockhash.clone() }).unwrap();
+        let sql = script.take();
+        assert_eq!(
+            sql,
+            format!("INSERT INTO finalized_blocks (blockhash, slot) VALUES ('{}', {}) ON CONFLICT DO NOTHING;\n", block.blockhash, block.slot)
+        );
+
+        sink.write_chain_event(&ChainEvent::Retracted { slot: block.slot, blockhash: block.blockhash.clone() }).unwrap();
+        let sql = script.take();
+        let blockhash = text(&block.blockhash);
+        let lines: Vec<&str> = sql.lines().collect();
+        assert_eq!(lines.first(), Some(&"BEGIN;"));
+        assert_eq!(lines.last(), Some(&"COMMIT;"));
+        // Child rows go before the transactions and block they reference.
+        assert_eq!(
+            &lines[1..lines.len() - 1],
+            [
+                format!("DELETE FROM instructions WHERE signature IN (SELECT signature FROM transactions WHERE blockhash = {blockhash});"),
+                format!("DELETE FROM transaction_accounts WHERE signature IN (SELECT signature FROM transactions WHERE blockhash = {blockhash});"),
+                format!("DELETE FROM transfers WHERE signature IN (SELECT signature FROM transactions WHERE blockhash = {blockhash});"),
+                format!("DELETE FROM transactions WHERE blockhash = {blockhash};"),
+                format!("DELETE FROM blocks WHERE blockhash = {blockhash};"),
+            ]
+        );
+        sink.finish().unwrap();
+    }
+}
@@ -0,0 +1,484 @@
+use super::{transfer_rows, Sink};
+use crate::reorg::ChainEvent;
+use crate::shutdown;
+use crate::types::{ParsedBlock, ParsedInstruction, ParsedTransaction};
+use std::error::Error;
+use std::io::{BufWriter, Write};
+use std::process::{Child, Command, Stdio};
+
+// ==========================================
+// SQLITE SINK
+// ==========================================
+// A local database file to run SQL against without standing up a server.
+// Like the Postgres sink, this streams SQL into the `sqlite3` shell rather
+// than linking a driver: the schema first, then one transaction per block.
+// rusqlite would compile in libsqlite3 with a C toolchain; the shell only
+// has to be on PATH where the sink is used.
+// Rows are upserted on their keys, so re-running a slot range leaves one,
+// current copy of each.
+// Instruction accounts are stored as JSON arrays; transaction_accounts has
+// one row per account for "what touched this address" queries, and
+// transfers the SOL and token transfers (see TransferRow); amounts past
+// i64::MAX come back from SQLite as REAL. A retracted
+// block is deleted with everything hanging off it; finalized blocks are
+// listed in finalized_blocks.
+//
+// The schema is created with IF NOT EXISTS, which leaves the tables of an
+// older file as they were; columns added since are added on open (see
+// ADDED_COLUMNS).
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS blocks (
+    blockhash           TEXT PRIMARY KEY,
+    slot                INTEGER NOT NULL,
+    block_height        INTEGER,
+    block_time          INTEGER,
+    parent_slot         INTEGER NOT NULL,
+    previous_blockhash  TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS blocks_slot_idx ON blocks (slot);
+CREATE TABLE IF NOT EXISTS transactions (
+    signature                  TEXT PRIMARY KEY,
+    blockhash                  TEXT REFERENCES blocks (blockhash),
+    fee_payer                  TEXT NOT NULL,
+    is_success                 INTEGER NOT NULL,
+    failure_reason             TEXT,
+    fee                        INTEGER NOT NULL,
+    base_fee                   INTEGER NOT NULL,
+    priority_fee               INTEGER NOT NULL,
+    compute_units_consumed     INTEGER,
+    compute_unit_limit         INTEGER,
+    unit_price_micro_lamports  INTEGER,
+    slot                       INTEGER,
+    index_in_block             INTEGER,
+    timestamp_ms               INTEGER
+);
+CREATE INDEX IF NOT EXISTS transactions_blockhash_idx ON transactions (blockhash);
+CREATE TABLE IF NOT EXISTS transaction_accounts (
+    signature    TEXT NOT NULL REFERENCES transactions (signature),
+    position     INTEGER NOT NULL,
+    account      TEXT NOT NULL,
+    is_signer    INTEGER NOT NULL,
+    is_writable  INTEGER NOT NULL,
+    PRIMARY KEY (signature, position)
+);
+CREATE INDEX IF NOT EXISTS transaction_accounts_account_idx ON transaction_accounts (account);
+CREATE TABLE IF NOT EXISTS instructions (
+    signature     TEXT NOT NULL REFERENCES transactions (signature),
+    parent_index  INTEGER,
+    position      INTEGER NOT NULL,
+    program_id    TEXT NOT NULL,
+    accounts      TEXT NOT NULL,
+    data          TEXT NOT NULL
+);
+CREATE UNIQUE INDEX IF NOT EXISTS instructions_position_idx ON instructions (signature, COALESCE(parent_index, -1), position);
+CREATE INDEX IF NOT EXISTS instructions_program_id_idx ON instructions (program_id);
+CREATE TABLE IF NOT EXISTS rewards (
+    blockhash     TEXT NOT NULL REFERENCES blocks (blockhash),
+    pubkey        TEXT NOT NULL,
+    lamports      INTEGER NOT NULL,
+    post_balance  INTEGER NOT NULL,
+    reward_type   TEXT NOT NULL,
+    commission    INTEGER,
+    PRIMARY KEY (blockhash, pubkey, reward_type)
+);
+CREATE TABLE IF NOT EXISTS transfers (
+    signature          TEXT NOT NULL REFERENCES transactions (signature),
+    position           INTEGER NOT NULL,
+    kind               TEXT NOT NULL,
+    source             TEXT NOT NULL,
+    destination        TEXT NOT NULL,
+    source_owner       TEXT,
+    destination_owner  TEXT,
+    mint               TEXT,
+    amount             INTEGER NOT NULL,
+    instruction_index  INTEGER NOT NULL,
+    inner_index        INTEGER,
+    PRIMARY KEY (signature, position)
+);
+CREATE INDEX IF NOT EXISTS transfers_source_idx ON transfers (source);
+CREATE INDEX IF NOT EXISTS transfers_destination_idx ON transfers (destination);
+CREATE INDEX IF NOT EXISTS transfers_mint_idx ON transfers (mint);
+CREATE TABLE IF NOT EXISTS finalized_blocks (
+    blockhash  TEXT PRIMARY KEY,
+    slot       INTEGER NOT NULL
+);
+";
+
+// (table, column, type) for columns added to SCHEMA after the table.
+type AddedColumn = (&'static str, &'static str, &'static str);
+
+const ADDED_COLUMNS: &[AddedColumn] = &[
+    ("transactions", "slot", "INTEGER"),
+    ("transactions", "index_in_block", "INTEGER"),
+    ("transactions", "timestamp_ms", "INTEGER"),
+];
+
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+pub struct SqliteSink {
+    out: BufWriter<Box<dyn Write + Send>>,
+    child: Option<Child>,
+    batch_size: usize,
+}
+
+impl SqliteSink {
+    // Spawns `sqlite3 <path>`, creating the database file if needed.
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        let missing = missing_columns(path)?;
+        let mut child = shutdown::own_process_group(&mut Command::new("sqlite3"))
+            .args(["-bail", path])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("failed to run sqlite3: {}", e))?;
+        let stdin = child.stdin.take().ok_or("sqlite3 stdin unavailable")?;
+
+        let mut sink = SqliteSink::from_writer(Box::new(stdin))?;
+        sink.child = Some(child);
+        for (table, column, column_type) in missing {
+            writeln!(sink.out, "ALTER TABLE {} ADD COLUMN {} {};", table, column, column_type)?;
+        }
+        sink.out.flush()?;
+        Ok(sink)
+    }
+
+    // Writes the same SQL script to an arbitrary writer instead of sqlite3.
+    pub fn from_writer(out: Box<dyn Write + Send>) -> Result<Self, Box<dyn Error>> {
+        let mut sink = SqliteSink {
+            out: BufWriter::new(out),
+            child: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+        };
+        writeln!(sink.out, "PRAGMA journal_mode = WAL;{}", SCHEMA)?;
+        sink.out.flush()?;
+        Ok(sink)
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        writeln!(self.out, "BEGIN;")?;
+        let row = format!(
+            "({}, {}, {}, {}, {}, {})",
+            text(&block.blockhash),
+            block.slot,
+            nullable(block.block_height),
+            nullable(block.block_time),
+            block.parent_slot,
+            text(&block.previous_blockhash),
+        );
+        self.upsert("blocks", "blockhash, slot, block_height, block_time, parent_slot, previous_blockhash", "blockhash", &[row])?;
+        let rewards: Vec<String> = block.rewards.iter().map(|reward| {
+            format!(
+                "({}, {}, {}, {}, {}, {})",
+                text(&block.blockhash),
+                text(&reward.pubkey.to_string()),
+                reward.lamports,
+                reward.post_balance,
+                text(reward.reward_type.as_str()),
+                nullable(reward.commission),
+            )
+        }).collect();
+        for batch in rewards.chunks(self.batch_size) {
+            self.upsert(
+                "rewards",
+                "blockhash, pubkey, lamports, post_balance, reward_type, commission",
+                "blockhash, pubkey, reward_type",
+                batch,
+            )?;
+        }
+        self.write_transactions(Some(&block.blockhash), &block.transactions)?;
+        writeln!(self.out, "COMMIT;")?;
+        self.out.flush()?;
+        Ok(())
+    }
+
+    // Transactions outside a block context are stored with a NULL blockhash.
+    pub fn write_transactions(&mut self, blockhash: Option<&str>, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        let blockhash = blockhash.map(text).unwrap_or_else(|| "NULL".to_string());
+
+        for batch in txs.chunks(self.batch_size) {
+            let rows: Vec<String> = batch.iter().map(|tx| {
+                format!(
+                    "({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {})",
+                    text(&tx.signature),
+                    blockhash,
+                    text(&tx.fee_payer.to_string()),
+                    tx.is_success as u8,
+                    tx.failure_reason.as_deref().map(text).unwrap_or_else(|| "NULL".to_string()),
+                    tx.fee,
+                    tx.base_fee,
+                    tx.priority_fee,
+                    nullable(tx.compute_units_consumed),
+                    nullable(tx.compute_unit_limit),
+                    nullable(tx.unit_price_micro_lamports),
+                    nullable(tx.slot),
+                    nullable(tx.index_in_block),
+                    nullable(tx.timestamp_ms),
+                )
+            }).collect();
+            self.upsert(
+                "transactions",
+                "signature, blockhash, fee_payer, is_success, failure_reason, fee, base_fee, priority_fee, compute_units_consumed, compute_unit_limit, unit_price_micro_lamports, slot, index_in_block, timestamp_ms",
+                "signature",
+                &rows,
+            )?;
+        }
+
+        let rows: Vec<String> = txs.iter().flat_map(|tx| {
+            tx.accounts.iter().enumerate().map(move |(position, account)| {
+                format!(
+                    "({}, {}, {}, {}, {})",
+                    text(&tx.signature),
+                    position,
+                    text(&account.pubkey.to_string()),
+                    account.is_signer as u8,
+                    account.is_writable as u8,
+                )
+            })
+        }).collect();
+        for batch in rows.chunks(self.batch_size) {
+            self.upsert("transaction_accounts", "signature, position, account, is_signer, is_writable", "signature, position", batch)?;
+        }
+
+        let mut rows: Vec<String> = Vec::new();
+        for tx in txs {
+            for (index, ix) in tx.instructions.iter().enumerate() {
+                rows.push(instruction_row(tx, None, index, ix));
+            }
+            for inner in &tx.inner_instructions {
+                for (index, ix) in inner.instructions.iter().enumerate() {
+                    rows.push(instruction_row(tx, Some(inner.index), index, ix));
+                }
+            }
+        }
+        for batch in rows.chunks(self.batch_size) {
+            self.upsert(
+                "instructions",
+                "signature, parent_index, position, program_id, accounts, data",
+                "signature, COALESCE(parent_index, -1), position",
+                batch,
+            )?;
+        }
+
+        let rows: Vec<String> = txs.iter().flat_map(|tx| {
+            transfer_rows(tx).into_iter().enumerate().map(move |(position, row)| {
+                format!(
+                    "({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {})",
+                    text(&tx.signature),
+                    position,
+                    text(row.kind),
+                    text(&row.source.to_string()),
+                    text(&row.destination.to_string()),
+                    nullable_text(row.source_owner),
+                    nullable_text(row.destination_owner),
+                    nullable_text(row.mint),
+                    row.amount,
+                    row.instruction_index,
+                    nullable(row.inner_index),
+                )
+            })
+        }).collect();
+        for batch in rows.chunks(self.batch_size) {
+            self.upsert(
+                "transfers",
+                "signature, position, kind, source, destination, source_owner, destination_owner, mint, amount, instruction_index, inner_index",
+                "signature, position",
+                batch,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn write_chain_event(&mut self, event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        let blockhash = text(event.blockhash());
+        match event {
+            ChainEvent::Finalized { slot, .. } => {
+                writeln!(self.out, "INSERT OR IGNORE INTO finalized_blocks (blockhash, slot) VALUES ({}, {});", blockhash, slot)?;
+            }
+            ChainEvent::Retracted { .. } => {
+                let signatures = format!("(SELECT signature FROM transactions WHERE blockhash = {})", blockhash);
+                writeln!(self.out, "BEGIN;")?;
+                writeln!(self.out, "DELETE FROM instructions WHERE signature IN {};", signatures)?;
+                writeln!(self.out, "DELETE FROM transfers WHERE signature IN {};", signatures)?;
+                writeln!(self.out, "DELETE FROM transaction_accounts WHERE signature IN {};", signatures)?;
+                writeln!(self.out, "DELETE FROM transactions WHERE blockhash = {};", blockhash)?;
+                writeln!(self.out, "DELETE FROM rewards WHERE blockhash = {};", blockhash)?;
+                writeln!(self.out, "DELETE FROM blocks WHERE blockhash = {};", blockhash)?;
+                writeln!(self.out, "COMMIT;")?;
+            }
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+
+    // Flushes remaining SQL and, when writing through sqlite3, waits for it
+    // to exit so failures surface here.
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        drop(self.out);
+        if let Some(mut child) = self.child.take() {
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(format!("sqlite3 exited with {}", status).into());
+            }
+        }
+        Ok(())
+    }
+
+    fn upsert(&mut self, table: &str, columns: &str, key: &str, rows: &[String]) -> Result<(), Box<dyn Error>> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        writeln!(self.out, "{}", upsert_sql(table, columns, key, rows))?;
+        Ok(())
+    }
+}
+
+impl Sink for SqliteSink {
+    fn name(&self) -> &str {
+        "sqlite"
+    }
+
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        SqliteSink::write_block(self, block)
+    }
+
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        SqliteSink::write_transactions(self, None, txs)
+    }
+
+    fn write_chain_event(&mut self, event: &ChainEvent) -> Result<(), Box<dyn Error>> {
+        SqliteSink::write_chain_event(self, event)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        SqliteSink::finish(*self)
+    }
+}
+
+fn instruction_row(tx: &ParsedTransaction, parent_index: Option<usize>, index: usize, ix: &ParsedInstruction) -> String {
+    let accounts: Vec<String> = ix.resolved_accounts(tx).map(|a| a.map(|key| key.to_string()).unwrap_or_default()).collect();
+    format!(
+        "({}, {}, {}, {}, {}, {})",
+        text(&tx.signature),
+        nullable(parent_index),
+        index,
+        text(&ix.program_id.to_string()),
+        text(&serde_json::to_string(&accounts).unwrap_or_default()),
+        text(&ix.data),
+    )
+}
+
+// Multi-row INSERT overwriting the row already stored under `key` (the
+// columns or expressions of a unique index); a NULL doesn't replace a value,
+// as in the Postgres sink.
+fn upsert_sql(table: &str, columns: &str, key: &str, rows: &[String]) -> String {
+    let key_columns: Vec<&str> = key.split(|c: char| !c.is_alphanumeric() && c != '_').collect();
+    let updates: Vec<String> = columns
+        .split(", ")
+        .filter(|column| !key_columns.contains(column))
+        .map(|column| format!("{column} = COALESCE(excluded.{column}, {table}.{column})"))
+        .collect();
+    format!(
+        "INSERT INTO {} ({}) VALUES\n{}\nON CONFLICT ({}) DO UPDATE SET {};",
+        table,
+        columns,
+        rows.join(",\n"),
+        key,
+        updates.join(", ")
+    )
+}
+
+fn text(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn nullable<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_else(|| "NULL".to_string())
+}
+
+fn nullable_text<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| text(&v.to_string())).unwrap_or_else(|| "NULL".to_string())
+}
+
+// The ADDED_COLUMNS an existing database lacks. Tables that don't exist
+// yet are left to SCHEMA.
+fn missing_columns(path: &str) -> Result<Vec<AddedColumn>, Box<dyn Error>> {
+    let mut missing = Vec::new();
+    if !std::path::Path::new(path).exists() {
+        return Ok(missing);
+    }
+    for &(table, column, column_type) in ADDED_COLUMNS {
+        let sql = format!("SELECT count(*) = 0, sum(name = '{}') = 0 FROM pragma_table_info('{}');", column, table);
+        let output = Command::new("sqlite3")
+            .args(["-readonly", "-bail", path, &sql])
+            .output()
+            .map_err(|e| format!("failed to run sqlite3: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("failed to read the schema of {}: {}", path, String::from_utf8_lossy(&output.stderr).trim()).into());
+        }
+        // "<table missing>|<column missing>"
+        if String::from_utf8_lossy(&output.stdout).trim() == "0|1" {
+            missing.push((table, column, column_type));
+        }
+    }
+    Ok(missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn query(path: &str, sql: &str) -> String {
+        let output = Command::new("sqlite3").args([path, sql]).output().unwrap();
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    #[test]
+    fn writes_a_queryable_database() {
+        let block = fixtures::block();
+        let path = std::env::temp_dir().join(format!("phase1-sqlite-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        // A transaction seen before its block picks up the blockhash, and
+        // writing the block twice leaves one copy of each row.
+        let mut sink = SqliteSink::open(path).unwrap();
+        sink.write_transactions(None, &block.transactions[..1]).unwrap();
+        sink.finish().unwrap();
+        for _ in 0..2 {
+            let mut sink = SqliteSink::open(path).unwrap().with_batch_size(100);
+            sink.write_block(block).unwrap();
+            sink.finish().unwrap();
+        }
+
+        assert_eq!(query(path, "SELECT count(*) FROM transactions"), block.transactions.len().to_string());
+        assert_eq!(query(path, "SELECT count(*) FROM rewards"), block.rewards.len().to_string());
+        assert_eq!(query(path, "SELECT count(*) FROM transactions WHERE blockhash IS NULL"), "0");
+        assert_eq!(query(path, "SELECT count(*) FROM transactions WHERE slot IS NULL OR index_in_block IS NULL"), "0");
+        let fee_payer = block.transactions[0].fee_payer;
+        let touched = block.transactions.iter().filter(|tx| tx.account_keys.contains(&fee_payer)).count();
+        assert_eq!(
+            query(path, &format!("SELECT count(DISTINCT signature) FROM transaction_accounts WHERE account = '{}'", fee_payer)),
+            touched.to_string()
+        );
+        let plan = query(path, "EXPLAIN QUERY PLAN SELECT * FROM instructions WHERE program_id = 'x'");
+        assert!(plan.contains("instructions_program_id_idx"), "{}", plan);
+
+        let mut sink = SqliteSink::open(path).unwrap();
+        let event = ChainEvent::Retracted { slot: block.slot, blockhash: block.blockhash.clone() };
+        sink.write_chain_event(&event).unwrap();
+        sink.finish().unwrap();
+        for table in ["blocks", "transactions", "transaction_accounts", "instructions", "transfers", "rewards"] {
+            assert_eq!(query(path, &format!("SELECT count(*) FROM {}", table)), "0", "{}", table);
+        }
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+    }
+}
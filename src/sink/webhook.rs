@@ -0,0 +1,407 @@
+use super::Sink;
+use crate::dex::{detect_swap, SwapEvent};
+use crate::filter::TxFilter;
+use crate::http;
+use crate::pubkey::Pubkey;
+use crate::shutdown;
+use crate::types::{ParsedBlock, ParsedTransaction};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// ==========================================
+// WEBHOOK SINK
+// ==========================================
+// Alerts on individual transactions: every transaction matching the sink's
+// own filter (and, when a minimum is set, swapping at least that much of a
+// mint) is POSTed to a webhook as soon as it's written. Slack and Discord
+// webhook URLs get a one-line message in their format; any other URL gets
+// the alert as JSON. Delivery is best-effort: a failed POST is logged and
+// the run carries on.
+//
+// Alerts are delivered on a thread of their own, so a slow or unreachable
+// endpoint never holds up the other sinks. They queue up to QUEUE_CAPACITY
+// deep (past that they're dropped and counted) and go out one POST at a
+// time, no more often than the send interval, each with a connect and a
+// total timeout. Finishing waits for the queue to drain, unless shutdown
+// was requested.
+
+const QUEUE_CAPACITY: usize = 1000;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Slack and Discord rate-limit webhooks to about one message a second.
+const CHAT_SEND_INTERVAL: Duration = Duration::from_secs(1);
+const JSON_SEND_INTERVAL: Duration = Duration::from_millis(100);
+
+pub const WRAPPED_SOL_MINT: Pubkey = Pubkey::from_str_const("So11111111111111111111111111111111111111112");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookFormat {
+    Json,
+    Slack,
+    Discord,
+}
+
+impl WebhookFormat {
+    // Slack incoming webhooks and Discord webhooks are told apart by host.
+    pub fn detect(url: &str) -> Self {
+        let host = url.split("://").nth(1).unwrap_or(url);
+        if host.starts_with("hooks.slack.com") {
+            WebhookFormat::Slack
+        } else if ["discord.com/api/webhooks", "discordapp.com/api/webhooks"].iter().any(|prefix| host.starts_with(prefix)) {
+            WebhookFormat::Discord
+        } else {
+            WebhookFormat::Json
+        }
+    }
+}
+
+// A swap matches when either side moves at least `amount` whole tokens of
+// `mint`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SwapThreshold {
+    pub mint: Pubkey,
+    pub amount: f64,
+}
+
+impl SwapThreshold {
+    pub fn sol(amount: f64) -> Self {
+        SwapThreshold { mint: WRAPPED_SOL_MINT, amount }
+    }
+
+    pub fn matches(&self, swap: &SwapEvent) -> bool {
+        let side = |mint: Pubkey, amount: u64, decimals: Option<u8>| {
+            // Native SOL legs don't always carry decimals.
+            let decimals = decimals.or((mint == WRAPPED_SOL_MINT).then_some(9)).unwrap_or(0);
+            mint == self.mint && ui_amount(amount, decimals) >= self.amount
+        };
+        side(swap.in_mint, swap.in_amount, swap.in_decimals) || side(swap.out_mint, swap.out_amount, swap.out_decimals)
+    }
+}
+
+// What the JSON format POSTs.
+#[derive(Debug, Serialize)]
+pub struct Alert<'a> {
+    pub signature: &'a str,
+    pub slot: Option<u64>,
+    pub block_time: Option<i64>,
+    pub fee_payer: Pubkey,
+    pub is_success: bool,
+    pub fee: u64,
+    pub swap: Option<SwapEvent>,
+}
+
+// A queued alert: its signature for the logs and the request body.
+type Delivery = (String, String);
+
+#[derive(Debug, Default)]
+struct DeliveryCounts {
+    sent: usize,
+    failed: usize,
+    dropped: usize,
+}
+
+#[derive(Debug)]
+pub struct WebhookSink {
+    url: String,
+    format: WebhookFormat,
+    filter: TxFilter,
+    min_swap: Option<SwapThreshold>,
+    // Per format unless set.
+    send_interval: Option<Duration>,
+    // Started with the first alert.
+    queue: Option<SyncSender<Delivery>>,
+    worker: Option<JoinHandle<DeliveryCounts>>,
+    // Alerts the full queue turned away.
+    dropped: usize,
+}
+
+impl WebhookSink {
+    // With no filter and no minimum swap, every transaction alerts.
+    pub fn new(url: &str) -> Self {
+        WebhookSink {
+            url: url.to_string(),
+            format: WebhookFormat::detect(url),
+            filter: TxFilter::new(),
+            min_swap: None,
+            send_interval: None,
+            queue: None,
+            worker: None,
+            dropped: 0,
+        }
+    }
+
+    pub fn with_format(mut self, format: WebhookFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: TxFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    // Only swaps at least this large alert; other transactions never do.
+    pub fn with_min_swap(mut self, threshold: SwapThreshold) -> Self {
+        self.min_swap = Some(threshold);
+        self
+    }
+
+    // The least time between two POSTs.
+    pub fn with_send_interval(mut self, interval: Duration) -> Self {
+        self.send_interval = Some(interval);
+        self
+    }
+
+    // Whether anything narrows the alerts down from every transaction.
+    pub fn is_selective(&self) -> bool {
+        !self.filter.is_empty() || self.min_swap.is_some()
+    }
+
+    // None when the transaction doesn't call for an alert.
+    pub fn alert<'a>(&self, block: Option<&ParsedBlock>, tx: &'a ParsedTransaction) -> Option<Alert<'a>> {
+        if !self.filter.matches(tx) {
+            return None;
+        }
+        let swap = detect_swap(tx);
+        if let Some(threshold) = &self.min_swap
+            && !swap.as_ref().is_some_and(|swap| threshold.matches(swap))
+        {
+            return None;
+        }
+        Some(Alert {
+            signature: &tx.signature,
+            slot: block.map(|block| block.slot),
+            block_time: block.and_then(|block| block.block_time),
+            fee_payer: tx.fee_payer,
+            is_success: tx.is_success,
+            fee: tx.fee,
+            swap,
+        })
+    }
+
+    // The request body for `alert` in this sink's format.
+    pub fn payload(&self, alert: &Alert) -> Value {
+        match self.format {
+            WebhookFormat::Json => json!(alert),
+            WebhookFormat::Slack => json!({ "text": message(alert) }),
+            WebhookFormat::Discord => json!({ "content": message(alert) }),
+        }
+    }
+
+    fn send(&mut self, alert: &Alert) {
+        let delivery = (alert.signature.to_string(), self.payload(alert).to_string());
+        let queue = match &self.queue {
+            Some(queue) => queue,
+            None => {
+                let (queue, deliveries) = mpsc::sync_channel(QUEUE_CAPACITY);
+                let interval = self.send_interval.unwrap_or(match self.format {
+                    WebhookFormat::Json => JSON_SEND_INTERVAL,
+                    WebhookFormat::Slack | WebhookFormat::Discord => CHAT_SEND_INTERVAL,
+                });
+                let url = self.url.clone();
+                self.worker = Some(thread::spawn(move || deliver(&url, deliveries, interval)));
+                self.queue.insert(queue)
+            }
+        };
+        match queue.try_send(delivery) {
+            Ok(()) => {}
+            Err(TrySendError::Full((signature, _))) => {
+                if self.dropped == 0 {
+                    log::warn!(signature = signature.as_str(); "webhook queue full, dropping alerts");
+                }
+                self.dropped += 1;
+            }
+            Err(TrySendError::Disconnected(_)) => self.dropped += 1,
+        }
+    }
+
+    fn notify(&mut self, block: Option<&ParsedBlock>, txs: &[ParsedTransaction]) {
+        for tx in txs {
+            if let Some(alert) = self.alert(block, tx) {
+                self.send(&alert);
+            }
+        }
+    }
+}
+
+impl Sink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn write_block(&mut self, block: &ParsedBlock) -> Result<(), Box<dyn Error>> {
+        self.notify(Some(block), &block.transactions);
+        Ok(())
+    }
+
+    fn write_transactions(&mut self, txs: &[ParsedTransaction]) -> Result<(), Box<dyn Error>> {
+        self.notify(None, txs);
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        // Closing the queue lets the worker finish once it's drained.
+        self.queue = None;
+        let Some(worker) = self.worker.take() else {
+            return Ok(());
+        };
+        let counts = worker.join().map_err(|_| "webhook delivery thread panicked")?;
+        let dropped = counts.dropped + self.dropped;
+        if counts.failed + dropped > 0 {
+            log::warn!(sent = counts.sent, failed = counts.failed, dropped; "some webhook alerts weren't delivered");
+        }
+        Ok(())
+    }
+}
+
+// The delivery thread: POSTs queued alerts in order until the queue closes.
+fn deliver(url: &str, deliveries: Receiver<Delivery>, interval: Duration) -> DeliveryCounts {
+    let mut counts = DeliveryCounts::default();
+    let mut last_sent: Option<Instant> = None;
+    for (signature, body) in deliveries {
+        if shutdown::requested() {
+            counts.dropped += 1;
+            continue;
+        }
+        if let Some(wait) = last_sent.and_then(|sent| interval.checked_sub(sent.elapsed())) {
+            thread::sleep(wait);
+        }
+        last_sent = Some(Instant::now());
+        let error = match http::post_with_timeouts(url, "application/json", body.as_bytes(), CONNECT_TIMEOUT, REQUEST_TIMEOUT) {
+            Ok(response) if response.is_success() => None,
+            Ok(response) => Some(format!("HTTP {}: {}", response.status, response.body.trim())),
+            Err(e) => Some(e.to_string()),
+        };
+        match error {
+            None => counts.sent += 1,
+            Some(error) => {
+                counts.failed += 1;
+                log::warn!(signature = signature.as_str(), error = error.as_str(); "webhook delivery failed");
+            }
+        }
+    }
+    counts
+}
+
+// One line for chat webhooks.
+fn message(alert: &Alert) -> String {
+    let what = match &alert.swap {
+        Some(swap) => format!(
+            "{} swapped {} {} for {} {} on {}",
+            swap.trader,
+            format_amount(swap.in_amount, swap.in_decimals),
+            swap.in_mint,
+            format_amount(swap.out_amount, swap.out_decimals),
+            swap.out_mint,
+            swap.venue
+        ),
+        None if alert.is_success => format!("{} sent a transaction", alert.fee_payer),
+        None => format!("{} sent a failed transaction", alert.fee_payer),
+    };
+    match alert.slot {
+        Some(slot) => format!("{} in slot {}: https://explorer.solana.com/tx/{}", what, slot, alert.signature),
+        None => format!("{}: https://explorer.solana.com/tx/{}", what, alert.signature),
+    }
+}
+
+fn ui_amount(amount: u64, decimals: u8) -> f64 {
+    amount as f64 / 10f64.powi(decimals as i32)
+}
+
+// Raw base units when the decimals aren't known.
+fn format_amount(amount: u64, decimals: Option<u8>) -> String {
+    match decimals {
+        Some(decimals) => {
+            let formatted = format!("{:.*}", decimals as usize, ui_amount(amount, decimals));
+            match formatted.contains('.') {
+                true => formatted.trim_end_matches('0').trim_end_matches('.').to_string(),
+                false => formatted,
+            }
+        }
+        None => amount.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoders::jupiter::JUPITER_V6_PROGRAM_ID;
+    use crate::fixtures;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    const USDC_MINT: Pubkey = Pubkey::from_str_const("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+
+    fn alerts(sink: &WebhookSink, block: &ParsedBlock) -> Vec<Value> {
+        block.transactions.iter().filter_map(|tx| sink.alert(Some(block), tx)).map(|alert| sink.payload(&alert)).collect()
+    }
+
+    #[test]
+    fn alerts_on_filtered_transactions_and_large_swaps() {
+        let block = fixtures::block();
+        assert_eq!(WebhookFormat::detect("https://hooks.slack.com/services/T0/B0/x"), WebhookFormat::Slack);
+        assert_eq!(WebhookFormat::detect("https://discord.com/api/webhooks/1/x"), WebhookFormat::Discord);
+        assert_eq!(WebhookFormat::detect("https://example.com/hooks.slack.com"), WebhookFormat::Json);
+
+        let jupiter = WebhookSink::new("https://example.com/hook").with_filter(TxFilter::new().program(JUPITER_V6_PROGRAM_ID));
+        let sent = alerts(&jupiter, block);
+        assert!(!sent.is_empty());
+        assert!(sent.iter().all(|alert| alert["slot"] == 381785271));
+
+        let whales = WebhookSink::new("https://hooks.slack.com/services/T0/B0/x").with_min_swap(SwapThreshold::sol(1.0));
+        assert_eq!(alerts(&whales, block).len(), 22);
+        let usdc = whales.with_min_swap(SwapThreshold { mint: USDC_MINT, amount: 100.0 });
+        let sent = alerts(&usdc, block);
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0]["text"].as_str().unwrap().starts_with(
+            "8qjB4NibFqqRyECMUMWw7X4are73dms6pfM9gBEJGNAP swapped 1 So11111111111111111111111111111111111111112 \
+             for 126.851615 EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v on Jupiter in slot 381785271: https://explorer.solana.com/tx/"
+        ));
+    }
+
+    #[test]
+    fn delivers_alerts_from_the_queue() {
+        let block = fixtures::block();
+        // Records each request's body and answers 200.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (received, bodies) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                let mut body = vec![0u8; length];
+                reader.read_exact(&mut body).unwrap();
+                received.send(serde_json::from_slice::<Value>(&body).unwrap()).unwrap();
+                reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+            }
+        });
+
+        let mut sink = WebhookSink::new(&url)
+            .with_min_swap(SwapThreshold { mint: USDC_MINT, amount: 100.0 })
+            .with_send_interval(Duration::ZERO);
+        assert!(sink.is_selective());
+        sink.write_block(block).unwrap();
+        Box::new(sink).finish().unwrap();
+        let sent: Vec<Value> = bodies.try_iter().collect();
+        assert_eq!(sent.len(), 2);
+        assert!(sent.iter().all(|alert| alert["slot"] == 381785271 && alert["swap"].is_object()));
+        assert!(!WebhookSink::new(&url).is_selective());
+    }
+}
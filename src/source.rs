@@ -0,0 +1,311 @@
+use crate::error::ParseError;
+use crate::io::load_from_json;
+use crate::parser::{parse_block_with_options, ParseOptions};
+use crate::rpc::{BlockFetch, RpcClient};
+use crate::types::{ParseReport, ParsedBlock, RpcBlockResponse, RpcBlockResult};
+use serde_json::StreamDeserializer;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufReader, Read, StdinLock};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+// ==========================================
+// BLOCK SOURCES
+// ==========================================
+// Where raw getBlock results come from, kept apart from parsing them: a
+// source only yields `RawBlock`s, and `parse_raw_block` (or `ParsedBlocks`
+// around any source) is the one place they're parsed and given their slot.
+// Sources here: block files, a directory of them, stdin, a slot range over
+// RPC; the WebSocket and Geyser block subscriptions implement it too.
+//
+// An error is one block that couldn't be read; the source goes on unless
+// it can't (a closed subscription, malformed input on stdin).
+
+pub struct RawBlock {
+    // None when the source doesn't know it (a file not named <slot>.json,
+    // stdin); the parser assumes parent_slot + 1 then.
+    pub slot: Option<u64>,
+    pub block: RpcBlockResult,
+}
+
+pub trait BlockSource {
+    // None once the source is exhausted.
+    fn next_block(&mut self) -> Option<Result<RawBlock, Box<dyn Error>>>;
+
+    fn parsed(self, options: ParseOptions) -> ParsedBlocks<Self>
+    where
+        Self: Sized,
+    {
+        ParsedBlocks { source: self, options }
+    }
+}
+
+impl<S: BlockSource + ?Sized> BlockSource for Box<S> {
+    fn next_block(&mut self) -> Option<Result<RawBlock, Box<dyn Error>>> {
+        (**self).next_block()
+    }
+}
+
+pub struct SourcedBlock {
+    pub slot: Option<u64>,
+    pub block: ParsedBlock,
+    pub report: ParseReport,
+}
+
+pub fn parse_raw_block(raw: RawBlock, options: &ParseOptions) -> Result<SourcedBlock, ParseError> {
+    let (mut block, report) = parse_block_with_options(raw.block, options)?;
+    if let Some(slot) = raw.slot {
+        block.set_slot(slot, &options.epoch_schedule);
+    }
+    Ok(SourcedBlock { slot: raw.slot, block, report })
+}
+
+pub struct ParsedBlocks<S> {
+    source: S,
+    options: ParseOptions,
+}
+
+impl<S: BlockSource> Iterator for ParsedBlocks<S> {
+    type Item = Result<SourcedBlock, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.source.next_block()?.and_then(|raw| Ok(parse_raw_block(raw, &self.options)?)))
+    }
+}
+
+// ==========================================
+// FILES AND DIRECTORIES
+// ==========================================
+
+pub struct BlockFile {
+    // The file name without .json and any .gz / .zst suffix.
+    pub name: String,
+    // From a <slot>.json name.
+    pub slot: Option<u64>,
+    pub path: PathBuf,
+}
+
+impl BlockFile {
+    // None unless the name ends in .json, optionally with .gz or .zst.
+    pub fn from_path(path: PathBuf) -> Option<Self> {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let name = [".json", ".json.gz", ".json.zst"].iter().find_map(|suffix| file_name.strip_suffix(suffix))?.to_string();
+        Some(BlockFile { slot: name.parse().ok(), name, path })
+    }
+}
+
+// The directory's .json files, compressed or not: those named <slot>.json
+// first, in slot order, then the rest by name.
+pub fn block_files(dir: &Path) -> Result<Vec<BlockFile>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))? {
+        let path = entry?.path();
+        if path.is_file()
+            && let Some(file) = BlockFile::from_path(path)
+        {
+            files.push(file);
+        }
+    }
+    files.sort_by(|a, b| (a.slot.is_none(), a.slot, &a.path).cmp(&(b.slot.is_none(), b.slot, &b.path)));
+    Ok(files)
+}
+
+// Saved getBlock responses, gzip or zstd compressed or not, in the order
+// given.
+pub struct FileSource {
+    files: VecDeque<BlockFile>,
+}
+
+impl FileSource {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let files = paths
+            .into_iter()
+            .map(|path| BlockFile::from_path(path.clone()).unwrap_or(BlockFile { name: String::new(), slot: None, path }))
+            .collect();
+        FileSource { files }
+    }
+}
+
+impl BlockSource for FileSource {
+    fn next_block(&mut self) -> Option<Result<RawBlock, Box<dyn Error>>> {
+        let file = self.files.pop_front()?;
+        Some(
+            load_from_json::<RpcBlockResponse>(&file.path.to_string_lossy())
+                .map(|response| RawBlock { slot: file.slot, block: response.result })
+                .map_err(|e| format!("{}: {}", file.path.display(), e).into()),
+        )
+    }
+}
+
+// A directory's block files, in `block_files` order.
+pub struct DirectorySource(FileSource);
+
+impl DirectorySource {
+    pub fn open(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(DirectorySource(FileSource { files: block_files(dir)?.into() }))
+    }
+}
+
+impl BlockSource for DirectorySource {
+    fn next_block(&mut self) -> Option<Result<RawBlock, Box<dyn Error>>> {
+        self.0.next_block()
+    }
+}
+
+// ==========================================
+// READERS
+// ==========================================
+// getBlock responses piped in one after another, newline-delimited or
+// just concatenated. Reading stops at the first malformed one, since the
+// rest of the stream can't be resynchronized.
+
+pub struct ReaderSource<R: Read> {
+    responses: StreamDeserializer<'static, serde_json::de::IoRead<R>, RpcBlockResponse>,
+    done: bool,
+}
+
+impl<R: Read> ReaderSource<R> {
+    pub fn new(reader: R) -> Self {
+        ReaderSource { responses: serde_json::Deserializer::from_reader(reader).into_iter(), done: false }
+    }
+}
+
+impl ReaderSource<BufReader<StdinLock<'static>>> {
+    pub fn stdin() -> Self {
+        ReaderSource::new(BufReader::new(io::stdin().lock()))
+    }
+}
+
+impl<R: Read> BlockSource for ReaderSource<R> {
+    fn next_block(&mut self) -> Option<Result<RawBlock, Box<dyn Error>>> {
+        if self.done {
+            return None;
+        }
+        match self.responses.next()? {
+            Ok(response) => Some(Ok(RawBlock { slot: None, block: response.result })),
+            Err(e) => {
+                self.done = true;
+                Some(Err(format!("failed to read block JSON: {}", e).into()))
+            }
+        }
+    }
+}
+
+// ==========================================
+// RPC POLLING
+// ==========================================
+// Walks slots in order with getBlock, passing over skipped slots. With no
+// end slot it follows the tip, polling getSlot whenever it catches up and
+// retrying a block produced but not yet queryable at this commitment.
+
+pub struct RpcSource {
+    pub(crate) client: RpcClient,
+    pub(crate) next_slot: u64,
+    pub(crate) end_slot: Option<u64>,
+    pub(crate) poll_interval: Duration,
+    tip: u64,
+}
+
+impl RpcSource {
+    // Inclusive range; `end_slot: None` follows the chain tip indefinitely.
+    pub fn new(client: RpcClient, start_slot: u64, end_slot: Option<u64>) -> Self {
+        RpcSource { client, next_slot: start_slot, end_slot, poll_interval: Duration::from_millis(400), tip: 0 }
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    // Every slot before this one has been yielded, skipped or reported as an
+    // error.
+    pub fn next_slot(&self) -> u64 {
+        self.next_slot
+    }
+
+    // Block until `next_slot` is at or below the node's current slot.
+    fn wait_for_tip(&mut self) -> Result<(), Box<dyn Error>> {
+        while self.next_slot > self.tip {
+            self.tip = self.client.get_slot()?;
+            if self.next_slot > self.tip {
+                thread::sleep(self.poll_interval);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BlockSource for RpcSource {
+    fn next_block(&mut self) -> Option<Result<RawBlock, Box<dyn Error>>> {
+        loop {
+            let slot = self.next_slot;
+            if self.end_slot.is_some_and(|end| slot > end) {
+                return None;
+            }
+            if self.end_slot.is_none()
+                && let Err(e) = self.wait_for_tip()
+            {
+                return Some(Err(e));
+            }
+
+            match self.client.fetch_block(slot) {
+                Ok(BlockFetch::Block(block)) => {
+                    self.next_slot += 1;
+                    return Some(Ok(RawBlock { slot: Some(slot), block }));
+                }
+                Ok(BlockFetch::SlotSkipped) => {
+                    log::debug!(slot; "slot skipped");
+                    self.next_slot += 1;
+                }
+                Ok(BlockFetch::BlockUnavailable) if self.end_slot.is_none() => {
+                    thread::sleep(self.poll_interval);
+                }
+                Ok(BlockFetch::BlockUnavailable) => {
+                    self.next_slot += 1;
+                    return Some(Err(format!("block for slot {} is not available", slot).into()));
+                }
+                Err(e) => {
+                    self.next_slot += 1;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_yield_raw_blocks_for_one_parser() {
+        let json = fs::read_to_string("src/json/block.json").unwrap();
+        let piped = format!("{}\n{}", json, json);
+        let parsed: Vec<SourcedBlock> =
+            ReaderSource::new(piped.as_bytes()).parsed(ParseOptions::default()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!((parsed[0].slot, parsed[0].block.slot), (None, parsed[0].block.parent_slot + 1));
+
+        // A <slot>.json file gets its slot; malformed input ends a reader.
+        let dir = std::env::temp_dir().join(format!("phase1-source-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("250000000.json"), &json).unwrap();
+        fs::write(dir.join("notes.txt"), "not a block").unwrap();
+        let blocks: Vec<SourcedBlock> =
+            DirectorySource::open(&dir).unwrap().parsed(ParseOptions::default()).collect::<Result<_, _>>().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!((blocks[0].slot, blocks[0].block.slot), (Some(250_000_000), 250_000_000));
+        assert!(blocks[0].block.transactions.iter().all(|tx| tx.slot == Some(250_000_000)));
+        assert_eq!(blocks[0].block.transactions.len(), parsed[0].block.transactions.len());
+
+        let truncated = format!("{}\n{{\"result\": ", json);
+        let mut broken = ReaderSource::new(truncated.as_bytes());
+        assert!(broken.next_block().unwrap().is_ok());
+        assert!(broken.next_block().unwrap().is_err());
+        assert!(broken.next_block().is_none());
+    }
+}
@@ -0,0 +1,166 @@
+use crate::aggregate::FeePayerSpill;
+use crate::pubkey::Pubkey;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// ==========================================
+// SPILL FILES
+// ==========================================
+// Sorted runs of keys on disk, for the sets a long run can't keep in memory
+// (--memory-budget). Each run is a file of 32-byte keys in ascending
+// order; the distinct keys across a group of runs are counted with a k-way
+// merge, reading one key per run at a time. The files live in a directory
+// of their own, removed with the SpillDir.
+
+// Distinguishes SpillDirs in one process.
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+pub struct SpillDir {
+    dir: PathBuf,
+    // Run files by period start.
+    runs: BTreeMap<i64, Vec<PathBuf>>,
+    next_run: usize,
+}
+
+impl SpillDir {
+    // Creates a fresh directory under `parent` (e.g. std::env::temp_dir()).
+    pub fn create(parent: &Path) -> io::Result<Self> {
+        let name = format!("phase1-spill-{}-{}", std::process::id(), NEXT_DIR.fetch_add(1, Ordering::Relaxed));
+        let dir = parent.join(name);
+        fs::create_dir_all(&dir)?;
+        Ok(SpillDir { dir, runs: BTreeMap::new(), next_run: 0 })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl fmt::Debug for SpillDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpillDir").field("dir", &self.dir).field("runs", &self.next_run).finish()
+    }
+}
+
+impl FeePayerSpill for SpillDir {
+    fn spill(&mut self, period_start: i64, fee_payers: &[Pubkey]) -> io::Result<()> {
+        if fee_payers.is_empty() {
+            return Ok(());
+        }
+        let path = self.dir.join(format!("{}.run", self.next_run));
+        self.next_run += 1;
+        let mut out = BufWriter::new(File::create(&path)?);
+        for key in fee_payers {
+            out.write_all(key.as_bytes())?;
+        }
+        out.flush()?;
+        self.runs.entry(period_start).or_default().push(path);
+        Ok(())
+    }
+
+    fn count_unique(&self, period_start: i64, fee_payers: &[Pubkey]) -> io::Result<usize> {
+        let Some(paths) = self.runs.get(&period_start) else {
+            return Ok(fee_payers.len());
+        };
+        let mut runs: Vec<Run> = paths.iter().map(|path| Ok(Run::File(BufReader::new(File::open(path)?)))).collect::<io::Result<_>>()?;
+        runs.push(Run::Memory(fee_payers.iter()));
+
+        let mut heap = BinaryHeap::new();
+        for (index, run) in runs.iter_mut().enumerate() {
+            if let Some(key) = run.next_key()? {
+                heap.push(Reverse((key, index)));
+            }
+        }
+        let (mut unique, mut last) = (0, None);
+        while let Some(Reverse((key, index))) = heap.pop() {
+            if last != Some(key) {
+                unique += 1;
+                last = Some(key);
+            }
+            if let Some(next) = runs[index].next_key()? {
+                heap.push(Reverse((next, index)));
+            }
+        }
+        Ok(unique)
+    }
+
+    fn discard(&mut self, period_start: i64) -> io::Result<()> {
+        for path in self.runs.remove(&period_start).unwrap_or_default() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+enum Run<'a> {
+    File(BufReader<File>),
+    Memory(std::slice::Iter<'a, Pubkey>),
+}
+
+impl Run<'_> {
+    fn next_key(&mut self) -> io::Result<Option<Pubkey>> {
+        match self {
+            Run::File(reader) => {
+                let mut bytes = [0u8; 32];
+                match reader.read_exact(&mut bytes) {
+                    Ok(()) => Ok(Some(Pubkey::new(bytes))),
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
+            Run::Memory(keys) => Ok(keys.next().copied()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregate::{Aggregator, RollupPeriod};
+    use crate::fixtures;
+
+    #[test]
+    fn spilled_rollups_match_in_memory_ones() {
+        let block = fixtures::block();
+        let hour = RollupPeriod::Hour.start_of(block.block_time.unwrap());
+        // Half the transactions in each block, overlapping, across two hours.
+        let half = block.transactions.len() / 2;
+        let blocks: Vec<_> = (0..6)
+            .map(|i| {
+                let mut part = block.clone();
+                part.slot = 100 + i as u64;
+                part.block_time = Some(hour + i * 1_000);
+                part.transactions = block.transactions[(i as usize % 3) * half / 2..][..half].to_vec();
+                part
+            })
+            .collect();
+
+        let mut in_memory = Aggregator::new(RollupPeriod::Hour);
+        let spill = SpillDir::create(&std::env::temp_dir()).unwrap();
+        let dir = spill.path().to_path_buf();
+        // Small enough to spill after every block.
+        let mut spilled = Aggregator::new(RollupPeriod::Hour).with_memory_budget(1_000, Box::new(spill));
+        for block in &blocks {
+            in_memory.record_block(block).unwrap();
+            spilled.record_block(block).unwrap();
+        }
+        assert!(fs::read_dir(&dir).unwrap().count() > 2);
+        let expected = in_memory.report().unwrap();
+        assert!(expected.rollups.iter().all(|rollup| rollup.unique_fee_payers > 0));
+        assert_eq!(spilled.report().unwrap(), expected);
+
+        drop(spilled);
+        assert!(!dir.exists());
+    }
+}
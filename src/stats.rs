@@ -0,0 +1,456 @@
+use crate::failure::{group_failures, FailureGroup};
+use crate::labels::LabelRegistry;
+use crate::pubkey::Pubkey;
+use crate::types::{ParsedBlock, ProgramComputeUnits, RewardType};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// ==========================================
+// BLOCK STATS
+// ==========================================
+// Aggregates for triaging a block at a glance: how much compute and fee the
+// transactions used, which programs they called and how many were votes.
+// Compute is also attributed to the programs that spent it, summing each
+// transaction's compute_by_program. Distributions are nearest-rank percentiles over the transactions that
+// report the value, so they're None for an empty block.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub min: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl Percentiles {
+    pub fn of(mut values: Vec<u64>) -> Option<Self> {
+        values.sort_unstable();
+        let rank = |p: usize| values[(p * values.len()).div_ceil(100).max(1) - 1];
+        Some(Percentiles {
+            min: *values.first()?,
+            p50: rank(50),
+            p90: rank(90),
+            p99: rank(99),
+            max: *values.last()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgramCount {
+    pub program_id: Pubkey,
+    // Top-level and inner instructions calling the program.
+    pub invocations: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewardTotal {
+    pub reward_type: RewardType,
+    pub count: usize,
+    // Net: rent and fee rewards can be negative.
+    pub lamports: i64,
+}
+
+// Compute unit prices (micro-lamports per CU, from SetComputeUnitPrice) bid
+// by the block's non-vote transactions, failed ones included since they
+// landed and paid too. Transactions that set no price count as 0 in the
+// distribution; min_price is the lowest price actually set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriorityFeeMarket {
+    pub transactions: usize,
+    pub priced_transactions: usize,
+    pub prices: Option<Percentiles>,
+    pub min_price: Option<u64>,
+}
+
+impl PriorityFeeMarket {
+    pub fn of(block: &ParsedBlock) -> Self {
+        let prices: Vec<u64> = block
+            .transactions
+            .iter()
+            .filter(|tx| !tx.is_vote())
+            .map(|tx| tx.unit_price_micro_lamports.unwrap_or(0))
+            .collect();
+        let set = || prices.iter().copied().filter(|&price| price > 0);
+        PriorityFeeMarket {
+            transactions: prices.len(),
+            priced_transactions: set().count(),
+            min_price: set().min(),
+            prices: Percentiles::of(prices),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockStats {
+    pub transactions: usize,
+    pub successful: usize,
+    pub failed: usize,
+    pub vote_transactions: usize,
+    pub non_vote_transactions: usize,
+    // Signed against a durable nonce (ParsedTransaction.durable_nonce).
+    pub durable_nonce_transactions: usize,
+    pub total_fees: u64,
+    pub fees: Option<Percentiles>,
+    // Only transactions whose meta reports computeUnitsConsumed.
+    pub compute_units: Option<Percentiles>,
+    pub priority_fees: PriorityFeeMarket,
+    // Most invoked first; ties by program ID.
+    pub programs: Vec<ProgramCount>,
+    // Most compute first; ties by program ID.
+    pub compute_by_program: Vec<ProgramComputeUnits>,
+    // Fee, Rent, Staking, Voting, then unknown types by name.
+    pub rewards: Vec<RewardTotal>,
+    // Failed transactions by root cause, most common first.
+    pub failures: Vec<FailureGroup>,
+}
+
+pub fn block_stats(block: &ParsedBlock) -> BlockStats {
+    let (successful, failed, total_fees) = block.transaction_stats();
+    let vote_transactions = block.transactions.iter().filter(|tx| tx.is_vote()).count();
+
+    let mut invocations: HashMap<Pubkey, usize> = HashMap::new();
+    let mut compute: HashMap<Pubkey, u64> = HashMap::new();
+    for tx in &block.transactions {
+        for program in &tx.compute_by_program {
+            *compute.entry(program.program_id).or_default() += program.compute_units;
+        }
+        let inner = tx.inner_instructions.iter().flat_map(|inner| &inner.instructions);
+        for program_id in tx.instructions.iter().map(|ix| ix.program_id).chain(inner.map(|ix| ix.program_id)) {
+            *invocations.entry(program_id).or_default() += 1;
+        }
+    }
+    let mut programs: Vec<ProgramCount> =
+        invocations.into_iter().map(|(program_id, invocations)| ProgramCount { program_id, invocations }).collect();
+    programs.sort_by(|a, b| b.invocations.cmp(&a.invocations).then_with(|| a.program_id.cmp(&b.program_id)));
+
+    let mut rewards: BTreeMap<&RewardType, RewardTotal> = BTreeMap::new();
+    for reward in &block.rewards {
+        let total = rewards
+            .entry(&reward.reward_type)
+            .or_insert_with(|| RewardTotal { reward_type: reward.reward_type.clone(), count: 0, lamports: 0 });
+        total.count += 1;
+        total.lamports += reward.lamports;
+    }
+
+    BlockStats {
+        transactions: block.transactions.len(),
+        successful,
+        failed,
+        vote_transactions,
+        non_vote_transactions: block.transactions.len() - vote_transactions,
+        durable_nonce_transactions: block.transactions.iter().filter(|tx| tx.durable_nonce.is_some()).count(),
+        total_fees,
+        fees: Percentiles::of(block.transactions.iter().map(|tx| tx.fee).collect()),
+        compute_units: Percentiles::of(block.transactions.iter().filter_map(|tx| tx.compute_units_consumed).collect()),
+        priority_fees: PriorityFeeMarket::of(block),
+        programs,
+        compute_by_program: ProgramComputeUnits::ranked(compute),
+        rewards: rewards.into_values().collect(),
+        failures: group_failures(&block.transactions),
+    }
+}
+
+// ==========================================
+// LEADER STATS
+// ==========================================
+// Per-validator totals over the blocks of a run, attributed through
+// ParsedBlock.leader. Blocks with no known leader are only counted.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaderStats {
+    pub leader: Pubkey,
+    pub blocks: usize,
+    pub transactions: usize,
+    pub vote_transactions: usize,
+    pub non_vote_transactions: usize,
+    // Paid by the blocks' transactions.
+    pub transaction_fees: u64,
+    // The leader's fee rewards for those blocks (the share not burned).
+    pub fees_earned: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaderTally {
+    // Most blocks first once sorted; ties by leader.
+    pub leaders: Vec<LeaderStats>,
+    pub unattributed_blocks: usize,
+}
+
+impl LeaderTally {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, block: &ParsedBlock) {
+        let Some(leader) = block.leader else {
+            self.unattributed_blocks += 1;
+            return;
+        };
+        let position = match self.leaders.iter().position(|stats| stats.leader == leader) {
+            Some(position) => position,
+            None => {
+                self.leaders.push(LeaderStats {
+                    leader,
+                    blocks: 0,
+                    transactions: 0,
+                    vote_transactions: 0,
+                    non_vote_transactions: 0,
+                    transaction_fees: 0,
+                    fees_earned: 0,
+                });
+                self.leaders.len() - 1
+            }
+        };
+        let stats = &mut self.leaders[position];
+        let votes = block.transactions.iter().filter(|tx| tx.is_vote()).count();
+        stats.blocks += 1;
+        stats.transactions += block.transactions.len();
+        stats.vote_transactions += votes;
+        stats.non_vote_transactions += block.transactions.len() - votes;
+        stats.transaction_fees += block.transactions.iter().map(|tx| tx.fee).sum::<u64>();
+        stats.fees_earned += block
+            .rewards
+            .iter()
+            .filter(|reward| reward.reward_type == RewardType::Fee && reward.pubkey == leader)
+            .map(|reward| reward.lamports)
+            .sum::<i64>();
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.leaders.iter().map(|stats| stats.blocks).sum::<usize>() + self.unattributed_blocks
+    }
+
+    pub fn sort(&mut self) {
+        self.leaders.sort_by(|a, b| b.blocks.cmp(&a.blocks).then_with(|| a.leader.cmp(&b.leader)));
+    }
+}
+
+// ==========================================
+// PROGRAM LEADERBOARD
+// ==========================================
+// Program usage over any number of blocks (top-programs): invocations split
+// into top-level and CPI, compute units from compute_by_program, and the
+// fees of the transactions calling each program. A transaction calling
+// several programs counts toward each, so fee shares add up to more than 1.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgramRank {
+    Invocations,
+    ComputeUnits,
+    Fees,
+}
+
+impl ProgramRank {
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "invocations" => Ok(ProgramRank::Invocations),
+            "compute" | "cu" => Ok(ProgramRank::ComputeUnits),
+            "fees" => Ok(ProgramRank::Fees),
+            _ => Err(format!("invalid ranking '{}' (expected invocations, compute or fees)", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProgramUsage {
+    pub program_id: Pubkey,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub invocations: usize,
+    pub top_level_invocations: usize,
+    pub cpi_invocations: usize,
+    // Transactions calling the program at least once.
+    pub transactions: usize,
+    pub compute_units: u64,
+    // Paid by those transactions, and as a share of all fees.
+    pub fees: u64,
+    pub fee_share: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProgramLeaderboard {
+    pub blocks: usize,
+    pub transactions: usize,
+    pub total_fees: u64,
+    // Programs seen, before `top` was applied.
+    pub programs_seen: usize,
+    pub programs: Vec<ProgramUsage>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProgramTally {
+    blocks: usize,
+    transactions: usize,
+    total_fees: u64,
+    programs: HashMap<Pubkey, ProgramUsage>,
+}
+
+impl ProgramTally {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_block(&mut self, block: &ParsedBlock) {
+        self.blocks += 1;
+        for tx in &block.transactions {
+            self.transactions += 1;
+            self.total_fees += tx.fee;
+            let inner = tx.inner_instructions.iter().flat_map(|inner| &inner.instructions);
+            let calls = tx.instructions.iter().map(|ix| (ix.program_id, true)).chain(inner.map(|ix| (ix.program_id, false)));
+            let mut called = BTreeSet::new();
+            for (program_id, top_level) in calls {
+                let usage = self.usage(program_id);
+                usage.invocations += 1;
+                usage.top_level_invocations += usize::from(top_level);
+                usage.cpi_invocations += usize::from(!top_level);
+                called.insert(program_id);
+            }
+            for program_id in called {
+                let usage = self.usage(program_id);
+                usage.transactions += 1;
+                usage.fees += tx.fee;
+            }
+            for program in &tx.compute_by_program {
+                self.usage(program.program_id).compute_units += program.compute_units;
+            }
+        }
+    }
+
+    // Ranked by `rank`, ties by program ID, cut to the first `top` when
+    // given; labels come from `labels`.
+    pub fn leaderboard(&self, rank: ProgramRank, top: Option<usize>, labels: &LabelRegistry) -> ProgramLeaderboard {
+        let mut programs: Vec<ProgramUsage> = self.programs.values().cloned().collect();
+        programs.sort_by(|a, b| {
+            let order = match rank {
+                ProgramRank::Invocations => b.invocations.cmp(&a.invocations),
+                ProgramRank::ComputeUnits => b.compute_units.cmp(&a.compute_units),
+                ProgramRank::Fees => b.fees.cmp(&a.fees),
+            };
+            order.then_with(|| a.program_id.cmp(&b.program_id))
+        });
+        programs.truncate(top.unwrap_or(usize::MAX));
+        for usage in &mut programs {
+            usage.label = labels.get(&usage.program_id).map(str::to_string);
+            usage.fee_share = if self.total_fees == 0 { 0.0 } else { usage.fees as f64 / self.total_fees as f64 };
+        }
+        ProgramLeaderboard {
+            blocks: self.blocks,
+            transactions: self.transactions,
+            total_fees: self.total_fees,
+            programs_seen: self.programs.len(),
+            programs,
+        }
+    }
+
+    fn usage(&mut self, program_id: Pubkey) -> &mut ProgramUsage {
+        self.programs.entry(program_id).or_insert_with(|| ProgramUsage {
+            program_id,
+            label: None,
+            invocations: 0,
+            top_level_invocations: 0,
+            cpi_invocations: 0,
+            transactions: 0,
+            compute_units: 0,
+            fees: 0,
+            fee_share: 0.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::types::VOTE_PROGRAM_ID;
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let p = Percentiles::of((1..=200).collect()).unwrap();
+        assert_eq!((p.min, p.p50, p.p90, p.p99, p.max), (1, 100, 180, 198, 200));
+        assert_eq!(Percentiles::of(vec![7]).unwrap(), Percentiles { min: 7, p50: 7, p90: 7, p99: 7, max: 7 });
+        assert_eq!(Percentiles::of(Vec::new()), None);
+    }
+
+    #[test]
+    fn block_stats_count_votes_and_programs() {
+        let block = fixtures::block();
+        let stats = block_stats(block);
+
+        assert_eq!(stats.transactions, block.transactions.len());
+        assert_eq!(stats.vote_transactions + stats.non_vote_transactions, stats.transactions);
+        let vote = stats.programs.iter().find(|p| p.program_id == VOTE_PROGRAM_ID).unwrap();
+        assert!(vote.invocations >= stats.vote_transactions);
+        assert!(stats.programs.windows(2).all(|w| w[0].invocations >= w[1].invocations));
+        assert!(stats.compute_by_program.windows(2).all(|w| w[0].compute_units >= w[1].compute_units));
+        // Attribution never exceeds what the transactions consumed.
+        let attributed: u64 = stats.compute_by_program.iter().map(|p| p.compute_units).sum();
+        assert!(attributed > 0);
+        assert!(attributed <= block.transactions.iter().filter_map(|tx| tx.compute_units_consumed).sum());
+        assert_eq!(stats.rewards, [RewardTotal {
+            reward_type: RewardType::Fee,
+            count: 1,
+            lamports: block.rewards[0].lamports,
+        }]);
+        let fees = stats.fees.unwrap();
+        assert!(fees.min <= fees.p50 && fees.p50 <= fees.p99 && fees.p99 <= fees.max);
+        let market = stats.priority_fees;
+        assert_eq!(market.transactions, stats.non_vote_transactions);
+        assert!(market.priced_transactions > 0 && market.priced_transactions <= market.transactions);
+        assert!(market.min_price.unwrap() > 0 && market.min_price.unwrap() <= market.prices.unwrap().max);
+    }
+
+    #[test]
+    fn leaders_come_from_the_fee_reward_and_are_tallied() {
+        let block = fixtures::block();
+        assert_eq!(block.leader, Some(block.rewards[0].pubkey));
+
+        let mut other = block.clone();
+        other.leader = Some(Pubkey::from_str_const("Vote111111111111111111111111111111111111111"));
+        let mut unknown = block.clone();
+        unknown.leader = None;
+        let mut tally = LeaderTally::new();
+        for block in [&other, block, &unknown, block] {
+            tally.record(block);
+        }
+        tally.sort();
+
+        assert_eq!(tally.blocks(), 4);
+        assert_eq!(tally.unattributed_blocks, 1);
+        let stats = block_stats(block);
+        let top = &tally.leaders[0];
+        assert_eq!((top.leader, top.blocks), (block.rewards[0].pubkey, 2));
+        assert_eq!(top.vote_transactions, stats.vote_transactions * 2);
+        assert_eq!(top.transaction_fees, stats.total_fees * 2);
+        assert_eq!(top.fees_earned, block.rewards[0].lamports * 2);
+        // Another validator's block pays it no fee reward.
+        assert_eq!((tally.leaders[1].blocks, tally.leaders[1].fees_earned), (1, 0));
+    }
+
+    #[test]
+    fn program_leaderboard_ranks_and_labels_programs() {
+        let block = fixtures::block();
+        let mut tally = ProgramTally::new();
+        tally.record_block(block);
+        tally.record_block(block);
+
+        let stats = block_stats(block);
+        let labels = LabelRegistry::builtins();
+        let by_invocations = tally.leaderboard(ProgramRank::Invocations, Some(3), &labels);
+        assert_eq!((by_invocations.blocks, by_invocations.transactions), (2, block.transactions.len() * 2));
+        assert_eq!(by_invocations.programs.len(), 3);
+        assert_eq!(by_invocations.programs_seen, stats.programs.len());
+        let top = &by_invocations.programs[0];
+        assert_eq!((top.program_id, top.invocations), (stats.programs[0].program_id, stats.programs[0].invocations * 2));
+        assert_eq!(top.invocations, top.top_level_invocations + top.cpi_invocations);
+        assert_eq!(top.label.as_deref(), Some("Vote Program"));
+
+        let by_compute = tally.leaderboard(ProgramRank::ComputeUnits, None, &labels);
+        assert_eq!(by_compute.programs[0].program_id, stats.compute_by_program[0].program_id);
+        assert_eq!(by_compute.programs[0].compute_units, stats.compute_by_program[0].compute_units * 2);
+        let by_fees = tally.leaderboard(ProgramRank::Fees, None, &labels);
+        assert!(by_fees.programs.windows(2).all(|pair| pair[0].fees >= pair[1].fees));
+        assert!(by_fees.programs.iter().all(|usage| usage.fee_share > 0.0 && usage.fee_share <= 1.0));
+    }
+}
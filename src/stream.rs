@@ -0,0 +1,578 @@
+use crate::error::ParseError;
+use crate::parser::ParseOptions;
+use crate::rpc::{BlockFetch, RpcClient};
+use crate::source::{parse_raw_block, BlockSource, RawBlock, RpcSource};
+use crate::types::{ParseReport, ParsedBlock, RpcBlockResult};
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// ==========================================
+// SLOT RANGE STREAMING
+// ==========================================
+// Walks slots in order, fetching each block through an RpcSource and parsing
+// it. Skipped slots are passed over silently. With no end slot the stream
+// follows the tip, polling getSlot whenever it catches up.
+//
+// With a concurrency above one, fetching moves to a pipeline of threads
+// (see FETCH PIPELINE below); blocks still come out in slot order.
+
+pub struct StreamedBlock {
+    pub slot: u64,
+    pub block: ParsedBlock,
+    pub report: ParseReport,
+}
+
+impl StreamedBlock {
+    pub(crate) fn parse(slot: u64, block: RpcBlockResult, options: &ParseOptions) -> Result<Self, ParseError> {
+        let parsed = parse_raw_block(RawBlock { slot: Some(slot), block }, options)?;
+        Ok(StreamedBlock { slot, block: parsed.block, report: parsed.report })
+    }
+}
+
+pub struct BlockStream {
+    source: RpcSource,
+    options: ParseOptions,
+    concurrency: usize,
+    // Started on the first call to next() when concurrency > 1.
+    pipeline: Option<Receiver<PipelineItem>>,
+}
+
+impl BlockStream {
+    // Inclusive range; `end_slot: None` follows the chain tip indefinitely.
+    pub fn new(client: RpcClient, start_slot: u64, end_slot: Option<u64>) -> Self {
+        BlockStream {
+            source: RpcSource::new(client, start_slot, end_slot),
+            options: ParseOptions::default(),
+            concurrency: 1,
+            pipeline: None,
+        }
+    }
+
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.source = self.source.with_poll_interval(poll_interval);
+        self
+    }
+
+    // How many getBlock requests may be in flight ahead of the parser.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    // Every slot before this one has been yielded, skipped or reported as an
+    // error.
+    pub fn next_slot(&self) -> u64 {
+        self.source.next_slot()
+    }
+}
+
+impl Iterator for BlockStream {
+    type Item = Result<StreamedBlock, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.concurrency > 1 {
+            return self.next_from_pipeline();
+        }
+        let raw = match self.source.next_block()? {
+            Ok(raw) => raw,
+            Err(e) => return Some(Err(e)),
+        };
+        let slot = raw.slot.expect("RpcSource knows each block's slot");
+        Some(StreamedBlock::parse(slot, raw.block, &self.options).map_err(Into::into))
+    }
+}
+
+// ==========================================
+// FETCH PIPELINE
+// ==========================================
+// Three stages joined by bounded channels: `concurrency` fetch workers, a
+// coordinator that hands out slots, puts results back in slot order and
+// parses them, and the stream's consumer. A slow consumer fills the parsed
+// channel, which stalls the coordinator, which stops handing out slots. A
+// slow slot holds back the rest the same way: slots are only handed out up
+// to PIPELINE_WINDOW per worker past the next one to yield, so at most that
+// many raw blocks are ever waiting in the reorder buffer. The threads exit
+// once the range is done or the stream is dropped.
+
+const PIPELINE_WINDOW: u64 = 4;
+
+enum PipelineItem {
+    Block(Box<StreamedBlock>),
+    Skipped(u64),
+    Failed(u64, String),
+    // Not tied to a slot (getSlot failing while following the tip).
+    Error(String),
+}
+
+// Where the pipeline gets blocks from: the RPC client, or a stand-in in
+// tests. Errors are strings so they can cross threads.
+pub(crate) trait SlotFetcher: Send + Sync + 'static {
+    fn fetch_block(&self, slot: u64) -> Result<BlockFetch, String>;
+    fn get_slot(&self) -> Result<u64, String>;
+}
+
+impl SlotFetcher for RpcClient {
+    fn fetch_block(&self, slot: u64) -> Result<BlockFetch, String> {
+        RpcClient::fetch_block(self, slot).map_err(|e| e.to_string())
+    }
+
+    fn get_slot(&self) -> Result<u64, String> {
+        RpcClient::get_slot(self).map_err(|e| e.to_string())
+    }
+}
+
+struct Pipeline<S> {
+    source: Arc<S>,
+    options: ParseOptions,
+    end_slot: Option<u64>,
+    poll_interval: Duration,
+    concurrency: usize,
+}
+
+impl BlockStream {
+    fn next_from_pipeline(&mut self) -> Option<Result<StreamedBlock, Box<dyn Error>>> {
+        if self.pipeline.is_none() {
+            let pipeline = Pipeline {
+                source: Arc::new(self.source.client.clone()),
+                options: self.options.clone(),
+                end_slot: self.source.end_slot,
+                poll_interval: self.source.poll_interval,
+                concurrency: self.concurrency,
+            };
+            self.pipeline = Some(pipeline.start(self.source.next_slot));
+        }
+        let items = self.pipeline.as_ref()?;
+        loop {
+            match items.recv().ok()? {
+                PipelineItem::Block(streamed) => {
+                    self.source.next_slot = streamed.slot + 1;
+                    return Some(Ok(*streamed));
+                }
+                PipelineItem::Skipped(slot) => self.source.next_slot = slot + 1,
+                PipelineItem::Failed(slot, e) => {
+                    self.source.next_slot = slot + 1;
+                    return Some(Err(e.into()));
+                }
+                PipelineItem::Error(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
+
+impl<S: SlotFetcher> Pipeline<S> {
+    fn start(self, start_slot: u64) -> Receiver<PipelineItem> {
+        let (jobs, job_queue) = mpsc::sync_channel::<u64>(self.concurrency);
+        let (results, fetched) = mpsc::sync_channel(self.concurrency);
+        let (parsed, items) = mpsc::sync_channel(self.concurrency);
+
+        let job_queue = Arc::new(Mutex::new(job_queue));
+        for _ in 0..self.concurrency {
+            let (source, job_queue, results) = (self.source.clone(), job_queue.clone(), results.clone());
+            let (follow, poll_interval) = (self.end_slot.is_none(), self.poll_interval);
+            thread::spawn(move || fetch_worker(&*source, &job_queue, &results, follow, poll_interval));
+        }
+        thread::spawn(move || self.coordinate(start_slot, jobs, fetched, parsed));
+        items
+    }
+
+    fn coordinate(
+        &self,
+        start_slot: u64,
+        jobs: SyncSender<u64>,
+        fetched: Receiver<(u64, Result<BlockFetch, String>)>,
+        parsed: SyncSender<PipelineItem>,
+    ) {
+        let mut next_dispatch = start_slot;
+        let mut next_expected = start_slot;
+        let mut in_flight = 0;
+        let mut tip = 0;
+        let mut pending = BTreeMap::new();
+        let window = self.concurrency as u64 * PIPELINE_WINDOW;
+
+        loop {
+            // The next slot to yield is always among those in flight, so a
+            // full window still has a fetch to wait for.
+            while in_flight < self.concurrency
+                && next_dispatch - next_expected < window
+                && self.end_slot.is_none_or(|end| next_dispatch <= end)
+            {
+                if self.end_slot.is_none() && next_dispatch > tip {
+                    // Let the outstanding fetches land before polling.
+                    if in_flight > 0 {
+                        break;
+                    }
+                    match self.source.get_slot() {
+                        Ok(slot) => tip = slot,
+                        Err(e) => {
+                            if parsed.send(PipelineItem::Error(e)).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    if next_dispatch > tip {
+                        thread::sleep(self.poll_interval);
+                        continue;
+                    }
+                }
+                if jobs.send(next_dispatch).is_err() {
+                    return;
+                }
+                in_flight += 1;
+                next_dispatch += 1;
+            }
+            if in_flight == 0 {
+                return;
+            }
+
+            let Ok((slot, fetch)) = fetched.recv() else {
+                return;
+            };
+            in_flight -= 1;
+            pending.insert(slot, fetch);
+            while let Some(fetch) = pending.remove(&next_expected) {
+                let slot = next_expected;
+                next_expected += 1;
+                let item = match fetch {
+                    Ok(BlockFetch::Block(raw_block)) => match StreamedBlock::parse(slot, raw_block, &self.options) {
+                        Ok(streamed) => PipelineItem::Block(Box::new(streamed)),
+                        Err(e) => PipelineItem::Failed(slot, e.to_string()),
+                    },
+                    Ok(BlockFetch::SlotSkipped) => {
+                        log::debug!(slot; "slot skipped");
+                        PipelineItem::Skipped(slot)
+                    }
+                    Ok(BlockFetch::BlockUnavailable) => PipelineItem::Failed(slot, format!("block for slot {} is not available", slot)),
+                    Err(e) => PipelineItem::Failed(slot, e),
+                };
+                if parsed.send(item).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// Fetches slots off the shared queue until it closes. When following the
+// tip, a block that isn't queryable yet is retried in place.
+fn fetch_worker<S: SlotFetcher>(
+    source: &S,
+    job_queue: &Mutex<Receiver<u64>>,
+    results: &SyncSender<(u64, Result<BlockFetch, String>)>,
+    follow: bool,
+    poll_interval: Duration,
+) {
+    loop {
+        let Ok(slot) = job_queue.lock().unwrap().recv() else {
+            return;
+        };
+        let fetch = loop {
+            match source.fetch_block(slot) {
+                Ok(BlockFetch::BlockUnavailable) if follow => thread::sleep(poll_interval),
+                fetch => break fetch,
+            }
+        };
+        if results.send((slot, fetch)).is_err() {
+            return;
+        }
+    }
+}
+
+// ==========================================
+// SLOT ORDER AND GAP REPAIR
+// ==========================================
+// Subscriptions deliver blocks as the node sees them: now and then out of
+// order, and with a slot missing when a notification is dropped. SlotOrder
+// sits between a subscription and the sinks and hands blocks on in strictly
+// increasing slot order. Early arrivals wait in a reorder buffer; once a
+// block `window` slots past a missing one has arrived, the missing slot is
+// fetched over RPC (a skipped slot just closes the gap). Blocks at or before
+// a slot already handed on are dropped as duplicates. Ordering starts at the
+// first block received.
+
+pub struct SlotOrder<I> {
+    blocks: I,
+    source: Box<dyn SlotFetcher>,
+    options: ParseOptions,
+    window: u64,
+    // None until the first block arrives.
+    next_slot: Option<u64>,
+    pending: BTreeMap<u64, StreamedBlock>,
+    ready: VecDeque<Result<StreamedBlock, Box<dyn Error>>>,
+    done: bool,
+}
+
+impl<I> SlotOrder<I>
+where
+    I: Iterator<Item = Result<StreamedBlock, Box<dyn Error>>>,
+{
+    // `client` fetches the slots the subscription missed.
+    pub fn new(blocks: I, client: RpcClient) -> Self {
+        Self::with_source(blocks, Box::new(client))
+    }
+
+    fn with_source(blocks: I, source: Box<dyn SlotFetcher>) -> Self {
+        SlotOrder {
+            blocks,
+            source,
+            options: ParseOptions::default(),
+            window: 4,
+            next_slot: None,
+            pending: BTreeMap::new(),
+            ready: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    // For parsing fetched blocks; use the subscription's options.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // How far past a missing slot blocks may arrive before it's fetched.
+    pub fn with_window(mut self, window: u64) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    fn accept(&mut self, streamed: StreamedBlock) {
+        let next = *self.next_slot.get_or_insert(streamed.slot);
+        if streamed.slot < next {
+            log::debug!(slot = streamed.slot; "dropping block for a slot already handled");
+            return;
+        }
+        self.pending.insert(streamed.slot, streamed);
+        self.release(false);
+    }
+
+    // Moves blocks from the buffer to the ready queue while the next slot is
+    // there or can be repaired; with `flush`, repairs every remaining gap.
+    fn release(&mut self, flush: bool) {
+        let Some(mut next) = self.next_slot else {
+            return;
+        };
+        while let Some(&last) = self.pending.keys().next_back() {
+            match self.pending.remove(&next) {
+                Some(streamed) => self.ready.push_back(Ok(streamed)),
+                None if flush || last - next >= self.window => self.repair(next),
+                None => break,
+            }
+            next += 1;
+        }
+        self.next_slot = Some(next);
+    }
+
+    fn repair(&mut self, slot: u64) {
+        let item = match self.source.fetch_block(slot) {
+            Ok(BlockFetch::Block(raw_block)) => match StreamedBlock::parse(slot, raw_block, &self.options) {
+                Ok(streamed) => {
+                    log::info!(slot; "fetched slot missed by the subscription");
+                    Ok(streamed)
+                }
+                Err(e) => Err(format!("slot {}: {}", slot, e).into()),
+            },
+            Ok(BlockFetch::SlotSkipped) => {
+                log::debug!(slot; "slot skipped");
+                return;
+            }
+            Ok(BlockFetch::BlockUnavailable) => Err(format!("slot {} was missed and its block is not available", slot).into()),
+            Err(e) => Err(format!("slot {} was missed and could not be fetched: {}", slot, e).into()),
+        };
+        self.ready.push_back(item);
+    }
+}
+
+impl<I> Iterator for SlotOrder<I>
+where
+    I: Iterator<Item = Result<StreamedBlock, Box<dyn Error>>>,
+{
+    type Item = Result<StreamedBlock, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.ready.pop_front() {
+                return Some(item);
+            }
+            if self.done {
+                return None;
+            }
+            match self.blocks.next() {
+                Some(Ok(streamed)) => self.accept(streamed),
+                // Not tied to a slot, so passed straight on.
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.done = true;
+                    self.release(true);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::RpcBlockResponse;
+
+    // Later slots answer sooner, so fetches complete out of order.
+    struct FakeSource {
+        block: String,
+    }
+
+    impl SlotFetcher for FakeSource {
+        fn fetch_block(&self, slot: u64) -> Result<BlockFetch, String> {
+            thread::sleep(Duration::from_millis(40 - slot % 8 * 5));
+            match slot {
+                13 | 18 => Ok(BlockFetch::SlotSkipped),
+                16 => Err("connection reset".to_string()),
+                _ => Ok(BlockFetch::Block(serde_json::from_str::<RpcBlockResponse>(&self.block).unwrap().result)),
+            }
+        }
+
+        fn get_slot(&self) -> Result<u64, String> {
+            Ok(u64::MAX)
+        }
+    }
+
+    #[test]
+    fn pipeline_delivers_in_slot_order() {
+        let pipeline = Pipeline {
+            source: Arc::new(FakeSource { block: std::fs::read_to_string("src/json/empty_block.json").unwrap() }),
+            options: ParseOptions::default(),
+            end_slot: Some(29),
+            poll_interval: Duration::from_millis(10),
+            concurrency: 8,
+        };
+        let items: Vec<String> = pipeline
+            .start(10)
+            .into_iter()
+            .map(|item| match item {
+                PipelineItem::Block(streamed) => {
+                    assert_eq!(streamed.block.slot, streamed.slot);
+                    format!("block {}", streamed.slot)
+                }
+                PipelineItem::Skipped(slot) => format!("skipped {}", slot),
+                PipelineItem::Failed(slot, e) => format!("failed {}: {}", slot, e),
+                PipelineItem::Error(e) => e,
+            })
+            .collect();
+
+        let expected: Vec<String> = (10..=29)
+            .map(|slot| match slot {
+                13 | 18 => format!("skipped {}", slot),
+                16 => "failed 16: connection reset".to_string(),
+                _ => format!("block {}", slot),
+            })
+            .collect();
+        assert_eq!(items, expected);
+    }
+
+    // Slot 10 answers last; records how far dispatch got meanwhile.
+    struct SlowSource {
+        block: String,
+        fetched: Mutex<Vec<u64>>,
+        furthest_while_slow: Mutex<u64>,
+    }
+
+    impl SlotFetcher for SlowSource {
+        fn fetch_block(&self, slot: u64) -> Result<BlockFetch, String> {
+            if slot == 10 {
+                thread::sleep(Duration::from_millis(300));
+                *self.furthest_while_slow.lock().unwrap() = self.fetched.lock().unwrap().iter().copied().max().unwrap_or(0);
+            } else {
+                self.fetched.lock().unwrap().push(slot);
+            }
+            Ok(BlockFetch::Block(serde_json::from_str::<RpcBlockResponse>(&self.block).unwrap().result))
+        }
+
+        fn get_slot(&self) -> Result<u64, String> {
+            Ok(u64::MAX)
+        }
+    }
+
+    #[test]
+    fn pipeline_holds_dispatch_behind_a_slow_slot() {
+        let source = Arc::new(SlowSource {
+            block: std::fs::read_to_string("src/json/empty_block.json").unwrap(),
+            fetched: Mutex::new(Vec::new()),
+            furthest_while_slow: Mutex::new(0),
+        });
+        let pipeline = Pipeline {
+            source: source.clone(),
+            options: ParseOptions::default(),
+            end_slot: Some(200),
+            poll_interval: Duration::from_millis(10),
+            concurrency: 2,
+        };
+        let slots: Vec<u64> = pipeline
+            .start(10)
+            .into_iter()
+            .map(|item| match item {
+                PipelineItem::Block(streamed) => streamed.slot,
+                _ => panic!("unexpected item"),
+            })
+            .collect();
+        assert_eq!(slots, (10..=200).collect::<Vec<_>>());
+        // Everything up to the window, and nothing past it.
+        assert_eq!(*source.furthest_while_slow.lock().unwrap(), 10 + 2 * PIPELINE_WINDOW - 1);
+    }
+
+    struct GapSource {
+        block: String,
+        fetched: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl SlotFetcher for GapSource {
+        fn fetch_block(&self, slot: u64) -> Result<BlockFetch, String> {
+            self.fetched.lock().unwrap().push(slot);
+            match slot {
+                13 => Ok(BlockFetch::SlotSkipped),
+                17 => Err("connection reset".to_string()),
+                _ => Ok(BlockFetch::Block(serde_json::from_str::<RpcBlockResponse>(&self.block).unwrap().result)),
+            }
+        }
+
+        fn get_slot(&self) -> Result<u64, String> {
+            Ok(u64::MAX)
+        }
+    }
+
+    #[test]
+    fn slot_order_buffers_early_blocks_and_repairs_gaps() {
+        let block = std::fs::read_to_string("src/json/empty_block.json").unwrap();
+        let parsed = crate::parser::parse_block(serde_json::from_str::<RpcBlockResponse>(&block).unwrap().result).unwrap();
+        let arrivals = [10, 12, 11, 14, 16, 15, 18, 21, 11].map(|slot| {
+            let mut block = parsed.clone();
+            block.slot = slot;
+            Ok(StreamedBlock { slot, block, report: ParseReport::default() })
+        });
+        let fetched = Arc::new(Mutex::new(Vec::new()));
+        let source = GapSource { block, fetched: fetched.clone() };
+
+        let items: Vec<String> = SlotOrder::with_source(arrivals.into_iter(), Box::new(source))
+            .with_window(2)
+            .map(|item| match item {
+                Ok(streamed) => {
+                    assert_eq!(streamed.block.slot, streamed.slot);
+                    format!("block {}", streamed.slot)
+                }
+                Err(e) => e.to_string(),
+            })
+            .collect();
+
+        let mut expected: Vec<String> = [10, 11, 12, 14, 15, 16].map(|slot| format!("block {}", slot)).to_vec();
+        expected.push("slot 17 was missed and could not be fetched: connection reset".to_string());
+        expected.extend([18, 19, 20, 21].map(|slot| format!("block {}", slot)));
+        assert_eq!(items, expected);
+        assert_eq!(*fetched.lock().unwrap(), [13, 17, 19, 20]);
+    }
+}
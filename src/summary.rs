@@ -0,0 +1,179 @@
+use crate::epoch::format_rfc3339;
+use crate::metrics::Metrics;
+use crate::shutdown;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// ==========================================
+// RUN SUMMARY
+// ==========================================
+// What a run did, written as JSON when it ends (--summary) so a scheduler
+// or backfill driver can check the result without scraping logs. The
+// counts come from the process-wide metrics, so they cover everything the
+// run parsed and wrote, whichever command it was.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunOutcome {
+    Completed,
+    // Stopped early by Ctrl-C or SIGTERM, after flushing the outputs.
+    Interrupted,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub command: String,
+    pub outcome: RunOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub started_at: String,
+    pub finished_at: String,
+    pub elapsed_secs: f64,
+    pub blocks: u64,
+    pub transactions: u64,
+    // Blocks handed to the outputs, after filters and deduplication.
+    pub blocks_written: u64,
+    pub duplicate_transactions: u64,
+    pub blocks_per_second: f64,
+    pub transactions_per_second: f64,
+    // "parse", "rpc.<method>" and "sink.<name>"; categories without errors
+    // are left out.
+    pub errors: BTreeMap<String, u64>,
+    pub sinks: BTreeMap<String, SinkSummary>,
+    // Slots and block times of the blocks written; None if there were none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<CoveredRange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SinkSummary {
+    pub writes: u64,
+    pub errors: u64,
+    pub seconds: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CoveredRange {
+    pub first_slot: u64,
+    pub last_slot: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_block_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_block_time: Option<i64>,
+}
+
+impl RunSummary {
+    // A run of `command` started at `started_at` and lasting `elapsed`,
+    // ending with `result`.
+    pub fn collect(
+        command: &str,
+        metrics: &Metrics,
+        started_at: SystemTime,
+        elapsed: Duration,
+        result: &Result<(), Box<dyn Error>>,
+    ) -> Self {
+        let (outcome, error) = match result {
+            Err(e) => (RunOutcome::Failed, Some(e.to_string())),
+            Ok(()) if shutdown::requested() => (RunOutcome::Interrupted, None),
+            Ok(()) => (RunOutcome::Completed, None),
+        };
+
+        let mut errors = BTreeMap::new();
+        if metrics.parse_errors() > 0 {
+            errors.insert("parse".to_string(), metrics.parse_errors());
+        }
+        for (method, count) in metrics.rpc_errors() {
+            errors.insert(format!("rpc.{}", method), count);
+        }
+        let sinks: BTreeMap<String, SinkSummary> = metrics
+            .sink_writes()
+            .into_iter()
+            .map(|(sink, writes)| (sink, SinkSummary { writes: writes.writes, errors: writes.errors, seconds: writes.seconds }))
+            .collect();
+        for (sink, writes) in &sinks {
+            if writes.errors > 0 {
+                errors.insert(format!("sink.{}", sink), writes.errors);
+            }
+        }
+
+        let written = metrics.written_blocks();
+        let range = written.first_slot.zip(written.last_slot).map(|(first_slot, last_slot)| CoveredRange {
+            first_slot,
+            last_slot,
+            first_block_time: written.first_block_time,
+            last_block_time: written.last_block_time,
+        });
+
+        let seconds = elapsed.as_secs_f64();
+        let rate = |count: u64| if seconds > 0.0 { count as f64 / seconds } else { 0.0 };
+        RunSummary {
+            command: command.to_string(),
+            outcome,
+            error,
+            started_at: format_rfc3339(unix_seconds(started_at)),
+            finished_at: format_rfc3339(unix_seconds(started_at + elapsed)),
+            elapsed_secs: seconds,
+            blocks: metrics.blocks_parsed(),
+            transactions: metrics.transactions_parsed(),
+            blocks_written: written.count,
+            duplicate_transactions: metrics.duplicate_transactions(),
+            blocks_per_second: rate(metrics.blocks_parsed()),
+            transactions_per_second: rate(metrics.transactions_parsed()),
+            errors,
+            sinks,
+            range,
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+            .map_err(|e| format!("run summary {}: {}", path.display(), e).into())
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).map(|since| since.as_secs() as i64).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_counts_errors_and_range() {
+        let metrics = Metrics::new();
+        metrics.record_block(10);
+        metrics.record_block(30);
+        metrics.record_parse_errors(2);
+        metrics.record_rpc("getBlock", Duration::from_millis(20), false);
+        metrics.record_sink_write("postgres", Duration::from_millis(5), true);
+        metrics.record_sink_write("postgres", Duration::from_millis(5), false);
+        metrics.record_written_block(101, Some(1_700_000_001));
+        metrics.record_written_block(100, None);
+
+        let started_at = UNIX_EPOCH + Duration::from_secs(1_763_823_185);
+        let summary = RunSummary::collect("backfill", &metrics, started_at, Duration::from_secs(4), &Ok(()));
+        assert_eq!(summary.outcome, RunOutcome::Completed);
+        assert_eq!(summary.started_at, "2025-11-22T14:53:05Z");
+        assert_eq!(summary.finished_at, "2025-11-22T14:53:09Z");
+        assert_eq!((summary.blocks, summary.transactions, summary.blocks_written), (2, 40, 2));
+        assert_eq!(summary.transactions_per_second, 10.0);
+        assert_eq!(
+            summary.errors,
+            BTreeMap::from([("parse".to_string(), 2), ("rpc.getBlock".to_string(), 1), ("sink.postgres".to_string(), 1)])
+        );
+        assert_eq!(summary.sinks["postgres"].writes, 2);
+        assert_eq!(
+            summary.range,
+            Some(CoveredRange { first_slot: 100, last_slot: 101, first_block_time: Some(1_700_000_001), last_block_time: Some(1_700_000_001) })
+        );
+
+        let failed = RunSummary::collect("backfill", &metrics, started_at, Duration::ZERO, &Err("boom".into()));
+        assert_eq!((failed.outcome, failed.error.as_deref()), (RunOutcome::Failed, Some("boom")));
+    }
+}
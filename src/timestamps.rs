@@ -0,0 +1,91 @@
+use crate::types::ParsedBlock;
+
+// ==========================================
+// TRANSACTION TIMESTAMPS
+// ==========================================
+// A block carries one blockTime, in whole seconds, but analytics usually
+// want a time per transaction. `stamp_transactions` fills in
+// ParsedTransaction.timestamp_ms from it: either blockTime itself on every
+// transaction, or blockTime spread across the slot by each transaction's
+// position in the block. Interpolated times only order transactions within
+// a block; the validator doesn't record when each one executed.
+//
+// Which mode applies is chosen per sink (see sink::FanOut); the parser
+// leaves timestamp_ms unset.
+
+// The target slot time. Slots run a little longer in practice, so a
+// block's last transactions stay just short of the next block's first.
+pub const SLOT_DURATION_MS: i64 = 400;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampMode {
+    // Leave timestamp_ms unset.
+    #[default]
+    None,
+    // blockTime on every transaction.
+    BlockTime,
+    // blockTime plus the transaction's share of the slot.
+    Interpolated,
+}
+
+impl TimestampMode {
+    pub fn from_arg(value: &str) -> Result<Self, String> {
+        match value {
+            "none" => Ok(TimestampMode::None),
+            "block-time" => Ok(TimestampMode::BlockTime),
+            "interpolated" => Ok(TimestampMode::Interpolated),
+            other => Err(format!("unknown timestamp mode '{}' (expected none, block-time or interpolated)", other)),
+        }
+    }
+}
+
+// Blocks without a blockTime are left alone. The position is the
+// transaction's index before any filter, out of the block's transaction
+// count as far as it can still be told after filtering.
+pub fn stamp_transactions(block: &mut ParsedBlock, mode: TimestampMode) {
+    let Some(block_time) = block.block_time else { return };
+    let base = block_time * 1000;
+    let total = block
+        .transactions
+        .iter()
+        .filter_map(|tx| tx.index_in_block)
+        .map(|index| index + 1)
+        .max()
+        .unwrap_or(0)
+        .max(block.transactions.len() + block.excluded_votes.unwrap_or(0))
+        .max(1) as i64;
+    for (position, tx) in block.transactions.iter_mut().enumerate() {
+        tx.timestamp_ms = match mode {
+            TimestampMode::None => None,
+            TimestampMode::BlockTime => Some(base),
+            TimestampMode::Interpolated => {
+                let position = tx.index_in_block.unwrap_or(position) as i64;
+                Some(base + position * SLOT_DURATION_MS / total)
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn interpolated_timestamps_spread_across_the_slot() {
+        let mut block = fixtures::block().clone();
+        let base = block.block_time.unwrap() * 1000;
+
+        stamp_transactions(&mut block, TimestampMode::BlockTime);
+        assert!(block.transactions.iter().all(|tx| tx.timestamp_ms == Some(base)));
+
+        stamp_transactions(&mut block, TimestampMode::Interpolated);
+        let stamps: Vec<i64> = block.transactions.iter().map(|tx| tx.timestamp_ms.unwrap()).collect();
+        assert_eq!(stamps[0], base);
+        assert!(stamps.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(stamps.iter().all(|stamp| *stamp < base + SLOT_DURATION_MS));
+
+        stamp_transactions(&mut block, TimestampMode::None);
+        assert!(block.transactions.iter().all(|tx| tx.timestamp_ms.is_none()));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::pubkey::Pubkey;
+#[cfg(feature = "native")]
+use crate::rpc::RpcClient;
+use crate::transfers::unresolved_token_accounts;
+use crate::types::ParsedTransaction;
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(feature = "native")]
+use std::sync::Mutex;
+
+// ==========================================
+// TOKEN OWNER RESOLUTION
+// ==========================================
+// Most token accounts a transfer touches are in the transaction's token
+// balances, owner included (see transfers::TokenAccounts). Older responses
+// leave the owner out, though, and accounts opened elsewhere and closed in
+// the transaction aren't listed at all. For those the parser asks a
+// TokenOwnerResolver and records what it finds in
+// ParsedTransaction.token_owners.
+//
+// The RPC resolver reads the accounts as they are now, so an account
+// closed since, or whose owner has changed, is missed or misattributed.
+
+// SPL Token account layout: mint (32 bytes), then owner (32 bytes).
+const TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
+const TOKEN_ACCOUNT_SIZE: usize = 165;
+// Token-2022 accounts with extensions are longer, with this account type
+// byte right after the base layout (1 marks a mint).
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+pub trait TokenOwnerResolver: Send + Sync + fmt::Debug {
+    // The wallet owning a token account, or None if it can't be found.
+    fn owner(&self, account: &Pubkey) -> Option<Pubkey>;
+}
+
+// Fixed map of token accounts to owners, e.g. from an earlier run.
+#[derive(Debug, Default, Clone)]
+pub struct StaticTokenOwners {
+    owners: HashMap<Pubkey, Pubkey>,
+}
+
+impl StaticTokenOwners {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, account: Pubkey, owner: Pubkey) {
+        self.owners.insert(account, owner);
+    }
+}
+
+impl TokenOwnerResolver for StaticTokenOwners {
+    fn owner(&self, account: &Pubkey) -> Option<Pubkey> {
+        self.owners.get(account).copied()
+    }
+}
+
+// Reads each account with getAccountInfo, remembering the answer (found or
+// not) for the rest of the run; the cache is dropped once it holds
+// `capacity` accounts.
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct RpcTokenOwnerResolver {
+    client: RpcClient,
+    capacity: usize,
+    cache: Mutex<HashMap<Pubkey, Option<Pubkey>>>,
+}
+
+#[cfg(feature = "native")]
+pub const DEFAULT_OWNER_CACHE_CAPACITY: usize = 100_000;
+
+#[cfg(feature = "native")]
+impl RpcTokenOwnerResolver {
+    pub fn new(client: RpcClient) -> Self {
+        RpcTokenOwnerResolver { client, capacity: DEFAULT_OWNER_CACHE_CAPACITY, cache: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+}
+
+#[cfg(feature = "native")]
+impl TokenOwnerResolver for RpcTokenOwnerResolver {
+    fn owner(&self, account: &Pubkey) -> Option<Pubkey> {
+        if let Some(owner) = self.cache.lock().unwrap().get(account) {
+            return *owner;
+        }
+        let owner = match self.client.get_account_data(&account.to_string()) {
+            Ok(data) => data.as_deref().and_then(decode_token_account_owner),
+            Err(e) => {
+                // Not cached, so a later transaction tries again.
+                log::warn!(account:% = account, error:% = e; "failed to fetch token account");
+                return None;
+            }
+        };
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= self.capacity {
+            cache.clear();
+        }
+        cache.insert(*account, owner);
+        owner
+    }
+}
+
+// The owner field of SPL Token or Token-2022 account data; None for
+// anything else, mints included.
+pub fn decode_token_account_owner(data: &[u8]) -> Option<Pubkey> {
+    if data.len() < TOKEN_ACCOUNT_SIZE || (data.len() > TOKEN_ACCOUNT_SIZE && data[TOKEN_ACCOUNT_SIZE] != ACCOUNT_TYPE_ACCOUNT) {
+        return None;
+    }
+    Pubkey::try_from(&data[TOKEN_ACCOUNT_OWNER_OFFSET..TOKEN_ACCOUNT_OWNER_OFFSET + 32]).ok()
+}
+
+// Looks up the owners `tx` doesn't name itself into tx.token_owners.
+pub fn resolve_token_owners(tx: &mut ParsedTransaction, resolver: &dyn TokenOwnerResolver) {
+    for account in unresolved_token_accounts(tx) {
+        if let Some(owner) = resolver.owner(&account) {
+            tx.token_owners.insert(account, owner);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::parser::{parse_block, parse_block_with_options, ParseOptions};
+    use crate::transfers::extract_token_transfers;
+    use std::sync::Arc;
+
+    #[test]
+    fn owners_missing_from_token_balances_come_from_the_resolver() {
+        let raw = fixtures::raw_block();
+        let expected: Vec<_> = parse_block(raw.result)
+            .unwrap()
+            .transactions
+            .iter()
+            .flat_map(extract_token_transfers)
+            .map(|transfer| (transfer.source, transfer.source_owner, transfer.destination, transfer.destination_owner))
+            .collect();
+        assert!(expected.iter().any(|(_, source_owner, _, _)| source_owner.is_some()));
+
+        let mut resolver = StaticTokenOwners::new();
+        for (source, source_owner, destination, destination_owner) in &expected {
+            resolver.insert(*source, source_owner.unwrap_or(*source));
+            resolver.insert(*destination, destination_owner.unwrap_or(*destination));
+        }
+        // As from an RPC that predates the owner field.
+        let mut stripped = fixtures::raw_block().result;
+        for tx in &mut stripped.transactions {
+            let meta = &mut tx.meta;
+            for balance in meta.pre_token_balances.iter_mut().chain(meta.post_token_balances.iter_mut()).flatten() {
+                balance.owner = None;
+            }
+        }
+        let options = ParseOptions { token_owners: Some(Arc::new(resolver)), ..ParseOptions::default() };
+        let (block, _) = parse_block_with_options(stripped, &options).unwrap();
+        let resolved: Vec<_> = block
+            .transactions
+            .iter()
+            .flat_map(extract_token_transfers)
+            .map(|transfer| (transfer.source, transfer.source_owner, transfer.destination, transfer.destination_owner))
+            .collect();
+        assert_eq!(resolved.len(), expected.len());
+        for ((source, source_owner, destination, destination_owner), (_, resolved_source, _, resolved_destination)) in
+            expected.iter().zip(&resolved)
+        {
+            assert_eq!(*resolved_source, Some(source_owner.unwrap_or(*source)));
+            assert_eq!(*resolved_destination, Some(destination_owner.unwrap_or(*destination)));
+        }
+        assert!(block.transactions.iter().any(|tx| !tx.token_owners.is_empty()));
+    }
+}
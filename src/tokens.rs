@@ -0,0 +1,211 @@
+use crate::pubkey::Pubkey;
+#[cfg(feature = "native")]
+use crate::rpc::RpcClient;
+use crate::transfers::{format_ui_amount, TokenTransfer};
+use serde::Deserialize;
+use std::collections::HashMap;
+#[cfg(feature = "native")]
+use std::error::Error;
+#[cfg(feature = "native")]
+use std::fs;
+#[cfg(feature = "native")]
+use std::path::Path;
+#[cfg(feature = "native")]
+use std::sync::Mutex;
+
+// ==========================================
+// TOKEN METADATA
+// ==========================================
+// Decimals and symbols by mint, so token amounts can be shown in UI units
+// ("1.5 USDC") instead of base units. Transfers mostly know their decimals
+// already, from TransferChecked or the transaction's token balances; the
+// registry fills in the rest from a token list and, with `with_rpc`, from
+// the mint account itself over getAccountInfo, fetched once per mint.
+// Symbols only come from token lists: a short built-in one for the major
+// mints, plus files in the Solana token-list format, e.g.
+//
+//     {"tokens": [{"address": "EPjF...Dt1v", "symbol": "USDC", "decimals": 6}]}
+//
+// (a bare array of entries works too). Entries in a file override the
+// built-in ones.
+
+const BUILTIN_TOKENS: &str = include_str!("json/tokens.json");
+
+// Offset of `decimals` in an SPL Token (and Token-2022) mint account:
+// after the COption<Pubkey> mint authority and the u64 supply.
+#[cfg(feature = "native")]
+const MINT_DECIMALS_OFFSET: usize = 44;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TokenInfo {
+    pub address: Pubkey,
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+    pub decimals: Option<u8>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TokenList {
+    Wrapped { tokens: Vec<TokenInfo> },
+    Bare(Vec<TokenInfo>),
+}
+
+#[derive(Debug, Default)]
+pub struct TokenRegistry {
+    tokens: HashMap<Pubkey, TokenInfo>,
+    #[cfg(feature = "native")]
+    rpc: Option<RpcClient>,
+    // Decimals read from mint accounts, None where the fetch failed.
+    #[cfg(feature = "native")]
+    fetched: Mutex<HashMap<Pubkey, Option<u8>>>,
+}
+
+impl TokenRegistry {
+    // No tokens at all; see `builtins` for the usual starting point.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn builtins() -> Self {
+        let mut registry = Self::new();
+        registry.extend(parse_token_list(BUILTIN_TOKENS).expect("built-in token list"));
+        registry
+    }
+
+    // Adds the tokens in a token-list file.
+    #[cfg(feature = "native")]
+    pub fn with_file(mut self, path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("failed to read token list {}: {}", path.display(), e))?;
+        let tokens = parse_token_list(&contents).map_err(|e| format!("invalid token list {}: {}", path.display(), e))?;
+        self.extend(tokens);
+        Ok(self)
+    }
+
+    // Look up the decimals of mints missing from the lists over RPC.
+    #[cfg(feature = "native")]
+    pub fn with_rpc(mut self, client: RpcClient) -> Self {
+        self.rpc = Some(client);
+        self
+    }
+
+    // Replaces any entry already there for the same mint.
+    pub fn extend(&mut self, tokens: impl IntoIterator<Item = TokenInfo>) {
+        self.tokens.extend(tokens.into_iter().map(|token| (token.address, token)));
+    }
+
+    pub fn get(&self, mint: &Pubkey) -> Option<&TokenInfo> {
+        self.tokens.get(mint)
+    }
+
+    pub fn symbol(&self, mint: &Pubkey) -> Option<&str> {
+        self.get(mint)?.symbol.as_deref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn decimals(&self, mint: &Pubkey) -> Option<u8> {
+        if let Some(decimals) = self.get(mint).and_then(|token| token.decimals) {
+            return Some(decimals);
+        }
+        self.fetch_decimals(mint)
+    }
+
+    #[cfg(feature = "native")]
+    fn fetch_decimals(&self, mint: &Pubkey) -> Option<u8> {
+        let client = self.rpc.as_ref()?;
+        if let Some(decimals) = self.fetched.lock().unwrap().get(mint) {
+            return *decimals;
+        }
+        let decimals = match client.get_account_data(&mint.to_string()) {
+            Ok(data) => data.and_then(|data| data.get(MINT_DECIMALS_OFFSET).copied()),
+            Err(e) => {
+                log::warn!(mint:% = mint, error:% = e; "failed to fetch mint decimals");
+                None
+            }
+        };
+        self.fetched.lock().unwrap().insert(*mint, decimals);
+        decimals
+    }
+
+    #[cfg(not(feature = "native"))]
+    fn fetch_decimals(&self, _mint: &Pubkey) -> Option<u8> {
+        None
+    }
+
+    // Fills in the decimals, UI amount and symbol of a transfer where the
+    // mint is known.
+    pub fn enrich(&self, transfer: &mut TokenTransfer) {
+        let Some(mint) = transfer.mint else {
+            return;
+        };
+        if transfer.decimals.is_none() {
+            transfer.decimals = self.decimals(&mint);
+            transfer.ui_amount = transfer.decimals.map(|decimals| format_ui_amount(transfer.amount, decimals));
+        }
+        if transfer.symbol.is_none() {
+            transfer.symbol = self.symbol(&mint).map(str::to_string);
+        }
+    }
+
+    // "1.5 USDC" when the decimals and symbol are known, falling back to
+    // base units and the mint address.
+    pub fn format_amount(&self, mint: &Pubkey, amount: u64, decimals: Option<u8>) -> String {
+        let amount = match decimals.or_else(|| self.decimals(mint)) {
+            Some(decimals) => format_ui_amount(amount, decimals),
+            None => amount.to_string(),
+        };
+        match self.symbol(mint) {
+            Some(symbol) => format!("{} {}", amount, symbol),
+            None => format!("{} {}", amount, mint),
+        }
+    }
+}
+
+pub fn parse_token_list(contents: &str) -> Result<Vec<TokenInfo>, serde_json::Error> {
+    Ok(match serde_json::from_str(contents)? {
+        TokenList::Wrapped { tokens } | TokenList::Bare(tokens) => tokens,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfers::NATIVE_MINT;
+
+    #[test]
+    fn enriches_transfers_from_token_lists() {
+        let usdc = Pubkey::from_str_const("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+        let mut registry = TokenRegistry::builtins();
+        assert_eq!(registry.symbol(&NATIVE_MINT), Some("SOL"));
+        assert_eq!(registry.format_amount(&usdc, 1_500_000, None), "1.5 USDC");
+
+        let other = Pubkey::new([7; 32]);
+        let list = format!(r#"[{{"address": "{}", "symbol": "OTHER", "decimals": 2}}]"#, other);
+        registry.extend(parse_token_list(&list).unwrap());
+        let mut transfer = TokenTransfer {
+            program_id: Pubkey::default(),
+            source: Pubkey::default(),
+            destination: Pubkey::default(),
+            source_owner: None,
+            destination_owner: None,
+            mint: Some(other),
+            amount: 1234,
+            fee: 0,
+            decimals: None,
+            ui_amount: None,
+            symbol: None,
+            instruction_index: 0,
+            inner_index: None,
+        };
+        registry.enrich(&mut transfer);
+        assert_eq!((transfer.decimals, transfer.ui_amount.as_deref(), transfer.symbol.as_deref()), (Some(2), Some("12.34"), Some("OTHER")));
+        assert_eq!(registry.format_amount(&Pubkey::default(), 5, None), format!("5 {}", Pubkey::default()));
+    }
+}
@@ -0,0 +1,474 @@
+use crate::decoders::system::SYSTEM_PROGRAM_ID;
+use crate::decoders::token::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+use crate::decoders::{DecodedInstruction, ProgramDecoder, SystemDecoder, SystemInstruction, Token2022Decoder, TokenDecoder, TokenInstruction};
+use crate::pubkey::Pubkey;
+use crate::types::{ParsedInstruction, ParsedTransaction};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// Mint of wrapped SOL; token accounts holding it carry their balance as
+// lamports.
+pub const NATIVE_MINT: Pubkey = Pubkey::from_str_const("So11111111111111111111111111111111111111112");
+
+// ==========================================
+// SOL TRANSFERS
+// ==========================================
+// Flattens the instructions that move lamports, top-level and CPI alike,
+// into one list in execution order: System Program transfers and account
+// creations, token transfers between wrapped SOL accounts (which move the
+// lamports themselves), and token account closes, which sweep the
+// account's lamports (rent, or the SOL behind wrapped SOL) to the
+// destination. A close carries
+// no amount, so it's taken from a running balance that starts at
+// pre_balances and follows the transfers before it. The list is then
+// checked against the transaction's balance deltas: anything the transfers
+// and the fee don't account for (stake or vote withdrawals, programs
+// debiting accounts they own) is reported rather than guessed at.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SolTransferKind {
+    // Transfer and TransferWithSeed.
+    Transfer,
+    // Funding of a new account (CreateAccount, CreateAccountWithSeed).
+    CreateAccount,
+    // Token transfer between wrapped SOL accounts.
+    WrappedSol,
+    // SPL Token / Token-2022 CloseAccount.
+    CloseAccount,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SolTransfer {
+    pub kind: SolTransferKind,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub lamports: u64,
+    // Top-level instruction this transfer belongs to.
+    pub instruction_index: usize,
+    // Position within that instruction's CPIs; None for the top-level
+    // instruction itself.
+    pub inner_index: Option<usize>,
+}
+
+// An account whose balance moved differently than the transfers and fee
+// imply.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnexplainedBalanceChange {
+    pub address: Pubkey,
+    pub expected_delta: i128,
+    pub actual_delta: i128,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SolTransfers {
+    pub transfers: Vec<SolTransfer>,
+    pub unexplained: Vec<UnexplainedBalanceChange>,
+}
+
+impl SolTransfers {
+    // True when the transfers and fee explain every balance change.
+    pub fn is_reconciled(&self) -> bool {
+        self.unexplained.is_empty()
+    }
+}
+
+// Failed transactions only pay the fee, so they come back without transfers.
+pub fn extract_sol_transfers(tx: &ParsedTransaction) -> SolTransfers {
+    let mut transfers = Vec::new();
+    if tx.is_success {
+        let mut balances: HashMap<Pubkey, u64> =
+            tx.account_keys.iter().copied().zip(tx.pre_balances.iter().copied()).collect();
+        // The fee is charged before any instruction runs.
+        if let Some(balance) = balances.get_mut(&tx.fee_payer) {
+            *balance = balance.saturating_sub(tx.fee);
+        }
+        let wrapped: HashSet<Pubkey> = tx.pre_token_balances.iter()
+            .chain(&tx.post_token_balances)
+            .filter(|balance| balance.mint == NATIVE_MINT)
+            .map(|balance| balance.account)
+            .collect();
+        let mut walker = Walker { tx, balances, wrapped };
+        walk_instructions(tx, |ix, index, inner_index| transfers.extend(walker.sol_transfer(ix, index, inner_index)));
+    }
+    let unexplained = reconcile(tx, &transfers);
+    SolTransfers { transfers, unexplained }
+}
+
+struct Walker<'a> {
+    tx: &'a ParsedTransaction,
+    // Running lamport balance per account.
+    balances: HashMap<Pubkey, u64>,
+    // Token accounts holding wrapped SOL. Accounts opened and closed within
+    // the transaction show up in neither token balance list, so
+    // InitializeAccount adds to this as the walk goes.
+    wrapped: HashSet<Pubkey>,
+}
+
+impl Walker<'_> {
+    fn sol_transfer(&mut self, ix: &ParsedInstruction, instruction_index: usize, inner_index: Option<usize>) -> Option<SolTransfer> {
+        let (kind, from, to, lamports) = match decode(self.tx, ix)?.as_ref() {
+            DecodedInstruction::System(instruction) => {
+                let (from, to, lamports) = instruction.lamport_transfer()?;
+                let kind = match instruction {
+                    SystemInstruction::CreateAccount { .. } | SystemInstruction::CreateAccountWithSeed { .. } => {
+                        SolTransferKind::CreateAccount
+                    }
+                    _ => SolTransferKind::Transfer,
+                };
+                (kind, from, to, lamports)
+            }
+            DecodedInstruction::Token(TokenInstruction::InitializeAccount { account, mint, .. }) => {
+                if *mint == NATIVE_MINT {
+                    self.wrapped.insert(*account);
+                }
+                return None;
+            }
+            DecodedInstruction::Token(
+                TokenInstruction::Transfer { source, destination, amount, .. }
+                | TokenInstruction::TransferChecked { source, destination, amount, .. },
+            ) if self.wrapped.contains(source) || self.wrapped.contains(destination) => {
+                (SolTransferKind::WrappedSol, *source, *destination, *amount)
+            }
+            DecodedInstruction::Token(TokenInstruction::CloseAccount { account, destination, .. }) => {
+                let lamports = self.balances.get(account).copied().unwrap_or(0);
+                (SolTransferKind::CloseAccount, *account, *destination, lamports)
+            }
+            _ => return None,
+        };
+
+        if let Some(balance) = self.balances.get_mut(&from) {
+            *balance = balance.saturating_sub(lamports);
+        }
+        if let Some(balance) = self.balances.get_mut(&to) {
+            *balance = balance.saturating_add(lamports);
+        }
+        Some(SolTransfer {
+            kind,
+            from,
+            to,
+            lamports,
+            instruction_index,
+            inner_index,
+        })
+    }
+}
+
+// ==========================================
+// SPL TOKEN TRANSFERS
+// ==========================================
+// Transfer, TransferChecked and Token-2022 TransferCheckedWithFee, with the
+// token accounts resolved to their owners (see TokenAccounts) and the
+// amount normalized by the mint's decimals. Plain Transfer names neither
+// mint nor decimals, so both come from what TokenAccounts knows of the
+// accounts and the transaction's token balances. Fields that can't be
+// established stay None rather than being guessed.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenTransfer {
+    // Token program that executed the transfer.
+    pub program_id: Pubkey,
+    // Token accounts.
+    pub source: Pubkey,
+    pub destination: Pubkey,
+    // Wallets owning those token accounts.
+    pub source_owner: Option<Pubkey>,
+    pub destination_owner: Option<Pubkey>,
+    pub mint: Option<Pubkey>,
+    // Raw amount in base units, as sent.
+    pub amount: u64,
+    // Token-2022 transfer fee withheld from `amount`; 0 otherwise.
+    pub fee: u64,
+    pub decimals: Option<u8>,
+    // `amount` scaled by decimals, as an exact decimal string ("1.5").
+    pub ui_amount: Option<String>,
+    // Only set by TokenRegistry::enrich; left out of the output otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    pub instruction_index: usize,
+    pub inner_index: Option<usize>,
+}
+
+// Failed transactions move no tokens, so they come back empty.
+pub fn extract_token_transfers(tx: &ParsedTransaction) -> Vec<TokenTransfer> {
+    let mut transfers = Vec::new();
+    if !tx.is_success {
+        return transfers;
+    }
+
+    let accounts = TokenAccounts::from_transaction(tx);
+    let known_decimals: HashMap<Pubkey, u8> = tx
+        .pre_token_balances
+        .iter()
+        .chain(&tx.post_token_balances)
+        .map(|balance| (balance.mint, balance.decimals))
+        .collect();
+
+    let visit = |ix: &ParsedInstruction, instruction_index: usize, inner_index: Option<usize>| {
+        let Some((source, destination, amount, fee, checked)) = token_transfer(tx, ix) else {
+            return;
+        };
+        let mint = match checked {
+            Some((mint, _)) => Some(mint),
+            None => accounts.mint(&source).or_else(|| accounts.mint(&destination)),
+        };
+        let mint_decimals = match checked {
+            Some((_, decimals)) => Some(decimals),
+            None => mint.as_ref().and_then(|mint| known_decimals.get(mint).copied()),
+        };
+        transfers.push(TokenTransfer {
+            program_id: ix.program_id,
+            source,
+            destination,
+            source_owner: accounts.owner(&source),
+            destination_owner: accounts.owner(&destination),
+            mint,
+            amount,
+            fee,
+            decimals: mint_decimals,
+            ui_amount: mint_decimals.map(|decimals| format_ui_amount(amount, decimals)),
+            symbol: None,
+            instruction_index,
+            inner_index,
+        });
+    };
+    walk_instructions(tx, visit);
+    transfers
+}
+
+// (source, destination, amount, fee, (mint, decimals) when checked) of a
+// token transfer instruction.
+type TransferParts = (Pubkey, Pubkey, u64, u64, Option<(Pubkey, u8)>);
+
+fn token_transfer(tx: &ParsedTransaction, ix: &ParsedInstruction) -> Option<TransferParts> {
+    let decoded = decode(tx, ix)?;
+    let DecodedInstruction::Token(instruction) = decoded.as_ref() else {
+        return None;
+    };
+    match instruction {
+        TokenInstruction::Transfer { source, destination, amount, .. } => Some((*source, *destination, *amount, 0, None)),
+        TokenInstruction::TransferChecked { source, destination, amount, mint, decimals, .. } => {
+            Some((*source, *destination, *amount, 0, Some((*mint, *decimals))))
+        }
+        TokenInstruction::TransferCheckedWithFee { source, destination, amount, fee, mint, decimals, .. } => {
+            Some((*source, *destination, *amount, *fee, Some((*mint, *decimals))))
+        }
+        _ => None,
+    }
+}
+
+// Base units to a decimal string without trailing zeros, matching the RPC's
+// uiAmountString: (1_500_000, 6) -> "1.5", (42, 0) -> "42".
+pub fn format_ui_amount(amount: u64, decimals: u8) -> String {
+    let digits = amount.to_string();
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return digits;
+    }
+    let padded = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = padded.split_at(padded.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+// Top-level instructions in order, each followed by its CPIs.
+pub(crate) fn walk_instructions<'a>(tx: &'a ParsedTransaction, mut visit: impl FnMut(&'a ParsedInstruction, usize, Option<usize>)) {
+    for (index, ix) in tx.instructions.iter().enumerate() {
+        visit(ix, index, None);
+        for inner in tx.inner_instructions.iter().filter(|inner| inner.index == index) {
+            for (inner_index, ix) in inner.instructions.iter().enumerate() {
+                visit(ix, index, Some(inner_index));
+            }
+        }
+    }
+}
+
+// Uses the parser's decoding when there is one, and decodes here when the
+// parser ran without the built-in decoders.
+fn decode<'a>(tx: &ParsedTransaction, ix: &'a ParsedInstruction) -> Option<Cow<'a, DecodedInstruction>> {
+    if let Some(decoded) = &ix.decoded {
+        return Some(Cow::Borrowed(decoded));
+    }
+    let decoder: &dyn ProgramDecoder = match ix.program_id {
+        SYSTEM_PROGRAM_ID => &SystemDecoder,
+        TOKEN_PROGRAM_ID => &TokenDecoder,
+        TOKEN_2022_PROGRAM_ID => &Token2022Decoder,
+        _ => return None,
+    };
+    let accounts: Vec<Pubkey> = ix.resolved_accounts(tx).collect::<Option<_>>()?;
+    decoder.decode(&ix.data_bytes()?, &accounts).map(Cow::Owned)
+}
+
+fn reconcile(tx: &ParsedTransaction, transfers: &[SolTransfer]) -> Vec<UnexplainedBalanceChange> {
+    // address -> (expected, actual)
+    let mut deltas: BTreeMap<Pubkey, (i128, i128)> = BTreeMap::new();
+    deltas.entry(tx.fee_payer).or_default().0 -= tx.fee as i128;
+    for transfer in transfers {
+        deltas.entry(transfer.from).or_default().0 -= transfer.lamports as i128;
+        deltas.entry(transfer.to).or_default().0 += transfer.lamports as i128;
+    }
+    for (address, (pre, post)) in tx.account_keys.iter().zip(tx.pre_balances.iter().zip(&tx.post_balances)) {
+        deltas.entry(*address).or_default().1 += *post as i128 - *pre as i128;
+    }
+
+    deltas
+        .into_iter()
+        .filter(|(_, (expected, actual))| expected != actual)
+        .map(|(address, (expected_delta, actual_delta))| UnexplainedBalanceChange {
+            address,
+            expected_delta,
+            actual_delta,
+        })
+        .collect()
+}
+
+// ==========================================
+// TOKEN ACCOUNT OWNERS
+// ==========================================
+// Token instructions name token accounts (usually associated token
+// accounts), not the wallets that own them. TokenAccounts maps one to the
+// other for a transaction, from, in order of preference: the owner fields
+// of its token balances, the InitializeAccount that opened an account
+// within the transaction (such accounts are often closed again before the
+// end, so neither balance list has them), and ParsedTransaction.token_owners,
+// which the parser fills in from a TokenOwnerResolver (see token_owners)
+// for whatever is still unknown.
+
+#[derive(Debug, Default)]
+struct TokenAccountInfo {
+    mint: Option<Pubkey>,
+    owner: Option<Pubkey>,
+}
+
+#[derive(Debug, Default)]
+pub struct TokenAccounts {
+    accounts: HashMap<Pubkey, TokenAccountInfo>,
+}
+
+impl TokenAccounts {
+    pub fn from_transaction(tx: &ParsedTransaction) -> Self {
+        let mut accounts: HashMap<Pubkey, TokenAccountInfo> = HashMap::new();
+        for (account, owner) in &tx.token_owners {
+            accounts.entry(*account).or_default().owner = Some(*owner);
+        }
+        walk_instructions(tx, |ix, _, _| {
+            if let Some(decoded) = decode(tx, ix)
+                && let DecodedInstruction::Token(TokenInstruction::InitializeAccount { account, mint, owner }) = decoded.as_ref()
+            {
+                let info = accounts.entry(*account).or_default();
+                info.mint = Some(*mint);
+                info.owner = Some(*owner);
+            }
+        });
+        for balance in tx.pre_token_balances.iter().chain(&tx.post_token_balances) {
+            let info = accounts.entry(balance.account).or_default();
+            info.mint = Some(balance.mint);
+            if balance.owner.is_some() {
+                info.owner = balance.owner;
+            }
+        }
+        TokenAccounts { accounts }
+    }
+
+    // The wallet owning a token account, when known.
+    pub fn owner(&self, account: &Pubkey) -> Option<Pubkey> {
+        self.accounts.get(account).and_then(|info| info.owner)
+    }
+
+    pub fn mint(&self, account: &Pubkey) -> Option<Pubkey> {
+        self.accounts.get(account).and_then(|info| info.mint)
+    }
+}
+
+// Token accounts the transaction's transfers move tokens between whose
+// owner the transaction itself doesn't say, in order of first use. Empty
+// for failed transactions.
+pub fn unresolved_token_accounts(tx: &ParsedTransaction) -> Vec<Pubkey> {
+    let mut unresolved = Vec::new();
+    if !tx.is_success {
+        return unresolved;
+    }
+    let accounts = TokenAccounts::from_transaction(tx);
+    walk_instructions(tx, |ix, _, _| {
+        if let Some((source, destination, ..)) = token_transfer(tx, ix) {
+            for account in [source, destination] {
+                if accounts.owner(&account).is_none() && !unresolved.contains(&account) {
+                    unresolved.push(account);
+                }
+            }
+        }
+    });
+    unresolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::io::load_from_json;
+    use crate::parser::parse_transaction;
+    use crate::types::RpcResponse;
+
+    #[test]
+    fn extracts_and_reconciles_a_plain_transfer() {
+        let raw: RpcResponse = load_from_json("src/json/transfer.json").unwrap();
+        let tx = parse_transaction(&raw.result.transaction, &raw.result.meta).unwrap();
+
+        let sol = extract_sol_transfers(&tx);
+        assert_eq!(sol.transfers.len(), 1);
+        assert_eq!(sol.transfers[0].kind, SolTransferKind::Transfer);
+        assert_eq!(sol.transfers[0].from, tx.account_keys[0]);
+        assert_eq!(sol.transfers[0].to, tx.account_keys[1]);
+        assert_eq!(sol.transfers[0].lamports, 44_862);
+        assert_eq!((sol.transfers[0].instruction_index, sol.transfers[0].inner_index), (1, None));
+        assert!(sol.is_reconciled(), "{:?}", sol.unexplained);
+    }
+
+    #[test]
+    fn follows_wrapped_sol_through_to_the_close() {
+        let block = fixtures::block();
+        let tx = block
+            .get_transaction("5a5bVs8xdqivYUCGcUaRRVRE7J4nJ2TmipA4PKWYizNY9xatdJybHAAML5sT4qF8QPMGYqewC4uG9fRyz2uor16X")
+            .unwrap();
+
+        let sol = extract_sol_transfers(tx);
+        let moves: Vec<(SolTransferKind, u64)> = sol.transfers.iter().map(|t| (t.kind, t.lamports)).collect();
+        assert_eq!(moves, [
+            (SolTransferKind::CreateAccount, 2_039_280),
+            (SolTransferKind::Transfer, 1_391_643_864),
+            (SolTransferKind::WrappedSol, 1_391_643_864),
+            (SolTransferKind::CloseAccount, 2_039_280),
+        ]);
+        assert!(sol.is_reconciled(), "{:?}", sol.unexplained);
+    }
+
+    #[test]
+    fn resolves_token_transfer_owners_and_decimals() {
+        let block = fixtures::block();
+        let tx = block
+            .get_transaction("5a5bVs8xdqivYUCGcUaRRVRE7J4nJ2TmipA4PKWYizNY9xatdJybHAAML5sT4qF8QPMGYqewC4uG9fRyz2uor16X")
+            .unwrap();
+
+        let wrapped = extract_token_transfers(tx).into_iter().find(|t| t.mint == Some(NATIVE_MINT)).unwrap();
+        assert_eq!(wrapped.amount, 1_391_643_864);
+        assert_eq!(wrapped.decimals, Some(9));
+        assert_eq!(wrapped.ui_amount.as_deref(), Some("1.391643864"));
+        // The wrapped SOL account is opened within the transaction, so its
+        // owner comes from InitializeAccount.
+        assert_eq!(wrapped.source_owner, Some(tx.fee_payer));
+    }
+
+    #[test]
+    fn formats_ui_amounts_like_the_rpc() {
+        assert_eq!(format_ui_amount(1_500_000, 6), "1.5");
+        assert_eq!(format_ui_amount(1_000_000, 6), "1");
+        assert_eq!(format_ui_amount(42, 0), "42");
+        assert_eq!(format_ui_amount(7, 9), "0.000000007");
+        assert_eq!(format_ui_amount(0, 6), "0");
+    }
+}
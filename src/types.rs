@@ -0,0 +1,1147 @@
+use crate::decoders::{DecodedEvent, DecodedInstruction};
+use crate::encoding::DataEncoding;
+use crate::epoch::{format_rfc3339, EpochSchedule};
+use crate::error::ParseError;
+use crate::failure::{FailureCause, InstructionExecution, TransactionError};
+use crate::logs::{parse_logs, ParsedLogs};
+use crate::pubkey::Pubkey;
+use serde::de::{self, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+pub(crate) const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+// Version of the ParsedBlock / ParsedTransaction JSON layout, written into
+// every block and transaction. Bump it when a field is renamed, removed or
+// changes meaning, and teach `upgrade` to bring older output forward.
+// Output from before the field existed reads back as version 0.
+//
+// 2: instruction accounts are AccountRefs, so an index past the account
+//    keys comes out as {"index": n} rather than failing the parse; CSV
+//    output gained timestamp_ms and instruction execution columns. Version
+//    1 JSON reads back unchanged, but CSV can't be appended across it.
+pub const SCHEMA_VERSION: u32 = 2;
+
+pub const VOTE_PROGRAM_ID: Pubkey = Pubkey::from_str_const("Vote111111111111111111111111111111111111111");
+
+pub const MEMO_PROGRAM_ID: Pubkey = Pubkey::from_str_const("MemoSq4gqABAXKb96qnH8TySNcWxMyWCqXgDLGmfcHr");
+
+// ==========================================
+// DESTINATION STRUCTS
+// ==========================================
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParsedBlock {
+    #[serde(default)]
+    pub schema_version: u32,
+    // getBlock doesn't echo the slot back. Without one from the caller this
+    // is parent_slot + 1, which is only right when the slot before wasn't
+    // skipped.
+    #[serde(default)]
+    pub slot: u64,
+    #[serde(default)]
+    pub epoch: u64,
+    #[serde(default)]
+    pub slot_index: u64,
+    // Null for blocks from before the node tracked height or time.
+    pub block_height: Option<u64>,
+    pub block_time: Option<i64>,
+    #[serde(default)]
+    pub block_time_rfc3339: Option<String>,
+    pub blockhash: String,
+    pub parent_slot: u64,
+    pub previous_blockhash: String,
+    // The validator that produced the block: the recipient of its fee
+    // reward, or from getSlotLeaders when the block has none. None when
+    // neither is available.
+    #[serde(default)]
+    pub leader: Option<Pubkey>,
+    pub rewards: Vec<BlockReward>,
+    pub transactions: Vec<ParsedTransaction>,
+    // The block's transaction signatures, for a block fetched with
+    // transactionDetails: signatures, which has no transactions to parse.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signatures: Vec<String>,
+    // Vote transactions left out by a vote-excluding filter; None when
+    // votes weren't excluded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub excluded_votes: Option<usize>,
+    // State of selected accounts the block wrote, read back over RPC after
+    // parsing; empty unless snapshots were asked for.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub account_snapshots: Vec<AccountSnapshot>,
+    // The RPC endpoint that served the block, when fetched through an
+    // endpoint pool.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountSnapshot {
+    pub pubkey: Pubkey,
+    // The slot the node read the account at: never before the block, but
+    // later blocks may have written it since.
+    pub context_slot: u64,
+    // None once the account no longer exists; lamports is 0 and data empty
+    // then.
+    pub owner: Option<Pubkey>,
+    pub lamports: u64,
+    pub data: String,
+    #[serde(default)]
+    pub data_encoding: DataEncoding,
+    // Whether lamports, owner or data differ from the account's previous
+    // snapshot in this run; None for its first.
+    #[serde(default)]
+    pub changed: Option<bool>,
+}
+
+impl ParsedBlock {
+    /// Records the block's actual slot, on the block and its transactions,
+    /// and recomputes epoch and slot_index.
+    pub fn set_slot(&mut self, slot: u64, schedule: &EpochSchedule) {
+        self.slot = slot;
+        (self.epoch, self.slot_index) = schedule.epoch_and_slot_index(slot);
+        for tx in &mut self.transactions {
+            tx.slot = Some(slot);
+        }
+    }
+
+    pub(crate) fn set_block_time(&mut self, block_time: Option<i64>) {
+        self.block_time = block_time;
+        self.block_time_rfc3339 = block_time.map(format_rfc3339);
+    }
+
+    /// True for blocks that carry neither transactions nor rewards, as seen
+    /// on skipped or otherwise empty slots.
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty() && self.rewards.is_empty()
+    }
+
+    /// Finds a transaction by signature. Signatures are unique within a
+    /// block, so the first match is the only match.
+    pub fn get_transaction(&self, signature: &str) -> Option<&ParsedTransaction> {
+        self.transactions.iter().find(|tx| tx.signature == signature)
+    }
+
+    /// Builds a signature -> transaction map for callers doing many lookups
+    /// against the same block.
+    pub fn index_by_signature(&self) -> HashMap<&str, &ParsedTransaction> {
+        self.transactions
+            .iter()
+            .map(|tx| (tx.signature.as_str(), tx))
+            .collect()
+    }
+
+    /// Builds an account -> involvement map: every transaction that lists
+    /// the account, with its role there and the top-level instructions that
+    /// use it (directly or through their inner instructions). Token accounts
+    /// are also listed under their owner, with `via` set, so looking up a
+    /// wallet finds the transactions that moved its tokens.
+    pub fn index_by_account(&self) -> HashMap<Pubkey, Vec<AccountInvolvement<'_>>> {
+        let mut index: HashMap<Pubkey, Vec<AccountInvolvement<'_>>> = HashMap::new();
+        for (transaction_index, tx) in self.transactions.iter().enumerate() {
+            let mut instructions: HashMap<Pubkey, Vec<usize>> = HashMap::new();
+            let inner = tx.inner_instructions.iter().flat_map(|inner| inner.instructions.iter().map(move |ix| (inner.index, ix)));
+            for (position, ix) in tx.instructions.iter().enumerate().chain(inner) {
+                for pubkey in std::iter::once(ix.program_id).chain(ix.account_keys()) {
+                    let positions = instructions.entry(pubkey).or_default();
+                    if !positions.contains(&position) {
+                        positions.push(position);
+                    }
+                }
+            }
+            let involvement = |pubkey: &Pubkey, is_signer: bool, is_writable: bool, via: Option<Pubkey>| {
+                let mut instructions = instructions.get(&via.unwrap_or(*pubkey)).cloned().unwrap_or_default();
+                instructions.sort_unstable();
+                AccountInvolvement { signature: &tx.signature, transaction_index, is_signer, is_writable, instructions, via }
+            };
+
+            for account in &tx.accounts {
+                index.entry(account.pubkey).or_default().push(involvement(&account.pubkey, account.is_signer, account.is_writable, None));
+            }
+            let mut owners: Vec<(Pubkey, Pubkey)> = tx
+                .pre_token_balances
+                .iter()
+                .chain(&tx.post_token_balances)
+                .filter_map(|balance| Some((balance.owner?, balance.account)))
+                .filter(|(owner, account)| owner != account)
+                .collect();
+            owners.sort_unstable();
+            owners.dedup();
+            for (owner, account) in owners {
+                // Writable if the token account was; the owner itself may
+                // not be in the transaction at all.
+                let is_writable = tx.accounts.iter().any(|a| a.pubkey == account && a.is_writable);
+                index.entry(owner).or_default().push(involvement(&owner, false, is_writable, Some(account)));
+            }
+        }
+        index
+    }
+
+    /// Brings a block read back from earlier output up to SCHEMA_VERSION.
+    /// Fails for output written by a newer version of the crate.
+    pub fn upgrade(&mut self) -> Result<(), ParseError> {
+        check_schema_version(self.schema_version)?;
+        if self.schema_version == 0 {
+            // block_time_rfc3339 was added without a version bump.
+            if self.block_time_rfc3339.is_none() {
+                self.set_block_time(self.block_time);
+            }
+        }
+        for tx in &mut self.transactions {
+            tx.upgrade()?;
+        }
+        self.schema_version = SCHEMA_VERSION;
+        Ok(())
+    }
+
+    /// Returns (successful, failed, total_fees). Counts only, so an empty
+    /// block comes out as all zeros.
+    pub fn transaction_stats(&self) -> (usize, usize, u64) {
+        let successful = self.transactions.iter().filter(|tx| tx.is_success).count();
+        let failed = self.transactions.len() - successful;
+        let total_fees: u64 = self.transactions.iter().map(|tx| tx.fee).sum();
+        (successful, failed, total_fees)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgramComputeUnits {
+    pub program_id: Pubkey,
+    pub compute_units: u64,
+}
+
+impl ProgramComputeUnits {
+    // Most units first; ties by program ID.
+    pub fn ranked(units: HashMap<Pubkey, u64>) -> Vec<Self> {
+        let mut ranked: Vec<Self> =
+            units.into_iter().map(|(program_id, compute_units)| ProgramComputeUnits { program_id, compute_units }).collect();
+        ranked.sort_by(|a, b| b.compute_units.cmp(&a.compute_units).then_with(|| a.program_id.cmp(&b.program_id)));
+        ranked
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockReward {
+    pub pubkey: Pubkey,
+    pub lamports: i64,
+    pub post_balance: u64,
+    pub reward_type: RewardType,
+    pub commission: Option<u8>,
+}
+
+/// The RPC's `rewardType`, written out as the RPC spells it. Types this
+/// version doesn't know keep their name in `Unknown`; a null or missing
+/// type reads as `Unknown("")`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RewardType {
+    Fee,
+    Rent,
+    Staking,
+    Voting,
+    Unknown(String),
+}
+
+impl RewardType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            RewardType::Fee => "Fee",
+            RewardType::Rent => "Rent",
+            RewardType::Staking => "Staking",
+            RewardType::Voting => "Voting",
+            RewardType::Unknown(name) => name,
+        }
+    }
+}
+
+impl Default for RewardType {
+    fn default() -> Self {
+        RewardType::Unknown(String::new())
+    }
+}
+
+impl From<&str> for RewardType {
+    fn from(name: &str) -> Self {
+        match name {
+            "Fee" => RewardType::Fee,
+            "Rent" => RewardType::Rent,
+            "Staking" => RewardType::Staking,
+            "Voting" => RewardType::Voting,
+            other => RewardType::Unknown(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for RewardType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for RewardType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for RewardType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Option::<String>::deserialize(deserializer)?.map_or_else(RewardType::default, |name| RewardType::from(name.as_str())))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParsedTransaction {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub signature: String,
+    // Where the transaction sits, so it can be read without its block (a
+    // CSV row, a Kafka message). The slot is None when it isn't known, as
+    // for a transaction fetched on its own from an RPC that left it out;
+    // the index is the position in the block as fetched, before filtering,
+    // and None outside a block.
+    #[serde(default)]
+    pub slot: Option<u64>,
+    #[serde(default)]
+    pub index_in_block: Option<usize>,
+    // Milliseconds since the epoch, from the block's blockTime; only set
+    // for sinks that ask for it (see timestamps), and left out otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_ms: Option<i64>,
+    pub fee_payer: Pubkey,
+    pub is_success: bool,
+    // meta.err, typed, and a one-line description of it; None on success.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<TransactionError>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+    // The same failure broken down for grouping; see failure::group_failures.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_cause: Option<FailureCause>,
+    pub account_keys: Vec<Pubkey>,
+    // The same accounts in the same order, with their roles.
+    #[serde(default)]
+    pub accounts: Vec<TransactionAccount>,
+    pub instructions: Vec<ParsedInstruction>,
+    pub inner_instructions: Vec<ParsedInnerInstructions>,
+    pub log_messages: Vec<String>,
+    // Events from "Program data:" logs that a registered decoder understood.
+    #[serde(default)]
+    pub events: Vec<DecodedEvent>,
+    // Memo program messages in execution order, CPI memos included.
+    #[serde(default)]
+    pub memos: Vec<String>,
+    pub pre_balances: Vec<u64>,
+    pub post_balances: Vec<u64>,
+    pub pre_token_balances: Vec<TokenBalance>,
+    pub post_token_balances: Vec<TokenBalance>,
+    pub token_balance_changes: Vec<TokenBalanceChange>,
+    pub fee: u64,
+    pub base_fee: u64,
+    pub priority_fee: u64,
+    pub compute_units_consumed: Option<u64>,
+    pub compute_unit_limit: Option<u32>,
+    pub unit_price_micro_lamports: Option<u64>,
+    // Units each program spent itself, from the logs' "consumed" lines; see
+    // ProgramInvocation::own_compute_units.
+    #[serde(default)]
+    pub compute_by_program: Vec<ProgramComputeUnits>,
+    #[serde(default)]
+    pub fee_breakdown: FeeBreakdown,
+    // Set only when ParseOptions.verify_signatures is on and the message
+    // could be rebuilt; left out of the output otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signatures_verified: Option<bool>,
+    // Labels for the account keys that have one; only filled in when
+    // ParseOptions.labels is set, and left out of the output when empty.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<Pubkey, String>,
+    // Protocol invariants the transaction breaks (account keys against the
+    // header, signatures, balances and instruction indices), as found in
+    // buggy RPC responses; left out of the output when there are none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub account_warnings: Vec<AccountWarning>,
+    // Set when the transaction uses a durable nonce instead of a recent
+    // blockhash; left out of the output otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub durable_nonce: Option<DurableNonce>,
+    // Owners of token accounts the transaction's transfers use that neither
+    // token balance list names, from ParseOptions.token_owners; left out of
+    // the output when empty.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub token_owners: BTreeMap<Pubkey, Pubkey>,
+}
+
+// A transaction signed against a nonce account's stored blockhash rather
+// than a recent one, so it stays valid until the nonce is advanced: the
+// usual setup for offline signing. Its first instruction must be the
+// AdvanceNonceAccount that consumes the nonce.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DurableNonce {
+    pub nonce_account: Pubkey,
+    pub nonce_authority: Pubkey,
+    // The nonce value, which the message carries as its recent blockhash.
+    pub nonce: Option<String>,
+}
+
+impl ParsedTransaction {
+    /// Priority fee paid on top of the base signature fee, in lamports.
+    ///
+    /// Computed as `unit_price_micro_lamports * compute_units_consumed / 1_000_000`.
+    /// The product is taken in `u128` so it cannot overflow, and the division
+    /// truncates toward zero, so any fractional lamport is rounded down.
+    /// Returns `None` if the transaction set no compute unit price or the RPC
+    /// response did not report `computeUnitsConsumed`.
+    pub fn priority_fee_lamports(&self) -> Option<u64> {
+        let price = self.unit_price_micro_lamports? as u128;
+        let consumed = self.compute_units_consumed? as u128;
+        let lamports = price * consumed / MICRO_LAMPORTS_PER_LAMPORT;
+        Some(u64::try_from(lamports).unwrap_or(u64::MAX))
+    }
+
+    /// Lamport balance changes per account, skipping accounts whose balance
+    /// didn't move. `account_keys` already includes loaded addresses in the
+    /// same order as `pre_balances` / `post_balances`, so entries line up by
+    /// position. The fee payer's delta includes the transaction fee.
+    pub fn balance_changes(&self) -> Vec<BalanceChange> {
+        self.account_keys.iter()
+            .zip(self.pre_balances.iter().zip(&self.post_balances))
+            .filter(|(_, (pre, post))| pre != post)
+            .map(|(address, (&pre_lamports, &post_lamports))| BalanceChange {
+                address: *address,
+                pre_lamports,
+                post_lamports,
+                delta: post_lamports as i128 - pre_lamports as i128,
+            })
+            .collect()
+    }
+
+    /// True when a top-level instruction calls the Vote program. Validators'
+    /// votes make up most of a mainnet block.
+    pub fn is_vote(&self) -> bool {
+        self.instructions.iter().any(|ix| ix.program_id == VOTE_PROGRAM_ID)
+    }
+
+    /// Brings a transaction read back from earlier output up to
+    /// SCHEMA_VERSION. Fails for output written by a newer version.
+    pub fn upgrade(&mut self) -> Result<(), ParseError> {
+        check_schema_version(self.schema_version)?;
+        self.schema_version = SCHEMA_VERSION;
+        Ok(())
+    }
+
+    /// Program invocation tree rebuilt from `log_messages`.
+    pub fn parsed_logs(&self) -> ParsedLogs {
+        parse_logs(&self.log_messages)
+    }
+}
+
+// One transaction's use of an account, from ParsedBlock::index_by_account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AccountInvolvement<'a> {
+    pub signature: &'a str,
+    // Position in ParsedBlock.transactions.
+    pub transaction_index: usize,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    // Top-level instruction indices, ascending.
+    pub instructions: Vec<usize>,
+    // The token account this owner is involved through; None when the
+    // account is in the transaction itself.
+    pub via: Option<Pubkey>,
+}
+
+fn check_schema_version(version: u32) -> Result<(), ParseError> {
+    if version > SCHEMA_VERSION {
+        return Err(ParseError::UnsupportedSchemaVersion { version, supported: SCHEMA_VERSION });
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountSource {
+    // Listed in the message itself.
+    Static,
+    // Loaded from an address lookup table (v0 transactions).
+    Lookup,
+}
+
+// An account as the message declares it. Flags are what the message
+// requests; the runtime may still demote a writable account to read-only
+// (reserved accounts, invoked programs).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionAccount {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    pub source: AccountSource,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParsedInstruction {
+    pub program_id: Pubkey,
+    pub accounts: Vec<AccountRef>,
+    pub account_indices: Vec<usize>,
+    // Instruction data in `data_encoding` (base58 unless configured otherwise).
+    pub data: String,
+    #[serde(default)]
+    pub data_encoding: DataEncoding,
+    pub decoded: Option<DecodedInstruction>,
+    // The node's own decoding, from encoding: jsonParsed input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parsed: Option<JsonParsedInstruction>,
+    // The RPC's stackHeight: 1 for top-level instructions, 2 for the CPIs
+    // they make, and so on. Older responses leave it out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack_height: Option<u32>,
+    // For an inner instruction made by another inner instruction, that
+    // instruction's position in the same inner list; None when the
+    // top-level instruction made it. Derived from stack heights only, so
+    // None throughout when they're missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invoked_by: Option<usize>,
+    // Only in a transaction that failed with an instruction error: whether
+    // the instruction ran, raised the error, or never got to run. See
+    // failure::mark_execution.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution: Option<InstructionExecution>,
+}
+
+// An instruction the node decoded for encoding: jsonParsed. It comes
+// without its accounts or data, so `program` and `parsed` are all there is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonParsedInstruction {
+    pub program: String,
+    // Usually {"type": ..., "info": {...}}; a plain string for some
+    // programs (memos).
+    pub parsed: serde_json::Value,
+}
+
+// An instruction account: the key its index points at, or the index itself
+// when it points past the transaction's account keys (a truncated or buggy
+// RPC response; the transaction's account_warnings says so too). Resolved
+// accounts serialize as the bare address, unresolved ones as {"index": n}.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AccountRef {
+    Resolved(Pubkey),
+    Unresolved { index: usize },
+}
+
+impl AccountRef {
+    pub fn pubkey(&self) -> Option<Pubkey> {
+        match self {
+            AccountRef::Resolved(pubkey) => Some(*pubkey),
+            AccountRef::Unresolved { .. } => None,
+        }
+    }
+}
+
+impl ParsedInstruction {
+    /// The resolved key at `position` in `accounts`.
+    pub fn account(&self, position: usize) -> Option<Pubkey> {
+        self.accounts.get(position)?.pubkey()
+    }
+
+    /// The resolved keys in `accounts`, skipping unresolved ones.
+    pub fn account_keys(&self) -> impl Iterator<Item = Pubkey> + '_ {
+        self.accounts.iter().filter_map(AccountRef::pubkey)
+    }
+
+    /// Resolves `account_indices` against the owning transaction's
+    /// `account_keys` without allocating. Indices past the end of the key
+    /// list come back as `None`.
+    pub fn resolved_accounts<'a>(
+        &'a self,
+        tx: &'a ParsedTransaction,
+    ) -> impl Iterator<Item = Option<Pubkey>> + 'a {
+        self.account_indices
+            .iter()
+            .map(|&idx| tx.account_keys.get(idx).copied())
+    }
+
+    /// Raw instruction bytes, or `None` if `data` isn't valid in its
+    /// declared encoding.
+    pub fn data_bytes(&self) -> Option<Vec<u8>> {
+        self.data_encoding.decode(&self.data)
+    }
+}
+
+// SPL token account balance as reported in meta.pre/postTokenBalances.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenBalance {
+    pub account_index: usize,
+    pub account: Pubkey,
+    pub mint: Pubkey,
+    pub owner: Option<Pubkey>,
+    pub program_id: Option<Pubkey>,
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+// Net token movement for one owner/mint pair across the transaction. Older
+// responses omit the owner, in which case the token account address stands in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenBalanceChange {
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub decimals: u8,
+    pub pre_amount: u64,
+    pub post_amount: u64,
+    pub delta: i128,
+}
+
+// The fee rebuilt from what the runtime charges for: signatures and
+// requested compute. Unlike base_fee/priority_fee, which split meta.fee
+// assuming one signature fee per transaction signature, this counts
+// precompile signatures and prices the compute unit limit the transaction
+// asked for (or was given by default), so base_fee + priority_fee here
+// should equal meta.fee.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeBreakdown {
+    // Transaction signatures plus those verified by the Ed25519 and
+    // secp256k1/secp256r1 precompiles.
+    pub signatures: u64,
+    pub base_fee: u64,
+    // Compute unit price x limit, rounded up to whole lamports; charged
+    // whether or not the units are used.
+    pub priority_fee: u64,
+    // From SetComputeUnitLimit, or the runtime default when unset.
+    pub compute_unit_limit: u32,
+    pub compute_unit_price_micro_lamports: u64,
+    // What the same price would cost for the units actually consumed.
+    pub consumed_priority_fee: Option<u64>,
+}
+
+impl FeeBreakdown {
+    pub fn total(&self) -> u64 {
+        self.base_fee + self.priority_fee
+    }
+}
+
+// Net lamport movement for one account across the transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceChange {
+    pub address: Pubkey,
+    pub pre_lamports: u64,
+    pub post_lamports: u64,
+    pub delta: i128,
+}
+
+// CPI calls made while executing the top-level instruction at `index`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParsedInnerInstructions {
+    pub index: usize,
+    pub instructions: Vec<ParsedInstruction>,
+}
+
+impl ParsedInnerInstructions {
+    /// The CPIs `caller` made directly, with their positions: `None` for
+    /// those of the top-level instruction, `Some(i)` for those of
+    /// `instructions[i]`. Empty when the response had no stack heights.
+    pub fn invoked_by(&self, caller: Option<usize>) -> impl Iterator<Item = (usize, &ParsedInstruction)> {
+        self.instructions
+            .iter()
+            .enumerate()
+            .filter(move |(_, ix)| ix.stack_height.is_some() && ix.invoked_by == caller)
+    }
+}
+
+// Everything the parser papered over while building a ParsedBlock.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ParseReport {
+    pub warnings: Vec<ParseWarning>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ParseWarning {
+    AccountIndexOutOfRange { tx_sig: String, index: usize },
+    SkippedTransaction { reason: String },
+    MissingComputeUnits { tx_sig: String },
+    UnresolvedLookupTable { tx_sig: String, table: Pubkey },
+    // Signature verification was requested but the signatures don't match
+    // the message, or the message couldn't be rebuilt to check them.
+    InvalidSignature { tx_sig: String },
+    UnverifiedSignature { tx_sig: String },
+    // Also on the transaction, in ParsedTransaction.account_warnings.
+    InconsistentAccounts { tx_sig: String, warning: AccountWarning },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountWarning {
+    // Keys listed more than once across the static keys and
+    // loadedAddresses. `removed` when the repeated loaded addresses were
+    // dropped from account_keys.
+    DuplicateAccountKeys { keys: Vec<Pubkey>, removed: bool },
+    // The balances arrays should have one entry per account key.
+    BalanceCountMismatch { account_keys: usize, pre_balances: usize, post_balances: usize },
+    // The message header asks for a different number of signatures.
+    SignatureCountMismatch { signatures: usize, required: usize },
+    // Header counts that don't fit the static keys: more signers than keys,
+    // more read-only signers than signers, or more read-only non-signers
+    // than non-signers.
+    InvalidHeader { static_keys: usize, required_signatures: u8, readonly_signed: u8, readonly_unsigned: u8 },
+    // Instruction and token balance indices are a u8, so no more than
+    // MAX_ACCOUNT_KEYS keys can be addressed.
+    TooManyAccountKeys { account_keys: usize },
+    // Indices, deduplicated, that point past the account keys. Instructions
+    // get AccountRef::Unresolved { index } in their place, token balances
+    // the default account; each is also a ParseWarning::AccountIndexOutOfRange.
+    AccountIndexOutOfRange { indices: Vec<usize>, account_keys: usize },
+}
+
+// The most account keys a transaction can address, static and loaded.
+pub const MAX_ACCOUNT_KEYS: usize = 256;
+
+// ==========================================
+// RAW BLOCK STRUCTS (RPC Input Format)
+// ==========================================
+#[derive(Debug, Deserialize)]
+pub struct RpcBlockResponse {
+    pub result: RpcBlockResult,
+}
+
+// `T` is RpcBlockTransaction except where the transactions are first read
+// as JSON text (TxFilter::prefilter_block).
+#[derive(Debug, Deserialize)]
+pub struct RpcBlockResult<T = RpcBlockTransaction> {
+    // Either can be null on mainnet, mostly for old slots.
+    #[serde(rename = "blockHeight")]
+    pub block_height: Option<u64>,
+    #[serde(rename = "blockTime")]
+    pub block_time: Option<i64>,
+    pub blockhash: String,
+    #[serde(rename = "parentSlot")]
+    pub parent_slot: u64,
+    #[serde(rename = "previousBlockhash")]
+    pub previous_blockhash: String,
+    // Left out of the response when the block was requested with
+    // rewards: false.
+    #[serde(default)]
+    pub rewards: Vec<RpcReward>,
+    // transactionDetails decides which of these the block has: full and
+    // accounts list transactions, signatures only their signatures, none
+    // neither.
+    #[serde(default = "Vec::new")]
+    pub transactions: Vec<T>,
+    #[serde(default)]
+    pub signatures: Vec<String>,
+    // Votes the pre-filter dropped before they were deserialized, still
+    // counted in ParsedBlock.excluded_votes.
+    #[serde(skip)]
+    pub prefiltered_votes: usize,
+    // The pool endpoint that served the block; see ParsedBlock.endpoint.
+    #[serde(skip)]
+    pub endpoint: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcReward {
+    pub pubkey: Pubkey,
+    pub lamports: i64,
+    #[serde(rename = "postBalance")]
+    pub post_balance: u64,
+    #[serde(rename = "rewardType", default)]
+    pub reward_type: RewardType,
+    pub commission: Option<u8>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(from = "RpcTransactionWithMeta")]
+pub struct RpcBlockTransaction {
+    pub meta: RpcMeta,
+    pub transaction: RpcTransactionContainer,
+    // Position in the block, set where transactions are dropped before
+    // parsing (the raw pre-filter) so the survivors keep their place. None
+    // means the position in the list being parsed.
+    pub index: Option<usize>,
+}
+
+impl From<RpcTransactionWithMeta> for RpcBlockTransaction {
+    fn from(raw: RpcTransactionWithMeta) -> Self {
+        let (transaction, meta, _) = raw.index_instruction_keys();
+        RpcBlockTransaction { meta, transaction, index: None }
+    }
+}
+
+// jsonParsed instructions name their program and accounts by key. They're
+// mapped to indices into the full key list (static, then loaded), which
+// needs the meta's loadedAddresses, so it's done here rather than on the
+// instructions themselves. Keys missing from the list get an out-of-range
+// index, which the parser reports.
+#[derive(Deserialize)]
+struct RpcTransactionWithMeta {
+    meta: RpcMeta,
+    transaction: RpcTransactionContainer,
+    // Only getTransaction results carry it.
+    #[serde(default)]
+    slot: Option<u64>,
+}
+
+impl RpcTransactionWithMeta {
+    fn index_instruction_keys(self) -> (RpcTransactionContainer, RpcMeta, Option<u64>) {
+        let RpcTransactionWithMeta { mut meta, mut transaction, slot } = self;
+        // transactionDetails: accounts leaves loadedAddresses out of the
+        // meta; the lookup table keys are in the key list instead.
+        if meta.loaded_addresses.is_none() {
+            meta.loaded_addresses = transaction.message.lookup_keys.take();
+        }
+        let loaded = meta.loaded_addresses.as_ref();
+        let keys: Vec<Pubkey> = transaction.message.account_keys.iter()
+            .chain(loaded.into_iter().flat_map(|loaded| loaded.writable.iter().chain(&loaded.readonly)))
+            .copied()
+            .collect();
+        let index = |key: Pubkey| keys.iter().position(|k| *k == key).unwrap_or(keys.len());
+        let inner = meta.inner_instructions.iter_mut().flatten().flat_map(|inner| &mut inner.instructions);
+        for ix in transaction.message.instructions.iter_mut().chain(inner) {
+            if let Some((program_id, accounts)) = ix.keys.take() {
+                ix.program_id_index = index(program_id);
+                ix.accounts = accounts.into_iter().map(index).collect();
+            }
+        }
+        (transaction, meta, slot)
+    }
+}
+
+// ==========================================
+// RAW TRANSACTION STRUCTS (RPC Input)
+// ==========================================
+
+#[derive(Debug, Deserialize)]
+pub struct RpcResponse {
+    pub result: RpcResult,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(from = "RpcTransactionWithMeta")]
+pub struct RpcResult {
+    pub meta: RpcMeta,
+    pub transaction: RpcTransactionContainer,
+    pub slot: Option<u64>,
+}
+
+impl From<RpcTransactionWithMeta> for RpcResult {
+    fn from(raw: RpcTransactionWithMeta) -> Self {
+        let (transaction, meta, slot) = raw.index_instruction_keys();
+        RpcResult { meta, transaction, slot }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RpcMeta {
+    pub err: Option<serde_json::Value>,
+    // Left out with transactionDetails: accounts, like the inner
+    // instructions and loaded addresses.
+    #[serde(rename = "logMessages", default)]
+    pub log_messages: Vec<String>,
+    #[serde(rename = "preBalances")]
+    pub pre_balances: Vec<u64>,
+    #[serde(rename = "postBalances")]
+    pub post_balances: Vec<u64>,
+    #[serde(rename = "loadedAddresses")]
+    pub loaded_addresses: Option<RpcLoadedAddresses>,
+    #[serde(rename = "innerInstructions")]
+    pub inner_instructions: Option<Vec<RpcInnerInstructions>>,
+    #[serde(rename = "preTokenBalances")]
+    pub pre_token_balances: Option<Vec<RpcTokenBalance>>,
+    #[serde(rename = "postTokenBalances")]
+    pub post_token_balances: Option<Vec<RpcTokenBalance>>,
+    pub fee: u64,
+    #[serde(rename = "computeUnitsConsumed")]
+    pub compute_units_consumed: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RpcLoadedAddresses {
+    pub writable: Vec<Pubkey>,
+    pub readonly: Vec<Pubkey>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RpcTokenBalance {
+    #[serde(rename = "accountIndex")]
+    pub account_index: usize,
+    pub mint: Pubkey,
+    pub owner: Option<Pubkey>,
+    #[serde(rename = "programId")]
+    pub program_id: Option<Pubkey>,
+    #[serde(rename = "uiTokenAmount")]
+    pub ui_token_amount: RpcUiTokenAmount,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RpcUiTokenAmount {
+    pub amount: String,
+    pub decimals: u8,
+    #[serde(rename = "uiAmount")]
+    pub ui_amount: Option<f64>,
+    #[serde(rename = "uiAmountString")]
+    pub ui_amount_string: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RpcInnerInstructions {
+    pub index: usize,
+    pub instructions: Vec<RpcInstruction>,
+}
+
+// Deserializes from either the `encoding: json` object or the
+// `[data, "base64"]` pair of `encoding: base64`, which is decoded from the
+// wire format.
+#[derive(Debug, Clone)]
+pub struct RpcTransactionContainer {
+    pub signatures: Vec<String>,
+    pub message: RpcMessage,
+}
+
+impl RpcTransactionContainer {
+    /// Same test as `ParsedTransaction::is_vote`. Invoked programs are
+    /// always static keys, so lookup tables don't matter.
+    pub fn is_vote(&self) -> bool {
+        let keys = &self.message.account_keys;
+        self.message.instructions.iter().any(|ix| keys.get(ix.program_id_index) == Some(&VOTE_PROGRAM_ID))
+    }
+}
+
+// The JSON shape tells the encodings apart, so the value is read once
+// rather than buffered and retried against each form in turn.
+impl<'de> Deserialize<'de> for RpcTransactionContainer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(RpcTransactionVisitor)
+    }
+}
+
+struct RpcTransactionVisitor;
+
+impl<'de> Visitor<'de> for RpcTransactionVisitor {
+    type Value = RpcTransactionContainer;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a transaction object or a [data, encoding] pair")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let data: String = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let encoding: String = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        if seq.next_element::<IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(3, &self));
+        }
+        if encoding != "base64" {
+            return Err(de::Error::custom(format!("unsupported transaction encoding '{}'", encoding)));
+        }
+        let bytes = DataEncoding::Base64.decode(&data).ok_or_else(|| de::Error::custom("invalid base64 transaction"))?;
+        crate::wire::decode_transaction(&bytes).map_err(de::Error::custom)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut signatures: Option<Vec<String>> = None;
+        let mut message: Option<RpcMessage> = None;
+        let mut account_keys: Option<Vec<RpcAccountKey>> = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "signatures" => signatures = Some(map.next_value()?),
+                "message" => message = Some(map.next_value()?),
+                "accountKeys" => account_keys = Some(map.next_value()?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        let signatures = signatures.ok_or_else(|| de::Error::missing_field("signatures"))?;
+        let message = match (message, account_keys) {
+            (Some(message), _) => message,
+            // transactionDetails: accounts, the keys with their flags and no
+            // instructions.
+            (None, Some(account_keys)) => RpcEncodedMessage {
+                header: None,
+                account_keys,
+                recent_blockhash: None,
+                instructions: Vec::new(),
+                address_table_lookups: None,
+            }
+            .into(),
+            (None, None) => return Err(de::Error::missing_field("message")),
+        };
+        Ok(RpcTransactionContainer { signatures, message })
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(from = "RpcEncodedMessage")]
+pub struct RpcMessage {
+    pub header: RpcMessageHeader,
+    // Static keys only; loaded addresses come from the meta.
+    pub account_keys: Vec<Pubkey>,
+    // Part of the signed message; only needed to verify signatures.
+    pub recent_blockhash: Option<String>,
+    pub instructions: Vec<RpcInstruction>,
+    pub address_table_lookups: Option<Vec<RpcAddressTableLookup>>,
+    // jsonParsed messages have no header; it's rebuilt from the keys'
+    // signer/writable flags. The node reports those after demoting reserved
+    // accounts to read-only, so the result can differ from what was signed.
+    pub header_from_flags: bool,
+    // Keys the jsonParsed / accounts key list marks as loaded from lookup
+    // tables, for responses whose meta doesn't list them (see
+    // RpcTransactionWithMeta). None when the keys carry no flags.
+    pub(crate) lookup_keys: Option<RpcLoadedAddresses>,
+}
+
+#[derive(Deserialize)]
+struct RpcEncodedMessage {
+    header: Option<RpcMessageHeader>,
+    #[serde(rename = "accountKeys")]
+    account_keys: Vec<RpcAccountKey>,
+    #[serde(rename = "recentBlockhash", default)]
+    recent_blockhash: Option<String>,
+    instructions: Vec<RpcInstruction>,
+    #[serde(rename = "addressTableLookups")]
+    address_table_lookups: Option<Vec<RpcAddressTableLookup>>,
+}
+
+// A bare key (json), or a key with its flags (jsonParsed), where keys
+// loaded from lookup tables are listed too.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RpcAccountKey {
+    Key(Pubkey),
+    Parsed { pubkey: Pubkey, signer: bool, writable: bool, source: Option<String> },
+}
+
+impl From<RpcEncodedMessage> for RpcMessage {
+    fn from(raw: RpcEncodedMessage) -> Self {
+        let mut lookup_keys: Option<RpcLoadedAddresses> = None;
+        let keys: Vec<(Pubkey, bool, bool)> = raw.account_keys.into_iter()
+            .filter_map(|key| match key {
+                RpcAccountKey::Key(pubkey) => Some((pubkey, false, false)),
+                RpcAccountKey::Parsed { pubkey, writable, source, .. } if source.as_deref() == Some("lookupTable") => {
+                    let loaded = lookup_keys.get_or_insert_with(RpcLoadedAddresses::default);
+                    match writable {
+                        true => loaded.writable.push(pubkey),
+                        false => loaded.readonly.push(pubkey),
+                    }
+                    None
+                }
+                RpcAccountKey::Parsed { pubkey, signer, writable, .. } => Some((pubkey, signer, writable)),
+            })
+            .collect();
+        let header_from_flags = raw.header.is_none();
+        let header = raw.header.unwrap_or_else(|| header_from_flags_of(&keys));
+        RpcMessage {
+            header,
+            account_keys: keys.into_iter().map(|(pubkey, ..)| pubkey).collect(),
+            recent_blockhash: raw.recent_blockhash,
+            instructions: raw.instructions,
+            address_table_lookups: raw.address_table_lookups,
+            header_from_flags,
+            lookup_keys,
+        }
+    }
+}
+
+// Signers come first and each group ends with its read-only keys, so the
+// read-only counts are the non-writable runs at the end of each group.
+fn header_from_flags_of(keys: &[(Pubkey, bool, bool)]) -> RpcMessageHeader {
+    let signers = keys.iter().take_while(|(_, signer, _)| *signer).count();
+    let (signed, unsigned) = keys.split_at(signers);
+    let readonly_run = |group: &[(Pubkey, bool, bool)]| group.iter().rev().take_while(|(_, _, writable)| !writable).count();
+    RpcMessageHeader {
+        num_required_signatures: signers as u8,
+        num_readonly_signed_accounts: readonly_run(signed) as u8,
+        num_readonly_unsigned_accounts: readonly_run(unsigned) as u8,
+    }
+}
+
+// Splits the static account keys into signers and non-signers, each with
+// its read-only accounts last.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RpcMessageHeader {
+    #[serde(rename = "numRequiredSignatures")]
+    pub num_required_signatures: u8,
+    #[serde(rename = "numReadonlySignedAccounts")]
+    pub num_readonly_signed_accounts: u8,
+    #[serde(rename = "numReadonlyUnsignedAccounts")]
+    pub num_readonly_unsigned_accounts: u8,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RpcAddressTableLookup {
+    #[serde(rename = "accountKey")]
+    pub account_key: Pubkey,
+    #[serde(rename = "writableIndexes")]
+    pub writable_indexes: Vec<u8>,
+    #[serde(rename = "readonlyIndexes")]
+    pub readonly_indexes: Vec<u8>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(from = "RpcEncodedInstruction")]
+pub struct RpcInstruction {
+    pub program_id_index: usize,
+    pub accounts: Vec<usize>,
+    // Empty for instructions the node parsed.
+    pub data: String,
+    pub parsed: Option<JsonParsedInstruction>,
+    // Program and account keys of a jsonParsed instruction, until they're
+    // mapped to indices (see RpcTransactionWithMeta).
+    pub(crate) keys: Option<(Pubkey, Vec<Pubkey>)>,
+    pub stack_height: Option<u32>,
+}
+
+impl RpcInstruction {
+    pub fn new(program_id_index: usize, accounts: Vec<usize>, data: String) -> Self {
+        RpcInstruction { program_id_index, accounts, data, parsed: None, keys: None, stack_height: None }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RpcEncodedInstruction {
+    Compiled {
+        #[serde(rename = "programIdIndex")]
+        program_id_index: usize,
+        accounts: Vec<usize>,
+        data: String,
+        #[serde(rename = "stackHeight", default)]
+        stack_height: Option<u32>,
+    },
+    // jsonParsed, for programs the node has no parser for.
+    Keyed {
+        #[serde(rename = "programId")]
+        program_id: Pubkey,
+        accounts: Vec<Pubkey>,
+        data: String,
+        #[serde(rename = "stackHeight", default)]
+        stack_height: Option<u32>,
+    },
+    Parsed {
+        #[serde(rename = "programId")]
+        program_id: Pubkey,
+        program: String,
+        parsed: serde_json::Value,
+        #[serde(rename = "stackHeight", default)]
+        stack_height: Option<u32>,
+    },
+}
+
+impl From<RpcEncodedInstruction> for RpcInstruction {
+    fn from(raw: RpcEncodedInstruction) -> Self {
+        match raw {
+            RpcEncodedInstruction::Compiled { program_id_index, accounts, data, stack_height } => RpcInstruction {
+                stack_height,
+                ..RpcInstruction::new(program_id_index, accounts, data)
+            },
+            RpcEncodedInstruction::Keyed { program_id, accounts, data, stack_height } => RpcInstruction {
+                keys: Some((program_id, accounts)),
+                stack_height,
+                ..RpcInstruction::new(0, Vec::new(), data)
+            },
+            RpcEncodedInstruction::Parsed { program_id, program, parsed, stack_height } => RpcInstruction {
+                parsed: Some(JsonParsedInstruction { program, parsed }),
+                keys: Some((program_id, Vec::new())),
+                stack_height,
+                ..RpcInstruction::new(0, Vec::new(), String::new())
+            },
+        }
+    }
+}
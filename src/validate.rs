@@ -0,0 +1,215 @@
+use crate::decoders::DecoderRegistry;
+use crate::pubkey::Pubkey;
+use crate::schema::{block_schema, schema_errors};
+use crate::types::{ParseReport, ParseWarning, ParsedBlock};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+// ==========================================
+// INPUT VALIDATION
+// ==========================================
+// Vets blocks without writing them anywhere (`validate`), for archives
+// about to be backfilled: what couldn't be read or parsed, the parser's
+// warnings, programs no decoder covers, and anomalies: output that breaks
+// the JSON schema, and blocks that don't chain onto the block before them.
+//
+// Failures and anomalies are errors; warnings and unknown programs are
+// only reported.
+
+// Warnings kept verbatim per kind; the rest are only counted.
+const WARNING_SAMPLES: usize = 5;
+// Schema errors kept per block.
+const SCHEMA_ERRORS_PER_BLOCK: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Anomaly {
+    // None for a block whose slot wasn't known.
+    pub slot: Option<u64>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WarningCount {
+    pub kind: String,
+    pub count: usize,
+    pub samples: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnknownProgram {
+    pub program_id: Pubkey,
+    // Top-level and inner.
+    pub instructions: usize,
+    pub transactions: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub blocks: usize,
+    pub transactions: usize,
+    // Inputs that couldn't be read, deserialized or parsed, with the error.
+    pub failures: Vec<String>,
+    // By kind, most frequent first.
+    pub warnings: Vec<WarningCount>,
+    // Most instructions first.
+    pub unknown_programs: Vec<UnknownProgram>,
+    pub anomalies: Vec<Anomaly>,
+}
+
+impl ValidationReport {
+    pub fn errors(&self) -> usize {
+        self.failures.len() + self.anomalies.len()
+    }
+}
+
+pub struct Validator {
+    decoders: Arc<DecoderRegistry>,
+    schema: Value,
+    report: ValidationReport,
+    warnings: BTreeMap<String, WarningCount>,
+    unknown_programs: HashMap<Pubkey, UnknownProgram>,
+    // Slot and blockhash of the last block, to check the next one's parent.
+    last: Option<(u64, String)>,
+}
+
+impl Validator {
+    // Programs count as unknown when `decoders` has nothing for them.
+    pub fn new(decoders: Arc<DecoderRegistry>) -> Self {
+        Validator {
+            decoders,
+            schema: block_schema(),
+            report: ValidationReport::default(),
+            warnings: BTreeMap::new(),
+            unknown_programs: HashMap::new(),
+            last: None,
+        }
+    }
+
+    pub fn record_failure(&mut self, error: String) {
+        self.report.failures.push(error);
+    }
+
+    // `slot` is the block's slot when the input knew it.
+    pub fn record_block(&mut self, slot: Option<u64>, block: &ParsedBlock, report: &ParseReport) {
+        self.report.blocks += 1;
+        self.report.transactions += block.transactions.len();
+
+        for warning in &report.warnings {
+            let kind = warning_kind(warning);
+            let entry = self.warnings.entry(kind.to_string()).or_insert(WarningCount {
+                kind: kind.to_string(),
+                count: 0,
+                samples: Vec::new(),
+            });
+            entry.count += 1;
+            if entry.samples.len() < WARNING_SAMPLES {
+                entry.samples.push(format!("{:?}", warning));
+            }
+        }
+
+        for tx in &block.transactions {
+            let mut programs: Vec<Pubkey> = Vec::new();
+            let inner = tx.inner_instructions.iter().flat_map(|inner| &inner.instructions);
+            for ix in tx.instructions.iter().chain(inner) {
+                if ix.parsed.is_some() || self.decoders.get(&ix.program_id).is_some() {
+                    continue;
+                }
+                let entry = self.unknown_programs.entry(ix.program_id).or_insert(UnknownProgram {
+                    program_id: ix.program_id,
+                    instructions: 0,
+                    transactions: 0,
+                });
+                entry.instructions += 1;
+                if !programs.contains(&ix.program_id) {
+                    programs.push(ix.program_id);
+                    entry.transactions += 1;
+                }
+            }
+        }
+
+        let mut anomaly = |message: String| self.report.anomalies.push(Anomaly { slot, message });
+        match serde_json::to_value(block) {
+            Ok(value) => {
+                let errors = schema_errors(&self.schema, &value);
+                for error in errors.iter().take(SCHEMA_ERRORS_PER_BLOCK) {
+                    anomaly(format!("schema: {}", error));
+                }
+                if errors.len() > SCHEMA_ERRORS_PER_BLOCK {
+                    anomaly(format!("schema: {} more errors", errors.len() - SCHEMA_ERRORS_PER_BLOCK));
+                }
+            }
+            Err(e) => anomaly(format!("schema: failed to serialize the block: {}", e)),
+        }
+        if slot.is_some_and(|slot| block.parent_slot >= slot) {
+            anomaly(format!("parent slot {} is not before the block", block.parent_slot));
+        }
+        if let Some((last_slot, last_blockhash)) = &self.last {
+            if block.parent_slot == *last_slot && block.previous_blockhash != *last_blockhash {
+                anomaly(format!(
+                    "previous blockhash {} doesn't match {} of slot {}",
+                    block.previous_blockhash, last_blockhash, last_slot
+                ));
+            }
+            if block.blockhash == *last_blockhash {
+                anomaly(format!("same blockhash as slot {}", last_slot));
+            }
+        }
+        self.last = Some((slot.unwrap_or(block.slot), block.blockhash.clone()));
+    }
+
+    pub fn finish(mut self) -> ValidationReport {
+        self.report.warnings = self.warnings.into_values().collect();
+        self.report.warnings.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.kind.cmp(&b.kind)));
+        self.report.unknown_programs = self.unknown_programs.into_values().collect();
+        self.report
+            .unknown_programs
+            .sort_by(|a, b| b.instructions.cmp(&a.instructions).then_with(|| a.program_id.cmp(&b.program_id)));
+        self.report
+    }
+}
+
+fn warning_kind(warning: &ParseWarning) -> &'static str {
+    match warning {
+        ParseWarning::AccountIndexOutOfRange { .. } => "AccountIndexOutOfRange",
+        ParseWarning::SkippedTransaction { .. } => "SkippedTransaction",
+        ParseWarning::MissingComputeUnits { .. } => "MissingComputeUnits",
+        ParseWarning::UnresolvedLookupTable { .. } => "UnresolvedLookupTable",
+        ParseWarning::InvalidSignature { .. } => "InvalidSignature",
+        ParseWarning::UnverifiedSignature { .. } => "UnverifiedSignature",
+        ParseWarning::InconsistentAccounts { .. } => "InconsistentAccounts",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::parser::parse_block_with_report;
+
+    #[test]
+    fn reports_unknown_programs_and_broken_chains() {
+        let raw = fixtures::raw_block();
+        let (block, report) = parse_block_with_report(raw.result).unwrap();
+        let mut validator = Validator::new(DecoderRegistry::builtins());
+        validator.record_block(Some(block.slot), &block, &report);
+        let mut next = block.clone();
+        next.slot += 1;
+        next.parent_slot = block.slot;
+        next.blockhash = "next".to_string();
+        next.previous_blockhash = "elsewhere".to_string();
+        validator.record_block(Some(next.slot), &next, &ParseReport::default());
+        validator.record_failure("broken.json: EOF while parsing".to_string());
+
+        let report = validator.finish();
+        assert_eq!((report.blocks, report.transactions), (2, block.transactions.len() * 2));
+        assert_eq!(report.anomalies.len(), 1, "{:?}", report.anomalies);
+        assert_eq!(report.anomalies[0].slot, Some(next.slot));
+        assert_eq!(report.errors(), 2);
+        let builtins = DecoderRegistry::builtins();
+        assert!(!report.unknown_programs.is_empty());
+        assert!(report.unknown_programs.iter().all(|program| builtins.get(&program.program_id).is_none()));
+        assert!(report.unknown_programs.windows(2).all(|pair| pair[0].instructions >= pair[1].instructions));
+    }
+}
@@ -0,0 +1,336 @@
+use crate::pubkey::Pubkey;
+use crate::tokens::TokenRegistry;
+use crate::transfers::{extract_token_transfers, TokenTransfer};
+use crate::types::{ParsedBlock, ParsedTransaction};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// ==========================================
+// WALLET ACTIVITY
+// ==========================================
+// Everything a set of blocks shows about one address. A transaction involves
+// the address when it's one of the account keys or owns one of the token
+// accounts in the token balances, so tokens received into an associated
+// account count even when the wallet itself isn't listed. Blocks are fed in
+// one at a time and may come in any order.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletToken {
+    pub mint: Pubkey,
+    // Only set by `enrich`; left out of the output otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    pub decimals: u8,
+    // Net change over the owner's token accounts, in base units.
+    pub delta: i128,
+    pub transfers_in: usize,
+    pub transfers_out: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletProgram {
+    pub program_id: Pubkey,
+    // Involving transactions calling the program, directly or via CPI.
+    pub transactions: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletTokenTransfer {
+    pub slot: u64,
+    pub signature: String,
+    #[serde(flatten)]
+    pub transfer: TokenTransfer,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletReport {
+    pub address: Pubkey,
+    pub blocks_scanned: usize,
+    pub transactions: usize,
+    pub failed_transactions: usize,
+    pub first_seen_slot: Option<u64>,
+    pub last_seen_slot: Option<u64>,
+    // Net lamports across the address's own balance, fees included.
+    pub sol_delta: i128,
+    // Transactions the address paid for, and what they cost it.
+    pub fee_payer_transactions: usize,
+    pub fees_paid: u64,
+    // By mint.
+    pub tokens: Vec<WalletToken>,
+    // Most used first; ties by program ID.
+    pub programs: Vec<WalletProgram>,
+    // In slot order, then block order.
+    pub token_transfers: Vec<WalletTokenTransfer>,
+}
+
+impl WalletReport {
+    pub fn new(address: Pubkey) -> Self {
+        WalletReport {
+            address,
+            blocks_scanned: 0,
+            transactions: 0,
+            failed_transactions: 0,
+            first_seen_slot: None,
+            last_seen_slot: None,
+            sol_delta: 0,
+            fee_payer_transactions: 0,
+            fees_paid: 0,
+            tokens: Vec::new(),
+            programs: Vec::new(),
+            token_transfers: Vec::new(),
+        }
+    }
+
+    pub fn involves(&self, tx: &ParsedTransaction) -> bool {
+        tx.account_keys.contains(&self.address)
+            || tx.pre_token_balances.iter().chain(&tx.post_token_balances).any(|balance| balance.owner == Some(self.address))
+    }
+
+    pub fn record_block(&mut self, block: &ParsedBlock) {
+        self.blocks_scanned += 1;
+        for tx in &block.transactions {
+            self.record_transaction(block.slot, tx);
+        }
+    }
+
+    // Ignores transactions not involving the address.
+    pub fn record_transaction(&mut self, slot: u64, tx: &ParsedTransaction) {
+        if !self.involves(tx) {
+            return;
+        }
+        self.transactions += 1;
+        self.failed_transactions += usize::from(!tx.is_success);
+        self.first_seen_slot = Some(self.first_seen_slot.map_or(slot, |first| first.min(slot)));
+        self.last_seen_slot = Some(self.last_seen_slot.map_or(slot, |last| last.max(slot)));
+        if tx.fee_payer == self.address {
+            self.fee_payer_transactions += 1;
+            self.fees_paid += tx.fee;
+        }
+        self.sol_delta += tx.balance_changes().iter().filter(|change| change.address == self.address).map(|change| change.delta).sum::<i128>();
+
+        let address = self.address;
+        for change in tx.token_balance_changes.iter().filter(|change| change.owner == address) {
+            self.token(change.mint, change.decimals).delta += change.delta;
+        }
+        for transfer in extract_token_transfers(tx) {
+            let sent = transfer.source_owner == Some(self.address) || transfer.source == self.address;
+            let received = transfer.destination_owner == Some(self.address) || transfer.destination == self.address;
+            if !sent && !received {
+                continue;
+            }
+            if let Some(mint) = transfer.mint {
+                let token = self.token(mint, transfer.decimals.unwrap_or(0));
+                token.transfers_out += usize::from(sent);
+                token.transfers_in += usize::from(received);
+            }
+            self.token_transfers.push(WalletTokenTransfer { slot, signature: tx.signature.clone(), transfer });
+        }
+
+        let programs: BTreeSet<Pubkey> = tx
+            .instructions
+            .iter()
+            .chain(tx.inner_instructions.iter().flat_map(|inner| &inner.instructions))
+            .map(|ix| ix.program_id)
+            .collect();
+        for program_id in programs {
+            match self.programs.iter_mut().find(|program| program.program_id == program_id) {
+                Some(program) => program.transactions += 1,
+                None => self.programs.push(WalletProgram { program_id, transactions: 1 }),
+            }
+        }
+    }
+
+    // Puts the lists in their documented order; call once every block is in.
+    pub fn sort(&mut self) {
+        self.tokens.sort_by_key(|token| token.mint);
+        self.programs.sort_by(|a, b| b.transactions.cmp(&a.transactions).then(a.program_id.cmp(&b.program_id)));
+        // Stable, so transfers within a block keep their order.
+        self.token_transfers.sort_by_key(|transfer| transfer.slot);
+    }
+
+    // Symbols for the tokens, and the decimals and symbols of transfers
+    // that lacked them.
+    pub fn enrich(&mut self, tokens: &TokenRegistry) {
+        for token in &mut self.tokens {
+            token.symbol = tokens.symbol(&token.mint).map(str::to_string);
+        }
+        for entry in &mut self.token_transfers {
+            tokens.enrich(&mut entry.transfer);
+        }
+    }
+
+    fn token(&mut self, mint: Pubkey, decimals: u8) -> &mut WalletToken {
+        let position = match self.tokens.iter().position(|token| token.mint == mint) {
+            Some(position) => position,
+            None => {
+                self.tokens.push(WalletToken { mint, symbol: None, decimals, delta: 0, transfers_in: 0, transfers_out: 0 });
+                self.tokens.len() - 1
+            }
+        };
+        &mut self.tokens[position]
+    }
+}
+
+// ==========================================
+// ACCOUNT EVENTS
+// ==========================================
+// A transaction exploded into one event per address it touches, for
+// indexers keyed by address: every account key once, with its role and
+// lamport delta, and every token owner that isn't an account key itself
+// (tokens received into an associated account). Token deltas go to the
+// owner, or to the token account when the balance names no owner, as in
+// `token_balance_changes`. Events come in account key order, the token
+// owners after them by address.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountRole {
+    FeePayer,
+    Signer,
+    Writable,
+    Readonly,
+    // Not an account key; only owns token accounts that are.
+    TokenOwner,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountTokenDelta {
+    pub mint: Pubkey,
+    pub decimals: u8,
+    pub pre_amount: u64,
+    pub post_amount: u64,
+    pub delta: i128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountEvent {
+    pub account: Pubkey,
+    // The block's slot, time and hash; None for standalone transactions.
+    pub slot: Option<u64>,
+    pub block_time: Option<i64>,
+    pub blockhash: Option<String>,
+    pub signature: String,
+    // Position in the block's transactions.
+    pub transaction_index: Option<usize>,
+    pub is_success: bool,
+    pub role: AccountRole,
+    pub is_writable: bool,
+    pub pre_lamports: Option<u64>,
+    pub post_lamports: Option<u64>,
+    // Zero when the balances weren't reported.
+    pub lamport_delta: i128,
+    // By mint; only mints whose balance moved.
+    pub token_deltas: Vec<AccountTokenDelta>,
+}
+
+pub fn block_account_events(block: &ParsedBlock) -> Vec<AccountEvent> {
+    block.transactions.iter().enumerate().flat_map(|(index, tx)| account_events(Some((block, index)), tx)).collect()
+}
+
+// `block` is the transaction's block and its position there, if any.
+pub fn account_events(block: Option<(&ParsedBlock, usize)>, tx: &ParsedTransaction) -> Vec<AccountEvent> {
+    let mut token_deltas: BTreeMap<Pubkey, Vec<AccountTokenDelta>> = BTreeMap::new();
+    for change in &tx.token_balance_changes {
+        token_deltas.entry(change.owner).or_default().push(AccountTokenDelta {
+            mint: change.mint,
+            decimals: change.decimals,
+            pre_amount: change.pre_amount,
+            post_amount: change.post_amount,
+            delta: change.delta,
+        });
+    }
+    let event = |account: Pubkey, role: AccountRole, is_writable: bool, lamports: Option<(u64, u64)>, token_deltas: Vec<AccountTokenDelta>| AccountEvent {
+        account,
+        slot: block.map(|(block, _)| block.slot),
+        block_time: block.and_then(|(block, _)| block.block_time),
+        blockhash: block.map(|(block, _)| block.blockhash.clone()),
+        signature: tx.signature.clone(),
+        transaction_index: block.map(|(_, index)| index),
+        is_success: tx.is_success,
+        role,
+        is_writable,
+        pre_lamports: lamports.map(|(pre, _)| pre),
+        post_lamports: lamports.map(|(_, post)| post),
+        lamport_delta: lamports.map_or(0, |(pre, post)| post as i128 - pre as i128),
+        token_deltas,
+    };
+
+    let mut seen = BTreeSet::new();
+    let mut events = Vec::new();
+    for (position, account) in tx.accounts.iter().enumerate() {
+        if !seen.insert(account.pubkey) {
+            continue;
+        }
+        let role = if account.pubkey == tx.fee_payer {
+            AccountRole::FeePayer
+        } else if account.is_signer {
+            AccountRole::Signer
+        } else if account.is_writable {
+            AccountRole::Writable
+        } else {
+            AccountRole::Readonly
+        };
+        let lamports = tx.pre_balances.get(position).zip(tx.post_balances.get(position)).map(|(&pre, &post)| (pre, post));
+        let deltas = token_deltas.remove(&account.pubkey).unwrap_or_default();
+        events.push(event(account.pubkey, role, account.is_writable, lamports, deltas));
+    }
+    for (owner, deltas) in token_deltas {
+        events.push(event(owner, AccountRole::TokenOwner, false, None, deltas));
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    const TRADER: Pubkey = Pubkey::from_str_const("8qjB4NibFqqRyECMUMWw7X4are73dms6pfM9gBEJGNAP");
+
+    #[test]
+    fn aggregates_a_wallets_activity_across_blocks() {
+        let block = fixtures::block();
+        let mut later = block.clone();
+        later.slot += 10;
+
+        let mut report = WalletReport::new(TRADER);
+        report.record_block(&later);
+        report.record_block(block);
+        report.sort();
+
+        let involving: Vec<&ParsedTransaction> = block.transactions.iter().filter(|tx| report.involves(tx)).collect();
+        assert!(!involving.is_empty());
+        assert_eq!(report.blocks_scanned, 2);
+        assert_eq!(report.transactions, involving.len() * 2);
+        assert_eq!((report.first_seen_slot, report.last_seen_slot), (Some(block.slot), Some(block.slot + 10)));
+        let paid: u64 = involving.iter().filter(|tx| tx.fee_payer == TRADER).map(|tx| tx.fee).sum();
+        assert!(paid > 0);
+        assert_eq!(report.fees_paid, paid * 2);
+        assert!(report.sol_delta < 0);
+        assert!(!report.tokens.is_empty());
+        assert!(report.token_transfers.windows(2).all(|pair| pair[0].slot <= pair[1].slot));
+        assert!(report.token_transfers.iter().all(|t| t.transfer.source_owner == Some(TRADER) || t.transfer.destination_owner == Some(TRADER)));
+        assert!(report.programs.windows(2).all(|pair| pair[0].transactions >= pair[1].transactions));
+    }
+
+    #[test]
+    fn explodes_transactions_into_account_events() {
+        let block = fixtures::block();
+        let events = block_account_events(block);
+        let (index, tx) = block.transactions.iter().enumerate().find(|(_, tx)| tx.fee_payer == TRADER && !tx.token_balance_changes.is_empty()).unwrap();
+        let tx_events: Vec<&AccountEvent> = events.iter().filter(|event| event.transaction_index == Some(index)).collect();
+
+        let payer = tx_events[0];
+        assert_eq!((payer.account, payer.role, payer.slot), (TRADER, AccountRole::FeePayer, Some(block.slot)));
+        assert_eq!(payer.lamport_delta, tx.balance_changes().iter().find(|change| change.address == TRADER).map_or(0, |change| change.delta));
+        // Every change lands on exactly one event.
+        let token_deltas: usize = tx_events.iter().map(|event| event.token_deltas.len()).sum();
+        assert_eq!(token_deltas, tx.token_balance_changes.len());
+        let keys: BTreeSet<Pubkey> = tx.account_keys.iter().copied().collect();
+        assert_eq!(tx_events.iter().filter(|event| event.role != AccountRole::TokenOwner).count(), keys.len());
+        for event in tx_events.iter().filter(|event| event.role == AccountRole::TokenOwner) {
+            assert!(!keys.contains(&event.account) && event.pre_lamports.is_none() && !event.token_deltas.is_empty());
+        }
+        assert_eq!(events.len(), block.transactions.iter().map(|tx| account_events(None, tx).len()).sum::<usize>());
+    }
+}
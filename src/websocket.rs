@@ -0,0 +1,392 @@
+use std::collections::hash_map::RandomState;
+use std::error::Error;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+// ==========================================
+// MINIMAL WEBSOCKET CLIENT
+// ==========================================
+// Just enough of RFC 6455 for JSON-RPC pubsub: plain ws:// connections,
+// masked text frames out, fragmented text/binary frames in, ping/pong and
+// close. There is no TLS stack in the crate, so wss:// endpoints need a local
+// proxy (e.g. stunnel or a validator's own ws:// port). The handshake checks
+// the server's Sec-WebSocket-Accept, which is why there's a SHA-1 here.
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+// Appended to the key for Sec-WebSocket-Accept.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// Full blocks can be several megabytes; anything past this is a broken peer.
+const MAX_MESSAGE_LEN: u64 = 256 * 1024 * 1024;
+
+// The Sec-WebSocket-Accept a server must answer `key` with.
+fn accept_key(key: &str) -> String {
+    BASE64.encode(sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes()))
+}
+
+// SHA-1 (RFC 3174), only for the handshake.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..20 => ((b & c) | (!b & d), 0x5A827999),
+                20..40 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..60 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (chunk, value) in digest.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+pub struct WebSocket {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    rng: u64,
+}
+
+impl WebSocket {
+    pub fn connect(url: &str) -> Result<Self, Box<dyn Error>> {
+        let rest = match url.split_once("://") {
+            Some(("ws", rest)) => rest,
+            Some(("wss", _)) => return Err("wss:// is not supported; connect through a ws:// proxy".into()),
+            _ => return Err(format!("invalid websocket url '{}'", url).into()),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(pos) => (&rest[..pos], &rest[pos..]),
+            None => (rest, "/"),
+        };
+        let address = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+
+        let writer = TcpStream::connect(&address)?;
+        writer.set_nodelay(true)?;
+        let mut socket = WebSocket {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+            rng: RandomState::new().build_hasher().finish() | 1,
+        };
+
+        let mut key = [0u8; 16];
+        socket.fill_random(&mut key);
+        let key = BASE64.encode(key);
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            path, authority, key,
+        );
+        socket.writer.write_all(request.as_bytes())?;
+
+        let mut status = String::new();
+        socket.reader.read_line(&mut status)?;
+        if status.split_whitespace().nth(1) != Some("101") {
+            return Err(format!("websocket handshake with {} failed: {}", url, status.trim()).into());
+        }
+        let mut accept = None;
+        loop {
+            let mut line = String::new();
+            if socket.reader.read_line(&mut line)? == 0 || line == "\r\n" {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':')
+                && name.trim().eq_ignore_ascii_case("sec-websocket-accept")
+            {
+                accept = Some(value.trim().to_string());
+            }
+        }
+        if accept.as_deref() != Some(accept_key(&key).as_str()) {
+            return Err(format!("websocket handshake with {} failed: missing or wrong Sec-WebSocket-Accept", url).into());
+        }
+        Ok(socket)
+    }
+
+    pub fn send_text(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        self.send_frame(OP_TEXT, text.as_bytes())
+    }
+
+    // Next text or binary message, or None once the server closes.
+    pub fn read_message(&mut self) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        let mut message = Vec::new();
+        loop {
+            let (fin, opcode, payload) = self.read_frame()?;
+            match opcode {
+                OP_TEXT | OP_BINARY | OP_CONTINUATION => {
+                    if message.len() as u64 + payload.len() as u64 > MAX_MESSAGE_LEN {
+                        return Err("websocket message too large".into());
+                    }
+                    message.extend_from_slice(&payload);
+                    if fin {
+                        return Ok(Some(message));
+                    }
+                }
+                OP_PING => self.send_frame(OP_PONG, &payload)?,
+                OP_PONG => {}
+                OP_CLOSE => {
+                    let _ = self.send_frame(OP_CLOSE, &payload);
+                    return Ok(None);
+                }
+                other => return Err(format!("unexpected websocket opcode {:#x}", other).into()),
+            }
+        }
+    }
+
+    fn read_frame(&mut self) -> Result<(bool, u8, Vec<u8>), Box<dyn Error>> {
+        let mut header = [0u8; 2];
+        self.reader.read_exact(&mut header)?;
+        let fin = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0F;
+        let masked = header[1] & 0x80 != 0;
+
+        let len = match header[1] & 0x7F {
+            126 => {
+                let mut buf = [0u8; 2];
+                self.reader.read_exact(&mut buf)?;
+                u16::from_be_bytes(buf) as u64
+            }
+            127 => {
+                let mut buf = [0u8; 8];
+                self.reader.read_exact(&mut buf)?;
+                u64::from_be_bytes(buf)
+            }
+            len => len as u64,
+        };
+        if len > MAX_MESSAGE_LEN {
+            return Err("websocket frame too large".into());
+        }
+
+        let mut mask = [0u8; 4];
+        if masked {
+            self.reader.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0u8; len as usize];
+        self.reader.read_exact(&mut payload)?;
+        if masked {
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[i % 4];
+            }
+        }
+        Ok((fin, opcode, payload))
+    }
+
+    // Client frames are always masked.
+    fn send_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<(), Box<dyn Error>> {
+        let mut frame = Vec::with_capacity(payload.len() + 14);
+        frame.push(0x80 | opcode);
+        match payload.len() {
+            len if len < 126 => frame.push(0x80 | len as u8),
+            len if len <= u16::MAX as usize => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(0x80 | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+
+        let mut mask = [0u8; 4];
+        self.fill_random(&mut mask);
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+
+        self.writer.write_all(&frame)?;
+        Ok(())
+    }
+
+    // xorshift64; masking keys only need to be unpredictable to proxies.
+    fn fill_random(&mut self, buf: &mut [u8]) {
+        for byte in buf {
+            self.rng ^= self.rng << 13;
+            self.rng ^= self.rng >> 7;
+            self.rng ^= self.rng << 17;
+            *byte = self.rng as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    // A server frame; `long` forces the 8-byte length form.
+    fn frame(fin: bool, opcode: u8, payload: &[u8], mask: Option<[u8; 4]>, long: bool) -> Vec<u8> {
+        let mut frame = vec![if fin { 0x80 } else { 0 } | opcode];
+        let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+        match payload.len() {
+            len if len < 126 && !long => frame.push(mask_bit | len as u8),
+            len if len <= u16::MAX as usize && !long => {
+                frame.push(mask_bit | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(mask_bit | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        match mask {
+            Some(mask) => {
+                frame.extend_from_slice(&mask);
+                frame.extend(payload.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+            }
+            None => frame.extend_from_slice(payload),
+        }
+        frame
+    }
+
+    // (first header byte, length field, unmasked payload); client frames
+    // must be masked.
+    fn read_client_frame(reader: &mut impl Read) -> (u8, u8, Vec<u8>) {
+        let mut header = [0u8; 2];
+        reader.read_exact(&mut header).unwrap();
+        assert!(header[1] & 0x80 != 0, "client frame not masked");
+        let len = match header[1] & 0x7F {
+            126 => {
+                let mut buf = [0u8; 2];
+                reader.read_exact(&mut buf).unwrap();
+                u16::from_be_bytes(buf) as usize
+            }
+            127 => {
+                let mut buf = [0u8; 8];
+                reader.read_exact(&mut buf).unwrap();
+                u64::from_be_bytes(buf) as usize
+            }
+            len => len as usize,
+        };
+        let mut mask = [0u8; 4];
+        reader.read_exact(&mut mask).unwrap();
+        let mut payload = vec![0u8; len];
+        reader.read_exact(&mut payload).unwrap();
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        (header[0], header[1] & 0x7F, payload)
+    }
+
+    #[test]
+    fn exchanges_frames_with_a_loopback_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let medium = vec![b'm'; 300];
+        let large = vec![b'l'; 70_000];
+        let (sent_medium, sent_large) = (medium.clone(), large.clone());
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut request = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                request.push(line);
+            }
+            assert_eq!(request[0], "GET /rpc HTTP/1.1\r\n");
+            assert!(request.contains(&"Upgrade: websocket\r\n".to_string()));
+            let key = request.iter().find_map(|line| line.strip_prefix("Sec-WebSocket-Key: ")).unwrap().trim();
+            assert_eq!(BASE64.decode(key).unwrap().len(), 16);
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(key)
+            );
+            writer.write_all(response.as_bytes()).unwrap();
+
+            // One frame per message, with the shortest length form.
+            assert_eq!(read_client_frame(&mut reader), (0x80 | OP_TEXT, 5, b"hello".to_vec()));
+            assert_eq!(read_client_frame(&mut reader), (0x80 | OP_TEXT, 126, sent_medium.clone()));
+            assert_eq!(read_client_frame(&mut reader), (0x80 | OP_TEXT, 127, sent_large.clone()));
+
+            let mut frames = frame(true, OP_TEXT, b"short", None, false);
+            // A fragmented message with a ping between fragments, masked and
+            // in every length form.
+            frames.extend(frame(false, OP_TEXT, b"first,", None, false));
+            frames.extend(frame(true, OP_PING, b"are you there", None, false));
+            frames.extend(frame(false, OP_CONTINUATION, &sent_medium, Some([1, 2, 3, 4]), false));
+            frames.extend(frame(true, OP_CONTINUATION, &sent_large, None, true));
+            frames.extend(frame(true, OP_BINARY, &[0, 159, 255], Some([9, 9, 9, 9]), true));
+            frames.extend(frame(true, OP_CLOSE, &1000u16.to_be_bytes(), None, false));
+            writer.write_all(&frames).unwrap();
+
+            // The ping is answered before the message completes, and the
+            // close is echoed.
+            assert_eq!(read_client_frame(&mut reader), (0x80 | OP_PONG, 13, b"are you there".to_vec()));
+            assert_eq!(read_client_frame(&mut reader), (0x80 | OP_CLOSE, 2, 1000u16.to_be_bytes().to_vec()));
+        });
+
+        let mut socket = WebSocket::connect(&format!("ws://127.0.0.1:{}/rpc", port)).unwrap();
+        socket.send_text("hello").unwrap();
+        socket.send_text(std::str::from_utf8(&medium).unwrap()).unwrap();
+        socket.send_text(std::str::from_utf8(&large).unwrap()).unwrap();
+
+        assert_eq!(socket.read_message().unwrap(), Some(b"short".to_vec()));
+        let mut expected = b"first,".to_vec();
+        expected.extend(&medium);
+        expected.extend(&large);
+        assert_eq!(socket.read_message().unwrap(), Some(expected));
+        assert_eq!(socket.read_message().unwrap(), Some(vec![0, 159, 255]));
+        assert_eq!(socket.read_message().unwrap(), None);
+        server.join().unwrap();
+
+        assert!(WebSocket::connect("wss://example.com").is_err());
+        assert!(WebSocket::connect("http://example.com").is_err());
+
+        // RFC 6455's own example, and a server answering with the wrong key.
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+            stream.write_all(b"HTTP/1.1 101 Switching Protocols\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n").unwrap();
+        });
+        let error = WebSocket::connect(&format!("ws://127.0.0.1:{}/", port)).err().unwrap();
+        assert!(error.to_string().contains("Sec-WebSocket-Accept"));
+        server.join().unwrap();
+    }
+}
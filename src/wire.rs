@@ -0,0 +1,198 @@
+use crate::encoding::DataEncoding;
+use crate::pubkey::Pubkey;
+use crate::types::{RpcAddressTableLookup, RpcInstruction, RpcMessage, RpcMessageHeader, RpcTransactionContainer};
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+
+// ==========================================
+// WIRE FORMAT
+// ==========================================
+// Transactions as they are signed and sent: a short-vec of signatures
+// followed by the serialized message. `encoding: base64` responses carry
+// these bytes, which are decoded into the same RpcTransactionContainer as
+// the JSON form. The JSON form carries every field of the message too, so
+// the signed bytes can be rebuilt from either and the signatures checked.
+
+const VERSION_PREFIX: u8 = 0x80;
+
+pub fn decode_transaction(bytes: &[u8]) -> Result<RpcTransactionContainer, String> {
+    let tx: VersionedTransaction = bincode::deserialize(bytes).map_err(|e| format!("invalid transaction bytes: {}", e))?;
+    let message = &tx.message;
+    let header = message.header();
+    Ok(RpcTransactionContainer {
+        signatures: tx.signatures.iter().map(|signature| signature.to_string()).collect(),
+        message: RpcMessage {
+            header: RpcMessageHeader {
+                num_required_signatures: header.num_required_signatures,
+                num_readonly_signed_accounts: header.num_readonly_signed_accounts,
+                num_readonly_unsigned_accounts: header.num_readonly_unsigned_accounts,
+            },
+            account_keys: message.static_account_keys().iter().map(|key| Pubkey::new(key.to_bytes())).collect(),
+            recent_blockhash: Some(message.recent_blockhash().to_string()),
+            instructions: message
+                .instructions()
+                .iter()
+                .map(|ix| RpcInstruction {
+                    // Not in the wire format, but top-level is always 1.
+                    stack_height: Some(1),
+                    ..RpcInstruction::new(
+                        ix.program_id_index as usize,
+                        ix.accounts.iter().map(|&i| i as usize).collect(),
+                        bs58::encode(&ix.data).into_string(),
+                    )
+                })
+                .collect(),
+            address_table_lookups: match message {
+                VersionedMessage::Legacy(_) => None,
+                VersionedMessage::V0(_) => Some(
+                    message
+                        .address_table_lookups()
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|lookup| RpcAddressTableLookup {
+                            account_key: Pubkey::new(lookup.account_key.to_bytes()),
+                            writable_indexes: lookup.writable_indexes.clone(),
+                            readonly_indexes: lookup.readonly_indexes.clone(),
+                        })
+                        .collect(),
+                ),
+            },
+            header_from_flags: false,
+            lookup_keys: None,
+        },
+    })
+}
+
+// The bytes the signatures cover. None when the message can't be rebuilt:
+// jsonParsed or no recent blockhash in the input, or values that don't fit
+// the format.
+// `data_encoding` is how the instruction data strings are encoded.
+pub fn message_bytes(message: &RpcMessage, data_encoding: DataEncoding) -> Option<Vec<u8>> {
+    // jsonParsed input leaves out the header and the data of the
+    // instructions the node parsed.
+    if message.header_from_flags || message.instructions.iter().any(|ix| ix.parsed.is_some()) {
+        return None;
+    }
+    let mut out = Vec::new();
+    if message.address_table_lookups.is_some() {
+        out.push(VERSION_PREFIX);
+    }
+    let header = &message.header;
+    out.extend([header.num_required_signatures, header.num_readonly_signed_accounts, header.num_readonly_unsigned_accounts]);
+    write_len(&mut out, message.account_keys.len());
+    for key in &message.account_keys {
+        out.extend(key.as_bytes());
+    }
+    out.extend(message.recent_blockhash.as_deref()?.parse::<Pubkey>().ok()?.as_bytes());
+    write_len(&mut out, message.instructions.len());
+    for ix in &message.instructions {
+        out.push(u8::try_from(ix.program_id_index).ok()?);
+        write_len(&mut out, ix.accounts.len());
+        for &index in &ix.accounts {
+            out.push(u8::try_from(index).ok()?);
+        }
+        let data = data_encoding.decode(&ix.data)?;
+        write_len(&mut out, data.len());
+        out.extend(data);
+    }
+    if let Some(lookups) = &message.address_table_lookups {
+        write_len(&mut out, lookups.len());
+        for lookup in lookups {
+            out.extend(lookup.account_key.as_bytes());
+            write_len(&mut out, lookup.writable_indexes.len());
+            out.extend(&lookup.writable_indexes);
+            write_len(&mut out, lookup.readonly_indexes.len());
+            out.extend(&lookup.readonly_indexes);
+        }
+    }
+    Some(out)
+}
+
+// Compact-u16: 7 bits per byte, low bits first.
+fn write_len(out: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let byte = (len & 0x7f) as u8;
+        len >>= 7;
+        if len == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+// Checks each required signature against the signer at the same position.
+// None when the message bytes can't be rebuilt; Some(false) also covers
+// missing or malformed signatures.
+pub fn verify_signatures(tx: &RpcTransactionContainer, data_encoding: DataEncoding) -> Option<bool> {
+    let message = message_bytes(&tx.message, data_encoding)?;
+    let required = tx.message.header.num_required_signatures as usize;
+    if tx.signatures.len() != required || tx.message.account_keys.len() < required {
+        return Some(false);
+    }
+    Some(tx.signatures.iter().zip(&tx.message.account_keys).all(|(signature, key)| {
+        signature.parse::<Signature>().is_ok_and(|signature| signature.verify(key.as_bytes(), &message))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::parser::parse_block;
+    use crate::types::RpcBlockResponse;
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+
+    // Signatures first, then the message, as sent to the cluster.
+    fn wire_bytes(tx: &RpcTransactionContainer) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_len(&mut out, tx.signatures.len());
+        for signature in &tx.signatures {
+            out.extend(bs58::decode(signature).into_vec().unwrap());
+        }
+        out.extend(message_bytes(&tx.message, DataEncoding::Base58).unwrap());
+        out
+    }
+
+    #[test]
+    fn verifies_block_signatures_and_round_trips_base64() {
+        let raw = fixtures::raw_block();
+        let transactions = &raw.result.transactions;
+        assert!(transactions.iter().all(|tx| verify_signatures(&tx.transaction, DataEncoding::Base58) == Some(true)));
+
+        let versioned = transactions.iter().find(|tx| tx.transaction.message.address_table_lookups.is_some()).unwrap();
+        let encoded = serde_json::json!([BASE64.encode(wire_bytes(&versioned.transaction)), "base64"]);
+        let decoded: RpcTransactionContainer = serde_json::from_value(encoded).unwrap();
+        assert_eq!(decoded.signatures, versioned.transaction.signatures);
+        assert_eq!(decoded.message.account_keys, versioned.transaction.message.account_keys);
+        assert_eq!(verify_signatures(&decoded, DataEncoding::Base58), Some(true));
+        let base58 = serde_json::from_value::<RpcTransactionContainer>(serde_json::json!(["AQID", "base58"])).unwrap_err();
+        assert!(base58.to_string().contains("unsupported transaction encoding 'base58'"));
+
+        let mut tampered = decoded.clone();
+        tampered.message.instructions[0].data = bs58::encode([1, 2, 3]).into_string();
+        assert_eq!(verify_signatures(&tampered, DataEncoding::Base58), Some(false));
+        tampered.message.recent_blockhash = None;
+        assert_eq!(verify_signatures(&tampered, DataEncoding::Base58), None);
+    }
+
+    #[test]
+    fn base64_block_parses_like_json() {
+        let json = fixtures::raw_block();
+        let transactions = &json.result.transactions;
+        assert!(transactions.iter().any(|tx| tx.transaction.message.address_table_lookups.is_none()));
+        assert!(transactions.iter().any(|tx| tx.transaction.message.address_table_lookups.is_some()));
+
+        // The same block as getBlock returns it with `encoding: base64`.
+        let mut raw: serde_json::Value = serde_json::from_str(&std::fs::read_to_string("src/json/block.json").unwrap()).unwrap();
+        for (tx, raw) in transactions.iter().zip(raw["result"]["transactions"].as_array_mut().unwrap()) {
+            raw["transaction"] = serde_json::json!([BASE64.encode(wire_bytes(&tx.transaction)), "base64"]);
+        }
+        let binary: RpcBlockResponse = serde_json::from_value(raw).unwrap();
+
+        let expected = serde_json::to_value(parse_block(json.result).unwrap()).unwrap();
+        assert_eq!(serde_json::to_value(parse_block(binary.result).unwrap()).unwrap(), expected);
+    }
+}
@@ -0,0 +1,53 @@
+use js_sys::JSON;
+use phase_1_connect::error::ParseError;
+use phase_1_connect::parser::parse_transaction;
+use phase_1_connect::types::RpcResult;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use wasm_bindgen::prelude::*;
+
+// ==========================================
+// WEBASSEMBLY BINDINGS
+// ==========================================
+// The transaction parser for the browser, built on the crate's core alone
+// (no "native" feature: no files, network or child processes). Input is the
+// JSON text of a getTransaction response, with or without the JSON-RPC
+// envelope, as a block explorer already has it; output is the object the
+// CLI writes with --output json.
+
+/// Parses a getTransaction response. Throws an Error on invalid input.
+#[wasm_bindgen(js_name = parseTransaction)]
+pub fn parse_transaction_json(json: &str) -> Result<JsValue, JsError> {
+    let output = parse(json).map_err(|e| JsError::new(&e.to_string()))?;
+    JSON::parse(&output).map_err(|_| JsError::new("parsed transaction isn't valid JSON"))
+}
+
+// The response envelope: only "result" is read, and left as raw text.
+#[derive(Deserialize)]
+struct Envelope<'a> {
+    #[serde(borrow)]
+    result: Option<&'a RawValue>,
+}
+
+fn parse(json: &str) -> Result<String, ParseError> {
+    // The response's "result", or the whole document when it has no envelope.
+    let envelope: Envelope = serde_json::from_str(json)?;
+    let raw: RpcResult = serde_json::from_str(envelope.result.map_or(json, RawValue::get))?;
+    let tx = parse_transaction(&raw.transaction, &raw.meta)?;
+    Ok(serde_json::to_string(&tx)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_transaction_with_or_without_the_envelope() {
+        let json = include_str!("../../src/json/transaction.json");
+        let bare = serde_json::from_str::<serde_json::Value>(json).unwrap()["result"].to_string();
+        let output = parse(json).unwrap();
+        assert_eq!(output, parse(&bare).unwrap());
+        assert!(output.contains("\"signature\""));
+        assert!(parse("{\"result\": {}}").is_err());
+    }
+}